@@ -0,0 +1,104 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+
+use ronald::memory::Write;
+use ronald::system::System;
+use ronald::{bus, cpu, crtc, gate_array, instruction, keyboard, memory, screen, system};
+
+const FRAME_MICROSECONDS: u64 = 20_000;
+
+fn fetch_and_execute(c: &mut Criterion) {
+    // adds C to a block of memory in a loop
+    let program = [
+        0x21, 0x00, 0x40, // ld hl, 0x4000
+        0x01, 0x00, 0x01, // ld bc, 0x0100
+        0x7e, // ld a, (hl)
+        0x81, // add a, c
+        0x77, // ld (hl), a
+        0x23, // inc hl
+        0x0b, // dec bc
+        0x78, // ld a, b
+        0xb1, // or c
+        0x20, 0xf7, // jr nz, -9
+        0xc3, 0x00, 0x00, // jp 0x0000
+    ];
+
+    let mut ram = memory::RAM::new(0x10000);
+    for (address, byte) in program.iter().enumerate() {
+        ram.write_byte(address, *byte);
+    }
+
+    let memory = Rc::new(RefCell::new(ram));
+    let bus = Rc::new(RefCell::new(bus::DummyBus::new()));
+    let cpu = cpu::CPU::new_shared(memory, bus, 0);
+
+    c.bench_function("fetch_and_execute x1000", |b| {
+        b.iter(|| {
+            for _ in 0..1000 {
+                cpu.borrow_mut().fetch_and_execute();
+            }
+        })
+    });
+}
+
+fn decoder(c: &mut Criterion) {
+    let memory = Rc::new(RefCell::new(memory::RAM::from_file(0x10000, "rom/os_464.rom", 0)));
+    let mut decoder = instruction::Decoder::new(memory);
+
+    c.bench_function("decode lower ROM 0x0000-0x0fff", |b| {
+        b.iter(|| {
+            let mut address = 0;
+            while address < 0x1000 {
+                let (_, next_address) = decoder.decode_at(address);
+                address = next_address;
+            }
+        })
+    });
+}
+
+fn gate_array_frame(c: &mut Criterion) {
+    let memory = memory::Memory::new_shared(memory::RamPattern::Stripes, 0, keyboard::Language::English, memory::Firmware::Amstrad, &memory::RomPaths::default(), memory::RamSize::Kilobytes64);
+    let crtc = crtc::CRTController::new_shared(crtc::CrtcType::Type0);
+    let screen = screen::Screen::new_shared();
+    let gate_array = gate_array::GateArray::new_shared(memory, crtc.clone(), screen);
+
+    // default CRTC setup of the firmware
+    let registers = [63, 40, 46, 0x8e, 38, 0, 25, 30, 0, 7, 0, 0, 0x30, 0];
+    for (register, value) in registers.iter().enumerate() {
+        crtc.borrow_mut().write_byte(0xbc00, register as u8);
+        crtc.borrow_mut().write_byte(0xbd00, *value);
+    }
+
+    c.bench_function("gate array frame", |b| {
+        b.iter(|| {
+            for _ in 0..FRAME_MICROSECONDS {
+                crtc.borrow_mut().step();
+                gate_array.borrow_mut().step();
+            }
+        })
+    });
+}
+
+fn full_frame(c: &mut Criterion) {
+    let config = system::SystemConfig {
+        deterministic: true,
+        seed: 0,
+        ram_pattern: memory::RamPattern::Zeros,
+        ..Default::default()
+    };
+
+    let mut cpc = system::CPC464::new(config);
+    c.bench_function("full frame", |b| b.iter(|| emulate_frame(&mut cpc)));
+}
+
+fn emulate_frame(cpc: &mut system::CPC464) {
+    let mut elapsed_microseconds = 0;
+    while elapsed_microseconds < FRAME_MICROSECONDS {
+        elapsed_microseconds += cpc.emulate() as u64;
+    }
+}
+
+criterion_group!(benches, fetch_and_execute, decoder, gate_array_frame, full_frame);
+criterion_main!(benches);
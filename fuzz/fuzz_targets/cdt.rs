@@ -0,0 +1,8 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use ronald::cdt_file;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = cdt_file::Tape::from_bytes(data);
+});
@@ -0,0 +1,15 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use ronald::{amsdos, dsk_file, media};
+
+// also walks the AMSDOS directory, which trusts the sector layout of the image
+fuzz_target!(|data: &[u8]| {
+    if let Ok(disk) = dsk_file::Disk::from_bytes(media::MediaSource::new("fuzz"), data) {
+        for name in amsdos::list_files(&disk) {
+            let _ = amsdos::read_file(&disk, &name);
+        }
+        let _ = amsdos::detect_run_command(&disk);
+        let _ = disk.to_bytes();
+    }
+});
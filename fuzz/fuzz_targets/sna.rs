@@ -0,0 +1,10 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use ronald::sna_file;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(snapshot) = sna_file::Snapshot::from_bytes(data) {
+        let _ = snapshot.to_bytes();
+    }
+});
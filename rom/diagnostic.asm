@@ -0,0 +1,615 @@
+; Ronald diagnostic kernel
+;
+; A lower ROM that needs no other firmware. It checks a few behaviours of the
+; CPU, the interrupt timing, the PSG and the CRTC that software relies on and
+; prints a line with OK or FAIL for each. The number of failed tests is left
+; at 0x8002 in RAM.
+;
+; The character matrix at 0x3800 is the public domain X11 misc-fixed 5x8 font.
+;
+; Assembles to diagnostic.rom, the gaps between the org blocks are filled with zeros.
+
+cursor:     equ 0x8000
+failures:   equ 0x8002
+interrupts: equ 0x8003
+
+        org 0x0000
+        di
+        ld bc,0x7f89            ; mode 1, lower ROM on, upper ROM off
+        out (c),c
+        jp start
+
+        org 0x0038
+        jp interrupt
+
+        org 0x0040
+start:
+        ld sp,0xc000
+        ld bc,0xf782            ; PPI port A and C output, B input
+        out (c),c
+
+        ld hl,crtc_registers
+        ld bc,0xbc00
+crtc:
+        out (c),c               ; select the register
+        inc b
+        ld a,(hl)
+        out (c),a
+        dec b
+        inc hl
+        inc c
+        ld a,c
+        cp 14
+        jr nz,crtc
+
+        ld bc,0x7f00            ; pen 0 and the border blue, pen 1 bright yellow
+        out (c),c
+        ld a,0x44
+        out (c),a
+        ld c,0x01
+        out (c),c
+        ld a,0x4a
+        out (c),a
+        ld c,0x10
+        out (c),c
+        ld a,0x44
+        out (c),a
+
+        ld hl,0xc000            ; clear the screen
+        ld de,0xc001
+        ld bc,0x3fff
+        ld (hl),0
+        ldir
+
+        ld hl,banner
+        ld de,0xc000 + 1 * 80 + 2 * 2; row 1, column 2
+        call print
+        ld hl,0xc000 + 3 * 80 + 2 * 2
+        ld (cursor),hl
+        xor a
+        ld (failures),a
+        ld (interrupts),a
+        im 1
+        ei
+
+; S, H and V set
+        ld a,0x7f
+        add a,1
+        push af
+        pop bc
+        ld a,c
+        and 0xd7                ; without the undocumented bits 5 and 3, like zexdoc
+        cp 0x94
+        ld hl,add_text
+        call report
+
+        ld a,0x80
+        sub 1
+        push af
+        pop bc
+        ld a,c
+        and 0xd7                ; without the undocumented bits 5 and 3, like zexdoc
+        cp 0x16
+        ld hl,sub_text
+        call report
+
+; INC leaves the carry alone
+        ld a,0x7f
+        scf
+        inc a
+        push af
+        pop bc
+        ld a,c
+        and 0xd7                ; without the undocumented bits 5 and 3, like zexdoc
+        cp 0x95
+        ld hl,inc_text
+        call report
+
+        ld a,0x15
+        add a,0x27
+        daa
+        push af
+        pop bc
+        ld a,b
+        cp 0x42
+        jr nz,daa_done
+        ld a,c
+        and 0xd7
+        cp 0x14
+daa_done:
+        ld hl,daa_text
+        call report
+
+; six interrupts per frame
+        call wait_vsync
+        xor a
+        ld (interrupts),a
+        call wait_vsync
+        ld a,(interrupts)
+        cp 6
+        ld hl,frame_text
+        call report
+
+; the interrupt two lines into VSYNC is followed by one 52 lines (3328 NOPs) later,
+; each round of the loop takes 10 NOPs
+        call wait_vsync
+        halt
+        ld a,(interrupts)
+        ld e,a
+        ld hl,0
+period:
+        inc hl
+        ld a,(interrupts)
+        cp e
+        jr z,period
+        ld de,-320
+        add hl,de
+        ld a,h
+        or a
+        jr nz,period_done
+        ld a,l
+        cp 21                   ; 320 to 340 rounds
+        sbc a,a                 ; 0xff if in range
+        inc a
+period_done:
+        ld hl,period_text
+        call report
+
+; registers keep what is written, limited to their width
+        ld de,0x5a00            ; d = value, e = register
+        call psg_write
+        call psg_read
+        cp 0x5a
+        jr nz,psg_done
+        ld de,0xff01
+        call psg_write
+        call psg_read
+        cp 0x0f
+psg_done:
+        ld hl,psg_text
+        call report
+
+; type 0 reads back the display start address, but not the timing registers
+        ld bc,0xbc0c
+        out (c),c
+        ld b,0xbf
+        in a,(c)
+        cp 0x30
+        jr nz,crtc_done
+        ld bc,0xbc00
+        out (c),c
+        ld b,0xbf
+        in a,(c)
+        or a
+crtc_done:
+        ld hl,crtc_text
+        call report
+
+        ld a,(failures)
+        or a
+        ld hl,passed_text
+        jr z,summary
+        ld hl,failed_text
+summary:
+        ld de,(cursor)
+        push hl
+        ld hl,80                ; after an empty row
+        add hl,de
+        ex de,hl
+        pop hl
+        call print
+idle:
+        halt
+        jr idle
+
+; prints the name at hl and the result of the test, z set if it passed
+report:
+        push af
+        ld de,(cursor)
+        call print
+        pop af
+        ld hl,ok_text
+        jr z,report_result
+        ld hl,failures
+        inc (hl)
+        ld hl,fail_text
+report_result:
+        ld de,(cursor)
+        push hl
+        ld hl,24 * 2            ; column 26
+        add hl,de
+        ex de,hl
+        pop hl
+        call print
+        ld hl,(cursor)
+        ld de,80                ; the next character row
+        add hl,de
+        ld (cursor),hl
+        ret
+
+; counts the interrupts, which the CPU acknowledges by taking them
+interrupt:
+        push af
+        ld a,(interrupts)
+        inc a
+        ld (interrupts),a
+        pop af
+        ei
+        ret
+
+; returns at the start of the next VSYNC
+wait_vsync:
+        ld b,0xf5
+wait_vsync_end:
+        in a,(c)
+        rra
+        jr c,wait_vsync_end
+wait_vsync_start:
+        in a,(c)
+        rra
+        jr nc,wait_vsync_start
+        ret
+
+; writes d to the PSG register e
+psg_write:
+        ld b,0xf4
+        out (c),e
+        ld bc,0xf6c0
+        out (c),c
+        ld c,0
+        out (c),c
+        ld b,0xf4
+        out (c),d
+        ld bc,0xf680
+        out (c),c
+        ld c,0
+        out (c),c
+        ret
+
+; reads the PSG register e into a, PPI port A is an input meanwhile
+psg_read:
+        ld b,0xf4
+        out (c),e
+        ld bc,0xf6c0
+        out (c),c
+        ld c,0
+        out (c),c
+        ld bc,0xf792
+        out (c),c
+        ld bc,0xf640
+        out (c),c
+        ld b,0xf4
+        in a,(c)
+        ld bc,0xf600
+        out (c),c
+        ld bc,0xf782
+        out (c),c
+        ret
+
+; prints the zero terminated text at hl in pen 1 to the mode 1 screen address in de
+print:
+        ld a,(hl)
+        or a
+        ret z
+        inc hl
+        push hl
+        push de
+        ld l,a
+        ld h,0
+        add hl,hl
+        add hl,hl
+        add hl,hl
+        ld bc,font
+        add hl,bc
+        ld b,8
+print_row:
+        ld a,(hl)               ; four pixels per byte, pen 1 sets the upper bits
+        and 0xf0
+        ld (de),a
+        inc de
+        ld a,(hl)
+        rlca
+        rlca
+        rlca
+        rlca
+        and 0xf0
+        ld (de),a
+        dec de
+        inc hl
+        ld a,d                  ; the next scan line is 0x800 bytes further
+        add a,8
+        ld d,a
+        djnz print_row
+        pop de
+        inc de
+        inc de
+        pop hl
+        jr print
+
+crtc_registers:
+        db 0x3f,0x28,0x2e,0x8e,0x26,0x00,0x19,0x1e,0x00,0x07,0x00,0x00,0x30,0x00; R0 to R13
+banner:
+        db 0x52,0x6f,0x6e,0x61,0x6c,0x64,0x20,0x64,0x69,0x61,0x67,0x6e,0x6f,0x73,0x74,0x69,0x63,0x20,0x6b,0x65,0x72,0x6e,0x65,0x6c,0x00
+add_text:
+        db 0x41,0x44,0x44,0x20,0x66,0x6c,0x61,0x67,0x73,0x00
+sub_text:
+        db 0x53,0x55,0x42,0x20,0x66,0x6c,0x61,0x67,0x73,0x00
+inc_text:
+        db 0x49,0x4e,0x43,0x20,0x66,0x6c,0x61,0x67,0x73,0x00
+daa_text:
+        db 0x44,0x41,0x41,0x20,0x72,0x65,0x73,0x75,0x6c,0x74,0x20,0x61,0x6e,0x64,0x20,0x66,0x6c,0x61,0x67,0x73,0x00
+frame_text:
+        db 0x49,0x6e,0x74,0x65,0x72,0x72,0x75,0x70,0x74,0x73,0x20,0x70,0x65,0x72,0x20,0x66,0x72,0x61,0x6d,0x65,0x00
+period_text:
+        db 0x49,0x6e,0x74,0x65,0x72,0x72,0x75,0x70,0x74,0x20,0x70,0x65,0x72,0x69,0x6f,0x64,0x00
+psg_text:
+        db 0x50,0x53,0x47,0x20,0x72,0x65,0x67,0x69,0x73,0x74,0x65,0x72,0x73,0x00
+crtc_text:
+        db 0x43,0x52,0x54,0x43,0x20,0x72,0x65,0x67,0x69,0x73,0x74,0x65,0x72,0x20,0x72,0x65,0x61,0x64,0x73,0x00
+ok_text:
+        db 0x4f,0x4b,0x00
+fail_text:
+        db 0x46,0x41,0x49,0x4c,0x00
+passed_text:
+        db 0x41,0x6c,0x6c,0x20,0x74,0x65,0x73,0x74,0x73,0x20,0x70,0x61,0x73,0x73,0x65,0x64,0x00
+failed_text:
+        db 0x53,0x6f,0x6d,0x65,0x20,0x74,0x65,0x73,0x74,0x73,0x20,0x66,0x61,0x69,0x6c,0x65,0x64,0x00
+
+        org 0x3800
+font:
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00; ' '
+        db 0x00,0x10,0x10,0x10,0x10,0x00,0x10,0x00; '!'
+        db 0x00,0x28,0x28,0x28,0x00,0x00,0x00,0x00; '"'
+        db 0x28,0x28,0x7c,0x28,0x7c,0x28,0x28,0x00; '#'
+        db 0x10,0x38,0x50,0x38,0x14,0x38,0x10,0x00; '$'
+        db 0x00,0x20,0x28,0x10,0x28,0x08,0x00,0x00; '%'
+        db 0x20,0x50,0x50,0x20,0x50,0x50,0x28,0x00; '&'
+        db 0x00,0x10,0x10,0x10,0x00,0x00,0x00,0x00; "'"
+        db 0x00,0x10,0x20,0x20,0x20,0x20,0x10,0x00; '('
+        db 0x00,0x20,0x10,0x10,0x10,0x10,0x20,0x00; ')'
+        db 0x00,0x00,0x48,0x30,0x78,0x30,0x48,0x00; '*'
+        db 0x00,0x00,0x10,0x10,0x7c,0x10,0x10,0x00; '+'
+        db 0x00,0x00,0x00,0x00,0x00,0x18,0x10,0x20; ','
+        db 0x00,0x00,0x00,0x00,0x78,0x00,0x00,0x00; '-'
+        db 0x00,0x00,0x00,0x00,0x00,0x10,0x38,0x10; '.'
+        db 0x00,0x08,0x08,0x10,0x20,0x40,0x40,0x00; '/'
+        db 0x00,0x10,0x28,0x28,0x28,0x28,0x10,0x00; '0'
+        db 0x00,0x10,0x30,0x10,0x10,0x10,0x38,0x00; '1'
+        db 0x00,0x30,0x48,0x08,0x30,0x40,0x78,0x00; '2'
+        db 0x00,0x78,0x10,0x30,0x08,0x48,0x30,0x00; '3'
+        db 0x00,0x10,0x30,0x50,0x78,0x10,0x10,0x00; '4'
+        db 0x00,0x78,0x40,0x70,0x08,0x48,0x30,0x00; '5'
+        db 0x00,0x30,0x40,0x70,0x48,0x48,0x30,0x00; '6'
+        db 0x00,0x78,0x08,0x10,0x10,0x20,0x20,0x00; '7'
+        db 0x00,0x30,0x48,0x30,0x48,0x48,0x30,0x00; '8'
+        db 0x00,0x30,0x48,0x48,0x38,0x08,0x30,0x00; '9'
+        db 0x00,0x00,0x30,0x30,0x00,0x30,0x30,0x00; ':'
+        db 0x00,0x00,0x18,0x18,0x00,0x18,0x10,0x20; ';'
+        db 0x00,0x08,0x10,0x20,0x20,0x10,0x08,0x00; '<'
+        db 0x00,0x00,0x00,0x78,0x00,0x78,0x00,0x00; '='
+        db 0x00,0x20,0x10,0x08,0x08,0x10,0x20,0x00; '>'
+        db 0x00,0x10,0x28,0x08,0x10,0x00,0x10,0x00; '?'
+        db 0x18,0x24,0x4c,0x54,0x54,0x48,0x20,0x18; '@'
+        db 0x00,0x30,0x48,0x48,0x78,0x48,0x48,0x00; 'A'
+        db 0x00,0x70,0x48,0x70,0x48,0x48,0x70,0x00; 'B'
+        db 0x00,0x30,0x48,0x40,0x40,0x48,0x30,0x00; 'C'
+        db 0x00,0x70,0x48,0x48,0x48,0x48,0x70,0x00; 'D'
+        db 0x00,0x78,0x40,0x70,0x40,0x40,0x78,0x00; 'E'
+        db 0x00,0x78,0x40,0x70,0x40,0x40,0x40,0x00; 'F'
+        db 0x00,0x30,0x48,0x40,0x58,0x48,0x30,0x00; 'G'
+        db 0x00,0x48,0x48,0x78,0x48,0x48,0x48,0x00; 'H'
+        db 0x00,0x38,0x10,0x10,0x10,0x10,0x38,0x00; 'I'
+        db 0x00,0x38,0x10,0x10,0x10,0x50,0x20,0x00; 'J'
+        db 0x00,0x48,0x50,0x60,0x50,0x50,0x48,0x00; 'K'
+        db 0x00,0x40,0x40,0x40,0x40,0x40,0x78,0x00; 'L'
+        db 0x00,0x48,0x78,0x78,0x48,0x48,0x48,0x00; 'M'
+        db 0x00,0x48,0x68,0x78,0x58,0x58,0x48,0x00; 'N'
+        db 0x00,0x30,0x48,0x48,0x48,0x48,0x30,0x00; 'O'
+        db 0x00,0x70,0x48,0x48,0x70,0x40,0x40,0x00; 'P'
+        db 0x00,0x30,0x48,0x48,0x68,0x58,0x30,0x08; 'Q'
+        db 0x00,0x70,0x48,0x48,0x70,0x48,0x48,0x00; 'R'
+        db 0x00,0x30,0x48,0x20,0x10,0x48,0x30,0x00; 'S'
+        db 0x00,0x38,0x10,0x10,0x10,0x10,0x10,0x00; 'T'
+        db 0x00,0x48,0x48,0x48,0x48,0x48,0x30,0x00; 'U'
+        db 0x00,0x48,0x48,0x48,0x48,0x30,0x30,0x00; 'V'
+        db 0x00,0x48,0x48,0x48,0x78,0x78,0x48,0x00; 'W'
+        db 0x00,0x48,0x48,0x30,0x30,0x48,0x48,0x00; 'X'
+        db 0x00,0x44,0x44,0x28,0x10,0x10,0x10,0x00; 'Y'
+        db 0x00,0x78,0x08,0x10,0x20,0x40,0x78,0x00; 'Z'
+        db 0x00,0x38,0x20,0x20,0x20,0x20,0x38,0x00; '['
+        db 0x00,0x40,0x40,0x20,0x10,0x08,0x08,0x00; '\\'
+        db 0x00,0x38,0x08,0x08,0x08,0x08,0x38,0x00; ']'
+        db 0x00,0x10,0x28,0x00,0x00,0x00,0x00,0x00; '^'
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x78; '_'
+        db 0x00,0x20,0x10,0x00,0x00,0x00,0x00,0x00; '`'
+        db 0x00,0x00,0x00,0x38,0x48,0x48,0x38,0x00; 'a'
+        db 0x00,0x40,0x40,0x70,0x48,0x48,0x70,0x00; 'b'
+        db 0x00,0x00,0x00,0x18,0x20,0x20,0x18,0x00; 'c'
+        db 0x00,0x08,0x08,0x38,0x48,0x48,0x38,0x00; 'd'
+        db 0x00,0x00,0x00,0x30,0x58,0x60,0x30,0x00; 'e'
+        db 0x00,0x10,0x28,0x20,0x70,0x20,0x20,0x00; 'f'
+        db 0x00,0x00,0x00,0x30,0x48,0x38,0x08,0x30; 'g'
+        db 0x00,0x40,0x40,0x70,0x48,0x48,0x48,0x00; 'h'
+        db 0x00,0x10,0x00,0x30,0x10,0x10,0x38,0x00; 'i'
+        db 0x00,0x08,0x00,0x08,0x08,0x08,0x28,0x10; 'j'
+        db 0x00,0x40,0x40,0x48,0x70,0x48,0x48,0x00; 'k'
+        db 0x00,0x30,0x10,0x10,0x10,0x10,0x38,0x00; 'l'
+        db 0x00,0x00,0x00,0x68,0x54,0x54,0x54,0x00; 'm'
+        db 0x00,0x00,0x00,0x70,0x48,0x48,0x48,0x00; 'n'
+        db 0x00,0x00,0x00,0x30,0x48,0x48,0x30,0x00; 'o'
+        db 0x00,0x00,0x00,0x70,0x48,0x70,0x40,0x40; 'p'
+        db 0x00,0x00,0x00,0x38,0x48,0x38,0x08,0x08; 'q'
+        db 0x00,0x00,0x00,0x50,0x68,0x40,0x40,0x00; 'r'
+        db 0x00,0x00,0x00,0x18,0x30,0x08,0x30,0x00; 's'
+        db 0x00,0x20,0x20,0x70,0x20,0x28,0x10,0x00; 't'
+        db 0x00,0x00,0x00,0x48,0x48,0x48,0x38,0x00; 'u'
+        db 0x00,0x00,0x00,0x28,0x28,0x28,0x10,0x00; 'v'
+        db 0x00,0x00,0x00,0x44,0x54,0x54,0x28,0x00; 'w'
+        db 0x00,0x00,0x00,0x48,0x30,0x30,0x48,0x00; 'x'
+        db 0x00,0x00,0x00,0x48,0x48,0x38,0x48,0x30; 'y'
+        db 0x00,0x00,0x00,0x78,0x10,0x20,0x78,0x00; 'z'
+        db 0x18,0x20,0x10,0x60,0x10,0x20,0x18,0x00; '{'
+        db 0x00,0x10,0x10,0x10,0x10,0x10,0x10,0x00; '|'
+        db 0x60,0x10,0x20,0x18,0x20,0x10,0x60,0x00; '}'
+        db 0x00,0x28,0x50,0x00,0x00,0x00,0x00,0x00; '~'
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
@@ -0,0 +1,378 @@
+; Ronald minimal firmware
+;
+; A free replacement for the lower ROM of the CPC 464, so the emulator can run
+; snapshots without the Amstrad ROMs. It sets up the hardware, prints a banner
+; and keeps interrupts running. There is no BASIC and no firmware jumpblock.
+;
+; The character matrix at 0x3800 is the public domain X11 misc-fixed 5x8 font.
+;
+; Assembles to minimal.rom, the gaps between the org blocks are filled with zeros.
+
+        org 0x0000
+        di
+        ld bc,0x7f89            ; mode 1, lower ROM on, upper ROM off
+        out (c),c
+        jp start
+
+        org 0x0038
+; the gate array interrupt is acknowledged by the CPU taking it
+        ei
+        ret
+
+        org 0x0040
+start:
+        ld sp,0xc000
+        ld bc,0xf782            ; PPI port A and C output, B input
+        out (c),c
+
+        ld hl,crtc_registers
+        ld bc,0xbc00
+crtc:
+        out (c),c               ; select the register
+        inc b
+        ld a,(hl)
+        out (c),a
+        dec b
+        inc hl
+        inc c
+        ld a,c
+        cp 14
+        jr nz,crtc
+
+        ld bc,0x7f00            ; pen 0 and the border blue, pen 1 bright yellow
+        out (c),c
+        ld a,0x44
+        out (c),a
+        ld c,0x01
+        out (c),c
+        ld a,0x4a
+        out (c),a
+        ld c,0x10
+        out (c),c
+        ld a,0x44
+        out (c),a
+
+        ld hl,0xc000            ; clear the screen
+        ld de,0xc001
+        ld bc,0x3fff
+        ld (hl),0
+        ldir
+
+        ld hl,banner
+        ld de,0xc000 + 2 * 80 + 2 * 2; row 2, column 2
+        call print
+        ld hl,hint
+        ld de,0xc000 + 4 * 80 + 2 * 2
+        call print
+
+        im 1
+        ei
+idle:
+        halt
+        jr idle
+
+; prints the zero terminated text at hl in pen 1 to the mode 1 screen address in de
+print:
+        ld a,(hl)
+        or a
+        ret z
+        inc hl
+        push hl
+        push de
+        ld l,a
+        ld h,0
+        add hl,hl
+        add hl,hl
+        add hl,hl
+        ld bc,font
+        add hl,bc
+        ld b,8
+print_row:
+        ld a,(hl)               ; four pixels per byte, pen 1 sets the upper bits
+        and 0xf0
+        ld (de),a
+        inc de
+        ld a,(hl)
+        rlca
+        rlca
+        rlca
+        rlca
+        and 0xf0
+        ld (de),a
+        dec de
+        inc hl
+        ld a,d                  ; the next scan line is 0x800 bytes further
+        add a,8
+        ld d,a
+        djnz print_row
+        pop de
+        inc de
+        inc de
+        pop hl
+        jr print
+
+crtc_registers:
+        db 0x3f,0x28,0x2e,0x8e,0x26,0x00,0x19,0x1e,0x00,0x07,0x00,0x00,0x30,0x00; R0 to R13
+banner:
+        db 0x52,0x6f,0x6e,0x61,0x6c,0x64,0x20,0x6d,0x69,0x6e,0x69,0x6d,0x61,0x6c,0x20,0x66,0x69,0x72,0x6d,0x77,0x61,0x72,0x65,0x00
+hint:
+        db 0x4e,0x6f,0x20,0x42,0x41,0x53,0x49,0x43,0x2c,0x20,0x6c,0x6f,0x61,0x64,0x20,0x61,0x20,0x73,0x6e,0x61,0x70,0x73,0x68,0x6f,0x74,0x20,0x74,0x6f,0x20,0x73,0x74,0x61,0x72,0x74,0x00
+
+        org 0x3800
+font:
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00; ' '
+        db 0x00,0x10,0x10,0x10,0x10,0x00,0x10,0x00; '!'
+        db 0x00,0x28,0x28,0x28,0x00,0x00,0x00,0x00; '"'
+        db 0x28,0x28,0x7c,0x28,0x7c,0x28,0x28,0x00; '#'
+        db 0x10,0x38,0x50,0x38,0x14,0x38,0x10,0x00; '$'
+        db 0x00,0x20,0x28,0x10,0x28,0x08,0x00,0x00; '%'
+        db 0x20,0x50,0x50,0x20,0x50,0x50,0x28,0x00; '&'
+        db 0x00,0x10,0x10,0x10,0x00,0x00,0x00,0x00; "'"
+        db 0x00,0x10,0x20,0x20,0x20,0x20,0x10,0x00; '('
+        db 0x00,0x20,0x10,0x10,0x10,0x10,0x20,0x00; ')'
+        db 0x00,0x00,0x48,0x30,0x78,0x30,0x48,0x00; '*'
+        db 0x00,0x00,0x10,0x10,0x7c,0x10,0x10,0x00; '+'
+        db 0x00,0x00,0x00,0x00,0x00,0x18,0x10,0x20; ','
+        db 0x00,0x00,0x00,0x00,0x78,0x00,0x00,0x00; '-'
+        db 0x00,0x00,0x00,0x00,0x00,0x10,0x38,0x10; '.'
+        db 0x00,0x08,0x08,0x10,0x20,0x40,0x40,0x00; '/'
+        db 0x00,0x10,0x28,0x28,0x28,0x28,0x10,0x00; '0'
+        db 0x00,0x10,0x30,0x10,0x10,0x10,0x38,0x00; '1'
+        db 0x00,0x30,0x48,0x08,0x30,0x40,0x78,0x00; '2'
+        db 0x00,0x78,0x10,0x30,0x08,0x48,0x30,0x00; '3'
+        db 0x00,0x10,0x30,0x50,0x78,0x10,0x10,0x00; '4'
+        db 0x00,0x78,0x40,0x70,0x08,0x48,0x30,0x00; '5'
+        db 0x00,0x30,0x40,0x70,0x48,0x48,0x30,0x00; '6'
+        db 0x00,0x78,0x08,0x10,0x10,0x20,0x20,0x00; '7'
+        db 0x00,0x30,0x48,0x30,0x48,0x48,0x30,0x00; '8'
+        db 0x00,0x30,0x48,0x48,0x38,0x08,0x30,0x00; '9'
+        db 0x00,0x00,0x30,0x30,0x00,0x30,0x30,0x00; ':'
+        db 0x00,0x00,0x18,0x18,0x00,0x18,0x10,0x20; ';'
+        db 0x00,0x08,0x10,0x20,0x20,0x10,0x08,0x00; '<'
+        db 0x00,0x00,0x00,0x78,0x00,0x78,0x00,0x00; '='
+        db 0x00,0x20,0x10,0x08,0x08,0x10,0x20,0x00; '>'
+        db 0x00,0x10,0x28,0x08,0x10,0x00,0x10,0x00; '?'
+        db 0x18,0x24,0x4c,0x54,0x54,0x48,0x20,0x18; '@'
+        db 0x00,0x30,0x48,0x48,0x78,0x48,0x48,0x00; 'A'
+        db 0x00,0x70,0x48,0x70,0x48,0x48,0x70,0x00; 'B'
+        db 0x00,0x30,0x48,0x40,0x40,0x48,0x30,0x00; 'C'
+        db 0x00,0x70,0x48,0x48,0x48,0x48,0x70,0x00; 'D'
+        db 0x00,0x78,0x40,0x70,0x40,0x40,0x78,0x00; 'E'
+        db 0x00,0x78,0x40,0x70,0x40,0x40,0x40,0x00; 'F'
+        db 0x00,0x30,0x48,0x40,0x58,0x48,0x30,0x00; 'G'
+        db 0x00,0x48,0x48,0x78,0x48,0x48,0x48,0x00; 'H'
+        db 0x00,0x38,0x10,0x10,0x10,0x10,0x38,0x00; 'I'
+        db 0x00,0x38,0x10,0x10,0x10,0x50,0x20,0x00; 'J'
+        db 0x00,0x48,0x50,0x60,0x50,0x50,0x48,0x00; 'K'
+        db 0x00,0x40,0x40,0x40,0x40,0x40,0x78,0x00; 'L'
+        db 0x00,0x48,0x78,0x78,0x48,0x48,0x48,0x00; 'M'
+        db 0x00,0x48,0x68,0x78,0x58,0x58,0x48,0x00; 'N'
+        db 0x00,0x30,0x48,0x48,0x48,0x48,0x30,0x00; 'O'
+        db 0x00,0x70,0x48,0x48,0x70,0x40,0x40,0x00; 'P'
+        db 0x00,0x30,0x48,0x48,0x68,0x58,0x30,0x08; 'Q'
+        db 0x00,0x70,0x48,0x48,0x70,0x48,0x48,0x00; 'R'
+        db 0x00,0x30,0x48,0x20,0x10,0x48,0x30,0x00; 'S'
+        db 0x00,0x38,0x10,0x10,0x10,0x10,0x10,0x00; 'T'
+        db 0x00,0x48,0x48,0x48,0x48,0x48,0x30,0x00; 'U'
+        db 0x00,0x48,0x48,0x48,0x48,0x30,0x30,0x00; 'V'
+        db 0x00,0x48,0x48,0x48,0x78,0x78,0x48,0x00; 'W'
+        db 0x00,0x48,0x48,0x30,0x30,0x48,0x48,0x00; 'X'
+        db 0x00,0x44,0x44,0x28,0x10,0x10,0x10,0x00; 'Y'
+        db 0x00,0x78,0x08,0x10,0x20,0x40,0x78,0x00; 'Z'
+        db 0x00,0x38,0x20,0x20,0x20,0x20,0x38,0x00; '['
+        db 0x00,0x40,0x40,0x20,0x10,0x08,0x08,0x00; '\\'
+        db 0x00,0x38,0x08,0x08,0x08,0x08,0x38,0x00; ']'
+        db 0x00,0x10,0x28,0x00,0x00,0x00,0x00,0x00; '^'
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x78; '_'
+        db 0x00,0x20,0x10,0x00,0x00,0x00,0x00,0x00; '`'
+        db 0x00,0x00,0x00,0x38,0x48,0x48,0x38,0x00; 'a'
+        db 0x00,0x40,0x40,0x70,0x48,0x48,0x70,0x00; 'b'
+        db 0x00,0x00,0x00,0x18,0x20,0x20,0x18,0x00; 'c'
+        db 0x00,0x08,0x08,0x38,0x48,0x48,0x38,0x00; 'd'
+        db 0x00,0x00,0x00,0x30,0x58,0x60,0x30,0x00; 'e'
+        db 0x00,0x10,0x28,0x20,0x70,0x20,0x20,0x00; 'f'
+        db 0x00,0x00,0x00,0x30,0x48,0x38,0x08,0x30; 'g'
+        db 0x00,0x40,0x40,0x70,0x48,0x48,0x48,0x00; 'h'
+        db 0x00,0x10,0x00,0x30,0x10,0x10,0x38,0x00; 'i'
+        db 0x00,0x08,0x00,0x08,0x08,0x08,0x28,0x10; 'j'
+        db 0x00,0x40,0x40,0x48,0x70,0x48,0x48,0x00; 'k'
+        db 0x00,0x30,0x10,0x10,0x10,0x10,0x38,0x00; 'l'
+        db 0x00,0x00,0x00,0x68,0x54,0x54,0x54,0x00; 'm'
+        db 0x00,0x00,0x00,0x70,0x48,0x48,0x48,0x00; 'n'
+        db 0x00,0x00,0x00,0x30,0x48,0x48,0x30,0x00; 'o'
+        db 0x00,0x00,0x00,0x70,0x48,0x70,0x40,0x40; 'p'
+        db 0x00,0x00,0x00,0x38,0x48,0x38,0x08,0x08; 'q'
+        db 0x00,0x00,0x00,0x50,0x68,0x40,0x40,0x00; 'r'
+        db 0x00,0x00,0x00,0x18,0x30,0x08,0x30,0x00; 's'
+        db 0x00,0x20,0x20,0x70,0x20,0x28,0x10,0x00; 't'
+        db 0x00,0x00,0x00,0x48,0x48,0x48,0x38,0x00; 'u'
+        db 0x00,0x00,0x00,0x28,0x28,0x28,0x10,0x00; 'v'
+        db 0x00,0x00,0x00,0x44,0x54,0x54,0x28,0x00; 'w'
+        db 0x00,0x00,0x00,0x48,0x30,0x30,0x48,0x00; 'x'
+        db 0x00,0x00,0x00,0x48,0x48,0x38,0x48,0x30; 'y'
+        db 0x00,0x00,0x00,0x78,0x10,0x20,0x78,0x00; 'z'
+        db 0x18,0x20,0x10,0x60,0x10,0x20,0x18,0x00; '{'
+        db 0x00,0x10,0x10,0x10,0x10,0x10,0x10,0x00; '|'
+        db 0x60,0x10,0x20,0x18,0x20,0x10,0x60,0x00; '}'
+        db 0x00,0x28,0x50,0x00,0x00,0x00,0x00,0x00; '~'
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
+        db 0x00,0x00,0x00,0x00,0x00,0x00,0x00,0x00
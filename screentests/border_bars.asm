@@ -0,0 +1,30 @@
+; border colour changes on every fourth scan line, synchronised to VSYNC
+; loaded at 0x4000 on top of a booted machine, the colour table at 0x4100 holds 0x40 + (line / 4) % 32
+
+        org 0x4000
+
+        di
+        ld bc,0xbc08
+        out (c),c               ; select R8
+        ld bc,0xbd00 + 0x00
+        out (c),c
+        ld bc,0x7f10
+        out (c),c               ; select the border pen
+
+frame:  ld b,0xf5
+vsync:  in a,(c)                ; wait for the start of VSYNC
+        rra
+        jr nc,vsync
+
+        ld b,0x7f
+        ld hl,0x4100
+        ld e,0                  ; 256 lines
+
+line:   ld a,(hl)               ; every line takes exactly 64 NOPs
+        out (c),a
+        inc l
+        ds 53,0                 ; nop
+        dec e
+        jp nz,line
+
+        jp frame
@@ -0,0 +1,113 @@
+use crate::workbench::PanelKind;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Action {
+    OpenPalette,
+    LoadMedia,
+    SaveDisk,
+    ToggleWriteProtect,
+    InsertNextDisk,
+    EjectDisk,
+    Reset,
+    MachineSettings,
+    ReloadFiles,
+    TogglePause,
+    ToggleTurbo,
+    ToggleTapeSound,
+    NextAudioDevice,
+    ToggleFastLoad,
+    ToggleKeyboardMode,
+    ConfigureKeys,
+    TogglePanel(PanelKind),
+    SaveState,
+    TakeScreenshot,
+    ActivateDebugger,
+    CaptureBugReport,
+    Quit,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Shortcut {
+    pub key: minifb::Key,
+    pub ctrl: bool,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct ActionInfo {
+    pub action: Action,
+    pub name: &'static str,
+    pub menu: &'static str,
+    pub shortcut: Option<Shortcut>,
+}
+
+const fn ctrl(key: minifb::Key) -> Option<Shortcut> {
+    Some(Shortcut { key, ctrl: true })
+}
+
+const fn plain(key: minifb::Key) -> Option<Shortcut> {
+    Some(Shortcut { key, ctrl: false })
+}
+
+// every frontend action, used by the menus, the command palette and the keyboard shortcuts
+pub const ACTIONS: [ActionInfo; 26] = [
+    ActionInfo { action: Action::OpenPalette, name: "Command palette", menu: "View", shortcut: ctrl(minifb::Key::P) },
+    ActionInfo { action: Action::LoadMedia, name: "Load disk or tape", menu: "Media", shortcut: ctrl(minifb::Key::O) },
+    ActionInfo { action: Action::SaveDisk, name: "Save disk", menu: "Media", shortcut: ctrl(minifb::Key::S) },
+    ActionInfo { action: Action::ToggleWriteProtect, name: "Toggle write protection", menu: "Media", shortcut: None },
+    ActionInfo { action: Action::InsertNextDisk, name: "Insert next disk", menu: "Media", shortcut: plain(minifb::Key::F8) },
+    ActionInfo { action: Action::EjectDisk, name: "Eject disk", menu: "Media", shortcut: None },
+    ActionInfo { action: Action::Reset, name: "Reset", menu: "Machine", shortcut: None },
+    ActionInfo { action: Action::MachineSettings, name: "Machine settings", menu: "Machine", shortcut: plain(minifb::Key::F9) },
+    ActionInfo { action: Action::ReloadFiles, name: "Reload ROMs and key map", menu: "Machine", shortcut: None },
+    ActionInfo { action: Action::TogglePause, name: "Toggle pause", menu: "Machine", shortcut: plain(minifb::Key::Pause) },
+    ActionInfo { action: Action::ToggleTurbo, name: "Toggle turbo", menu: "Machine", shortcut: None },
+    ActionInfo { action: Action::ToggleTapeSound, name: "Toggle tape sound", menu: "Machine", shortcut: None },
+    ActionInfo { action: Action::NextAudioDevice, name: "Next audio device", menu: "Machine", shortcut: None },
+    ActionInfo { action: Action::ToggleFastLoad, name: "Toggle fast loading", menu: "Machine", shortcut: None },
+    ActionInfo { action: Action::ToggleKeyboardMode, name: "Toggle positional/symbolic keys", menu: "Machine", shortcut: ctrl(minifb::Key::K) },
+    ActionInfo { action: Action::ConfigureKeys, name: "Configure keys", menu: "Machine", shortcut: None },
+    ActionInfo { action: Action::TogglePanel(PanelKind::Registers), name: "Toggle registers panel", menu: "View", shortcut: ctrl(minifb::Key::Key1) },
+    ActionInfo { action: Action::TogglePanel(PanelKind::Memory), name: "Toggle memory panel", menu: "View", shortcut: ctrl(minifb::Key::Key2) },
+    ActionInfo { action: Action::TogglePanel(PanelKind::Disassembly), name: "Toggle disassembly panel", menu: "View", shortcut: ctrl(minifb::Key::Key3) },
+    ActionInfo { action: Action::TogglePanel(PanelKind::Log), name: "Toggle log panel", menu: "View", shortcut: ctrl(minifb::Key::Key4) },
+    ActionInfo { action: Action::TogglePanel(PanelKind::Monitor), name: "Toggle monitor panel", menu: "View", shortcut: ctrl(minifb::Key::Key5) },
+    ActionInfo { action: Action::SaveState, name: "Save state", menu: "Machine", shortcut: plain(minifb::Key::F5) },
+    ActionInfo { action: Action::TakeScreenshot, name: "Take screenshot", menu: "View", shortcut: plain(minifb::Key::F6) },
+    ActionInfo { action: Action::ActivateDebugger, name: "Debugger", menu: "View", shortcut: plain(minifb::Key::F12) },
+    ActionInfo { action: Action::CaptureBugReport, name: "Capture bug report", menu: "View", shortcut: plain(minifb::Key::F11) },
+    ActionInfo { action: Action::Quit, name: "Quit", menu: "Machine", shortcut: ctrl(minifb::Key::Q) },
+];
+
+pub fn search(query: &str) -> Vec<&'static ActionInfo> {
+    // best matches first, ties keep the registry order
+    let mut matches: Vec<(u32, &ActionInfo)> = ACTIONS
+        .iter()
+        .filter_map(|info| fuzzy_score(query, info.name).map(|score| (score, info)))
+        .collect();
+    matches.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+
+    matches.into_iter().map(|(_, info)| info).collect()
+}
+
+fn fuzzy_score(query: &str, name: &str) -> Option<u32> {
+    // all query characters have to appear in order, consecutive characters and word starts score higher
+    let name: Vec<char> = name.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut position = 0;
+    let mut previous_match = None;
+    for character in query.to_lowercase().chars().filter(|character| !character.is_whitespace()) {
+        let offset = name[position..].iter().position(|candidate| *candidate == character)?;
+        let index = position + offset;
+        score += 1;
+        if index > 0 && previous_match == Some(index - 1) {
+            score += 2;
+        }
+        if index == 0 || name[index - 1] == ' ' {
+            score += 3;
+        }
+        previous_match = Some(index);
+        position = index + 1;
+    }
+
+    Some(score)
+}
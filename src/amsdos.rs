@@ -0,0 +1,414 @@
+use crate::bytes;
+use crate::dsk_file;
+use crate::media;
+
+const TRACKS: usize = 40;
+const SECTORS_PER_TRACK: usize = 9;
+const SECTOR_SIZE: usize = 512;
+const SECTOR_SIZE_CODE: u8 = 2;
+const RECORD_SIZE: usize = 128;
+const BLOCK_SIZE: usize = 1024;
+const DIRECTORY_ENTRIES: usize = 64;
+const DIRECTORY_BLOCKS: usize = DIRECTORY_ENTRIES * 32 / BLOCK_SIZE;
+const BLOCKS_PER_EXTENT: usize = 16;
+const RECORDS_PER_EXTENT: usize = 128;
+const UNUSED_ENTRY: u8 = 0xe5;
+const END_OF_FILE: u8 = 0x1a; // pads the last record of files without a header
+
+pub const FILE_TYPE_BASIC: u8 = 0;
+pub const FILE_TYPE_BINARY: u8 = 2;
+
+pub struct File {
+    pub name: String,
+    pub file_type: Option<u8>, // None for files without an AMSDOS header
+    pub load_address: u16,
+    pub entry_address: u16,
+    pub data: Vec<u8>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DiskKind {
+    Data,   // all 40 tracks hold files
+    System, // the first two tracks are reserved for CP/M
+}
+
+impl DiskKind {
+    pub fn from_name(name: &str) -> Option<DiskKind> {
+        match name {
+            "data" => Some(DiskKind::Data),
+            "system" => Some(DiskKind::System),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            DiskKind::Data => "data",
+            DiskKind::System => "system",
+        }
+    }
+
+    fn format(&self) -> Format {
+        match self {
+            DiskKind::Data => Format {
+                reserved_tracks: 0,
+                first_sector_id: 0xc1,
+            },
+            DiskKind::System => Format {
+                reserved_tracks: 2,
+                first_sector_id: 0x41,
+            },
+        }
+    }
+}
+
+struct DirectoryEntry {
+    user: u8,
+    name: String,
+    extent: u8,
+    num_records: u8,
+    blocks: Vec<u8>,
+    hidden: bool, // system files are not shown by CAT
+}
+
+struct Format {
+    reserved_tracks: usize,
+    first_sector_id: u8,
+}
+
+impl Format {
+    fn detect(disk: &dsk_file::Disk) -> Option<Format> {
+        if disk.read_sector(0, 0, 0xc1).is_some() {
+            Some(Format {
+                reserved_tracks: 0,
+                first_sector_id: 0xc1,
+            })
+        } else if disk.read_sector(0, 0, 0x41).is_some() {
+            Some(Format {
+                reserved_tracks: 2, // system tracks hold the CP/M boot sector
+                first_sector_id: 0x41,
+            })
+        } else {
+            None
+        }
+    }
+
+    fn read_logical_sector<'a>(&self, disk: &'a dsk_file::Disk, sector: usize) -> Option<&'a [u8]> {
+        let track = self.reserved_tracks + sector / SECTORS_PER_TRACK;
+        let sector_id = self.first_sector_id + (sector % SECTORS_PER_TRACK) as u8;
+
+        disk.read_sector(track as u8, 0, sector_id)
+    }
+
+    fn write_logical_sector(&self, disk: &mut dsk_file::Disk, sector: usize, data: &[u8]) -> bool {
+        let track = self.reserved_tracks + sector / SECTORS_PER_TRACK;
+        let sector_id = self.first_sector_id + (sector % SECTORS_PER_TRACK) as u8;
+
+        disk.write_sector(track as u8, 0, sector_id, data)
+    }
+
+    fn num_blocks(&self) -> usize {
+        (TRACKS - self.reserved_tracks) * SECTORS_PER_TRACK * SECTOR_SIZE / BLOCK_SIZE
+    }
+
+    fn read_block(&self, disk: &dsk_file::Disk, block: u8) -> Vec<u8> {
+        // blocks are 1K, i.e. two sectors
+        let mut data = Vec::new();
+        for sector in 0..2 {
+            match self.read_logical_sector(disk, block as usize * 2 + sector) {
+                Some(sector_data) => data.extend_from_slice(sector_data),
+                None => data.resize(data.len() + SECTOR_SIZE, 0xe5),
+            }
+        }
+
+        data
+    }
+}
+
+pub fn create_disk(kind: DiskKind, name: &str) -> dsk_file::Disk {
+    // a blank disk as FORMAT would leave it, 40 single sided tracks of nine interleaved sectors
+    let format = kind.format();
+    let mut disk = dsk_file::Disk::new(media::MediaSource::new(name));
+    for track in 0..TRACKS as u8 {
+        let sector_infos = [0, 5, 1, 6, 2, 7, 3, 8, 4]
+            .iter()
+            .map(|sector| dsk_file::SectorInfo {
+                track,
+                side: 0,
+                sector_id: format.first_sector_id + sector,
+                sector_size: SECTOR_SIZE_CODE,
+                fdc_status1: 0,
+                fdc_status2: 0,
+            })
+            .collect();
+        disk.format_track(track, 0, sector_infos, UNUSED_ENTRY);
+    }
+
+    disk
+}
+
+pub fn write_file(disk: &mut dsk_file::Disk, file: &File) -> std::io::Result<()> {
+    // stores the file like SAVE does: with a header if it has a file type, in the first free blocks
+    // and directory entries, one entry per 16K
+    let format = Format::detect(disk).ok_or_else(|| invalid_input("The disk has neither the data nor the system format."))?;
+    let (base_name, extension) = split_name(&file.name)?;
+    let name = format!("{}.{}", base_name, extension);
+    let directory = read_directory(disk);
+    if directory.iter().any(|entry| entry.name == name) {
+        return Err(std::io::Error::new(std::io::ErrorKind::AlreadyExists, format!("{} is already on the disk.", name)));
+    }
+
+    let mut contents = match file.file_type {
+        Some(file_type) => make_header(&base_name, &extension, file_type, file.load_address, file.entry_address, file.data.len()),
+        None => Vec::new(),
+    };
+    contents.extend_from_slice(&file.data);
+    if file.file_type.is_none() {
+        contents.resize(units(contents.len(), RECORD_SIZE) * RECORD_SIZE, END_OF_FILE);
+    }
+
+    let mut used = vec![false; format.num_blocks()];
+    for block in (0..DIRECTORY_BLOCKS).chain(directory.iter().flat_map(|entry| entry.blocks.iter().map(|block| *block as usize))) {
+        if let Some(used) = used.get_mut(block) {
+            *used = true;
+        }
+    }
+    let num_blocks = units(contents.len(), BLOCK_SIZE);
+    let blocks: Vec<usize> = (0..used.len()).filter(|block| !used[*block]).take(num_blocks).collect();
+    if blocks.len() < num_blocks {
+        return Err(invalid_input("The disk is full."));
+    }
+
+    let num_extents = units(num_blocks, BLOCKS_PER_EXTENT).max(1);
+    let slots: Vec<usize> = (0..DIRECTORY_ENTRIES)
+        .filter(|slot| read_directory_slot(&format, disk, *slot).map(|entry| entry[0]) == Some(UNUSED_ENTRY))
+        .take(num_extents)
+        .collect();
+    if slots.len() < num_extents {
+        return Err(invalid_input("The directory is full."));
+    }
+
+    for (block, data) in blocks.iter().zip(contents.chunks(BLOCK_SIZE)) {
+        for (sector, data) in data.chunks(SECTOR_SIZE).enumerate() {
+            format.write_logical_sector(disk, block * 2 + sector, data);
+        }
+    }
+
+    let mut records = units(contents.len(), RECORD_SIZE);
+    for (extent, slot) in slots.iter().enumerate() {
+        let mut entry = [0u8; 32];
+        entry[1..9].copy_from_slice(format!("{:<8}", base_name).as_bytes());
+        entry[9..12].copy_from_slice(format!("{:<3}", extension).as_bytes());
+        entry[12] = extent as u8;
+        entry[15] = records.min(RECORDS_PER_EXTENT) as u8;
+        records -= entry[15] as usize;
+        let extent_blocks = blocks.iter().skip(extent * BLOCKS_PER_EXTENT).take(BLOCKS_PER_EXTENT);
+        for (index, block) in extent_blocks.enumerate() {
+            entry[16 + index] = *block as u8;
+        }
+        write_directory_slot(&format, disk, *slot, &entry);
+    }
+
+    Ok(())
+}
+
+pub fn list_files(disk: &dsk_file::Disk) -> Vec<String> {
+    let mut names: Vec<String> = read_directory(disk)
+        .into_iter()
+        .filter(|entry| entry.extent == 0)
+        .map(|entry| entry.name)
+        .collect();
+    names.sort();
+    names.dedup();
+
+    names
+}
+
+pub fn read_file(disk: &dsk_file::Disk, name: &str) -> Option<File> {
+    let format = Format::detect(disk)?;
+    let directory = read_directory(disk);
+
+    // like AMSDOS, try the name as given, then with .BAS and .BIN appended
+    let name = name.to_uppercase();
+    let candidates = if name.contains('.') {
+        vec![name]
+    } else {
+        vec![format!("{}.", name), format!("{}.BAS", name), format!("{}.BIN", name)]
+    };
+    let name = candidates
+        .into_iter()
+        .find(|candidate| directory.iter().any(|entry| entry.name == *candidate))?;
+
+    let mut extents: Vec<&DirectoryEntry> = directory.iter().filter(|entry| entry.name == name).collect();
+    extents.sort_by_key(|entry| entry.extent);
+
+    let mut data = Vec::new();
+    for extent in extents {
+        let mut extent_data = Vec::new();
+        for block in extent.blocks.iter().filter(|block| **block != 0) {
+            extent_data.extend(format.read_block(disk, *block));
+        }
+        extent_data.truncate(extent.num_records as usize * RECORD_SIZE);
+        data.extend(extent_data);
+    }
+
+    Some(parse_header(name, data))
+}
+
+pub fn detect_run_command(disk: &dsk_file::Disk) -> Option<String> {
+    let format = Format::detect(disk)?;
+    let mut names: Vec<String> = read_directory(disk)
+        .into_iter()
+        .filter(|entry| entry.extent == 0 && !entry.hidden)
+        .map(|entry| entry.name)
+        .collect();
+    names.sort();
+    names.dedup();
+
+    if names.is_empty() {
+        // disks without visible files are usually bootable CP/M disks
+        return if format.reserved_tracks > 0 && has_boot_sector(disk) { Some("|CPM".to_string()) } else { None };
+    }
+
+    // loaders are conventionally called DISC, otherwise prefer BASIC programs over binaries
+    let name = names
+        .iter()
+        .find(|name| name.split('.').next() == Some("DISC"))
+        .or_else(|| names.iter().find(|name| name.ends_with(".BAS")))
+        .or_else(|| names.iter().find(|name| name.ends_with(".BIN")))
+        .or_else(|| names.iter().find(|name| name.ends_with('.')))?;
+
+    // the extension can be left out if AMSDOS would find the same file without it
+    let base_name = name.split('.').next().unwrap();
+    let resolved = read_file(disk, base_name).map(|file| file.name);
+    if resolved.as_deref() == Some(name.as_str()) {
+        Some(format!("RUN\"{}", base_name))
+    } else {
+        Some(format!("RUN\"{}", name))
+    }
+}
+
+pub fn has_boot_sector(disk: &dsk_file::Disk) -> bool {
+    // |CPM loads the first sector of a system format disk to &0100 and runs it
+    match disk.read_sector(0, 0, 0x41) {
+        Some(sector) => sector.iter().any(|byte| *byte != 0xe5 && *byte != 0x00),
+        None => false,
+    }
+}
+
+fn read_directory(disk: &dsk_file::Disk) -> Vec<DirectoryEntry> {
+    let format = match Format::detect(disk) {
+        Some(format) => format,
+        None => return Vec::new(),
+    };
+
+    let mut directory = Vec::new();
+    for sector in 0..(DIRECTORY_ENTRIES * 32 / SECTOR_SIZE) {
+        let data = match format.read_logical_sector(disk, sector) {
+            Some(data) => data,
+            None => continue,
+        };
+
+        for entry in data.chunks(32) {
+            if entry.len() < 32 || entry[0] > 15 {
+                continue; // deleted (0xe5) or unused entry
+            }
+
+            let base_name: String = entry[1..9].iter().map(|c| (c & 0x7f) as char).collect();
+            let extension: String = entry[9..12].iter().map(|c| (c & 0x7f) as char).collect(); // bit 7 holds attributes
+
+            directory.push(DirectoryEntry {
+                user: entry[0],
+                name: format!("{}.{}", base_name.trim_end(), extension.trim_end()),
+                extent: entry[12],
+                num_records: entry[15],
+                blocks: entry[16..32].to_vec(),
+                hidden: entry[10] & 0x80 != 0,
+            });
+        }
+    }
+
+    directory
+}
+
+fn read_directory_slot(format: &Format, disk: &dsk_file::Disk, slot: usize) -> Option<Vec<u8>> {
+    let sector = format.read_logical_sector(disk, slot * 32 / SECTOR_SIZE)?;
+    let offset = slot * 32 % SECTOR_SIZE;
+    Some(sector[offset..(offset + 32)].to_vec())
+}
+
+fn write_directory_slot(format: &Format, disk: &mut dsk_file::Disk, slot: usize, entry: &[u8; 32]) {
+    let sector_number = slot * 32 / SECTOR_SIZE;
+    let mut sector = match format.read_logical_sector(disk, sector_number) {
+        Some(sector) => sector.to_vec(),
+        None => return,
+    };
+    let offset = slot * 32 % SECTOR_SIZE;
+    sector[offset..(offset + 32)].copy_from_slice(entry);
+    format.write_logical_sector(disk, sector_number, &sector);
+}
+
+fn split_name(name: &str) -> std::io::Result<(String, String)> {
+    // AMSDOS names have up to eight characters and an extension of up to three
+    let name = name.to_uppercase();
+    let (base_name, extension) = match name.find('.') {
+        Some(index) => (&name[..index], &name[(index + 1)..]),
+        None => (name.as_str(), ""),
+    };
+    let is_valid = |part: &str| part.chars().all(|c| c.is_ascii_graphic() && !"<>.,;:=?*[]\"".contains(c));
+    if base_name.is_empty() || base_name.len() > 8 || extension.len() > 3 || !is_valid(base_name) || !is_valid(extension) {
+        return Err(invalid_input(&format!("\"{}\" is not a valid AMSDOS file name.", name)));
+    }
+
+    Ok((base_name.to_string(), extension.to_string()))
+}
+
+fn make_header(base_name: &str, extension: &str, file_type: u8, load_address: u16, entry_address: u16, length: usize) -> Vec<u8> {
+    let mut header = vec![0; RECORD_SIZE];
+    header[1..9].copy_from_slice(format!("{:<8}", base_name).as_bytes());
+    header[9..12].copy_from_slice(format!("{:<3}", extension).as_bytes());
+    header[18] = file_type;
+    bytes::write_word(&mut header, 21, load_address);
+    bytes::write_word(&mut header, 24, length as u16);
+    bytes::write_word(&mut header, 26, entry_address);
+    header[64..67].copy_from_slice(&(length as u32).to_le_bytes()[..3]);
+    let checksum: u16 = header[0..67].iter().map(|byte| *byte as u16).sum();
+    bytes::write_word(&mut header, 67, checksum);
+
+    header
+}
+
+fn units(length: usize, size: usize) -> usize {
+    // the number of records, blocks or extents it takes to hold the length, the last one partly used
+    length / size + (length % size).min(1)
+}
+
+fn invalid_input(message: &str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidInput, message)
+}
+
+fn parse_header(name: String, data: Vec<u8>) -> File {
+    if data.len() >= RECORD_SIZE {
+        let checksum: u16 = data[0..67].iter().map(|byte| *byte as u16).sum();
+        if bytes::read_word(&data, 67) == Some(checksum) && checksum != 0 {
+            let length = bytes::read_triple(&data, 64).unwrap_or(0) as usize;
+            let end = (RECORD_SIZE + length).min(data.len());
+
+            return File {
+                name,
+                file_type: Some(data[18]),
+                load_address: bytes::read_word(&data, 21).unwrap_or(0),
+                entry_address: bytes::read_word(&data, 26).unwrap_or(0),
+                data: data[RECORD_SIZE..end].to_vec(),
+            };
+        }
+    }
+
+    File {
+        name,
+        file_type: None,
+        load_address: 0,
+        entry_address: 0,
+        data,
+    }
+}
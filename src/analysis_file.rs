@@ -0,0 +1,121 @@
+use std::fmt::Write;
+
+use crate::coverage;
+use crate::project;
+
+// exports what was learned while running a program to static analysis tools: executed
+// instructions, entry points of called routines, and the labels, comments and data regions of
+// the project. The file extension selects the format:
+//
+// .py  - script for Ghidra's script manager, run on a program loaded at address 0
+// .idc - script for IDA, run via File > Script file
+pub fn write_analysis(filename: &str, coverage: &coverage::Coverage, project: &project::Project) -> std::io::Result<()> {
+    let contents = if filename.ends_with(".py") {
+        ghidra_script(coverage, project)
+    } else if filename.ends_with(".idc") {
+        idc_script(coverage, project)
+    } else {
+        return Err(invalid_data(format!(
+            "Unknown analysis format \"{}\". Expected a .py or .idc file.",
+            filename
+        )));
+    };
+
+    std::fs::write(filename, contents)
+}
+
+fn ghidra_script(coverage: &coverage::Coverage, project: &project::Project) -> String {
+    let mut script = String::from("# ronald analysis for Ghidra\n# @category Ronald\n\n");
+    script.push_str("from ghidra.program.model.symbol import SourceType\n\n");
+
+    let _ = writeln!(script, "executed = {}", python_list(&coverage.read_executed_addresses()));
+    let _ = writeln!(script, "entry_points = {}", python_list(&coverage.read_entry_points()));
+    let _ = writeln!(script, "data_regions = [{}]", join_regions(project, |start, end| format!("({}, {})", start, end)));
+    let _ = writeln!(script, "labels = {{{}}}", join_annotations(project.read_labels(), |address, text| format!("{}: \"{}\"", address, escape(text))));
+    let _ = writeln!(script, "comments = {{{}}}", join_annotations(project.read_comments(), |address, text| format!("{}: \"{}\"", address, escape(text))));
+
+    script.push_str(
+        "
+for start, end in data_regions:
+    clearListing(toAddr(start), toAddr(end))
+    for address in range(start, end + 1):
+        createByte(toAddr(address))
+for address in executed:
+    disassemble(toAddr(address))
+for address in entry_points:
+    if getFunctionAt(toAddr(address)) is None:
+        createFunction(toAddr(address), None)
+for address, label in labels.items():
+    createLabel(toAddr(address), label, True, SourceType.USER_DEFINED)
+for address, comment in comments.items():
+    setEOLComment(toAddr(address), comment)
+",
+    );
+
+    script
+}
+
+fn idc_script(coverage: &coverage::Coverage, project: &project::Project) -> String {
+    let mut script = String::from("// ronald analysis for IDA\n#include <idc.idc>\n\nstatic main() {\n");
+
+    for (start, end) in read_regions(project) {
+        let _ = writeln!(script, "    del_items({:#06x}, DELIT_SIMPLE, {});", start, end as u32 - start as u32 + 1);
+        for address in start..=end {
+            let _ = writeln!(script, "    create_byte({:#06x});", address);
+        }
+    }
+    for address in coverage.read_executed_addresses() {
+        let _ = writeln!(script, "    create_insn({:#06x});", address);
+    }
+    for address in coverage.read_entry_points() {
+        let _ = writeln!(script, "    add_func({:#06x});", address);
+    }
+    for (address, label) in project.read_labels() {
+        let _ = writeln!(script, "    set_name({:#06x}, \"{}\", SN_NOWARN);", address, escape(label));
+    }
+    for (address, comment) in project.read_comments() {
+        let _ = writeln!(script, "    set_cmt({:#06x}, \"{}\", 0);", address, escape(comment));
+    }
+
+    script.push_str("}\n");
+    script
+}
+
+fn read_regions(project: &project::Project) -> Vec<(u16, u16)> {
+    project
+        .read_data_regions()
+        .iter()
+        .map(|region| (region.start, region.end))
+        .collect()
+}
+
+fn join_regions(project: &project::Project, format: impl Fn(u16, u16) -> String) -> String {
+    read_regions(project)
+        .into_iter()
+        .map(|(start, end)| format(start, end))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn join_annotations<'a>(
+    annotations: impl Iterator<Item = (u16, &'a str)>,
+    format: impl Fn(u16, &str) -> String,
+) -> String {
+    annotations
+        .map(|(address, text)| format(address, text))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn python_list(addresses: &[u16]) -> String {
+    let items: Vec<String> = addresses.iter().map(|address| address.to_string()).collect();
+    format!("[{}]", items.join(", "))
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn invalid_data(message: String) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message)
+}
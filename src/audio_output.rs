@@ -0,0 +1,147 @@
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+
+use crate::audio_ring;
+use crate::mixer;
+
+pub const DEFAULT_LATENCY_MILLISECONDS: u32 = 60;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct AudioSettings {
+    pub device: Option<String>,      // the host's default output if None
+    pub buffer_frames: Option<u32>,  // per callback of the device, its default if None
+    pub latency_milliseconds: u32,   // samples buffered between the emulation and the device
+}
+
+impl Default for AudioSettings {
+    fn default() -> Self {
+        AudioSettings {
+            device: None,
+            buffer_frames: None,
+            latency_milliseconds: DEFAULT_LATENCY_MILLISECONDS,
+        }
+    }
+}
+
+pub fn list_devices() -> Vec<String> {
+    let host = cpal::default_host();
+    match host.output_devices() {
+        Ok(devices) => devices.filter_map(|device| device.name().ok()).collect(),
+        Err(error) => {
+            log::warn!("Could not list the audio devices: {}", error);
+            Vec::new()
+        }
+    }
+}
+
+// plays the samples of the mixer on an audio device, the device pulls them from a ring buffer on
+// its own thread. Small buffers lower the latency, large ones avoid crackling on slow devices
+pub struct AudioOutput {
+    _stream: cpal::Stream, // stops playing when dropped
+    device_name: String,
+    settings: AudioSettings,
+}
+
+impl AudioOutput {
+    pub fn open(mixer: &mut mixer::Mixer, settings: &AudioSettings) -> std::io::Result<AudioOutput> {
+        let host = cpal::default_host();
+        let device = match &settings.device {
+            Some(name) => host
+                .output_devices()
+                .map_err(device_error)?
+                .find(|device| device.name().map(|device_name| &device_name == name).unwrap_or(false))
+                .ok_or_else(|| device_error(format!("no audio device named \"{}\"", name)))?,
+            None => host.default_output_device().ok_or_else(|| device_error("no default audio device"))?,
+        };
+        let device_name = device.name().map_err(device_error)?;
+
+        // the mixer's sample rate is fixed, so the device has to play at that rate
+        let sample_rate = cpal::SampleRate(mixer.get_sample_rate());
+        let supported = device
+            .supported_output_configs()
+            .map_err(device_error)?
+            .filter(|range| range.min_sample_rate() <= sample_rate && sample_rate <= range.max_sample_rate())
+            .min_by_key(|range| (range.sample_format() != cpal::SampleFormat::F32, range.channels()))
+            .ok_or_else(|| device_error(format!("{} does not play {}Hz", device_name, sample_rate.0)))?
+            .with_sample_rate(sample_rate);
+
+        let mut config = supported.config();
+        if let Some(frames) = settings.buffer_frames {
+            if let cpal::SupportedBufferSize::Range { min, max } = supported.buffer_size() {
+                if frames < *min || frames > *max {
+                    return Err(device_error(format!("{} takes buffers of {} to {} frames", device_name, min, max)));
+                }
+            }
+            config.buffer_size = cpal::BufferSize::Fixed(frames);
+        }
+
+        let capacity = (mixer.get_sample_rate() * settings.latency_milliseconds / 1000) as usize;
+        let consumer = mixer.connect_output(capacity);
+        let stream = match supported.sample_format() {
+            cpal::SampleFormat::F32 => build_stream::<f32>(&device, &config, consumer),
+            cpal::SampleFormat::I16 => build_stream::<i16>(&device, &config, consumer),
+            cpal::SampleFormat::U16 => build_stream::<u16>(&device, &config, consumer),
+        }
+        .map_err(device_error)
+        .and_then(|stream| stream.play().map(|_| stream).map_err(device_error));
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(error) => {
+                mixer.disconnect_output();
+                return Err(error);
+            }
+        };
+        log::info!("Playing audio on {} with {}ms latency.", device_name, settings.latency_milliseconds);
+
+        Ok(AudioOutput {
+            _stream: stream,
+            device_name,
+            settings: settings.clone(),
+        })
+    }
+
+    pub fn get_device_name(&self) -> &str {
+        &self.device_name
+    }
+
+    pub fn get_settings(&self) -> &AudioSettings {
+        &self.settings
+    }
+}
+
+fn build_stream<T: cpal::Sample>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    mut consumer: audio_ring::AudioConsumer,
+) -> Result<cpal::Stream, cpal::BuildStreamError> {
+    let channels = config.channels as usize;
+    let mut samples = Vec::new();
+    let mut last_sample = 0.0;
+    device.build_output_stream(
+        config,
+        move |data: &mut [T], _| {
+            // the mixer is mono, every channel gets the same samples
+            let frames = data.len() / channels;
+            samples.resize(frames, 0.0);
+            let count = consumer.pop_into(&mut samples);
+            if count > 0 {
+                last_sample = samples[count - 1];
+            }
+            // holding the last level on underruns avoids clicks
+            for sample in samples[count..].iter_mut() {
+                *sample = last_sample;
+            }
+
+            for (frame, sample) in data.chunks_mut(channels).zip(samples.iter()) {
+                for output in frame.iter_mut() {
+                    *output = T::from(sample);
+                }
+            }
+        },
+        |error| log::warn!("Audio output failed: {}", error),
+    )
+}
+
+fn device_error<E: ToString>(error: E) -> std::io::Error {
+    // mostly settings the device does not support
+    std::io::Error::new(std::io::ErrorKind::InvalidInput, error.to_string())
+}
@@ -0,0 +1,80 @@
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+use std::sync::Arc;
+
+struct Ring {
+    samples: Vec<AtomicU32>, // bits of f32 samples
+    read: AtomicUsize,       // samples taken so far, wrapping
+    write: AtomicUsize,      // samples added so far, wrapping
+}
+
+impl Ring {
+    fn len(&self) -> usize {
+        self.write.load(Ordering::Acquire).wrapping_sub(self.read.load(Ordering::Acquire))
+    }
+}
+
+// the emulation side of a single producer, single consumer ring buffer. Neither side ever waits
+// for the other, so an audio callback on its own thread can drain it while the emulation runs
+pub struct AudioProducer {
+    ring: Arc<Ring>,
+}
+
+// the audio output side, which can be moved to the thread of the audio device
+pub struct AudioConsumer {
+    ring: Arc<Ring>,
+}
+
+pub fn audio_ring(capacity: usize) -> (AudioProducer, AudioConsumer) {
+    let ring = Arc::new(Ring {
+        samples: (0..capacity.max(1)).map(|_| AtomicU32::new(0)).collect(),
+        read: AtomicUsize::new(0),
+        write: AtomicUsize::new(0),
+    });
+
+    (AudioProducer { ring: ring.clone() }, AudioConsumer { ring })
+}
+
+impl AudioProducer {
+    pub fn push(&mut self, sample: f32) -> bool {
+        // returns false if the consumer fell behind and the sample was dropped
+        let ring = &self.ring;
+        let write = ring.write.load(Ordering::Relaxed);
+        if write.wrapping_sub(ring.read.load(Ordering::Acquire)) == ring.samples.len() {
+            return false;
+        }
+
+        ring.samples[write % ring.samples.len()].store(sample.to_bits(), Ordering::Relaxed);
+        ring.write.store(write.wrapping_add(1), Ordering::Release);
+        true
+    }
+
+    pub fn read_fill(&self) -> f32 {
+        // between 0 and 1
+        self.ring.len() as f32 / self.ring.samples.len() as f32
+    }
+}
+
+impl AudioConsumer {
+    pub fn pop_into(&mut self, buffer: &mut [f32]) -> usize {
+        // returns how many samples were available, the rest of the buffer is left untouched
+        let ring = &self.ring;
+        let read = ring.read.load(Ordering::Relaxed);
+        let available = ring.write.load(Ordering::Acquire).wrapping_sub(read);
+        let count = available.min(buffer.len());
+        for (offset, sample) in buffer[..count].iter_mut().enumerate() {
+            let index = read.wrapping_add(offset) % ring.samples.len();
+            *sample = f32::from_bits(ring.samples[index].load(Ordering::Relaxed));
+        }
+        ring.read.store(read.wrapping_add(count), Ordering::Release);
+
+        count
+    }
+
+    pub fn len(&self) -> usize {
+        self.ring.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
@@ -0,0 +1,587 @@
+pub const PROGRAM_START: usize = 0x0170; // start of the BASIC program area on the CPC 464
+
+const KEYWORDS: [&str; 127] = [
+    "AFTER", "AUTO", "BORDER", "CALL", "CAT", "CHAIN", "CLEAR", "CLG", // 0x80
+    "CLOSEIN", "CLOSEOUT", "CLS", "CONT", "DATA", "DEF", "DEFINT", "DEFREAL", // 0x88
+    "DEFSTR", "DEG", "DELETE", "DIM", "DRAW", "DRAWR", "EDIT", "ELSE", // 0x90
+    "END", "ENT", "ENV", "ERASE", "ERROR", "EVERY", "FOR", "GOSUB", // 0x98
+    "GOTO", "IF", "INK", "INPUT", "KEY", "LET", "LINE", "LIST", // 0xa0
+    "LOAD", "LOCATE", "MEMORY", "MERGE", "MID$", "MODE", "MOVE", "MOVER", // 0xa8
+    "NEXT", "NEW", "ON", "ON BREAK", "ON ERROR GOTO", "ON SQ", "OPENIN", "OPENOUT", // 0xb0
+    "ORIGIN", "OUT", "PAPER", "PEN", "PLOT", "PLOTR", "POKE", "PRINT", // 0xb8
+    "'", "RAD", "RANDOMIZE", "READ", "RELEASE", "REM", "RENUM", "RESTORE", // 0xc0
+    "RESUME", "RETURN", "RUN", "SAVE", "SOUND", "SPEED", "STOP", "SYMBOL", // 0xc8
+    "TAG", "TAGOFF", "TROFF", "TRON", "WAIT", "WEND", "WHILE", "WIDTH", // 0xd0
+    "WINDOW", "WRITE", "ZONE", "DI", "EI", "FILL", "GRAPHICS", "MASK", // 0xd8
+    "FRAME", "CURSOR", "", "ERL", "FN", "SPC", "STEP", "SWAP", // 0xe0
+    "", "", "TAB", "THEN", "TO", "USING", ">", "=", // 0xe8
+    ">=", "<", "<>", "<=", "+", "-", "*", "/", // 0xf0
+    "^", "\\", "AND", "MOD", "OR", "XOR", "NOT", // 0xf8
+];
+
+const FUNCTIONS: [(u8, &str); 55] = [
+    (0x00, "ABS"), (0x01, "ASC"), (0x02, "ATN"), (0x03, "CHR$"),
+    (0x04, "CINT"), (0x05, "COS"), (0x06, "CREAL"), (0x07, "EXP"),
+    (0x08, "FIX"), (0x09, "FRE"), (0x0a, "INKEY"), (0x0b, "INP"),
+    (0x0c, "INT"), (0x0d, "JOY"), (0x0e, "LEN"), (0x0f, "LOG"),
+    (0x10, "LOG10"), (0x11, "LOWER$"), (0x12, "PEEK"), (0x13, "REMAIN"),
+    (0x14, "SGN"), (0x15, "SIN"), (0x16, "SPACE$"), (0x17, "SQ"),
+    (0x18, "SQR"), (0x19, "STR$"), (0x1a, "TAN"), (0x1b, "UNT"),
+    (0x1c, "UPPER$"), (0x1d, "VAL"), (0x40, "EOF"), (0x41, "ERR"),
+    (0x42, "HIMEM"), (0x43, "INKEY$"), (0x44, "PI"), (0x45, "RND"),
+    (0x46, "TIME"), (0x47, "XPOS"), (0x48, "YPOS"), (0x49, "DERR"),
+    (0x71, "BIN$"), (0x72, "DEC$"), (0x73, "HEX$"), (0x74, "INSTR"),
+    (0x75, "LEFT$"), (0x76, "MAX"), (0x77, "MIN"), (0x78, "POS"),
+    (0x79, "RIGHT$"), (0x7a, "ROUND"), (0x7b, "STRING$"), (0x7c, "TEST"),
+    (0x7d, "TESTR"), (0x7e, "COPYCHR$"), (0x7f, "VPOS"),
+];
+
+const SEPARATOR: u8 = 0x01;
+const TOKEN_ELSE: u8 = 0x97;
+const TOKEN_DATA: u8 = 0x8c;
+const TOKEN_COMMENT: u8 = 0xc0;
+const TOKEN_REM: u8 = 0xc5;
+const TOKEN_ON: u8 = 0xb2;
+const TOKEN_FN: u8 = 0xe4;
+const TOKEN_FUNCTION: u8 = 0xff;
+
+// symbols of the CPC character set above 0x7f, which program text can contain in strings and REMs
+const CPC_CHARACTERS: [(char, u8); 31] = [
+    ('´', 0xa1), ('¨', 0xa2), ('£', 0xa3), ('©', 0xa4), ('¶', 0xa5), ('§', 0xa6), ('‘', 0xa7),
+    ('¼', 0xa8), ('½', 0xa9), ('¾', 0xaa), ('±', 0xab), ('÷', 0xac), ('¬', 0xad), ('¿', 0xae), ('¡', 0xaf),
+    ('α', 0xb0), ('β', 0xb1), ('γ', 0xb2), ('δ', 0xb3), ('ε', 0xb4), ('θ', 0xb5), ('λ', 0xb6), ('μ', 0xb7),
+    ('π', 0xb8), ('σ', 0xb9), ('φ', 0xba), ('ψ', 0xbb), ('χ', 0xbc), ('ω', 0xbd), ('Σ', 0xbe), ('Ω', 0xbf),
+];
+
+// tokens that are followed by line numbers instead of plain numbers
+const LINE_NUMBER_TOKENS: [u8; 12] = [0x81, 0x92, 0x96, 0x97, 0x9f, 0xa0, 0xa7, 0xc6, 0xc7, 0xc8, 0xca, 0xeb];
+
+pub fn read_program_from_memory(read_byte: impl Fn(usize) -> u8) -> Vec<u8> {
+    let mut program = Vec::new();
+    let mut address = PROGRAM_START;
+
+    // each line starts with its length and number, a line that is too short or runs past the end of
+    // memory ends the program, like the zero length after the last line
+    while address + 4 <= 0x10000 {
+        let length = u16::from_le_bytes([read_byte(address), read_byte(address + 1)]) as usize;
+        if length < 4 || address + length > 0x10000 {
+            break;
+        }
+
+        for offset in 0..length {
+            program.push(read_byte(address + offset));
+        }
+        address += length;
+    }
+
+    program.extend_from_slice(&[0, 0]);
+    program
+}
+
+pub fn detokenize(program: &[u8]) -> String {
+    let mut text = String::new();
+    let mut position = 0;
+
+    while position + 4 <= program.len() {
+        let length = u16::from_le_bytes([program[position], program[position + 1]]) as usize;
+        if length < 4 || position + length > program.len() {
+            break;
+        }
+
+        let line_number = u16::from_le_bytes([program[position + 2], program[position + 3]]);
+        text.push_str(&format!("{} ", line_number));
+        detokenize_line(&program[(position + 4)..(position + length)], &mut text);
+        text.push('\n');
+
+        position += length;
+    }
+
+    text
+}
+
+fn detokenize_line(tokens: &[u8], text: &mut String) {
+    let mut position = 0;
+    let mut literal_until_separator = false;
+
+    while position < tokens.len() {
+        let token = tokens[position];
+        position += 1;
+
+        if literal_until_separator && token != SEPARATOR && token != 0x00 {
+            text.push(to_character(token));
+            continue;
+        }
+        literal_until_separator = false;
+
+        match token {
+            0x00 => break,
+            SEPARATOR => {
+                // ELSE and ' are stored with a separator in front which is not listed
+                match tokens.get(position) {
+                    Some(&TOKEN_ELSE) | Some(&TOKEN_COMMENT) => (),
+                    _ => text.push(':'),
+                }
+            }
+            0x02..=0x0d => {
+                // variable with a two byte offset, followed by the name (last character has bit 7 set)
+                position += 2;
+                while position < tokens.len() {
+                    let character = tokens[position];
+                    position += 1;
+                    text.push((character & 0x7f) as char);
+                    if character & 0x80 != 0 {
+                        break;
+                    }
+                }
+
+                match token {
+                    0x02 => text.push('%'),
+                    0x03 => text.push('$'),
+                    0x04 => text.push('!'),
+                    _ => (),
+                }
+            }
+            0x0e..=0x18 => text.push_str(&format!("{}", token - 0x0e)),
+            0x19 => {
+                text.push_str(&format!("{}", tokens.get(position).copied().unwrap_or(0)));
+                position += 1;
+            }
+            0x1a..=0x1e => {
+                let value = read_word(tokens, position);
+                position += 2;
+
+                match token {
+                    0x1b => text.push_str(&format!("&X{:b}", value)),
+                    0x1c => text.push_str(&format!("&{:X}", value)),
+                    0x1d => text.push_str("<line pointer>"), // only present while a program is running
+                    _ => text.push_str(&format!("{}", value)),
+                }
+            }
+            0x1f => {
+                if position + 5 <= tokens.len() {
+                    text.push_str(&format_real(&tokens[position..(position + 5)]));
+                }
+                position += 5;
+            }
+            b'"' => {
+                text.push('"');
+                while position < tokens.len() && tokens[position] != 0x00 {
+                    let character = tokens[position];
+                    position += 1;
+                    text.push(to_character(character));
+                    if character == b'"' {
+                        break;
+                    }
+                }
+            }
+            b'|' => {
+                // RSX name, preceded by an offset byte
+                text.push('|');
+                position += 1;
+                while position < tokens.len() {
+                    let character = tokens[position];
+                    position += 1;
+                    text.push((character & 0x7f) as char);
+                    if character & 0x80 != 0 {
+                        break;
+                    }
+                }
+            }
+            0x20..=0x7e => text.push(token as char),
+            TOKEN_FUNCTION => {
+                let function = tokens.get(position).copied().unwrap_or(0);
+                position += 1;
+                match FUNCTIONS.iter().find(|(code, _)| *code == function) {
+                    Some((_, name)) if !name.is_empty() => text.push_str(name),
+                    _ => text.push_str(&format!("<{:#04x}>", function)),
+                }
+            }
+            0x80..=0xfe => {
+                let keyword = KEYWORDS[(token - 0x80) as usize];
+                text.push_str(keyword);
+
+                if token == TOKEN_REM || token == TOKEN_COMMENT {
+                    while position < tokens.len() && tokens[position] != 0x00 {
+                        text.push(to_character(tokens[position]));
+                        position += 1;
+                    }
+                } else if token == TOKEN_DATA {
+                    literal_until_separator = true;
+                }
+            }
+            _ => text.push_str(&format!("<{:#04x}>", token)),
+        }
+    }
+}
+
+fn read_word(tokens: &[u8], position: usize) -> u16 {
+    let low_byte = tokens.get(position).copied().unwrap_or(0);
+    let high_byte = tokens.get(position + 1).copied().unwrap_or(0);
+    u16::from_le_bytes([low_byte, high_byte])
+}
+
+fn format_real(bytes: &[u8]) -> String {
+    let exponent = bytes[4];
+    if exponent == 0 {
+        return String::from("0");
+    }
+
+    let mantissa = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    let negative = mantissa & 0x8000_0000 != 0;
+    let value = (mantissa | 0x8000_0000) as f64 / 4_294_967_296.0 * 2f64.powi(exponent as i32 - 128);
+
+    // BASIC prints nine significant digits
+    let digits = 9 - (value.abs().log10().floor() as i32 + 1).clamp(0, 9);
+    let formatted = format!("{:.*}", digits as usize, value);
+    let formatted = if formatted.contains('.') {
+        formatted.trim_end_matches('0').trim_end_matches('.')
+    } else {
+        &formatted
+    };
+
+    if negative {
+        format!("-{}", formatted)
+    } else {
+        formatted.to_string()
+    }
+}
+
+fn encode_real(value: f64) -> [u8; 5] {
+    if value == 0.0 {
+        return [0; 5];
+    }
+
+    let mut exponent = value.abs().log2().floor() as i32 + 1;
+    let mut mantissa = (value.abs() / 2f64.powi(exponent) * 4_294_967_296.0).round() as u64;
+    if mantissa > 0xffff_ffff {
+        mantissa >>= 1;
+        exponent += 1;
+    }
+
+    let mut mantissa = mantissa as u32 & 0x7fff_ffff;
+    if value < 0.0 {
+        mantissa |= 0x8000_0000;
+    }
+
+    let bytes = mantissa.to_le_bytes();
+    [bytes[0], bytes[1], bytes[2], bytes[3], (exponent + 128) as u8]
+}
+
+pub fn tokenize(text: &str) -> Result<Vec<u8>, String> {
+    let mut program = Vec::new();
+
+    for line in text.lines() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let digits = line.chars().take_while(|c| c.is_ascii_digit()).count();
+        let line_number = match line[..digits].parse::<u16>() {
+            Ok(line_number) => line_number,
+            Err(_) => return Err(format!("Missing line number in \"{}\".", line)),
+        };
+
+        let characters = to_character_codes(line, line_number)?;
+        let tokens = tokenize_line(&characters[(line.len() - line[digits..].trim_start().len())..]);
+        let length = tokens.len() + 5;
+        if length > 0xffff {
+            return Err(format!("Line {} is too long.", line_number));
+        }
+
+        program.extend_from_slice(&(length as u16).to_le_bytes());
+        program.extend_from_slice(&line_number.to_le_bytes());
+        program.extend_from_slice(&tokens);
+        program.push(0x00);
+    }
+
+    program.extend_from_slice(&[0, 0]);
+    Ok(program)
+}
+
+fn to_character_codes(line: &str, line_number: u16) -> Result<Vec<u8>, String> {
+    line.chars()
+        .enumerate()
+        .map(|(column, character)| match CPC_CHARACTERS.iter().find(|(symbol, _)| *symbol == character) {
+            _ if character.is_ascii() => Ok(character as u8),
+            Some((_, code)) => Ok(*code),
+            None => Err(format!("Line {}, column {}: \"{}\" is not in the CPC character set.", line_number, column + 1, character)),
+        })
+        .collect()
+}
+
+fn to_character(code: u8) -> char {
+    match CPC_CHARACTERS.iter().find(|(_, symbol_code)| *symbol_code == code) {
+        Some((symbol, _)) => *symbol,
+        None => code as char,
+    }
+}
+
+fn tokenize_line(bytes: &[u8]) -> Vec<u8> {
+    // keywords and numbers are matched in text with one character per byte, where the CPC
+    // characters above 0x7f stand in as DEL, which is in no keyword
+    let line: String = bytes.iter().map(|byte| if byte.is_ascii() { *byte as char } else { '\x7f' }).collect();
+    let line = line.as_str();
+    let mut tokens = Vec::new();
+    let mut position = 0;
+    let mut expect_line_number = false;
+
+    while position < bytes.len() {
+        let character = bytes[position];
+
+        if character == b'"' {
+            let end = match bytes[(position + 1)..].iter().position(|c| *c == b'"') {
+                Some(end) => position + end + 2,
+                None => bytes.len(),
+            };
+            tokens.extend_from_slice(&bytes[position..end]);
+            position = end;
+            continue;
+        }
+
+        if character == b':' {
+            tokens.push(SEPARATOR);
+            position += 1;
+            expect_line_number = false;
+            continue;
+        }
+
+        if character == b' ' {
+            tokens.push(character);
+            position += 1;
+            continue;
+        }
+
+        if character == b'&' {
+            let (base, start) = match bytes.get(position + 1) {
+                Some(b'X') | Some(b'x') => (2, position + 2),
+                Some(b'H') | Some(b'h') => (16, position + 2),
+                _ => (16, position + 1),
+            };
+            let length = bytes[start..].iter().take_while(|c| (**c as char).is_digit(base)).count();
+            if let Ok(value) = u16::from_str_radix(&line[start..(start + length)], base) {
+                tokens.push(if base == 2 { 0x1b } else { 0x1c });
+                tokens.extend_from_slice(&value.to_le_bytes());
+                position = start + length;
+                continue;
+            }
+        }
+
+        if character.is_ascii_digit() || (character == b'.' && matches!(bytes.get(position + 1), Some(c) if c.is_ascii_digit())) {
+            let length = bytes[position..]
+                .iter()
+                .take_while(|c| c.is_ascii_digit() || **c == b'.')
+                .count();
+            let number = &line[position..(position + length)];
+            position += length;
+
+            match number.parse::<u16>() {
+                Ok(value) if expect_line_number => {
+                    tokens.push(0x1e);
+                    tokens.extend_from_slice(&value.to_le_bytes());
+                }
+                Ok(value) if value <= 10 => tokens.push(0x0e + value as u8),
+                Ok(value) if value < 0x100 => tokens.extend_from_slice(&[0x19, value as u8]),
+                Ok(value) if value < 0x8000 => {
+                    tokens.push(0x1a);
+                    tokens.extend_from_slice(&value.to_le_bytes());
+                }
+                _ => {
+                    tokens.push(0x1f);
+                    tokens.extend_from_slice(&encode_real(number.parse::<f64>().unwrap_or(0.0)));
+                }
+            }
+            continue;
+        }
+
+        if character == b'\'' {
+            tokens.extend_from_slice(&[SEPARATOR, TOKEN_COMMENT]);
+            tokens.extend_from_slice(&bytes[(position + 1)..]);
+            break;
+        }
+
+        if let Some(token) = match_operator(&line[position..]) {
+            let keyword = KEYWORDS[(token - 0x80) as usize];
+            tokens.push(token);
+            position += keyword.len();
+            expect_line_number = false;
+            continue;
+        }
+
+        if character.is_ascii_alphabetic() {
+            // keywords are only recognized as whole words, so "TOTAL" is a variable and not "TO" "TAL"
+            let mut length = bytes[position..]
+                .iter()
+                .take_while(|c| c.is_ascii_alphanumeric() || **c == b'.')
+                .count();
+            if bytes.get(position + length) == Some(&b'$') && match_keyword(&line[position..(position + length + 1)]).is_some() {
+                length += 1;
+            }
+            let word = &line[position..(position + length)];
+
+            if let Some(token) = match_keyword(word) {
+                position += length;
+                let token = push_keyword(&mut tokens, token, &line[position..], &mut position);
+
+                if token == TOKEN_REM || token == TOKEN_COMMENT {
+                    tokens.extend_from_slice(&bytes[position..]);
+                    break;
+                }
+
+                if token == TOKEN_DATA {
+                    let end = match bytes[position..].iter().position(|c| *c == b':') {
+                        Some(end) => position + end,
+                        None => bytes.len(),
+                    };
+                    tokens.extend_from_slice(&bytes[position..end]);
+                    position = end;
+                }
+
+                expect_line_number = LINE_NUMBER_TOKENS.contains(&token);
+                continue;
+            }
+
+            let name = if length > 2 && word[..2].eq_ignore_ascii_case("FN") {
+                // user defined functions are stored as the FN keyword followed by their name
+                tokens.push(TOKEN_FN);
+                &bytes[(position + 2)..(position + length)]
+            } else {
+                &bytes[position..(position + length)]
+            };
+            position += length;
+
+            let token = match bytes.get(position) {
+                Some(b'%') => 0x02,
+                Some(b'$') => 0x03,
+                Some(b'!') => 0x04,
+                _ => 0x0d,
+            };
+            if token != 0x0d {
+                position += 1;
+            }
+
+            tokens.push(token);
+            tokens.extend_from_slice(&[0, 0]); // offset into the variable table, filled in by BASIC
+            tokens.extend_from_slice(&name[..(name.len() - 1)]);
+            tokens.push(name[name.len() - 1] | 0x80);
+            expect_line_number = false;
+            continue;
+        }
+
+        if character != b',' {
+            expect_line_number = false;
+        }
+
+        tokens.push(character);
+        position += 1;
+    }
+
+    tokens
+}
+
+fn push_keyword(tokens: &mut Vec<u8>, token: u16, rest: &str, position: &mut usize) -> u8 {
+    if token >= 0x100 {
+        tokens.extend_from_slice(&[TOKEN_FUNCTION, (token & 0xff) as u8]);
+        return TOKEN_FUNCTION;
+    }
+
+    let mut token = token as u8;
+    if token == TOKEN_ON {
+        for (suffix, combined_token) in [(" ERROR GOTO", 0xb4), (" BREAK", 0xb3), (" SQ", 0xb5)] {
+            if rest.len() >= suffix.len() && rest[..suffix.len()].eq_ignore_ascii_case(suffix) {
+                token = combined_token;
+                *position += suffix.len();
+                break;
+            }
+        }
+    }
+
+    if token == TOKEN_ELSE || token == TOKEN_COMMENT {
+        tokens.push(SEPARATOR);
+    }
+    tokens.push(token);
+
+    token
+}
+
+fn match_keyword(word: &str) -> Option<u16> {
+    // functions are returned as 0x100 + code
+    for (index, keyword) in KEYWORDS.iter().enumerate() {
+        if !keyword.is_empty() && keyword.eq_ignore_ascii_case(word) && keyword.starts_with(|c: char| c.is_ascii_alphabetic()) {
+            return Some(0x80 + index as u16);
+        }
+    }
+
+    FUNCTIONS
+        .iter()
+        .find(|(_, name)| name.eq_ignore_ascii_case(word))
+        .map(|(code, _)| 0x100 + *code as u16)
+}
+
+fn match_operator(text: &str) -> Option<u8> {
+    let operators = [
+        (">=", 0xf0), ("<>", 0xf2), ("<=", 0xf3), (">", 0xee), ("=", 0xef), ("<", 0xf1),
+        ("+", 0xf4), ("-", 0xf5), ("*", 0xf6), ("/", 0xf7), ("^", 0xf8), ("\\", 0xf9),
+    ];
+
+    operators
+        .iter()
+        .find(|(operator, _)| text.starts_with(operator))
+        .map(|(_, token)| *token)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_from(ram: &[u8]) -> Vec<u8> {
+        read_program_from_memory(|address| ram[address])
+    }
+
+    #[test]
+    fn reads_lines_up_to_the_end_marker() {
+        let mut ram = vec![0; 0x10000];
+        let program = [0x09, 0x00, 0x0a, 0x00, 0xbf, 0x20, 0x19, 0x00, 0x00, 0x00, 0x00];
+        ram[PROGRAM_START..(PROGRAM_START + program.len())].copy_from_slice(&program);
+
+        let read = read_from(&ram);
+        assert_eq!(read, program);
+        assert_eq!(detokenize(&read), "10 PRINT 0\n");
+    }
+
+    #[test]
+    fn stops_at_lines_without_a_header() {
+        let mut ram = vec![0; 0x10000];
+        ram[PROGRAM_START] = 0x03;
+        assert_eq!(read_from(&ram), [0x00, 0x00]);
+    }
+
+    #[test]
+    fn stays_within_memory_filled_with_0xff() {
+        let ram = vec![0xff; 0x10000];
+        detokenize(&read_from(&ram));
+
+        // a line that ends exactly at the top of memory is the last one
+        let mut ram = vec![0xff; 0x10000];
+        let length = 0x10000 - PROGRAM_START;
+        ram[PROGRAM_START..(PROGRAM_START + 2)].copy_from_slice(&(length as u16).to_le_bytes());
+        assert_eq!(read_from(&ram).len(), length + 2);
+    }
+
+    #[test]
+    fn stays_within_random_memory() {
+        // xorshift64, so every run checks the same contents
+        let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+        for _ in 0..64 {
+            let ram: Vec<u8> = (0..0x10000)
+                .map(|_| {
+                    state ^= state << 13;
+                    state ^= state >> 7;
+                    state ^= state << 17;
+                    state as u8
+                })
+                .collect();
+            detokenize(&read_from(&ram));
+        }
+    }
+}
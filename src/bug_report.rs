@@ -0,0 +1,107 @@
+use std::convert::TryInto;
+
+use crate::media;
+use crate::system::System;
+
+// everything needed to reproduce a problem, bundled into a single zip file:
+//
+// config.txt  machine configuration
+// state.sna   snapshot of the machine at the time of the report
+// input.txt   recent key changes, in the format of replay files
+// trace.txt   addresses of the recently executed instructions
+pub fn write_bug_report(system: &dyn System, filename: &str) -> std::io::Result<()> {
+    let config = system.get_config();
+    let mut config_text = String::from("# ronald bug report\n");
+    config_text.push_str(&format!("ronald_version = {}\n", env!("CARGO_PKG_VERSION")));
+    config_text.push_str(&format!("deterministic = {}\n", if config.deterministic { "on" } else { "off" }));
+    config_text.push_str(&format!("seed = {}\n", config.seed)); // only reproducible in deterministic mode
+    config_text.push_str(&format!("ram_pattern = {}\n", config.ram_pattern.name()));
+    config_text.push_str(&format!("power_on_random = {}\n", config.power_on.names()));
+    config_text.push_str(&format!("language = {}\n", config.language.name()));
+    config_text.push_str(&format!("firmware = {}\n", config.firmware.name()));
+    config_text.push_str(&format!("expansions = {}\n", system.list_expansions().join(", ")));
+    config_text.push_str(&format!("emulated_microseconds = {}\n", system.get_sync_statistics().emulated_microseconds));
+
+    let mut input_text = String::new();
+    for event in system.get_keyboard().borrow().read_recent_events() {
+        input_text.push_str(&format!(
+            "key = {} {} {} {}\n",
+            event.timestamp,
+            event.line,
+            event.bit,
+            if event.pressed { "down" } else { "up" }
+        ));
+    }
+
+    let mut trace_text = String::new();
+    for (timestamp, program_counter) in system.read_recent_trace() {
+        trace_text.push_str(&format!("{} {:04x}\n", timestamp, program_counter));
+    }
+
+    let files = [
+        ("config.txt", config_text.into_bytes()),
+        ("state.sna", system.save_snapshot().to_bytes()),
+        ("input.txt", input_text.into_bytes()),
+        ("trace.txt", trace_text.into_bytes()),
+    ];
+    write_zip(filename, &files)
+}
+
+fn write_zip(filename: &str, files: &[(&str, Vec<u8>)]) -> std::io::Result<()> {
+    // files are stored without compression, which every unzip tool can read
+    let mut contents = Vec::new();
+    let mut directory = Vec::new();
+    for (name, data) in files {
+        let offset = to_u32(contents.len())?;
+        let size = to_u32(data.len())?;
+        let crc32 = media::crc32(data);
+        let fields = [
+            &20u16.to_le_bytes()[..], // version needed to extract
+            &0u16.to_le_bytes(),      // flags
+            &0u16.to_le_bytes(),      // stored
+            &0u16.to_le_bytes(),      // modification time
+            &0x21u16.to_le_bytes(),   // modification date, 1980-01-01
+            &crc32.to_le_bytes(),
+            &size.to_le_bytes(), // compressed size
+            &size.to_le_bytes(),
+            &(name.len() as u16).to_le_bytes(),
+            &0u16.to_le_bytes(), // extra field length
+        ]
+        .concat();
+
+        contents.extend_from_slice(&0x0403_4b50u32.to_le_bytes());
+        contents.extend_from_slice(&fields);
+        contents.extend_from_slice(name.as_bytes());
+        contents.extend_from_slice(data);
+
+        directory.extend_from_slice(&0x0201_4b50u32.to_le_bytes());
+        directory.extend_from_slice(&20u16.to_le_bytes()); // version made by
+        directory.extend_from_slice(&fields);
+        directory.extend_from_slice(&[0; 6]); // comment length, disk number, internal attributes
+        directory.extend_from_slice(&0u32.to_le_bytes()); // external attributes
+        directory.extend_from_slice(&offset.to_le_bytes());
+        directory.extend_from_slice(name.as_bytes());
+    }
+
+    let directory_offset = to_u32(contents.len())?;
+    let directory_size = to_u32(directory.len())?;
+    let count: u16 = files.len().try_into().map_err(|_| invalid_data("Too many files."))?;
+    contents.extend_from_slice(&directory);
+    contents.extend_from_slice(&0x0605_4b50u32.to_le_bytes());
+    contents.extend_from_slice(&[0; 4]); // disk numbers
+    contents.extend_from_slice(&count.to_le_bytes());
+    contents.extend_from_slice(&count.to_le_bytes());
+    contents.extend_from_slice(&directory_size.to_le_bytes());
+    contents.extend_from_slice(&directory_offset.to_le_bytes());
+    contents.extend_from_slice(&0u16.to_le_bytes()); // comment length
+
+    std::fs::write(filename, contents)
+}
+
+fn to_u32(size: usize) -> std::io::Result<u32> {
+    size.try_into().map_err(|_| invalid_data("Bug report exceeds the zip size limit."))
+}
+
+fn invalid_data(message: &str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message)
+}
@@ -1,15 +1,134 @@
 use crate::crtc;
+use crate::expansion;
 use crate::fdc;
 use crate::gate_array;
 use crate::memory;
 use crate::ppi;
 
 use std::cell::RefCell;
+use std::fmt;
+use std::io::Write;
 use std::rc::Rc;
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SyncSignal {
+    Horizontal,
+    Vertical,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Device {
+    GateArray,
+    Crtc,
+    RomSelect,
+    Printer,
+    Ppi,
+    Fdc,
+    PeripheralReset,
+    Expansion(&'static str),
+    Unknown,
+}
+
+impl fmt::Display for Device {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Device::GateArray => "gate array",
+            Device::Crtc => "CRTC",
+            Device::RomSelect => "ROM select",
+            Device::Printer => "printer",
+            Device::Ppi => "PPI",
+            Device::Fdc => "FDC",
+            Device::PeripheralReset => "peripheral reset",
+            Device::Expansion(name) => name,
+            Device::Unknown => "unknown",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// the devices a port reaches, kept on the stack as every IN and OUT decodes its port
+struct SelectedDevices {
+    devices: [Device; 7], // at most all devices a write can reach
+    count: usize,
+}
+
+impl SelectedDevices {
+    fn as_slice(&self) -> &[Device] {
+        &self.devices[..self.count]
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct IoAccess {
+    pub program_counter: Option<u16>, // address of the IN/OUT instruction
+    pub port: u16,
+    pub value: u8,
+    pub write: bool,
+    pub device: Device,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Intercept {
+    Pass,      // the device handles the access as usual
+    Reply(u8), // a read returns this value without reaching the device
+    Swallow,   // a write does not reach the device
+}
+
+// sees every I/O access before the devices do: the port, the value of writes (None for reads) and
+// the device the port decodes to, which is Unknown for ports no device answers. Ports that select
+// several devices are seen once per device. Meant for trying out models of new expansion hardware
+// without changing the emulator
+pub type IoSnooper = Box<dyn FnMut(u16, Option<u8>, Device) -> Intercept>;
+
+// writes an I/O trace to a CSV file while it is recorded, so long runs do not keep it in memory
+pub struct IoTraceWriter {
+    file: std::io::BufWriter<std::fs::File>,
+}
+
+impl IoTraceWriter {
+    pub fn create(filename: &str) -> std::io::Result<IoTraceWriter> {
+        let mut file = std::io::BufWriter::new(std::fs::File::create(filename)?);
+        writeln!(file, "pc,direction,port,value,device")?;
+
+        Ok(IoTraceWriter { file })
+    }
+
+    pub fn write(&mut self, trace: &[IoAccess]) -> std::io::Result<()> {
+        for access in trace {
+            let program_counter = match access.program_counter {
+                Some(program_counter) => format!("{:#06x}", program_counter),
+                None => String::new(),
+            };
+            writeln!(
+                self.file,
+                "{},{},{:#06x},{:#04x},{}",
+                program_counter,
+                if access.write { "out" } else { "in" },
+                access.port,
+                access.value,
+                access.device
+            )?;
+        }
+
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> std::io::Result<()> {
+        self.file.flush()
+    }
+}
+
 pub trait Bus {
     fn read_byte(&self, port: u16) -> u8;
     fn write_byte(&mut self, port: u16, value: u8);
+
+    fn count_sync_signals(&self, _signal: SyncSignal) -> u64 {
+        0 // no video hardware
+    }
+
+    fn read_raster_position(&self) -> (u16, u8) {
+        (0, 0) // no video hardware
+    }
 }
 
 pub struct DummyBus {}
@@ -20,6 +139,12 @@ impl DummyBus {
     }
 }
 
+impl Default for DummyBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Bus for DummyBus {
     fn read_byte(&self, _port: u16) -> u8 {
         unimplemented!()
@@ -32,12 +157,29 @@ impl Bus for DummyBus {
 
 pub type StandardBusShared = Rc<RefCell<StandardBus>>;
 
+#[derive(Clone)]
+pub struct SyncState {
+    horizontal_sync: bool,
+    vertical_sync: bool,
+    horizontal_sync_count: u64,
+    vertical_sync_count: u64,
+    printer_data: u8,
+}
+
 pub struct StandardBus {
     crtc: crtc::CRTControllerShared,
     fdc: fdc::FloppyDiskControllerShared,
     gate_array: gate_array::GateArrayShared,
     memory: memory::MemoryShared,
     ppi: ppi::PeripheralInterfaceShared,
+    expansions: Vec<expansion::ExpansionDeviceShared>,
+    horizontal_sync: bool,
+    vertical_sync: bool,
+    horizontal_sync_count: u64,
+    vertical_sync_count: u64,
+    io_trace: RefCell<Option<Vec<IoAccess>>>, // reads are traced through a shared reference
+    io_snooper: RefCell<Option<IoSnooper>>,
+    printer_data: u8,
 }
 
 impl StandardBus {
@@ -54,6 +196,14 @@ impl StandardBus {
             gate_array,
             memory,
             ppi,
+            expansions: Vec::new(),
+            horizontal_sync: false,
+            vertical_sync: false,
+            horizontal_sync_count: 0,
+            vertical_sync_count: 0,
+            io_trace: RefCell::new(None),
+            io_snooper: RefCell::new(None),
+            printer_data: 0,
         };
 
         Rc::new(RefCell::new(bus))
@@ -61,40 +211,233 @@ impl StandardBus {
 
     pub fn step(&mut self) -> bool {
         self.crtc.borrow_mut().step();
+
+        // count the start of each sync signal, so execution can be stopped at video timing boundaries
+        let crtc = self.crtc.borrow();
+        let horizontal_sync = crtc.read_horizontal_sync();
+        let vertical_sync = crtc.read_vertical_sync();
+        drop(crtc);
+
+        if horizontal_sync && !self.horizontal_sync {
+            self.horizontal_sync_count += 1;
+        }
+        if vertical_sync && !self.vertical_sync {
+            self.vertical_sync_count += 1;
+        }
+        self.horizontal_sync = horizontal_sync;
+        self.vertical_sync = vertical_sync;
+
         self.gate_array.borrow_mut().step()
     }
 
     pub fn acknowledge_interrupt(&mut self) {
         self.gate_array.borrow_mut().acknowledge_interrupt();
     }
+
+    pub fn save_sync_state(&self) -> SyncState {
+        SyncState {
+            horizontal_sync: self.horizontal_sync,
+            vertical_sync: self.vertical_sync,
+            horizontal_sync_count: self.horizontal_sync_count,
+            vertical_sync_count: self.vertical_sync_count,
+            printer_data: self.printer_data,
+        }
+    }
+
+    pub fn load_sync_state(&mut self, state: &SyncState) {
+        self.horizontal_sync = state.horizontal_sync;
+        self.vertical_sync = state.vertical_sync;
+        self.horizontal_sync_count = state.horizontal_sync_count;
+        self.vertical_sync_count = state.vertical_sync_count;
+        self.printer_data = state.printer_data;
+    }
+
+    pub fn attach_expansion(&mut self, device: expansion::ExpansionDeviceShared) {
+        self.expansions.push(device);
+    }
+
+    pub fn detach_expansion(&mut self, name: &str) -> Option<expansion::ExpansionDeviceShared> {
+        let index = self.expansions.iter().position(|device| device.borrow().name() == name)?;
+
+        Some(self.expansions.remove(index))
+    }
+
+    pub fn read_expansion_names(&self) -> Vec<&'static str> {
+        self.expansions.iter().map(|device| device.borrow().name()).collect()
+    }
+
+    pub fn step_expansions(&mut self, microseconds: u8) {
+        for device in &self.expansions {
+            device.borrow_mut().step(microseconds);
+        }
+    }
+
+    pub fn read_expansion_audio_level(&self) -> f32 {
+        self.expansions
+            .iter()
+            .filter_map(|device| device.borrow().read_audio_level())
+            .sum::<f32>()
+            .min(1.0)
+    }
+
+    pub fn read_printer_data(&self) -> u8 {
+        self.printer_data
+    }
+
+    pub fn enable_io_trace(&mut self, enable: bool) {
+        *self.io_trace.borrow_mut() = if enable { Some(Vec::new()) } else { None };
+    }
+
+    pub fn take_io_trace(&mut self) -> Vec<IoAccess> {
+        match self.io_trace.borrow_mut().as_mut() {
+            Some(trace) => std::mem::take(trace),
+            None => Vec::new(),
+        }
+    }
+
+    pub fn tag_io_trace(&self, program_counter: u16) {
+        // accesses are recorded while the instruction is still executing, so the CPU tags them afterwards
+        if let Some(trace) = self.io_trace.borrow_mut().as_mut() {
+            for access in trace.iter_mut().rev() {
+                if access.program_counter.is_some() {
+                    break;
+                }
+                access.program_counter = Some(program_counter);
+            }
+        }
+    }
+
+    pub fn set_io_snooper(&mut self, snooper: Option<IoSnooper>) -> Option<IoSnooper> {
+        // returns the snooper that was set before
+        self.io_snooper.replace(snooper)
+    }
+
+    fn snoop_io(&self, port: u16, value: Option<u8>, device: Device) -> Intercept {
+        match self.io_snooper.borrow_mut().as_mut() {
+            Some(snooper) => snooper(port, value, device),
+            None => Intercept::Pass,
+        }
+    }
+
+    fn decode_read(&self, port: u16) -> SelectedDevices {
+        // the gate array, ROM select and printer port cannot be read
+        let selected = [
+            (port & 0x4000 == 0, Device::Crtc),
+            (port & 0x0800 == 0, Device::Ppi),
+            (port & 0x0480 == 0, Device::Fdc),
+        ];
+        self.select_devices(port, &selected)
+    }
+
+    fn decode_write(&self, port: u16) -> SelectedDevices {
+        let selected = [
+            (port & 0xc000 == 0x4000, Device::GateArray),
+            (port & 0x4000 == 0, Device::Crtc),
+            (port & 0x2000 == 0, Device::RomSelect),
+            (port & 0x1000 == 0, Device::Printer),
+            (port & 0x0800 == 0, Device::Ppi),
+            (port & 0x0480 == 0, Device::Fdc),
+            (port == 0xf8ff, Device::PeripheralReset), // a convention of expansions, not decoded by the CPC
+        ];
+        self.select_devices(port, &selected)
+    }
+
+    fn select_devices(&self, port: u16, selected: &[(bool, Device)]) -> SelectedDevices {
+        // every device only looks at its own address lines, e.g. the CRTC at A14 being low, so any
+        // port with that line low reaches it and a port with several lines low reaches several devices
+        let mut devices = SelectedDevices {
+            devices: [Device::Unknown; 7],
+            count: 0,
+        };
+        for (_, device) in selected.iter().filter(|(selected, _)| *selected) {
+            devices.devices[devices.count] = *device;
+            devices.count += 1;
+        }
+        if devices.count == 0 {
+            devices.devices[0] = self.decode_expansion(port);
+            devices.count = 1;
+        }
+
+        devices
+    }
+
+    fn decode_expansion(&self, port: u16) -> Device {
+        // built-in devices take precedence, so expansions only see ports that are otherwise unused
+        match self.find_expansion(port) {
+            Some(device) => Device::Expansion(device.borrow().name()),
+            None => Device::Unknown,
+        }
+    }
+
+    fn find_expansion(&self, port: u16) -> Option<&expansion::ExpansionDeviceShared> {
+        self.expansions.iter().find(|device| device.borrow().decodes_port(port))
+    }
+
+    fn trace_io(&self, port: u16, value: u8, write: bool, device: Device) {
+        if let Some(trace) = self.io_trace.borrow_mut().as_mut() {
+            trace.push(IoAccess {
+                program_counter: None,
+                port,
+                value,
+                write,
+                device,
+            });
+        }
+    }
 }
 
 impl Bus for StandardBus {
     fn read_byte(&self, port: u16) -> u8 {
-        match port {
-            _ if port & 0x4000 == 0 => self.crtc.borrow().read_byte(port),
-            _ if port & 0x0800 == 0 => self.ppi.borrow().read_byte(port),
-            0xfb7e | 0xfb7f => self.fdc.borrow().read_byte(port),
-            _ => unimplemented!(),
+        // devices selected together drive the data bus together, a bit any of them pulls low reads as 0
+        let mut value = 0xff;
+        for &device in self.decode_read(port).as_slice() {
+            let device_value = match self.snoop_io(port, None, device) {
+                Intercept::Reply(value) => value,
+                _ => match device {
+                    Device::Crtc => self.crtc.borrow().read_byte(port),
+                    Device::Ppi => self.ppi.borrow().read_byte(port),
+                    Device::Fdc => self.fdc.borrow_mut().read_byte(port),
+                    Device::Expansion(_) => self.find_expansion(port).unwrap().borrow_mut().read_byte(port),
+                    _ => 0xff, // nothing drives the data bus
+                },
+            };
+            self.trace_io(port, device_value, false, device);
+            value &= device_value;
         }
+
+        value
     }
 
     fn write_byte(&mut self, port: u16, value: u8) {
         // TODO: do we need "value" or is it always the lower half of "port"?
-        match port {
-            _ if port & 0x8000 == 0 && port & 0x4000 != 0 => {
-                self.gate_array.borrow_mut().write_byte(port, value)
+        for &device in self.decode_write(port).as_slice() {
+            self.trace_io(port, value, true, device);
+            if self.snoop_io(port, Some(value), device) != Intercept::Pass {
+                continue;
             }
-            _ if port & 0x4000 == 0 => self.crtc.borrow_mut().write_byte(port, value),
-            _ if port & 0xdf00 == 0xdf00 => self.memory.borrow_mut().select_upper_rom(value),
-            _ if port & 0xef00 == 0xef00 => (), // printer port (unsupported)
-            _ if port & 0x0800 == 0 => self.ppi.borrow_mut().write_byte(port, value),
-            0xfa7e | 0xfb7f => self.fdc.borrow_mut().write_byte(port, value),
-            0xf8ff => (), // peripheral soft reset (ignored)
-            _ => {
-                println!("BUS {:#06x} {:#010b}", port, value);
-                unimplemented!();
+
+            match device {
+                Device::GateArray => self.gate_array.borrow_mut().write_byte(port, value),
+                Device::Crtc => self.crtc.borrow_mut().write_byte(port, value),
+                Device::RomSelect => self.memory.borrow_mut().select_upper_rom(value),
+                Device::Printer => self.printer_data = value, // only latched, e.g. for a Digiblaster
+                Device::Ppi => self.ppi.borrow_mut().write_byte(port, value),
+                Device::Fdc => self.fdc.borrow_mut().write_byte(port, value),
+                Device::PeripheralReset => (), // ignored
+                Device::Expansion(_) => self.find_expansion(port).unwrap().borrow_mut().write_byte(port, value),
+                Device::Unknown => log::warn!("Write to unused port {:#06x}: {:#010b}", port, value),
             }
         }
     }
+
+    fn count_sync_signals(&self, signal: SyncSignal) -> u64 {
+        match signal {
+            SyncSignal::Horizontal => self.horizontal_sync_count,
+            SyncSignal::Vertical => self.vertical_sync_count,
+        }
+    }
+
+    fn read_raster_position(&self) -> (u16, u8) {
+        self.crtc.borrow().read_raster_position()
+    }
 }
@@ -0,0 +1,78 @@
+// little endian values in file formats, read and written byte by byte so neither the byte order
+// nor the pointer width of the host matter. Reads return None where the value would run past the
+// end of the data
+pub fn read_word(bytes: &[u8], offset: usize) -> Option<u16> {
+    let bytes = bytes.get(offset..offset.checked_add(2)?)?;
+
+    Some(u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+pub fn read_triple(bytes: &[u8], offset: usize) -> Option<u32> {
+    // e.g. lengths in AMSDOS headers and tape blocks
+    let bytes = bytes.get(offset..offset.checked_add(3)?)?;
+
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], 0]))
+}
+
+pub fn read_dword(bytes: &[u8], offset: usize) -> Option<u32> {
+    let bytes = bytes.get(offset..offset.checked_add(4)?)?;
+
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+pub fn write_word(bytes: &mut [u8], offset: usize, value: u16) {
+    bytes[offset..(offset + 2)].copy_from_slice(&value.to_le_bytes());
+}
+
+pub fn write_dword(bytes: &mut [u8], offset: usize, value: u32) {
+    bytes[offset..(offset + 4)].copy_from_slice(&value.to_le_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BYTES: [u8; 5] = [0x01, 0x23, 0x45, 0x67, 0x89];
+
+    #[test]
+    fn reads_little_endian() {
+        assert_eq!(read_word(&BYTES, 0), Some(0x2301));
+        assert_eq!(read_word(&BYTES, 3), Some(0x8967));
+        assert_eq!(read_dword(&BYTES, 0), Some(0x6745_2301));
+        assert_eq!(read_dword(&BYTES, 1), Some(0x8967_4523));
+    }
+
+    #[test]
+    fn reads_24_bit_values() {
+        assert_eq!(read_triple(&BYTES, 0), Some(0x45_2301));
+        assert_eq!(read_triple(&BYTES, 2), Some(0x89_6745));
+        assert_eq!(read_triple(&[0xff, 0xff, 0xff, 0xff], 0), Some(0xff_ffff));
+    }
+
+    #[test]
+    fn returns_none_past_the_end() {
+        assert_eq!(read_word(&BYTES, 4), None);
+        assert_eq!(read_word(&BYTES, 5), None);
+        assert_eq!(read_triple(&BYTES, 3), None);
+        assert_eq!(read_dword(&BYTES, 2), None);
+        assert_eq!(read_dword(&[], 0), None);
+    }
+
+    #[test]
+    fn does_not_wrap_offsets_near_the_end_of_the_address_space() {
+        for offset in [usize::MAX, usize::MAX - 1, usize::MAX - 2, usize::MAX - 3] {
+            assert_eq!(read_word(&BYTES, offset), None);
+            assert_eq!(read_triple(&BYTES, offset), None);
+            assert_eq!(read_dword(&BYTES, offset), None);
+        }
+    }
+
+    #[test]
+    fn writes_little_endian() {
+        let mut bytes = [0; 6];
+        write_word(&mut bytes, 0, 0x2301);
+        write_dword(&mut bytes, 2, 0x8967_4523);
+        assert_eq!(bytes, [0x01, 0x23, 0x23, 0x45, 0x67, 0x89]);
+        assert_eq!(read_dword(&bytes, 2), Some(0x8967_4523));
+    }
+}
@@ -0,0 +1,333 @@
+use crate::amsdos;
+use crate::bytes;
+use crate::media;
+
+pub const TSTATES_PER_SECOND: u32 = 3_500_000; // TZX timings refer to a ZX Spectrum clock
+const MAX_PULSES: usize = 32_000_000; // hours of tape, a few bytes of tone blocks could ask for far more
+
+// how the firmware saves at its default 1000 baud: a zero bit is two pulses of 333us, a one bit two
+// of 667us, and records start with 2048 one bits and a zero bit
+const ZERO_PULSE: u32 = TSTATES_PER_SECOND / 3000;
+const ONE_PULSE: u32 = 2 * ZERO_PULSE;
+const PILOT_PULSES: u32 = 4096;
+const HEADER_SYNC_BYTE: u8 = 0x2c;
+const DATA_SYNC_BYTE: u8 = 0x16;
+const HEADER_SIZE: usize = 64;
+const SEGMENT_SIZE: usize = 256;
+const BLOCK_SIZE: usize = 2048; // files are saved in blocks, each a header and a data record
+const TRAILER: [u8; 4] = [0xff; 4];
+const MAX_NAME_LENGTH: usize = 16;
+const HEADER_PAUSE: u32 = 15; // in milliseconds, between the header and the data record
+const DATA_PAUSE: u32 = 1000;
+
+// a span of constant signal level, in TZX T-states
+#[derive(Clone, Copy, Debug)]
+pub struct Pulse {
+    pub level: bool,
+    pub duration: u32,
+}
+
+// the bytes of a data block, kept for fast loading
+#[derive(Clone, Debug)]
+pub struct DataBlock {
+    pub first_pulse: usize,
+    pub end_pulse: usize,
+    pub data: Vec<u8>,
+}
+
+pub struct Tape {
+    pulses: Vec<Pulse>,
+    data_blocks: Vec<DataBlock>,
+    content_hash: u32,
+}
+
+struct DataTiming {
+    zero: u32,
+    one: u32,
+    used_bits: u8,
+    pause: u32, // in milliseconds
+}
+
+impl Tape {
+    pub fn load(filename: &str) -> std::io::Result<Tape> {
+        Tape::from_bytes(&std::fs::read(filename)?)
+    }
+
+    pub fn from_bytes(contents: &[u8]) -> std::io::Result<Tape> {
+        Tape::from_bytes_with_progress(contents, &mut |_| ())
+    }
+
+    pub fn from_bytes_with_progress(contents: &[u8], progress: &mut dyn FnMut(f32)) -> std::io::Result<Tape> {
+        // progress is reported after each block, between 0 and 1
+        if contents.len() < 10 || &contents[0..8] != b"ZXTape!\x1a" {
+            return Err(invalid_data("Could not find the expected file header."));
+        }
+
+        let mut tape = Tape {
+            pulses: Vec::new(),
+            data_blocks: Vec::new(),
+            content_hash: 0,
+        };
+        let mut level = false;
+        let mut hashed = Vec::new();
+        let mut position = 10;
+        while position < contents.len() {
+            let id = contents[position];
+            let block = &contents[(position + 1)..];
+            let length = match id {
+                0x10 => {
+                    // standard speed data, as used by the ZX Spectrum ROM
+                    let pause = read_word(block, 0x00)?;
+                    let data = read_bytes(block, 0x04, read_word(block, 0x02)? as usize)?;
+                    let pilot_pulses = if matches!(data.first(), Some(flag) if *flag < 0x80) { 8063 } else { 3223 };
+                    tape.add_tone(&mut level, 2168, pilot_pulses);
+                    tape.add_pulses(&mut level, &[667, 735]);
+                    tape.add_data(&mut level, data, &DataTiming { zero: 855, one: 1710, used_bits: 8, pause });
+                    hashed.extend_from_slice(data);
+                    0x04 + data.len()
+                }
+                0x11 => {
+                    // turbo speed data, which is what CPC tapes consist of
+                    let data = read_bytes(block, 0x12, read_triple(block, 0x0f)?)?;
+                    tape.add_tone(&mut level, read_word(block, 0x00)?, read_word(block, 0x0a)?);
+                    tape.add_pulses(&mut level, &[read_word(block, 0x02)?, read_word(block, 0x04)?]);
+                    let timing = DataTiming {
+                        zero: read_word(block, 0x06)?,
+                        one: read_word(block, 0x08)?,
+                        used_bits: read_bytes(block, 0x0c, 1)?[0],
+                        pause: read_word(block, 0x0d)?,
+                    };
+                    tape.add_data(&mut level, data, &timing);
+                    hashed.extend_from_slice(data);
+                    0x12 + data.len()
+                }
+                0x12 => {
+                    tape.add_tone(&mut level, read_word(block, 0x00)?, read_word(block, 0x02)?);
+                    0x04
+                }
+                0x13 => {
+                    let count = read_bytes(block, 0x00, 1)?[0] as usize;
+                    let pulses = (0..count).map(|index| read_word(block, 1 + 2 * index)).collect::<std::io::Result<Vec<u32>>>()?;
+                    tape.add_pulses(&mut level, &pulses);
+                    1 + 2 * count
+                }
+                0x14 => {
+                    let data = read_bytes(block, 0x0a, read_triple(block, 0x07)?)?;
+                    let timing = DataTiming {
+                        zero: read_word(block, 0x00)?,
+                        one: read_word(block, 0x02)?,
+                        used_bits: read_bytes(block, 0x04, 1)?[0],
+                        pause: read_word(block, 0x05)?,
+                    };
+                    tape.add_data(&mut level, data, &timing);
+                    hashed.extend_from_slice(data);
+                    0x0a + data.len()
+                }
+                0x20 => {
+                    // a pause of 0 means "stop the tape", which we treat like a short pause
+                    tape.add_pause(&mut level, read_word(block, 0x00)?.max(1));
+                    0x02
+                }
+                0x21 | 0x30 => 1 + read_bytes(block, 0x00, 1)?[0] as usize, // group start, text description
+                0x22 => 0,                                                  // group end
+                0x32 => 2 + read_word(block, 0x00)? as usize,               // archive info
+                0x33 => 1 + 3 * read_bytes(block, 0x00, 1)?[0] as usize,   // hardware type
+                0x35 => 0x14 + read_dword(block, 0x10)?,                    // custom info
+                0x5a => 0x09,                                               // glued files
+                _ => return Err(invalid_data(&format!("Unsupported block type {:#04x}.", id))),
+            };
+
+            if tape.pulses.len() > MAX_PULSES {
+                return Err(invalid_data("The tape is too long."));
+            }
+
+            position += 1 + length;
+            progress(position.min(contents.len()) as f32 / contents.len() as f32);
+        }
+
+        // like disks, tapes are identified by their data rather than by timings or descriptions
+        tape.content_hash = media::crc32(&hashed);
+
+        Ok(tape)
+    }
+
+    pub fn read_pulses(&self) -> &[Pulse] {
+        &self.pulses
+    }
+
+    pub fn read_data_blocks(&self) -> &[DataBlock] {
+        &self.data_blocks
+    }
+
+    pub fn content_hash(&self) -> u32 {
+        self.content_hash
+    }
+
+    fn add_pulses(&mut self, level: &mut bool, durations: &[u32]) {
+        // every pulse inverts the signal level
+        for duration in durations {
+            *level = !*level;
+            self.pulses.push(Pulse {
+                level: *level,
+                duration: *duration,
+            });
+        }
+    }
+
+    fn add_tone(&mut self, level: &mut bool, duration: u32, count: u32) {
+        for _ in 0..count {
+            self.add_pulses(level, &[duration]);
+        }
+    }
+
+    fn add_data(&mut self, level: &mut bool, data: &[u8], timing: &DataTiming) {
+        let first_pulse = self.pulses.len();
+        for (index, byte) in data.iter().enumerate() {
+            let bits = if index == data.len() - 1 { timing.used_bits.clamp(1, 8) } else { 8 };
+            for bit in 0..bits {
+                let duration = if byte & (0x80 >> bit) != 0 { timing.one } else { timing.zero };
+                self.add_pulses(level, &[duration, duration]);
+            }
+        }
+        self.data_blocks.push(DataBlock {
+            first_pulse,
+            end_pulse: self.pulses.len(),
+            data: data.to_vec(),
+        });
+
+        if timing.pause > 0 {
+            self.add_pause(level, timing.pause);
+        }
+    }
+
+    fn add_pause(&mut self, level: &mut bool, milliseconds: u32) {
+        *level = false;
+        self.pulses.push(Pulse {
+            level: false,
+            duration: milliseconds * (TSTATES_PER_SECOND / 1000),
+        });
+    }
+}
+
+// writes files to a CDT like the firmware's SAVE writes them to tape, so programs built on the host
+// load with RUN" or LOAD"
+pub struct TapeBuilder {
+    contents: Vec<u8>,
+}
+
+impl TapeBuilder {
+    pub fn new() -> TapeBuilder {
+        let mut contents = b"ZXTape!\x1a".to_vec();
+        contents.extend_from_slice(&[1, 20]); // TZX version 1.20
+
+        TapeBuilder { contents }
+    }
+
+    pub fn add_file(&mut self, file: &amsdos::File) -> std::io::Result<()> {
+        // tape files always have a header, files without a type are saved as binaries
+        let name = file.name.to_uppercase();
+        if name.is_empty() || name.len() > MAX_NAME_LENGTH || !name.chars().all(|c| c.is_ascii_graphic() || c == ' ') {
+            let message = format!("\"{}\" is not a valid tape file name, it needs 1 to {} characters.", name, MAX_NAME_LENGTH);
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, message));
+        }
+        if file.data.is_empty() || file.data.len() > 0xffff {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "Tape files hold 1 to 65535 bytes."));
+        }
+
+        let num_blocks = (file.data.len() - 1) / BLOCK_SIZE + 1;
+        for (index, data) in file.data.chunks(BLOCK_SIZE).enumerate() {
+            let mut header = vec![0; HEADER_SIZE];
+            header[..name.len()].copy_from_slice(name.as_bytes());
+            header[16] = index as u8 + 1;
+            header[17] = if index + 1 == num_blocks { 0xff } else { 0 };
+            header[18] = file.file_type.unwrap_or(amsdos::FILE_TYPE_BINARY);
+            bytes::write_word(&mut header, 19, data.len() as u16);
+            bytes::write_word(&mut header, 21, file.load_address.wrapping_add((index * BLOCK_SIZE) as u16));
+            header[23] = if index == 0 { 0xff } else { 0 };
+            bytes::write_word(&mut header, 24, file.data.len() as u16);
+            bytes::write_word(&mut header, 26, file.entry_address);
+
+            self.add_record(HEADER_SYNC_BYTE, &header, HEADER_PAUSE);
+            self.add_record(DATA_SYNC_BYTE, data, DATA_PAUSE);
+        }
+
+        Ok(())
+    }
+
+    pub fn to_bytes(&self) -> &[u8] {
+        &self.contents
+    }
+
+    pub fn save(&self, filename: &str) -> std::io::Result<()> {
+        std::fs::write(filename, &self.contents)
+    }
+
+    fn add_record(&mut self, sync_byte: u8, data: &[u8], pause: u32) {
+        // a record is the sync byte, 256 byte segments padded with zeros that end with their CRC,
+        // and a trailer, stored in a turbo speed data block
+        let mut record = vec![sync_byte];
+        for segment in data.chunks(SEGMENT_SIZE) {
+            let mut segment = segment.to_vec();
+            segment.resize(SEGMENT_SIZE, 0);
+            record.extend_from_slice(&segment);
+            record.extend_from_slice(&segment_crc(&segment).to_be_bytes());
+        }
+        record.extend_from_slice(&TRAILER);
+
+        self.contents.push(0x11);
+        for duration in [ONE_PULSE, ZERO_PULSE, ZERO_PULSE, ZERO_PULSE, ONE_PULSE, PILOT_PULSES].iter() {
+            self.contents.extend_from_slice(&(*duration as u16).to_le_bytes());
+        }
+        self.contents.push(8); // all bits of the last byte are used
+        self.contents.extend_from_slice(&(pause as u16).to_le_bytes());
+        self.contents.extend_from_slice(&(record.len() as u32).to_le_bytes()[..3]);
+        self.contents.extend_from_slice(&record);
+    }
+}
+
+impl Default for TapeBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn segment_crc(segment: &[u8]) -> u16 {
+    // the firmware's CRC-16 with the CCITT polynomial, stored inverted
+    let mut crc: u16 = 0xffff;
+    for byte in segment {
+        crc ^= (*byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 { (crc << 1) ^ 0x1021 } else { crc << 1 };
+        }
+    }
+
+    !crc
+}
+
+fn read_bytes(block: &[u8], offset: usize, length: usize) -> std::io::Result<&[u8]> {
+    block
+        .get(offset..(offset + length))
+        .ok_or_else(|| invalid_data("Block exceeds the file size."))
+}
+
+fn read_word(block: &[u8], offset: usize) -> std::io::Result<u32> {
+    let word = bytes::read_word(block, offset).ok_or_else(|| invalid_data("Block exceeds the file size."))?;
+
+    Ok(word as u32)
+}
+
+fn read_triple(block: &[u8], offset: usize) -> std::io::Result<usize> {
+    let triple = bytes::read_triple(block, offset).ok_or_else(|| invalid_data("Block exceeds the file size."))?;
+
+    Ok(triple as usize)
+}
+
+fn read_dword(block: &[u8], offset: usize) -> std::io::Result<usize> {
+    let dword = bytes::read_dword(block, offset).ok_or_else(|| invalid_data("Block exceeds the file size."))?;
+
+    Ok(dword as usize)
+}
+
+fn invalid_data(message: &str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message)
+}
@@ -0,0 +1,190 @@
+use crate::memory;
+use crate::system;
+
+pub struct Poke {
+    pub bank: Option<u8>, // 16K RAM block, None if the address refers to the default memory configuration
+    pub address: u16,
+    pub value: Option<u8>, // None if the value has to be entered by the user
+    pub original: u8,
+}
+
+pub struct Trainer {
+    pub name: String,
+    pub pokes: Vec<Poke>,
+}
+
+pub struct CheatManager {
+    trainers: Vec<Trainer>,
+}
+
+impl Default for CheatManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CheatManager {
+    pub fn new() -> CheatManager {
+        CheatManager {
+            trainers: Vec::new(),
+        }
+    }
+
+    pub fn load_pok(&mut self, filename: &str) -> std::io::Result<usize> {
+        // see https://worldofspectrum.org/faq/reference/formats.htm#POK (bank 8 means "no bank")
+        let contents = std::fs::read_to_string(filename)?;
+        let mut trainers = Vec::new();
+        let mut current: Option<Trainer> = None;
+
+        for (line_number, line) in contents.lines().enumerate() {
+            let line = line.trim_end();
+            match line.chars().next() {
+                Some('N') => {
+                    if current.is_some() {
+                        return Err(invalid_data(line_number, "trainer without final poke"));
+                    }
+                    current = Some(Trainer {
+                        name: line[1..].trim().to_string(),
+                        pokes: Vec::new(),
+                    });
+                }
+                Some(kind @ 'M') | Some(kind @ 'Z') => {
+                    let mut trainer = match current.take() {
+                        Some(trainer) => trainer,
+                        None => return Err(invalid_data(line_number, "poke outside of a trainer")),
+                    };
+                    trainer.pokes.push(parse_poke(line_number, &line[1..])?);
+
+                    if kind == 'Z' {
+                        trainers.push(trainer);
+                    } else {
+                        current = Some(trainer);
+                    }
+                }
+                Some('Y') => break,
+                None => (),
+                Some(_) => return Err(invalid_data(line_number, "unknown line type")),
+            }
+        }
+
+        if current.is_some() {
+            return Err(invalid_data(contents.lines().count().saturating_sub(1), "trainer without final poke"));
+        }
+
+        let count = trainers.len();
+        self.trainers.append(&mut trainers);
+
+        Ok(count)
+    }
+
+    pub fn get_trainers(&self) -> &[Trainer] {
+        &self.trainers
+    }
+
+    pub fn apply(&self, index: usize, system: &mut dyn system::System, input: Option<u8>) -> std::io::Result<()> {
+        let trainer = &self.trainers[index];
+        let ram_size = system.get_config().ram_size;
+        for poke in &trainer.pokes {
+            let value = match poke.value.or(input) {
+                Some(value) => value,
+                None => {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidInput,
+                        format!("Trainer \"{}\" needs a value.", trainer.name),
+                    ))
+                }
+            };
+            system.write_ram(physical_address(poke, ram_size)?, value);
+        }
+
+        Ok(())
+    }
+
+    pub fn revert(&self, index: usize, system: &mut dyn system::System) -> std::io::Result<()> {
+        let ram_size = system.get_config().ram_size;
+        for poke in &self.trainers[index].pokes {
+            system.write_ram(physical_address(poke, ram_size)?, poke.original);
+        }
+
+        Ok(())
+    }
+
+    pub fn print_trainers(&self) {
+        for (index, trainer) in self.trainers.iter().enumerate() {
+            println!("{}: {}", index, trainer.name);
+            for poke in &trainer.pokes {
+                let bank = match poke.bank {
+                    Some(bank) => format!(" (bank {})", bank),
+                    None => String::new(),
+                };
+                match poke.value {
+                    Some(value) => println!("    POKE &{:04X},&{:02X}{}", poke.address, value, bank),
+                    None => println!("    POKE &{:04X},?{}", poke.address, bank),
+                }
+            }
+        }
+    }
+}
+
+fn parse_poke(line_number: usize, fields: &str) -> std::io::Result<Poke> {
+    let numbers = fields
+        .split_whitespace()
+        .map(|field| field.parse::<u32>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| invalid_data(line_number, "invalid number"))?;
+
+    if numbers.len() != 4 || numbers[1] > 0xffff || numbers[2] > 256 || numbers[3] > 0xff {
+        return Err(invalid_data(line_number, "expected bank, address, value and original value"));
+    }
+
+    Ok(Poke {
+        bank: if numbers[0] & 0x08 != 0 { None } else { Some(numbers[0] as u8 & 0x07) },
+        address: numbers[1] as u16,
+        value: if numbers[2] == 256 { None } else { Some(numbers[2] as u8) },
+        original: numbers[3] as u8,
+    })
+}
+
+fn physical_address(poke: &Poke, ram_size: memory::RamSize) -> std::io::Result<usize> {
+    match poke.bank {
+        None => Ok(poke.address as usize),
+        Some(bank) if (bank as usize + 1) * 0x4000 <= ram_size.bytes() => {
+            Ok(bank as usize * 0x4000 + (poke.address as usize & 0x3fff))
+        }
+        Some(bank) => Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("RAM bank {} is not in the configured {} of memory.", bank, ram_size.name().to_uppercase()),
+        )),
+    }
+}
+
+fn invalid_data(line_number: usize, message: &str) -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::InvalidData,
+        format!("Line {}: {}", line_number + 1, message),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn poke(bank: Option<u8>, address: u16) -> Poke {
+        Poke { bank, address, value: Some(0), original: 0 }
+    }
+
+    #[test]
+    fn maps_banks_to_physical_ram() {
+        let ram_size = memory::RamSize::Kilobytes128;
+        assert_eq!(physical_address(&poke(None, 0x8123), ram_size).unwrap(), 0x8123);
+        assert_eq!(physical_address(&poke(Some(2), 0x0123), ram_size).unwrap(), 0x8123);
+        assert_eq!(physical_address(&poke(Some(7), 0xc123), ram_size).unwrap(), 0x1c123);
+    }
+
+    #[test]
+    fn rejects_banks_beyond_the_configured_ram() {
+        assert!(physical_address(&poke(Some(3), 0x4000), memory::RamSize::Kilobytes64).is_ok());
+        assert!(physical_address(&poke(Some(4), 0x4000), memory::RamSize::Kilobytes64).is_err());
+        assert!(physical_address(&poke(Some(7), 0x4000), memory::RamSize::Kilobytes320).is_ok());
+    }
+}
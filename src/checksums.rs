@@ -0,0 +1,97 @@
+use crate::dsk_file;
+use crate::media;
+
+const ROM_SIZE: usize = 0x4000;
+const AMSDOS_HEADER_SIZE: usize = 0x80;
+
+// CRC-32s of ROM dumps known to work, by the file name the emulator loads them from
+const KNOWN_ROMS: [(&str, u32, &str); 3] = [
+    ("os_464.rom", 0x8157_52df, "CPC 464 operating system"),
+    ("basic_1.0.rom", 0x7d9a_3bac, "Locomotive BASIC 1.0"),
+    ("amsdos_0.5.rom", 0x1fe2_2ecd, "AMSDOS 0.5"),
+];
+
+pub struct Verification {
+    pub filename: String,
+    pub crc: u32, // of the file, media are identified by media::hash_media instead
+    pub known_as: Option<String>, // the title of media, from a game database
+    pub problems: Vec<String>,
+}
+
+impl Verification {
+    pub fn is_good(&self) -> bool {
+        self.problems.is_empty()
+    }
+
+    pub fn print(&self) {
+        let status = match (self.is_good(), &self.known_as) {
+            (true, Some(name)) => format!("ok, {}", name),
+            (true, None) => "ok, not in the list of known dumps".to_string(),
+            (false, _) => "BAD".to_string(),
+        };
+        println!("{:#010x}  {}: {}", self.crc, self.filename, status);
+        for problem in &self.problems {
+            println!("            {}", problem);
+        }
+    }
+}
+
+pub fn verify_rom(filename: &str) -> std::io::Result<Verification> {
+    // bad ROM dumps rarely fail outright, they crash or misbehave much later, e.g. when BASIC
+    // calls into a corrupted firmware routine
+    let data = std::fs::read(filename)?;
+    let crc = media::crc32(&data);
+    let mut problems = Vec::new();
+
+    let name = std::path::Path::new(filename)
+        .file_name()
+        .map(|name| name.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let expected = KNOWN_ROMS.iter().find(|(known_name, _, _)| *known_name == name);
+    let known = KNOWN_ROMS.iter().find(|(_, known_crc, _)| *known_crc == crc);
+
+    match (expected, known) {
+        (Some((_, expected_crc, description)), None) => {
+            problems.push(format!("Expected the {} with CRC {:#010x}.", description, expected_crc));
+        }
+        (Some((_, _, expected)), Some((_, _, description))) if expected != description => {
+            problems.push(format!("Contains the {} instead of the {}.", description, expected));
+        }
+        _ => (),
+    }
+
+    if data.len() == ROM_SIZE + AMSDOS_HEADER_SIZE {
+        problems.push("Starts with a 128 byte AMSDOS header, which has to be removed.".to_string());
+    } else if data.len() == 2 * ROM_SIZE {
+        problems.push("Holds two ROMs, e.g. the operating system and BASIC, which have to be split.".to_string());
+    } else if data.len() != ROM_SIZE && expected.is_some() {
+        problems.push(format!("Is {} bytes long instead of {}.", data.len(), ROM_SIZE));
+    }
+
+    Ok(Verification {
+        filename: filename.to_string(),
+        crc,
+        known_as: known.map(|(_, _, description)| description.to_string()),
+        problems,
+    })
+}
+
+pub fn verify_media(filename: &str) -> std::io::Result<Verification> {
+    // media are checked by parsing them strictly, so damage the emulator would silently repair
+    // shows up. Whether a title is a known dump is up to the game database
+    let crc = media::hash_media(filename)?;
+    let mut problems = Vec::new();
+
+    if let Some(media::MediaType::Disk) = media::MediaType::detect(filename) {
+        if let Err(error) = dsk_file::Disk::load_with_mode(filename, media::ParseMode::Strict) {
+            problems.push(format!("Damaged, loads only with repairs: {}", error));
+        }
+    }
+
+    Ok(Verification {
+        filename: filename.to_string(),
+        crc,
+        known_as: None,
+        problems,
+    })
+}
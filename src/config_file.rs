@@ -0,0 +1,282 @@
+use serde::Deserialize;
+
+use crate::crtc;
+use crate::frame_pacer;
+use crate::gui;
+use crate::key_mapper;
+use crate::keyboard;
+use crate::media;
+use crate::memory;
+use crate::speech;
+use crate::system;
+
+// the key of a name in the file, its value and whether the name is known
+type NameCheck<'a> = (&'static str, &'a Option<String>, fn(&str) -> bool);
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigFileContents {
+    #[serde(default)]
+    machine: MachineSection,
+    #[serde(default)]
+    roms: RomSection,
+    #[serde(default)]
+    media: MediaSection,
+    #[serde(default)]
+    peripherals: PeripheralSection,
+    #[serde(default)]
+    video: VideoSection,
+    #[serde(default)]
+    audio: AudioSection,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MachineSection {
+    language: Option<String>,
+    firmware: Option<String>,
+    ram_pattern: Option<String>,
+    power_on_random: Option<String>,
+    ram: Option<String>,
+    crtc: Option<String>,
+    deterministic: Option<bool>,
+    seed: Option<u64>,
+    keymap: Option<String>,
+    keyboard_mode: Option<String>,
+    auto_repeat: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RomSection {
+    os: Option<String>,
+    basic: Option<String>,
+    amsdos: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MediaSection {
+    #[serde(default)]
+    files: Vec<String>,
+    autorun: Option<bool>,
+    parsing: Option<String>,
+    write_protect: Option<bool>,
+    fast_load: Option<bool>,
+    tape_sound: Option<bool>,
+    game_db: Option<String>,
+    storage: Option<String>,
+    #[serde(default)]
+    patches: Vec<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PeripheralSection {
+    rtc: Option<bool>,
+    rtc_time: Option<u64>,
+    speech: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct VideoSection {
+    frame_pacing: Option<String>,
+    refresh_rate: Option<u32>,
+    layout: Option<String>,
+    scale: Option<String>,
+    lite: Option<bool>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AudioSection {
+    device: Option<String>,
+    buffer: Option<u32>,
+    latency: Option<u32>,
+}
+
+// everything the run command is set up with, in one TOML file. Every key is optional and has the
+// same meaning as the command line option of the same name, options given on the command line win.
+// The setup command writes one for a first run:
+//
+//     [machine]
+//     language = "english"      # english, french, spanish, danish
+//     firmware = "amstrad"      # amstrad, minimal, diagnostic
+//     ram_pattern = "zeros"     # zeros, stripes, random
+//     power_on_random = "none"  # crtc, gate-array, psg, all or none, comma separated
+//     ram = "64k"               # 64k, 128k, 320k, 576k
+//     crtc = "0"                # 0, 1, 2, 3
+//     deterministic = false
+//     seed = 0
+//     keymap = "keys.toml"
+//     keyboard_mode = "positional" # positional, symbolic
+//     auto_repeat = "600,40"    # delay and period of held keys in milliseconds, or "off"
+//
+//     [roms]                    # replace the firmware's ROM files
+//     os = "rom/os_464.rom"
+//     basic = "rom/basic_1.0.rom"
+//     amsdos = "rom/amsdos_0.5.rom"
+//
+//     [media]
+//     files = ["game.dsk"]
+//     autorun = true
+//     parsing = "permissive"    # permissive, strict
+//     write_protect = false
+//     fast_load = false
+//     tape_sound = false
+//     game_db = "games.toml"
+//     storage = "storage"       # states, screenshots and bug reports, by game
+//     patches = ["patches"]
+//
+//     [peripherals]
+//     rtc = false
+//     rtc_time = 0              # seconds since 1970
+//     speech = "ssa1"           # ssa1, dktronics
+//
+//     [video]
+//     frame_pacing = "blend"    # repeat, blend
+//     refresh_rate = 60
+//     layout = "ronald.layout"
+//     scale = "2"               # 1, 2, 4, fit
+//     lite = false              # just the screen, without menus and debug panels
+//
+//     [audio]
+//     device = "default"
+//     buffer = 512              # frames
+//     latency = 60              # milliseconds
+//
+// Switches set to false leave the option off, they cannot turn off one given on the command line.
+// Relative paths are relative to the working directory, like on the command line
+pub struct ConfigFile {
+    filename: String,
+    contents: ConfigFileContents,
+}
+
+impl ConfigFile {
+    pub fn load(filename: &str) -> std::io::Result<ConfigFile> {
+        let text = std::fs::read_to_string(filename)?;
+        ConfigFile::parse(filename, &text)
+    }
+
+    pub fn parse(filename: &str, text: &str) -> std::io::Result<ConfigFile> {
+        let contents: ConfigFileContents = toml::from_str(text).map_err(|error| invalid_data(filename, &error.to_string()))?;
+        let config_file = ConfigFile {
+            filename: filename.to_string(),
+            contents,
+        };
+        config_file.validate()?;
+
+        Ok(config_file)
+    }
+
+    pub fn get_filename(&self) -> &str {
+        &self.filename
+    }
+
+    fn validate(&self) -> std::io::Result<()> {
+        // names are checked here, so a typo points at the key in the file instead of an option
+        let contents = &self.contents;
+        let names: [NameCheck; 12] = [
+            ("machine.language", &contents.machine.language, |name| keyboard::Language::from_name(name).is_some()),
+            ("machine.firmware", &contents.machine.firmware, |name| memory::Firmware::from_name(name).is_some()),
+            ("machine.ram_pattern", &contents.machine.ram_pattern, |name| memory::RamPattern::from_name(name).is_some()),
+            ("machine.power_on_random", &contents.machine.power_on_random, |names| system::PowerOnState::from_names(names).is_some()),
+            ("machine.ram", &contents.machine.ram, |name| memory::RamSize::from_name(name).is_some()),
+            ("machine.crtc", &contents.machine.crtc, |name| crtc::CrtcType::from_name(name).is_some()),
+            ("machine.keyboard_mode", &contents.machine.keyboard_mode, |name| key_mapper::KeyboardMode::from_name(name).is_some()),
+            ("machine.auto_repeat", &contents.machine.auto_repeat, |name| name == "off" || keyboard::AutoRepeat::parse(name).is_some()),
+            ("media.parsing", &contents.media.parsing, |name| media::ParseMode::from_name(name).is_some()),
+            ("peripherals.speech", &contents.peripherals.speech, |name| speech::Interface::from_name(name).is_some()),
+            ("video.frame_pacing", &contents.video.frame_pacing, |name| frame_pacer::FramePacing::from_name(name).is_some()),
+            ("video.scale", &contents.video.scale, |name| gui::WindowScale::from_name(name).is_some()),
+        ];
+        for (key, value, is_known) in names.iter() {
+            match value {
+                Some(value) if !is_known(value) => {
+                    return Err(invalid_data(&self.filename, &format!("{}: unknown value \"{}\"", key, value)));
+                }
+                _ => (),
+            }
+        }
+
+        if contents.video.refresh_rate == Some(0) {
+            return Err(invalid_data(&self.filename, "video.refresh_rate: has to be above 0"));
+        }
+        if contents.audio.latency == Some(0) {
+            return Err(invalid_data(&self.filename, "audio.latency: has to be above 0"));
+        }
+        if contents.peripherals.rtc_time.is_some() && contents.peripherals.rtc != Some(true) {
+            return Err(invalid_data(&self.filename, "peripherals.rtc_time: needs rtc = true"));
+        }
+        if contents.video.refresh_rate.is_some() && contents.video.frame_pacing.is_none() {
+            return Err(invalid_data(&self.filename, "video.refresh_rate: needs a frame_pacing"));
+        }
+
+        Ok(())
+    }
+
+    pub fn get_media(&self) -> &[String] {
+        &self.contents.media.files
+    }
+
+    pub fn get_options(&self) -> Vec<(&'static str, Option<String>)> {
+        // the run command's options with their values, None for switches, in the order of the file
+        let contents = &self.contents;
+        let mut options = Vec::new();
+        let mut value = |name: &'static str, value: Option<String>| {
+            if let Some(value) = value {
+                options.push((name, Some(value)));
+            }
+        };
+        value("language", contents.machine.language.clone());
+        value("firmware", contents.machine.firmware.clone());
+        value("ram-pattern", contents.machine.ram_pattern.clone());
+        value("power-on-random", contents.machine.power_on_random.clone());
+        value("ram", contents.machine.ram.clone());
+        value("crtc", contents.machine.crtc.clone());
+        value("seed", contents.machine.seed.map(|seed| seed.to_string()));
+        value("keymap", contents.machine.keymap.clone());
+        value("keyboard-mode", contents.machine.keyboard_mode.clone());
+        value("auto-repeat", contents.machine.auto_repeat.clone());
+        value("os-rom", contents.roms.os.clone());
+        value("basic-rom", contents.roms.basic.clone());
+        value("amsdos-rom", contents.roms.amsdos.clone());
+        value("media-parsing", contents.media.parsing.clone());
+        value("game-db", contents.media.game_db.clone());
+        value("storage", contents.media.storage.clone());
+        value("rtc-time", contents.peripherals.rtc_time.map(|time| time.to_string()));
+        value("speech", contents.peripherals.speech.clone());
+        value("frame-pacing", contents.video.frame_pacing.clone());
+        value("refresh-rate", contents.video.refresh_rate.map(|rate| rate.to_string()));
+        value("layout", contents.video.layout.clone());
+        value("scale", contents.video.scale.clone());
+        value("audio-device", contents.audio.device.clone());
+        value("audio-buffer", contents.audio.buffer.map(|frames| frames.to_string()));
+        value("audio-latency", contents.audio.latency.map(|milliseconds| milliseconds.to_string()));
+        for patches in &contents.media.patches {
+            options.push(("patches", Some(patches.clone())));
+        }
+
+        let switches = [
+            ("deterministic", contents.machine.deterministic),
+            ("autorun", contents.media.autorun),
+            ("write-protect", contents.media.write_protect),
+            ("fast-load", contents.media.fast_load),
+            ("tape-sound", contents.media.tape_sound),
+            ("rtc", contents.peripherals.rtc),
+            ("lite", contents.video.lite),
+        ];
+        for (name, enabled) in switches.iter() {
+            if *enabled == Some(true) {
+                options.push((name, None));
+            }
+        }
+
+        options
+    }
+}
+
+fn invalid_data(filename: &str, message: &str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, format!("{}: {}", filename, message))
+}
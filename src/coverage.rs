@@ -0,0 +1,45 @@
+use std::collections::BTreeSet;
+
+// addresses of executed instructions and of the routines they called, gathered while the
+// program runs and exported for static analysis tools
+#[derive(Clone)]
+pub struct Coverage {
+    executed: Vec<bool>,
+    entry_points: BTreeSet<u16>,
+}
+
+impl Coverage {
+    pub fn new() -> Coverage {
+        Coverage {
+            executed: vec![false; 0x10000],
+            entry_points: BTreeSet::new(),
+        }
+    }
+
+    pub fn record_instruction(&mut self, address: u16) {
+        self.executed[address as usize] = true;
+    }
+
+    pub fn record_entry_point(&mut self, address: u16) {
+        // targets of calls, restarts and interrupts
+        self.entry_points.insert(address);
+    }
+
+    pub fn is_executed(&self, address: u16) -> bool {
+        self.executed[address as usize]
+    }
+
+    pub fn read_executed_addresses(&self) -> Vec<u16> {
+        (0..=0xffff).filter(|address| self.executed[*address as usize]).collect()
+    }
+
+    pub fn read_entry_points(&self) -> Vec<u16> {
+        self.entry_points.iter().copied().collect()
+    }
+}
+
+impl Default for Coverage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
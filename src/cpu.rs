@@ -1,9 +1,7 @@
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::fmt;
 use std::rc::Rc;
 
-use log;
-
 use crate::bus;
 use crate::instruction::{Decoder, Instruction, JumpTest, Operand, InterruptMode};
 use crate::memory;
@@ -25,6 +23,7 @@ pub enum Register8 {
     IYL,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Register16 {
     AF,
     BC,
@@ -37,133 +36,150 @@ pub enum Register16 {
 }
 
 pub struct RegisterFile {
-    data: Vec<u16>,
+    af: u16,
+    bc: u16,
+    de: u16,
+    hl: u16,
+    shadow_af: u16,
+    shadow_bc: u16,
+    shadow_de: u16,
+    shadow_hl: u16,
+    i: u8,
+    r: u8,
+    ix: u16,
+    iy: u16,
+    sp: u16,
+    pc: u16,
+}
+
+fn high_byte(word: u16) -> u8 {
+    (word >> 8) as u8
+}
+
+fn low_byte(word: u16) -> u8 {
+    word as u8
+}
+
+fn set_high_byte(word: &mut u16, value: u8) {
+    *word = ((value as u16) << 8) | (*word & 0x00ff);
+}
+
+fn set_low_byte(word: &mut u16, value: u8) {
+    *word = (*word & 0xff00) | value as u16;
 }
 
 impl RegisterFile {
     fn new() -> RegisterFile {
-        RegisterFile { data: vec![0; 14] }
+        RegisterFile {
+            af: 0,
+            bc: 0,
+            de: 0,
+            hl: 0,
+            shadow_af: 0,
+            shadow_bc: 0,
+            shadow_de: 0,
+            shadow_hl: 0,
+            i: 0,
+            r: 0,
+            ix: 0,
+            iy: 0,
+            sp: 0,
+            pc: 0,
+        }
     }
 
     pub fn read_byte(&self, register: &Register8) -> u8 {
-        let value = match register {
-            Register8::A => self.data[0] >> 8,
-            Register8::F => self.data[0] & 0xff,
-            Register8::B => self.data[1] >> 8,
-            Register8::C => self.data[1] & 0xff,
-            Register8::D => self.data[2] >> 8,
-            Register8::E => self.data[2] & 0xff,
-            Register8::H => self.data[3] >> 8,
-            Register8::L => self.data[3] & 0xff,
-            Register8::I => self.data[8] & 0xff,
-            Register8::R => self.data[9] & 0xff,
-            Register8::IXH => self.data[10] >> 8,
-            Register8::IXL => self.data[10] & 0xff,
-            Register8::IYH => self.data[11] >> 8,
-            Register8::IYL => self.data[11] & 0xff,
-        };
-
-        value as u8
+        match register {
+            Register8::A => high_byte(self.af),
+            Register8::F => low_byte(self.af),
+            Register8::B => high_byte(self.bc),
+            Register8::C => low_byte(self.bc),
+            Register8::D => high_byte(self.de),
+            Register8::E => low_byte(self.de),
+            Register8::H => high_byte(self.hl),
+            Register8::L => low_byte(self.hl),
+            Register8::I => self.i,
+            Register8::R => self.r,
+            Register8::IXH => high_byte(self.ix),
+            Register8::IXL => low_byte(self.ix),
+            Register8::IYH => high_byte(self.iy),
+            Register8::IYL => low_byte(self.iy),
+        }
     }
 
-    fn write_byte(&mut self, register: &Register8, value: u8) {
+    pub fn write_byte(&mut self, register: &Register8, value: u8) {
         // TODO: add tests
-        let value = value as u16;
-
         match register {
-            Register8::A => {
-                self.data[0] = (value << 8) + (self.data[0] & 0xff);
-            }
-            Register8::F => {
-                self.data[0] = (self.data[0] & 0xff00) + value;
-            }
-            Register8::B => {
-                self.data[1] = (value << 8) + (self.data[1] & 0xff);
-            }
-            Register8::C => {
-                self.data[1] = (self.data[1] & 0xff00) + value;
-            }
-            Register8::D => {
-                self.data[2] = (value << 8) + (self.data[2] & 0xff);
-            }
-            Register8::E => {
-                self.data[2] = (self.data[2] & 0xff00) + value;
-            }
-            Register8::H => {
-                self.data[3] = (value << 8) + (self.data[3] & 0xff);
-            }
-            Register8::L => {
-                self.data[3] = (self.data[3] & 0xff00) + value;
-            }
-            Register8::I => {
-                self.data[8] = (self.data[8] & 0xff00) + value;
-            }
-            Register8::R => {
-                self.data[9] = (self.data[9] & 0xff00) + value;
-            }
-            Register8::IXH => {
-                self.data[10] = (value << 8) + (self.data[10] & 0xff);
-            }
-            Register8::IXL => {
-                self.data[10] = (self.data[10] & 0xff00) + value;
-            }
-            Register8::IYH => {
-                self.data[11] = (value << 8) + (self.data[11] & 0xff);
-            }
-            Register8::IYL => {
-                self.data[11] = (self.data[11] & 0xff00) + value;
-            }
+            Register8::A => set_high_byte(&mut self.af, value),
+            Register8::F => set_low_byte(&mut self.af, value),
+            Register8::B => set_high_byte(&mut self.bc, value),
+            Register8::C => set_low_byte(&mut self.bc, value),
+            Register8::D => set_high_byte(&mut self.de, value),
+            Register8::E => set_low_byte(&mut self.de, value),
+            Register8::H => set_high_byte(&mut self.hl, value),
+            Register8::L => set_low_byte(&mut self.hl, value),
+            Register8::I => self.i = value,
+            Register8::R => self.r = value,
+            Register8::IXH => set_high_byte(&mut self.ix, value),
+            Register8::IXL => set_low_byte(&mut self.ix, value),
+            Register8::IYH => set_high_byte(&mut self.iy, value),
+            Register8::IYL => set_low_byte(&mut self.iy, value),
         }
     }
 
     pub fn read_word(&self, register: &Register16) -> u16 {
         match register {
-            Register16::AF => self.data[0],
-            Register16::BC => self.data[1],
-            Register16::DE => self.data[2],
-            Register16::HL => self.data[3],
-            Register16::IX => self.data[10],
-            Register16::IY => self.data[11],
-            Register16::SP => self.data[12],
-            Register16::PC => self.data[13],
+            Register16::AF => self.af,
+            Register16::BC => self.bc,
+            Register16::DE => self.de,
+            Register16::HL => self.hl,
+            Register16::IX => self.ix,
+            Register16::IY => self.iy,
+            Register16::SP => self.sp,
+            Register16::PC => self.pc,
         }
     }
 
-    fn write_word(&mut self, register: &Register16, value: u16) {
+    pub fn write_word(&mut self, register: &Register16, value: u16) {
         match register {
-            Register16::AF => {
-                self.data[0] = value;
-            }
-            Register16::BC => {
-                self.data[1] = value;
-            }
-            Register16::DE => {
-                self.data[2] = value;
-            }
-            Register16::HL => {
-                self.data[3] = value;
-            }
-            Register16::IX => {
-                self.data[10] = value;
-            }
-            Register16::IY => {
-                self.data[11] = value;
-            }
-            Register16::SP => {
-                self.data[12] = value;
-            }
-            Register16::PC => {
-                self.data[13] = value;
-            }
+            Register16::AF => self.af = value,
+            Register16::BC => self.bc = value,
+            Register16::DE => self.de = value,
+            Register16::HL => self.hl = value,
+            Register16::IX => self.ix = value,
+            Register16::IY => self.iy = value,
+            Register16::SP => self.sp = value,
+            Register16::PC => self.pc = value,
+        }
+    }
+
+    pub fn read_shadow_word(&self, register: &Register16) -> u16 {
+        // the alternate register set swapped in by EX AF,AF' and EXX
+        match register {
+            Register16::AF => self.shadow_af,
+            Register16::BC => self.shadow_bc,
+            Register16::DE => self.shadow_de,
+            Register16::HL => self.shadow_hl,
+            _ => self.read_word(register),
+        }
+    }
+
+    pub fn write_shadow_word(&mut self, register: &Register16, value: u16) {
+        match register {
+            Register16::AF => self.shadow_af = value,
+            Register16::BC => self.shadow_bc = value,
+            Register16::DE => self.shadow_de = value,
+            Register16::HL => self.shadow_hl = value,
+            _ => self.write_word(register, value),
         }
     }
 
     fn swap_word(&mut self, register: &Register16) {
         match register {
-            Register16::AF => self.data.swap(0, 4),
-            Register16::BC => self.data.swap(1, 5),
-            Register16::DE => self.data.swap(2, 6),
-            Register16::HL => self.data.swap(3, 7),
+            Register16::AF => std::mem::swap(&mut self.af, &mut self.shadow_af),
+            Register16::BC => std::mem::swap(&mut self.bc, &mut self.shadow_bc),
+            Register16::DE => std::mem::swap(&mut self.de, &mut self.shadow_de),
+            Register16::HL => std::mem::swap(&mut self.hl, &mut self.shadow_hl),
             _ => unreachable!(),
         }
     }
@@ -171,20 +187,20 @@ impl RegisterFile {
 
 impl fmt::Display for RegisterFile {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "AF: {:#06x}    ", self.data[0])?;
-        writeln!(f, "AF': {:#06x}", self.data[4])?;
-        write!(f, "BC: {:#06x}    ", self.data[1])?;
-        writeln!(f, "BC': {:#06x}", self.data[5])?;
-        write!(f, "DE: {:#06x}    ", self.data[2])?;
-        writeln!(f, "DE': {:#06x}", self.data[6])?;
-        write!(f, "HL: {:#06x}    ", self.data[3])?;
-        writeln!(f, "HL': {:#06x}", self.data[7])?;
-        writeln!(f, " I: {:#04x}", self.data[8] & 0xff)?;
-        writeln!(f, " R: {:#04x}", self.data[9] & 0xff)?;
-        writeln!(f, "IX: {:#06x}", self.data[10])?;
-        writeln!(f, "IY: {:#06x}", self.data[11])?;
-        writeln!(f, "SP: {:#06x}", self.data[12])?;
-        writeln!(f, "PC: {:#06x}", self.data[13])
+        write!(f, "AF: {:#06x}    ", self.af)?;
+        writeln!(f, "AF': {:#06x}", self.shadow_af)?;
+        write!(f, "BC: {:#06x}    ", self.bc)?;
+        writeln!(f, "BC': {:#06x}", self.shadow_bc)?;
+        write!(f, "DE: {:#06x}    ", self.de)?;
+        writeln!(f, "DE': {:#06x}", self.shadow_de)?;
+        write!(f, "HL: {:#06x}    ", self.hl)?;
+        writeln!(f, "HL': {:#06x}", self.shadow_hl)?;
+        writeln!(f, " I: {:#04x}", self.i)?;
+        writeln!(f, " R: {:#04x}", self.r)?;
+        writeln!(f, "IX: {:#06x}", self.ix)?;
+        writeln!(f, "IY: {:#06x}", self.iy)?;
+        writeln!(f, "SP: {:#06x}", self.sp)?;
+        writeln!(f, "PC: {:#06x}", self.pc)
     }
 }
 
@@ -210,8 +226,43 @@ impl Flag {
     }
 }
 
+// what a handler may change about the instruction it executes, besides registers and memory
+struct Execution {
+    next_address: u16,
+    timing: u8, // in NOPs, preset from the instruction for handlers that take longer or shorter
+    prevent_interrupt: bool,
+}
+
+type Handler<M, B> = fn(&mut CPU<M, B>, &Instruction, &mut Execution);
+
+pub type MemoryObserverShared = Rc<RefCell<dyn MemoryObserver>>;
+pub type IoObserverShared = Rc<RefCell<dyn IoObserver>>;
+
+// notified of each memory access made by an instruction, for profilers, watchpoints, contention
+// models and the like. Opcode fetches through the decoder are not included
+pub trait MemoryObserver {
+    fn notify_memory_read(&mut self, _t_state: u64, _address: u16, _value: u8) {}
+
+    fn notify_memory_write(&mut self, _t_state: u64, _address: u16, _value: u8) {}
+}
+
+pub trait IoObserver {
+    fn notify_port_read(&mut self, _t_state: u64, _port: u16, _value: u8) {}
+
+    fn notify_port_write(&mut self, _t_state: u64, _port: u16, _value: u8) {}
+}
+
 pub type CPUShared<M, B> = Rc<RefCell<CPU<M, B>>>;
 
+// what the CPU keeps between instructions besides registers and the interrupt state
+#[derive(Clone)]
+pub struct ExecutionState {
+    halted: bool,
+    irq_received: bool,
+    interrupt_deferred: bool,
+    t_states: u64,
+}
+
 pub struct CPU<M, B> {
     pub registers: RegisterFile,
     pub memory: Rc<RefCell<M>>,
@@ -221,8 +272,13 @@ pub struct CPU<M, B> {
     iff2: bool,
     halted: bool,
     interrupt_mode: InterruptMode,
-    enable_interrupt: bool,
     irq_received: bool,
+    interrupt_deferred: bool, // the last instruction held back an interrupt IFF1 would have let through
+    t_states: u64, // since power on, four per NOP
+    access_t_state: Cell<u64>, // estimated T-state of the next memory or port access
+    memory_observers: Vec<(usize, MemoryObserverShared)>,
+    io_observers: Vec<(usize, IoObserverShared)>,
+    next_observer_id: usize,
 }
 
 impl<M, B> CPU<M, B>
@@ -241,8 +297,13 @@ where
             halted: false,
             decoder: Decoder::new(memory.clone()),
             interrupt_mode: InterruptMode::Mode0,
-            enable_interrupt: false,
             irq_received: false,
+            interrupt_deferred: false,
+            t_states: 0,
+            access_t_state: Cell::new(0),
+            memory_observers: Vec::new(),
+            io_observers: Vec::new(),
+            next_observer_id: 0,
             memory,
             bus,
         };
@@ -253,1197 +314,1577 @@ where
     }
 
     pub fn fetch_and_execute(&mut self) -> (u8, bool) {
-        if self.enable_interrupt {
-            self.iff1 = true;
-            self.iff2 = true;
-            self.enable_interrupt = false;
-        }
-
-        let mut prevent_interrupt = false;
-
         let pc = self.registers.read_word(&Register16::PC);
 
         let (instruction, next_address) = self
             .decoder
             .decode_at(pc as usize);
 
+        #[cfg(feature = "instrumentation")]
         log::trace!("{:#06x}: {}", pc, &instruction);
 
-        let mut timing_in_nops = instruction.timing();
-
-        match &instruction {
-            Instruction::Adc(destination, source) => {
-                match destination {
-                    Operand::Register8(Register8::A) => {
-                        let left = self.load_byte(destination);
-                        let right = self.load_byte(source);
-                        let carry_value = if self.check_flag(Flag::Carry) { 1 } else { 0 };
-                        let (value, carry1) = right.overflowing_add(carry_value);
-                        let (value, carry2) = left.overflowing_add(value);
-                        let overflow =
-                            (left & 0x80) == (right & 0x80) && (right & 0x80) != (value & 0x80);
-                        self.store_byte(destination, value);
-
-                        self.set_flag(Flag::Sign, (value as i8) < 0);
-                        self.set_flag(Flag::Zero, value == 0);
-                        self.set_flag(
-                            Flag::HalfCarry,
-                            (((left & 0xf) + (right & 0xf) + carry_value) & 0x10) != 0,
-                        );
-                        self.set_flag(Flag::ParityOverflow, overflow);
-                        self.set_flag(Flag::AddSubtract, false);
-                        self.set_flag(Flag::Carry, carry1 || carry2);
-                    }
-                    Operand::Register16(Register16::HL) => {
-                        let left = self.load_word(destination);
-                        let right = self.load_word(source);
-                        let carry_value = if self.check_flag(Flag::Carry) { 1 } else { 0 };
-                        let (value, carry1) = right.overflowing_add(carry_value);
-                        let (value, carry2) = left.overflowing_add(value);
-                        let overflow = (left & 0x8000) == (right & 0x8000)
-                            && (right & 0x8000) != (value & 0x8000);
-                        self.store_word(destination, value);
-
-                        self.set_flag(Flag::Sign, (value as i16) < 0);
-                        self.set_flag(Flag::Zero, value == 0);
-                        self.set_flag(
-                            Flag::HalfCarry,
-                            (((left & 0xfff) + (right & 0xfff) + carry_value) & 0x1000) != 0,
-                        );
-                        self.set_flag(Flag::ParityOverflow, overflow);
-                        self.set_flag(Flag::AddSubtract, false);
-                        self.set_flag(Flag::Carry, carry1 || carry2);
-                    }
-                    _ => unreachable!(),
-                }
-
-                self.registers
-                    .write_word(&Register16::PC, next_address as u16);
-            }
-            Instruction::Add(destination, source) => {
-                match destination {
-                    Operand::Register8(Register8::A) => {
-                        // ALUOP / checked
-                        let left = self.load_byte(destination);
-                        let right = self.load_byte(source);
-                        let (value, carry) = left.overflowing_add(right);
-                        let (_, overflow) = (left as i8).overflowing_add(right as i8);
-                        self.store_byte(destination, value);
-
-                        self.set_flag(Flag::Sign, (value as i8) < 0); // TODO: make this reusable?
-                        self.set_flag(Flag::Zero, value == 0);
-                        self.set_flag(
-                            Flag::HalfCarry,
-                            (((left & 0xf) + (right & 0xf)) & 0x10) != 0,
-                        );
-                        self.set_flag(Flag::ParityOverflow, overflow);
-                        self.set_flag(Flag::AddSubtract, false);
-                        self.set_flag(Flag::Carry, carry);
-                    }
-                    _ => {
-                        let left = self.load_word(destination);
-                        let right = self.load_word(source);
-                        let (value, carry) = left.overflowing_add(right);
-                        self.store_word(destination, value);
-
-                        self.set_flag(
-                            Flag::HalfCarry,
-                            (((left & 0xfff) + (right & 0xfff)) & 0x1000) != 0,
-                        );
-                        self.set_flag(Flag::AddSubtract, false);
-                        self.set_flag(Flag::Carry, carry);
-                    }
-                }
+        // accesses are spread over the instruction assuming 3 T-states per memory and 4 per port
+        // access after the opcode fetch, which is close enough for most instructions
+        self.access_t_state.set(self.t_states + 4);
 
-                self.registers
-                    .write_word(&Register16::PC, next_address as u16);
-            }
-            Instruction::And(operand) => {
-                let value = self.load_byte(operand);
+        let mut execution = Execution {
+            next_address: next_address as u16,
+            timing: instruction.timing(),
+            prevent_interrupt: false,
+        };
+        Self::HANDLERS[instruction.index()](self, &instruction, &mut execution);
+        let mut timing_in_nops = execution.timing;
+        self.t_states += 4 * timing_in_nops as u64;
 
-                let result = self.registers.read_byte(&Register8::A) & value;
-                self.registers.write_byte(&Register8::A, result);
+        // interrupts are only accepted between instructions, and not right after EI or a prefix
+        self.interrupt_deferred = self.irq_received && self.iff1 && execution.prevent_interrupt;
+        if self.irq_received && self.iff1 && !execution.prevent_interrupt {
+            // TODO: allow non-maskable interrupts (they are not used in the CPC)?
+            self.irq_received = false; // TODO: make requester hold interrupt until acknowledged?
 
-                self.set_flag(Flag::Sign, (result as i8) < 0);
-                self.set_flag(Flag::Zero, result == 0);
-                self.set_flag(Flag::HalfCarry, true);
-                self.set_flag(Flag::ParityOverflow, (result.count_ones() & 1) == 0);
-                self.set_flag(Flag::AddSubtract, false);
-                self.set_flag(Flag::Carry, false);
+            match self.interrupt_mode {
+                InterruptMode::Mode1 => {
+                    // println!("handle interrupt");
+                    let mut old_pc = self.registers.read_word(&Register16::PC); // PC has already been set to next instruction
+                    if self.halted {
+                        self.halted = false;
+                        old_pc = old_pc.wrapping_add(1); // continue after the HALT instruction
+                    }
+                    let new_sp = self.registers.read_word(&Register16::SP).wrapping_sub(2);
+                    self.registers.write_word(&Register16::SP, new_sp);
+                    self.write_memory_word(new_sp, old_pc);
+                    
+                    self.registers.write_word(&Register16::PC, 0x0038);
 
-                self.registers
-                    .write_word(&Register16::PC, next_address as u16);
+                    timing_in_nops += 4; // + Instruction::Rst(_).timing()
+                    self.t_states += 16;
+                }
+                _ => unimplemented!(),
             }
-            Instruction::Bit(Operand::Bit(bit), operand) => {
-                let value = self.load_byte(operand);
+            
+            (timing_in_nops, true)
+        } else {
+            (timing_in_nops, false)
+        }
+    }
 
-                self.set_flag(Flag::Zero, (value & (1 << bit)) == 0);
-                self.set_flag(Flag::HalfCarry, true);
-                self.set_flag(Flag::AddSubtract, false);
+    pub fn request_interrupt(&mut self) {
+        self.irq_received = true;
+    }
 
-                self.registers
-                    .write_word(&Register16::PC, next_address as u16);
-            }
-            Instruction::Call(jump_test, Operand::Immediate16(address)) => {
-                if self.check_jump(jump_test) {
-                    let new_sp = self.registers.read_word(&Register16::SP) - 2;
-                    self.registers.write_word(&Register16::SP, new_sp);
-                    self.memory
-                        .borrow_mut()
-                        .write_word(new_sp as usize, next_address as u16);
-                    self.registers.write_word(&Register16::PC, *address);
-                } else {
-                    self.registers
-                        .write_word(&Register16::PC, next_address as u16);
-                }
-            }
-            Instruction::Ccf => {
-                let carry = self.check_flag(Flag::Carry);
+    pub fn is_interrupt_deferred(&self) -> bool {
+        self.interrupt_deferred
+    }
 
-                self.set_flag(Flag::HalfCarry, carry);
-                self.set_flag(Flag::AddSubtract, false);
-                self.set_flag(Flag::Carry, !carry);
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
 
-                self.registers
-                    .write_word(&Register16::PC, next_address as u16);
-            }
-            Instruction::Cp(operand) => {
-                // ALUOP / checked
-                let left = self.registers.read_byte(&Register8::A);
-                let right = self.load_byte(operand);
-                let (value, carry) = left.overflowing_sub(right);
-                let (_, overflow) = (left as i8).overflowing_sub(right as i8);
-
-                self.set_flag(Flag::Sign, (value as i8) < 0); // TODO: make this reusable?
-                self.set_flag(Flag::Zero, value == 0);
-                self.set_flag(Flag::HalfCarry, (left & 0xf) < (right & 0xf));
-                self.set_flag(Flag::ParityOverflow, overflow);
-                self.set_flag(Flag::AddSubtract, true);
-                self.set_flag(Flag::Carry, carry);
+    pub fn read_interrupt_state(&self) -> (bool, bool, u8) {
+        // IFF1, IFF2 and the interrupt mode
+        let mode = match self.interrupt_mode {
+            InterruptMode::Mode0 => 0,
+            InterruptMode::Mode1 => 1,
+            InterruptMode::Mode2 => 2,
+        };
+        (self.iff1, self.iff2, mode)
+    }
 
-                self.registers
-                    .write_word(&Register16::PC, next_address as u16);
-            }
-            Instruction::Cpd => {
-                let left = self.registers.read_byte(&Register8::A);
-                let right = self.load_byte(&Operand::RegisterIndirect(Register16::HL));
-                let value = left.wrapping_sub(right);
+    pub fn write_interrupt_state(&mut self, iff1: bool, iff2: bool, mode: u8) {
+        self.iff1 = iff1;
+        self.iff2 = iff2;
+        self.interrupt_mode = match mode {
+            0 => InterruptMode::Mode0,
+            1 => InterruptMode::Mode1,
+            _ => InterruptMode::Mode2,
+        };
+        self.halted = false;
+    }
 
-                let source = self.registers.read_word(&Register16::HL).wrapping_sub(1);
-                self.registers.write_word(&Register16::HL, source);
+    pub fn read_t_states(&self) -> u64 {
+        self.t_states
+    }
 
-                let counter = self.registers.read_word(&Register16::BC).wrapping_sub(1);
-                self.registers.write_word(&Register16::BC, counter);
+    pub fn save_execution_state(&self) -> ExecutionState {
+        ExecutionState {
+            halted: self.halted,
+            irq_received: self.irq_received,
+            interrupt_deferred: self.interrupt_deferred,
+            t_states: self.t_states,
+        }
+    }
 
-                self.set_flag(Flag::Sign, (value as i8) < 0);
-                self.set_flag(Flag::Zero, value == 0);
-                self.set_flag(Flag::HalfCarry, (left & 0xf) < (right & 0xf));
-                self.set_flag(Flag::ParityOverflow, counter != 0);
-                self.set_flag(Flag::AddSubtract, true);
+    pub fn load_execution_state(&mut self, state: &ExecutionState) {
+        self.halted = state.halted;
+        self.irq_received = state.irq_received;
+        self.interrupt_deferred = state.interrupt_deferred;
+        self.t_states = state.t_states;
+        self.access_t_state.set(state.t_states);
+    }
 
-                self.registers
-                    .write_word(&Register16::PC, next_address as u16);
-            }
-            Instruction::Cpdr => {
-                let left = self.registers.read_byte(&Register8::A);
-                let right = self.load_byte(&Operand::RegisterIndirect(Register16::HL));
-                let value = left.wrapping_sub(right);
+    pub fn add_memory_observer(&mut self, observer: MemoryObserverShared) -> usize {
+        let id = self.next_observer_id;
+        self.next_observer_id += 1;
+        self.memory_observers.push((id, observer));
 
-                let source = self.registers.read_word(&Register16::HL).wrapping_sub(1);
-                self.registers.write_word(&Register16::HL, source);
+        id
+    }
 
-                let counter = self.registers.read_word(&Register16::BC).wrapping_sub(1);
-                self.registers.write_word(&Register16::BC, counter);
+    pub fn add_io_observer(&mut self, observer: IoObserverShared) -> usize {
+        let id = self.next_observer_id;
+        self.next_observer_id += 1;
+        self.io_observers.push((id, observer));
 
-                self.set_flag(Flag::Sign, (value as i8) < 0);
-                self.set_flag(Flag::Zero, value == 0);
-                self.set_flag(Flag::HalfCarry, (left & 0xf) < (right & 0xf));
-                self.set_flag(Flag::ParityOverflow, counter != 0);
-                self.set_flag(Flag::AddSubtract, true);
-
-                if counter == 0 || value == 0 {
-                    self.registers
-                        .write_word(&Register16::PC, next_address as u16);
-                    timing_in_nops = 5; // not having to adjust the PC saves time
-                }
-            }
-            Instruction::Cpi => {
-                let left = self.registers.read_byte(&Register8::A);
-                let right = self.load_byte(&Operand::RegisterIndirect(Register16::HL));
-                let value = left.wrapping_sub(right);
+        id
+    }
 
-                let source = self.registers.read_word(&Register16::HL).wrapping_add(1);
-                self.registers.write_word(&Register16::HL, source);
+    pub fn remove_observer(&mut self, id: usize) {
+        // ids are shared between memory and IO observers
+        self.memory_observers.retain(|(observer_id, _)| *observer_id != id);
+        self.io_observers.retain(|(observer_id, _)| *observer_id != id);
+    }
 
-                let counter = self.registers.read_word(&Register16::BC).wrapping_sub(1);
-                self.registers.write_word(&Register16::BC, counter);
+    fn reset(&mut self) {
+        // TODO: implement reset
+    }
 
-                self.set_flag(Flag::Sign, (value as i8) < 0);
-                self.set_flag(Flag::Zero, value == 0);
-                self.set_flag(Flag::HalfCarry, (left & 0xf) < (right & 0xf));
-                self.set_flag(Flag::ParityOverflow, counter != 0);
-                self.set_flag(Flag::AddSubtract, true);
+    fn check_flag(&self, flag: Flag) -> bool {
+        // TODO: move this from CPU to register file
+        let flags = self.registers.read_byte(&Register8::F);
 
-                self.registers
-                    .write_word(&Register16::PC, next_address as u16);
-            }
-            Instruction::Cpir => {
-                let left = self.registers.read_byte(&Register8::A);
-                let right = self.load_byte(&Operand::RegisterIndirect(Register16::HL));
-                let value = left.wrapping_sub(right);
+        flags & flag.mask() != 0
+    }
 
-                let source = self.registers.read_word(&Register16::HL).wrapping_add(1);
-                self.registers.write_word(&Register16::HL, source);
+    fn set_flag(&mut self, flag: Flag, value: bool) {
+        // TODO: move this from CPU to register file
+        let old_flags = self.registers.read_byte(&Register8::F);
 
-                let counter = self.registers.read_word(&Register16::BC).wrapping_sub(1);
-                self.registers.write_word(&Register16::BC, counter);
+        let new_flags = if value {
+            old_flags | flag.mask()
+        } else {
+            old_flags & (!flag.mask()) // TODO: add tests
+        };
 
-                self.set_flag(Flag::Sign, (value as i8) < 0);
-                self.set_flag(Flag::Zero, value == 0);
-                self.set_flag(Flag::HalfCarry, (left & 0xf) < (right & 0xf));
-                self.set_flag(Flag::ParityOverflow, counter != 0);
-                self.set_flag(Flag::AddSubtract, true);
-
-                if counter == 0 || value == 0 {
-                    self.registers
-                        .write_word(&Register16::PC, next_address as u16);
-                    timing_in_nops = 5; // not having to adjust the PC saves time
-                }
-            }
-            Instruction::Cpl => {
-                let value = self.registers.read_byte(&Register8::A);
-                self.registers.write_byte(&Register8::A, !value);
+        self.registers.write_byte(&Register8::F, new_flags);
+    }
 
-                self.set_flag(Flag::HalfCarry, true);
-                self.set_flag(Flag::AddSubtract, true);
+    fn check_jump(&self, jump_test: &JumpTest) -> bool {
+        match jump_test {
+            JumpTest::Unconditional => true,
+            JumpTest::NonZero => !self.check_flag(Flag::Zero),
+            JumpTest::Zero => self.check_flag(Flag::Zero),
+            JumpTest::NoCarry => !self.check_flag(Flag::Carry),
+            JumpTest::Carry => self.check_flag(Flag::Carry),
+            JumpTest::ParityOdd => !self.check_flag(Flag::ParityOverflow),
+            JumpTest::ParityEven => self.check_flag(Flag::ParityOverflow),
+            JumpTest::SignPositive => !self.check_flag(Flag::Sign),
+            JumpTest::SignNegative => self.check_flag(Flag::Sign),
+        }
+    }
 
-                self.registers
-                    .write_word(&Register16::PC, next_address as u16);
+    fn operand_address(&self, operand: &Operand) -> u16 {
+        match operand {
+            Operand::Direct16(address) => *address,
+            Operand::RegisterIndirect(register) => self.registers.read_word(register),
+            Operand::Indexed(register, displacement) => {
+                self.registers.read_word(register).wrapping_add(*displacement as u16)
             }
-            Instruction::Daa => {
-                let mut value = self.registers.read_byte(&Register8::A);
-                let mut half_carry = self.check_flag(Flag::HalfCarry);
-                let mut carry = self.check_flag(Flag::Carry);
-                let mut correction = 0;
-
-                if (value & 0xf) > 0x9 || half_carry {
-                    half_carry = half_carry && (value & 0xf) < 0x6;
-                    correction += 0x6;
-                }
+            _ => unreachable!(),
+        }
+    }
 
-                if value > 0x99 || carry {
-                    correction += 0x60;
-                    carry = true;
-                }
+    fn load_byte(&self, operand: &Operand) -> u8 {
+        match operand {
+            Operand::Immediate8(value) => *value,
+            Operand::Register8(register) => self.registers.read_byte(register),
+            Operand::Direct8(_) => unimplemented!(),
+            _ => self.read_memory_byte(self.operand_address(operand)),
+        }
+    }
 
-                if self.check_flag(Flag::AddSubtract) {
-                    half_carry = half_carry && (value & 0xf) < 0x6;
-                    value -= correction;
-                } else {
-                    half_carry = (value & 0xf) > 0x9;
-                    value += correction;
-                }
+    fn store_byte(&mut self, operand: &Operand, value: u8) {
+        match operand {
+            Operand::Register8(register) => self.registers.write_byte(register, value),
+            Operand::Direct8(_) => unimplemented!(),
+            _ => self.write_memory_byte(self.operand_address(operand), value),
+        }
+    }
 
-                self.registers.write_byte(&Register8::A, value);
+    fn load_word(&self, operand: &Operand) -> u16 {
+        match operand {
+            Operand::Immediate16(value) => *value,
+            Operand::Register16(register) => self.registers.read_word(register),
+            _ => self.read_memory_word(self.operand_address(operand)),
+        }
+    }
 
-                self.set_flag(Flag::Sign, (value as i8) < 0);
-                self.set_flag(Flag::Zero, value == 0);
-                self.set_flag(Flag::HalfCarry, half_carry);
-                self.set_flag(Flag::ParityOverflow, (value.count_ones() & 1) == 0);
-                self.set_flag(Flag::Carry, carry);
+    fn store_word(&mut self, operand: &Operand, value: u16) {
+        match operand {
+            Operand::Register16(register) => self.registers.write_word(register, value),
+            _ => self.write_memory_word(self.operand_address(operand), value),
+        }
+    }
 
-                self.registers
-                    .write_word(&Register16::PC, next_address as u16);
-            }
-            Instruction::Dec(destination) => {
-                match destination {
-                    Operand::Register16(register) => {
-                        let value = self.registers.read_word(register);
-                        self.registers.write_word(register, value.wrapping_sub(1));
-                    }
-                    _ => {
-                        let old_value = self.load_byte(destination);
-                        self.set_flag(Flag::ParityOverflow, old_value == 0x80);
-                        let (value, _) = old_value.overflowing_sub(1);
-                        self.store_byte(destination, value);
-
-                        self.set_flag(Flag::Sign, (value as i8) < 0);
-                        self.set_flag(Flag::Zero, value == 0);
-                        self.set_flag(Flag::HalfCarry, (old_value & 0xf) < 1);
-                        self.set_flag(Flag::AddSubtract, true);
-                    }
-                }
+    // instructions access memory and ports only through these, so that features depending on
+    // individual accesses like contention or access traces have a single place to hook into
+    fn read_memory_byte(&self, address: u16) -> u8 {
+        let value = self.memory.borrow().read_byte(address as usize);
+        for (_, observer) in &self.memory_observers {
+            observer.borrow_mut().notify_memory_read(self.access_t_state.get(), address, value);
+        }
+        self.access_t_state.set(self.access_t_state.get() + 3);
 
-                self.registers
-                    .write_word(&Register16::PC, next_address as u16);
-            }
-            Instruction::Defb(_operand) => {
-                prevent_interrupt = true;
-                self.registers
-                    .write_word(&Register16::PC, next_address as u16);
-            }
-            Instruction::Defw(_operand) => {
-                // TODO: disable interrupts??? or not???
-                self.registers
-                    .write_word(&Register16::PC, next_address as u16);
-            }
-            Instruction::Di => {
-                self.iff1 = false;
-                self.iff2 = false;
+        value
+    }
 
-                self.registers
-                    .write_word(&Register16::PC, next_address as u16);
-            }
-            Instruction::Djnz(target) => {
-                let value = self.registers.read_byte(&Register8::B).wrapping_sub(1);
-                self.registers.write_byte(&Register8::B, value);
-
-                if value != 0 {
-                    let address = match target {
-                        Operand::Immediate16(address) => address,
-                        _ => {
-                            unimplemented!();
-                        }
-                    };
-                    self.registers.write_word(&Register16::PC, *address);
-                    timing_in_nops = 4;
-                } else {
-                    self.registers
-                        .write_word(&Register16::PC, next_address as u16);
-                }
-            }
-            Instruction::Ei => {
-                self.enable_interrupt = true;
+    fn write_memory_byte(&mut self, address: u16, value: u8) {
+        self.memory.borrow_mut().write_byte(address as usize, value);
+        for (_, observer) in &self.memory_observers {
+            observer.borrow_mut().notify_memory_write(self.access_t_state.get(), address, value);
+        }
+        self.access_t_state.set(self.access_t_state.get() + 3);
+    }
 
-                self.registers
-                    .write_word(&Register16::PC, next_address as u16);
-            }
-            Instruction::Ex(left, right) => {
-                match (left, right) {
-                    (Operand::Register16(Register16::AF), Operand::Register16(Register16::AF)) => { // TODO: test if this match arm works with references
-                        self.registers.swap_word(&Register16::AF);
-                    }
-                    (left, right) => {
-                        let left_value = self.load_word(left);
-                        let right_value = self.load_word(right);
+    fn read_memory_word(&self, address: u16) -> u16 {
+        let low_byte = self.read_memory_byte(address);
+        let high_byte = self.read_memory_byte(address.wrapping_add(1));
+        u16::from_le_bytes([low_byte, high_byte])
+    }
 
-                        self.store_word(left, right_value);
-                        self.store_word(right, left_value);
-                    }
-                }
+    fn write_memory_word(&mut self, address: u16, value: u16) {
+        let bytes = value.to_le_bytes();
+        self.write_memory_byte(address, bytes[0]);
+        self.write_memory_byte(address.wrapping_add(1), bytes[1]);
+    }
 
-                self.registers
-                    .write_word(&Register16::PC, next_address as u16);
-            }
-            Instruction::Exx => {
-                self.registers.swap_word(&Register16::BC);
-                self.registers.swap_word(&Register16::DE);
-                self.registers.swap_word(&Register16::HL);
+    fn read_port(&self, port: u16) -> u8 {
+        let value = self.bus.borrow().read_byte(port);
+        for (_, observer) in &self.io_observers {
+            observer.borrow_mut().notify_port_read(self.access_t_state.get(), port, value);
+        }
+        self.access_t_state.set(self.access_t_state.get() + 4);
 
-                self.registers
-                    .write_word(&Register16::PC, next_address as u16);
-            }
-            Instruction::Im(mode) => {
-                self.interrupt_mode = *mode;
+        value
+    }
 
-                self.registers
-                    .write_word(&Register16::PC, next_address as u16);
-            }
-            Instruction::In(Operand::Register8(Register8::A), Operand::Direct8(port_low)) => {
-                let port_high = self.registers.read_byte(&Register8::A);
-                let port = (port_high as u16) << 8 | (*port_low as u16);
+    fn write_port(&mut self, port: u16, value: u8) {
+        self.bus.borrow_mut().write_byte(port, value);
+        for (_, observer) in &self.io_observers {
+            observer.borrow_mut().notify_port_write(self.access_t_state.get(), port, value);
+        }
+        self.access_t_state.set(self.access_t_state.get() + 4);
+    }
 
-                let value = self.bus.borrow().read_byte(port);
-                self.registers.write_byte(&Register8::A, value);
+    fn input_block_byte(&mut self, step: u16) -> u8 {
+        // one iteration of INI, IND, INIR and INDR: the port is read with B before it counts down
+        let port = self.registers.read_word(&Register16::BC);
+        let value = self.read_port(port);
+        self.store_byte(&Operand::RegisterIndirect(Register16::HL), value);
 
-                self.registers
-                    .write_word(&Register16::PC, next_address as u16);
-            }
-            Instruction::In(Operand::Register8(destination), Operand::RegisterIndirect(Register16::BC)) => {
-                // TODO: make this a special case of the other IN instruction above?
-                let port = self.registers.read_word(&Register16::BC);
+        let address = self.registers.read_word(&Register16::HL).wrapping_add(step);
+        self.registers.write_word(&Register16::HL, address);
 
-                let value = self.bus.borrow().read_byte(port);
-                self.registers.write_byte(destination, value);
+        let counter = self.registers.read_byte(&Register8::B).wrapping_sub(1);
+        self.registers.write_byte(&Register8::B, counter);
 
-                self.registers
-                    .write_word(&Register16::PC, next_address as u16);
-            }
-            Instruction::Inc(destination) => {
-                match destination {
-                    Operand::Register16(register) => {
-                        let value = self.registers.read_word(register);
-                        self.registers.write_word(&register, value.wrapping_add(1));
-                    }
-                    _ => {
-                        let old_value = self.load_byte(destination);
-                        self.set_flag(Flag::ParityOverflow, old_value == 0x7f);
-                        let (value, _) = old_value.overflowing_add(1);
-                        self.store_byte(destination, value);
-
-                        self.set_flag(Flag::Sign, (value as i8) < 0);
-                        self.set_flag(Flag::Zero, value == 0);
-                        self.set_flag(Flag::HalfCarry, (((old_value & 0xf) + 1) & 0x10) != 0);
-                        self.set_flag(Flag::AddSubtract, false);
-                    }
-                }
+        let port_low = self.registers.read_byte(&Register8::C).wrapping_add(step as u8);
+        self.set_block_io_flags(value, port_low, counter);
 
-                self.registers
-                    .write_word(&Register16::PC, next_address as u16);
-            }
-            Instruction::Jp(jump_test, target) => {
-                if self.check_jump(jump_test) {
-                    let address = match target {
-                        Operand::RegisterIndirect(register) => {
-                            self.registers.read_word(register) // special case for JP (HL) and friends
-                        }
-                        _ => self.load_word(target),
-                    };
-
-                    self.registers.write_word(&Register16::PC, address);
-                } else {
-                    self.registers
-                        .write_word(&Register16::PC, next_address as u16);
-                }
-            }
-            Instruction::Jr(jump_test, target) => {
-                if self.check_jump(jump_test) {
-                    let address = match target {
-                        Operand::Immediate16(address) => address,
-                        _ => {
-                            unimplemented!();
-                        }
-                    };
-                    self.registers.write_word(&Register16::PC, *address);
-                } else {
-                    self.registers
-                        .write_word(&Register16::PC, next_address as u16);
-                    timing_in_nops = 2;
-                }
-            }
-            Instruction::Ld(destination, source) => {
-                match (&destination, &source) {
-                    (Operand::Register16(_), _) => {
-                        let value = self.load_word(source);
-                        self.store_word(destination, value);
-                    }
-                    (_, Operand::Register16(_)) => {
-                        let value = self.load_word(source);
-                        self.store_word(destination, value);
-                    }
-                    _ => {
-                        // TODO: store iff2 in parity flag if instruction is ld a,i or ld a,r
-                        let value = self.load_byte(source);
-                        self.store_byte(destination, value);
-                    }
-                }
+        counter
+    }
 
-                self.registers
-                    .write_word(&Register16::PC, next_address as u16);
-            }
-            Instruction::LdDirect16(destination, source) => {
-                let value = self.load_word(source);
-                self.store_word(destination, value);
+    fn output_block_byte(&mut self, step: u16) -> u8 {
+        // one iteration of OUTI, OUTD, OTIR and OTDR: B counts down before it goes out on the bus
+        let value = self.load_byte(&Operand::RegisterIndirect(Register16::HL));
 
-                self.registers
-                    .write_word(&Register16::PC, next_address as u16);
-            }
-            Instruction::Ldd => {
-                let value = self.load_byte(&Operand::RegisterIndirect(Register16::HL));
-                self.store_byte(&Operand::RegisterIndirect(Register16::DE), value);
+        let counter = self.registers.read_byte(&Register8::B).wrapping_sub(1);
+        self.registers.write_byte(&Register8::B, counter);
 
-                let address = self.registers.read_word(&Register16::DE).wrapping_sub(1);
-                self.registers.write_word(&Register16::DE, address);
+        let port = self.registers.read_word(&Register16::BC);
+        self.write_port(port, value);
 
-                let address = self.registers.read_word(&Register16::HL).wrapping_sub(1);
-                self.registers.write_word(&Register16::HL, address);
+        let address = self.registers.read_word(&Register16::HL).wrapping_add(step);
+        self.registers.write_word(&Register16::HL, address);
 
-                let counter = self.registers.read_word(&Register16::BC).wrapping_sub(1);
-                self.registers.write_word(&Register16::BC, counter);
+        self.set_block_io_flags(value, address as u8, counter);
 
-                self.set_flag(Flag::HalfCarry, false);
-                self.set_flag(Flag::ParityOverflow, counter != 0);
-                self.set_flag(Flag::AddSubtract, false);
+        counter
+    }
 
-                self.registers
-                    .write_word(&Register16::PC, next_address as u16);
-            }
-            Instruction::Lddr => {
-                let value = self.load_byte(&Operand::RegisterIndirect(Register16::HL));
-                self.store_byte(&Operand::RegisterIndirect(Register16::DE), value);
+    fn set_block_io_flags(&mut self, value: u8, addend: u8, counter: u8) {
+        // as measured on real Z80s, the carries come from adding the byte to C +/- 1 or to L
+        let sum = value as u16 + addend as u16;
+        let parity_source = (sum as u8 & 0x07) ^ counter;
+
+        self.set_flag(Flag::Sign, (counter as i8) < 0);
+        self.set_flag(Flag::Zero, counter == 0);
+        self.set_flag(Flag::HalfCarry, sum > 0xff);
+        self.set_flag(Flag::ParityOverflow, (parity_source.count_ones() & 1) == 0);
+        self.set_flag(Flag::AddSubtract, (value & 0x80) != 0);
+        self.set_flag(Flag::Carry, sum > 0xff);
+    }
 
-                let address = self.registers.read_word(&Register16::DE).wrapping_sub(1);
-                self.registers.write_word(&Register16::DE, address);
+    pub fn print_state(&self) {
+        // TODO: move this from CPU to register file
+        let ix = self.registers.read_word(&Register16::IX);
+        let iy = self.registers.read_word(&Register16::IY);
+        let hl = self.registers.read_word(&Register16::HL);
+        let de = self.registers.read_word(&Register16::DE);
+        let bc = self.registers.read_word(&Register16::BC);
+        let af = self.registers.read_word(&Register16::AF);
+        let sp = self.registers.read_word(&Register16::SP);
+        println!(
+            "IX = {:#06x}, IY = {:#06x}, HL = {:#06x}, DE = {:#06x}, BC = {:#06x}, AF = {:#06x}, SP = {:#06x}",
+            ix, iy, hl, de, bc, af, sp
+        );
 
-                let address = self.registers.read_word(&Register16::HL).wrapping_sub(1);
-                self.registers.write_word(&Register16::HL, address);
+        let sign = self.check_flag(Flag::Sign);
+        let zero = self.check_flag(Flag::Zero);
+        let half_carry = self.check_flag(Flag::HalfCarry);
+        let parity_oveflow = self.check_flag(Flag::ParityOverflow);
+        let add_subtract = self.check_flag(Flag::AddSubtract);
+        let carry = self.check_flag(Flag::Carry);
+        println!(
+            "S = {}, Z = {}, H = {}, P/V = {}, N = {}, C = {}",
+            sign, zero, half_carry, parity_oveflow, add_subtract, carry
+        );
+    }
+}
 
-                let counter = self.registers.read_word(&Register16::BC).wrapping_sub(1);
-                self.registers.write_word(&Register16::BC, counter);
+// instruction handlers, which only see the CPU and the instruction they are executing
+impl<M, B> CPU<M, B>
+where
+    M: memory::Read + memory::Write,
+    B: bus::Bus,
+{
+    // one handler per instruction, in the order of the Instruction enum
+    const HANDLERS: [Handler<M, B>; 71] = [
+        CPU::execute_adc,
+        CPU::execute_add,
+        CPU::execute_and,
+        CPU::execute_bit,
+        CPU::execute_call,
+        CPU::execute_ccf,
+        CPU::execute_cp,
+        CPU::execute_cpd,
+        CPU::execute_cpdr,
+        CPU::execute_cpi,
+        CPU::execute_cpir,
+        CPU::execute_cpl,
+        CPU::execute_daa,
+        CPU::execute_dec,
+        CPU::execute_defb,
+        CPU::execute_defw,
+        CPU::execute_di,
+        CPU::execute_djnz,
+        CPU::execute_ei,
+        CPU::execute_ex,
+        CPU::execute_exx,
+        CPU::execute_halt,
+        CPU::execute_im,
+        CPU::execute_in,
+        CPU::execute_ind,
+        CPU::execute_indr,
+        CPU::execute_ini,
+        CPU::execute_inir,
+        CPU::execute_inc,
+        CPU::execute_jp,
+        CPU::execute_jr,
+        CPU::execute_ld,
+        CPU::execute_ld_direct16,
+        CPU::execute_ldd,
+        CPU::execute_lddr,
+        CPU::execute_ldi,
+        CPU::execute_ldir,
+        CPU::execute_neg,
+        CPU::execute_nop,
+        CPU::execute_or,
+        CPU::execute_out,
+        CPU::execute_otdr,
+        CPU::execute_otir,
+        CPU::execute_outd,
+        CPU::execute_outi,
+        CPU::execute_pop,
+        CPU::execute_push,
+        CPU::execute_res,
+        CPU::execute_ret,
+        CPU::execute_unimplemented,
+        CPU::execute_unimplemented,
+        CPU::execute_rl,
+        CPU::execute_rla,
+        CPU::execute_rlc,
+        CPU::execute_rlca,
+        CPU::execute_rld,
+        CPU::execute_rr,
+        CPU::execute_rrc,
+        CPU::execute_rra,
+        CPU::execute_rrca,
+        CPU::execute_rrd,
+        CPU::execute_rst,
+        CPU::execute_sbc,
+        CPU::execute_scf,
+        CPU::execute_set,
+        CPU::execute_sla,
+        CPU::execute_sll,
+        CPU::execute_sra,
+        CPU::execute_srl,
+        CPU::execute_sub,
+        CPU::execute_xor,
+    ];
+
+    fn execute_adc(&mut self, instruction: &Instruction, execution: &mut Execution) {
+        if let Instruction::Adc(destination, source) = instruction {
+            match destination {
+                Operand::Register8(Register8::A) => {
+                    let left = self.load_byte(destination);
+                    let right = self.load_byte(source);
+                    let carry_value = if self.check_flag(Flag::Carry) { 1 } else { 0 };
+                    let (value, carry1) = right.overflowing_add(carry_value);
+                    let (value, carry2) = left.overflowing_add(value);
+                    let overflow =
+                        (left & 0x80) == (right & 0x80) && (right & 0x80) != (value & 0x80);
+                    self.store_byte(destination, value);
+
+                    self.set_flag(Flag::Sign, (value as i8) < 0);
+                    self.set_flag(Flag::Zero, value == 0);
+                    self.set_flag(
+                        Flag::HalfCarry,
+                        (((left & 0xf) + (right & 0xf) + carry_value) & 0x10) != 0,
+                    );
+                    self.set_flag(Flag::ParityOverflow, overflow);
+                    self.set_flag(Flag::AddSubtract, false);
+                    self.set_flag(Flag::Carry, carry1 || carry2);
+                }
+                Operand::Register16(Register16::HL) => {
+                    let left = self.load_word(destination);
+                    let right = self.load_word(source);
+                    let carry_value = if self.check_flag(Flag::Carry) { 1 } else { 0 };
+                    let (value, carry1) = right.overflowing_add(carry_value);
+                    let (value, carry2) = left.overflowing_add(value);
+                    let overflow = (left & 0x8000) == (right & 0x8000)
+                        && (right & 0x8000) != (value & 0x8000);
+                    self.store_word(destination, value);
+
+                    self.set_flag(Flag::Sign, (value as i16) < 0);
+                    self.set_flag(Flag::Zero, value == 0);
+                    self.set_flag(
+                        Flag::HalfCarry,
+                        (((left & 0xfff) + (right & 0xfff) + carry_value) & 0x1000) != 0,
+                    );
+                    self.set_flag(Flag::ParityOverflow, overflow);
+                    self.set_flag(Flag::AddSubtract, false);
+                    self.set_flag(Flag::Carry, carry1 || carry2);
+                }
+                _ => unreachable!(),
+            }
 
-                self.set_flag(Flag::HalfCarry, false);
-                self.set_flag(Flag::ParityOverflow, counter != 0);
-                self.set_flag(Flag::AddSubtract, false);
+            self.registers.write_word(&Register16::PC, execution.next_address);
+        }
+    }
 
-                if counter == 0 {
-                    self.registers
-                        .write_word(&Register16::PC, next_address as u16);
-                    timing_in_nops = 5; // not having to adjust the PC saves time
+    fn execute_add(&mut self, instruction: &Instruction, execution: &mut Execution) {
+        if let Instruction::Add(destination, source) = instruction {
+            match destination {
+                Operand::Register8(Register8::A) => {
+                    // ALUOP / checked
+                    let left = self.load_byte(destination);
+                    let right = self.load_byte(source);
+                    let (value, carry) = left.overflowing_add(right);
+                    let (_, overflow) = (left as i8).overflowing_add(right as i8);
+                    self.store_byte(destination, value);
+
+                    self.set_flag(Flag::Sign, (value as i8) < 0); // TODO: make this reusable?
+                    self.set_flag(Flag::Zero, value == 0);
+                    self.set_flag(
+                        Flag::HalfCarry,
+                        (((left & 0xf) + (right & 0xf)) & 0x10) != 0,
+                    );
+                    self.set_flag(Flag::ParityOverflow, overflow);
+                    self.set_flag(Flag::AddSubtract, false);
+                    self.set_flag(Flag::Carry, carry);
+                }
+                _ => {
+                    let left = self.load_word(destination);
+                    let right = self.load_word(source);
+                    let (value, carry) = left.overflowing_add(right);
+                    self.store_word(destination, value);
+
+                    self.set_flag(
+                        Flag::HalfCarry,
+                        (((left & 0xfff) + (right & 0xfff)) & 0x1000) != 0,
+                    );
+                    self.set_flag(Flag::AddSubtract, false);
+                    self.set_flag(Flag::Carry, carry);
                 }
             }
-            Instruction::Ldi => {
-                let value = self.load_byte(&Operand::RegisterIndirect(Register16::HL));
-                self.store_byte(&Operand::RegisterIndirect(Register16::DE), value);
 
-                let address = self.registers.read_word(&Register16::DE).wrapping_add(1);
-                self.registers.write_word(&Register16::DE, address);
+            self.registers.write_word(&Register16::PC, execution.next_address);
+        }
+    }
+
+    fn execute_and(&mut self, instruction: &Instruction, execution: &mut Execution) {
+        if let Instruction::And(operand) = instruction {
+            let value = self.load_byte(operand);
 
-                let address = self.registers.read_word(&Register16::HL).wrapping_add(1);
-                self.registers.write_word(&Register16::HL, address);
+            let result = self.registers.read_byte(&Register8::A) & value;
+            self.registers.write_byte(&Register8::A, result);
 
-                let counter = self.registers.read_word(&Register16::BC).wrapping_sub(1);
-                self.registers.write_word(&Register16::BC, counter);
+            self.set_flag(Flag::Sign, (result as i8) < 0);
+            self.set_flag(Flag::Zero, result == 0);
+            self.set_flag(Flag::HalfCarry, true);
+            self.set_flag(Flag::ParityOverflow, (result.count_ones() & 1) == 0);
+            self.set_flag(Flag::AddSubtract, false);
+            self.set_flag(Flag::Carry, false);
 
-                self.set_flag(Flag::HalfCarry, false);
-                self.set_flag(Flag::ParityOverflow, counter != 0);
-                self.set_flag(Flag::AddSubtract, false);
+            self.registers.write_word(&Register16::PC, execution.next_address);
+        }
+    }
+
+    fn execute_bit(&mut self, instruction: &Instruction, execution: &mut Execution) {
+        if let Instruction::Bit(Operand::Bit(bit), operand) = instruction {
+            let value = self.load_byte(operand);
+
+            self.set_flag(Flag::Zero, (value & (1 << bit)) == 0);
+            self.set_flag(Flag::HalfCarry, true);
+            self.set_flag(Flag::AddSubtract, false);
 
-                self.registers
-                    .write_word(&Register16::PC, next_address as u16);
+            self.registers.write_word(&Register16::PC, execution.next_address);
+        } else {
+            self.execute_unimplemented(instruction, execution);
+        }
+    }
+
+    fn execute_call(&mut self, instruction: &Instruction, execution: &mut Execution) {
+        if let Instruction::Call(jump_test, Operand::Immediate16(address)) = instruction {
+            if self.check_jump(jump_test) {
+                let new_sp = self.registers.read_word(&Register16::SP) - 2;
+                self.registers.write_word(&Register16::SP, new_sp);
+                self.write_memory_word(new_sp, execution.next_address);
+                self.registers.write_word(&Register16::PC, *address);
+            } else {
+                self.registers.write_word(&Register16::PC, execution.next_address);
             }
-            Instruction::Ldir => {
-                let value = self.load_byte(&Operand::RegisterIndirect(Register16::HL));
-                self.store_byte(&Operand::RegisterIndirect(Register16::DE), value);
+        } else {
+            self.execute_unimplemented(instruction, execution);
+        }
+    }
 
-                let address = self.registers.read_word(&Register16::DE).wrapping_add(1);
-                self.registers.write_word(&Register16::DE, address);
+    fn execute_ccf(&mut self, _instruction: &Instruction, execution: &mut Execution) {
+        let carry = self.check_flag(Flag::Carry);
 
-                let address = self.registers.read_word(&Register16::HL).wrapping_add(1);
-                self.registers.write_word(&Register16::HL, address);
+        self.set_flag(Flag::HalfCarry, carry);
+        self.set_flag(Flag::AddSubtract, false);
+        self.set_flag(Flag::Carry, !carry);
 
-                let counter = self.registers.read_word(&Register16::BC).wrapping_sub(1);
-                self.registers.write_word(&Register16::BC, counter);
+        self.registers.write_word(&Register16::PC, execution.next_address);
+    }
 
-                self.set_flag(Flag::HalfCarry, false);
-                self.set_flag(Flag::ParityOverflow, counter != 0);
-                self.set_flag(Flag::AddSubtract, false);
+    fn execute_cp(&mut self, instruction: &Instruction, execution: &mut Execution) {
+        if let Instruction::Cp(operand) = instruction {
+            // ALUOP / checked
+            let left = self.registers.read_byte(&Register8::A);
+            let right = self.load_byte(operand);
+            let (value, carry) = left.overflowing_sub(right);
+            let (_, overflow) = (left as i8).overflowing_sub(right as i8);
+
+            self.set_flag(Flag::Sign, (value as i8) < 0); // TODO: make this reusable?
+            self.set_flag(Flag::Zero, value == 0);
+            self.set_flag(Flag::HalfCarry, (left & 0xf) < (right & 0xf));
+            self.set_flag(Flag::ParityOverflow, overflow);
+            self.set_flag(Flag::AddSubtract, true);
+            self.set_flag(Flag::Carry, carry);
+
+            self.registers.write_word(&Register16::PC, execution.next_address);
+        }
+    }
+
+    fn execute_cpd(&mut self, _instruction: &Instruction, execution: &mut Execution) {
+        let left = self.registers.read_byte(&Register8::A);
+        let right = self.load_byte(&Operand::RegisterIndirect(Register16::HL));
+        let value = left.wrapping_sub(right);
+
+        let source = self.registers.read_word(&Register16::HL).wrapping_sub(1);
+        self.registers.write_word(&Register16::HL, source);
+
+        let counter = self.registers.read_word(&Register16::BC).wrapping_sub(1);
+        self.registers.write_word(&Register16::BC, counter);
+
+        self.set_flag(Flag::Sign, (value as i8) < 0);
+        self.set_flag(Flag::Zero, value == 0);
+        self.set_flag(Flag::HalfCarry, (left & 0xf) < (right & 0xf));
+        self.set_flag(Flag::ParityOverflow, counter != 0);
+        self.set_flag(Flag::AddSubtract, true);
+
+        self.registers.write_word(&Register16::PC, execution.next_address);
+    }
+
+    fn execute_cpdr(&mut self, _instruction: &Instruction, execution: &mut Execution) {
+        let left = self.registers.read_byte(&Register8::A);
+        let right = self.load_byte(&Operand::RegisterIndirect(Register16::HL));
+        let value = left.wrapping_sub(right);
+
+        let source = self.registers.read_word(&Register16::HL).wrapping_sub(1);
+        self.registers.write_word(&Register16::HL, source);
+
+        let counter = self.registers.read_word(&Register16::BC).wrapping_sub(1);
+        self.registers.write_word(&Register16::BC, counter);
+
+        self.set_flag(Flag::Sign, (value as i8) < 0);
+        self.set_flag(Flag::Zero, value == 0);
+        self.set_flag(Flag::HalfCarry, (left & 0xf) < (right & 0xf));
+        self.set_flag(Flag::ParityOverflow, counter != 0);
+        self.set_flag(Flag::AddSubtract, true);
+
+        if counter == 0 || value == 0 {
+            self.registers.write_word(&Register16::PC, execution.next_address);
+            execution.timing = 5; // not having to adjust the PC saves time
+        }
+    }
+
+    fn execute_cpi(&mut self, _instruction: &Instruction, execution: &mut Execution) {
+        let left = self.registers.read_byte(&Register8::A);
+        let right = self.load_byte(&Operand::RegisterIndirect(Register16::HL));
+        let value = left.wrapping_sub(right);
+
+        let source = self.registers.read_word(&Register16::HL).wrapping_add(1);
+        self.registers.write_word(&Register16::HL, source);
+
+        let counter = self.registers.read_word(&Register16::BC).wrapping_sub(1);
+        self.registers.write_word(&Register16::BC, counter);
+
+        self.set_flag(Flag::Sign, (value as i8) < 0);
+        self.set_flag(Flag::Zero, value == 0);
+        self.set_flag(Flag::HalfCarry, (left & 0xf) < (right & 0xf));
+        self.set_flag(Flag::ParityOverflow, counter != 0);
+        self.set_flag(Flag::AddSubtract, true);
+
+        self.registers.write_word(&Register16::PC, execution.next_address);
+    }
+
+    fn execute_cpir(&mut self, _instruction: &Instruction, execution: &mut Execution) {
+        let left = self.registers.read_byte(&Register8::A);
+        let right = self.load_byte(&Operand::RegisterIndirect(Register16::HL));
+        let value = left.wrapping_sub(right);
+
+        let source = self.registers.read_word(&Register16::HL).wrapping_add(1);
+        self.registers.write_word(&Register16::HL, source);
+
+        let counter = self.registers.read_word(&Register16::BC).wrapping_sub(1);
+        self.registers.write_word(&Register16::BC, counter);
 
-                if counter == 0 {
-                    self.registers
-                        .write_word(&Register16::PC, next_address as u16);
-                    timing_in_nops = 5; // not having to adjust the PC saves time
+        self.set_flag(Flag::Sign, (value as i8) < 0);
+        self.set_flag(Flag::Zero, value == 0);
+        self.set_flag(Flag::HalfCarry, (left & 0xf) < (right & 0xf));
+        self.set_flag(Flag::ParityOverflow, counter != 0);
+        self.set_flag(Flag::AddSubtract, true);
+
+        if counter == 0 || value == 0 {
+            self.registers.write_word(&Register16::PC, execution.next_address);
+            execution.timing = 5; // not having to adjust the PC saves time
+        }
+    }
+
+    fn execute_cpl(&mut self, _instruction: &Instruction, execution: &mut Execution) {
+        let value = self.registers.read_byte(&Register8::A);
+        self.registers.write_byte(&Register8::A, !value);
+
+        self.set_flag(Flag::HalfCarry, true);
+        self.set_flag(Flag::AddSubtract, true);
+
+        self.registers.write_word(&Register16::PC, execution.next_address);
+    }
+
+    fn execute_daa(&mut self, _instruction: &Instruction, execution: &mut Execution) {
+        let mut value = self.registers.read_byte(&Register8::A);
+        let mut half_carry = self.check_flag(Flag::HalfCarry);
+        let mut carry = self.check_flag(Flag::Carry);
+        let mut correction = 0;
+
+        if (value & 0xf) > 0x9 || half_carry {
+            half_carry = half_carry && (value & 0xf) < 0x6;
+            correction += 0x6;
+        }
+
+        if value > 0x99 || carry {
+            correction += 0x60;
+            carry = true;
+        }
+
+        if self.check_flag(Flag::AddSubtract) {
+            half_carry = half_carry && (value & 0xf) < 0x6;
+            value = value.wrapping_sub(correction);
+        } else {
+            half_carry = (value & 0xf) > 0x9;
+            value = value.wrapping_add(correction);
+        }
+
+        self.registers.write_byte(&Register8::A, value);
+
+        self.set_flag(Flag::Sign, (value as i8) < 0);
+        self.set_flag(Flag::Zero, value == 0);
+        self.set_flag(Flag::HalfCarry, half_carry);
+        self.set_flag(Flag::ParityOverflow, (value.count_ones() & 1) == 0);
+        self.set_flag(Flag::Carry, carry);
+
+        self.registers.write_word(&Register16::PC, execution.next_address);
+    }
+
+    fn execute_dec(&mut self, instruction: &Instruction, execution: &mut Execution) {
+        if let Instruction::Dec(destination) = instruction {
+            match destination {
+                Operand::Register16(register) => {
+                    let value = self.registers.read_word(register);
+                    self.registers.write_word(register, value.wrapping_sub(1));
+                }
+                _ => {
+                    let old_value = self.load_byte(destination);
+                    self.set_flag(Flag::ParityOverflow, old_value == 0x80);
+                    let (value, _) = old_value.overflowing_sub(1);
+                    self.store_byte(destination, value);
+
+                    self.set_flag(Flag::Sign, (value as i8) < 0);
+                    self.set_flag(Flag::Zero, value == 0);
+                    self.set_flag(Flag::HalfCarry, (old_value & 0xf) < 1);
+                    self.set_flag(Flag::AddSubtract, true);
                 }
             }
-            Instruction::Neg => {
-                let right = self.registers.read_byte(&Register8::A);
-                let (value, carry) = (0_u8).overflowing_sub(right);
-                let (_, overflow) = (0_i8).overflowing_sub(right as i8);
-                self.registers.write_byte(&Register8::A, value);
 
-                self.set_flag(Flag::Sign, (value as i8) < 0); // TODO: make this reusable?
-                self.set_flag(Flag::Zero, value == 0);
-                self.set_flag(Flag::HalfCarry, 0 < (right & 0xf));
-                self.set_flag(Flag::ParityOverflow, overflow);
-                self.set_flag(Flag::AddSubtract, true);
-                self.set_flag(Flag::Carry, carry);
+            self.registers.write_word(&Register16::PC, execution.next_address);
+        }
+    }
 
-                self.registers
-                    .write_word(&Register16::PC, next_address as u16);
-            }
-            Instruction::Nop => {
-                self.registers
-                    .write_word(&Register16::PC, next_address as u16);
-            }
-            Instruction::Or(operand) => {
-                let value = self.load_byte(operand);
+    fn execute_defb(&mut self, instruction: &Instruction, execution: &mut Execution) {
+        if let Instruction::Defb(_operand) = instruction {
+            execution.prevent_interrupt = true;
+            self.registers.write_word(&Register16::PC, execution.next_address);
+        }
+    }
 
-                let result = self.registers.read_byte(&Register8::A) | value;
-                self.registers.write_byte(&Register8::A, result);
+    fn execute_defw(&mut self, instruction: &Instruction, execution: &mut Execution) {
+        if let Instruction::Defw(_operand) = instruction {
+            // TODO: disable interrupts??? or not???
+            self.registers.write_word(&Register16::PC, execution.next_address);
+        }
+    }
 
-                self.set_flag(Flag::Sign, (result as i8) < 0); // TODO: make this reusable?
-                self.set_flag(Flag::Zero, result == 0);
-                self.set_flag(Flag::HalfCarry, false);
-                self.set_flag(Flag::ParityOverflow, (result.count_ones() & 1) == 0);
-                self.set_flag(Flag::AddSubtract, false);
-                self.set_flag(Flag::Carry, false);
+    fn execute_di(&mut self, _instruction: &Instruction, execution: &mut Execution) {
+        self.iff1 = false;
+        self.iff2 = false;
 
-                self.registers
-                    .write_word(&Register16::PC, next_address as u16);
-            }
-            Instruction::Out(Operand::RegisterIndirect(port), Operand::Register8(source)) => {
-                let address = self.registers.read_word(port);
-                let value = self.registers.read_byte(source);
-                self.bus.borrow_mut().write_byte(address, value);
+        self.registers.write_word(&Register16::PC, execution.next_address);
+    }
 
-                self.registers
-                    .write_word(&Register16::PC, next_address as u16);
-            }
-            Instruction::Out(Operand::RegisterIndirect(port), Operand::Immediate8(value)) => {
-                // TODO: make this a special case of the other OUT instruction above
-                let address = self.registers.read_word(port);
-                self.bus.borrow_mut().write_byte(address, *value);
+    fn execute_djnz(&mut self, instruction: &Instruction, execution: &mut Execution) {
+        if let Instruction::Djnz(target) = instruction {
+            let value = self.registers.read_byte(&Register8::B).wrapping_sub(1);
+            self.registers.write_byte(&Register8::B, value);
 
-                self.registers
-                    .write_word(&Register16::PC, next_address as u16);
-            }
-            Instruction::Pop(Operand::Register16(destination)) => {
-                let old_sp = self.registers.read_word(&Register16::SP);
-                self.registers.write_word(&Register16::SP, old_sp + 2);
-                self.registers.write_word(
-                    destination,
-                    self.memory.borrow().read_word(old_sp as usize),
-                );
-                self.registers
-                    .write_word(&Register16::PC, next_address as u16);
-            }
-            Instruction::Push(Operand::Register16(source)) => {
-                let new_sp = self.registers.read_word(&Register16::SP) - 2;
-                self.registers.write_word(&Register16::SP, new_sp);
-                self.memory
-                    .borrow_mut()
-                    .write_word(new_sp as usize, self.registers.read_word(source));
-                self.registers
-                    .write_word(&Register16::PC, next_address as u16);
+            if value != 0 {
+                let address = match target {
+                    Operand::Immediate16(address) => address,
+                    _ => {
+                        unimplemented!();
+                    }
+                };
+                self.registers.write_word(&Register16::PC, *address);
+                execution.timing = 4;
+            } else {
+                self.registers.write_word(&Register16::PC, execution.next_address);
             }
-            Instruction::Res(destination, Operand::Bit(bit), operand) => {
-                let value = self.load_byte(operand) & (!(1 << bit));
-                self.store_byte(destination, value); // copy for undocumented instructions
-                self.store_byte(operand, value);
+        }
+    }
 
-                self.registers
-                    .write_word(&Register16::PC, next_address as u16);
-            }
-            Instruction::Ret(jump_test) => {
-                if self.check_jump(jump_test) {
-                    let old_sp = self.registers.read_word(&Register16::SP);
-                    self.registers.write_word(&Register16::SP, old_sp + 2);
-                    self.registers.write_word(
-                        &Register16::PC,
-                        self.memory.borrow().read_word(old_sp as usize),
-                    );
-                } else {
-                    self.registers
-                        .write_word(&Register16::PC, next_address as u16);
+    fn execute_ei(&mut self, _instruction: &Instruction, execution: &mut Execution) {
+        // the flip-flops are set at once, which LD A,I shows, but an interrupt is first accepted
+        // after the next instruction. That also holds for a series of EIs, each delays it further
+        self.iff1 = true;
+        self.iff2 = true;
+        execution.prevent_interrupt = true;
+
+        self.registers.write_word(&Register16::PC, execution.next_address);
+    }
+
+    fn execute_ex(&mut self, instruction: &Instruction, execution: &mut Execution) {
+        if let Instruction::Ex(left, right) = instruction {
+            match (left, right) {
+                (Operand::Register16(Register16::AF), Operand::Register16(Register16::AF)) => { // TODO: test if this match arm works with references
+                    self.registers.swap_word(&Register16::AF);
+                }
+                (left, right) => {
+                    let left_value = self.load_word(left);
+                    let right_value = self.load_word(right);
+
+                    self.store_word(left, right_value);
+                    self.store_word(right, left_value);
                 }
             }
-            Instruction::Rl(destination, operand) => {
-                let value = self.load_byte(operand);
-                let carry_value = if self.check_flag(Flag::Carry) { 1 } else { 0 };
-                let carry = (value >> 7) != 0;
-                let value = (value.rotate_left(1) & 0xfe) | carry_value;
-                self.store_byte(destination, value); // copy for undocumented instructions
-                self.store_byte(operand, value);
 
-                self.set_flag(Flag::Sign, (value as i8) < 0);
-                self.set_flag(Flag::Zero, value == 0);
-                self.set_flag(Flag::HalfCarry, false);
-                self.set_flag(Flag::ParityOverflow, (value.count_ones() & 1) == 0);
-                self.set_flag(Flag::AddSubtract, false);
-                self.set_flag(Flag::Carry, carry);
+            self.registers.write_word(&Register16::PC, execution.next_address);
+        }
+    }
 
-                self.registers
-                    .write_word(&Register16::PC, next_address as u16);
-            }
-            Instruction::Rla => {
-                let value = self.registers.read_byte(&Register8::A);
-                let carry_value = if self.check_flag(Flag::Carry) { 1 } else { 0 };
-                let carry = (value >> 7) != 0;
-                let result = (value.rotate_left(1) & 0xfe) | carry_value;
-                self.registers.write_byte(&Register8::A, result);
+    fn execute_exx(&mut self, _instruction: &Instruction, execution: &mut Execution) {
+        self.registers.swap_word(&Register16::BC);
+        self.registers.swap_word(&Register16::DE);
+        self.registers.swap_word(&Register16::HL);
 
-                self.set_flag(Flag::HalfCarry, false);
-                self.set_flag(Flag::AddSubtract, false);
-                self.set_flag(Flag::Carry, carry);
+        self.registers.write_word(&Register16::PC, execution.next_address);
+    }
 
-                self.registers
-                    .write_word(&Register16::PC, next_address as u16);
-            }
-            Instruction::Rlc(destination, operand) => {
-                let value = self.load_byte(operand).rotate_left(1);
-                self.store_byte(destination, value); // copy for undocumented instructions
-                self.store_byte(operand, value);
+    fn execute_halt(&mut self, _instruction: &Instruction, execution: &mut Execution) {
+        // PC stays on the HALT instruction, which keeps executing NOPs until an interrupt occurs
+        self.halted = true;
+    }
 
-                self.set_flag(Flag::Sign, (value as i8) < 0);
-                self.set_flag(Flag::Zero, value == 0);
-                self.set_flag(Flag::HalfCarry, false);
-                self.set_flag(Flag::ParityOverflow, (value.count_ones() & 1) == 0);
-                self.set_flag(Flag::AddSubtract, false);
-                self.set_flag(Flag::Carry, (value & 1) != 0);
+    fn execute_im(&mut self, instruction: &Instruction, execution: &mut Execution) {
+        if let Instruction::Im(mode) = instruction {
+            self.interrupt_mode = *mode;
 
-                self.registers
-                    .write_word(&Register16::PC, next_address as u16);
-            }
-            Instruction::Rlca => {
-                let value = self.registers.read_byte(&Register8::A);
-                let result = value.rotate_left(1);
-                self.registers.write_byte(&Register8::A, result);
+            self.registers.write_word(&Register16::PC, execution.next_address);
+        }
+    }
 
-                self.set_flag(Flag::HalfCarry, false);
-                self.set_flag(Flag::AddSubtract, false);
-                self.set_flag(Flag::Carry, (result & 1) != 0);
+    fn execute_in(&mut self, instruction: &Instruction, execution: &mut Execution) {
+        match instruction {
+            Instruction::In(Operand::Register8(Register8::A), Operand::Direct8(port_low)) => {
+                let port_high = self.registers.read_byte(&Register8::A);
+                let port = (port_high as u16) << 8 | (*port_low as u16);
 
-                self.registers
-                    .write_word(&Register16::PC, next_address as u16);
+                let value = self.read_port(port);
+                self.registers.write_byte(&Register8::A, value);
+
+                self.registers.write_word(&Register16::PC, execution.next_address);
             }
-            Instruction::Rr(destination, operand) => {
-                let value = self.load_byte(operand);
-                let carry_value = if self.check_flag(Flag::Carry) { 1 } else { 0 };
-                let carry = (value & 1) != 0;
-                let value = (value.rotate_right(1) & 0x7f) | (carry_value << 7);
-                self.store_byte(destination, value); // copy for undocumented instructions
-                self.store_byte(operand, value);
+            Instruction::In(Operand::Register8(destination), Operand::RegisterIndirect(Register16::BC)) => {
+                // TODO: make this a special case of the other IN instruction above?
+                let port = self.registers.read_word(&Register16::BC);
+
+                let value = self.read_port(port);
+                self.registers.write_byte(destination, value);
 
+                // unlike IN A,(n) this form affects the flags, which polling loops like the one in AMSDOS rely on
                 self.set_flag(Flag::Sign, (value as i8) < 0);
                 self.set_flag(Flag::Zero, value == 0);
                 self.set_flag(Flag::HalfCarry, false);
                 self.set_flag(Flag::ParityOverflow, (value.count_ones() & 1) == 0);
                 self.set_flag(Flag::AddSubtract, false);
-                self.set_flag(Flag::Carry, carry);
 
-                self.registers
-                    .write_word(&Register16::PC, next_address as u16);
+                self.registers.write_word(&Register16::PC, execution.next_address);
             }
-            Instruction::Rra => {
-                let value = self.registers.read_byte(&Register8::A);
-                let carry_value = if self.check_flag(Flag::Carry) { 1 } else { 0 };
-                let carry = (value & 1) != 0;
-                let result = (value.rotate_right(1) & 0x7f) | (carry_value << 7);
-                self.registers.write_byte(&Register8::A, result);
+            _ => self.execute_unimplemented(instruction, execution),
+        }
+    }
 
-                self.set_flag(Flag::HalfCarry, false);
-                self.set_flag(Flag::AddSubtract, false);
-                self.set_flag(Flag::Carry, carry);
+    fn execute_ind(&mut self, _instruction: &Instruction, execution: &mut Execution) {
+        self.input_block_byte(0xffff);
 
-                self.registers
-                    .write_word(&Register16::PC, next_address as u16);
-            }
-            Instruction::Rrc(destination, operand) => {
-                let value = self.load_byte(operand).rotate_right(1);
-                self.store_byte(destination, value); // copy for undocumented instructions
-                self.store_byte(operand, value);
+        self.registers.write_word(&Register16::PC, execution.next_address);
+    }
 
-                self.set_flag(Flag::Sign, (value as i8) < 0);
-                self.set_flag(Flag::Zero, value == 0);
-                self.set_flag(Flag::HalfCarry, false);
-                self.set_flag(Flag::ParityOverflow, (value.count_ones() & 1) == 0);
-                self.set_flag(Flag::AddSubtract, false);
-                self.set_flag(Flag::Carry, (value & 0x80) != 0);
+    fn execute_indr(&mut self, _instruction: &Instruction, execution: &mut Execution) {
+        let counter = self.input_block_byte(0xffff);
 
-                self.registers
-                    .write_word(&Register16::PC, next_address as u16);
-            }
-            Instruction::Rrca => {
-                let value = self.registers.read_byte(&Register8::A);
-                let result = value.rotate_right(1);
-                self.registers.write_byte(&Register8::A, result);
+        if counter == 0 {
+            self.registers.write_word(&Register16::PC, execution.next_address);
+            execution.timing = 5; // not having to adjust the PC saves time
+        }
+    }
 
-                self.set_flag(Flag::HalfCarry, false);
-                self.set_flag(Flag::AddSubtract, false);
-                self.set_flag(Flag::Carry, (result & 0x80) != 0);
+    fn execute_ini(&mut self, _instruction: &Instruction, execution: &mut Execution) {
+        self.input_block_byte(1);
 
-                self.registers
-                    .write_word(&Register16::PC, next_address as u16);
-            }
-            Instruction::Rld => {
-                let accumulator = self.registers.read_byte(&Register8::A);
-                let memory_hl = self.load_byte(&Operand::RegisterIndirect(Register16::HL));
-                let new_accumulator = (accumulator & 0xf0) | (memory_hl >> 4);
-                self.registers.write_byte(&Register8::A, new_accumulator);
-                let new_memory_hl = (memory_hl << 4) | (accumulator & 0xf);
-                self.store_byte(&Operand::RegisterIndirect(Register16::HL), new_memory_hl);
-
-                self.set_flag(Flag::Sign, (new_accumulator as i8) < 0);
-                self.set_flag(Flag::Zero, new_accumulator == 0);
-                self.set_flag(Flag::HalfCarry, false);
-                self.set_flag(
-                    Flag::ParityOverflow,
-                    (new_accumulator.count_ones() & 1) == 0,
-                );
-                self.set_flag(Flag::AddSubtract, false);
+        self.registers.write_word(&Register16::PC, execution.next_address);
+    }
 
-                self.registers
-                    .write_word(&Register16::PC, next_address as u16);
-            }
-            Instruction::Rrd => {
-                let accumulator = self.registers.read_byte(&Register8::A);
-                let memory_hl = self.load_byte(&Operand::RegisterIndirect(Register16::HL));
-                let new_accumulator = (accumulator & 0xf0) | (memory_hl & 0xf);
-                self.registers.write_byte(&Register8::A, new_accumulator);
-                let new_memory_hl = ((accumulator & 0xf) << 4) | (memory_hl >> 4);
-                self.store_byte(&Operand::RegisterIndirect(Register16::HL), new_memory_hl);
-
-                self.set_flag(Flag::Sign, (new_accumulator as i8) < 0);
-                self.set_flag(Flag::Zero, new_accumulator == 0);
-                self.set_flag(Flag::HalfCarry, false);
-                self.set_flag(
-                    Flag::ParityOverflow,
-                    (new_accumulator.count_ones() & 1) == 0,
-                );
-                self.set_flag(Flag::AddSubtract, false);
+    fn execute_inir(&mut self, _instruction: &Instruction, execution: &mut Execution) {
+        let counter = self.input_block_byte(1);
 
-                self.registers
-                    .write_word(&Register16::PC, next_address as u16);
-            }
-            Instruction::Rst(target) => {
-                let new_sp = self.registers.read_word(&Register16::SP) - 2;
-                self.registers.write_word(&Register16::SP, new_sp);
-                self.memory
-                    .borrow_mut()
-                    .write_word(new_sp as usize, next_address as u16);
-                let address_lower = self.load_byte(target);
-                
-                self.registers.write_word(&Register16::PC, address_lower as u16);
+        if counter == 0 {
+            self.registers.write_word(&Register16::PC, execution.next_address);
+            execution.timing = 5; // not having to adjust the PC saves time
+        }
+    }
+
+    fn execute_inc(&mut self, instruction: &Instruction, execution: &mut Execution) {
+        if let Instruction::Inc(destination) = instruction {
+            match destination {
+                Operand::Register16(register) => {
+                    let value = self.registers.read_word(register);
+                    self.registers.write_word(&register, value.wrapping_add(1));
+                }
+                _ => {
+                    let old_value = self.load_byte(destination);
+                    self.set_flag(Flag::ParityOverflow, old_value == 0x7f);
+                    let (value, _) = old_value.overflowing_add(1);
+                    self.store_byte(destination, value);
+
+                    self.set_flag(Flag::Sign, (value as i8) < 0);
+                    self.set_flag(Flag::Zero, value == 0);
+                    self.set_flag(Flag::HalfCarry, (((old_value & 0xf) + 1) & 0x10) != 0);
+                    self.set_flag(Flag::AddSubtract, false);
+                }
             }
-            Instruction::Sbc(destination, source) => {
-                match destination {
-                    Operand::Register8(Register8::A) => {
-                        let left = self.load_byte(destination);
-                        let right = self.load_byte(source);
-                        let carry_value = if self.check_flag(Flag::Carry) { 1 } else { 0 };
-                        let (value, carry1) = right.overflowing_add(carry_value);
-                        let (value, carry2) = left.overflowing_sub(value);
-                        let overflow =
-                            (left & 0x80) != (right & 0x80) && (right & 0x80) == (value & 0x80);
-                        self.store_byte(destination, value);
-
-                        self.set_flag(Flag::Sign, (value as i8) < 0);
-                        self.set_flag(Flag::Zero, value == 0);
-                        self.set_flag(Flag::HalfCarry, (left & 0xf) < (right & 0xf) + carry_value);
-                        self.set_flag(Flag::ParityOverflow, overflow);
-                        self.set_flag(Flag::AddSubtract, true);
-                        self.set_flag(Flag::Carry, carry1 || carry2);
-                    }
-                    Operand::Register16(Register16::HL) => {
-                        let left = self.load_word(destination);
-                        let right = self.load_word(source);
-                        let carry_value = if self.check_flag(Flag::Carry) { 1 } else { 0 };
-                        let (value, carry1) = right.overflowing_add(carry_value);
-                        let (value, carry2) = left.overflowing_sub(value);
-                        let overflow = (left & 0x8000) != (right & 0x8000)
-                            && (right & 0x8000) == (value & 0x8000);
-                        self.store_word(destination, value);
-
-                        self.set_flag(Flag::Sign, (value as i16) < 0);
-                        self.set_flag(Flag::Zero, value == 0);
-                        self.set_flag(
-                            Flag::HalfCarry,
-                            (left & 0xfff) < (right & 0xfff) + carry_value,
-                        );
-                        self.set_flag(Flag::ParityOverflow, overflow);
-                        self.set_flag(Flag::AddSubtract, true);
-                        self.set_flag(Flag::Carry, carry1 || carry2);
+
+            self.registers.write_word(&Register16::PC, execution.next_address);
+        }
+    }
+
+    fn execute_jp(&mut self, instruction: &Instruction, execution: &mut Execution) {
+        if let Instruction::Jp(jump_test, target) = instruction {
+            if self.check_jump(jump_test) {
+                let address = match target {
+                    Operand::RegisterIndirect(register) => {
+                        self.registers.read_word(register) // special case for JP (HL) and friends
                     }
-                    _ => unreachable!(),
-                }
+                    _ => self.load_word(target),
+                };
 
-                self.registers
-                    .write_word(&Register16::PC, next_address as u16);
+                self.registers.write_word(&Register16::PC, address);
+            } else {
+                self.registers.write_word(&Register16::PC, execution.next_address);
             }
-            Instruction::Scf => {
-                self.set_flag(Flag::HalfCarry, false);
-                self.set_flag(Flag::AddSubtract, false);
-                self.set_flag(Flag::Carry, true);
+        }
+    }
 
-                self.registers
-                    .write_word(&Register16::PC, next_address as u16);
+    fn execute_jr(&mut self, instruction: &Instruction, execution: &mut Execution) {
+        if let Instruction::Jr(jump_test, target) = instruction {
+            if self.check_jump(jump_test) {
+                let address = match target {
+                    Operand::Immediate16(address) => address,
+                    _ => {
+                        unimplemented!();
+                    }
+                };
+                self.registers.write_word(&Register16::PC, *address);
+            } else {
+                self.registers.write_word(&Register16::PC, execution.next_address);
+                execution.timing = 2;
             }
-            Instruction::Set(destination, Operand::Bit(bit), operand) => {
-                let value = self.load_byte(operand) | (1 << bit);
-                self.store_byte(destination, value); // copy for undocumented instructions
-                self.store_byte(operand, value);
+        }
+    }
 
-                self.registers
-                    .write_word(&Register16::PC, next_address as u16);
+    fn execute_ld(&mut self, instruction: &Instruction, execution: &mut Execution) {
+        if let Instruction::Ld(destination, source) = instruction {
+            match (&destination, &source) {
+                (Operand::Register16(_), _) => {
+                    let value = self.load_word(source);
+                    self.store_word(destination, value);
+                }
+                (_, Operand::Register16(_)) => {
+                    let value = self.load_word(source);
+                    self.store_word(destination, value);
+                }
+                _ => {
+                    // TODO: store iff2 in parity flag if instruction is ld a,i or ld a,r
+                    let value = self.load_byte(source);
+                    self.store_byte(destination, value);
+                }
             }
-            Instruction::Sla(destination, operand) => {
-                let value = self.load_byte(operand);
-                let carry = (value >> 7) != 0;
-                let result = value << 1;
-                self.store_byte(destination, result); // copy for undocumented instructions
-                self.store_byte(operand, result);
-
-                self.set_flag(Flag::Sign, (result as i8) < 0); // TODO: make this reusable?
-                self.set_flag(Flag::Zero, result == 0);
-                self.set_flag(Flag::HalfCarry, false);
-                self.set_flag(Flag::ParityOverflow, (result.count_ones() & 1) == 0);
-                self.set_flag(Flag::AddSubtract, false);
-                self.set_flag(Flag::Carry, carry);
 
-                self.registers
-                    .write_word(&Register16::PC, next_address as u16);
-            }
-            Instruction::Sll(destination, operand) => {
-                let value = self.load_byte(operand);
-                let carry = (value >> 7) != 0;
-                let result = (value << 1) | 1;
-                self.store_byte(destination, result); // copy for undocumented instructions
-                self.store_byte(operand, result);
-
-                self.set_flag(Flag::Sign, (result as i8) < 0); // TODO: make this reusable?
-                self.set_flag(Flag::Zero, result == 0);
-                self.set_flag(Flag::HalfCarry, false);
-                self.set_flag(Flag::ParityOverflow, (result.count_ones() & 1) == 0);
-                self.set_flag(Flag::AddSubtract, false);
-                self.set_flag(Flag::Carry, carry);
+            self.registers.write_word(&Register16::PC, execution.next_address);
+        }
+    }
 
-                self.registers
-                    .write_word(&Register16::PC, next_address as u16);
-            }
-            Instruction::Sra(destination, operand) => {
-                let value = self.load_byte(operand);
-                let sign = value & 0x80;
-                let carry = (value & 1) != 0;
-                let result = sign | (value >> 1);
-                self.store_byte(destination, result); // copy for undocumented instructions
-                self.store_byte(operand, result);
-
-                self.set_flag(Flag::Sign, (result as i8) < 0); // TODO: make this reusable?
-                self.set_flag(Flag::Zero, result == 0);
-                self.set_flag(Flag::HalfCarry, false);
-                self.set_flag(Flag::ParityOverflow, (result.count_ones() & 1) == 0);
-                self.set_flag(Flag::AddSubtract, false);
-                self.set_flag(Flag::Carry, carry);
+    fn execute_ld_direct16(&mut self, instruction: &Instruction, execution: &mut Execution) {
+        if let Instruction::LdDirect16(destination, source) = instruction {
+            let value = self.load_word(source);
+            self.store_word(destination, value);
+
+            self.registers.write_word(&Register16::PC, execution.next_address);
+        }
+    }
+
+    fn execute_ldd(&mut self, _instruction: &Instruction, execution: &mut Execution) {
+        let value = self.load_byte(&Operand::RegisterIndirect(Register16::HL));
+        self.store_byte(&Operand::RegisterIndirect(Register16::DE), value);
+
+        let address = self.registers.read_word(&Register16::DE).wrapping_sub(1);
+        self.registers.write_word(&Register16::DE, address);
+
+        let address = self.registers.read_word(&Register16::HL).wrapping_sub(1);
+        self.registers.write_word(&Register16::HL, address);
+
+        let counter = self.registers.read_word(&Register16::BC).wrapping_sub(1);
+        self.registers.write_word(&Register16::BC, counter);
+
+        self.set_flag(Flag::HalfCarry, false);
+        self.set_flag(Flag::ParityOverflow, counter != 0);
+        self.set_flag(Flag::AddSubtract, false);
+
+        self.registers.write_word(&Register16::PC, execution.next_address);
+    }
+
+    fn execute_lddr(&mut self, _instruction: &Instruction, execution: &mut Execution) {
+        let value = self.load_byte(&Operand::RegisterIndirect(Register16::HL));
+        self.store_byte(&Operand::RegisterIndirect(Register16::DE), value);
+
+        let address = self.registers.read_word(&Register16::DE).wrapping_sub(1);
+        self.registers.write_word(&Register16::DE, address);
+
+        let address = self.registers.read_word(&Register16::HL).wrapping_sub(1);
+        self.registers.write_word(&Register16::HL, address);
+
+        let counter = self.registers.read_word(&Register16::BC).wrapping_sub(1);
+        self.registers.write_word(&Register16::BC, counter);
+
+        self.set_flag(Flag::HalfCarry, false);
+        self.set_flag(Flag::ParityOverflow, counter != 0);
+        self.set_flag(Flag::AddSubtract, false);
+
+        if counter == 0 {
+            self.registers.write_word(&Register16::PC, execution.next_address);
+            execution.timing = 5; // not having to adjust the PC saves time
+        }
+    }
+
+    fn execute_ldi(&mut self, _instruction: &Instruction, execution: &mut Execution) {
+        let value = self.load_byte(&Operand::RegisterIndirect(Register16::HL));
+        self.store_byte(&Operand::RegisterIndirect(Register16::DE), value);
+
+        let address = self.registers.read_word(&Register16::DE).wrapping_add(1);
+        self.registers.write_word(&Register16::DE, address);
+
+        let address = self.registers.read_word(&Register16::HL).wrapping_add(1);
+        self.registers.write_word(&Register16::HL, address);
+
+        let counter = self.registers.read_word(&Register16::BC).wrapping_sub(1);
+        self.registers.write_word(&Register16::BC, counter);
+
+        self.set_flag(Flag::HalfCarry, false);
+        self.set_flag(Flag::ParityOverflow, counter != 0);
+        self.set_flag(Flag::AddSubtract, false);
+
+        self.registers.write_word(&Register16::PC, execution.next_address);
+    }
 
-                self.registers
-                    .write_word(&Register16::PC, next_address as u16);
+    fn execute_ldir(&mut self, _instruction: &Instruction, execution: &mut Execution) {
+        let value = self.load_byte(&Operand::RegisterIndirect(Register16::HL));
+        self.store_byte(&Operand::RegisterIndirect(Register16::DE), value);
+
+        let address = self.registers.read_word(&Register16::DE).wrapping_add(1);
+        self.registers.write_word(&Register16::DE, address);
+
+        let address = self.registers.read_word(&Register16::HL).wrapping_add(1);
+        self.registers.write_word(&Register16::HL, address);
+
+        let counter = self.registers.read_word(&Register16::BC).wrapping_sub(1);
+        self.registers.write_word(&Register16::BC, counter);
+
+        self.set_flag(Flag::HalfCarry, false);
+        self.set_flag(Flag::ParityOverflow, counter != 0);
+        self.set_flag(Flag::AddSubtract, false);
+
+        if counter == 0 {
+            self.registers.write_word(&Register16::PC, execution.next_address);
+            execution.timing = 5; // not having to adjust the PC saves time
+        }
+    }
+
+    fn execute_neg(&mut self, _instruction: &Instruction, execution: &mut Execution) {
+        let right = self.registers.read_byte(&Register8::A);
+        let (value, carry) = (0_u8).overflowing_sub(right);
+        let (_, overflow) = (0_i8).overflowing_sub(right as i8);
+        self.registers.write_byte(&Register8::A, value);
+
+        self.set_flag(Flag::Sign, (value as i8) < 0); // TODO: make this reusable?
+        self.set_flag(Flag::Zero, value == 0);
+        self.set_flag(Flag::HalfCarry, 0 < (right & 0xf));
+        self.set_flag(Flag::ParityOverflow, overflow);
+        self.set_flag(Flag::AddSubtract, true);
+        self.set_flag(Flag::Carry, carry);
+
+        self.registers.write_word(&Register16::PC, execution.next_address);
+    }
+
+    fn execute_nop(&mut self, _instruction: &Instruction, execution: &mut Execution) {
+        self.registers.write_word(&Register16::PC, execution.next_address);
+    }
+
+    fn execute_or(&mut self, instruction: &Instruction, execution: &mut Execution) {
+        if let Instruction::Or(operand) = instruction {
+            let value = self.load_byte(operand);
+
+            let result = self.registers.read_byte(&Register8::A) | value;
+            self.registers.write_byte(&Register8::A, result);
+
+            self.set_flag(Flag::Sign, (result as i8) < 0); // TODO: make this reusable?
+            self.set_flag(Flag::Zero, result == 0);
+            self.set_flag(Flag::HalfCarry, false);
+            self.set_flag(Flag::ParityOverflow, (result.count_ones() & 1) == 0);
+            self.set_flag(Flag::AddSubtract, false);
+            self.set_flag(Flag::Carry, false);
+
+            self.registers.write_word(&Register16::PC, execution.next_address);
+        }
+    }
+
+    fn execute_out(&mut self, instruction: &Instruction, execution: &mut Execution) {
+        match instruction {
+            Instruction::Out(Operand::RegisterIndirect(port), Operand::Register8(source)) => {
+                let address = self.registers.read_word(port);
+                let value = self.registers.read_byte(source);
+                self.write_port(address, value);
+
+                self.registers.write_word(&Register16::PC, execution.next_address);
             }
-            Instruction::Srl(destination, operand) => {
-                let value = self.load_byte(operand);
-                let carry = (value & 1) != 0;
-                let result = value >> 1;
-                self.store_byte(destination, result); // copy for undocumented instructions
-                self.store_byte(operand, result);
-
-                self.set_flag(Flag::Sign, (result as i8) < 0); // TODO: make this reusable?
-                self.set_flag(Flag::Zero, result == 0);
-                self.set_flag(Flag::HalfCarry, false);
-                self.set_flag(Flag::ParityOverflow, (result.count_ones() & 1) == 0);
-                self.set_flag(Flag::AddSubtract, false);
-                self.set_flag(Flag::Carry, carry);
+            Instruction::Out(Operand::RegisterIndirect(port), Operand::Immediate8(value)) => {
+                // TODO: make this a special case of the other OUT instruction above
+                let address = self.registers.read_word(port);
+                self.write_port(address, *value);
 
-                self.registers
-                    .write_word(&Register16::PC, next_address as u16);
+                self.registers.write_word(&Register16::PC, execution.next_address);
             }
-            Instruction::Sub(operand) => {
-                // ALUOP / checked
-                let left = self.registers.read_byte(&Register8::A);
-                let right = self.load_byte(operand);
-                let (value, carry) = left.overflowing_sub(right);
-                let (_, overflow) = (left as i8).overflowing_sub(right as i8);
-                self.registers.write_byte(&Register8::A, value);
+            _ => self.execute_unimplemented(instruction, execution),
+        }
+    }
 
-                self.set_flag(Flag::Sign, (value as i8) < 0); // TODO: make this reusable?
-                self.set_flag(Flag::Zero, value == 0);
-                self.set_flag(Flag::HalfCarry, (left & 0xf) < (right & 0xf));
-                self.set_flag(Flag::ParityOverflow, overflow);
-                self.set_flag(Flag::AddSubtract, true);
-                self.set_flag(Flag::Carry, carry);
+    fn execute_otdr(&mut self, _instruction: &Instruction, execution: &mut Execution) {
+        let counter = self.output_block_byte(0xffff);
 
-                self.registers
-                    .write_word(&Register16::PC, next_address as u16);
-            }
-            Instruction::Xor(operand) => {
-                let value = self.load_byte(operand);
+        if counter == 0 {
+            self.registers.write_word(&Register16::PC, execution.next_address);
+            execution.timing = 5; // not having to adjust the PC saves time
+        }
+    }
 
-                let result = self.registers.read_byte(&Register8::A) ^ value;
-                self.registers.write_byte(&Register8::A, result);
+    fn execute_otir(&mut self, _instruction: &Instruction, execution: &mut Execution) {
+        let counter = self.output_block_byte(1);
 
-                self.set_flag(Flag::Sign, (result as i8) < 0); // TODO: make this reusable?
-                self.set_flag(Flag::Zero, result == 0);
-                self.set_flag(Flag::HalfCarry, false);
-                self.set_flag(Flag::ParityOverflow, (result.count_ones() & 1) == 0);
-                self.set_flag(Flag::AddSubtract, false);
-                self.set_flag(Flag::Carry, false);
+        if counter == 0 {
+            self.registers.write_word(&Register16::PC, execution.next_address);
+            execution.timing = 5; // not having to adjust the PC saves time
+        }
+    }
 
-                self.registers
-                    .write_word(&Register16::PC, next_address as u16);
-            }
-            _ => {
-                // TODO: don't forget to adjust timing for:
-                // Indr, Inir, Jr, Otdr, Otir
-                println!(
-                    "{:#06x}: {}",
-                    self.registers.read_word(&Register16::PC),
-                    &instruction
+    fn execute_outd(&mut self, _instruction: &Instruction, execution: &mut Execution) {
+        self.output_block_byte(0xffff);
+
+        self.registers.write_word(&Register16::PC, execution.next_address);
+    }
+
+    fn execute_outi(&mut self, _instruction: &Instruction, execution: &mut Execution) {
+        self.output_block_byte(1);
+
+        self.registers.write_word(&Register16::PC, execution.next_address);
+    }
+
+    fn execute_pop(&mut self, instruction: &Instruction, execution: &mut Execution) {
+        if let Instruction::Pop(Operand::Register16(destination)) = instruction {
+            let old_sp = self.registers.read_word(&Register16::SP);
+            self.registers.write_word(&Register16::SP, old_sp + 2);
+            self.registers.write_word(
+                destination,
+                self.read_memory_word(old_sp),
+            );
+            self.registers.write_word(&Register16::PC, execution.next_address);
+        } else {
+            self.execute_unimplemented(instruction, execution);
+        }
+    }
+
+    fn execute_push(&mut self, instruction: &Instruction, execution: &mut Execution) {
+        if let Instruction::Push(Operand::Register16(source)) = instruction {
+            let new_sp = self.registers.read_word(&Register16::SP) - 2;
+            self.registers.write_word(&Register16::SP, new_sp);
+            self.write_memory_word(new_sp, self.registers.read_word(source));
+            self.registers.write_word(&Register16::PC, execution.next_address);
+        } else {
+            self.execute_unimplemented(instruction, execution);
+        }
+    }
+
+    fn execute_res(&mut self, instruction: &Instruction, execution: &mut Execution) {
+        if let Instruction::Res(destination, Operand::Bit(bit), operand) = instruction {
+            let value = self.load_byte(operand) & (!(1 << bit));
+            self.store_byte(destination, value); // copy for undocumented instructions
+            self.store_byte(operand, value);
+
+            self.registers.write_word(&Register16::PC, execution.next_address);
+        } else {
+            self.execute_unimplemented(instruction, execution);
+        }
+    }
+
+    fn execute_ret(&mut self, instruction: &Instruction, execution: &mut Execution) {
+        if let Instruction::Ret(jump_test) = instruction {
+            if self.check_jump(jump_test) {
+                let old_sp = self.registers.read_word(&Register16::SP);
+                self.registers.write_word(&Register16::SP, old_sp + 2);
+                self.registers.write_word(
+                    &Register16::PC,
+                    self.read_memory_word(old_sp),
                 );
-                unimplemented!();
+            } else {
+                self.registers.write_word(&Register16::PC, execution.next_address);
             }
         }
+    }
 
-        if self.irq_received && self.iff1 && !prevent_interrupt {
-            // TODO: allow non-maskable interrupts (they are not used in the CPC)?
-            self.irq_received = false; // TODO: make requester hold interrupt until acknowledged?
+    fn execute_rl(&mut self, instruction: &Instruction, execution: &mut Execution) {
+        if let Instruction::Rl(destination, operand) = instruction {
+            let value = self.load_byte(operand);
+            let carry_value = if self.check_flag(Flag::Carry) { 1 } else { 0 };
+            let carry = (value >> 7) != 0;
+            let value = (value.rotate_left(1) & 0xfe) | carry_value;
+            self.store_byte(destination, value); // copy for undocumented instructions
+            self.store_byte(operand, value);
+
+            self.set_flag(Flag::Sign, (value as i8) < 0);
+            self.set_flag(Flag::Zero, value == 0);
+            self.set_flag(Flag::HalfCarry, false);
+            self.set_flag(Flag::ParityOverflow, (value.count_ones() & 1) == 0);
+            self.set_flag(Flag::AddSubtract, false);
+            self.set_flag(Flag::Carry, carry);
+
+            self.registers.write_word(&Register16::PC, execution.next_address);
+        }
+    }
 
-            match self.interrupt_mode {
-                InterruptMode::Mode1 => {
-                    // println!("handle interrupt");
-                    let old_pc = self.registers.read_word(&Register16::PC); // PC has already been set to next instruction
-                    let new_sp = self.registers.read_word(&Register16::SP) - 2;
-                    self.registers.write_word(&Register16::SP, new_sp);
-                    self.memory
-                        .borrow_mut()
-                        .write_word(new_sp as usize, old_pc);
-                    
-                    self.registers.write_word(&Register16::PC, 0x0038);
+    fn execute_rla(&mut self, _instruction: &Instruction, execution: &mut Execution) {
+        let value = self.registers.read_byte(&Register8::A);
+        let carry_value = if self.check_flag(Flag::Carry) { 1 } else { 0 };
+        let carry = (value >> 7) != 0;
+        let result = (value.rotate_left(1) & 0xfe) | carry_value;
+        self.registers.write_byte(&Register8::A, result);
 
-                    timing_in_nops += 4; // + Instruction::Rst(_).timing()
-                }
-                _ => unimplemented!(),
-            }
-            
-            (timing_in_nops, true)
-        } else {
-            (timing_in_nops, false)
+        self.set_flag(Flag::HalfCarry, false);
+        self.set_flag(Flag::AddSubtract, false);
+        self.set_flag(Flag::Carry, carry);
+
+        self.registers.write_word(&Register16::PC, execution.next_address);
+    }
+
+    fn execute_rlc(&mut self, instruction: &Instruction, execution: &mut Execution) {
+        if let Instruction::Rlc(destination, operand) = instruction {
+            let value = self.load_byte(operand).rotate_left(1);
+            self.store_byte(destination, value); // copy for undocumented instructions
+            self.store_byte(operand, value);
+
+            self.set_flag(Flag::Sign, (value as i8) < 0);
+            self.set_flag(Flag::Zero, value == 0);
+            self.set_flag(Flag::HalfCarry, false);
+            self.set_flag(Flag::ParityOverflow, (value.count_ones() & 1) == 0);
+            self.set_flag(Flag::AddSubtract, false);
+            self.set_flag(Flag::Carry, (value & 1) != 0);
+
+            self.registers.write_word(&Register16::PC, execution.next_address);
         }
     }
 
-    pub fn request_interrupt(&mut self) {
-        self.irq_received = true;
+    fn execute_rlca(&mut self, _instruction: &Instruction, execution: &mut Execution) {
+        let value = self.registers.read_byte(&Register8::A);
+        let result = value.rotate_left(1);
+        self.registers.write_byte(&Register8::A, result);
+
+        self.set_flag(Flag::HalfCarry, false);
+        self.set_flag(Flag::AddSubtract, false);
+        self.set_flag(Flag::Carry, (result & 1) != 0);
+
+        self.registers.write_word(&Register16::PC, execution.next_address);
     }
 
-    fn reset(&mut self) {
-        // TODO: implement reset
+    fn execute_rld(&mut self, _instruction: &Instruction, execution: &mut Execution) {
+        let accumulator = self.registers.read_byte(&Register8::A);
+        let memory_hl = self.load_byte(&Operand::RegisterIndirect(Register16::HL));
+        let new_accumulator = (accumulator & 0xf0) | (memory_hl >> 4);
+        self.registers.write_byte(&Register8::A, new_accumulator);
+        let new_memory_hl = (memory_hl << 4) | (accumulator & 0xf);
+        self.store_byte(&Operand::RegisterIndirect(Register16::HL), new_memory_hl);
+
+        self.set_flag(Flag::Sign, (new_accumulator as i8) < 0);
+        self.set_flag(Flag::Zero, new_accumulator == 0);
+        self.set_flag(Flag::HalfCarry, false);
+        self.set_flag(
+            Flag::ParityOverflow,
+            (new_accumulator.count_ones() & 1) == 0,
+        );
+        self.set_flag(Flag::AddSubtract, false);
+
+        self.registers.write_word(&Register16::PC, execution.next_address);
     }
 
-    fn check_flag(&self, flag: Flag) -> bool {
-        // TODO: move this from CPU to register file
-        let flags = self.registers.read_byte(&Register8::F);
+    fn execute_rr(&mut self, instruction: &Instruction, execution: &mut Execution) {
+        if let Instruction::Rr(destination, operand) = instruction {
+            let value = self.load_byte(operand);
+            let carry_value = if self.check_flag(Flag::Carry) { 1 } else { 0 };
+            let carry = (value & 1) != 0;
+            let value = (value.rotate_right(1) & 0x7f) | (carry_value << 7);
+            self.store_byte(destination, value); // copy for undocumented instructions
+            self.store_byte(operand, value);
+
+            self.set_flag(Flag::Sign, (value as i8) < 0);
+            self.set_flag(Flag::Zero, value == 0);
+            self.set_flag(Flag::HalfCarry, false);
+            self.set_flag(Flag::ParityOverflow, (value.count_ones() & 1) == 0);
+            self.set_flag(Flag::AddSubtract, false);
+            self.set_flag(Flag::Carry, carry);
+
+            self.registers.write_word(&Register16::PC, execution.next_address);
+        }
+    }
 
-        flags & flag.mask() != 0
+    fn execute_rrc(&mut self, instruction: &Instruction, execution: &mut Execution) {
+        if let Instruction::Rrc(destination, operand) = instruction {
+            let value = self.load_byte(operand).rotate_right(1);
+            self.store_byte(destination, value); // copy for undocumented instructions
+            self.store_byte(operand, value);
+
+            self.set_flag(Flag::Sign, (value as i8) < 0);
+            self.set_flag(Flag::Zero, value == 0);
+            self.set_flag(Flag::HalfCarry, false);
+            self.set_flag(Flag::ParityOverflow, (value.count_ones() & 1) == 0);
+            self.set_flag(Flag::AddSubtract, false);
+            self.set_flag(Flag::Carry, (value & 0x80) != 0);
+
+            self.registers.write_word(&Register16::PC, execution.next_address);
+        }
     }
 
-    fn set_flag(&mut self, flag: Flag, value: bool) {
-        // TODO: move this from CPU to register file
-        let old_flags = self.registers.read_byte(&Register8::F);
+    fn execute_rra(&mut self, _instruction: &Instruction, execution: &mut Execution) {
+        let value = self.registers.read_byte(&Register8::A);
+        let carry_value = if self.check_flag(Flag::Carry) { 1 } else { 0 };
+        let carry = (value & 1) != 0;
+        let result = (value.rotate_right(1) & 0x7f) | (carry_value << 7);
+        self.registers.write_byte(&Register8::A, result);
 
-        let new_flags = if value {
-            old_flags | flag.mask()
-        } else {
-            old_flags & (!flag.mask()) // TODO: add tests
-        };
+        self.set_flag(Flag::HalfCarry, false);
+        self.set_flag(Flag::AddSubtract, false);
+        self.set_flag(Flag::Carry, carry);
 
-        self.registers.write_byte(&Register8::F, new_flags);
+        self.registers.write_word(&Register16::PC, execution.next_address);
     }
 
-    fn check_jump(&self, jump_test: &JumpTest) -> bool {
-        match jump_test {
-            JumpTest::Unconditional => true,
-            JumpTest::NonZero => !self.check_flag(Flag::Zero),
-            JumpTest::Zero => self.check_flag(Flag::Zero),
-            JumpTest::NoCarry => !self.check_flag(Flag::Carry),
-            JumpTest::Carry => self.check_flag(Flag::Carry),
-            JumpTest::ParityOdd => !self.check_flag(Flag::ParityOverflow),
-            JumpTest::ParityEven => self.check_flag(Flag::ParityOverflow),
-            JumpTest::SignPositive => !self.check_flag(Flag::Sign),
-            JumpTest::SignNegative => self.check_flag(Flag::Sign),
+    fn execute_rrca(&mut self, _instruction: &Instruction, execution: &mut Execution) {
+        let value = self.registers.read_byte(&Register8::A);
+        let result = value.rotate_right(1);
+        self.registers.write_byte(&Register8::A, result);
+
+        self.set_flag(Flag::HalfCarry, false);
+        self.set_flag(Flag::AddSubtract, false);
+        self.set_flag(Flag::Carry, (result & 0x80) != 0);
+
+        self.registers.write_word(&Register16::PC, execution.next_address);
+    }
+
+    fn execute_rrd(&mut self, _instruction: &Instruction, execution: &mut Execution) {
+        let accumulator = self.registers.read_byte(&Register8::A);
+        let memory_hl = self.load_byte(&Operand::RegisterIndirect(Register16::HL));
+        let new_accumulator = (accumulator & 0xf0) | (memory_hl & 0xf);
+        self.registers.write_byte(&Register8::A, new_accumulator);
+        let new_memory_hl = ((accumulator & 0xf) << 4) | (memory_hl >> 4);
+        self.store_byte(&Operand::RegisterIndirect(Register16::HL), new_memory_hl);
+
+        self.set_flag(Flag::Sign, (new_accumulator as i8) < 0);
+        self.set_flag(Flag::Zero, new_accumulator == 0);
+        self.set_flag(Flag::HalfCarry, false);
+        self.set_flag(
+            Flag::ParityOverflow,
+            (new_accumulator.count_ones() & 1) == 0,
+        );
+        self.set_flag(Flag::AddSubtract, false);
+
+        self.registers.write_word(&Register16::PC, execution.next_address);
+    }
+
+    fn execute_rst(&mut self, instruction: &Instruction, execution: &mut Execution) {
+        if let Instruction::Rst(target) = instruction {
+            let new_sp = self.registers.read_word(&Register16::SP) - 2;
+            self.registers.write_word(&Register16::SP, new_sp);
+            self.write_memory_word(new_sp, execution.next_address);
+            let address_lower = self.load_byte(target);
+
+            self.registers.write_word(&Register16::PC, address_lower as u16);
         }
     }
 
-    fn load_byte(&self, operand: &Operand) -> u8 {
-        match operand {
-            Operand::Immediate8(value) => *value,
-            Operand::Register8(register) => self.registers.read_byte(register),
-            Operand::Direct8(_) => unimplemented!(),
-            Operand::Direct16(address) => self.memory.borrow().read_byte(*address as usize),
-            Operand::RegisterIndirect(register) => {
-                let address = self.registers.read_word(register);
-                self.memory.borrow().read_byte(address as usize)
-            }
-            Operand::Indexed(register, displacement) => {
-                let address = self.registers.read_word(register);
-                self.memory
-                    .borrow()
-                    .read_byte((address as i64 + *displacement as i64) as usize)
+    fn execute_sbc(&mut self, instruction: &Instruction, execution: &mut Execution) {
+        if let Instruction::Sbc(destination, source) = instruction {
+            match destination {
+                Operand::Register8(Register8::A) => {
+                    let left = self.load_byte(destination);
+                    let right = self.load_byte(source);
+                    let carry_value = if self.check_flag(Flag::Carry) { 1 } else { 0 };
+                    let (value, carry1) = right.overflowing_add(carry_value);
+                    let (value, carry2) = left.overflowing_sub(value);
+                    let overflow =
+                        (left & 0x80) != (right & 0x80) && (right & 0x80) == (value & 0x80);
+                    self.store_byte(destination, value);
+
+                    self.set_flag(Flag::Sign, (value as i8) < 0);
+                    self.set_flag(Flag::Zero, value == 0);
+                    self.set_flag(Flag::HalfCarry, (left & 0xf) < (right & 0xf) + carry_value);
+                    self.set_flag(Flag::ParityOverflow, overflow);
+                    self.set_flag(Flag::AddSubtract, true);
+                    self.set_flag(Flag::Carry, carry1 || carry2);
+                }
+                Operand::Register16(Register16::HL) => {
+                    let left = self.load_word(destination);
+                    let right = self.load_word(source);
+                    let carry_value = if self.check_flag(Flag::Carry) { 1 } else { 0 };
+                    let (value, carry1) = right.overflowing_add(carry_value);
+                    let (value, carry2) = left.overflowing_sub(value);
+                    let overflow = (left & 0x8000) != (right & 0x8000)
+                        && (right & 0x8000) == (value & 0x8000);
+                    self.store_word(destination, value);
+
+                    self.set_flag(Flag::Sign, (value as i16) < 0);
+                    self.set_flag(Flag::Zero, value == 0);
+                    self.set_flag(
+                        Flag::HalfCarry,
+                        (left & 0xfff) < (right & 0xfff) + carry_value,
+                    );
+                    self.set_flag(Flag::ParityOverflow, overflow);
+                    self.set_flag(Flag::AddSubtract, true);
+                    self.set_flag(Flag::Carry, carry1 || carry2);
+                }
+                _ => unreachable!(),
             }
-            _ => unreachable!(),
+
+            self.registers.write_word(&Register16::PC, execution.next_address);
         }
     }
 
-    fn store_byte(&mut self, operand: &Operand, value: u8) {
-        match operand {
-            Operand::Register8(register) => self.registers.write_byte(register, value),
-            Operand::Direct8(_) => unimplemented!(),
-            Operand::Direct16(address) => self
-                .memory
-                .borrow_mut()
-                .write_byte(*address as usize, value),
-            Operand::RegisterIndirect(register) => {
-                let address = self.registers.read_word(register);
-                self.memory.borrow_mut().write_byte(address as usize, value)
-            }
-            Operand::Indexed(register, displacement) => {
-                let address = self.registers.read_word(register);
-                self.memory
-                    .borrow_mut()
-                    .write_byte((address as i64 + *displacement as i64) as usize, value)
-            }
-            _ => unreachable!(),
+    fn execute_scf(&mut self, _instruction: &Instruction, execution: &mut Execution) {
+        self.set_flag(Flag::HalfCarry, false);
+        self.set_flag(Flag::AddSubtract, false);
+        self.set_flag(Flag::Carry, true);
+
+        self.registers.write_word(&Register16::PC, execution.next_address);
+    }
+
+    fn execute_set(&mut self, instruction: &Instruction, execution: &mut Execution) {
+        if let Instruction::Set(destination, Operand::Bit(bit), operand) = instruction {
+            let value = self.load_byte(operand) | (1 << bit);
+            self.store_byte(destination, value); // copy for undocumented instructions
+            self.store_byte(operand, value);
+
+            self.registers.write_word(&Register16::PC, execution.next_address);
+        } else {
+            self.execute_unimplemented(instruction, execution);
         }
     }
 
-    fn load_word(&self, operand: &Operand) -> u16 {
-        match operand {
-            Operand::Immediate16(value) => *value,
-            Operand::Register16(register) => self.registers.read_word(register),
-            Operand::Direct16(address) => self.memory.borrow().read_word(*address as usize),
-            Operand::RegisterIndirect(register) => {
-                let address = self.registers.read_word(register);
-                self.memory.borrow().read_word(address as usize)
-            }
-            Operand::Indexed(register, displacement) => {
-                let address = self.registers.read_word(register);
-                self.memory
-                    .borrow()
-                    .read_word((address as i64 + *displacement as i64) as usize)
-            }
-            _ => unreachable!(),
+    fn execute_sla(&mut self, instruction: &Instruction, execution: &mut Execution) {
+        if let Instruction::Sla(destination, operand) = instruction {
+            let value = self.load_byte(operand);
+            let carry = (value >> 7) != 0;
+            let result = value << 1;
+            self.store_byte(destination, result); // copy for undocumented instructions
+            self.store_byte(operand, result);
+
+            self.set_flag(Flag::Sign, (result as i8) < 0); // TODO: make this reusable?
+            self.set_flag(Flag::Zero, result == 0);
+            self.set_flag(Flag::HalfCarry, false);
+            self.set_flag(Flag::ParityOverflow, (result.count_ones() & 1) == 0);
+            self.set_flag(Flag::AddSubtract, false);
+            self.set_flag(Flag::Carry, carry);
+
+            self.registers.write_word(&Register16::PC, execution.next_address);
         }
     }
 
-    fn store_word(&mut self, operand: &Operand, value: u16) {
-        match operand {
-            Operand::Register16(register) => self.registers.write_word(register, value),
-            Operand::Direct16(address) => self
-                .memory
-                .borrow_mut()
-                .write_word(*address as usize, value),
-            Operand::RegisterIndirect(register) => {
-                let address = self.registers.read_word(register);
-                self.memory.borrow_mut().write_word(address as usize, value)
-            }
-            Operand::Indexed(register, displacement) => {
-                let address = self.registers.read_word(register);
-                self.memory
-                    .borrow_mut()
-                    .write_word((address as i64 + *displacement as i64) as usize, value)
-            }
-            _ => unreachable!(),
+    fn execute_sll(&mut self, instruction: &Instruction, execution: &mut Execution) {
+        if let Instruction::Sll(destination, operand) = instruction {
+            let value = self.load_byte(operand);
+            let carry = (value >> 7) != 0;
+            let result = (value << 1) | 1;
+            self.store_byte(destination, result); // copy for undocumented instructions
+            self.store_byte(operand, result);
+
+            self.set_flag(Flag::Sign, (result as i8) < 0); // TODO: make this reusable?
+            self.set_flag(Flag::Zero, result == 0);
+            self.set_flag(Flag::HalfCarry, false);
+            self.set_flag(Flag::ParityOverflow, (result.count_ones() & 1) == 0);
+            self.set_flag(Flag::AddSubtract, false);
+            self.set_flag(Flag::Carry, carry);
+
+            self.registers.write_word(&Register16::PC, execution.next_address);
         }
     }
 
-    pub fn print_state(&self) {
-        // TODO: move this from CPU to register file
-        let ix = self.registers.read_word(&Register16::IX);
-        let iy = self.registers.read_word(&Register16::IY);
-        let hl = self.registers.read_word(&Register16::HL);
-        let de = self.registers.read_word(&Register16::DE);
-        let bc = self.registers.read_word(&Register16::BC);
-        let af = self.registers.read_word(&Register16::AF);
-        let sp = self.registers.read_word(&Register16::SP);
-        println!(
-            "IX = {:#06x}, IY = {:#06x}, HL = {:#06x}, DE = {:#06x}, BC = {:#06x}, AF = {:#06x}, SP = {:#06x}",
-            ix, iy, hl, de, bc, af, sp
-        );
+    fn execute_sra(&mut self, instruction: &Instruction, execution: &mut Execution) {
+        if let Instruction::Sra(destination, operand) = instruction {
+            let value = self.load_byte(operand);
+            let sign = value & 0x80;
+            let carry = (value & 1) != 0;
+            let result = sign | (value >> 1);
+            self.store_byte(destination, result); // copy for undocumented instructions
+            self.store_byte(operand, result);
+
+            self.set_flag(Flag::Sign, (result as i8) < 0); // TODO: make this reusable?
+            self.set_flag(Flag::Zero, result == 0);
+            self.set_flag(Flag::HalfCarry, false);
+            self.set_flag(Flag::ParityOverflow, (result.count_ones() & 1) == 0);
+            self.set_flag(Flag::AddSubtract, false);
+            self.set_flag(Flag::Carry, carry);
+
+            self.registers.write_word(&Register16::PC, execution.next_address);
+        }
+    }
 
-        let sign = self.check_flag(Flag::Sign);
-        let zero = self.check_flag(Flag::Zero);
-        let half_carry = self.check_flag(Flag::HalfCarry);
-        let parity_oveflow = self.check_flag(Flag::ParityOverflow);
-        let add_subtract = self.check_flag(Flag::AddSubtract);
-        let carry = self.check_flag(Flag::Carry);
+    fn execute_srl(&mut self, instruction: &Instruction, execution: &mut Execution) {
+        if let Instruction::Srl(destination, operand) = instruction {
+            let value = self.load_byte(operand);
+            let carry = (value & 1) != 0;
+            let result = value >> 1;
+            self.store_byte(destination, result); // copy for undocumented instructions
+            self.store_byte(operand, result);
+
+            self.set_flag(Flag::Sign, (result as i8) < 0); // TODO: make this reusable?
+            self.set_flag(Flag::Zero, result == 0);
+            self.set_flag(Flag::HalfCarry, false);
+            self.set_flag(Flag::ParityOverflow, (result.count_ones() & 1) == 0);
+            self.set_flag(Flag::AddSubtract, false);
+            self.set_flag(Flag::Carry, carry);
+
+            self.registers.write_word(&Register16::PC, execution.next_address);
+        }
+    }
+
+    fn execute_sub(&mut self, instruction: &Instruction, execution: &mut Execution) {
+        if let Instruction::Sub(operand) = instruction {
+            // ALUOP / checked
+            let left = self.registers.read_byte(&Register8::A);
+            let right = self.load_byte(operand);
+            let (value, carry) = left.overflowing_sub(right);
+            let (_, overflow) = (left as i8).overflowing_sub(right as i8);
+            self.registers.write_byte(&Register8::A, value);
+
+            self.set_flag(Flag::Sign, (value as i8) < 0); // TODO: make this reusable?
+            self.set_flag(Flag::Zero, value == 0);
+            self.set_flag(Flag::HalfCarry, (left & 0xf) < (right & 0xf));
+            self.set_flag(Flag::ParityOverflow, overflow);
+            self.set_flag(Flag::AddSubtract, true);
+            self.set_flag(Flag::Carry, carry);
+
+            self.registers.write_word(&Register16::PC, execution.next_address);
+        }
+    }
+
+    fn execute_xor(&mut self, instruction: &Instruction, execution: &mut Execution) {
+        if let Instruction::Xor(operand) = instruction {
+            let value = self.load_byte(operand);
+
+            let result = self.registers.read_byte(&Register8::A) ^ value;
+            self.registers.write_byte(&Register8::A, result);
+
+            self.set_flag(Flag::Sign, (result as i8) < 0); // TODO: make this reusable?
+            self.set_flag(Flag::Zero, result == 0);
+            self.set_flag(Flag::HalfCarry, false);
+            self.set_flag(Flag::ParityOverflow, (result.count_ones() & 1) == 0);
+            self.set_flag(Flag::AddSubtract, false);
+            self.set_flag(Flag::Carry, false);
+
+            self.registers.write_word(&Register16::PC, execution.next_address);
+        }
+    }
+
+    fn execute_unimplemented(&mut self, instruction: &Instruction, _execution: &mut Execution) {
         println!(
-            "S = {}, Z = {}, H = {}, P/V = {}, N = {}, C = {}",
-            sign, zero, half_carry, parity_oveflow, add_subtract, carry
+            "{:#06x}: {}",
+            self.registers.read_word(&Register16::PC),
+            instruction
         );
+        unimplemented!();
     }
 }
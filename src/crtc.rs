@@ -3,6 +3,37 @@ use std::rc::Rc;
 
 pub type CRTControllerShared = Rc<RefCell<CRTController>>;
 
+// the CRTCs fitted to CPCs over the years, which differ in how sync widths and register reads work
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum CrtcType {
+    #[default]
+    Type0, // HD6845S and UM6845
+    Type1, // UM6845R
+    Type2, // MC6845
+    Type3, // AMS40489, in the Plus range but also fitted to late 6128s
+}
+
+impl CrtcType {
+    pub fn from_name(name: &str) -> Option<CrtcType> {
+        match name {
+            "0" => Some(CrtcType::Type0),
+            "1" => Some(CrtcType::Type1),
+            "2" => Some(CrtcType::Type2),
+            "3" => Some(CrtcType::Type3),
+            _ => None,
+        }
+    }
+
+    pub fn number(&self) -> u8 {
+        match self {
+            CrtcType::Type0 => 0,
+            CrtcType::Type1 => 1,
+            CrtcType::Type2 => 2,
+            CrtcType::Type3 => 3,
+        }
+    }
+}
+
 enum Register {
     HorizontalTotal,
     HorizontalDisplayed,
@@ -24,23 +55,28 @@ enum Register {
     LightPenAddressLow,
 }
 
+#[derive(Clone)]
 pub struct CRTController {
+    crtc_type: CrtcType,
     registers: [u8; 18],
     selected_register: usize,
     horizontal_counter: u8,
     horizontal_sync_width_counter: u8,
+    vertical_sync_width_counter: u8, // remaining scan lines of the current VSYNC
     character_row_counter: u8,
     scan_line_counter: u8,
     display_start_address: u16,
 }
 
 impl CRTController {
-    pub fn new_shared() -> CRTControllerShared {
+    pub fn new_shared(crtc_type: CrtcType) -> CRTControllerShared {
         let crtc = CRTController {
+            crtc_type,
             registers: [0; 18],
             selected_register: 0,
             horizontal_counter: 0,
             horizontal_sync_width_counter: 0,
+            vertical_sync_width_counter: 0,
             character_row_counter: 0,
             scan_line_counter: 0,
             display_start_address: 0,
@@ -49,9 +85,19 @@ impl CRTController {
         Rc::new(RefCell::new(crtc))
     }
 
+    pub fn get_crtc_type(&self) -> CrtcType {
+        self.crtc_type
+    }
+
     pub fn read_byte(&self, port: u16) -> u8 {
-        dbg!(port);
-        unimplemented!()
+        // only type 1 has a status register, type 3 answers on both ports with the register contents
+        let function = (port >> 8) & 0x03;
+
+        match (function, self.crtc_type) {
+            (3, _) | (2, CrtcType::Type3) => self.read_register(),
+            (2, CrtcType::Type1) => self.read_status(),
+            _ => 0xff,
+        }
     }
 
     pub fn write_byte(&mut self, port: u16, value: u8) {
@@ -65,57 +111,116 @@ impl CRTController {
     }
 
     fn select_register(&mut self, register: usize) {
-        self.selected_register = register;
+        self.selected_register = register & 0x1f;
     }
 
     fn write_register(&mut self, value: u8) {
-        self.registers[self.selected_register] = value; // TODO: restrict to registers 0-15, TODO: restrict bit-width
+        // the light pen registers and the unused ones above them cannot be written
+        if self.selected_register < 16 {
+            self.registers[self.selected_register] = value; // TODO: restrict bit-width
+        }
     }
 
     fn read_register(&self) -> u8 {
-        self.registers[self.selected_register] // TODO: restrict to registers 14-17
+        // types 0 and 3 can read back the start, cursor and light pen addresses, 1 and 2 not the start
+        match (self.selected_register, self.crtc_type) {
+            (12..=13, CrtcType::Type1) | (12..=13, CrtcType::Type2) => 0,
+            (12..=17, _) => self.registers[self.selected_register],
+            _ => 0,
+        }
+    }
+
+    fn read_status(&self) -> u8 {
+        // bit 5 is set in the vertical border
+        let vertical_border = self.character_row_counter >= self.registers[Register::VerticalDisplayed as usize];
+        if vertical_border {
+            0x20
+        } else {
+            0x00
+        }
+    }
+
+    pub fn read_registers(&self) -> (u8, [u8; 18]) {
+        // the selected register and the contents of all registers
+        (self.selected_register as u8, self.registers)
     }
 
     pub fn read_address(&self) -> usize {
-        let refresh_memory_address = self.display_start_address
-            + self.registers[Register::HorizontalDisplayed as usize] as u16
-                * self.character_row_counter as u16
-            + self.horizontal_counter as u16;
+        let refresh_memory_address =
+            self.refresh_memory_address(self.display_start_address, self.character_row_counter, self.horizontal_counter);
 
-        let bits_14_and_15 = (refresh_memory_address & (0b11 << 12)) << 2;
-        let bits_11_to_13 = ((self.scan_line_counter & 0b111) as u16) << 11;
-        let bits_0_to_10 = (refresh_memory_address & 0b11_1111_1111) << 1;
+        screen_address(refresh_memory_address, self.scan_line_counter)
+    }
 
-        (bits_14_and_15 | bits_11_to_13 | bits_0_to_10) as usize
+    pub fn read_character_address(&self, character_row: u8, column: u8, scan_line: u8) -> usize {
+        // uses the programmed display start, which takes effect at the start of the next frame
+        let refresh_memory_address = self.refresh_memory_address(self.read_display_start_address(), character_row, column);
+
+        screen_address(refresh_memory_address, scan_line)
+    }
+
+    fn read_display_start_address(&self) -> u16 {
+        // R12 only has 6 bits
+        ((self.registers[Register::DisplayStartAddressHigh as usize] as u16 & 0x3f) << 8)
+            | self.registers[Register::DisplayStartAddressLow as usize] as u16
+    }
+
+    fn refresh_memory_address(&self, display_start_address: u16, character_row: u8, column: u8) -> u16 {
+        // the counter has 14 bits and wraps around
+        let row_start = (self.registers[Register::HorizontalDisplayed as usize] as u16).wrapping_mul(character_row as u16);
+
+        display_start_address.wrapping_add(row_start).wrapping_add(column as u16) & 0x3fff
+    }
+
+    pub fn read_horizontal_displayed(&self) -> u8 {
+        self.registers[Register::HorizontalDisplayed as usize]
+    }
+
+    pub fn read_vertical_displayed(&self) -> u8 {
+        self.registers[Register::VerticalDisplayed as usize]
+    }
+
+    pub fn read_raster_position(&self) -> (u16, u8) {
+        // returns the scan line within the frame and the character position within the scan line
+        let raster_line = self.character_row_counter as u16
+            * (self.registers[Register::MaximumRasterAddress as usize] as u16 + 1)
+            + self.scan_line_counter as u16;
+
+        (raster_line, self.horizontal_counter)
     }
 
     pub fn read_display_enabled(&self) -> bool {
-        self.horizontal_counter < self.registers[Register::HorizontalDisplayed as usize]
+        // a display skew of 3 turns the display off entirely, leaving only the border
+        let skew = (self.registers[Register::InterlaceAndSkew as usize] >> 4) & 0x03;
+
+        skew != 0x03
+            && self.horizontal_counter < self.registers[Register::HorizontalDisplayed as usize]
             && self.character_row_counter < self.registers[Register::VerticalDisplayed as usize]
     }
 
     pub fn read_horizontal_sync(&self) -> bool {
         // TODO: what happens before registers are initialized?
         let sync_start = self.registers[Register::HorizontalSyncPosition as usize];
-        let sync_end = self.registers[Register::HorizontalSyncPosition as usize]
-            + (self.registers[Register::HorizontalAndVerticalSyncWidths as usize] & 0b1111);
-        self.horizontal_counter >= sync_start && self.horizontal_counter < sync_end
-        // this results in NO sync if the horizontal sync width is 0
+        let width = match self.registers[Register::HorizontalAndVerticalSyncWidths as usize] & 0b1111 {
+            0 if self.crtc_type == CrtcType::Type2 || self.crtc_type == CrtcType::Type3 => 16,
+            width => width, // types 0 and 1 have no HSYNC with a width of 0
+        };
+        let sync_end = sync_start as u16 + width as u16;
+        self.horizontal_counter >= sync_start && (self.horizontal_counter as u16) < sync_end
     }
 
     pub fn read_vertical_sync(&self) -> bool {
-        // TODO: what happens before registers are initialized?
-        let sync_start = self.registers[Register::VerticalSyncPosition as usize];
-        let sync_end = self.registers[Register::VerticalSyncPosition as usize] + 16; // this can be switched between 0 and 16 on type 0
-        self.character_row_counter >= sync_start && self.character_row_counter < sync_end
+        self.vertical_sync_width_counter > 0
     }
 
     pub fn step(&mut self) {
         self.horizontal_counter += 1;
 
-        if self.horizontal_counter > self.registers[Register::HorizontalTotal as usize] {
+        let line_ended = self.horizontal_counter > self.registers[Register::HorizontalTotal as usize];
+        if line_ended {
             self.scan_line_counter += 1;
             self.horizontal_counter = 0;
+            self.vertical_sync_width_counter = self.vertical_sync_width_counter.saturating_sub(1);
         }
 
         if self.scan_line_counter > self.registers[Register::MaximumRasterAddress as usize] {
@@ -128,10 +233,29 @@ impl CRTController {
             self.character_row_counter = 0;
         }
 
+        if line_ended
+            && self.scan_line_counter == 0
+            && self.character_row_counter == self.registers[Register::VerticalSyncPosition as usize]
+        {
+            // VSYNC starts with the character row and lasts for a number of scan lines, 0 meaning 16.
+            // Types 1 and 2 ignore the programmed width
+            let width = match self.crtc_type {
+                CrtcType::Type1 | CrtcType::Type2 => 0,
+                _ => self.registers[Register::HorizontalAndVerticalSyncWidths as usize] >> 4,
+            };
+            self.vertical_sync_width_counter = if width == 0 { 16 } else { width };
+        }
+
         if self.horizontal_counter == 0 && self.character_row_counter == 0 {
-            self.display_start_address =
-                ((self.registers[Register::DisplayStartAddressHigh as usize] as u16) << 8)
-                    + self.registers[Register::DisplayStartAddressLow as usize] as u16;
+            self.display_start_address = self.read_display_start_address();
         }
     }
 }
+
+fn screen_address(refresh_memory_address: u16, scan_line: u8) -> usize {
+    let bits_14_and_15 = (refresh_memory_address & (0b11 << 12)) << 2;
+    let bits_11_to_13 = ((scan_line & 0b111) as u16) << 11;
+    let bits_0_to_10 = (refresh_memory_address & 0b11_1111_1111) << 1;
+
+    (bits_14_and_15 | bits_11_to_13 | bits_0_to_10) as usize
+}
@@ -0,0 +1,531 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::bus;
+use crate::cpu;
+use crate::debugger::Resume;
+use crate::expression::{self, Expression};
+use crate::memory;
+use crate::sna_file;
+use crate::snapshot_diff;
+use crate::system;
+
+pub type DebugConsoleShared = Rc<RefCell<DebugConsole>>;
+
+const MAX_HISTORY: usize = 100;
+const DEFAULT_ROWS: usize = 16; // for memory dumps and disassembly
+
+const HELP: [&str; 27] = [
+    "r [reg value]       registers",
+    "psg [reg value]     sound registers",
+    "m [addr] [rows]     dump memory",
+    "d [addr] [count]    disassemble",
+    "bp [addr [if expr]] toggle breakpoint",
+    "g [addr]            go",
+    "s [count]           step, LDIR etc. by iteration",
+    "n                   next instruction",
+    "hsync/vsync [count] run to sync",
+    "sl count            run scan lines",
+    "line n              run to scan line",
+    "fill start end byte fill memory",
+    "save start end file save memory",
+    "load addr file      load memory",
+    "p expr              print",
+    "w expr              toggle watch",
+    "label addr [name]   set label",
+    "comment addr [text] set comment",
+    "data start end      mark as data",
+    "code start end      mark as code",
+    "snap                remember the state",
+    "diff [file]         changes since snap",
+    "banks [count]       ROM paging changes",
+    "mw start end [f]    watch RAM, f by frame",
+    "mw                  RAM changes",
+    "mu id               unwatch RAM",
+    "Addresses are expressions, e.g. hl+2",
+];
+
+// the monitor shared by the workbench panel and the command line: parses commands, inspects and
+// changes the system and resumes its debugger. Returns output lines instead of printing them.
+pub struct DebugConsole {
+    history: Vec<String>,
+    history_position: usize, // equal to the history length while not browsing it
+    watches: Vec<Expression>,
+    memory_address: u16, // where m and d continue when no address is given
+    disassembly_address: Option<u16>,
+    reference: Option<sna_file::Snapshot>, // the state diff compares with
+}
+
+impl DebugConsole {
+    pub fn new() -> DebugConsole {
+        DebugConsole {
+            history: Vec::new(),
+            history_position: 0,
+            watches: Vec::new(),
+            memory_address: 0,
+            disassembly_address: None,
+            reference: None,
+        }
+    }
+
+    pub fn new_shared() -> DebugConsoleShared {
+        Rc::new(RefCell::new(DebugConsole::new()))
+    }
+
+    pub fn previous_command(&mut self) -> Option<&str> {
+        self.history_position = self.history_position.saturating_sub(1);
+        self.history.get(self.history_position).map(|command| command.as_str())
+    }
+
+    pub fn next_command(&mut self) -> Option<&str> {
+        // None after the newest command, i.e. back to an empty line
+        self.history_position = (self.history_position + 1).min(self.history.len());
+        self.history.get(self.history_position).map(|command| command.as_str())
+    }
+
+    pub fn stop_lines(&mut self, system: &dyn system::System) -> Vec<String> {
+        // shown whenever the debugger stops: the next instruction and the watches
+        let program_counter = system.get_program_counter();
+        self.disassembly_address = None;
+        let mut lines = disassembly_lines(system, program_counter, 1);
+        let context = expression::SystemContext(system);
+        for watch in &self.watches {
+            lines.push(format_value(watch.text(), watch.evaluate(&context)));
+        }
+
+        lines
+    }
+
+    pub fn execute(&mut self, system: &mut dyn system::System, line: &str) -> Vec<String> {
+        // an empty line repeats the last command, e.g. to keep stepping
+        let line = match line.trim() {
+            "" => match self.history.last() {
+                Some(line) => line.clone(),
+                None => return Vec::new(),
+            },
+            line => {
+                if self.history.last().map(|last| last.as_str()) != Some(line) {
+                    self.history.push(line.to_string());
+                    if self.history.len() > MAX_HISTORY {
+                        self.history.remove(0);
+                    }
+                }
+                line.to_string()
+            }
+        };
+        self.history_position = self.history.len();
+
+        let (command, arguments) = match line.find(char::is_whitespace) {
+            Some(index) => (&line[..index], line[index..].trim()),
+            None => (line.as_str(), ""),
+        };
+        match self.execute_command(system, command, arguments) {
+            Ok(lines) => lines,
+            Err(error) => vec![error],
+        }
+    }
+
+    fn execute_command(&mut self, system: &mut dyn system::System, command: &str, arguments: &str) -> Result<Vec<String>, String> {
+        let words: Vec<&str> = arguments.split_whitespace().collect();
+        match command {
+            "help" | "?" => Ok(HELP.iter().map(|line| line.to_string()).collect()),
+            "r" | "registers" => match words.as_slice() {
+                [] => Ok(register_lines(&*system)),
+                [name, value] => {
+                    let register = parse_register(name).ok_or_else(|| format!("Unknown register \"{}\".", name))?;
+                    let value = parse_address(&*system, value)?;
+                    system.write_register(register, value);
+                    Ok(register_lines(&*system))
+                }
+                _ => Err("Usage: r [register value]".to_string()),
+            },
+            "psg" => match words.as_slice() {
+                [] => Ok(psg_lines(&*system)),
+                [register, value] => {
+                    let register = parse_address(&*system, register)?;
+                    if register > 0x0f {
+                        return Err(format!("There is no sound register {}.", register));
+                    }
+                    let value = parse_address(&*system, value)? as u8;
+                    system.write_psg_register(register as u8, value);
+                    Ok(psg_lines(&*system))
+                }
+                _ => Err("Usage: psg [register value]".to_string()),
+            },
+            "m" | "mem" => {
+                let start = match words.first() {
+                    Some(address) => parse_address(&*system, address)?,
+                    None => self.memory_address,
+                };
+                let rows = parse_count(&*system, words.get(1), DEFAULT_ROWS)?;
+                self.memory_address = start.wrapping_add(rows as u16 * 8);
+                Ok(memory_lines(&*system, start, rows))
+            }
+            "d" | "dis" | "disassemble" => {
+                let start = match words.first() {
+                    Some(address) => parse_address(&*system, address)?,
+                    None => self.disassembly_address.unwrap_or_else(|| system.get_program_counter()),
+                };
+                let count = parse_count(&*system, words.get(1), DEFAULT_ROWS)?;
+                let instructions = system.disassemble(start, count + 1);
+                self.disassembly_address = instructions.last().map(|(address, _)| *address);
+                Ok(disassembly_lines(&*system, start, count))
+            }
+            "bp" | "break" => {
+                if words.is_empty() {
+                    let breakpoints = system.list_breakpoints();
+                    if breakpoints.is_empty() {
+                        return Ok(vec!["No breakpoints".to_string()]);
+                    }
+                    return Ok(breakpoints
+                        .iter()
+                        .map(|(address, condition)| match condition {
+                            Some(condition) => format!("{:04x} if {}", address, condition),
+                            None => format!("{:04x}", address),
+                        })
+                        .collect());
+                }
+
+                // e.g. bp 0x1234 if a == 3
+                let (address, condition) = match arguments.find(" if ") {
+                    Some(index) => (&arguments[..index], Some(arguments[index + 4..].trim())),
+                    None => (arguments, None),
+                };
+                let address = parse_address(&*system, address)?;
+                match condition {
+                    Some(condition) => {
+                        system.add_breakpoint(address, Some(Expression::parse(condition)?));
+                        Ok(vec![format!("Breakpoint at {:04x} if {}", address, condition)])
+                    }
+                    None if system.remove_breakpoint(address) => Ok(vec![format!("Removed breakpoint at {:04x}", address)]),
+                    None => {
+                        system.add_breakpoint(address, None);
+                        Ok(vec![format!("Breakpoint at {:04x}", address)])
+                    }
+                }
+            }
+            "g" | "go" | "c" | "continue" => {
+                if let Some(address) = words.first() {
+                    let address = parse_address(&*system, address)?;
+                    system.write_register(cpu::Register16::PC, address);
+                }
+                system.resume_debugger(Resume::Run);
+                Ok(Vec::new())
+            }
+            "s" | "step" => {
+                let count = parse_count(&*system, words.first(), 1)?;
+                system.resume_debugger(Resume::Step(count as u16));
+                Ok(Vec::new())
+            }
+            "n" | "next" => {
+                system.resume_debugger(Resume::Next);
+                Ok(Vec::new())
+            }
+            "hsync" | "vsync" => {
+                let signal = if command == "hsync" { bus::SyncSignal::Horizontal } else { bus::SyncSignal::Vertical };
+                let count = parse_count(&*system, words.first(), 1)?;
+                system.resume_debugger(Resume::RunToSync(signal, count as u64));
+                Ok(Vec::new())
+            }
+            "line" => match words.as_slice() {
+                [line] => {
+                    let line = parse_address(&*system, line)?;
+                    system.resume_debugger(Resume::RunToLine(line));
+                    Ok(Vec::new())
+                }
+                _ => Err("Usage: line n".to_string()),
+            },
+            "sl" | "scanlines" => {
+                // every scan line ends with a horizontal sync
+                let count = parse_count(&*system, words.first(), 1)?;
+                system.resume_debugger(Resume::RunToSync(bus::SyncSignal::Horizontal, count as u64));
+                Ok(Vec::new())
+            }
+            "snap" => {
+                self.reference = Some(system.save_snapshot());
+                Ok(vec!["Remembered the state, diff shows what changes".to_string()])
+            }
+            "diff" => {
+                let loaded;
+                let before = match words.as_slice() {
+                    [] => self.reference.as_ref().ok_or_else(|| "Take a snap first, or give a snapshot file.".to_string())?,
+                    [filename] => {
+                        loaded = sna_file::Snapshot::load(filename).map_err(|error| format!("Could not load \"{}\": {}", filename, error))?;
+                        &loaded
+                    }
+                    _ => return Err("Usage: diff [file]".to_string()),
+                };
+                Ok(snapshot_diff::SnapshotDiff::compare(before, &system.save_snapshot()).lines())
+            }
+            "banks" => {
+                let count = match words.as_slice() {
+                    [] => 8,
+                    [count] => parse_address(&*system, count)? as usize,
+                    _ => return Err("Usage: banks [count]".to_string()),
+                };
+                Ok(banking_lines(&*system, count))
+            }
+            "mw" => match words.as_slice() {
+                [] => Ok(memory_diff_lines(&*system)),
+                [start, end] | [start, end, _] => {
+                    let mode = match words.get(2) {
+                        None => memory::WatchMode::EveryWrite,
+                        Some(&"f") => memory::WatchMode::EveryFrame,
+                        Some(_) => return Err("Usage: mw [start end [f]]".to_string()),
+                    };
+                    let (start, end) = parse_range(&*system, start, end)?;
+                    let id = system.watch_memory(start as usize, (end - start) as usize + 1, mode);
+                    Ok(vec![format!("Watch #{} on {:04x}-{:04x}", id, start, end)])
+                }
+                _ => Err("Usage: mw [start end [f]]".to_string()),
+            },
+            "mu" => match words.as_slice() {
+                [id] => {
+                    let id = id.parse::<usize>().map_err(|_| format!("Invalid watch \"{}\".", id))?;
+                    system.unwatch_memory(id);
+                    Ok(vec![format!("Removed watch #{}", id)])
+                }
+                _ => Err("Usage: mu id".to_string()),
+            },
+            "fill" => match words.as_slice() {
+                [start, end, value] => {
+                    let (start, end) = parse_range(&*system, start, end)?;
+                    let value = parse_address(&*system, value)? as u8;
+                    // into the base 64K of RAM, whatever the CPU currently sees there
+                    for address in start..=end {
+                        system.write_ram(address as usize, value);
+                    }
+                    Ok(vec![format!("Filled {:04x}-{:04x} with {:02x}", start, end, value)])
+                }
+                _ => Err("Usage: fill start end value".to_string()),
+            },
+            "save" => match words.as_slice() {
+                [start, end, filename] => {
+                    // as seen by the CPU, i.e. including enabled ROMs
+                    let (start, end) = parse_range(&*system, start, end)?;
+                    let bytes: Vec<u8> = (start..=end).map(|address| system.read_memory(address)).collect();
+                    std::fs::write(filename, &bytes).map_err(|error| format!("Could not save \"{}\": {}", filename, error))?;
+                    Ok(vec![format!("Saved {} bytes to \"{}\"", bytes.len(), filename)])
+                }
+                _ => Err("Usage: save start end file".to_string()),
+            },
+            "load" => match words.as_slice() {
+                [address, filename] => {
+                    let address = parse_address(&*system, address)?;
+                    let bytes = std::fs::read(filename).map_err(|error| format!("Could not load \"{}\": {}", filename, error))?;
+                    // into the base 64K of RAM, wrapping around at its end rather than failing halfway
+                    for (offset, byte) in bytes.iter().enumerate() {
+                        system.write_ram(address.wrapping_add(offset as u16) as usize, *byte);
+                    }
+                    Ok(vec![format!("Loaded {} bytes at {:04x}", bytes.len(), address)])
+                }
+                _ => Err("Usage: load address file".to_string()),
+            },
+            "p" | "print" => {
+                let expression = Expression::parse(arguments)?;
+                Ok(vec![format_value(arguments, expression.evaluate(&expression::SystemContext(&*system)))])
+            }
+            "w" | "watch" => {
+                let expression = Expression::parse(arguments)?;
+                if let Some(index) = self.watches.iter().position(|watch| *watch == expression) {
+                    self.watches.remove(index);
+                    Ok(vec![format!("Removed watch {}", arguments)])
+                } else {
+                    self.watches.push(expression);
+                    Ok(vec![format!("Watching {}", arguments)])
+                }
+            }
+            "label" | "comment" => {
+                // the rest of the line, or nothing to remove the annotation
+                let (address, text) = match arguments.find(char::is_whitespace) {
+                    Some(index) => (&arguments[..index], Some(arguments[index..].trim().to_string())),
+                    None => (arguments, None),
+                };
+                let address = parse_address(&*system, address)?;
+                let project = system.get_project();
+                let mut project = project.borrow_mut();
+                if command == "label" {
+                    project.set_label(address, text);
+                } else {
+                    project.set_comment(address, text);
+                }
+                Ok(Vec::new())
+            }
+            "data" | "code" => match words.as_slice() {
+                [start, end] => {
+                    let (start, end) = parse_range(&*system, start, end)?;
+                    let project = system.get_project();
+                    let mut project = project.borrow_mut();
+                    if command == "data" {
+                        project.mark_data(start, end);
+                    } else {
+                        project.unmark_data(start, end);
+                    }
+                    Ok(Vec::new())
+                }
+                _ => Err(format!("Usage: {} start end", command)),
+            },
+            _ => Err(format!("Unknown command \"{}\", try help", command)),
+        }
+    }
+}
+
+impl Default for DebugConsole {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub fn register_lines(system: &dyn system::System) -> Vec<String> {
+    let registers = [
+        ("AF", cpu::Register16::AF),
+        ("BC", cpu::Register16::BC),
+        ("DE", cpu::Register16::DE),
+        ("HL", cpu::Register16::HL),
+        ("IX", cpu::Register16::IX),
+        ("IY", cpu::Register16::IY),
+        ("SP", cpu::Register16::SP),
+        ("PC", cpu::Register16::PC),
+    ];
+    let mut lines: Vec<String> = registers
+        .iter()
+        .map(|(name, register)| format!("{}  {:04x}", name, system.read_register(*register)))
+        .collect();
+
+    let flags = system.read_register(cpu::Register16::AF) as u8;
+    let names: String = "SZ-H-PNC"
+        .chars()
+        .enumerate()
+        .map(|(bit, name)| if flags & (0x80 >> bit) != 0 { name } else { '.' })
+        .collect();
+    lines.push(String::new());
+    lines.push(format!("Flags  {}", names));
+    let (raster_line, character) = system.read_raster_position();
+    lines.push(format!("Beam   line {}, char {}", raster_line, character));
+    let banks: Vec<String> = system.get_debug_view().memory_banks.iter().map(|bank| bank.to_string()).collect();
+    lines.push(format!("Banks  {}", banks.join(" / ")));
+
+    lines
+}
+
+fn banking_lines(system: &dyn system::System, count: usize) -> Vec<String> {
+    // the latest changes of the memory layout, oldest first, e.g. the ROMs the firmware pages in and out
+    let events = system.get_debug_view().banking_events;
+    if events.is_empty() {
+        return vec!["No banking changes".to_string()];
+    }
+
+    events[events.len().saturating_sub(count)..]
+        .iter()
+        .map(|event| {
+            let changes: Vec<String> = (0..4)
+                .filter(|slot| event.previous[*slot] != event.current[*slot])
+                .map(|slot| format!("{:04x}: {} -> {}", slot * 0x4000, event.previous[slot], event.current[slot]))
+                .collect();
+            changes.join(", ")
+        })
+        .collect()
+}
+
+fn memory_diff_lines(system: &dyn system::System) -> Vec<String> {
+    // the latest changes of watched RAM, oldest first
+    let diffs = system.get_debug_view().memory_diffs;
+    if diffs.is_empty() {
+        return vec!["No RAM changes".to_string()];
+    }
+
+    diffs.iter().flat_map(|diff| diff.lines()).collect()
+}
+
+pub fn psg_lines(system: &dyn system::System) -> Vec<String> {
+    (0..16u8)
+        .collect::<Vec<u8>>()
+        .chunks(8)
+        .map(|registers| {
+            let values: Vec<String> = registers.iter().map(|register| format!("{:02x}", system.read_psg_register(*register))).collect();
+            format!("R{:<2} {}", registers[0], values.join(" "))
+        })
+        .collect()
+}
+
+pub fn memory_lines(system: &dyn system::System, start: u16, rows: usize) -> Vec<String> {
+    // labels within a row are shown above it
+    let project = system.get_project();
+    let project = project.borrow();
+    (0..rows as u16)
+        .flat_map(|row| {
+            let address = start.wrapping_add(row * 8);
+            let mut lines: Vec<String> = (0..8)
+                .filter_map(|offset| {
+                    let address = address.wrapping_add(offset);
+                    project.read_label(address).map(|label| format!("{:04x} {}:", address, label))
+                })
+                .collect();
+            let bytes: Vec<u8> = (0..8).map(|offset| system.read_memory(address.wrapping_add(offset))).collect();
+            let hex: Vec<String> = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+            let text: String = bytes
+                .iter()
+                .map(|byte| if (0x20..0x7f).contains(byte) { *byte as char } else { '.' })
+                .collect();
+            lines.push(format!("{:04x} {} {}", address, hex.join(" "), text));
+            lines
+        })
+        .collect()
+}
+
+pub fn disassembly_lines(system: &dyn system::System, start: u16, count: usize) -> Vec<String> {
+    let project = system.get_project();
+    let project = project.borrow();
+    system
+        .disassemble(start, count)
+        .iter()
+        .flat_map(|(address, text)| project.annotate(*address, text))
+        .collect()
+}
+
+fn parse_address(system: &dyn system::System, text: &str) -> Result<u16, String> {
+    let value = Expression::parse(text)?.evaluate(&expression::SystemContext(system))?;
+    if !(-0x8000..=0xffff).contains(&value) {
+        return Err(format!("{} is out of range", value));
+    }
+
+    Ok(value as u16)
+}
+
+fn parse_count(system: &dyn system::System, text: Option<&&str>, default: usize) -> Result<usize, String> {
+    match text {
+        Some(text) => Ok(parse_address(system, text)? as usize),
+        None => Ok(default),
+    }
+}
+
+fn parse_range(system: &dyn system::System, start: &str, end: &str) -> Result<(u16, u16), String> {
+    let start = parse_address(system, start)?;
+    let end = parse_address(system, end)?;
+    if end < start {
+        return Err(format!("{:04x} is before {:04x}", end, start));
+    }
+
+    Ok((start, end))
+}
+
+fn parse_register(name: &str) -> Option<cpu::Register16> {
+    match name.to_lowercase().as_str() {
+        "af" => Some(cpu::Register16::AF),
+        "bc" => Some(cpu::Register16::BC),
+        "de" => Some(cpu::Register16::DE),
+        "hl" => Some(cpu::Register16::HL),
+        "ix" => Some(cpu::Register16::IX),
+        "iy" => Some(cpu::Register16::IY),
+        "sp" => Some(cpu::Register16::SP),
+        "pc" => Some(cpu::Register16::PC),
+        _ => None,
+    }
+}
+
+fn format_value(text: &str, value: Result<i64, String>) -> String {
+    match value {
+        Ok(value) => format!("{} = {} ({:#06x})", text, value, value as u16),
+        Err(error) => format!("{}: {}", text, error),
+    }
+}
@@ -1,127 +1,30 @@
-use std::io::Write;
-use nom::{
-    IResult,
-    branch::alt,
-    bytes::complete::{tag, take_while, take_while1, take_while_m_n},
-    combinator::{map_res, opt},
-    sequence::{delimited, pair, separated_pair}
-};
-
 use crate::bus;
 use crate::cpu;
+use crate::expression;
 use crate::memory;
+use crate::project;
 
 
-#[derive(Debug)]
-enum Command {
-    ToggleBreakpoint(u16),
-    ShowCpuRegisters,
-    Step(u16),
-    Continue,
-    Disassemble(u16),
-}
-
-impl Command {
-    fn parse(input: &str) -> IResult<&str, Command> {
-        alt((
-            parse_toggle_breakpoint,
-            parse_show_cpu_registers,
-            parse_step,
-            parse_continue,
-            parse_disassemble,
-        ))(input)
-    }
-}
-
-fn is_decimal_digit(c: char) -> bool {
-    c.is_digit(10)
-}
-
-fn from_decimal_str(input: &str) -> Result<u16, std::num::ParseIntError> {
-    u16::from_str_radix(input, 10)
-}
-
-fn parse_decimal(input: &str) -> IResult<&str, u16> {
-    map_res(
-        take_while_m_n(1, 5, is_decimal_digit),
-        from_decimal_str
-    )(input)
-}
-
-fn is_hex_digit(c: char) -> bool {
-    c.is_digit(16)
-}
-
-fn from_hex_str(input: &str) -> Result<u16, std::num::ParseIntError> {
-    u16::from_str_radix(input, 16)
-}
-
-fn parse_hex(input: &str) -> IResult<&str, u16> {
-    let (input, _) = tag("0x")(input)?;
-    map_res(
-        take_while_m_n(1, 4, is_hex_digit),
-        from_hex_str
-    )(input)
-}
-
-fn is_whitespace(c: char) -> bool {
-    c.is_whitespace()
-}
-
-fn parse_toggle_breakpoint(input: &str) -> IResult<&str, Command> {
-    let (input, (_, address)) = delimited(
-        take_while(is_whitespace),
-        separated_pair(
-            alt((tag("breakpoint"), tag("break"), tag("b"))),
-            take_while1(is_whitespace),
-            alt((parse_hex, parse_decimal))
-        ),
-        take_while(is_whitespace)
-    )(input)?;
-
-    Ok((input, Command::ToggleBreakpoint(address)))
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Resume {
+    Run,
+    Step(u16), // number of instructions to execute before stopping again, block instructions count each iteration
+    Next,      // until the PC leaves the current instruction, i.e. after all iterations of a block instruction
+    RunToSync(bus::SyncSignal, u64),
+    RunToLine(u16), // until the CRTC starts this scan line
 }
 
-fn parse_show_cpu_registers(input: &str) -> IResult<&str, Command> {
-    let (input, _) = alt((tag("registers"), tag("reg"), tag("r")))(input)?;
-
-    Ok((input, Command::ShowCpuRegisters))
-}
-
-fn parse_step(input: &str) -> IResult<&str, Command> {
-    let (input, _) = take_while(is_whitespace)(input)?;
-    let (input, _) = alt((tag("step"), tag("s")))(input)?;
-
-    let (input, argument) = opt(pair(
-        take_while1(is_whitespace),
-        alt((parse_hex, parse_decimal))
-    ))(input)?;
-
-    let (input, _) = take_while(is_whitespace)(input)?;
-
-    match argument {
-        Some((_, count)) => Ok((input, Command::Step(count))),
-        None => Ok((input, Command::Step(0))),
-    }
-}
-
-fn parse_continue(input: &str) -> IResult<&str, Command> {
-    let (input, _) = alt((tag("continue"), tag("cont"), tag("c")))(input)?;
-
-    Ok((input, Command::Continue))
-}
-
-fn parse_disassemble(input: &str) -> IResult<&str, Command> {
-    let (input, _) = alt((tag("disassemble"), tag("dis"), tag("d")))(input)?;
-
-    Ok((input, Command::Disassemble(10)))
-}
-
-
+// decides when the emulation stops for debugging, the debug console then takes over until it resumes
 pub struct Debugger<M, B> {
     cpu: cpu::CPUShared<M, B>,
-    breakpoints: Vec<u16>,
+    project: project::ProjectShared,
+    breakpoints: Vec<(u16, Option<expression::Expression>)>,
+    stopped: bool,
+    resume_address: Option<u16>, // breakpoints here are passed over until the PC leaves, e.g. for the iterations of an LDIR
+    until_next: bool,
     countdown: Option<u16>,
+    sync_target: Option<(bus::SyncSignal, u64)>,
+    line_target: Option<(u16, u16)>, // with the scan line seen before the last instruction
 }
 
 impl<M, B> Debugger<M, B>
@@ -129,108 +32,155 @@ where
     M: memory::Read + memory::Write,
     B: bus::Bus,
 {
-    pub fn new_shared(cpu: cpu::CPUShared<M, B>) -> Debugger<M, B> {
+    pub fn new_shared(cpu: cpu::CPUShared<M, B>, project: project::ProjectShared) -> Debugger<M, B> {
         Debugger {
             cpu,
+            project,
             breakpoints: Vec::new(),
+            stopped: false,
+            resume_address: None,
+            until_next: false,
             countdown: None,
+            sync_target: None,
+            line_target: None,
         }
     }
 
     pub fn activate(&mut self) {
+        // stops before the next instruction
         self.countdown = Some(0);
     }
 
-    pub fn is_active(&mut self) -> bool {
+    pub fn is_stopped(&self) -> bool {
+        self.stopped
+    }
+
+    pub fn check(&mut self) -> bool {
+        // called before each instruction, returns whether the emulation has to stay stopped
+        if self.stopped {
+            return true;
+        }
+
+        // repeating block instructions leave the PC on themselves until the last iteration
         let address = self.cpu.borrow().registers.read_word(&cpu::Register16::PC);
-        if self.breakpoint_at(address) {
+        let left = match self.resume_address {
+            Some(resume_address) if resume_address != address => {
+                self.resume_address = None;
+                true
+            }
+            _ => false,
+        };
+        if (left && self.until_next) || (self.resume_address.is_none() && self.breakpoint_hit(address)) {
+            self.stop();
             return true;
         }
 
+        if let Some((signal, target)) = self.sync_target {
+            if self.cpu.borrow().bus.borrow().count_sync_signals(signal) >= target {
+                self.stop();
+                return true;
+            }
+        }
+
+        if let Some((target, previous)) = self.line_target {
+            let (raster_line, _) = self.cpu.borrow().bus.borrow().read_raster_position();
+            if raster_line == target && previous != target {
+                self.stop();
+                return true;
+            }
+            self.line_target = Some((target, raster_line));
+        }
+
         match self.countdown {
+            Some(0) => {
+                self.stop();
+                true
+            }
             Some(countdown) => {
-                if countdown == 0 {
-                    self.countdown = None;
-                    true
-                } else {
-                    self.countdown = Some(countdown - 1);
-                    false
-                }
+                self.countdown = Some(countdown - 1);
+                false
             }
             None => false,
         }
     }
 
-    pub fn run_command_shell(&mut self) {
-        let address = self.cpu.borrow().registers.read_word(&cpu::Register16::PC) as usize;
-        let (instruction, _) = self.cpu.borrow_mut().decoder.decode_at(address);
-        println!("{:#06x}: {}", address, &instruction);
-
-        loop {
-            print!("> ");
-            std::io::stdout().flush().unwrap(); // TODO: is the a better way to handle the result?
-
-            let mut input = String::new();
-            match std::io::stdin().read_line(&mut input) {
-                Ok(_) => {
-                    if let Ok((_, command)) = Command::parse(&input) {
-                        match command {
-                            Command::ToggleBreakpoint(address) => {
-                                if self.breakpoint_at(address) {
-                                    self.remove_breakpoint(address);
-                                } else {
-                                    self.add_breakpoint(address);
-                                }
-                                
-                                println!("Active breakpoints:");
-                                for breakpoint in &self.breakpoints {
-                                    println!("{:#06x}", breakpoint);
-                                }
-                            }
-                            Command::ShowCpuRegisters => {
-                                self.cpu.borrow().print_state();
-                            }
-                            Command::Step(skip) => {
-                                self.countdown = Some(skip);
-                                break;
-                            }
-                            Command::Continue => {
-                                break;
-                            }
-                            Command::Disassemble(count) => {
-                                let mut address = self.cpu.borrow().registers.read_word(&cpu::Register16::PC) as usize;
-                                for _ in 0..count {
-                                    let (instruction, next_adress) = self.cpu.borrow_mut().decoder.decode_at(address);
-                                    println!("{:#06x}: {}", address, &instruction);
-                                    address = next_adress;                    
-                                }
-                            }
-                        }
-                    }
-                }
-                Err(error) => {
-                    println!("Error reading from stdin: {}", error);
-                    break;
-                }
+    pub fn resume(&mut self, resume: Resume) {
+        self.stopped = false;
+        self.resume_address = Some(self.cpu.borrow().registers.read_word(&cpu::Register16::PC));
+        match resume {
+            Resume::Run => (),
+            Resume::Step(count) => self.countdown = Some(count.max(1)),
+            Resume::Next => self.until_next = true,
+            Resume::RunToSync(signal, count) => {
+                let current = self.cpu.borrow().bus.borrow().count_sync_signals(signal);
+                self.sync_target = Some((signal, current + count));
+            }
+            Resume::RunToLine(line) => {
+                // the current line only counts once the beam comes around again
+                let (raster_line, _) = self.cpu.borrow().bus.borrow().read_raster_position();
+                self.line_target = Some((line, raster_line));
             }
         }
     }
 
-    fn breakpoint_at(&self, address: u16) -> bool {
-        for breakpoint in &self.breakpoints {
-            if *breakpoint == address {
-                return true;
-            }
-        }
-        
-        false
+    pub fn add_breakpoint(&mut self, address: u16, condition: Option<expression::Expression>) {
+        self.remove_breakpoint(address);
+        self.breakpoints.push((address, condition));
     }
-    
-    fn add_breakpoint(&mut self, address: u16) {
-        self.breakpoints.push(address);
+
+    pub fn remove_breakpoint(&mut self, address: u16) -> bool {
+        let count = self.breakpoints.len();
+        self.breakpoints.retain(|(breakpoint, _)| *breakpoint != address);
+
+        self.breakpoints.len() != count
+    }
+
+    pub fn list_breakpoints(&self) -> Vec<(u16, Option<String>)> {
+        self.breakpoints
+            .iter()
+            .map(|(address, condition)| (*address, condition.as_ref().map(|condition| condition.text().to_string())))
+            .collect()
+    }
+
+    fn stop(&mut self) {
+        self.stopped = true;
+        self.resume_address = None;
+        self.until_next = false;
+        self.countdown = None;
+        self.sync_target = None;
+        self.line_target = None;
     }
 
-    fn remove_breakpoint(&mut self, address: u16) {
-        self.breakpoints = self.breakpoints.iter().filter(|breakpoint| **breakpoint != address ).copied().collect();
+    fn breakpoint_hit(&self, address: u16) -> bool {
+        // conditions that cannot be evaluated, e.g. because of a missing label, stop as well
+        self.breakpoints.iter().any(|(breakpoint, condition)| {
+            *breakpoint == address
+                && match condition {
+                    Some(condition) => condition.is_true(self).unwrap_or(true),
+                    None => true,
+                }
+        })
     }
-}
\ No newline at end of file
+}
+
+impl<M, B> expression::Context for Debugger<M, B>
+where
+    M: memory::Read,
+    B: bus::Bus,
+{
+    fn read_register(&self, register: cpu::Register16) -> u16 {
+        self.cpu.borrow().registers.read_word(&register)
+    }
+
+    fn read_memory(&self, address: u16) -> u8 {
+        self.cpu.borrow().memory.borrow().read_byte(address as usize)
+    }
+
+    fn read_raster_position(&self) -> (u16, u8) {
+        self.cpu.borrow().bus.borrow().read_raster_position()
+    }
+
+    fn find_symbol(&self, name: &str) -> Option<u16> {
+        self.project.borrow().find_label(name)
+    }
+}
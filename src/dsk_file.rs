@@ -1,28 +1,121 @@
-use std::convert::TryInto;
 use std::io::Read;
 
+use crate::bytes;
+use crate::media;
+
+const MAX_SECTORS: u8 = 29; // sector infos that fit into a track header
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DiskFormat {
+    Standard, // all tracks have the same size, which copy protected disks often do not fit
+    Extended,
+}
+
+impl DiskFormat {
+    pub fn from_name(name: &str) -> Option<DiskFormat> {
+        match name {
+            "standard" | "dsk" => Some(DiskFormat::Standard),
+            "extended" | "edsk" => Some(DiskFormat::Extended),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            DiskFormat::Standard => "standard",
+            DiskFormat::Extended => "extended",
+        }
+    }
+}
+
+// what writes to a disk can change, to roll them back
+#[derive(Clone, PartialEq)]
+pub struct DiskContents {
+    num_tracks: u8,
+    num_sides: u8,
+    track_size: u16,
+    tracks: Vec<Track>,
+    dirty: bool,
+}
 
 pub struct Disk {
+    source: media::MediaSource,
     extended: bool,
     creator: String,
     num_tracks: u8,
     num_sides: u8,
     track_size: u16,
     tracks: Vec<Track>,
+    dirty: bool, // written since it was loaded or saved
+    write_protected: bool,
 }
 
 impl Disk {
+    pub fn new(source: media::MediaSource) -> Disk {
+        // an unformatted single sided disk, tracks appear when they are formatted
+        Disk {
+            source,
+            extended: true,
+            creator: "Ronald".to_string(),
+            num_tracks: 0,
+            num_sides: 1,
+            track_size: 0,
+            tracks: Vec::new(),
+            dirty: false,
+            write_protected: false,
+        }
+    }
+
     pub fn load(filename: &str) -> std::io::Result<Disk> {
+        Disk::load_with_mode(filename, media::ParseMode::Permissive)
+    }
+
+    pub fn load_with_mode(filename: &str, mode: media::ParseMode) -> std::io::Result<Disk> {
         let mut file = std::fs::File::open(filename)?;
         let mut contents = Vec::new();
         file.read_to_end(&mut contents)?;
 
-        let header = b"MV - CPCEMU Disk-File\r\nDisk-Info\r\n";
-        let extended = match contents[0..0x22].cmp(header) {
-            std::cmp::Ordering::Equal => false,
-            _ => return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "Could not find the expected file header.")),
+        Disk::from_bytes_with_mode(media::MediaSource::from_file(filename), &contents, mode, &mut |_| ())
+    }
+
+    pub fn from_bytes(source: media::MediaSource, contents: &[u8]) -> std::io::Result<Disk> {
+        Disk::from_bytes_with_progress(source, contents, &mut |_| ())
+    }
+
+    pub fn from_bytes_with_progress(
+        source: media::MediaSource,
+        contents: &[u8],
+        progress: &mut dyn FnMut(f32),
+    ) -> std::io::Result<Disk> {
+        Disk::from_bytes_with_mode(source, contents, media::ParseMode::Permissive, progress)
+    }
+
+    pub fn from_bytes_with_mode(
+        source: media::MediaSource,
+        contents: &[u8],
+        mode: media::ParseMode,
+        progress: &mut dyn FnMut(f32),
+    ) -> std::io::Result<Disk> {
+        // the source is only used when the disk is saved. Progress is reported after each track,
+        // between 0 and 1, so large images can be parsed without a frontend looking frozen
+        if contents.len() < 0x100 {
+            return Err(invalid_data("Could not find the expected file header."));
+        }
+
+        let extended = if contents.starts_with(b"EXTENDED CPC DSK File\r\nDisk-Info\r\n") {
+            true
+        } else if contents.starts_with(b"MV - CPCEMU Disk-File\r\nDisk-Info\r\n") {
+            false
+        } else if contents.starts_with(b"EXTENDED") {
+            repair(mode, "The extended disk header is misspelled.")?;
+            true
+        } else if contents.starts_with(b"MV - CPC") {
+            repair(mode, "The disk header is misspelled.")?;
+            false
+        } else {
+            return Err(invalid_data("Could not find the expected file header."));
         };
-        
+
         let creator = match String::from_utf8(contents[0x22..0x30].to_vec()) {
             Ok(creator) => creator,
             _ => String::new(),
@@ -30,69 +123,431 @@ impl Disk {
 
         let num_tracks = contents[0x30];
         let num_sides = contents[0x31];
-        let track_size = u16::from_le_bytes(contents[0x32..0x34].try_into().unwrap());
+        let track_size = bytes::read_word(contents, 0x32).unwrap_or(0);
 
-        let header = b"Track-Info\r\n";
         let mut tracks = Vec::new();
-        for track in 0..num_tracks {
-            for side in 0..num_sides {
-                let track_start = track_size as usize * (num_sides as usize * track as usize + side as usize) + 0x100;
-                match contents[track_start..(track_start + 0x0c)].cmp(header) {
-                    std::cmp::Ordering::Equal => {
-                        let track = contents[track_start + 0x10]; // TODO: verify this is the same as the shadowed value?
-                        let side = contents[track_start + 0x11]; // TODO: verify this is the same as the shadowed value?
-                        let sector_size = contents[track_start + 0x14];
-                        let num_sectors = contents[track_start + 0x15];
-                        let gap3_length = contents[track_start + 0x16];
-                        let filler_byte = contents[track_start + 0x15];
-
-                        let mut sector_infos = Vec::new();
-                        let mut sectors = Vec::new();
-                        for sector in 0..num_sectors {
-                            let sector_info_start = 8 * sector as usize + track_start + 0x18;
-                            let sector_data_start = sector_size as usize * sector as usize + track_start + 0x100;
-
-                            sector_infos.push(SectorInfo {
-                                track: contents[sector_info_start + 0x00], // TODO: verify this is the same as above?
-                                side: contents[sector_info_start + 0x01], // TODO: verify this is the same as above?
-                                sector_id: contents[sector_info_start + 0x02],
-                                sector_size: contents[sector_info_start + 0x03], // TODO: verify this is the same as above?
-                                fdc_status1: contents[sector_info_start + 0x04],
-                                fdc_status2: contents[sector_info_start + 0x05],
-                            });
-
-                            sectors.push(contents[sector_data_start..(sector_data_start + sector_size as usize)].to_vec());
-                        }
-
-                        tracks.push(Track {
-                            track,
-                            side,
-                            sector_size,
-                            num_sectors,
-                            gap3_length,
-                            filler_byte,
-                            sector_infos,
-                            sectors,
-                        });
+        let mut track_start = 0x100;
+        for index in 0..(num_tracks as usize * num_sides as usize) {
+            // extended images store the size of each track, where 0 means unformatted
+            let mut size = if extended {
+                contents.get(0x34 + index).copied().unwrap_or(0) as usize * 0x100
+            } else {
+                track_size as usize
+            };
+            if size == 0 && extended {
+                continue;
+            }
+
+            if contents.len() < track_start + 0x100 {
+                let message = format!("The image ends after {} of {} tracks.", index, num_tracks as usize * num_sides as usize);
+                repair(mode, &message)?;
+                break;
+            }
+            if !contents[track_start..].starts_with(b"Track-Info\r\n") {
+                if !contents[track_start..].starts_with(b"Track-Info") {
+                    return Err(invalid_data("Could not find the expected track header."));
+                }
+                repair(mode, "A track header is misspelled.")?;
+            }
+
+            let track = contents[track_start + 0x10];
+            let side = contents[track_start + 0x11];
+            let sector_size = contents[track_start + 0x14];
+            let mut num_sectors = contents[track_start + 0x15];
+            let gap3_length = contents[track_start + 0x16];
+            let filler_byte = contents[track_start + 0x17];
+            if num_sectors > MAX_SECTORS {
+                repair(mode, &format!("Track {} lists more sectors than its header can hold.", track))?;
+                num_sectors = MAX_SECTORS;
+            }
+
+            // some tools leave out the data lengths of extended images, or write a track size
+            // into standard images that does not fit the sectors
+            let mut lengths: Vec<usize> = (0..num_sectors as usize)
+                .map(|sector| {
+                    let sector_info_start = track_start + 0x18 + 8 * sector;
+                    if extended {
+                        bytes::read_word(contents, sector_info_start + 0x06).unwrap_or(0) as usize
+                    } else {
+                        sector_bytes(contents[sector_info_start + 0x03])
                     }
-                    _ => return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "Could not find the expected track header.")),
+                })
+                .collect();
+            if extended && num_sectors > 0 && lengths.iter().all(|length| *length == 0) {
+                repair(mode, &format!("Track {} has no sector data lengths.", track))?;
+                for (sector, length) in lengths.iter_mut().enumerate() {
+                    *length = sector_bytes(contents[track_start + 0x18 + 8 * sector + 0x03]);
                 }
             }
+            let needed = 0x100 + lengths.iter().sum::<usize>();
+            if !extended && size < needed {
+                repair(mode, &format!("Track {} does not fit the track size of the disk header.", track))?;
+                size = needed;
+            }
+
+            if contents.len() < track_start + needed {
+                repair(mode, &format!("Track {} is cut off.", track))?;
+            }
+
+            let mut sector_infos = Vec::new();
+            let mut sectors = Vec::new();
+            let mut sector_data_start = track_start + 0x100;
+            for (sector, length) in lengths.into_iter().enumerate() {
+                let sector_info_start = track_start + 0x18 + 8 * sector;
+                let info = SectorInfo {
+                    track: contents[sector_info_start],
+                    side: contents[sector_info_start + 0x01],
+                    sector_id: contents[sector_info_start + 0x02],
+                    sector_size: contents[sector_info_start + 0x03],
+                    fdc_status1: contents[sector_info_start + 0x04],
+                    fdc_status2: contents[sector_info_start + 0x05],
+                };
+
+                // the actual data length may differ from the declared size on copy protected disks
+                let available = contents.get(sector_data_start..).unwrap_or(&[]);
+                let mut data = available[..length.min(available.len())].to_vec();
+                data.resize(length, filler_byte);
+
+                sectors.push(data);
+                sector_infos.push(info);
+                sector_data_start += length;
+            }
+
+            tracks.push(Track {
+                track,
+                side,
+                sector_size,
+                num_sectors,
+                gap3_length,
+                filler_byte,
+                sector_infos,
+                sectors,
+            });
+
+            track_start += size;
+            progress(track_start.min(contents.len()) as f32 / contents.len() as f32);
         }
 
         let disk = Disk {
+            source,
             extended,
             creator,
             num_tracks,
             num_sides,
             track_size,
             tracks,
+            dirty: false,
+            write_protected: false,
         };
 
         Ok(disk)
     }
+
+    pub fn save(&mut self) -> std::io::Result<()> {
+        // writes the disk back to where it came from
+        let contents = self.to_bytes()?;
+        self.source.write_back(&contents)?;
+        self.dirty = false;
+
+        Ok(())
+    }
+
+    pub fn save_as(&mut self, filename: &str) -> std::io::Result<()> {
+        // later saves go to the new file as well
+        let contents = self.to_bytes()?;
+        std::fs::write(filename, contents)?;
+        self.source = media::MediaSource::from_file(filename);
+        self.dirty = false;
+
+        Ok(())
+    }
+
+    pub fn to_bytes(&self) -> std::io::Result<Vec<u8>> {
+        // written as an extended image, which keeps the sizes of copy protected sectors
+        self.to_bytes_as(DiskFormat::Extended)
+    }
+
+    pub fn to_bytes_as(&self, format: DiskFormat) -> std::io::Result<Vec<u8>> {
+        match format {
+            DiskFormat::Standard => self.to_standard_bytes(),
+            DiskFormat::Extended => self.to_extended_bytes(),
+        }
+    }
+
+    pub fn is_extended(&self) -> bool {
+        // the format the disk was loaded from
+        self.extended
+    }
+
+    pub fn normalize(&mut self) -> usize {
+        // orders the tracks and gives every sector the data length its size code declares, so the
+        // disk fits a standard image. Returns the number of sectors that were cut or padded
+        self.tracks.sort_by_key(|track| (track.track, track.side));
+
+        let mut changed = 0;
+        for track in &mut self.tracks {
+            for (info, data) in track.sector_infos.iter().zip(track.sectors.iter_mut()) {
+                let length = sector_bytes(info.sector_size);
+                if data.len() != length {
+                    data.resize(length, track.filler_byte);
+                    changed += 1;
+                }
+            }
+        }
+        if changed > 0 {
+            self.dirty = true;
+        }
+
+        changed
+    }
+
+    fn to_standard_bytes(&self) -> std::io::Result<Vec<u8>> {
+        let num_sides = self.tracks.iter().map(|t| t.side + 1).max().unwrap_or(1).max(self.num_sides);
+        let mut track_size = 0x100;
+        for track in &self.tracks {
+            if track.sector_infos.len() > MAX_SECTORS as usize {
+                return Err(invalid_data("Too many sectors for the track header."));
+            }
+            for (info, data) in track.sector_infos.iter().zip(track.sectors.iter()) {
+                if data.len() != sector_bytes(info.sector_size) {
+                    let message = format!("Track {} has sectors that need an extended image, normalize the disk first.", track.track);
+                    return Err(invalid_data(&message));
+                }
+            }
+            track_size = track_size.max(0x100 + track.sectors.iter().map(|data| data.len()).sum::<usize>());
+        }
+        if track_size > 0xffff {
+            return Err(invalid_data("Track exceeds the maximum size of standard disk images."));
+        }
+
+        let mut contents = vec![0; 0x100];
+        contents[..0x22].copy_from_slice(b"MV - CPCEMU Disk-File\r\nDisk-Info\r\n");
+        let creator = format!("{:<14}", "Ronald");
+        contents[0x22..0x30].copy_from_slice(&creator.as_bytes()[..14]);
+        contents[0x30] = self.num_tracks;
+        contents[0x31] = num_sides;
+        bytes::write_word(&mut contents, 0x32, track_size as u16);
+
+        for index in 0..(self.num_tracks as usize * num_sides as usize) {
+            // unformatted tracks are written without sectors, since every track has to be there
+            let (track_number, side) = ((index / num_sides as usize) as u8, (index % num_sides as usize) as u8);
+            let track = self.tracks.iter().find(|t| t.track == track_number && t.side == side);
+            let track_start = contents.len();
+            contents.extend_from_slice(&track_header(track_number, side, track));
+            if let Some(track) = track {
+                for data in &track.sectors {
+                    contents.extend_from_slice(data);
+                }
+            }
+            contents.resize(track_start + track_size, 0);
+        }
+
+        Ok(contents)
+    }
+
+    fn to_extended_bytes(&self) -> std::io::Result<Vec<u8>> {
+        let num_sides = self.tracks.iter().map(|t| t.side + 1).max().unwrap_or(1).max(self.num_sides);
+        let mut contents = vec![0; 0x100];
+        contents[..0x22].copy_from_slice(b"EXTENDED CPC DSK File\r\nDisk-Info\r\n");
+        let creator = format!("{:<14}", "Ronald");
+        contents[0x22..0x30].copy_from_slice(&creator.as_bytes()[..14]);
+        contents[0x30] = self.num_tracks;
+        contents[0x31] = num_sides;
+        if self.num_tracks as usize * num_sides as usize > 0x100 - 0x34 {
+            return Err(invalid_data("Too many tracks for the disk header."));
+        }
+
+        for index in 0..(self.num_tracks as usize * num_sides as usize) {
+            let (track_number, side) = ((index / num_sides as usize) as u8, (index % num_sides as usize) as u8);
+            let track = match self.tracks.iter().find(|t| t.track == track_number && t.side == side) {
+                Some(track) => track,
+                None => continue, // unformatted, the size stays 0
+            };
+            if track.sector_infos.len() > MAX_SECTORS as usize {
+                return Err(invalid_data("Too many sectors for the track header."));
+            }
+
+            let track_start = contents.len();
+            contents.extend_from_slice(&track_header(track_number, side, Some(track)));
+            for data in &track.sectors {
+                contents.extend_from_slice(data);
+            }
+            let size = (contents.len() - track_start + 0xff) & !0xff;
+            if size > 0xff00 {
+                return Err(invalid_data("Track exceeds the maximum size of extended disk images."));
+            }
+            contents.resize(track_start + size, 0);
+            contents[0x34 + index] = (size / 0x100) as u8;
+        }
+
+        Ok(contents)
+    }
+
+    pub fn get_source(&self) -> &media::MediaSource {
+        &self.source
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    pub fn save_contents(&self) -> DiskContents {
+        DiskContents {
+            num_tracks: self.num_tracks,
+            num_sides: self.num_sides,
+            track_size: self.track_size,
+            tracks: self.tracks.clone(),
+            dirty: self.dirty,
+        }
+    }
+
+    pub fn load_contents(&mut self, contents: &DiskContents) {
+        self.num_tracks = contents.num_tracks;
+        self.num_sides = contents.num_sides;
+        self.track_size = contents.track_size;
+        self.tracks = contents.tracks.clone();
+        self.dirty = contents.dirty;
+    }
+
+    pub fn set_write_protected(&mut self, protect: bool) {
+        self.write_protected = protect;
+    }
+
+    pub fn is_write_protected(&self) -> bool {
+        self.write_protected
+    }
+
+    pub fn content_hash(&self) -> u32 {
+        // only sector IDs and contents are hashed, so the hash does not depend on the image format or creator
+        let mut data = Vec::new();
+        for track in &self.tracks {
+            data.extend_from_slice(&[track.track, track.side]);
+            for (info, sector) in track.sector_infos.iter().zip(track.sectors.iter()) {
+                data.push(info.sector_id);
+                data.extend_from_slice(sector);
+            }
+        }
+
+        media::crc32(&data)
+    }
+
+    pub fn read_sector(&self, track: u8, side: u8, sector_id: u8) -> Option<&[u8]> {
+        let track = self.tracks.iter().find(|t| t.track == track && t.side == side)?;
+        let index = track.sector_infos.iter().position(|info| info.sector_id == sector_id)?;
+
+        Some(&track.sectors[index])
+    }
+
+    pub fn read_sector_infos(&self, track: u8, side: u8) -> &[SectorInfo] {
+        match self.tracks.iter().find(|t| t.track == track && t.side == side) {
+            Some(track) => &track.sector_infos,
+            None => &[], // unformatted
+        }
+    }
+
+    pub fn write_sector(&mut self, track: u8, side: u8, sector_id: u8, data: &[u8]) -> bool {
+        let track = match self.tracks.iter_mut().find(|t| t.track == track && t.side == side) {
+            Some(track) => track,
+            None => return false,
+        };
+
+        match track.sector_infos.iter().position(|info| info.sector_id == sector_id) {
+            Some(index) => {
+                let sector = &mut track.sectors[index];
+                let length = sector.len().min(data.len());
+                sector[..length].copy_from_slice(&data[..length]);
+                self.dirty = true;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn format_track(&mut self, track: u8, side: u8, sector_infos: Vec<SectorInfo>, filler_byte: u8) {
+        let sector_size = sector_infos.first().map(|info| info.sector_size).unwrap_or(2);
+        let sectors = sector_infos
+            .iter()
+            .map(|info| vec![filler_byte; sector_bytes(info.sector_size)])
+            .collect();
+
+        let formatted = Track {
+            track,
+            side,
+            sector_size,
+            num_sectors: sector_infos.len() as u8,
+            gap3_length: 0x4e,
+            filler_byte,
+            sector_infos,
+            sectors,
+        };
+
+        self.dirty = true;
+        match self.tracks.iter_mut().find(|t| t.track == track && t.side == side) {
+            Some(existing) => *existing = formatted,
+            None => {
+                self.tracks.push(formatted);
+                self.num_tracks = self.num_tracks.max(track + 1);
+            }
+        }
+    }
+
+    pub fn num_sides(&self) -> u8 {
+        self.num_sides
+    }
+}
+
+fn track_header(track_number: u8, side: u8, track: Option<&Track>) -> Vec<u8> {
+    // sector data lengths are only read from extended images, standard ones ignore them
+    let mut header = vec![0; 0x100];
+    header[..0x0c].copy_from_slice(b"Track-Info\r\n");
+    header[0x10] = track_number;
+    header[0x11] = side;
+    let track = match track {
+        Some(track) => track,
+        None => return header,
+    };
+
+    header[0x14] = track.sector_size;
+    header[0x15] = track.sector_infos.len() as u8;
+    header[0x16] = track.gap3_length;
+    header[0x17] = track.filler_byte;
+    for (sector, (info, data)) in track.sector_infos.iter().zip(track.sectors.iter()).enumerate() {
+        let start = 8 * sector + 0x18;
+        header[start..(start + 6)].copy_from_slice(&[
+            info.track,
+            info.side,
+            info.sector_id,
+            info.sector_size,
+            info.fdc_status1,
+            info.fdc_status2,
+        ]);
+        bytes::write_word(&mut header, start + 6, data.len() as u16);
+    }
+
+    header
+}
+
+fn repair(mode: media::ParseMode, message: &str) -> std::io::Result<()> {
+    // permissive parsing works around the problem, strict parsing rejects the image
+    match mode {
+        media::ParseMode::Strict => Err(invalid_data(message)),
+        media::ParseMode::Permissive => {
+            log::warn!("{} Repairing the disk image.", message);
+            Ok(())
+        }
+    }
 }
 
+fn invalid_data(message: &str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message)
+}
+
+fn sector_bytes(size_code: u8) -> usize {
+    // the FDC encodes sector sizes as 128 << N
+    0x80 << size_code.min(6)
+}
+
+#[derive(Clone, PartialEq)]
 struct Track {
     track: u8,
     side: u8,
@@ -104,11 +559,12 @@ struct Track {
     sectors: Vec<Vec<u8>>,
 }
 
-struct SectorInfo {
-    track: u8,
-    side: u8,
-    sector_id: u8,
-    sector_size: u8,
-    fdc_status1: u8,
-    fdc_status2: u8,
+#[derive(Clone, Debug, PartialEq)]
+pub struct SectorInfo {
+    pub track: u8,
+    pub side: u8,
+    pub sector_id: u8,
+    pub sector_size: u8,
+    pub fdc_status1: u8,
+    pub fdc_status2: u8,
 }
@@ -0,0 +1,39 @@
+use std::cell::RefCell;
+use std::ops::RangeInclusive;
+use std::rc::Rc;
+
+use crate::system::SystemConfig;
+
+pub type ExpansionDeviceShared = Rc<RefCell<dyn ExpansionDevice>>;
+pub type ExpansionFactory = fn(&SystemConfig) -> ExpansionDeviceShared;
+
+// peripherals connected to the expansion port, e.g. Multiface, serial interfaces, clocks or memory expansions
+pub trait ExpansionDevice {
+    fn name(&self) -> &'static str;
+
+    fn port_ranges(&self) -> Vec<RangeInclusive<u16>>;
+
+    fn read_byte(&mut self, port: u16) -> u8;
+
+    fn write_byte(&mut self, port: u16, value: u8);
+
+    fn step(&mut self, _microseconds: u8) {}
+
+    fn overlays_memory(&self) -> bool {
+        false
+    }
+
+    fn read_memory(&self, _address: usize) -> Option<u8> {
+        // devices with a memory overlay return Some(value) for addresses they map in
+        None
+    }
+
+    fn read_audio_level(&self) -> Option<f32> {
+        // devices producing sound, e.g. speech synthesizers, return their output level between 0 and 1
+        None
+    }
+
+    fn decodes_port(&self, port: u16) -> bool {
+        self.port_ranges().iter().any(|range| range.contains(&port))
+    }
+}
@@ -0,0 +1,495 @@
+use crate::cpu;
+use crate::system;
+
+// what expressions can refer to, implemented for the running system and the debugger
+pub trait Context {
+    fn read_register(&self, register: cpu::Register16) -> u16;
+
+    fn read_memory(&self, address: u16) -> u8;
+
+    fn read_raster_position(&self) -> (u16, u8);
+
+    fn find_symbol(&self, name: &str) -> Option<u16>;
+}
+
+pub struct SystemContext<'a>(pub &'a dyn system::System);
+
+impl<'a> Context for SystemContext<'a> {
+    fn read_register(&self, register: cpu::Register16) -> u16 {
+        self.0.read_register(register)
+    }
+
+    fn read_memory(&self, address: u16) -> u8 {
+        self.0.read_memory(address)
+    }
+
+    fn read_raster_position(&self) -> (u16, u8) {
+        self.0.read_raster_position()
+    }
+
+    fn find_symbol(&self, name: &str) -> Option<u16> {
+        self.0.get_project().borrow().find_label(name)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Register {
+    Word(cpu::Register16),
+    High(cpu::Register16),
+    Low(cpu::Register16),
+    RasterLine,      // scan line of the CRTC within the frame
+    RasterCharacter, // character position within the scan line
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum UnaryOperator {
+    Negate,
+    Not,
+    Complement,
+    PeekByte,
+    PeekWord, // little endian, as stored by the Z80
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum BinaryOperator {
+    Or,
+    And,
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    BitOr,
+    BitXor,
+    BitAnd,
+    ShiftLeft,
+    ShiftRight,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Remainder,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Node {
+    Number(i64),
+    Register(Register),
+    Symbol(String), // resolved on evaluation, as labels may change while debugging
+    Unary(UnaryOperator, Box<Node>),
+    Binary(BinaryOperator, Box<Node>, Box<Node>),
+}
+
+// small expression language for conditional breakpoints, watches and the command palette:
+//
+// hl + 2 == 0x4000 && peek(sp) != $ff
+//
+// Numbers are decimal or hexadecimal with a 0x, & or $ prefix. Names are registers (a, hl, ixh, ...),
+// the beam position (line, char) or labels from the project, peek() and peekw() read a byte or word from memory as the CPU sees it.
+// Operators follow C precedence, comparisons and logical operators yield 0 or 1.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Expression {
+    root: Node,
+    text: String,
+}
+
+impl Expression {
+    pub fn parse(text: &str) -> Result<Expression, String> {
+        let mut parser = Parser {
+            tokens: tokenize(text)?,
+            position: 0,
+        };
+        let root = parser.parse_binary(0)?;
+        if let Some(token) = parser.peek() {
+            return Err(format!("Unexpected \"{}\" in \"{}\".", token, text));
+        }
+
+        Ok(Expression {
+            root,
+            text: text.trim().to_string(),
+        })
+    }
+
+    pub fn evaluate(&self, context: &dyn Context) -> Result<i64, String> {
+        evaluate(&self.root, context)
+    }
+
+    pub fn is_true(&self, context: &dyn Context) -> Result<bool, String> {
+        self.evaluate(context).map(|value| value != 0)
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+}
+
+fn evaluate(node: &Node, context: &dyn Context) -> Result<i64, String> {
+    let value = match node {
+        Node::Number(value) => *value,
+        Node::Register(Register::Word(register)) => context.read_register(*register) as i64,
+        Node::Register(Register::High(register)) => (context.read_register(*register) >> 8) as i64,
+        Node::Register(Register::Low(register)) => (context.read_register(*register) & 0xff) as i64,
+        Node::Register(Register::RasterLine) => context.read_raster_position().0 as i64,
+        Node::Register(Register::RasterCharacter) => context.read_raster_position().1 as i64,
+        Node::Symbol(name) => match context.find_symbol(name) {
+            Some(address) => address as i64,
+            None => return Err(format!("Unknown symbol \"{}\".", name)),
+        },
+        Node::Unary(operator, operand) => {
+            let operand = evaluate(operand, context)?;
+            match operator {
+                UnaryOperator::Negate => operand.wrapping_neg(),
+                UnaryOperator::Not => (operand == 0) as i64,
+                UnaryOperator::Complement => !operand,
+                UnaryOperator::PeekByte => context.read_memory(operand as u16) as i64,
+                UnaryOperator::PeekWord => {
+                    let address = operand as u16;
+                    context.read_memory(address) as i64 | (context.read_memory(address.wrapping_add(1)) as i64) << 8
+                }
+            }
+        }
+        Node::Binary(BinaryOperator::And, left, right) => {
+            // short circuits, so guards like sp < 0xc000 && peek(sp) == 1 work as expected
+            (evaluate(left, context)? != 0 && evaluate(right, context)? != 0) as i64
+        }
+        Node::Binary(BinaryOperator::Or, left, right) => {
+            (evaluate(left, context)? != 0 || evaluate(right, context)? != 0) as i64
+        }
+        Node::Binary(operator, left, right) => {
+            let left = evaluate(left, context)?;
+            let right = evaluate(right, context)?;
+            match operator {
+                BinaryOperator::Equal => (left == right) as i64,
+                BinaryOperator::NotEqual => (left != right) as i64,
+                BinaryOperator::Less => (left < right) as i64,
+                BinaryOperator::LessOrEqual => (left <= right) as i64,
+                BinaryOperator::Greater => (left > right) as i64,
+                BinaryOperator::GreaterOrEqual => (left >= right) as i64,
+                BinaryOperator::BitOr => left | right,
+                BinaryOperator::BitXor => left ^ right,
+                BinaryOperator::BitAnd => left & right,
+                BinaryOperator::ShiftLeft => left.wrapping_shl(right as u32),
+                BinaryOperator::ShiftRight => left.wrapping_shr(right as u32),
+                BinaryOperator::Add => left.wrapping_add(right),
+                BinaryOperator::Subtract => left.wrapping_sub(right),
+                BinaryOperator::Multiply => left.wrapping_mul(right),
+                BinaryOperator::Divide => left.checked_div(right).ok_or("Division by zero.")?,
+                BinaryOperator::Remainder => left.checked_rem(right).ok_or("Division by zero.")?,
+                BinaryOperator::And | BinaryOperator::Or => unreachable!(),
+            }
+        }
+    };
+
+    Ok(value)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Number(i64),
+    Name(String),
+    Operator(&'static str),
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Token::Number(value) => write!(formatter, "{}", value),
+            Token::Name(name) => write!(formatter, "{}", name),
+            Token::Operator(operator) => write!(formatter, "{}", operator),
+        }
+    }
+}
+
+// longer operators first, so << is not taken for <
+const OPERATORS: [&str; 22] = [
+    "||", "&&", "==", "!=", "<=", ">=", "<<", ">>", "<", ">", "|", "^", "&", "+", "-", "*", "/", "%", "!", "~", "(", ")",
+];
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let characters: Vec<char> = text.chars().collect();
+    let mut tokens = Vec::new();
+    let mut position = 0;
+    while position < characters.len() {
+        let character = characters[position];
+        if character.is_whitespace() {
+            position += 1;
+            continue;
+        }
+
+        // & and $ are hexadecimal prefixes as in BASIC and assemblers, where a number is expected
+        let rest: String = characters[position..].iter().collect();
+        let expects_number = matches!(tokens.last(), None | Some(Token::Operator(_))) && tokens.last() != Some(&Token::Operator(")"));
+        let hex_prefix = ["0x", "&", "$"].iter().find(|prefix| {
+            (expects_number || **prefix == "0x")
+                && rest.starts_with(*prefix)
+                && rest[prefix.len()..].starts_with(|c: char| c.is_ascii_hexdigit())
+        });
+        if let Some(prefix) = hex_prefix {
+            let digits: String = rest[prefix.len()..].chars().take_while(|c| c.is_ascii_hexdigit()).collect();
+            let value = i64::from_str_radix(&digits, 16).map_err(|error| format!("Invalid number \"{}\": {}", digits, error))?;
+            tokens.push(Token::Number(value));
+            position += prefix.len() + digits.len();
+        } else if character.is_ascii_digit() {
+            let digits: String = rest.chars().take_while(|c| c.is_ascii_digit()).collect();
+            let value = digits.parse().map_err(|error| format!("Invalid number \"{}\": {}", digits, error))?;
+            tokens.push(Token::Number(value));
+            position += digits.len();
+        } else if character.is_alphabetic() || character == '_' {
+            let name: String = rest.chars().take_while(|c| c.is_alphanumeric() || *c == '_' || *c == '.').collect();
+            position += name.chars().count();
+            tokens.push(Token::Name(name));
+        } else {
+            match OPERATORS.iter().find(|operator| rest.starts_with(*operator)) {
+                Some(operator) => {
+                    tokens.push(Token::Operator(operator));
+                    position += operator.len();
+                }
+                None => return Err(format!("Unexpected \"{}\" in \"{}\".", character, text)),
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+// binary operators from lowest to highest precedence
+const PRECEDENCE: [&[(&str, BinaryOperator)]; 10] = [
+    &[("||", BinaryOperator::Or)],
+    &[("&&", BinaryOperator::And)],
+    &[("|", BinaryOperator::BitOr)],
+    &[("^", BinaryOperator::BitXor)],
+    &[("&", BinaryOperator::BitAnd)],
+    &[("==", BinaryOperator::Equal), ("!=", BinaryOperator::NotEqual)],
+    &[
+        ("<", BinaryOperator::Less),
+        ("<=", BinaryOperator::LessOrEqual),
+        (">", BinaryOperator::Greater),
+        (">=", BinaryOperator::GreaterOrEqual),
+    ],
+    &[("<<", BinaryOperator::ShiftLeft), (">>", BinaryOperator::ShiftRight)],
+    &[("+", BinaryOperator::Add), ("-", BinaryOperator::Subtract)],
+    &[
+        ("*", BinaryOperator::Multiply),
+        ("/", BinaryOperator::Divide),
+        ("%", BinaryOperator::Remainder),
+    ],
+];
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn expect(&mut self, operator: &str) -> Result<(), String> {
+        match self.next() {
+            Some(Token::Operator(found)) if found == operator => Ok(()),
+            Some(token) => Err(format!("Expected \"{}\" but found \"{}\".", operator, token)),
+            None => Err(format!("Expected \"{}\" at the end.", operator)),
+        }
+    }
+
+    fn parse_binary(&mut self, level: usize) -> Result<Node, String> {
+        if level == PRECEDENCE.len() {
+            return self.parse_unary();
+        }
+
+        let mut left = self.parse_binary(level + 1)?;
+        loop {
+            let operator = match self.peek() {
+                Some(Token::Operator(operator)) => PRECEDENCE[level]
+                    .iter()
+                    .find(|(text, _)| text == operator)
+                    .map(|(_, operator)| *operator),
+                _ => None,
+            };
+            match operator {
+                Some(operator) => {
+                    self.position += 1;
+                    let right = self.parse_binary(level + 1)?;
+                    left = Node::Binary(operator, Box::new(left), Box::new(right));
+                }
+                None => return Ok(left),
+            }
+        }
+    }
+
+    fn parse_unary(&mut self) -> Result<Node, String> {
+        let operator = match self.peek() {
+            Some(Token::Operator("-")) => Some(UnaryOperator::Negate),
+            Some(Token::Operator("!")) => Some(UnaryOperator::Not),
+            Some(Token::Operator("~")) => Some(UnaryOperator::Complement),
+            _ => None,
+        };
+        match operator {
+            Some(operator) => {
+                self.position += 1;
+                Ok(Node::Unary(operator, Box::new(self.parse_unary()?)))
+            }
+            None => self.parse_primary(),
+        }
+    }
+
+    fn parse_primary(&mut self) -> Result<Node, String> {
+        match self.next() {
+            Some(Token::Number(value)) => Ok(Node::Number(value)),
+            Some(Token::Operator("(")) => {
+                let node = self.parse_binary(0)?;
+                self.expect(")")?;
+                Ok(node)
+            }
+            Some(Token::Name(name)) => {
+                let function = match name.to_lowercase().as_str() {
+                    "peek" => Some(UnaryOperator::PeekByte),
+                    "peekw" => Some(UnaryOperator::PeekWord),
+                    _ => None,
+                };
+                if let (Some(function), Some(Token::Operator("("))) = (function, self.peek()) {
+                    self.position += 1;
+                    let address = self.parse_binary(0)?;
+                    self.expect(")")?;
+                    return Ok(Node::Unary(function, Box::new(address)));
+                }
+
+                match parse_register(&name) {
+                    Some(register) => Ok(Node::Register(register)),
+                    None => Ok(Node::Symbol(name)),
+                }
+            }
+            Some(token) => Err(format!("Unexpected \"{}\".", token)),
+            None => Err("Unexpected end of expression.".to_string()),
+        }
+    }
+}
+
+fn parse_register(name: &str) -> Option<Register> {
+    let register = match name.to_lowercase().as_str() {
+        "af" => Register::Word(cpu::Register16::AF),
+        "bc" => Register::Word(cpu::Register16::BC),
+        "de" => Register::Word(cpu::Register16::DE),
+        "hl" => Register::Word(cpu::Register16::HL),
+        "ix" => Register::Word(cpu::Register16::IX),
+        "iy" => Register::Word(cpu::Register16::IY),
+        "sp" => Register::Word(cpu::Register16::SP),
+        "pc" => Register::Word(cpu::Register16::PC),
+        "a" => Register::High(cpu::Register16::AF),
+        "f" => Register::Low(cpu::Register16::AF),
+        "b" => Register::High(cpu::Register16::BC),
+        "c" => Register::Low(cpu::Register16::BC),
+        "d" => Register::High(cpu::Register16::DE),
+        "e" => Register::Low(cpu::Register16::DE),
+        "h" => Register::High(cpu::Register16::HL),
+        "l" => Register::Low(cpu::Register16::HL),
+        "ixh" => Register::High(cpu::Register16::IX),
+        "ixl" => Register::Low(cpu::Register16::IX),
+        "iyh" => Register::High(cpu::Register16::IY),
+        "iyl" => Register::Low(cpu::Register16::IY),
+        "line" => Register::RasterLine,
+        "char" => Register::RasterCharacter,
+        _ => return None,
+    };
+
+    Some(register)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct TestContext {
+        memory: Vec<u8>,
+    }
+
+    impl TestContext {
+        fn new() -> TestContext {
+            let mut memory = vec![0; 0x10000];
+            memory[0x0000] = 0x12;
+            memory[0xffff] = 0x34;
+            TestContext { memory }
+        }
+    }
+
+    impl Context for TestContext {
+        fn read_register(&self, register: cpu::Register16) -> u16 {
+            match register {
+                cpu::Register16::AF => 0xa5c3,
+                cpu::Register16::HL => 0x4000,
+                _ => 0,
+            }
+        }
+
+        fn read_memory(&self, address: u16) -> u8 {
+            self.memory[address as usize]
+        }
+
+        fn read_raster_position(&self) -> (u16, u8) {
+            (100, 20)
+        }
+
+        fn find_symbol(&self, name: &str) -> Option<u16> {
+            match name {
+                "start" => Some(0x4000),
+                _ => None,
+            }
+        }
+    }
+
+    fn evaluate_text(text: &str) -> Result<i64, String> {
+        Expression::parse(text)?.evaluate(&TestContext::new())
+    }
+
+    #[test]
+    fn follows_c_precedence() {
+        assert_eq!(evaluate_text("1 | 2 == 2"), Ok(1));
+        assert_eq!(evaluate_text("1 + 2 * 3"), Ok(7));
+        assert_eq!(evaluate_text("(1 + 2) * 3"), Ok(9));
+        assert_eq!(evaluate_text("1 << 2 + 1"), Ok(8));
+        assert_eq!(evaluate_text("2 < 3 == 1"), Ok(1));
+        assert_eq!(evaluate_text("10 - 4 - 3"), Ok(3));
+    }
+
+    #[test]
+    fn tells_hex_prefixes_from_the_and_operator() {
+        assert_eq!(evaluate_text("a & $ff"), Ok(0xa5));
+        assert_eq!(evaluate_text("a&$0f"), Ok(0x05));
+        assert_eq!(evaluate_text("hl == &4000"), Ok(1));
+        assert_eq!(evaluate_text("(hl) & 1"), Ok(0));
+        assert_eq!(evaluate_text("(hl) & &4000"), Ok(0x4000));
+        assert_eq!(evaluate_text("&ff && 0x10"), Ok(1));
+        assert_eq!(evaluate_text("start & $f000"), Ok(0x4000));
+    }
+
+    #[test]
+    fn short_circuits_logical_operators() {
+        assert_eq!(evaluate_text("0 && missing"), Ok(0));
+        assert_eq!(evaluate_text("1 || missing"), Ok(1));
+        assert_eq!(evaluate_text("hl == &4000 || 1 / 0"), Ok(1));
+        assert!(evaluate_text("1 && missing").is_err());
+        assert!(evaluate_text("0 || missing").is_err());
+    }
+
+    #[test]
+    fn wraps_word_reads_at_the_end_of_memory() {
+        assert_eq!(evaluate_text("peekw(&ffff)"), Ok(0x1234));
+        assert_eq!(evaluate_text("peek(&ffff)"), Ok(0x34));
+        assert_eq!(evaluate_text("peekw(&10000)"), Ok(0x0012));
+    }
+
+    #[test]
+    fn rejects_division_by_zero() {
+        assert_eq!(evaluate_text("1 / 0"), Err("Division by zero.".to_string()));
+        assert_eq!(evaluate_text("1 % (hl - &4000)"), Err("Division by zero.".to_string()));
+        assert_eq!(evaluate_text("7 / 2"), Ok(3));
+    }
+}
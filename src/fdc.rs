@@ -1,42 +1,641 @@
 use std::cell::RefCell;
+use std::collections::VecDeque;
 use std::rc::Rc;
 
+use crate::dsk_file;
+
 pub type FloppyDiskControllerShared = Rc<RefCell<FloppyDiskController>>;
 
-pub struct FloppyDiskController {}
+// main status register
+const STATUS_REQUEST_FOR_MASTER: u8 = 0x80;
+const STATUS_DATA_INPUT: u8 = 0x40; // data flows from the FDC to the CPU
+const STATUS_EXECUTION_MODE: u8 = 0x20;
+const STATUS_BUSY: u8 = 0x10;
+
+// status register 0
+const ST0_ABNORMAL_TERMINATION: u8 = 0x40;
+const ST0_INVALID_COMMAND: u8 = 0x80;
+const ST0_SEEK_END: u8 = 0x20;
+const ST0_NOT_READY: u8 = 0x08;
+
+// status register 1
+const ST1_END_OF_CYLINDER: u8 = 0x80;
+const ST1_NO_DATA: u8 = 0x04;
+const ST1_NOT_WRITABLE: u8 = 0x02;
+const ST1_MISSING_ADDRESS_MARK: u8 = 0x01;
+
+// status register 3
+const ST3_WRITE_PROTECTED: u8 = 0x40;
+const ST3_READY: u8 = 0x20;
+const ST3_TRACK_0: u8 = 0x10;
+const ST3_TWO_SIDE: u8 = 0x08;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Phase {
+    Command,
+    ExecutionRead,
+    ExecutionWrite,
+    Result,
+}
+
+// the controller between two accesses, with the disk contents so writes can be rolled back
+#[derive(Clone)]
+pub struct ControllerState {
+    motor_on: bool,
+    phase: Phase,
+    command: Vec<u8>,
+    data: VecDeque<u8>,
+    result: VecDeque<u8>,
+    present_cylinders: [u8; 4],
+    pending_interrupts: VecDeque<(u8, u8)>,
+    read_id_index: usize,
+    disk_contents: Option<Rc<dsk_file::DiskContents>>,
+}
+
+impl ControllerState {
+    pub fn share_disk_contents(&mut self, previous: &ControllerState) {
+        // states taken one after another mostly hold the same disk, which is only kept once
+        if let (Some(contents), Some(previous_contents)) = (&self.disk_contents, &previous.disk_contents) {
+            if contents == previous_contents {
+                self.disk_contents = previous.disk_contents.clone();
+            }
+        }
+    }
+}
+
+// NEC µPD765A as used in the DDI-1 interface and the CPC 664/6128
+pub struct FloppyDiskController {
+    disk: Option<dsk_file::Disk>,
+    motor_on: bool,
+    phase: Phase,
+    command: Vec<u8>,
+    data: VecDeque<u8>,
+    result: VecDeque<u8>,
+    present_cylinders: [u8; 4],
+    pending_interrupts: VecDeque<(u8, u8)>, // ST0 and cylinder of finished seeks
+    read_id_index: usize,
+}
 
 impl FloppyDiskController {
     pub fn new_shared() -> FloppyDiskControllerShared {
-        let fdc = FloppyDiskController {};
+        let fdc = FloppyDiskController {
+            disk: None,
+            motor_on: false,
+            phase: Phase::Command,
+            command: Vec::new(),
+            data: VecDeque::new(),
+            result: VecDeque::new(),
+            present_cylinders: [0; 4],
+            pending_interrupts: VecDeque::new(),
+            read_id_index: 0,
+        };
 
         Rc::new(RefCell::new(fdc))
     }
 
-    pub fn read_byte(&self, port: u16) -> u8 {
-        match port {
-            0xfb7e => 0x80, // always report ready -> TODO: read main status register
+    pub fn insert_disk(&mut self, disk: dsk_file::Disk) {
+        self.disk = Some(disk);
+    }
+
+    pub fn eject_disk(&mut self) -> Option<dsk_file::Disk> {
+        self.disk.take()
+    }
+
+    pub fn has_disk(&self) -> bool {
+        self.disk.is_some()
+    }
+
+    pub fn is_disk_dirty(&self) -> bool {
+        matches!(&self.disk, Some(disk) if disk.is_dirty())
+    }
+
+    pub fn save_disk(&mut self) -> std::io::Result<()> {
+        match &mut self.disk {
+            Some(disk) => disk.save(),
+            None => Ok(()),
+        }
+    }
+
+    pub fn set_write_protected(&mut self, protect: bool) {
+        if let Some(disk) = &mut self.disk {
+            disk.set_write_protected(protect);
+        }
+    }
+
+    pub fn is_write_protected(&self) -> bool {
+        matches!(&self.disk, Some(disk) if disk.is_write_protected())
+    }
+
+    pub fn is_motor_on(&self) -> bool {
+        self.motor_on
+    }
+
+    pub fn save_state(&self) -> ControllerState {
+        ControllerState {
+            motor_on: self.motor_on,
+            phase: self.phase,
+            command: self.command.clone(),
+            data: self.data.clone(),
+            result: self.result.clone(),
+            present_cylinders: self.present_cylinders,
+            pending_interrupts: self.pending_interrupts.clone(),
+            read_id_index: self.read_id_index,
+            disk_contents: self.disk.as_ref().map(|disk| Rc::new(disk.save_contents())),
+        }
+    }
+
+    pub fn load_state(&mut self, state: &ControllerState) {
+        // the inserted disk stays, only its contents are restored
+        self.motor_on = state.motor_on;
+        self.phase = state.phase;
+        self.command = state.command.clone();
+        self.data = state.data.clone();
+        self.result = state.result.clone();
+        self.present_cylinders = state.present_cylinders;
+        self.pending_interrupts = state.pending_interrupts.clone();
+        self.read_id_index = state.read_id_index;
+        if let (Some(disk), Some(contents)) = (&mut self.disk, &state.disk_contents) {
+            disk.load_contents(contents);
+        }
+    }
+
+    pub fn read_byte(&mut self, port: u16) -> u8 {
+        // A8 selects the controller rather than the motor latch, A0 its data rather than its status
+        match port & 0x0101 {
+            0x0100 => self.read_main_status(),
+            0x0101 => self.read_data(),
             _ => {
-                println!("FDC read {:#06x}", port);
-                unimplemented!()
+                log::warn!("FDC read {:#06x}", port);
+                0xff
             }
         }
     }
 
     pub fn write_byte(&mut self, port: u16, value: u8) {
-        match port {
-            0xfa7e => {
-                match value {
-                    0 => (), // TODO: switch motor off
-                    _ => {
-                        println!("FDC write {:#06x} {:#010b}", port, value);
-                        unimplemented!()
-                    }
+        match port & 0x0101 {
+            0x0000 | 0x0001 => self.motor_on = value & 0x01 != 0,
+            0x0101 => self.write_data(value),
+            _ => log::warn!("FDC write {:#06x} {:#010b}", port, value),
+        }
+    }
+
+    fn read_main_status(&self) -> u8 {
+        match self.phase {
+            Phase::Command if self.command.is_empty() => STATUS_REQUEST_FOR_MASTER,
+            Phase::Command => STATUS_REQUEST_FOR_MASTER | STATUS_BUSY,
+            Phase::ExecutionRead => STATUS_REQUEST_FOR_MASTER | STATUS_DATA_INPUT | STATUS_EXECUTION_MODE | STATUS_BUSY,
+            Phase::ExecutionWrite => STATUS_REQUEST_FOR_MASTER | STATUS_EXECUTION_MODE | STATUS_BUSY,
+            Phase::Result => STATUS_REQUEST_FOR_MASTER | STATUS_DATA_INPUT | STATUS_BUSY,
+        }
+    }
+
+    fn read_data(&mut self) -> u8 {
+        match self.phase {
+            Phase::ExecutionRead => {
+                let value = self.data.pop_front().unwrap_or(0xff);
+                if self.data.is_empty() {
+                    self.phase = Phase::Result;
+                }
+                value
+            }
+            Phase::Result => {
+                let value = self.result.pop_front().unwrap_or(0xff);
+                if self.result.is_empty() {
+                    self.phase = Phase::Command;
                 }
+                value
             }
+            _ => 0xff,
+        }
+    }
+
+    fn write_data(&mut self, value: u8) {
+        match self.phase {
+            Phase::Command => {
+                self.command.push(value);
+                if self.command.len() == command_length(self.command[0]) {
+                    self.execute_command();
+                }
+            }
+            Phase::ExecutionWrite => {
+                self.data.push_back(value);
+                self.continue_write();
+            }
+            _ => log::warn!("FDC received {:#04x} outside of the command phase", value),
+        }
+    }
+
+    fn execute_command(&mut self) {
+        let command = self.command[0];
+        log::debug!("FDC command {:02x?}", self.command);
+
+        match command & 0x1f {
+            0x03 => self.finish(&[]), // specify: step rate and head timings are irrelevant here
+            0x04 => self.sense_drive_status(),
+            0x05 | 0x09 => self.start_write_data(),
+            0x06 | 0x0c => self.start_read_data(),
+            0x07 => self.seek(0),
+            0x08 => self.sense_interrupt_status(),
+            0x0a => self.read_id(),
+            0x0d => self.start_format_track(),
+            0x0f => self.seek(self.command[2]),
             _ => {
-                println!("FDC write {:#06x} {:#010b}", port, value);
-                unimplemented!()
+                log::warn!("Unsupported FDC command {:#04x}", command);
+                self.finish(&[ST0_INVALID_COMMAND]);
+            }
+        }
+    }
+
+    fn finish(&mut self, result: &[u8]) {
+        self.command.clear();
+        self.data.clear();
+        self.result = result.iter().copied().collect();
+        self.phase = if self.result.is_empty() { Phase::Command } else { Phase::Result };
+    }
+
+    fn drive(&self) -> usize {
+        self.command[1] as usize & 0x03
+    }
+
+    fn head(&self) -> u8 {
+        (self.command[1] >> 2) & 0x01
+    }
+
+    fn base_status(&self) -> u8 {
+        (self.head() << 2) | self.drive() as u8
+    }
+
+    fn is_ready(&self) -> bool {
+        // only drive A is connected
+        self.drive() & 0x01 == 0 && self.disk.is_some()
+    }
+
+    fn sense_drive_status(&mut self) {
+        let mut status = self.base_status();
+        if self.is_ready() {
+            status |= ST3_READY;
+            if self.disk.as_ref().unwrap().num_sides() > 1 {
+                status |= ST3_TWO_SIDE;
+            }
+            if self.is_write_protected() {
+                status |= ST3_WRITE_PROTECTED;
+            }
+        }
+        if self.present_cylinders[self.drive()] == 0 {
+            status |= ST3_TRACK_0;
+        }
+
+        self.finish(&[status]);
+    }
+
+    fn seek(&mut self, cylinder: u8) {
+        let drive = self.drive();
+        let status = if self.is_ready() {
+            self.present_cylinders[drive] = cylinder;
+            ST0_SEEK_END | drive as u8
+        } else {
+            ST0_SEEK_END | ST0_ABNORMAL_TERMINATION | ST0_NOT_READY | drive as u8
+        };
+
+        self.pending_interrupts.push_back((status, self.present_cylinders[drive]));
+        self.finish(&[]);
+    }
+
+    fn sense_interrupt_status(&mut self) {
+        match self.pending_interrupts.pop_front() {
+            Some((status, cylinder)) => self.finish(&[status, cylinder]),
+            None => self.finish(&[ST0_INVALID_COMMAND]),
+        }
+    }
+
+    fn read_id(&mut self) {
+        if !self.is_ready() {
+            let status = self.base_status() | ST0_ABNORMAL_TERMINATION | ST0_NOT_READY;
+            self.finish(&[status, 0, 0, 0, 0, 0, 0]);
+            return;
+        }
+
+        // successive calls return the sectors in the order they pass under the head
+        let cylinder = self.present_cylinders[self.drive()];
+        let infos = self.disk.as_ref().unwrap().read_sector_infos(cylinder, self.head()).to_vec();
+        if infos.is_empty() {
+            let status = self.base_status() | ST0_ABNORMAL_TERMINATION;
+            self.finish(&[status, ST1_MISSING_ADDRESS_MARK, 0, cylinder, self.head(), 0, 0]);
+            return;
+        }
+
+        let info = &infos[self.read_id_index % infos.len()];
+        self.read_id_index = self.read_id_index.wrapping_add(1);
+        self.finish(&[self.base_status(), 0, 0, info.track, info.side, info.sector_id, info.sector_size]);
+    }
+
+    fn start_read_data(&mut self) {
+        if !self.is_ready() {
+            self.finish_transfer(ST0_ABNORMAL_TERMINATION | ST0_NOT_READY, 0, 0);
+            return;
+        }
+
+        let (cylinder, head, first_sector, last_sector) = (self.command[2], self.command[3], self.command[4], self.command[6]);
+        let physical_cylinder = self.present_cylinders[self.drive()];
+        let infos = self.disk.as_ref().unwrap().read_sector_infos(physical_cylinder, self.head()).to_vec();
+
+        let mut data = Vec::new();
+        for sector_id in first_sector..=last_sector {
+            let info = match infos.iter().find(|info| info.sector_id == sector_id && info.track == cylinder && info.side == head) {
+                Some(info) => info,
+                None => {
+                    self.command[4] = sector_id;
+                    self.finish_transfer(ST0_ABNORMAL_TERMINATION, ST1_NO_DATA, 0);
+                    return;
+                }
+            };
+
+            let sector = self.disk.as_ref().unwrap().read_sector(physical_cylinder, self.head(), sector_id).unwrap();
+            data.extend_from_slice(sector);
+
+            // copy protections rely on the error flags stored with each sector, which end the transfer
+            if info.fdc_status1 != 0 || info.fdc_status2 != 0 {
+                self.command[4] = sector_id;
+                self.start_transfer(data, ST0_ABNORMAL_TERMINATION, info.fdc_status1, info.fdc_status2);
+                return;
+            }
+        }
+
+        // without a terminal count signal, the CPC always reads up to the end of the track
+        self.start_transfer(data, ST0_ABNORMAL_TERMINATION, ST1_END_OF_CYLINDER, 0);
+    }
+
+    fn start_transfer(&mut self, data: Vec<u8>, status0: u8, status1: u8, status2: u8) {
+        self.data = data.into_iter().collect();
+        self.result = self.transfer_result(status0, status1, status2);
+        self.command.clear();
+        self.phase = if self.data.is_empty() { Phase::Result } else { Phase::ExecutionRead };
+    }
+
+    fn start_write_data(&mut self) {
+        if !self.is_ready() {
+            self.finish_transfer(ST0_ABNORMAL_TERMINATION | ST0_NOT_READY, 0, 0);
+            return;
+        }
+        if self.is_write_protected() {
+            self.finish_transfer(ST0_ABNORMAL_TERMINATION, ST1_NOT_WRITABLE, 0);
+            return;
+        }
+
+        self.data.clear();
+        self.phase = Phase::ExecutionWrite;
+    }
+
+    fn continue_write(&mut self) {
+        if self.command[0] & 0x1f == 0x0d {
+            self.continue_format_track();
+            return;
+        }
+
+        let sector_id = self.command[4];
+        let physical_cylinder = self.present_cylinders[self.drive()];
+        let head = self.head();
+        let length = self.disk.as_ref().unwrap().read_sector(physical_cylinder, head, sector_id).map(|sector| sector.len());
+
+        match length {
+            Some(length) if self.data.len() >= length => {
+                let data: Vec<u8> = self.data.drain(..).collect();
+                self.disk.as_mut().unwrap().write_sector(physical_cylinder, head, sector_id, &data);
+
+                if sector_id == self.command[6] {
+                    self.finish_transfer(ST0_ABNORMAL_TERMINATION, ST1_END_OF_CYLINDER, 0);
+                } else {
+                    self.command[4] = self.command[4].wrapping_add(1); // copy protected disks use ids up to 0xff
+                }
+            }
+            Some(_) => (),
+            None => self.finish_transfer(ST0_ABNORMAL_TERMINATION, ST1_NO_DATA, 0),
+        }
+    }
+
+    fn start_format_track(&mut self) {
+        if !self.is_ready() {
+            self.finish(&[self.base_status() | ST0_ABNORMAL_TERMINATION | ST0_NOT_READY, 0, 0, 0, 0, 0, 0]);
+            return;
+        }
+        if self.is_write_protected() {
+            self.finish(&[self.base_status() | ST0_ABNORMAL_TERMINATION, ST1_NOT_WRITABLE, 0, 0, 0, 0, 0]);
+            return;
+        }
+
+        self.data.clear();
+        self.phase = Phase::ExecutionWrite;
+    }
+
+    fn continue_format_track(&mut self) {
+        // the CPU sends four ID bytes (C, H, R, N) for each sector
+        let (size_code, num_sectors, filler_byte) = (self.command[2], self.command[3] as usize, self.command[5]);
+        if self.data.len() < num_sectors * 4 {
+            return;
+        }
+
+        let ids: Vec<u8> = self.data.drain(..).collect();
+        let infos = ids
+            .chunks(4)
+            .map(|id| dsk_file::SectorInfo {
+                track: id[0],
+                side: id[1],
+                sector_id: id[2],
+                sector_size: id[3],
+                fdc_status1: 0,
+                fdc_status2: 0,
+            })
+            .collect();
+
+        let physical_cylinder = self.present_cylinders[self.drive()];
+        let head = self.head();
+        self.disk.as_mut().unwrap().format_track(physical_cylinder, head, infos, filler_byte);
+
+        let status = self.base_status();
+        self.finish(&[status, 0, 0, physical_cylinder, head, 0, size_code]);
+    }
+
+    fn finish_transfer(&mut self, status0: u8, status1: u8, status2: u8) {
+        let result: Vec<u8> = self.transfer_result(status0, status1, status2).into_iter().collect();
+        self.finish(&result);
+    }
+
+    fn transfer_result(&self, status0: u8, status1: u8, status2: u8) -> VecDeque<u8> {
+        let (cylinder, head, sector, size) = (self.command[2], self.command[3], self.command[4], self.command[5]);
+        let status0 = status0 | self.base_status();
+
+        if status1 & ST1_END_OF_CYLINDER != 0 {
+            // the ID points to the first sector of the next cylinder
+            vec![status0, status1, status2, cylinder.wrapping_add(1), head, 1, size].into_iter().collect()
+        } else {
+            vec![status0, status1, status2, cylinder, head, sector, size].into_iter().collect()
+        }
+    }
+}
+
+fn command_length(command: u8) -> usize {
+    match command & 0x1f {
+        0x02 | 0x05 | 0x06 | 0x09 | 0x0c | 0x11 | 0x19 | 0x1d => 9,
+        0x0d => 6,
+        0x03 | 0x0f => 3,
+        0x04 | 0x07 | 0x0a => 2,
+        _ => 1, // sense interrupt status and invalid commands
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::media;
+
+    const STATUS_PORT: u16 = 0xfb7e;
+    const DATA_PORT: u16 = 0xfb7f;
+
+    fn new_controller() -> FloppyDiskController {
+        // two tracks of three 512 byte sectors, the second one holding its id and track in every byte
+        let mut disk = dsk_file::Disk::new(media::MediaSource::new("test.dsk"));
+        for track in 0..2 {
+            let sector_infos = [0xc1, 0xc3, 0xc2]
+                .iter()
+                .map(|sector_id| dsk_file::SectorInfo {
+                    track,
+                    side: 0,
+                    sector_id: *sector_id,
+                    sector_size: 2,
+                    fdc_status1: 0,
+                    fdc_status2: 0,
+                })
+                .collect();
+            disk.format_track(track, 0, sector_infos, 0xe5);
+            for sector_id in 0xc1..=0xc3 {
+                disk.write_sector(track, 0, sector_id, &[sector_id ^ track; 512]);
             }
         }
+
+        let fdc = FloppyDiskController::new_shared();
+        fdc.borrow_mut().insert_disk(disk);
+        Rc::try_unwrap(fdc).ok().unwrap().into_inner()
+    }
+
+    fn send_command(fdc: &mut FloppyDiskController, command: &[u8]) {
+        for value in command {
+            assert_eq!(fdc.read_byte(STATUS_PORT) & (STATUS_REQUEST_FOR_MASTER | STATUS_DATA_INPUT), STATUS_REQUEST_FOR_MASTER);
+            fdc.write_byte(DATA_PORT, *value);
+        }
+    }
+
+    fn read_phase(fdc: &mut FloppyDiskController, mode: u8) -> Vec<u8> {
+        // the bytes the CPU reads while the main status stays in the given mode
+        let mut values = Vec::new();
+        while fdc.read_byte(STATUS_PORT) & (STATUS_DATA_INPUT | STATUS_EXECUTION_MODE) == mode {
+            values.push(fdc.read_byte(DATA_PORT));
+        }
+
+        values
+    }
+
+    fn read_result(fdc: &mut FloppyDiskController) -> Vec<u8> {
+        let result = read_phase(fdc, STATUS_DATA_INPUT);
+        assert_eq!(fdc.read_byte(STATUS_PORT), STATUS_REQUEST_FOR_MASTER, "back in the command phase");
+        result
+    }
+
+    #[test]
+    fn seeks_report_through_sense_interrupt_status() {
+        let mut fdc = new_controller();
+        send_command(&mut fdc, &[0x0f, 0x00, 0x01]);
+        assert!(read_result(&mut fdc).is_empty(), "seeks have no result phase");
+        send_command(&mut fdc, &[0x08]);
+        assert_eq!(read_result(&mut fdc), [ST0_SEEK_END, 0x01]);
+
+        send_command(&mut fdc, &[0x07, 0x00]);
+        send_command(&mut fdc, &[0x08]);
+        assert_eq!(read_result(&mut fdc), [ST0_SEEK_END, 0x00], "recalibrated to track 0");
+
+        send_command(&mut fdc, &[0x08]);
+        assert_eq!(read_result(&mut fdc), [ST0_INVALID_COMMAND], "no interrupt is pending");
+    }
+
+    #[test]
+    fn seeks_without_a_disk_are_not_ready() {
+        let mut fdc = new_controller();
+        fdc.eject_disk();
+        send_command(&mut fdc, &[0x0f, 0x00, 0x01]);
+        send_command(&mut fdc, &[0x08]);
+
+        assert_eq!(read_result(&mut fdc), [ST0_SEEK_END | ST0_ABNORMAL_TERMINATION | ST0_NOT_READY, 0x00]);
+    }
+
+    #[test]
+    fn reads_ids_in_the_order_of_the_track() {
+        let mut fdc = new_controller();
+        let mut sector_ids = Vec::new();
+        for _ in 0..4 {
+            send_command(&mut fdc, &[0x4a, 0x00]);
+            let result = read_result(&mut fdc);
+            assert_eq!(result, [0x00, 0x00, 0x00, 0x00, 0x00, result[5], 0x02]);
+            sector_ids.push(result[5]);
+        }
+
+        assert_eq!(sector_ids, [0xc1, 0xc3, 0xc2, 0xc1]);
+    }
+
+    #[test]
+    fn reads_ids_of_the_track_under_the_head() {
+        let mut fdc = new_controller();
+        send_command(&mut fdc, &[0x0f, 0x00, 0x01]);
+        send_command(&mut fdc, &[0x08]);
+        read_result(&mut fdc);
+        send_command(&mut fdc, &[0x4a, 0x00]);
+        assert_eq!(read_result(&mut fdc)[3], 0x01);
+
+        send_command(&mut fdc, &[0x0f, 0x00, 0x05]);
+        send_command(&mut fdc, &[0x08]);
+        read_result(&mut fdc);
+        send_command(&mut fdc, &[0x4a, 0x00]);
+        assert_eq!(read_result(&mut fdc), [ST0_ABNORMAL_TERMINATION, ST1_MISSING_ADDRESS_MARK, 0, 0x05, 0, 0, 0], "unformatted");
+    }
+
+    #[test]
+    fn reads_data_up_to_the_end_of_the_track() {
+        let mut fdc = new_controller();
+        send_command(&mut fdc, &[0x46, 0x00, 0x00, 0x00, 0xc2, 0x02, 0xc3, 0x2a, 0xff]);
+        let data = read_phase(&mut fdc, STATUS_DATA_INPUT | STATUS_EXECUTION_MODE);
+
+        assert_eq!(data.len(), 1024);
+        assert!(data[..512].iter().all(|value| *value == 0xc2));
+        assert!(data[512..].iter().all(|value| *value == 0xc3));
+        // without a terminal count the transfer runs past the last sector
+        assert_eq!(read_result(&mut fdc), [ST0_ABNORMAL_TERMINATION, ST1_END_OF_CYLINDER, 0x00, 0x01, 0x00, 0x01, 0x02]);
+    }
+
+    #[test]
+    fn reads_data_of_the_track_under_the_head() {
+        let mut fdc = new_controller();
+        send_command(&mut fdc, &[0x0f, 0x00, 0x01]);
+        send_command(&mut fdc, &[0x08]);
+        read_result(&mut fdc);
+        send_command(&mut fdc, &[0x46, 0x00, 0x01, 0x00, 0xc1, 0x02, 0xc1, 0x2a, 0xff]);
+        let data = read_phase(&mut fdc, STATUS_DATA_INPUT | STATUS_EXECUTION_MODE);
+
+        assert_eq!(data, [0xc0; 512]);
+        assert_eq!(read_result(&mut fdc), [ST0_ABNORMAL_TERMINATION, ST1_END_OF_CYLINDER, 0x00, 0x02, 0x00, 0x01, 0x02]);
+    }
+
+    #[test]
+    fn reports_sectors_missing_from_the_track() {
+        let mut fdc = new_controller();
+        send_command(&mut fdc, &[0x46, 0x00, 0x00, 0x00, 0xc4, 0x02, 0xc4, 0x2a, 0xff]);
+
+        assert_eq!(read_result(&mut fdc), [ST0_ABNORMAL_TERMINATION, ST1_NO_DATA, 0x00, 0x00, 0x00, 0xc4, 0x02], "no execution phase");
+    }
+
+    #[test]
+    fn reads_without_a_disk_are_not_ready() {
+        let mut fdc = new_controller();
+        fdc.eject_disk();
+        send_command(&mut fdc, &[0x46, 0x00, 0x00, 0x00, 0xc1, 0x02, 0xc1, 0x2a, 0xff]);
+
+        assert_eq!(read_result(&mut fdc), [ST0_ABNORMAL_TERMINATION | ST0_NOT_READY, 0x00, 0x00, 0x00, 0x00, 0xc1, 0x02]);
     }
 }
@@ -0,0 +1,54 @@
+use std::time::SystemTime;
+
+// notices changes to files by polling their modification times, which is cheap enough to do every
+// few frames and works the same on every host without a notification API
+pub struct FileWatcher {
+    files: Vec<(String, Option<SystemTime>)>, // None if the file could not be read
+}
+
+impl Default for FileWatcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FileWatcher {
+    pub fn new() -> FileWatcher {
+        FileWatcher { files: Vec::new() }
+    }
+
+    pub fn watch(&mut self, filename: &str) {
+        if self.files.iter().any(|(watched, _)| watched == filename) {
+            return;
+        }
+
+        self.files.push((filename.to_string(), modified(filename)));
+    }
+
+    pub fn unwatch(&mut self, filename: &str) {
+        self.files.retain(|(watched, _)| watched != filename);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    pub fn poll(&mut self) -> Vec<String> {
+        // returns the files that were modified since the last poll, files that are missing for a
+        // while, e.g. because an editor replaces them, count as modified once they are back
+        let mut changed = Vec::new();
+        for (filename, last_modified) in &mut self.files {
+            let modified = modified(filename);
+            if modified.is_some() && modified != *last_modified {
+                changed.push(filename.clone());
+            }
+            *last_modified = modified;
+        }
+
+        changed
+    }
+}
+
+fn modified(filename: &str) -> Option<SystemTime> {
+    std::fs::metadata(filename).and_then(|metadata| metadata.modified()).ok()
+}
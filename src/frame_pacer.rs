@@ -0,0 +1,105 @@
+const FRAME_MICROSECONDS: u64 = 20_000; // the CPC's 50Hz
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FramePacing {
+    Repeat, // shows the latest frame, some of them twice
+    Blend,  // mixes the two latest frames by how far the host refresh is between them
+}
+
+impl FramePacing {
+    pub fn from_name(name: &str) -> Option<FramePacing> {
+        match name {
+            "repeat" => Some(FramePacing::Repeat),
+            "blend" => Some(FramePacing::Blend),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            FramePacing::Repeat => "repeat",
+            FramePacing::Blend => "blend",
+        }
+    }
+}
+
+// presents the 50Hz frames at the refresh rate of the host display, e.g. 60Hz, where showing every
+// emulated frame once would judder. Displays with a variable refresh rate should not use it, they
+// can follow the emulation's 50Hz by themselves
+pub struct FramePacer {
+    pacing: FramePacing,
+    refresh_rate: u32,
+    pending: u64, // host time not yet emulated, in microseconds times the refresh rate
+    previous: Vec<u32>,
+    latest: Vec<u32>,
+    blended: Vec<u32>,
+}
+
+impl FramePacer {
+    pub fn new(pacing: FramePacing, refresh_rate: u32) -> FramePacer {
+        FramePacer {
+            pacing,
+            refresh_rate: refresh_rate.max(1),
+            pending: 0,
+            previous: Vec::new(),
+            latest: Vec::new(),
+            blended: Vec::new(),
+        }
+    }
+
+    pub fn get_pacing(&self) -> FramePacing {
+        self.pacing
+    }
+
+    pub fn get_present_interval(&self) -> std::time::Duration {
+        std::time::Duration::from_micros(1_000_000 / self.refresh_rate as u64)
+    }
+
+    pub fn frames_due(&mut self) -> u32 {
+        // called once per host refresh, returns how many frames to emulate before presenting
+        let frame = FRAME_MICROSECONDS * self.refresh_rate as u64;
+        self.pending += 1_000_000;
+        let due = self.pending / frame;
+        self.pending %= frame;
+
+        due as u32
+    }
+
+    pub fn add_frame(&mut self, frame: &[u32]) {
+        // called after each emulated frame
+        std::mem::swap(&mut self.previous, &mut self.latest);
+        self.latest.clear();
+        self.latest.extend_from_slice(frame);
+    }
+
+    pub fn present(&mut self) -> Option<&[u32]> {
+        // None until the first frame was emulated
+        if self.latest.is_empty() {
+            return None;
+        }
+        if self.pacing == FramePacing::Repeat || self.previous.len() != self.latest.len() {
+            return Some(&self.latest);
+        }
+
+        // the display runs one frame behind, so it can show the moment between the two latest frames
+        let weight = (self.pending * 256 / (FRAME_MICROSECONDS * self.refresh_rate as u64)) as u32;
+        self.blended.resize(self.latest.len(), 0);
+        for ((blended, previous), latest) in self.blended.iter_mut().zip(&self.previous).zip(&self.latest) {
+            *blended = blend(*previous, *latest, weight);
+        }
+
+        Some(&self.blended)
+    }
+}
+
+fn blend(from: u32, to: u32, weight: u32) -> u32 {
+    // weight is between 0 (from) and 256 (to), for each 8 bit channel
+    let mut color = 0;
+    for shift in [0, 8, 16].iter() {
+        let from = (from >> shift) & 0xff;
+        let to = (to >> shift) & 0xff;
+        color |= ((from * (256 - weight) + to * weight) >> 8) << shift;
+    }
+
+    color
+}
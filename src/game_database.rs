@@ -0,0 +1,92 @@
+use std::collections::HashMap;
+
+#[derive(Clone, Debug, Default)]
+pub struct GameInfo {
+    pub title: String,
+    pub publisher: Option<String>,
+    pub year: Option<u16>,
+    pub crtc_type: Option<u8>,
+    pub keymap: Option<String>, // key map file with the controls for this title
+}
+
+// frontends can plug in other sources, e.g. an online database
+pub trait GameDatabase {
+    fn lookup(&self, hash: u32) -> Option<GameInfo>;
+}
+
+// entries are keyed by media hashes as computed by media::hash_media:
+//
+// [0x1234abcd]
+// title = Some Game
+// publisher = Someone
+// year = 1987
+// crtc = 1
+// keymap = some_game.keymap
+#[derive(Default)]
+pub struct FileGameDatabase {
+    entries: HashMap<u32, GameInfo>,
+}
+
+impl FileGameDatabase {
+    pub fn load(filename: &str) -> std::io::Result<FileGameDatabase> {
+        let contents = std::fs::read_to_string(filename)?;
+        let mut entries = HashMap::new();
+        let mut current: Option<(u32, GameInfo)> = None;
+
+        for line in contents.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            if let Some(section) = line.strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+                if let Some((hash, info)) = current.take() {
+                    entries.insert(hash, info);
+                }
+                current = Some((parse_hash(section)?, GameInfo::default()));
+                continue;
+            }
+
+            let info = match &mut current {
+                Some((_, info)) => info,
+                None => return Err(invalid_data(format!("Entry \"{}\" outside of a [hash] section.", line))),
+            };
+
+            let (key, value) = match line.split_once('=') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => return Err(invalid_data(format!("Missing \"=\" in line \"{}\".", line))),
+            };
+
+            match key {
+                "title" => info.title = value.to_string(),
+                "publisher" => info.publisher = Some(value.to_string()),
+                "year" => info.year = Some(value.parse().map_err(|_| invalid_data(format!("Invalid year \"{}\".", value)))?),
+                "crtc" => info.crtc_type = Some(value.parse().map_err(|_| invalid_data(format!("Invalid CRTC type \"{}\".", value)))?),
+                "keymap" => info.keymap = Some(value.to_string()),
+                _ => return Err(invalid_data(format!("Unknown entry \"{}\".", key))),
+            }
+        }
+
+        if let Some((hash, info)) = current {
+            entries.insert(hash, info);
+        }
+
+        Ok(FileGameDatabase { entries })
+    }
+}
+
+impl GameDatabase for FileGameDatabase {
+    fn lookup(&self, hash: u32) -> Option<GameInfo> {
+        self.entries.get(&hash).cloned()
+    }
+}
+
+fn parse_hash(value: &str) -> std::io::Result<u32> {
+    let hex = value.strip_prefix("0x").unwrap_or(value);
+
+    u32::from_str_radix(hex, 16).map_err(|_| invalid_data(format!("Invalid media hash \"{}\".", value)))
+}
+
+fn invalid_data(message: String) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message)
+}
@@ -3,10 +3,32 @@ use crate::memory;
 use crate::screen;
 
 use std::cell::RefCell;
+use std::collections::HashMap;
 use std::rc::Rc;
 
 pub type GateArrayShared = Rc<RefCell<GateArray>>;
 
+const FONT_ADDRESS: usize = 0x3800; // character matrix table in the lower ROM
+const CHARACTERS_PER_LINE: usize = screen::BUFFER_WIDTH / 16;
+const MAX_CACHED_LINES: usize = 4096;
+const VSYNC_BLANKING_LINES: u8 = 26; // the gate array blanks at most this many lines of a VSYNC
+
+// kinds of character slots collected for a line
+const SLOT_SYNC: u8 = 0xff;
+const SLOT_BORDER: u8 = 0xfe;
+
+// the counters and latches that snapshots leave out
+#[derive(Clone)]
+pub struct CounterState {
+    current_screen_mode: u8,
+    requested_screen_mode: u8,
+    hsync_active: bool,
+    vsync_active: bool,
+    hsyncs_since_last_vsync: u8,
+    vsync_blanking_lines: u8,
+    interrupt_counter: u8,
+}
+
 pub struct GateArray {
     memory: memory::MemoryShared,
     crtc: crtc::CRTControllerShared,
@@ -16,9 +38,12 @@ pub struct GateArray {
     hsync_active: bool,
     vsync_active: bool,
     hsyncs_since_last_vsync: u8,
+    vsync_blanking_lines: u8, // remaining lines of the current VSYNC that are blanked
     interrupt_counter: u8,
     selected_pen: usize,
     pen_colors: Vec<u8>,
+    line_slots: Vec<u8>, // screen mode (or slot kind) and two bytes of video memory per character
+    line_cache: HashMap<usize, Vec<u8>>, // palette and slots of the line last drawn at each gun position
 }
 
 impl GateArray {
@@ -35,10 +60,13 @@ impl GateArray {
             requested_screen_mode: 0,
             hsync_active: false,
             vsync_active: false,
-            hsyncs_since_last_vsync: 0,
+            hsyncs_since_last_vsync: 2, // no VSYNC pending
+            vsync_blanking_lines: 0,
             interrupt_counter: 0,
             selected_pen: 0,
             pen_colors: vec![0; 17],
+            line_slots: Vec::with_capacity(CHARACTERS_PER_LINE * 3),
+            line_cache: HashMap::new(),
         };
 
         Rc::new(RefCell::new(gate_array))
@@ -58,22 +86,25 @@ impl GateArray {
             }
             1 => {
                 // println!("color select (pen {}): {:#04x} ({:#04x})", self.selected_pen, value, value & 0x1f);
+                self.flush_line(); // the palette of a line is cached as a whole
                 self.pen_colors[self.selected_pen] = value & 0x1f;
             }
             2 => {
                 self.requested_screen_mode = value & 0x03;
 
-                self.memory.borrow_mut().enable_lower_rom(value & 0x04 == 0);
-                self.memory.borrow_mut().enable_upper_rom(value & 0x08 == 0);
+                // both ROMs are switched by the same write, the mode only changes at the next HSYNC
+                let mut memory = self.memory.borrow_mut();
+                memory.enable_lower_rom(value & 0x04 == 0);
+                memory.enable_upper_rom(value & 0x08 == 0);
+                drop(memory);
 
                 if value & 0x10 != 0 {
                     self.interrupt_counter = 0;
                 }
             }
             3 => {
-                // ROM banking (only available in CPC 6128)
-                println!("GA {:#06x} {:#010b}", port, value);
-                unimplemented!();
+                // decoded by the PAL of the CPC 6128 or a memory expansion, if there is one
+                self.memory.borrow_mut().select_ram_configuration(value);
             }
             _ => {
                 unreachable!();
@@ -81,13 +112,51 @@ impl GateArray {
         }
     }
 
+    pub fn clear_line_cache(&mut self) {
+        // draws every line again, e.g. when the screen starts to capture layers
+        self.line_cache.clear();
+    }
+
+    pub fn read_pens(&self) -> (u8, Vec<u8>) {
+        // the selected pen and the hardware colors of all pens, with the border last
+        (self.selected_pen as u8, self.pen_colors.clone())
+    }
+
+    pub fn read_screen_mode(&self) -> u8 {
+        self.requested_screen_mode
+    }
+
     pub fn acknowledge_interrupt(&mut self) {
         self.interrupt_counter &= 0x1f;
     }
 
+    pub fn save_counters(&self) -> CounterState {
+        CounterState {
+            current_screen_mode: self.current_screen_mode,
+            requested_screen_mode: self.requested_screen_mode,
+            hsync_active: self.hsync_active,
+            vsync_active: self.vsync_active,
+            hsyncs_since_last_vsync: self.hsyncs_since_last_vsync,
+            vsync_blanking_lines: self.vsync_blanking_lines,
+            interrupt_counter: self.interrupt_counter,
+        }
+    }
+
+    pub fn load_counters(&mut self, state: &CounterState) {
+        self.current_screen_mode = state.current_screen_mode;
+        self.requested_screen_mode = state.requested_screen_mode;
+        self.hsync_active = state.hsync_active;
+        self.vsync_active = state.vsync_active;
+        self.hsyncs_since_last_vsync = state.hsyncs_since_last_vsync;
+        self.vsync_blanking_lines = state.vsync_blanking_lines;
+        self.interrupt_counter = state.interrupt_counter;
+        self.line_cache.clear();
+    }
+
     pub fn step(&mut self) -> bool {
         let generate_interrupt = self.update_interrupt_counter();
         self.update_screen_mode();
+        self.update_blanking();
         self.write_to_screen();
 
         self.hsync_active = self.crtc.borrow().read_horizontal_sync();
@@ -97,32 +166,36 @@ impl GateArray {
     }
 
     fn update_interrupt_counter(&mut self) -> bool {
-        let mut generate_interrupt = false;
-        if self.hsync_active && !self.crtc.borrow().read_horizontal_sync() {
-            self.interrupt_counter += 1;
-            self.hsyncs_since_last_vsync += 1;
+        let crtc = self.crtc.borrow();
+        if !self.vsync_active && crtc.read_vertical_sync() {
+            self.hsyncs_since_last_vsync = 0;
+        }
+        let hsync_ended = self.hsync_active && !crtc.read_horizontal_sync();
+        drop(crtc);
 
-            // TODO: handle this case properly
-            if self.hsyncs_since_last_vsync > 3 {
-                self.hsyncs_since_last_vsync = 3;
-            }
+        if !hsync_ended {
+            return false;
+        }
+
+        self.interrupt_counter += 1;
 
-            if self.interrupt_counter == 52 {
+        if self.hsyncs_since_last_vsync < 2 {
+            self.hsyncs_since_last_vsync += 1;
+
+            if self.hsyncs_since_last_vsync == 2 {
+                // interrupts are synchronized to the VSYNC, the pending one is only kept if it is far enough away
+                let generate_interrupt = self.interrupt_counter >= 32;
                 self.interrupt_counter = 0;
-                generate_interrupt = true;
+                return generate_interrupt;
             }
         }
 
-        if !self.vsync_active && self.crtc.borrow().read_vertical_sync() {
-            self.hsyncs_since_last_vsync = 0;
-        }
-
-        if self.hsyncs_since_last_vsync == 2 {
+        if self.interrupt_counter == 52 {
             self.interrupt_counter = 0;
-            generate_interrupt = self.interrupt_counter & 0x20 != 0;
+            return true;
         }
 
-        generate_interrupt
+        false
     }
 
     fn update_screen_mode(&mut self) {
@@ -132,64 +205,205 @@ impl GateArray {
         }
     }
 
-    fn write_to_screen(&self) {
+    fn update_blanking(&mut self) {
+        // overscan screens often shorten the CRTC VSYNC, which then ends the blanking early
+        let crtc = self.crtc.borrow();
+        if !crtc.read_vertical_sync() {
+            self.vsync_blanking_lines = 0;
+        } else if !self.vsync_active {
+            self.vsync_blanking_lines = VSYNC_BLANKING_LINES;
+        } else if self.hsync_active && !crtc.read_horizontal_sync() {
+            self.vsync_blanking_lines = self.vsync_blanking_lines.saturating_sub(1);
+        }
+    }
+
+    fn write_to_screen(&mut self) {
         if !self.vsync_active && self.crtc.borrow().read_vertical_sync() {
+            self.flush_line();
             self.screen.borrow_mut().trigger_vsync();
         }
 
-        if self.crtc.borrow().read_horizontal_sync() || self.crtc.borrow().read_vertical_sync() {
-            // TODO: use modified hsync/vsync durations (see http://www.cpcwiki.eu/index.php?title=CRTC#HSYNC_and_VSYNC)
-            for _ in 0..16 {
-                self.screen.borrow_mut().write(20); // black
-            }
+        let crtc = self.crtc.borrow();
+        let slot = if crtc.read_horizontal_sync() || self.vsync_blanking_lines > 0 {
+            // TODO: use the shortened hsync sent to the monitor (see http://www.cpcwiki.eu/index.php?title=CRTC#HSYNC_and_VSYNC)
+            [SLOT_SYNC, 0, 0]
+        } else if !crtc.read_display_enabled() {
+            [SLOT_BORDER, 0, 0]
+        } else {
+            let address = crtc.read_address();
+            let memory = self.memory.borrow();
+            [
+                self.current_screen_mode,
+                memory.read_byte_from_ram(address),
+                memory.read_byte_from_ram(address + 1),
+            ]
+        };
+        drop(crtc);
+
+        self.line_slots.extend_from_slice(&slot);
+        if self.line_slots.len() == CHARACTERS_PER_LINE * 3 {
+            self.flush_line();
+        }
+    }
+
+    fn flush_line(&mut self) {
+        // lines are only converted to pixels if they differ from what was drawn at the same position before
+        if self.line_slots.is_empty() {
             return;
         }
 
-        if !self.crtc.borrow().read_display_enabled() {
-            for _ in 0..16 {
-                self.screen.borrow_mut().write(12); // bright red
+        let mut screen = self.screen.borrow_mut();
+        if let Some(gun_position) = screen.read_gun_position() {
+            let mut key = Vec::with_capacity(self.pen_colors.len() + self.line_slots.len());
+            key.extend_from_slice(&self.pen_colors);
+            key.extend_from_slice(&self.line_slots);
+
+            if self.line_cache.get(&gun_position) == Some(&key) {
+                screen.skip(self.line_slots.len() / 3 * 16);
+            } else {
+                for slot in self.line_slots.chunks(3) {
+                    self.draw_slot(&mut screen, slot);
+                }
+
+                if self.line_cache.len() == MAX_CACHED_LINES {
+                    self.line_cache.clear();
+                }
+                self.line_cache.insert(gun_position, key);
             }
-            return;
         }
 
-        for offset in 0..2 {
-            let address = self.crtc.borrow().read_address() + offset;
-            let packed = self.memory.borrow().read_byte_from_ram(address);
-            match self.current_screen_mode {
-                0 => {
-                    let pixels = [
-                        ((packed & 0x80) >> 7) | ((packed & 0x08) >> 2) | ((packed & 0x20) >> 3) | ((packed & 0x02) << 2),
-                        ((packed & 0x40) >> 6) | ((packed & 0x04) >> 1) | ((packed & 0x10) >> 2) | ((packed & 0x01) << 3),
-                    ];
+        self.line_slots.clear();
+    }
+
+    fn draw_slot(&self, screen: &mut screen::Screen, slot: &[u8]) {
+        match slot[0] {
+            SLOT_SYNC => {
+                for _ in 0..16 {
+                    screen.write(20, screen::SOURCE_BLANK); // black
+                }
+            }
+            SLOT_BORDER => {
+                for _ in 0..16 {
+                    screen.write(self.pen_colors[0x10] as usize, screen::SOURCE_BORDER);
+                }
+            }
+            screen_mode => {
+                let pixels_per_byte = match screen_mode {
+                    0 => 2,
+                    1 => 4,
+                    2 => 8,
+                    _ => unimplemented!(),
+                };
 
-                    for pixel in pixels {
-                        for _ in 0..4 {
-                            self.screen.borrow_mut().write(self.pen_colors[pixel as usize] as usize);
+                for packed in &slot[1..] {
+                    let pixels = unpack_pixels(screen_mode, *packed);
+                    for pixel in &pixels[..pixels_per_byte] {
+                        for _ in 0..(8 / pixels_per_byte) {
+                            screen.write(self.pen_colors[*pixel as usize] as usize, *pixel);
                         }
                     }
                 }
-                1 => {
-                    let pixels = [
-                        ((packed & 0x80) >> 7) | ((packed & 0x08) >> 2),
-                        ((packed & 0x40) >> 6) | ((packed & 0x04) >> 1),
-                        ((packed & 0x20) >> 5) | ((packed & 0x02) >> 0),
-                        ((packed & 0x10) >> 4) | ((packed & 0x01) << 1),
-                    ];
-
-                    for pixel in pixels {
-                        for _ in 0..2 {
-                            self.screen.borrow_mut().write(self.pen_colors[pixel as usize] as usize);
-                        }
+            }
+        }
+    }
+
+    pub fn read_screen_text(&self) -> String {
+        let crtc = self.crtc.borrow();
+        let memory = self.memory.borrow();
+
+        let bytes_per_character = match self.current_screen_mode {
+            0 => 4,
+            1 => 2,
+            _ => 1,
+        };
+        let columns = crtc.read_horizontal_displayed() as usize * 2 / bytes_per_character;
+
+        let mut text = String::new();
+        for character_row in 0..crtc.read_vertical_displayed() {
+            let mut line = String::new();
+
+            for column in 0..columns {
+                let mut pens = [[0; 8]; 8];
+                for (scan_line, row) in pens.iter_mut().enumerate() {
+                    for byte in 0..bytes_per_character {
+                        let offset = column * bytes_per_character + byte;
+                        let address = crtc.read_character_address(character_row, (offset / 2) as u8, scan_line as u8) + offset % 2;
+                        let pixels = unpack_pixels(self.current_screen_mode, memory.read_byte_from_ram(address));
+                        let pixels_per_byte = 8 / bytes_per_character;
+                        row[(byte * pixels_per_byte)..((byte + 1) * pixels_per_byte)].copy_from_slice(&pixels[..pixels_per_byte]);
                     }
                 }
-                2 => {
-                    for bit in 0..8 {
-                        let pixel = (packed >> (7 - bit)) & 1;
-                        self.screen.borrow_mut().write(self.pen_colors[pixel as usize] as usize);
-                    }                    
+
+                // the most common pen is assumed to be the paper
+                let mut counts = [0; 16];
+                for pen in pens.iter().flatten() {
+                    counts[*pen as usize] += 1;
+                }
+                let paper = (0..16).max_by_key(|pen| counts[*pen]).unwrap() as u8;
+
+                let mut glyph = [0u8; 8];
+                for (scan_line, row) in pens.iter().enumerate() {
+                    for (x, pen) in row.iter().enumerate() {
+                        if *pen != paper {
+                            glyph[scan_line] |= 0x80 >> x;
+                        }
+                    }
                 }
-                _ => unimplemented!(),
+
+                line.push(match_glyph(&memory, &glyph));
             }
+
+            text.push_str(line.trim_end());
+            text.push('\n');
         }
+
+        text
     }
 }
+
+fn unpack_pixels(screen_mode: u8, packed: u8) -> [u8; 8] {
+    // pixels are widened to eight entries, so that each character cell is 8x8 regardless of the mode
+    match screen_mode {
+        0 => [
+            ((packed & 0x80) >> 7) | ((packed & 0x08) >> 2) | ((packed & 0x20) >> 3) | ((packed & 0x02) << 2),
+            ((packed & 0x40) >> 6) | ((packed & 0x04) >> 1) | ((packed & 0x10) >> 2) | ((packed & 0x01) << 3),
+            0, 0, 0, 0, 0, 0,
+        ],
+        1 => [
+            ((packed & 0x80) >> 7) | ((packed & 0x08) >> 2),
+            ((packed & 0x40) >> 6) | ((packed & 0x04) >> 1),
+            ((packed & 0x20) >> 5) | (packed & 0x02),
+            ((packed & 0x10) >> 4) | ((packed & 0x01) << 1),
+            0, 0, 0, 0,
+        ],
+        _ => {
+            let mut pixels = [0; 8];
+            for (bit, pixel) in pixels.iter_mut().enumerate() {
+                *pixel = (packed >> (7 - bit)) & 1;
+            }
+            pixels
+        }
+    }
+}
+
+fn match_glyph(memory: &memory::Memory, glyph: &[u8; 8]) -> char {
+    if glyph.iter().all(|row| *row == 0) {
+        return ' ';
+    }
+
+    for character in 0x20..0x7f {
+        let mut matches = true;
+        let mut matches_inverted = true;
+        for (scan_line, row) in glyph.iter().enumerate() {
+            let font_row = memory.read_byte_from_lower_rom(FONT_ADDRESS + character * 8 + scan_line);
+            matches &= font_row == *row;
+            matches_inverted &= !font_row == *row;
+        }
+
+        if matches || matches_inverted {
+            return character as u8 as char;
+        }
+    }
+
+    '?'
+}
@@ -1,148 +1,830 @@
-use crate::{screen, system};
+use std::io::Write;
+
+use crate::actions::{self, Action};
+use crate::status_bar::{self, StatusBar};
+use crate::media_library::MediaLibrary;
+use crate::{audio_output, bug_report, cdt_file, crtc, debug_console, debugger, dsk_file, file_watcher, frame_pacer, key_configurator, key_mapper, logger, media, memory, midi_clock, mixer, palette, patches, replay, screen, sna_file, storage, system, window_title, workbench};
+
+const TURBO_FRAMES: u32 = 5; // emulated per presented frame
+const WATCH_INTERVAL_FRAMES: u32 = 25; // between checks for modified ROM and key map files
+const DISK_MENU_ID_BASE: usize = 1000; // the ids below are taken by the action registry
+const FRAME_MICROSECONDS: u64 = 20_000;
+const INPUT_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(2);
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum WindowScale {
+    #[default]
+    X1,
+    X2,
+    X4,
+    Fit, // the largest that fits the desktop
+}
+
+impl WindowScale {
+    pub fn from_name(name: &str) -> Option<WindowScale> {
+        match name {
+            "1" => Some(WindowScale::X1),
+            "2" => Some(WindowScale::X2),
+            "4" => Some(WindowScale::X4),
+            "fit" => Some(WindowScale::Fit),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            WindowScale::X1 => "1",
+            WindowScale::X2 => "2",
+            WindowScale::X4 => "4",
+            WindowScale::Fit => "fit",
+        }
+    }
+
+    pub fn window_options(&self) -> minifb::WindowOptions {
+        // the frame buffer keeps its size, the window system stretches it when presenting, so a
+        // larger window costs the emulation nothing
+        let scale = match self {
+            WindowScale::X1 => minifb::Scale::X1,
+            WindowScale::X2 => minifb::Scale::X2,
+            WindowScale::X4 => minifb::Scale::X4,
+            WindowScale::Fit => minifb::Scale::FitScreen,
+        };
+
+        minifb::WindowOptions {
+            scale,
+            ..minifb::WindowOptions::default()
+        }
+    }
+}
+
+// input polled while waiting to present the next frame, with the host time it arrived at. Key
+// events keep their offset into the frame, so they land on about the right scan line instead of
+// all at the start of the next frame
+pub struct HostInput {
+    interval_start: std::time::Instant, // when the key events were last taken
+    key_events: Vec<(std::time::Instant, minifb::Key, bool)>,
+    shortcuts: Vec<(minifb::Key, bool)>, // pressed keys with whether Ctrl was down
+    menu_presses: Vec<usize>,
+}
+
+impl HostInput {
+    pub fn new() -> HostInput {
+        HostInput {
+            interval_start: std::time::Instant::now(),
+            key_events: Vec::new(),
+            shortcuts: Vec::new(),
+            menu_presses: Vec::new(),
+        }
+    }
+
+    pub fn poll(&mut self, window: &mut minifb::Window) {
+        // keys only count as pressed until the next window update, so every update has to be polled
+        let now = std::time::Instant::now();
+        let ctrl_down = window.is_key_down(minifb::Key::LeftCtrl);
+        for key in window.get_keys_pressed(minifb::KeyRepeat::No).unwrap_or_default() {
+            self.key_events.push((now, key, true));
+            self.shortcuts.push((key, ctrl_down));
+        }
+        for key in window.get_keys_released().unwrap_or_default() {
+            self.key_events.push((now, key, false));
+        }
+        if let Some(id) = window.is_menu_pressed() {
+            self.menu_presses.push(id);
+        }
+    }
+
+    pub fn wait_until(&mut self, window: &mut minifb::Window, until: std::time::Instant) {
+        // the window does not limit its update rate, this waits in its place and keeps polling
+        loop {
+            let now = std::time::Instant::now();
+            if now >= until {
+                break;
+            }
+            std::thread::sleep((until - now).min(INPUT_POLL_INTERVAL));
+            window.update();
+            self.poll(window);
+        }
+    }
+
+    pub fn take_key_events(&mut self, frames: u32) -> Vec<(u64, minifb::Key, bool)> {
+        // with the delay into the frames about to be emulated, host time maps one to one onto them
+        let latest = frames.max(1) as u64 * FRAME_MICROSECONDS - 1;
+        let interval_start = std::mem::replace(&mut self.interval_start, std::time::Instant::now());
+        self.key_events
+            .drain(..)
+            .map(|(instant, key, pressed)| {
+                let delay = instant.saturating_duration_since(interval_start).as_micros() as u64;
+                (delay.min(latest), key, pressed)
+            })
+            .collect()
+    }
+
+    pub fn take_shortcuts(&mut self) -> Vec<(minifb::Key, bool)> {
+        std::mem::take(&mut self.shortcuts)
+    }
+
+    pub fn take_menu_presses(&mut self) -> Vec<usize> {
+        std::mem::take(&mut self.menu_presses)
+    }
+}
+
+impl Default for HostInput {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 pub struct GUI {
     system: Box<dyn system::System>,
     window: minifb::Window,
+    host_input: HostInput,
+    key_mapper: key_mapper::KeyMapper,
+    input_enabled: bool,
+    workbench: workbench::Workbench,
+    debug_console: debug_console::DebugConsoleShared,
+    log_history: logger::LogHistoryShared,
+    layout_file: Option<String>,
+    palette: Option<palette::CommandPalette>,
+    key_configurator: Option<key_configurator::KeyConfigurator>,
+    status_bar: StatusBar,
+    window_title: window_title::WindowTitle,
+    storage: storage::StorageLayout,
+    frame: Vec<u32>, // the screen with the status bar below it
+    media_library: MediaLibrary,
+    disk_menu: Option<minifb::MenuHandle>,
+    midi_clock: Option<midi_clock::MidiClockShared>,
+    audio_output: Option<audio_output::AudioOutput>,
+    patches: patches::PatchSet,
+    file_watcher: Option<file_watcher::FileWatcher>,
+    frame_pacer: Option<frame_pacer::FramePacer>, // None presents every emulated frame at 50Hz
+    recording: Option<replay::Replay>,
+    paused: bool,
+    turbo: bool,
+    quit: bool,
 }
 
 impl GUI {
-    pub fn new(system: Box<dyn system::System>) -> GUI {
+    pub fn new(
+        system: Box<dyn system::System>,
+        key_mapper: key_mapper::KeyMapper,
+        log_history: logger::LogHistoryShared,
+        scale: WindowScale,
+    ) -> GUI {
         let mut window = minifb::Window::new(
             "Ronald - Amstrad CPC Emulator",
             screen::BUFFER_WIDTH,
-            screen::BUFFER_HEIGHT,
-            minifb::WindowOptions::default(),
+            screen::BUFFER_HEIGHT + status_bar::STATUS_BAR_HEIGHT,
+            scale.window_options(),
         )
         .unwrap(); // TODO: handle errors properly
 
-        window.limit_update_rate(Some(std::time::Duration::from_micros(20_000))); // 50 fps
-                                                                                  // TODO: measure actual fps
+        window.limit_update_rate(None); // paced by the run loop, which polls the input while it waits
+        add_menus(&mut window);
+        let debug_console = debug_console::DebugConsole::new_shared();
 
-        GUI { system, window }
+        GUI {
+            system,
+            window,
+            host_input: HostInput::new(),
+            key_mapper,
+            input_enabled: true,
+            workbench: workbench::Workbench::new(&workbench::Layout::default(), log_history.clone(), debug_console.clone()),
+            debug_console,
+            log_history,
+            layout_file: None,
+            palette: None,
+            key_configurator: None,
+            status_bar: StatusBar::new(),
+            window_title: window_title::WindowTitle::new(),
+            storage: storage::StorageLayout::new(storage::DEFAULT_ROOT),
+            frame: vec![0; screen::BUFFER_WIDTH * (screen::BUFFER_HEIGHT + status_bar::STATUS_BAR_HEIGHT)],
+            media_library: MediaLibrary::new(),
+            disk_menu: None,
+            midi_clock: None,
+            audio_output: None,
+            patches: patches::PatchSet::new(),
+            file_watcher: None,
+            frame_pacer: None,
+            recording: None,
+            paused: false,
+            turbo: false,
+            quit: false,
+        }
+    }
+
+    pub fn load_layout(&mut self, filename: &str) {
+        // a missing file just means that no panels were arranged yet
+        let layout = match workbench::Layout::load(filename) {
+            Ok(layout) => layout,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => workbench::Layout::default(),
+            Err(error) => {
+                println!("Could not load layout \"{}\": {}", filename, error);
+                workbench::Layout::default()
+            }
+        };
+        self.workbench = workbench::Workbench::new(&layout, self.log_history.clone(), self.debug_console.clone());
+        self.workbench.arrange(&mut self.window);
+        self.layout_file = Some(filename.to_string());
+    }
+
+    pub fn set_media_library(&mut self, media_library: MediaLibrary) {
+        self.media_library = media_library;
+        self.update_disk_menu();
+    }
+
+    pub fn set_midi_clock(&mut self, midi_clock: midi_clock::MidiClockShared) {
+        self.midi_clock = Some(midi_clock);
+    }
+
+    pub fn open_audio(&mut self, settings: &audio_output::AudioSettings) {
+        // without a working device the emulation just runs silently
+        let mixer = self.system.get_mixer();
+        let result = audio_output::AudioOutput::open(&mut mixer.borrow_mut(), settings);
+        match result {
+            Ok(output) => self.audio_output = Some(output),
+            Err(error) => println!("Could not open the audio device: {}", error),
+        }
+    }
+
+    pub fn set_storage(&mut self, storage: storage::StorageLayout) {
+        self.storage = storage;
+    }
+
+    pub fn set_media_name(&mut self, name: Option<String>) {
+        // shown in the window title, e.g. the title from the game database
+        self.window_title.set_media_name(name);
+    }
+
+    pub fn set_patches(&mut self, patches: patches::PatchSet) {
+        self.patches = patches;
+    }
+
+    pub fn set_recording(&mut self, recording: replay::Replay) {
+        // the replay gets the metadata of every emulated frame, the key events are taken from the
+        // keyboard when it is finished
+        self.recording = Some(recording);
+    }
+
+    pub fn take_recording(&mut self) -> Option<replay::Replay> {
+        self.recording.take()
+    }
+
+    pub fn enable_hot_reload(&mut self) {
+        // ROMs and the key map are reloaded as soon as they are saved, e.g. while hacking the firmware
+        let mut file_watcher = file_watcher::FileWatcher::new();
+        for filename in self.system.list_rom_files() {
+            file_watcher.watch(&filename);
+        }
+        if let Some(filename) = self.key_mapper.get_filename() {
+            file_watcher.watch(filename);
+        }
+        self.file_watcher = Some(file_watcher);
+    }
+
+    pub fn set_frame_pacer(&mut self, frame_pacer: frame_pacer::FramePacer) {
+        log::info!("Frame pacing: {}", frame_pacer.get_pacing().name());
+        self.frame_pacer = Some(frame_pacer);
+    }
+
+    pub fn disable_input(&mut self) {
+        // e.g. while playing back a replay, which any key press would desynchronize
+        self.input_enabled = false;
+    }
+
+    pub fn into_system(self) -> Box<dyn system::System> {
+        self.system
     }
 
     pub fn run(&mut self) {
-        self.window.limit_update_rate(Some(std::time::Duration::from_micros(20_000)));
+        let present_interval = match &self.frame_pacer {
+            Some(frame_pacer) => frame_pacer.get_present_interval(),
+            None => std::time::Duration::from_micros(20_000),
+        };
+        let mut last_present = std::time::Instant::now();
 
-        while self.window.is_open() && !self.should_quit() {
+        let mut frames_until_report = 250;
+        let mut frames_until_watch = WATCH_INTERVAL_FRAMES;
+        while self.window.is_open() && !self.quit {
             // println!("new frame");
 
-            if self.window.is_key_down(minifb::Key::F12) {
-                self.system.activate_debugger();
+            for action in self.read_triggered_actions() {
+                self.execute(action);
             }
 
+            let frames = match (self.paused, self.turbo, &mut self.frame_pacer) {
+                (true, _, _) => 0,
+                (false, true, _) => TURBO_FRAMES,
+                (false, false, Some(frame_pacer)) => frame_pacer.frames_due(),
+                (false, false, None) => 1,
+            };
+            self.update_keys(frames);
             let mut elapsed_microseconds: u32 = 0;
-            while elapsed_microseconds < 20_000 { // TODO: tie this to vsync instead of fixed value
-                self.update_keys();
-                elapsed_microseconds += self.system.emulate() as u32;
+            for frame in 1..=frames {
+                while elapsed_microseconds < frame * 20_000 { // TODO: tie this to vsync instead of fixed value
+                    if self.system.is_debugger_stopped() {
+                        break;
+                    }
+                    elapsed_microseconds += self.system.emulate() as u32;
+                }
+                if let Some(midi_clock) = &self.midi_clock {
+                    midi_clock.borrow_mut().update(self.system.as_ref());
+                }
+                self.patches.update(self.system.as_mut());
+                if let Some(recording) = &mut self.recording {
+                    recording.record_frame(self.system.as_ref());
+                }
+                if let Some(frame_pacer) = &mut self.frame_pacer {
+                    frame_pacer.add_frame(self.system.get_screen().borrow().get_frame_buffer());
+                }
+            }
+
+            if self.system.is_debugger_stopped() && !self.workbench.is_open(workbench::PanelKind::Monitor) {
+                self.run_debug_console();
             }
-            
+
+            self.status_bar.update(self.system.as_ref(), self.paused, self.turbo);
+            let speed = self.status_bar.get_speed();
+            if let Some(title) = self.window_title.update(self.system.as_ref(), speed, self.paused, self.turbo) {
+                self.window.set_title(title);
+            }
+            let screen_size = screen::BUFFER_WIDTH * screen::BUFFER_HEIGHT;
+            match self.frame_pacer.as_mut().and_then(|frame_pacer| frame_pacer.present()) {
+                Some(frame) if !self.paused => self.frame[..screen_size].copy_from_slice(frame),
+                _ => self.frame[..screen_size].copy_from_slice(self.system.get_screen().borrow().get_frame_buffer()),
+            }
+            self.frame[screen_size..].copy_from_slice(self.status_bar.get_buffer());
+            self.host_input.wait_until(&mut self.window, last_present + present_interval);
+            last_present = std::time::Instant::now();
             self.window
                 .update_with_buffer(
-                    self.system.get_screen().borrow().get_frame_buffer(),
+                    &self.frame,
                     screen::BUFFER_WIDTH,
-                    screen::BUFFER_HEIGHT,
+                    screen::BUFFER_HEIGHT + status_bar::STATUS_BAR_HEIGHT,
                 )
                 .unwrap(); // TODO: handle errors properly
+            self.host_input.poll(&mut self.window);
+            if self.frame_pacer.is_none() || frames > 0 {
+                // repeated frames do not make up for dropped ones
+                self.system.notify_frame_presented();
+            }
+
+            if self.workbench.update(self.system.as_mut()) {
+                self.workbench.arrange(&mut self.window);
+            }
+
+            frames_until_report -= 1;
+            if frames_until_report == 0 {
+                frames_until_report = 250;
+                let statistics = self.system.get_sync_statistics();
+                log::info!(
+                    "Drift: {}ms, frames dropped: {}, audio rate: {:+}ppm",
+                    statistics.drift_microseconds / 1000,
+                    statistics.frames_dropped,
+                    statistics.audio_rate_adjustment_ppm
+                );
+            }
+
+            frames_until_watch -= 1;
+            if frames_until_watch == 0 {
+                frames_until_watch = WATCH_INTERVAL_FRAMES;
+                self.check_watched_files();
+            }
+        }
+
+        if let Some(midi_clock) = &self.midi_clock {
+            midi_clock.borrow_mut().stop();
+        }
+
+        let question = "Save the changes to the disks before quitting?";
+        if self.media_library.has_unsaved_changes(self.system.as_ref()) && confirm("Unsaved disk changes", question) {
+            if let Err(error) = self.media_library.save_disks(self.system.as_mut()) {
+                println!("Could not save the disks: {}", error);
+            }
+        }
+
+        if let Some(filename) = &self.layout_file {
+            if let Err(error) = self.workbench.layout().save(filename) {
+                println!("Could not save layout \"{}\": {}", filename, error);
+            }
         }
     }
 
-    fn should_quit(&self) -> bool {
-        let ctrl_down = self.window.is_key_down(minifb::Key::LeftCtrl);
-        ctrl_down && self.window.is_key_down(minifb::Key::Q)
+    fn run_debug_console(&mut self) {
+        // without a monitor panel the debugger is controlled from the command line, which blocks the window
+        let mut debug_console = self.debug_console.borrow_mut();
+        for line in debug_console.stop_lines(self.system.as_ref()) {
+            println!("{}", line);
+        }
+
+        while self.system.is_debugger_stopped() {
+            print!("> ");
+            std::io::stdout().flush().unwrap();
+            let mut command = String::new();
+            match std::io::stdin().read_line(&mut command) {
+                Ok(0) | Err(_) => self.system.resume_debugger(debugger::Resume::Run), // no more input
+                Ok(_) => {
+                    for line in debug_console.execute(self.system.as_mut(), &command) {
+                        println!("{}", line);
+                    }
+                }
+            }
+        }
     }
 
-    fn update_keys(&mut self) {
-        let keyboard = self.system.get_keyboard();
-        keyboard.borrow_mut().reset_all();
-
-        if let Some(keys) = self.window.get_keys() {
-            for key in keys {
-                match key {
-                    minifb::Key::Up => keyboard.borrow_mut().set_key(0, 0),
-                    minifb::Key::Right => keyboard.borrow_mut().set_key(0, 1),
-                    minifb::Key::Down => keyboard.borrow_mut().set_key(0, 2),
-                    minifb::Key::NumPad9 => keyboard.borrow_mut().set_key(0, 3),
-                    minifb::Key::NumPad6 => keyboard.borrow_mut().set_key(0, 4),
-                    minifb::Key::NumPad3 => keyboard.borrow_mut().set_key(0, 5),
-                    minifb::Key::NumPadEnter => keyboard.borrow_mut().set_key(0, 6),
-                    minifb::Key::NumPadDot => keyboard.borrow_mut().set_key(0, 7),
-                    minifb::Key::Left => keyboard.borrow_mut().set_key(1, 0),
-                    minifb::Key::Insert => keyboard.borrow_mut().set_key(1, 1),
-                    minifb::Key::NumPad7 => keyboard.borrow_mut().set_key(1, 2),
-                    minifb::Key::NumPad8 => keyboard.borrow_mut().set_key(1, 3),
-                    minifb::Key::NumPad5 => keyboard.borrow_mut().set_key(1, 4),
-                    minifb::Key::NumPad1 => keyboard.borrow_mut().set_key(1, 5),
-                    minifb::Key::NumPad2 => keyboard.borrow_mut().set_key(1, 6),
-                    minifb::Key::NumPad0 => keyboard.borrow_mut().set_key(1, 7),
-                    minifb::Key::Delete => keyboard.borrow_mut().set_key(2, 0),
-                    minifb::Key::LeftBracket => keyboard.borrow_mut().set_key(2, 1),
-                    minifb::Key::Enter => keyboard.borrow_mut().set_key(2, 2),
-                    minifb::Key::RightBracket => keyboard.borrow_mut().set_key(2, 3),
-                    minifb::Key::NumPad4 => keyboard.borrow_mut().set_key(2, 4),
-                    minifb::Key::LeftShift => keyboard.borrow_mut().set_key(2, 5),
-                    minifb::Key::RightShift => keyboard.borrow_mut().set_key(2, 5),
-                    minifb::Key::Backslash => keyboard.borrow_mut().set_key(2, 6),
-                    minifb::Key::RightCtrl => keyboard.borrow_mut().set_key(2, 7),
-                    minifb::Key::Equal => keyboard.borrow_mut().set_key(3, 0),
-                    minifb::Key::Minus => keyboard.borrow_mut().set_key(3, 1),
-                    minifb::Key::End => keyboard.borrow_mut().set_key(3, 2),
-                    minifb::Key::P => keyboard.borrow_mut().set_key(3, 3),
-                    minifb::Key::Semicolon => keyboard.borrow_mut().set_key(3, 4),
-                    minifb::Key::Apostrophe => keyboard.borrow_mut().set_key(3, 5),
-                    minifb::Key::Slash => keyboard.borrow_mut().set_key(3, 6),
-                    minifb::Key::Period => keyboard.borrow_mut().set_key(3, 7),
-                    minifb::Key::Key0 => keyboard.borrow_mut().set_key(4, 0),
-                    minifb::Key::Key9 => keyboard.borrow_mut().set_key(4, 1),
-                    minifb::Key::O => keyboard.borrow_mut().set_key(4, 2),
-                    minifb::Key::I => keyboard.borrow_mut().set_key(4, 3),
-                    minifb::Key::L => keyboard.borrow_mut().set_key(4, 4),
-                    minifb::Key::K => keyboard.borrow_mut().set_key(4, 5),
-                    minifb::Key::M => keyboard.borrow_mut().set_key(4, 6),
-                    minifb::Key::Comma => keyboard.borrow_mut().set_key(4, 7),
-                    minifb::Key::Key8 => keyboard.borrow_mut().set_key(5, 0),
-                    minifb::Key::Key7 => keyboard.borrow_mut().set_key(5, 1),
-                    minifb::Key::U => keyboard.borrow_mut().set_key(5, 2),
-                    minifb::Key::Y => keyboard.borrow_mut().set_key(5, 3),
-                    minifb::Key::H => keyboard.borrow_mut().set_key(5, 4),
-                    minifb::Key::J => keyboard.borrow_mut().set_key(5, 5),
-                    minifb::Key::N => keyboard.borrow_mut().set_key(5, 6),
-                    minifb::Key::Space => keyboard.borrow_mut().set_key(5, 7),
-                    minifb::Key::Key6 => keyboard.borrow_mut().set_key(6, 0),
-                    minifb::Key::Key5 => keyboard.borrow_mut().set_key(6, 1),
-                    minifb::Key::R => keyboard.borrow_mut().set_key(6, 2),
-                    minifb::Key::T => keyboard.borrow_mut().set_key(6, 3),
-                    minifb::Key::G => keyboard.borrow_mut().set_key(6, 4),
-                    minifb::Key::F => keyboard.borrow_mut().set_key(6, 5),
-                    minifb::Key::B => keyboard.borrow_mut().set_key(6, 6),
-                    minifb::Key::V => keyboard.borrow_mut().set_key(6, 7),
-                    minifb::Key::Key4 => keyboard.borrow_mut().set_key(7, 0),
-                    minifb::Key::Key3 => keyboard.borrow_mut().set_key(7, 1),
-                    minifb::Key::E => keyboard.borrow_mut().set_key(7, 2),
-                    minifb::Key::W => keyboard.borrow_mut().set_key(7, 3),
-                    minifb::Key::S => keyboard.borrow_mut().set_key(7, 4),
-                    minifb::Key::D => keyboard.borrow_mut().set_key(7, 5),
-                    minifb::Key::C => keyboard.borrow_mut().set_key(7, 6),
-                    minifb::Key::X => keyboard.borrow_mut().set_key(7, 7),
-                    minifb::Key::Key1 => keyboard.borrow_mut().set_key(8, 0),
-                    minifb::Key::Key2 => keyboard.borrow_mut().set_key(8, 1),
-                    minifb::Key::Escape => keyboard.borrow_mut().set_key(8, 2),
-                    minifb::Key::Q => keyboard.borrow_mut().set_key(8, 3),
-                    minifb::Key::Tab => keyboard.borrow_mut().set_key(8, 4),
-                    minifb::Key::A => keyboard.borrow_mut().set_key(8, 5),
-                    minifb::Key::CapsLock => keyboard.borrow_mut().set_key(8, 6),
-                    minifb::Key::Z => keyboard.borrow_mut().set_key(8, 7),
-                    // minifb::Key::NumPad8 => keyboard.borrow_mut().set_key(9, 0), // TODO: map joystick 1
-                    // minifb::Key::NumPad2 => keyboard.borrow_mut().set_key(9, 1),
-                    // minifb::Key::NumPad4 => keyboard.borrow_mut().set_key(9, 2),
-                    // minifb::Key::NumPad6 => keyboard.borrow_mut().set_key(9, 3),
-                    // minifb::Key::NumPad5 => keyboard.borrow_mut().set_key(9, 4),
-                    // minifb::Key::NumPad0 => keyboard.borrow_mut().set_key(9, 5),
-                    // minifb::Key::NumPadDot => keyboard.borrow_mut().set_key(9, 6),
-                    minifb::Key::Backspace => keyboard.borrow_mut().set_key(9, 7),
-                    _ => {}
+    fn read_triggered_actions(&mut self) -> Vec<Action> {
+        let mut triggered = Vec::new();
+        for id in self.host_input.take_menu_presses() {
+            if id >= DISK_MENU_ID_BASE {
+                self.media_library.insert_disk(self.system.as_mut(), id - DISK_MENU_ID_BASE);
+                self.update_disk_menu();
+            } else {
+                triggered.push(actions::ACTIONS[id].action);
+            }
+        }
+
+        for (key, ctrl_down) in self.host_input.take_shortcuts() {
+            for info in actions::ACTIONS.iter() {
+                if let Some(shortcut) = info.shortcut {
+                    if shortcut.ctrl == ctrl_down && shortcut.key == key {
+                        triggered.push(info.action);
+                    }
+                }
+            }
+        }
+
+        let event = match &mut self.palette {
+            Some(palette) => palette.update(self.system.as_ref()),
+            None => palette::PaletteEvent::None,
+        };
+        match event {
+            palette::PaletteEvent::Chosen(action) => {
+                self.palette = None;
+                triggered.push(action);
+            }
+            palette::PaletteEvent::Closed => self.palette = None,
+            palette::PaletteEvent::None => (),
+        }
+
+        let event = match &mut self.key_configurator {
+            Some(configurator) => configurator.update(&mut self.key_mapper),
+            None => key_configurator::ConfiguratorEvent::None,
+        };
+        if let key_configurator::ConfiguratorEvent::Closed = event {
+            self.key_configurator = None;
+        }
+
+        triggered
+    }
+
+    fn execute(&mut self, action: Action) {
+        match action {
+            Action::OpenPalette => {
+                if self.palette.is_none() {
+                    self.palette = palette::CommandPalette::open();
+                }
+            }
+            Action::LoadMedia => self.load_media(),
+            Action::SaveDisk => {
+                if let Err(error) = self.system.save_disk() {
+                    println!("Could not save the disk: {}", error);
+                }
+            }
+            Action::ToggleWriteProtect => {
+                let protect = !self.system.is_write_protected();
+                self.system.set_write_protected(protect);
+            }
+            Action::InsertNextDisk => {
+                self.media_library.insert_next_disk(self.system.as_mut());
+                self.update_disk_menu();
+            }
+            Action::EjectDisk => {
+                self.media_library.eject_disk(self.system.as_mut());
+                self.update_disk_menu();
+            }
+            Action::Reset => {
+                let config = self.system.get_config();
+                self.system.reconfigure(config);
+            }
+            Action::MachineSettings => self.show_machine_dialog(),
+            Action::ReloadFiles => {
+                self.reload_roms();
+                self.reload_key_map();
+            }
+            Action::TogglePause => self.paused = !self.paused,
+            Action::ToggleTurbo => self.turbo = !self.turbo,
+            Action::ToggleTapeSound => {
+                let mixer = self.system.get_mixer();
+                let gain = if mixer.borrow().get_gain(mixer::AudioSource::Tape) > 0.0 { 0.0 } else { 1.0 };
+                mixer.borrow_mut().set_gain(mixer::AudioSource::Tape, gain);
+            }
+            Action::NextAudioDevice => self.select_next_audio_device(),
+            Action::ToggleFastLoad => {
+                let tape = self.system.get_tape();
+                let fast_load = tape.borrow().is_fast_load_enabled();
+                tape.borrow_mut().set_fast_load(!fast_load);
+            }
+            Action::ToggleKeyboardMode => {
+                let keyboard = self.system.get_keyboard();
+                let mode = self.key_mapper.toggle_mode(&mut keyboard.borrow_mut());
+                log::info!("Keyboard mode: {}", mode.name());
+            }
+            Action::ConfigureKeys => {
+                if self.key_configurator.is_none() {
+                    self.key_configurator = key_configurator::KeyConfigurator::open();
                 }
             }
+            Action::TogglePanel(kind) => {
+                self.workbench.toggle_panel(kind);
+                self.workbench.arrange(&mut self.window);
+            }
+            Action::ActivateDebugger => self.system.activate_debugger(),
+            Action::SaveState => {
+                let snapshot = self.system.save_snapshot();
+                self.write_to_storage(storage::StorageKind::States, "state", |filename| snapshot.save(filename));
+            }
+            Action::TakeScreenshot => {
+                let screen = self.system.get_screen();
+                self.write_to_storage(storage::StorageKind::Screenshots, "screenshot", |filename| {
+                    screen.borrow().write_png(filename)
+                });
+            }
+            Action::CaptureBugReport => {
+                let system = self.system.as_ref();
+                self.write_to_storage(storage::StorageKind::BugReports, "bug report", |filename| {
+                    bug_report::write_bug_report(system, filename)
+                });
+            }
+            Action::Quit => self.quit = true,
+        }
+    }
+
+    fn check_watched_files(&mut self) {
+        let changed = match &mut self.file_watcher {
+            Some(file_watcher) => file_watcher.poll(),
+            None => return,
+        };
+        if changed.is_empty() {
+            return;
+        }
+
+        if self.system.list_rom_files().iter().any(|filename| changed.contains(filename)) {
+            self.reload_roms();
+        }
+        if let Some(filename) = self.key_mapper.get_filename() {
+            if changed.iter().any(|changed| changed == filename) {
+                self.reload_key_map();
+            }
+        }
+    }
+
+    fn reload_roms(&mut self) {
+        // the running code is not reset, which is up to the user
+        match self.system.reload_roms() {
+            Ok(()) => log::info!("Reloaded the ROMs."),
+            Err(error) => println!("Could not reload the ROMs: {}", error),
+        }
+    }
+
+    fn reload_key_map(&mut self) {
+        let filename = match self.key_mapper.get_filename() {
+            Some(filename) => filename.to_string(),
+            None => return,
+        };
+        let keyboard = self.system.get_keyboard();
+        let result = self.key_mapper.reload(&mut keyboard.borrow_mut());
+        match result {
+            Ok(()) => log::info!("Reloaded key map \"{}\".", filename),
+            Err(error) => println!("Could not reload key map \"{}\": {}", filename, error),
+        }
+    }
+
+    fn load_media(&mut self) {
+        let path = native_dialog::FileDialog::new()
+            .add_filter("Disks, tapes and snapshots", &["dsk", "cdt", "tzx", "sna"])
+            .show_open_single_file();
+        let filename = match path {
+            Ok(Some(path)) => path.to_string_lossy().to_string(),
+            _ => return,
+        };
+
+        let result = match media::MediaType::detect(&filename) {
+            Some(media::MediaType::Disk) => dsk_file::Disk::load(&filename).map(|disk| {
+                // the previous disk stays in the library, so swapping back keeps its unsaved changes
+                let index = self.media_library.add_disk(disk);
+                self.media_library.insert_disk(self.system.as_mut(), index);
+                self.update_disk_menu();
+            }),
+            Some(media::MediaType::Tape) => cdt_file::Tape::load(&filename).map(|tape| self.system.load_tape(tape)),
+            Some(media::MediaType::Snapshot) => {
+                sna_file::Snapshot::load(&filename).map(|snapshot| self.system.load_snapshot(&snapshot))
+            }
+            _ => Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "Unsupported media type.")),
+        };
+        if let Err(error) = result {
+            println!("Could not load \"{}\": {}", filename, error);
+            return;
+        }
+        let name = window_title::media_name(&filename);
+        if let Ok(hash) = media::hash_media(&filename) {
+            self.patches.select_media(hash);
+            self.storage.select_media(hash, &name);
+        }
+        self.window_title.set_media_name(Some(name));
+    }
+
+    fn write_to_storage(&self, kind: storage::StorageKind, description: &str, write: impl FnOnce(&str) -> std::io::Result<()>) {
+        let filename = match self.storage.new_path(kind) {
+            Ok(filename) => filename,
+            Err(error) => {
+                println!("Could not create the {} folder: {}", kind.name(), error);
+                return;
+            }
+        };
+        match write(&filename) {
+            Ok(()) => println!("Wrote {} \"{}\"", description, filename),
+            Err(error) => println!("Could not write {} \"{}\": {}", description, filename, error),
+        }
+    }
+
+    fn update_disk_menu(&mut self) {
+        // lists the library, choosing a disk inserts it into drive A
+        if let Some(handle) = self.disk_menu.take() {
+            self.window.remove_menu(handle);
+        }
+        if self.media_library.is_empty() {
+            return;
+        }
+
+        let mut menu = match minifb::Menu::new("Disks") {
+            Ok(menu) => menu,
+            Err(error) => {
+                log::error!("Could not create the Disks menu: {}", error);
+                return;
+            }
+        };
+        for (index, (name, inserted)) in self.media_library.list_disks().into_iter().enumerate() {
+            let label = if inserted { format!("{} (inserted)", name) } else { name.to_string() };
+            menu.add_item(&label, DISK_MENU_ID_BASE + index).build();
+        }
+        self.disk_menu = Some(self.window.add_menu(&menu));
+    }
+
+    fn show_machine_dialog(&mut self) {
+        // TODO: replace the chain of message boxes with a proper settings window
+        let expansions = self.system.list_expansions();
+        let text = if expansions.is_empty() {
+            "No expansion devices are connected.\n\nAttach a real-time clock?".to_string()
+        } else {
+            format!(
+                "Connected expansion devices: {}\n\nToggle the real-time clock?",
+                expansions.join(", ")
+            )
+        };
+        if confirm("Machine", &text) {
+            if self.system.get_real_time_clock().is_some() {
+                self.system.detach_expansion("RTC");
+            } else {
+                self.system.enable_real_time_clock();
+            }
+        }
+
+        let mixer = self.system.get_mixer();
+        let tape_sound = mixer.borrow().get_gain(mixer::AudioSource::Tape) > 0.0;
+        let text = format!("Tape sound is {}.\n\nToggle it?", if tape_sound { "on" } else { "off" });
+        if confirm("Machine", &text) {
+            mixer.borrow_mut().set_gain(mixer::AudioSource::Tape, if tape_sound { 0.0 } else { 1.0 });
+        }
+
+        let device = match &self.audio_output {
+            Some(output) => output.get_device_name().to_string(),
+            None => "off".to_string(),
+        };
+        let text = format!("Audio output: {}\n\nSwitch to the next audio device?", device);
+        if confirm("Machine", &text) {
+            self.select_next_audio_device();
+        }
+
+        let tape = self.system.get_tape();
+        let fast_load = tape.borrow().is_fast_load_enabled();
+        let text = format!("Fast loading is {}.\n\nToggle it?", if fast_load { "on" } else { "off" });
+        if confirm("Machine", &text) {
+            tape.borrow_mut().set_fast_load(!fast_load);
+        }
+
+        // the machine options take effect with a reset
+        let mut config = self.system.get_config();
+        let mut changed = false;
+        let next_crtc_type = match config.crtc_type {
+            crtc::CrtcType::Type0 => crtc::CrtcType::Type1,
+            crtc::CrtcType::Type1 => crtc::CrtcType::Type2,
+            crtc::CrtcType::Type2 => crtc::CrtcType::Type3,
+            crtc::CrtcType::Type3 => crtc::CrtcType::Type0,
+        };
+        let text = format!(
+            "The CRTC is type {}.\n\nSwitch to type {} and reset the machine?",
+            config.crtc_type.number(),
+            next_crtc_type.number()
+        );
+        if confirm("Machine", &text) {
+            config.crtc_type = next_crtc_type;
+            changed = true;
+        }
+
+        let next_ram_size = match config.ram_size {
+            memory::RamSize::Kilobytes64 => memory::RamSize::Kilobytes128,
+            memory::RamSize::Kilobytes128 => memory::RamSize::Kilobytes320,
+            memory::RamSize::Kilobytes320 => memory::RamSize::Kilobytes576,
+            memory::RamSize::Kilobytes576 => memory::RamSize::Kilobytes64,
+        };
+        let text = format!(
+            "RAM: {}\n\nSwitch to {} and reset the machine?",
+            config.ram_size.name().to_uppercase(),
+            next_ram_size.name().to_uppercase()
+        );
+        if confirm("Machine", &text) {
+            config.ram_size = next_ram_size;
+            changed = true;
+        }
+
+        if changed || confirm("Machine", "Reset the machine?") {
+            self.system.reconfigure(config);
+        }
+
+        // keys held while the dialog was open would never be released otherwise
+        self.system.get_keyboard().borrow_mut().reset_all();
+    }
+
+    fn select_next_audio_device(&mut self) {
+        // the buffer size and latency stay the same
+        let devices = audio_output::list_devices();
+        if devices.is_empty() {
+            println!("No audio devices found.");
+            return;
+        }
+
+        let mut settings = match &self.audio_output {
+            Some(output) => output.get_settings().clone(),
+            None => audio_output::AudioSettings::default(),
+        };
+        let current = self.audio_output.as_ref().and_then(|output| devices.iter().position(|device| device == output.get_device_name()));
+        let next = current.map(|index| (index + 1) % devices.len()).unwrap_or(0);
+        settings.device = Some(devices[next].clone());
+
+        self.audio_output = None; // a device can only be opened once on some hosts
+        self.open_audio(&settings);
+    }
+
+    fn update_keys(&mut self, frames: u32) {
+        let key_events = self.host_input.take_key_events(frames);
+        if !self.input_enabled {
+            return;
+        }
+
+        let keyboard = self.system.get_keyboard();
+        for (delay, key, pressed) in key_events {
+            if pressed {
+                self.key_mapper.press_key(key, delay, &mut keyboard.borrow_mut());
+            } else {
+                self.key_mapper.release_key(key, delay, &mut keyboard.borrow_mut());
+            }
+        }
+    }
+}
+
+fn add_menus(window: &mut minifb::Window) {
+    // menu item ids are indices into the action registry
+    let mut names: Vec<&str> = Vec::new();
+    for info in actions::ACTIONS.iter() {
+        if !names.contains(&info.menu) {
+            names.push(info.menu);
+        }
+    }
+
+    for name in names {
+        let mut menu = match minifb::Menu::new(name) {
+            Ok(menu) => menu,
+            Err(error) => {
+                log::error!("Could not create the {} menu: {}", name, error);
+                continue;
+            }
+        };
+        for (id, info) in actions::ACTIONS.iter().enumerate().filter(|(_, info)| info.menu == name) {
+            let mut item = menu.add_item(info.name, id);
+            if let Some(shortcut) = info.shortcut {
+                let modifier = if shortcut.ctrl { minifb::MENU_KEY_CTRL } else { 0 };
+                item = item.shortcut(shortcut.key, modifier);
+            }
+            item.build();
         }
+        window.add_menu(&menu);
     }
 }
+
+fn confirm(title: &str, text: &str) -> bool {
+    native_dialog::MessageDialog::new()
+        .set_title(title)
+        .set_text(text)
+        .set_type(native_dialog::MessageType::Info)
+        .show_confirm()
+        .unwrap_or(false)
+}
@@ -0,0 +1,59 @@
+use crate::keyboard;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum JoystickButton {
+    Up,
+    Down,
+    Left,
+    Right,
+    Fire1,
+    Fire2,
+}
+
+impl JoystickButton {
+    pub fn from_name(name: &str) -> Option<JoystickButton> {
+        match name {
+            "up" => Some(JoystickButton::Up),
+            "down" => Some(JoystickButton::Down),
+            "left" => Some(JoystickButton::Left),
+            "right" => Some(JoystickButton::Right),
+            "fire1" => Some(JoystickButton::Fire1),
+            "fire2" => Some(JoystickButton::Fire2),
+            _ => None,
+        }
+    }
+
+    fn bit(&self) -> u8 {
+        match self {
+            JoystickButton::Up => 0,
+            JoystickButton::Down => 1,
+            JoystickButton::Left => 2,
+            JoystickButton::Right => 3,
+            JoystickButton::Fire2 => 4,
+            JoystickButton::Fire1 => 5,
+        }
+    }
+}
+
+// input from any host device in terms of the CPC, so frontends can wire up gamepads and the like
+// without knowing about the keyboard matrix
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum InputEvent {
+    Key { line: usize, bit: u8, pressed: bool },
+    Joystick { port: usize, button: JoystickButton, pressed: bool },
+}
+
+pub fn inject(keyboard: &mut keyboard::Keyboard, event: InputEvent) {
+    // joysticks are wired into the keyboard matrix, the second one shares its line with keys
+    let (line, bit, pressed) = match event {
+        InputEvent::Key { line, bit, pressed } if line < 10 && bit < 8 => (line, bit, pressed),
+        InputEvent::Joystick { port: 0, button, pressed } => (9, button.bit(), pressed),
+        InputEvent::Joystick { port: 1, button, pressed } => (6, button.bit(), pressed),
+        _ => {
+            log::warn!("Ignoring input {:?} that the CPC has no wiring for.", event);
+            return;
+        }
+    };
+
+    keyboard.queue_key_event(0, line, bit, pressed);
+}
@@ -198,474 +198,469 @@ pub enum Instruction {
 }
 
 impl Instruction {
+    pub fn index(&self) -> usize {
+        // the position in the enum, which execution tables are ordered by
+        match self {
+            Instruction::Adc(..) => 0,
+            Instruction::Add(..) => 1,
+            Instruction::And(..) => 2,
+            Instruction::Bit(..) => 3,
+            Instruction::Call(..) => 4,
+            Instruction::Ccf => 5,
+            Instruction::Cp(..) => 6,
+            Instruction::Cpd => 7,
+            Instruction::Cpdr => 8,
+            Instruction::Cpi => 9,
+            Instruction::Cpir => 10,
+            Instruction::Cpl => 11,
+            Instruction::Daa => 12,
+            Instruction::Dec(..) => 13,
+            Instruction::Defb(..) => 14,
+            Instruction::Defw(..) => 15,
+            Instruction::Di => 16,
+            Instruction::Djnz(..) => 17,
+            Instruction::Ei => 18,
+            Instruction::Ex(..) => 19,
+            Instruction::Exx => 20,
+            Instruction::Halt => 21,
+            Instruction::Im(..) => 22,
+            Instruction::In(..) => 23,
+            Instruction::Ind => 24,
+            Instruction::Indr => 25,
+            Instruction::Ini => 26,
+            Instruction::Inir => 27,
+            Instruction::Inc(..) => 28,
+            Instruction::Jp(..) => 29,
+            Instruction::Jr(..) => 30,
+            Instruction::Ld(..) => 31,
+            Instruction::LdDirect16(..) => 32,
+            Instruction::Ldd => 33,
+            Instruction::Lddr => 34,
+            Instruction::Ldi => 35,
+            Instruction::Ldir => 36,
+            Instruction::Neg => 37,
+            Instruction::Nop => 38,
+            Instruction::Or(..) => 39,
+            Instruction::Out(..) => 40,
+            Instruction::Otdr => 41,
+            Instruction::Otir => 42,
+            Instruction::Outd => 43,
+            Instruction::Outi => 44,
+            Instruction::Pop(..) => 45,
+            Instruction::Push(..) => 46,
+            Instruction::Res(..) => 47,
+            Instruction::Ret(..) => 48,
+            Instruction::Reti => 49,
+            Instruction::Retn => 50,
+            Instruction::Rl(..) => 51,
+            Instruction::Rla => 52,
+            Instruction::Rlc(..) => 53,
+            Instruction::Rlca => 54,
+            Instruction::Rld => 55,
+            Instruction::Rr(..) => 56,
+            Instruction::Rrc(..) => 57,
+            Instruction::Rra => 58,
+            Instruction::Rrca => 59,
+            Instruction::Rrd => 60,
+            Instruction::Rst(..) => 61,
+            Instruction::Sbc(..) => 62,
+            Instruction::Scf => 63,
+            Instruction::Set(..) => 64,
+            Instruction::Sla(..) => 65,
+            Instruction::Sll(..) => 66,
+            Instruction::Sra(..) => 67,
+            Instruction::Srl(..) => 68,
+            Instruction::Sub(..) => 69,
+            Instruction::Xor(..) => 70,
+        }
+    }
+
     pub fn timing(&self) -> u8 {
+        match self.lookup_timing() {
+            Some(timing) => timing,
+            None => {
+                println!("No timing for {}", self);
+                unimplemented!()
+            }
+        }
+    }
+
+    pub fn lookup_timing(&self) -> Option<u8> {
+        // in NOPs, None for operand combinations the table does not cover yet
         match self {
             Instruction::Adc(
                 Operand::Register8(cpu::Register8::A),
                 Operand::Register8(cpu::Register8::IXH),
-            ) => 2,
+            ) => Some(2),
             Instruction::Adc(
                 Operand::Register8(cpu::Register8::A),
                 Operand::Register8(cpu::Register8::IXL),
-            ) => 2,
+            ) => Some(2),
             Instruction::Adc(
                 Operand::Register8(cpu::Register8::A),
                 Operand::Register8(cpu::Register8::IYH),
-            ) => 2,
+            ) => Some(2),
             Instruction::Adc(
                 Operand::Register8(cpu::Register8::A),
                 Operand::Register8(cpu::Register8::IYL),
-            ) => 2,
-            Instruction::Adc(Operand::Register8(cpu::Register8::A), Operand::Register8(_)) => 1,
-            Instruction::Adc(Operand::Register8(cpu::Register8::A), Operand::Immediate8(_)) => 2,
+            ) => Some(2),
+            Instruction::Adc(Operand::Register8(cpu::Register8::A), Operand::Register8(_)) => Some(1),
+            Instruction::Adc(Operand::Register8(cpu::Register8::A), Operand::Immediate8(_)) => Some(2),
             Instruction::Adc(
                 Operand::Register8(cpu::Register8::A),
                 Operand::RegisterIndirect(cpu::Register16::HL),
-            ) => 2,
-            Instruction::Adc(Operand::Register8(cpu::Register8::A), Operand::Indexed(_, _)) => 5,
-            Instruction::Adc(Operand::Register16(cpu::Register16::HL), Operand::Register16(_)) => 4,
-            Instruction::Adc(_, _) => {
-                println!("No timing for {}", self);
-                unimplemented!()
-            }
-            Instruction::Add(Operand::Register8(cpu::Register8::A), Operand::Register8(_)) => 1,
-            Instruction::Add(Operand::Register8(cpu::Register8::A), Operand::Immediate8(_)) => 2,
+            ) => Some(2),
+            Instruction::Adc(Operand::Register8(cpu::Register8::A), Operand::Indexed(_, _)) => Some(5),
+            Instruction::Adc(Operand::Register16(cpu::Register16::HL), Operand::Register16(_)) => Some(4),
+            Instruction::Adc(_, _) => None,
+            Instruction::Add(Operand::Register8(cpu::Register8::A), Operand::Register8(_)) => Some(1),
+            Instruction::Add(Operand::Register8(cpu::Register8::A), Operand::Immediate8(_)) => Some(2),
             Instruction::Add(
                 Operand::Register8(cpu::Register8::A),
                 Operand::RegisterIndirect(cpu::Register16::HL),
-            ) => 2,
-            Instruction::Add(Operand::Register8(cpu::Register8::A), Operand::Indexed(_, _)) => 5,
-            Instruction::Add(Operand::Register16(cpu::Register16::HL), Operand::Register16(_)) => 3,
-            Instruction::Add(Operand::Register16(cpu::Register16::IX), Operand::Register16(_)) => 4,
-            Instruction::Add(Operand::Register16(cpu::Register16::IY), Operand::Register16(_)) => 4,
-            Instruction::Add(_, _) => {
-                println!("No timing for {}", self);
-                unimplemented!()
-            }
-            Instruction::And(Operand::Register8(cpu::Register8::IXH)) => 2,
-            Instruction::And(Operand::Register8(cpu::Register8::IXL)) => 2,
-            Instruction::And(Operand::Register8(cpu::Register8::IYH)) => 2,
-            Instruction::And(Operand::Register8(cpu::Register8::IYL)) => 2,
-            Instruction::And(Operand::Register8(_)) => 1,
-            Instruction::And(Operand::Immediate8(_)) => 2,
-            Instruction::And(Operand::RegisterIndirect(cpu::Register16::HL)) => 2,
-            Instruction::And(Operand::Indexed(_, _)) => 5,
-            Instruction::And(_) => {
-                println!("No timing for {}", self);
-                unimplemented!()
-            }
-            Instruction::Bit(_, Operand::Register8(_)) => 2,
-            Instruction::Bit(_, Operand::RegisterIndirect(cpu::Register16::HL)) => 3,
-            Instruction::Bit(_, Operand::Indexed(_, _)) => 6, // TODO: check why this is not 5 as per official docs
-            Instruction::Bit(_, _) => {
-                println!("No timing for {}", self);
-                unimplemented!()
-            }
-            Instruction::Call(JumpTest::Unconditional, _) => 5,
-            Instruction::Call(JumpTest::NonZero, _) => 3,
-            Instruction::Call(JumpTest::Zero, _) => 5,
-            Instruction::Call(JumpTest::NoCarry, _) => 3,
-            Instruction::Call(JumpTest::Carry, _) => 5,
-            Instruction::Call(JumpTest::ParityOdd, _) => 3,
-            Instruction::Call(JumpTest::ParityEven, _) => 5,
-            Instruction::Call(JumpTest::SignPositive, _) => 3,
-            Instruction::Call(JumpTest::SignNegative, _) => 5,
-            Instruction::Ccf => 1,
-            Instruction::Cp(Operand::Register8(_)) => 1,
-            Instruction::Cp(Operand::Immediate8(_)) => 2,
-            Instruction::Cp(Operand::RegisterIndirect(cpu::Register16::HL)) => 2,
-            Instruction::Cp(Operand::Indexed(_, _)) => 5,
-            Instruction::Cp(_) => {
-                println!("No timing for {}", self);
-                unimplemented!()
-            }
-            Instruction::Cpd => 5,
-            Instruction::Cpdr => 6,
-            Instruction::Cpi => 5,
-            Instruction::Cpir => 6,
-            Instruction::Cpl => 1,
-            Instruction::Daa => 1,
-            Instruction::Dec(Operand::Register8(_)) => 1,
-            Instruction::Dec(Operand::RegisterIndirect(cpu::Register16::HL)) => 3,
-            Instruction::Dec(Operand::Indexed(_, _)) => 6,
-            Instruction::Dec(Operand::Register16(cpu::Register16::IX)) => 3,
-            Instruction::Dec(Operand::Register16(cpu::Register16::IY)) => 3,
-            Instruction::Dec(Operand::Register16(_)) => 2,
-            Instruction::Dec(_) => {
-                println!("No timing for {}", self);
-                unimplemented!()
-            }
-            Instruction::Defb(_) => 1,
-            Instruction::Defw(_) => 2,
-            Instruction::Di => 1,
-            Instruction::Djnz(_) => 3,
-            Instruction::Ei => 1,
+            ) => Some(2),
+            Instruction::Add(Operand::Register8(cpu::Register8::A), Operand::Indexed(_, _)) => Some(5),
+            Instruction::Add(Operand::Register16(cpu::Register16::HL), Operand::Register16(_)) => Some(3),
+            Instruction::Add(Operand::Register16(cpu::Register16::IX), Operand::Register16(_)) => Some(4),
+            Instruction::Add(Operand::Register16(cpu::Register16::IY), Operand::Register16(_)) => Some(4),
+            Instruction::Add(_, _) => None,
+            Instruction::And(Operand::Register8(cpu::Register8::IXH)) => Some(2),
+            Instruction::And(Operand::Register8(cpu::Register8::IXL)) => Some(2),
+            Instruction::And(Operand::Register8(cpu::Register8::IYH)) => Some(2),
+            Instruction::And(Operand::Register8(cpu::Register8::IYL)) => Some(2),
+            Instruction::And(Operand::Register8(_)) => Some(1),
+            Instruction::And(Operand::Immediate8(_)) => Some(2),
+            Instruction::And(Operand::RegisterIndirect(cpu::Register16::HL)) => Some(2),
+            Instruction::And(Operand::Indexed(_, _)) => Some(5),
+            Instruction::And(_) => None,
+            Instruction::Bit(_, Operand::Register8(_)) => Some(2),
+            Instruction::Bit(_, Operand::RegisterIndirect(cpu::Register16::HL)) => Some(3),
+            Instruction::Bit(_, Operand::Indexed(_, _)) => Some(6), // TODO: check why this is not 5 as per official docs
+            Instruction::Bit(_, _) => None,
+            Instruction::Call(JumpTest::Unconditional, _) => Some(5),
+            Instruction::Call(JumpTest::NonZero, _) => Some(3),
+            Instruction::Call(JumpTest::Zero, _) => Some(5),
+            Instruction::Call(JumpTest::NoCarry, _) => Some(3),
+            Instruction::Call(JumpTest::Carry, _) => Some(5),
+            Instruction::Call(JumpTest::ParityOdd, _) => Some(3),
+            Instruction::Call(JumpTest::ParityEven, _) => Some(5),
+            Instruction::Call(JumpTest::SignPositive, _) => Some(3),
+            Instruction::Call(JumpTest::SignNegative, _) => Some(5),
+            Instruction::Ccf => Some(1),
+            Instruction::Cp(Operand::Register8(_)) => Some(1),
+            Instruction::Cp(Operand::Immediate8(_)) => Some(2),
+            Instruction::Cp(Operand::RegisterIndirect(cpu::Register16::HL)) => Some(2),
+            Instruction::Cp(Operand::Indexed(_, _)) => Some(5),
+            Instruction::Cp(_) => None,
+            Instruction::Cpd => Some(5),
+            Instruction::Cpdr => Some(6),
+            Instruction::Cpi => Some(5),
+            Instruction::Cpir => Some(6),
+            Instruction::Cpl => Some(1),
+            Instruction::Daa => Some(1),
+            Instruction::Dec(Operand::Register8(_)) => Some(1),
+            Instruction::Dec(Operand::RegisterIndirect(cpu::Register16::HL)) => Some(3),
+            Instruction::Dec(Operand::Indexed(_, _)) => Some(6),
+            Instruction::Dec(Operand::Register16(cpu::Register16::IX)) => Some(3),
+            Instruction::Dec(Operand::Register16(cpu::Register16::IY)) => Some(3),
+            Instruction::Dec(Operand::Register16(_)) => Some(2),
+            Instruction::Dec(_) => None,
+            Instruction::Defb(_) => Some(1),
+            Instruction::Defw(_) => Some(2),
+            Instruction::Di => Some(1),
+            Instruction::Djnz(_) => Some(3),
+            Instruction::Ei => Some(1),
             Instruction::Ex(
                 Operand::Register16(cpu::Register16::AF),
                 Operand::Register16(cpu::Register16::AF),
-            ) => 1,
+            ) => Some(1),
             Instruction::Ex(
                 Operand::Register16(cpu::Register16::DE),
                 Operand::Register16(cpu::Register16::HL),
-            ) => 1,
+            ) => Some(1),
             Instruction::Ex(
                 Operand::RegisterIndirect(cpu::Register16::SP),
                 Operand::Register16(cpu::Register16::HL),
-            ) => 6,
+            ) => Some(6),
             Instruction::Ex(
                 Operand::RegisterIndirect(cpu::Register16::SP),
                 Operand::Register16(cpu::Register16::IX),
-            ) => 7,
+            ) => Some(7),
             Instruction::Ex(
                 Operand::RegisterIndirect(cpu::Register16::SP),
                 Operand::Register16(cpu::Register16::IY),
-            ) => 7,
-            Instruction::Ex(_, _) => {
-                println!("No timing for {}", self);
-                unimplemented!()
-            }
-            Instruction::Exx => 1,
-            Instruction::Halt => 1,
-            Instruction::Im(_) => 2,
-            Instruction::In(_, Operand::RegisterIndirect(cpu::Register16::BC)) => 4,
-            Instruction::In(Operand::Register8(_), _) => 3,
-            Instruction::In(_, _) => {
-                println!("No timing for {}", self);
-                unimplemented!()
-            }
-            Instruction::Inc(Operand::Register8(_)) => 1,
-            Instruction::Inc(Operand::RegisterIndirect(cpu::Register16::HL)) => 3,
-            Instruction::Inc(Operand::Indexed(_, _)) => 6,
-            Instruction::Inc(Operand::Register16(cpu::Register16::IX)) => 3,
-            Instruction::Inc(Operand::Register16(cpu::Register16::IY)) => 3,
-            Instruction::Inc(Operand::Register16(_)) => 2,
-            Instruction::Inc(_) => {
-                println!("No timing for {}", self);
-                unimplemented!()
-            }
-            Instruction::Ind => 5,
-            Instruction::Indr => 6,
-            Instruction::Ini => 5,
-            Instruction::Inir => 6,
+            ) => Some(7),
+            Instruction::Ex(_, _) => None,
+            Instruction::Exx => Some(1),
+            Instruction::Halt => Some(1),
+            Instruction::Im(_) => Some(2),
+            Instruction::In(_, Operand::RegisterIndirect(cpu::Register16::BC)) => Some(4),
+            Instruction::In(Operand::Register8(_), _) => Some(3),
+            Instruction::In(_, _) => None,
+            Instruction::Inc(Operand::Register8(_)) => Some(1),
+            Instruction::Inc(Operand::RegisterIndirect(cpu::Register16::HL)) => Some(3),
+            Instruction::Inc(Operand::Indexed(_, _)) => Some(6),
+            Instruction::Inc(Operand::Register16(cpu::Register16::IX)) => Some(3),
+            Instruction::Inc(Operand::Register16(cpu::Register16::IY)) => Some(3),
+            Instruction::Inc(Operand::Register16(_)) => Some(2),
+            Instruction::Inc(_) => None,
+            Instruction::Ind => Some(5),
+            Instruction::Indr => Some(6),
+            Instruction::Ini => Some(5),
+            Instruction::Inir => Some(6),
             Instruction::Jp(
                 JumpTest::Unconditional,
                 Operand::RegisterIndirect(cpu::Register16::HL),
-            ) => 1,
+            ) => Some(1),
             Instruction::Jp(
                 JumpTest::Unconditional,
                 Operand::RegisterIndirect(cpu::Register16::IX),
-            ) => 2,
+            ) => Some(2),
             Instruction::Jp(
                 JumpTest::Unconditional,
                 Operand::RegisterIndirect(cpu::Register16::IY),
-            ) => 2,
-            Instruction::Jp(_, Operand::Immediate16(_)) => 3,
-            Instruction::Jp(_, _) => {
-                println!("No timing for {}", self);
-                unimplemented!()
-            }
-            Instruction::Jr(JumpTest::Unconditional, _) => 3,
-            Instruction::Jr(JumpTest::NonZero, _) => 3,
-            Instruction::Jr(JumpTest::Zero, _) => 3,
-            Instruction::Jr(JumpTest::NoCarry, _) => 3,
-            Instruction::Jr(JumpTest::Carry, _) => 3,
-            Instruction::Jr(_, _) => {
-                println!("No timing for {}", self);
-                unimplemented!()
-            }
+            ) => Some(2),
+            Instruction::Jp(_, Operand::Immediate16(_)) => Some(3),
+            Instruction::Jp(_, _) => None,
+            Instruction::Jr(JumpTest::Unconditional, _) => Some(3),
+            Instruction::Jr(JumpTest::NonZero, _) => Some(3),
+            Instruction::Jr(JumpTest::Zero, _) => Some(3),
+            Instruction::Jr(JumpTest::NoCarry, _) => Some(3),
+            Instruction::Jr(JumpTest::Carry, _) => Some(3),
+            Instruction::Jr(_, _) => None,
             Instruction::Ld(
                 Operand::Register8(cpu::Register8::A),
                 Operand::Register8(cpu::Register8::I),
-            ) => 3,
+            ) => Some(3),
             Instruction::Ld(
                 Operand::Register8(cpu::Register8::I),
                 Operand::Register8(cpu::Register8::A),
-            ) => 3,
+            ) => Some(3),
             Instruction::Ld(
                 Operand::Register8(cpu::Register8::A),
                 Operand::Register8(cpu::Register8::R),
-            ) => 3,
+            ) => Some(3),
             Instruction::Ld(
                 Operand::Register8(cpu::Register8::R),
                 Operand::Register8(cpu::Register8::A),
-            ) => 3,
-            Instruction::Ld(Operand::Register8(_), Operand::Register8(cpu::Register8::IXL)) => 2,
-            Instruction::Ld(Operand::Register8(cpu::Register8::IXL), Operand::Register8(_)) => 2,
-            Instruction::Ld(Operand::Register8(_), Operand::Register8(cpu::Register8::IXH)) => 2,
-            Instruction::Ld(Operand::Register8(cpu::Register8::IXH), Operand::Register8(_)) => 2,
-            Instruction::Ld(Operand::Register8(_), Operand::Register8(cpu::Register8::IYL)) => 2,
-            Instruction::Ld(Operand::Register8(cpu::Register8::IYL), Operand::Register8(_)) => 2,
-            Instruction::Ld(Operand::Register8(_), Operand::Register8(cpu::Register8::IYH)) => 2,
-            Instruction::Ld(Operand::Register8(cpu::Register8::IYH), Operand::Register8(_)) => 2,
-            Instruction::Ld(Operand::Register8(_), Operand::Register8(_)) => 1,
+            ) => Some(3),
+            Instruction::Ld(Operand::Register8(_), Operand::Register8(cpu::Register8::IXL)) => Some(2),
+            Instruction::Ld(Operand::Register8(cpu::Register8::IXL), Operand::Register8(_)) => Some(2),
+            Instruction::Ld(Operand::Register8(_), Operand::Register8(cpu::Register8::IXH)) => Some(2),
+            Instruction::Ld(Operand::Register8(cpu::Register8::IXH), Operand::Register8(_)) => Some(2),
+            Instruction::Ld(Operand::Register8(_), Operand::Register8(cpu::Register8::IYL)) => Some(2),
+            Instruction::Ld(Operand::Register8(cpu::Register8::IYL), Operand::Register8(_)) => Some(2),
+            Instruction::Ld(Operand::Register8(_), Operand::Register8(cpu::Register8::IYH)) => Some(2),
+            Instruction::Ld(Operand::Register8(cpu::Register8::IYH), Operand::Register8(_)) => Some(2),
+            Instruction::Ld(Operand::Register8(_), Operand::Register8(_)) => Some(1),
             Instruction::Ld(
                 Operand::Register8(cpu::Register8::A),
                 Operand::RegisterIndirect(cpu::Register16::BC),
-            ) => 2,
+            ) => Some(2),
             Instruction::Ld(
                 Operand::RegisterIndirect(cpu::Register16::BC),
                 Operand::Register8(cpu::Register8::A),
-            ) => 2,
+            ) => Some(2),
             Instruction::Ld(
                 Operand::Register8(cpu::Register8::A),
                 Operand::RegisterIndirect(cpu::Register16::DE),
-            ) => 2,
+            ) => Some(2),
             Instruction::Ld(
                 Operand::RegisterIndirect(cpu::Register16::DE),
                 Operand::Register8(cpu::Register8::A),
-            ) => 2,
+            ) => Some(2),
             Instruction::Ld(
                 Operand::Register8(_),
                 Operand::RegisterIndirect(cpu::Register16::HL),
-            ) => 2,
+            ) => Some(2),
             Instruction::Ld(
                 Operand::RegisterIndirect(cpu::Register16::HL),
                 Operand::Register8(_),
-            ) => 2,
-            Instruction::Ld(Operand::Register8(cpu::Register8::IXL), Operand::Immediate8(_)) => 3,
-            Instruction::Ld(Operand::Register8(cpu::Register8::IXH), Operand::Immediate8(_)) => 3,
-            Instruction::Ld(Operand::Register8(cpu::Register8::IYL), Operand::Immediate8(_)) => 3,
-            Instruction::Ld(Operand::Register8(cpu::Register8::IYH), Operand::Immediate8(_)) => 3,
-            Instruction::Ld(Operand::Register8(_), Operand::Immediate8(_)) => 2,
+            ) => Some(2),
+            Instruction::Ld(Operand::Register8(cpu::Register8::IXL), Operand::Immediate8(_)) => Some(3),
+            Instruction::Ld(Operand::Register8(cpu::Register8::IXH), Operand::Immediate8(_)) => Some(3),
+            Instruction::Ld(Operand::Register8(cpu::Register8::IYL), Operand::Immediate8(_)) => Some(3),
+            Instruction::Ld(Operand::Register8(cpu::Register8::IYH), Operand::Immediate8(_)) => Some(3),
+            Instruction::Ld(Operand::Register8(_), Operand::Immediate8(_)) => Some(2),
             Instruction::Ld(
                 Operand::RegisterIndirect(cpu::Register16::HL),
                 Operand::Immediate8(_),
-            ) => 3,
-            Instruction::Ld(Operand::Register16(cpu::Register16::IX), Operand::Immediate16(_)) => 4,
-            Instruction::Ld(Operand::Register16(cpu::Register16::IY), Operand::Immediate16(_)) => 4,
-            Instruction::Ld(Operand::Register16(_), Operand::Immediate16(_)) => 3,
-            Instruction::Ld(Operand::Register8(cpu::Register8::A), Operand::Direct16(_)) => 4,
-            Instruction::Ld(Operand::Direct16(_), Operand::Register8(cpu::Register8::A)) => 4,
-            Instruction::Ld(Operand::Register16(cpu::Register16::HL), Operand::Direct16(_)) => 5,
-            Instruction::Ld(Operand::Direct16(_), Operand::Register16(cpu::Register16::HL)) => 5,
-            Instruction::Ld(Operand::Register16(cpu::Register16::IX), Operand::Direct16(_)) => 5,
-            Instruction::Ld(Operand::Direct16(_), Operand::Register16(cpu::Register16::IX)) => 5,
-            Instruction::Ld(Operand::Register16(cpu::Register16::IY), Operand::Direct16(_)) => 5,
-            Instruction::Ld(Operand::Direct16(_), Operand::Register16(cpu::Register16::IY)) => 5,
-            Instruction::Ld(Operand::Register8(_), Operand::Indexed(_, _)) => 5,
-            Instruction::Ld(Operand::Indexed(_, _), Operand::Register8(_)) => 5,
-            Instruction::Ld(Operand::Indexed(_, _), Operand::Immediate8(_)) => 6,
-            Instruction::Ld(Operand::Register16(cpu::Register16::SP), Operand::Register16(cpu::Register16::HL)) => 2,
-            Instruction::Ld(_, _) => {
-                println!("No timing for {}", self);
-                unimplemented!()
-            }
+            ) => Some(3),
+            Instruction::Ld(Operand::Register16(cpu::Register16::IX), Operand::Immediate16(_)) => Some(4),
+            Instruction::Ld(Operand::Register16(cpu::Register16::IY), Operand::Immediate16(_)) => Some(4),
+            Instruction::Ld(Operand::Register16(_), Operand::Immediate16(_)) => Some(3),
+            Instruction::Ld(Operand::Register8(cpu::Register8::A), Operand::Direct16(_)) => Some(4),
+            Instruction::Ld(Operand::Direct16(_), Operand::Register8(cpu::Register8::A)) => Some(4),
+            Instruction::Ld(Operand::Register16(cpu::Register16::HL), Operand::Direct16(_)) => Some(5),
+            Instruction::Ld(Operand::Direct16(_), Operand::Register16(cpu::Register16::HL)) => Some(5),
+            Instruction::Ld(Operand::Register16(cpu::Register16::IX), Operand::Direct16(_)) => Some(5),
+            Instruction::Ld(Operand::Direct16(_), Operand::Register16(cpu::Register16::IX)) => Some(5),
+            Instruction::Ld(Operand::Register16(cpu::Register16::IY), Operand::Direct16(_)) => Some(5),
+            Instruction::Ld(Operand::Direct16(_), Operand::Register16(cpu::Register16::IY)) => Some(5),
+            Instruction::Ld(Operand::Register8(_), Operand::Indexed(_, _)) => Some(5),
+            Instruction::Ld(Operand::Indexed(_, _), Operand::Register8(_)) => Some(5),
+            Instruction::Ld(Operand::Indexed(_, _), Operand::Immediate8(_)) => Some(6),
+            Instruction::Ld(Operand::Register16(cpu::Register16::SP), Operand::Register16(cpu::Register16::HL)) => Some(2),
+            Instruction::Ld(_, _) => None,
             Instruction::LdDirect16(
                 Operand::Register16(cpu::Register16::IX),
                 Operand::Direct16(_),
-            ) => 6,
+            ) => Some(6),
             Instruction::LdDirect16(
                 Operand::Direct16(_),
                 Operand::Register16(cpu::Register16::IX),
-            ) => 6,
+            ) => Some(6),
             Instruction::LdDirect16(
                 Operand::Register16(cpu::Register16::IY),
                 Operand::Direct16(_),
-            ) => 6,
+            ) => Some(6),
             Instruction::LdDirect16(
                 Operand::Direct16(_),
                 Operand::Register16(cpu::Register16::IY),
-            ) => 6,
-            Instruction::LdDirect16(Operand::Register16(_), Operand::Direct16(_)) => 6,
-            Instruction::LdDirect16(Operand::Direct16(_), Operand::Register16(_)) => 6,
-            Instruction::LdDirect16(_, _) => {
-                println!("No timing for {}", self);
-                unimplemented!()
-            }
-            Instruction::Ldd => 5,
-            Instruction::Lddr => 6,
-            Instruction::Ldi => 5,
-            Instruction::Ldir => 6,
-            Instruction::Neg => 2,
-            Instruction::Nop => 4,
-            Instruction::Or(Operand::Register8(cpu::Register8::IXH)) => 2,
-            Instruction::Or(Operand::Register8(cpu::Register8::IXL)) => 2,
-            Instruction::Or(Operand::Register8(cpu::Register8::IYH)) => 2,
-            Instruction::Or(Operand::Register8(cpu::Register8::IYL)) => 2,
-            Instruction::Or(Operand::Register8(_)) => 1,
-            Instruction::Or(Operand::Immediate8(_)) => 2,
-            Instruction::Or(Operand::RegisterIndirect(cpu::Register16::HL)) => 2,
-            Instruction::Or(Operand::Indexed(_, _)) => 5,
-            Instruction::Or(_) => {
-                println!("No timing for {}", self);
-                unimplemented!()
-            }
-            Instruction::Out(Operand::Direct8(_), Operand::Register8(cpu::Register8::A)) => 3,
+            ) => Some(6),
+            Instruction::LdDirect16(Operand::Register16(_), Operand::Direct16(_)) => Some(6),
+            Instruction::LdDirect16(Operand::Direct16(_), Operand::Register16(_)) => Some(6),
+            Instruction::LdDirect16(_, _) => None,
+            Instruction::Ldd => Some(5),
+            Instruction::Lddr => Some(6),
+            Instruction::Ldi => Some(5),
+            Instruction::Ldir => Some(6),
+            Instruction::Neg => Some(2),
+            Instruction::Nop => Some(1),
+            Instruction::Or(Operand::Register8(cpu::Register8::IXH)) => Some(2),
+            Instruction::Or(Operand::Register8(cpu::Register8::IXL)) => Some(2),
+            Instruction::Or(Operand::Register8(cpu::Register8::IYH)) => Some(2),
+            Instruction::Or(Operand::Register8(cpu::Register8::IYL)) => Some(2),
+            Instruction::Or(Operand::Register8(_)) => Some(1),
+            Instruction::Or(Operand::Immediate8(_)) => Some(2),
+            Instruction::Or(Operand::RegisterIndirect(cpu::Register16::HL)) => Some(2),
+            Instruction::Or(Operand::Indexed(_, _)) => Some(5),
+            Instruction::Or(_) => None,
+            Instruction::Out(Operand::Direct8(_), Operand::Register8(cpu::Register8::A)) => Some(3),
             Instruction::Out(
                 Operand::RegisterIndirect(cpu::Register16::BC),
                 Operand::Register8(_),
-            ) => 4,
+            ) => Some(4),
             Instruction::Out(
                 Operand::RegisterIndirect(cpu::Register16::BC),
                 Operand::Immediate8(_),
-            ) => 4,
-            Instruction::Out(_, _) => {
-                println!("No timing for {}", self);
-                unimplemented!()
-            }
-            Instruction::Otdr => 6,
-            Instruction::Otir => 6,
-            Instruction::Outd => 5,
-            Instruction::Outi => 5,
-            Instruction::Pop(Operand::Register16(cpu::Register16::IX)) => 5,
-            Instruction::Pop(Operand::Register16(cpu::Register16::IY)) => 5,
-            Instruction::Pop(Operand::Register16(_)) => 3,
-            Instruction::Pop(_) => {
-                println!("No timing for {}", self);
-                unimplemented!()
-            }
-            Instruction::Push(Operand::Register16(cpu::Register16::IX)) => 5,
-            Instruction::Push(Operand::Register16(cpu::Register16::IY)) => 5,
-            Instruction::Push(Operand::Register16(_)) => 4,
-            Instruction::Push(_) => {
-                println!("No timing for {}", self);
-                unimplemented!()
-            }
-            Instruction::Res(_, _, Operand::Register8(_)) => 2,
-            Instruction::Res(_, _, Operand::RegisterIndirect(cpu::Register16::HL)) => 4,
-            Instruction::Res(_, _, Operand::Indexed(_, _)) => 7,
-            Instruction::Res(_, _, _) => {
-                println!("No timing for {}", self);
-                unimplemented!()
-            }
-            Instruction::Ret(JumpTest::Unconditional) => 3,
-            Instruction::Ret(JumpTest::NonZero) => 2,
-            Instruction::Ret(JumpTest::Zero) => 4,
-            Instruction::Ret(JumpTest::NoCarry) => 2,
-            Instruction::Ret(JumpTest::Carry) => 4,
-            Instruction::Ret(JumpTest::ParityOdd) => 2,
-            Instruction::Ret(JumpTest::ParityEven) => 4,
-            Instruction::Ret(JumpTest::SignPositive) => 2,
-            Instruction::Ret(JumpTest::SignNegative) => 4,
-            Instruction::Reti => 4,
-            Instruction::Retn => 4,
-            Instruction::Rl(_, Operand::Register8(_)) => 2,
-            Instruction::Rl(_, Operand::RegisterIndirect(cpu::Register16::HL)) => 4,
-            Instruction::Rl(_, Operand::Indexed(_, _)) => 7,
-            Instruction::Rl(_, _) => {
-                println!("No timing for {}", self);
-                unimplemented!()
-            }
-            Instruction::Rla => 1,
-            Instruction::Rlc(_, Operand::Register8(_)) => 2,
-            Instruction::Rlc(_, Operand::RegisterIndirect(cpu::Register16::HL)) => 4,
-            Instruction::Rlc(_, Operand::Indexed(_, _)) => 7,
-            Instruction::Rlc(_, _) => {
-                println!("No timing for {}", self);
-                unimplemented!()
-            }
-            Instruction::Rlca => 1,
-            Instruction::Rld => 5,
-            Instruction::Rr(_, Operand::Register8(_)) => 2,
-            Instruction::Rr(_, Operand::RegisterIndirect(cpu::Register16::HL)) => 4,
-            Instruction::Rr(_, Operand::Indexed(_, _)) => 7,
-            Instruction::Rr(_, _) => {
-                println!("No timing for {}", self);
-                unimplemented!()
-            }
-            Instruction::Rra => 1,
-            Instruction::Rrc(_, Operand::Register8(_)) => 2,
-            Instruction::Rrc(_, Operand::RegisterIndirect(cpu::Register16::HL)) => 4,
-            Instruction::Rrc(_, Operand::Indexed(_, _)) => 7,
-            Instruction::Rrc(_, _) => {
-                println!("No timing for {}", self);
-                unimplemented!()
-            }
-            Instruction::Rrca => 1,
-            Instruction::Rrd => 5,
-            Instruction::Rst(Operand::Immediate8(_)) => 4,
-            Instruction::Rst(_) => {
-                println!("No timing for {}", self);
-                unimplemented!()
-            }
+            ) => Some(4),
+            Instruction::Out(_, _) => None,
+            Instruction::Otdr => Some(6),
+            Instruction::Otir => Some(6),
+            Instruction::Outd => Some(5),
+            Instruction::Outi => Some(5),
+            Instruction::Pop(Operand::Register16(cpu::Register16::IX)) => Some(5),
+            Instruction::Pop(Operand::Register16(cpu::Register16::IY)) => Some(5),
+            Instruction::Pop(Operand::Register16(_)) => Some(3),
+            Instruction::Pop(_) => None,
+            Instruction::Push(Operand::Register16(cpu::Register16::IX)) => Some(5),
+            Instruction::Push(Operand::Register16(cpu::Register16::IY)) => Some(5),
+            Instruction::Push(Operand::Register16(_)) => Some(4),
+            Instruction::Push(_) => None,
+            Instruction::Res(_, _, Operand::Register8(_)) => Some(2),
+            Instruction::Res(_, _, Operand::RegisterIndirect(cpu::Register16::HL)) => Some(4),
+            Instruction::Res(_, _, Operand::Indexed(_, _)) => Some(7),
+            Instruction::Res(_, _, _) => None,
+            Instruction::Ret(JumpTest::Unconditional) => Some(3),
+            Instruction::Ret(JumpTest::NonZero) => Some(2),
+            Instruction::Ret(JumpTest::Zero) => Some(4),
+            Instruction::Ret(JumpTest::NoCarry) => Some(2),
+            Instruction::Ret(JumpTest::Carry) => Some(4),
+            Instruction::Ret(JumpTest::ParityOdd) => Some(2),
+            Instruction::Ret(JumpTest::ParityEven) => Some(4),
+            Instruction::Ret(JumpTest::SignPositive) => Some(2),
+            Instruction::Ret(JumpTest::SignNegative) => Some(4),
+            Instruction::Reti => Some(4),
+            Instruction::Retn => Some(4),
+            Instruction::Rl(_, Operand::Register8(_)) => Some(2),
+            Instruction::Rl(_, Operand::RegisterIndirect(cpu::Register16::HL)) => Some(4),
+            Instruction::Rl(_, Operand::Indexed(_, _)) => Some(7),
+            Instruction::Rl(_, _) => None,
+            Instruction::Rla => Some(1),
+            Instruction::Rlc(_, Operand::Register8(_)) => Some(2),
+            Instruction::Rlc(_, Operand::RegisterIndirect(cpu::Register16::HL)) => Some(4),
+            Instruction::Rlc(_, Operand::Indexed(_, _)) => Some(7),
+            Instruction::Rlc(_, _) => None,
+            Instruction::Rlca => Some(1),
+            Instruction::Rld => Some(5),
+            Instruction::Rr(_, Operand::Register8(_)) => Some(2),
+            Instruction::Rr(_, Operand::RegisterIndirect(cpu::Register16::HL)) => Some(4),
+            Instruction::Rr(_, Operand::Indexed(_, _)) => Some(7),
+            Instruction::Rr(_, _) => None,
+            Instruction::Rra => Some(1),
+            Instruction::Rrc(_, Operand::Register8(_)) => Some(2),
+            Instruction::Rrc(_, Operand::RegisterIndirect(cpu::Register16::HL)) => Some(4),
+            Instruction::Rrc(_, Operand::Indexed(_, _)) => Some(7),
+            Instruction::Rrc(_, _) => None,
+            Instruction::Rrca => Some(1),
+            Instruction::Rrd => Some(5),
+            Instruction::Rst(Operand::Immediate8(_)) => Some(4),
+            Instruction::Rst(_) => None,
             Instruction::Sbc(
                 Operand::Register8(cpu::Register8::A),
                 Operand::Register8(cpu::Register8::IXH),
-            ) => 2,
+            ) => Some(2),
             Instruction::Sbc(
                 Operand::Register8(cpu::Register8::A),
                 Operand::Register8(cpu::Register8::IXL),
-            ) => 2,
+            ) => Some(2),
             Instruction::Sbc(
                 Operand::Register8(cpu::Register8::A),
                 Operand::Register8(cpu::Register8::IYH),
-            ) => 2,
+            ) => Some(2),
             Instruction::Sbc(
                 Operand::Register8(cpu::Register8::A),
                 Operand::Register8(cpu::Register8::IYL),
-            ) => 2,
-            Instruction::Sbc(Operand::Register8(cpu::Register8::A), Operand::Register8(_)) => 1,
-            Instruction::Sbc(Operand::Register8(cpu::Register8::A), Operand::Immediate8(_)) => 2,
+            ) => Some(2),
+            Instruction::Sbc(Operand::Register8(cpu::Register8::A), Operand::Register8(_)) => Some(1),
+            Instruction::Sbc(Operand::Register8(cpu::Register8::A), Operand::Immediate8(_)) => Some(2),
             Instruction::Sbc(
                 Operand::Register8(cpu::Register8::A),
                 Operand::RegisterIndirect(cpu::Register16::HL),
-            ) => 2,
-            Instruction::Sbc(Operand::Register8(cpu::Register8::A), Operand::Indexed(_, _)) => 5,
-            Instruction::Sbc(Operand::Register16(cpu::Register16::HL), Operand::Register16(_)) => 4,
-            Instruction::Sbc(_, _) => {
-                println!("No timing for {}", self);
-                unimplemented!()
-            }
-            Instruction::Scf => 1,
-            Instruction::Set(_, _, Operand::Register8(_)) => 2,
-            Instruction::Set(_, _, Operand::RegisterIndirect(cpu::Register16::HL)) => 4,
-            Instruction::Set(_, _, Operand::Indexed(_, _)) => 7,
-            Instruction::Set(_, _, _) => {
-                println!("No timing for {}", self);
-                unimplemented!()
-            }
-            Instruction::Sla(_, Operand::Register8(_)) => 2,
-            Instruction::Sla(_, Operand::RegisterIndirect(cpu::Register16::HL)) => 4,
-            Instruction::Sla(_, Operand::Indexed(_, _)) => 7,
-            Instruction::Sla(_, _) => {
-                println!("No timing for {}", self);
-                unimplemented!()
-            }
-            Instruction::Sll(_, Operand::Register8(_)) => 2,
-            Instruction::Sll(_, Operand::RegisterIndirect(cpu::Register16::HL)) => 4,
-            Instruction::Sll(_, Operand::Indexed(_, _)) => 7,
-            Instruction::Sll(_, _) => {
-                println!("No timing for {}", self);
-                unimplemented!()
-            }
-            Instruction::Sra(_, Operand::Register8(_)) => 2,
-            Instruction::Sra(_, Operand::RegisterIndirect(cpu::Register16::HL)) => 4,
-            Instruction::Sra(_, Operand::Indexed(_, _)) => 7,
-            Instruction::Sra(_, _) => {
-                println!("No timing for {}", self);
-                unimplemented!()
-            }
-            Instruction::Srl(_, Operand::Register8(_)) => 2,
-            Instruction::Srl(_, Operand::RegisterIndirect(cpu::Register16::HL)) => 4,
-            Instruction::Srl(_, Operand::Indexed(_, _)) => 7,
-            Instruction::Srl(_, _) => {
-                println!("No timing for {}", self);
-                unimplemented!()
-            }
-            Instruction::Sub(Operand::Register8(cpu::Register8::IXH)) => 2,
-            Instruction::Sub(Operand::Register8(cpu::Register8::IXL)) => 2,
-            Instruction::Sub(Operand::Register8(cpu::Register8::IYH)) => 2,
-            Instruction::Sub(Operand::Register8(cpu::Register8::IYL)) => 2,
-            Instruction::Sub(Operand::Register8(_)) => 1,
-            Instruction::Sub(Operand::Immediate8(_)) => 2,
-            Instruction::Sub(Operand::RegisterIndirect(cpu::Register16::HL)) => 2,
-            Instruction::Sub(Operand::Indexed(_, _)) => 5,
-            Instruction::Sub(_) => {
-                println!("No timing for {}", self);
-                unimplemented!()
-            }
-            Instruction::Xor(Operand::Register8(cpu::Register8::IXH)) => 2,
-            Instruction::Xor(Operand::Register8(cpu::Register8::IXL)) => 2,
-            Instruction::Xor(Operand::Register8(cpu::Register8::IYH)) => 2,
-            Instruction::Xor(Operand::Register8(cpu::Register8::IYL)) => 2,
-            Instruction::Xor(Operand::Register8(_)) => 1,
-            Instruction::Xor(Operand::Immediate8(_)) => 2,
-            Instruction::Xor(Operand::RegisterIndirect(cpu::Register16::HL)) => 2,
-            Instruction::Xor(Operand::Indexed(_, _)) => 5,
-            Instruction::Xor(_) => {
-                println!("No timing for {}", self);
-                unimplemented!()
-            }
+            ) => Some(2),
+            Instruction::Sbc(Operand::Register8(cpu::Register8::A), Operand::Indexed(_, _)) => Some(5),
+            Instruction::Sbc(Operand::Register16(cpu::Register16::HL), Operand::Register16(_)) => Some(4),
+            Instruction::Sbc(_, _) => None,
+            Instruction::Scf => Some(1),
+            Instruction::Set(_, _, Operand::Register8(_)) => Some(2),
+            Instruction::Set(_, _, Operand::RegisterIndirect(cpu::Register16::HL)) => Some(4),
+            Instruction::Set(_, _, Operand::Indexed(_, _)) => Some(7),
+            Instruction::Set(_, _, _) => None,
+            Instruction::Sla(_, Operand::Register8(_)) => Some(2),
+            Instruction::Sla(_, Operand::RegisterIndirect(cpu::Register16::HL)) => Some(4),
+            Instruction::Sla(_, Operand::Indexed(_, _)) => Some(7),
+            Instruction::Sla(_, _) => None,
+            Instruction::Sll(_, Operand::Register8(_)) => Some(2),
+            Instruction::Sll(_, Operand::RegisterIndirect(cpu::Register16::HL)) => Some(4),
+            Instruction::Sll(_, Operand::Indexed(_, _)) => Some(7),
+            Instruction::Sll(_, _) => None,
+            Instruction::Sra(_, Operand::Register8(_)) => Some(2),
+            Instruction::Sra(_, Operand::RegisterIndirect(cpu::Register16::HL)) => Some(4),
+            Instruction::Sra(_, Operand::Indexed(_, _)) => Some(7),
+            Instruction::Sra(_, _) => None,
+            Instruction::Srl(_, Operand::Register8(_)) => Some(2),
+            Instruction::Srl(_, Operand::RegisterIndirect(cpu::Register16::HL)) => Some(4),
+            Instruction::Srl(_, Operand::Indexed(_, _)) => Some(7),
+            Instruction::Srl(_, _) => None,
+            Instruction::Sub(Operand::Register8(cpu::Register8::IXH)) => Some(2),
+            Instruction::Sub(Operand::Register8(cpu::Register8::IXL)) => Some(2),
+            Instruction::Sub(Operand::Register8(cpu::Register8::IYH)) => Some(2),
+            Instruction::Sub(Operand::Register8(cpu::Register8::IYL)) => Some(2),
+            Instruction::Sub(Operand::Register8(_)) => Some(1),
+            Instruction::Sub(Operand::Immediate8(_)) => Some(2),
+            Instruction::Sub(Operand::RegisterIndirect(cpu::Register16::HL)) => Some(2),
+            Instruction::Sub(Operand::Indexed(_, _)) => Some(5),
+            Instruction::Sub(_) => None,
+            Instruction::Xor(Operand::Register8(cpu::Register8::IXH)) => Some(2),
+            Instruction::Xor(Operand::Register8(cpu::Register8::IXL)) => Some(2),
+            Instruction::Xor(Operand::Register8(cpu::Register8::IYH)) => Some(2),
+            Instruction::Xor(Operand::Register8(cpu::Register8::IYL)) => Some(2),
+            Instruction::Xor(Operand::Register8(_)) => Some(1),
+            Instruction::Xor(Operand::Immediate8(_)) => Some(2),
+            Instruction::Xor(Operand::RegisterIndirect(cpu::Register16::HL)) => Some(2),
+            Instruction::Xor(Operand::Indexed(_, _)) => Some(5),
+            Instruction::Xor(_) => None,
         }
     }
 }
@@ -0,0 +1,202 @@
+use crate::key_mapper;
+use crate::memory::{self, Read};
+use crate::workbench;
+
+const WIDTH: usize = 464;
+const HEIGHT: usize = 160;
+const FONT_ADDRESS: usize = 0x3800; // character matrix table in the lower ROM
+const KEYBOARD_Y: usize = 56;
+const KEYPAD_X: usize = 352;
+const KEY_HEIGHT: usize = 18;
+const ROW_HEIGHT: usize = 20;
+
+// label, matrix line and bit, width in pixels
+type KeyCap = (&'static str, usize, u8, usize);
+
+// the main block of the CPC 464 keyboard, row by row
+const MAIN_ROWS: [&[KeyCap]; 5] = [
+    &[
+        ("ESC", 8, 2, 32), ("1", 8, 0, 20), ("2", 8, 1, 20), ("3", 7, 1, 20), ("4", 7, 0, 20), ("5", 6, 1, 20), ("6", 6, 0, 20),
+        ("7", 5, 1, 20), ("8", 5, 0, 20), ("9", 4, 1, 20), ("0", 4, 0, 20), ("-", 3, 1, 20), ("^", 3, 0, 20), ("CLR", 2, 0, 32),
+        ("DEL", 9, 7, 32),
+    ],
+    &[
+        ("TAB", 8, 4, 32), ("Q", 8, 3, 20), ("W", 7, 3, 20), ("E", 7, 2, 20), ("R", 6, 2, 20), ("T", 6, 3, 20), ("Y", 5, 3, 20),
+        ("U", 5, 2, 20), ("I", 4, 3, 20), ("O", 4, 2, 20), ("P", 3, 3, 20), ("@", 3, 2, 20), ("[", 2, 1, 20), ("RET", 2, 2, 64),
+    ],
+    &[
+        ("CAPS", 8, 6, 40), ("A", 8, 5, 20), ("S", 7, 4, 20), ("D", 7, 5, 20), ("F", 6, 5, 20), ("G", 6, 4, 20), ("H", 5, 4, 20),
+        ("J", 5, 5, 20), ("K", 4, 5, 20), ("L", 4, 4, 20), (":", 3, 5, 20), (";", 3, 4, 20), ("]", 2, 3, 20), ("RET", 2, 2, 56),
+    ],
+    &[
+        ("SHIFT", 2, 5, 48), ("\\", 2, 6, 20), ("Z", 8, 7, 20), ("X", 7, 7, 20), ("C", 7, 6, 20), ("V", 6, 7, 20), ("B", 6, 6, 20),
+        ("N", 5, 6, 20), ("M", 4, 6, 20), (",", 4, 7, 20), (".", 3, 7, 20), ("/", 3, 6, 20), ("SHIFT", 2, 5, 68),
+    ],
+    &[("CTRL", 2, 7, 48), ("COPY", 1, 1, 40), ("SPACE", 5, 7, 176), ("ENTER", 0, 6, 72)],
+];
+
+// function keys and cursor keys to the right of the main block
+const KEYPAD_ROWS: [&[KeyCap]; 5] = [
+    &[("F7", 1, 2, 32), ("F8", 1, 3, 32), ("F9", 0, 3, 32)],
+    &[("F4", 2, 4, 32), ("F5", 1, 4, 32), ("F6", 0, 4, 32)],
+    &[("F1", 1, 5, 32), ("F2", 1, 6, 32), ("F3", 0, 5, 32)],
+    &[("F0", 1, 7, 32), ("UP", 0, 0, 32), ("F.", 0, 7, 32)],
+    &[("LT", 1, 0, 32), ("DN", 0, 2, 32), ("RT", 0, 1, 32)],
+];
+
+pub enum ConfiguratorEvent {
+    None,
+    Closed,
+}
+
+struct KeyBox {
+    label: &'static str,
+    key: (usize, u8),
+    x: usize,
+    y: usize,
+    width: usize,
+}
+
+// assigns host keys to CPC keys: pressing a host key shows its raw code and the CPC key it is
+// mapped to, clicking a CPC key on the keyboard assigns it. Ctrl+S saves to the key map file
+pub struct KeyConfigurator {
+    window: minifb::Window,
+    boxes: Vec<KeyBox>,
+    host_key: Option<minifb::Key>, // the last host key pressed
+    mouse_down: bool,
+    message: String,
+    buffer: Vec<u32>,
+    font: memory::ROM,
+}
+
+impl KeyConfigurator {
+    pub fn open() -> Option<KeyConfigurator> {
+        let options = minifb::WindowOptions {
+            scale: minifb::Scale::X2,
+            ..minifb::WindowOptions::default()
+        };
+        let window = match minifb::Window::new("Key Configurator", WIDTH, HEIGHT, options) {
+            Ok(window) => window,
+            Err(error) => {
+                log::error!("Could not open the key configurator: {}", error);
+                return None;
+            }
+        };
+
+        let mut boxes = Vec::new();
+        for (rows, origin) in [(&MAIN_ROWS, 8), (&KEYPAD_ROWS, KEYPAD_X)].iter() {
+            for (row, caps) in rows.iter().enumerate() {
+                let mut x = *origin;
+                for (label, line, bit, width) in caps.iter() {
+                    boxes.push(KeyBox {
+                        label,
+                        key: (*line, *bit),
+                        x,
+                        y: KEYBOARD_Y + row * ROW_HEIGHT,
+                        width: width - 2,
+                    });
+                    x += width;
+                }
+            }
+        }
+
+        Some(KeyConfigurator {
+            window,
+            boxes,
+            host_key: None,
+            mouse_down: false,
+            message: "Ctrl+S saves, Escape closes.".to_string(),
+            buffer: vec![0; WIDTH * HEIGHT],
+            font: memory::ROM::font(),
+        })
+    }
+
+    pub fn update(&mut self, key_mapper: &mut key_mapper::KeyMapper) -> ConfiguratorEvent {
+        if !self.window.is_open() {
+            return ConfiguratorEvent::Closed;
+        }
+
+        let ctrl_down = self.window.is_key_down(minifb::Key::LeftCtrl) || self.window.is_key_down(minifb::Key::RightCtrl);
+        let keys = self.window.get_keys_pressed(minifb::KeyRepeat::No).unwrap_or_default();
+        for key in keys {
+            match key {
+                minifb::Key::Escape => return ConfiguratorEvent::Closed,
+                minifb::Key::S if ctrl_down => {
+                    self.message = match key_mapper.save_remaps() {
+                        Ok(filename) => format!("Saved to {}.", filename),
+                        Err(error) => format!("Could not save: {}", error),
+                    };
+                }
+                _ => self.host_key = Some(key),
+            }
+        }
+
+        // assign on the click, not while the button is held
+        let mouse_down = self.window.get_mouse_down(minifb::MouseButton::Left);
+        if mouse_down && !self.mouse_down {
+            let clicked = self.window.get_mouse_pos(minifb::MouseMode::Discard).and_then(|(x, y)| self.key_at(x as usize, y as usize));
+            if let (Some(host_key), Some((line, bit))) = (self.host_key, clicked) {
+                key_mapper.assign_key(host_key, line, bit);
+                self.message = format!("{:?} assigned, Ctrl+S saves.", host_key);
+            }
+        }
+        self.mouse_down = mouse_down;
+
+        self.draw(key_mapper);
+        if let Err(error) = self.window.update_with_buffer(&self.buffer, WIDTH, HEIGHT) {
+            log::error!("Could not update the key configurator: {}", error);
+        }
+
+        ConfiguratorEvent::None
+    }
+
+    fn key_at(&self, x: usize, y: usize) -> Option<(usize, u8)> {
+        self.boxes
+            .iter()
+            .find(|key_box| x >= key_box.x && x < key_box.x + key_box.width && y >= key_box.y && y < key_box.y + KEY_HEIGHT)
+            .map(|key_box| key_box.key)
+    }
+
+    fn draw(&mut self, key_mapper: &key_mapper::KeyMapper) {
+        let mapped = self.host_key.and_then(|key| key_mapper.lookup_key(key));
+        let mut lines = vec!["Press a host key, then click a CPC key.".to_string(), String::new()];
+        match self.host_key {
+            Some(key) => {
+                lines.push(format!("Host key: {:?} (code {})", key, key as usize));
+                let name = mapped.and_then(|(line, bit)| key_mapper::cpc_key_name(line, bit));
+                lines.push(format!("CPC key:  {}", name.unwrap_or_else(|| "none".to_string())));
+            }
+            None => lines.extend(vec![String::new(), String::new()]),
+        }
+        lines.push(String::new());
+        lines.push(self.message.clone());
+        workbench::draw_text(&self.font, &mut self.buffer, WIDTH, 1, &lines);
+
+        for key_box in &self.boxes {
+            let highlighted = mapped == Some(key_box.key);
+            let (background, foreground) = if highlighted { (0xffff00, 0x000080) } else { (0x000080, 0xffff00) };
+            for y in key_box.y..key_box.y + KEY_HEIGHT {
+                for x in key_box.x..key_box.x + key_box.width {
+                    let border = y == key_box.y || y == key_box.y + KEY_HEIGHT - 1 || x == key_box.x || x == key_box.x + key_box.width - 1;
+                    self.buffer[y * WIDTH + x] = if border { 0xffff00 } else { background };
+                }
+            }
+
+            let label_x = key_box.x + (key_box.width.saturating_sub(key_box.label.len() * 8)) / 2;
+            for (index, character) in key_box.label.chars().enumerate() {
+                draw_glyph(&self.font, &mut self.buffer, label_x + index * 8, key_box.y + 5, character, foreground);
+            }
+        }
+    }
+}
+
+fn draw_glyph(font: &memory::ROM, buffer: &mut [u32], x: usize, y: usize, character: char, color: u32) {
+    for row in 0..8 {
+        let bits = font.read_byte(FONT_ADDRESS + character as usize * 8 + row);
+        for column in 0..8 {
+            if bits & (0x80 >> column) != 0 {
+                buffer[(y + row) * WIDTH + x + column] = color;
+            }
+        }
+    }
+}
@@ -0,0 +1,904 @@
+use std::collections::HashMap;
+
+use crate::keyboard;
+
+const KEY_PRESS_MICROSECONDS: u64 = 40_000; // long enough for the firmware to notice, which scans every 20ms
+const KEY_RELEASE_MICROSECONDS: u64 = 60_000; // shorter gaps can fall between two scans and swallow repeated characters
+
+pub const DEFAULT_KEY_MAP_FILE: &str = "ronald.keymap"; // where the key configurator saves without a --keymap file
+
+const SHIFT: (usize, u8) = (2, 5);
+const CONTROL: (usize, u8) = (2, 7);
+
+// names used to refer to CPC keys in the key map file, e.g. "{RETURN}"
+const KEY_NAMES: [(&str, (usize, u8)); 27] = [
+    ("UP", (0, 0)),
+    ("RIGHT", (0, 1)),
+    ("DOWN", (0, 2)),
+    ("F9", (0, 3)),
+    ("F6", (0, 4)),
+    ("F3", (0, 5)),
+    ("ENTER", (0, 6)),
+    ("F.", (0, 7)),
+    ("LEFT", (1, 0)),
+    ("COPY", (1, 1)),
+    ("F7", (1, 2)),
+    ("F8", (1, 3)),
+    ("F5", (1, 4)),
+    ("F1", (1, 5)),
+    ("F2", (1, 6)),
+    ("F0", (1, 7)),
+    ("CLR", (2, 0)),
+    ("RETURN", (2, 2)),
+    ("F4", (2, 4)),
+    ("SHIFT", (2, 5)),
+    ("CONTROL", (2, 7)),
+    ("ESC", (8, 2)),
+    ("TAB", (8, 4)),
+    ("CAPSLOCK", (8, 6)),
+    ("DEL", (9, 7)),
+    ("SPACE", (5, 7)),
+    ("FIRE", (9, 5)),
+];
+
+const LETTER_KEYS: [(minifb::Key, char); 26] = [
+    (minifb::Key::A, 'A'),
+    (minifb::Key::B, 'B'),
+    (minifb::Key::C, 'C'),
+    (minifb::Key::D, 'D'),
+    (minifb::Key::E, 'E'),
+    (minifb::Key::F, 'F'),
+    (minifb::Key::G, 'G'),
+    (minifb::Key::H, 'H'),
+    (minifb::Key::I, 'I'),
+    (minifb::Key::J, 'J'),
+    (minifb::Key::K, 'K'),
+    (minifb::Key::L, 'L'),
+    (minifb::Key::M, 'M'),
+    (minifb::Key::N, 'N'),
+    (minifb::Key::O, 'O'),
+    (minifb::Key::P, 'P'),
+    (minifb::Key::Q, 'Q'),
+    (minifb::Key::R, 'R'),
+    (minifb::Key::S, 'S'),
+    (minifb::Key::T, 'T'),
+    (minifb::Key::U, 'U'),
+    (minifb::Key::V, 'V'),
+    (minifb::Key::W, 'W'),
+    (minifb::Key::X, 'X'),
+    (minifb::Key::Y, 'Y'),
+    (minifb::Key::Z, 'Z'),
+];
+
+// every host key, named as in the key map file, e.g. "map Semicolon = {COPY}"
+const HOST_KEYS: [minifb::Key; 106] = [
+    minifb::Key::Key0, minifb::Key::Key1, minifb::Key::Key2, minifb::Key::Key3, minifb::Key::Key4, minifb::Key::Key5,
+    minifb::Key::Key6, minifb::Key::Key7, minifb::Key::Key8, minifb::Key::Key9, minifb::Key::A, minifb::Key::B,
+    minifb::Key::C, minifb::Key::D, minifb::Key::E, minifb::Key::F, minifb::Key::G, minifb::Key::H, minifb::Key::I,
+    minifb::Key::J, minifb::Key::K, minifb::Key::L, minifb::Key::M, minifb::Key::N, minifb::Key::O, minifb::Key::P,
+    minifb::Key::Q, minifb::Key::R, minifb::Key::S, minifb::Key::T, minifb::Key::U, minifb::Key::V, minifb::Key::W,
+    minifb::Key::X, minifb::Key::Y, minifb::Key::Z, minifb::Key::F1, minifb::Key::F2, minifb::Key::F3, minifb::Key::F4,
+    minifb::Key::F5, minifb::Key::F6, minifb::Key::F7, minifb::Key::F8, minifb::Key::F9, minifb::Key::F10,
+    minifb::Key::F11, minifb::Key::F12, minifb::Key::F13, minifb::Key::F14, minifb::Key::F15, minifb::Key::Down,
+    minifb::Key::Left, minifb::Key::Right, minifb::Key::Up, minifb::Key::Apostrophe, minifb::Key::Backquote,
+    minifb::Key::Backslash, minifb::Key::Comma, minifb::Key::Equal, minifb::Key::LeftBracket, minifb::Key::Minus,
+    minifb::Key::Period, minifb::Key::RightBracket, minifb::Key::Semicolon, minifb::Key::Slash, minifb::Key::Backspace,
+    minifb::Key::Delete, minifb::Key::End, minifb::Key::Enter, minifb::Key::Escape, minifb::Key::Home,
+    minifb::Key::Insert, minifb::Key::Menu, minifb::Key::PageDown, minifb::Key::PageUp, minifb::Key::Pause,
+    minifb::Key::Space, minifb::Key::Tab, minifb::Key::NumLock, minifb::Key::CapsLock, minifb::Key::ScrollLock,
+    minifb::Key::LeftShift, minifb::Key::RightShift, minifb::Key::LeftCtrl, minifb::Key::RightCtrl,
+    minifb::Key::NumPad0, minifb::Key::NumPad1, minifb::Key::NumPad2, minifb::Key::NumPad3, minifb::Key::NumPad4,
+    minifb::Key::NumPad5, minifb::Key::NumPad6, minifb::Key::NumPad7, minifb::Key::NumPad8, minifb::Key::NumPad9,
+    minifb::Key::NumPadDot, minifb::Key::NumPadSlash, minifb::Key::NumPadAsterisk, minifb::Key::NumPadMinus,
+    minifb::Key::NumPadPlus, minifb::Key::NumPadEnter, minifb::Key::LeftAlt, minifb::Key::RightAlt,
+    minifb::Key::LeftSuper, minifb::Key::RightSuper,
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum KeyboardMode {
+    Positional, // host keys press the CPC key in the same place, for games
+    Symbolic,   // host keys type the character printed on them, for typing
+}
+
+impl KeyboardMode {
+    pub fn from_name(name: &str) -> Option<KeyboardMode> {
+        match name {
+            "positional" => Some(KeyboardMode::Positional),
+            "symbolic" => Some(KeyboardMode::Symbolic),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            KeyboardMode::Positional => "positional",
+            KeyboardMode::Symbolic => "symbolic",
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct KeyStroke {
+    line: usize,
+    bit: u8,
+    shift: bool,
+}
+
+pub struct KeyMapper {
+    macros: HashMap<minifb::Key, Vec<KeyStroke>>,
+    sticky_modifiers: bool, // modifiers stay down until the next key has been released
+    auto_repeat: Option<keyboard::AutoRepeat>, // holding a host key holds and repeats the CPC key, None only types it once
+    one_handed: bool, // holding space mirrors the keyboard (half-QWERTY)
+    pressed_keys: HashMap<minifb::Key, Option<KeyStroke>>, // None if the release is already queued
+    latched_modifiers: Vec<(usize, u8)>,
+    mode: KeyboardMode,
+    host_shift: bool, // held on the host keyboard, which only reaches the CPC directly in positional mode
+    mirroring: bool,
+    mirrored_key_typed: bool,
+    language: keyboard::Language,
+    remaps: HashMap<minifb::Key, (usize, u8)>, // host keys assigned to other CPC keys than their default
+    filename: Option<String>, // for reloading
+}
+
+impl Default for KeyMapper {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl KeyMapper {
+    pub fn new() -> KeyMapper {
+        KeyMapper {
+            macros: HashMap::new(),
+            sticky_modifiers: false,
+            auto_repeat: Some(keyboard::AutoRepeat::default()),
+            one_handed: false,
+            pressed_keys: HashMap::new(),
+            latched_modifiers: Vec::new(),
+            mode: KeyboardMode::Positional,
+            host_shift: false,
+            mirroring: false,
+            mirrored_key_typed: false,
+            language: keyboard::Language::English,
+            remaps: HashMap::new(),
+            filename: None,
+        }
+    }
+
+    pub fn load(filename: &str) -> std::io::Result<KeyMapper> {
+        let contents = std::fs::read_to_string(filename)?;
+        let mut key_mapper = KeyMapper::new();
+
+        for line in contents.lines() {
+            let line = line.trim_start();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (hotkey, text) = match line.split_once('=') {
+                Some((hotkey, text)) => (hotkey.trim(), text.trim_start()),
+                None => return Err(invalid_data(format!("Missing \"=\" in key map line \"{}\".", line))),
+            };
+
+            match hotkey {
+                "sticky_modifiers" => key_mapper.sticky_modifiers = parse_switch(text)?,
+                "auto_repeat" => key_mapper.auto_repeat = parse_auto_repeat(text)?,
+                "one_handed" => key_mapper.one_handed = parse_switch(text)?,
+                _ if hotkey.starts_with("map ") => {
+                    let name = hotkey["map ".len()..].trim();
+                    let key = match parse_host_key(name) {
+                        Some(key) => key,
+                        None => return Err(invalid_data(format!("Unknown host key \"{}\".", name))),
+                    };
+                    match parse_macro(text.trim())?.as_slice() {
+                        [stroke] => key_mapper.remaps.insert(key, (stroke.line, stroke.bit)),
+                        _ => return Err(invalid_data(format!("Host key {} has to map to a single CPC key.", name))),
+                    };
+                }
+                "mode" => {
+                    key_mapper.mode = match KeyboardMode::from_name(text.trim()) {
+                        Some(mode) => mode,
+                        None => return Err(invalid_data(format!("Unknown keyboard mode \"{}\".", text.trim()))),
+                    }
+                }
+                _ => {
+                    let hotkey = match parse_hotkey(hotkey) {
+                        Some(hotkey) => hotkey,
+                        None => return Err(invalid_data(format!("Unknown hotkey \"{}\".", hotkey))),
+                    };
+
+                    key_mapper.macros.insert(hotkey, parse_macro(text)?);
+                }
+            }
+        }
+
+        key_mapper.filename = Some(filename.to_string());
+        Ok(key_mapper)
+    }
+
+    pub fn get_filename(&self) -> Option<&str> {
+        self.filename.as_deref()
+    }
+
+    pub fn assign_key(&mut self, key: minifb::Key, line: usize, bit: u8) {
+        self.remaps.insert(key, (line, bit));
+    }
+
+    pub fn lookup_key(&self, key: minifb::Key) -> Option<(usize, u8)> {
+        // the CPC key pressed in positional mode
+        self.map_key(key)
+    }
+
+    pub fn save_remaps(&mut self) -> std::io::Result<String> {
+        // replaces the map lines of the key map file, the other settings and the macros stay as they are written
+        let filename = self.filename.clone().unwrap_or_else(|| DEFAULT_KEY_MAP_FILE.to_string());
+        let contents = match std::fs::read_to_string(&filename) {
+            Ok(contents) => contents,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(error) => return Err(error),
+        };
+
+        let mut lines: Vec<String> = contents
+            .lines()
+            .filter(|line| !line.trim_start().starts_with("map "))
+            .map(|line| line.to_string())
+            .collect();
+        let mut remaps: Vec<String> = self
+            .remaps
+            .iter()
+            .filter_map(|(key, (line, bit))| cpc_key_name(*line, *bit).map(|name| format!("map {:?} = {}", key, name)))
+            .collect();
+        remaps.sort();
+        lines.extend(remaps);
+        std::fs::write(&filename, lines.join("\n") + "\n")?;
+        self.filename = Some(filename.clone());
+
+        Ok(filename)
+    }
+
+    pub fn reload(&mut self, keyboard: &mut keyboard::Keyboard) -> std::io::Result<()> {
+        // the settings and macros come from the file again, keys held across the reload are released
+        let filename = match &self.filename {
+            Some(filename) => filename.clone(),
+            None => return Ok(()),
+        };
+        let mut key_mapper = KeyMapper::load(&filename)?;
+        key_mapper.language = self.language;
+        keyboard.reset_all();
+        keyboard.set_auto_repeat(key_mapper.auto_repeat);
+        *self = key_mapper;
+
+        Ok(())
+    }
+
+    pub fn print_bindings(&self) {
+        println!("Sticky modifiers: {}", self.sticky_modifiers);
+        println!("Auto repeat: {}", self.auto_repeat.map_or("off".to_string(), |auto_repeat| auto_repeat.name()));
+        println!("One-handed mode: {}", self.one_handed);
+        println!("Keyboard mode: {}", self.mode.name());
+
+        let mut hotkeys: Vec<_> = self.macros.iter().map(|(hotkey, strokes)| (format!("{:?}", hotkey), strokes.len())).collect();
+        hotkeys.sort();
+        for (hotkey, num_strokes) in hotkeys {
+            println!("{}: macro with {} key strokes", hotkey, num_strokes);
+        }
+
+        let mut remaps: Vec<_> = self.remaps.iter().map(|(key, (line, bit))| (format!("{:?}", key), cpc_key_name(*line, *bit))).collect();
+        remaps.sort();
+        for (key, name) in remaps {
+            println!("{}: mapped to {}", key, name.unwrap_or_else(|| "?".to_string()));
+        }
+    }
+
+    pub fn set_language(&mut self, language: keyboard::Language) {
+        // host keys are mapped by their label, so they have to follow the layout of the localized keyboard
+        self.language = language;
+    }
+
+    pub fn set_auto_repeat(&mut self, auto_repeat: Option<keyboard::AutoRepeat>, keyboard: &mut keyboard::Keyboard) {
+        // the key map decides whether keys are held, the keyboard repeats them in the matrix
+        self.auto_repeat = auto_repeat;
+        keyboard.set_auto_repeat(auto_repeat);
+    }
+
+    pub fn get_auto_repeat(&self) -> Option<keyboard::AutoRepeat> {
+        self.auto_repeat
+    }
+
+    pub fn set_mode(&mut self, mode: KeyboardMode, keyboard: &mut keyboard::Keyboard) {
+        // keys held across the switch would be released as different CPC keys
+        keyboard.reset_all();
+        self.pressed_keys.clear();
+        self.latched_modifiers.clear();
+        self.mode = mode;
+    }
+
+    pub fn get_mode(&self) -> KeyboardMode {
+        self.mode
+    }
+
+    pub fn toggle_mode(&mut self, keyboard: &mut keyboard::Keyboard) -> KeyboardMode {
+        let mode = match self.mode {
+            KeyboardMode::Positional => KeyboardMode::Symbolic,
+            KeyboardMode::Symbolic => KeyboardMode::Positional,
+        };
+        self.set_mode(mode, keyboard);
+
+        mode
+    }
+
+    pub fn press_key(&mut self, key: minifb::Key, delay_microseconds: u64, keyboard: &mut keyboard::Keyboard) {
+        // the delay places the key within the next emulated frame like it was pressed on the host
+        let delay = keyboard.delay_from_now(delay_microseconds);
+        if let Some(strokes) = self.macros.get(&key) {
+            queue_key_strokes(keyboard, delay, strokes);
+            return;
+        }
+
+        if matches!(key, minifb::Key::LeftShift | minifb::Key::RightShift) {
+            self.host_shift = true;
+            if self.mode == KeyboardMode::Symbolic {
+                return; // every symbol brings its own shift state
+            }
+        }
+
+        if self.one_handed && key == minifb::Key::Space {
+            self.mirroring = true;
+            self.mirrored_key_typed = false;
+            return;
+        }
+
+        let mapped_key = if self.mirroring {
+            self.mirrored_key_typed = true;
+            mirror_key(key)
+        } else {
+            key
+        };
+
+        let stroke = match self.mode {
+            KeyboardMode::Positional => self.map_key(mapped_key).map(|(line, bit)| KeyStroke { line, bit, shift: false }),
+            KeyboardMode::Symbolic => self.map_symbol(mapped_key),
+        };
+        let stroke = match stroke {
+            Some(stroke) => stroke,
+            None => return,
+        };
+        let (line, bit) = (stroke.line, stroke.bit);
+
+        if self.sticky_modifiers && ((line, bit) == SHIFT || (line, bit) == CONTROL) {
+            if self.latched_modifiers.contains(&(line, bit)) {
+                self.latched_modifiers.retain(|modifier| *modifier != (line, bit));
+                keyboard.queue_key_event(delay, line, bit, false);
+            } else {
+                self.latched_modifiers.push((line, bit));
+                keyboard.queue_key_event(delay, line, bit, true);
+            }
+            return;
+        }
+
+        if stroke.shift {
+            keyboard.queue_key_event(delay, SHIFT.0, SHIFT.1, true);
+            keyboard.queue_key_event(0, line, bit, true);
+        } else {
+            keyboard.queue_key_event(delay, line, bit, true);
+        }
+        if self.auto_repeat.is_some() {
+            self.pressed_keys.insert(key, Some(stroke));
+        } else {
+            release_stroke(keyboard, KEY_PRESS_MICROSECONDS, stroke);
+            self.pressed_keys.insert(key, None);
+        }
+    }
+
+    pub fn release_key(&mut self, key: minifb::Key, delay_microseconds: u64, keyboard: &mut keyboard::Keyboard) {
+        let mut delay = keyboard.delay_from_now(delay_microseconds);
+        if matches!(key, minifb::Key::LeftShift | minifb::Key::RightShift) {
+            self.host_shift = false;
+            if self.mode == KeyboardMode::Symbolic {
+                return;
+            }
+        }
+
+        if self.one_handed && key == minifb::Key::Space {
+            self.mirroring = false;
+            if !self.mirrored_key_typed {
+                keyboard.queue_key_event(delay, 5, 7, true);
+                keyboard.queue_key_event(KEY_PRESS_MICROSECONDS, 5, 7, false);
+            }
+            return;
+        }
+
+        match self.pressed_keys.remove(&key) {
+            Some(Some(stroke)) => release_stroke(keyboard, std::mem::take(&mut delay), stroke),
+            Some(None) => (),
+            None => return, // unmapped or latched modifier
+        }
+
+        for (line, bit) in self.latched_modifiers.drain(..) {
+            keyboard.queue_key_event(std::mem::take(&mut delay), line, bit, false);
+        }
+    }
+
+    fn map_symbol(&self, key: minifb::Key) -> Option<KeyStroke> {
+        // the character is typed with the CPC shift state it needs, keys without one keep the host shift state
+        let character = match host_character(key, self.host_shift) {
+            Some(character) => character,
+            None => {
+                let (line, bit) = self.map_key(key)?;
+                return Some(KeyStroke { line, bit, shift: self.host_shift });
+            }
+        };
+        let code = self.language.to_character_code(character)?;
+        let character = if code == 0xa3 { '£' } else { code as char };
+
+        map_localized_character(character, self.language).or_else(|| map_character(character))
+    }
+
+    fn map_key(&self, key: minifb::Key) -> Option<(usize, u8)> {
+        if let Some(remap) = self.remaps.get(&key) {
+            return Some(*remap);
+        }
+
+        if self.language == keyboard::Language::French {
+            // AZERTY
+            match key {
+                minifb::Key::A => return Some((8, 3)),
+                minifb::Key::Q => return Some((8, 5)),
+                minifb::Key::Z => return Some((7, 3)),
+                minifb::Key::W => return Some((8, 7)),
+                minifb::Key::M => return Some((3, 5)),
+                minifb::Key::Comma => return Some((4, 6)),
+                _ => (),
+            }
+        }
+
+        match key {
+            minifb::Key::Up => Some((0, 0)),
+            minifb::Key::Right => Some((0, 1)),
+            minifb::Key::Down => Some((0, 2)),
+            minifb::Key::NumPad9 => Some((0, 3)),
+            minifb::Key::NumPad6 => Some((0, 4)),
+            minifb::Key::NumPad3 => Some((0, 5)),
+            minifb::Key::NumPadEnter => Some((0, 6)),
+            minifb::Key::NumPadDot => Some((0, 7)),
+            minifb::Key::Left => Some((1, 0)),
+            minifb::Key::Insert => Some((1, 1)),
+            minifb::Key::NumPad7 => Some((1, 2)),
+            minifb::Key::NumPad8 => Some((1, 3)),
+            minifb::Key::NumPad5 => Some((1, 4)),
+            minifb::Key::NumPad1 => Some((1, 5)),
+            minifb::Key::NumPad2 => Some((1, 6)),
+            minifb::Key::NumPad0 => Some((1, 7)),
+            minifb::Key::Delete => Some((2, 0)),
+            minifb::Key::LeftBracket => Some((2, 1)),
+            minifb::Key::Enter => Some((2, 2)),
+            minifb::Key::RightBracket => Some((2, 3)),
+            minifb::Key::NumPad4 => Some((2, 4)),
+            minifb::Key::LeftShift => Some((2, 5)),
+            minifb::Key::RightShift => Some((2, 5)),
+            minifb::Key::Backslash => Some((2, 6)),
+            minifb::Key::RightCtrl => Some((2, 7)),
+            minifb::Key::Equal => Some((3, 0)),
+            minifb::Key::Minus => Some((3, 1)),
+            minifb::Key::End => Some((3, 2)),
+            minifb::Key::P => Some((3, 3)),
+            minifb::Key::Semicolon => Some((3, 4)),
+            minifb::Key::Apostrophe => Some((3, 5)),
+            minifb::Key::Slash => Some((3, 6)),
+            minifb::Key::Period => Some((3, 7)),
+            minifb::Key::Key0 => Some((4, 0)),
+            minifb::Key::Key9 => Some((4, 1)),
+            minifb::Key::O => Some((4, 2)),
+            minifb::Key::I => Some((4, 3)),
+            minifb::Key::L => Some((4, 4)),
+            minifb::Key::K => Some((4, 5)),
+            minifb::Key::M => Some((4, 6)),
+            minifb::Key::Comma => Some((4, 7)),
+            minifb::Key::Key8 => Some((5, 0)),
+            minifb::Key::Key7 => Some((5, 1)),
+            minifb::Key::U => Some((5, 2)),
+            minifb::Key::Y => Some((5, 3)),
+            minifb::Key::H => Some((5, 4)),
+            minifb::Key::J => Some((5, 5)),
+            minifb::Key::N => Some((5, 6)),
+            minifb::Key::Space => Some((5, 7)),
+            minifb::Key::Key6 => Some((6, 0)),
+            minifb::Key::Key5 => Some((6, 1)),
+            minifb::Key::R => Some((6, 2)),
+            minifb::Key::T => Some((6, 3)),
+            minifb::Key::G => Some((6, 4)),
+            minifb::Key::F => Some((6, 5)),
+            minifb::Key::B => Some((6, 6)),
+            minifb::Key::V => Some((6, 7)),
+            minifb::Key::Key4 => Some((7, 0)),
+            minifb::Key::Key3 => Some((7, 1)),
+            minifb::Key::E => Some((7, 2)),
+            minifb::Key::W => Some((7, 3)),
+            minifb::Key::S => Some((7, 4)),
+            minifb::Key::D => Some((7, 5)),
+            minifb::Key::C => Some((7, 6)),
+            minifb::Key::X => Some((7, 7)),
+            minifb::Key::Key1 => Some((8, 0)),
+            minifb::Key::Key2 => Some((8, 1)),
+            minifb::Key::Escape => Some((8, 2)),
+            minifb::Key::Q => Some((8, 3)),
+            minifb::Key::Tab => Some((8, 4)),
+            minifb::Key::A => Some((8, 5)),
+            minifb::Key::CapsLock => Some((8, 6)),
+            minifb::Key::Z => Some((8, 7)),
+            // minifb::Key::NumPad8 => Some((9, 0)), // TODO: map joystick 1
+            // minifb::Key::NumPad2 => Some((9, 1)),
+            // minifb::Key::NumPad4 => Some((9, 2)),
+            // minifb::Key::NumPad6 => Some((9, 3)),
+            // minifb::Key::NumPad5 => Some((9, 4)),
+            // minifb::Key::NumPad0 => Some((9, 5)),
+            // minifb::Key::NumPadDot => Some((9, 6)),
+            minifb::Key::Backspace => Some((9, 7)),
+            _ => None,
+        }
+    }
+
+}
+
+pub fn autotype(keyboard: &mut keyboard::Keyboard, text: &str, language: keyboard::Language) -> std::io::Result<()> {
+    autotype_after(keyboard, 0, text, language)
+}
+
+pub fn autotype_after(
+    keyboard: &mut keyboard::Keyboard,
+    delay_microseconds: u64,
+    text: &str,
+    language: keyboard::Language,
+) -> std::io::Result<()> {
+    // host text is converted to the character codes of the firmware language first
+    let mut codes = Vec::new();
+    for character in text.chars().filter(|character| *character != '\r') {
+        match language.to_character_code(character) {
+            Some(code) => codes.push(code),
+            None => {
+                return Err(invalid_data(format!(
+                    "Cannot type character {:?} with {} firmware.",
+                    character,
+                    language.name()
+                )))
+            }
+        }
+    }
+
+    autotype_codes_after(keyboard, delay_microseconds, &codes, language)
+}
+
+pub fn autotype_codes_after(
+    keyboard: &mut keyboard::Keyboard,
+    delay_microseconds: u64,
+    codes: &[u8],
+    language: keyboard::Language,
+) -> std::io::Result<()> {
+    // e.g. commands that are already given in CPC character codes, like "|TAPE" regardless of what the bar looks like
+    let mut strokes = Vec::new();
+    for code in codes {
+        let character = if *code == 0xa3 { '£' } else { *code as char };
+        match map_localized_character(character, language).or_else(|| map_character(character)) {
+            Some(stroke) => strokes.push(stroke),
+            None => return Err(invalid_data(format!("Cannot type character code {:#04x}.", code))),
+        }
+    }
+
+    queue_key_strokes(keyboard, delay_microseconds, &strokes);
+
+    Ok(())
+}
+
+fn queue_key_strokes(keyboard: &mut keyboard::Keyboard, delay_microseconds: u64, strokes: &[KeyStroke]) {
+    for (i, stroke) in strokes.iter().enumerate() {
+        let delay = if i == 0 { delay_microseconds + KEY_RELEASE_MICROSECONDS } else { KEY_RELEASE_MICROSECONDS };
+        if stroke.shift {
+            keyboard.queue_key_event(delay, SHIFT.0, SHIFT.1, true);
+            keyboard.queue_key_event(0, stroke.line, stroke.bit, true);
+        } else {
+            keyboard.queue_key_event(delay, stroke.line, stroke.bit, true);
+        }
+
+        keyboard.queue_key_event(KEY_PRESS_MICROSECONDS, stroke.line, stroke.bit, false);
+        if stroke.shift {
+            keyboard.queue_key_event(0, SHIFT.0, SHIFT.1, false);
+        }
+    }
+}
+
+fn release_stroke(keyboard: &mut keyboard::Keyboard, delay_microseconds: u64, stroke: KeyStroke) {
+    keyboard.queue_key_event(delay_microseconds, stroke.line, stroke.bit, false);
+    if stroke.shift {
+        keyboard.queue_key_event(0, SHIFT.0, SHIFT.1, false);
+    }
+}
+
+fn host_character(key: minifb::Key, shift: bool) -> Option<char> {
+    // as printed on a US host keyboard
+    let (plain, shifted) = match key {
+        minifb::Key::Key0 => ('0', ')'),
+        minifb::Key::Key1 => ('1', '!'),
+        minifb::Key::Key2 => ('2', '@'),
+        minifb::Key::Key3 => ('3', '#'),
+        minifb::Key::Key4 => ('4', '$'),
+        minifb::Key::Key5 => ('5', '%'),
+        minifb::Key::Key6 => ('6', '^'),
+        minifb::Key::Key7 => ('7', '&'),
+        minifb::Key::Key8 => ('8', '*'),
+        minifb::Key::Key9 => ('9', '('),
+        minifb::Key::Minus => ('-', '_'),
+        minifb::Key::Equal => ('=', '+'),
+        minifb::Key::LeftBracket => ('[', '{'),
+        minifb::Key::RightBracket => (']', '}'),
+        minifb::Key::Backslash => ('\\', '|'),
+        minifb::Key::Semicolon => (';', ':'),
+        minifb::Key::Apostrophe => ('\'', '"'),
+        minifb::Key::Comma => (',', '<'),
+        minifb::Key::Period => ('.', '>'),
+        minifb::Key::Slash => ('/', '?'),
+        minifb::Key::Backquote => ('`', '~'),
+        minifb::Key::Space => (' ', ' '),
+        _ => {
+            let (_, letter) = LETTER_KEYS.iter().find(|(letter_key, _)| *letter_key == key)?;
+            (letter.to_ascii_lowercase(), *letter)
+        }
+    };
+
+    Some(if shift { shifted } else { plain })
+}
+
+pub fn cpc_key_name(line: usize, bit: u8) -> Option<String> {
+    // as written in the key map file, either a key name in braces or the unshifted character
+    if let Some((name, _)) = KEY_NAMES.iter().find(|(_, key)| *key == (line, bit)) {
+        return Some(format!("{{{}}}", name));
+    }
+
+    (0x20u8..0x7f).map(|code| code as char).find(|character| {
+        matches!(map_character(*character), Some(stroke) if (stroke.line, stroke.bit, stroke.shift) == (line, bit, false))
+    }).map(|character| character.to_string())
+}
+
+fn parse_host_key(name: &str) -> Option<minifb::Key> {
+    HOST_KEYS.iter().find(|key| format!("{:?}", key) == name).copied()
+}
+
+fn parse_hotkey(name: &str) -> Option<minifb::Key> {
+    match name {
+        "F1" => Some(minifb::Key::F1),
+        "F2" => Some(minifb::Key::F2),
+        "F3" => Some(minifb::Key::F3),
+        "F4" => Some(minifb::Key::F4),
+        "F5" => Some(minifb::Key::F5),
+        "F6" => Some(minifb::Key::F6),
+        "F7" => Some(minifb::Key::F7),
+        "F8" => Some(minifb::Key::F8),
+        "F9" => Some(minifb::Key::F9),
+        "F10" => Some(minifb::Key::F10),
+        "F11" => Some(minifb::Key::F11),
+        _ => None, // F12 is reserved for the debugger
+    }
+}
+
+fn mirror_key(key: minifb::Key) -> minifb::Key {
+    let pairs = [
+        (minifb::Key::Key1, minifb::Key::Key0),
+        (minifb::Key::Key2, minifb::Key::Key9),
+        (minifb::Key::Key3, minifb::Key::Key8),
+        (minifb::Key::Key4, minifb::Key::Key7),
+        (minifb::Key::Key5, minifb::Key::Key6),
+        (minifb::Key::Q, minifb::Key::P),
+        (minifb::Key::W, minifb::Key::O),
+        (minifb::Key::E, minifb::Key::I),
+        (minifb::Key::R, minifb::Key::U),
+        (minifb::Key::T, minifb::Key::Y),
+        (minifb::Key::A, minifb::Key::Semicolon),
+        (minifb::Key::S, minifb::Key::L),
+        (minifb::Key::D, minifb::Key::K),
+        (minifb::Key::F, minifb::Key::J),
+        (minifb::Key::G, minifb::Key::H),
+        (minifb::Key::Z, minifb::Key::Slash),
+        (minifb::Key::X, minifb::Key::Period),
+        (minifb::Key::C, minifb::Key::Comma),
+        (minifb::Key::V, minifb::Key::M),
+        (minifb::Key::B, minifb::Key::N),
+        (minifb::Key::Tab, minifb::Key::Backspace),
+        (minifb::Key::CapsLock, minifb::Key::Enter),
+    ];
+
+    for (left, right) in pairs {
+        if key == left {
+            return right;
+        }
+        if key == right {
+            return left;
+        }
+    }
+
+    key
+}
+
+fn parse_switch(value: &str) -> std::io::Result<bool> {
+    match value.trim() {
+        "on" | "true" => Ok(true),
+        "off" | "false" => Ok(false),
+        _ => Err(invalid_data(format!("Expected \"on\" or \"off\" instead of \"{}\".", value.trim()))),
+    }
+}
+
+fn parse_auto_repeat(value: &str) -> std::io::Result<Option<keyboard::AutoRepeat>> {
+    // "on" repeats with the firmware's timing
+    match value.trim() {
+        "on" | "true" => Ok(Some(keyboard::AutoRepeat::default())),
+        "off" | "false" => Ok(None),
+        value => match keyboard::AutoRepeat::parse(value) {
+            Some(auto_repeat) => Ok(Some(auto_repeat)),
+            None => Err(invalid_data(format!("Expected \"on\", \"off\" or delay and period in milliseconds instead of \"{}\".", value))),
+        },
+    }
+}
+
+fn parse_macro(text: &str) -> std::io::Result<Vec<KeyStroke>> {
+    let mut strokes = Vec::new();
+    let mut remaining = text;
+
+    while let Some(character) = remaining.chars().next() {
+        if character == '{' {
+            let end = match remaining.find('}') {
+                Some(end) => end,
+                None => return Err(invalid_data(format!("Unterminated key name in \"{}\".", text))),
+            };
+
+            let name = &remaining[1..end];
+            match KEY_NAMES.iter().find(|(key_name, _)| *key_name == name) {
+                Some((_, (line, bit))) => strokes.push(KeyStroke {
+                    line: *line,
+                    bit: *bit,
+                    shift: false,
+                }),
+                None => return Err(invalid_data(format!("Unknown CPC key \"{}\".", name))),
+            }
+
+            remaining = &remaining[end + 1..];
+        } else {
+            match map_character(character) {
+                Some(stroke) => strokes.push(stroke),
+                None => return Err(invalid_data(format!("Cannot type character {:?}.", character))),
+            }
+
+            remaining = &remaining[character.len_utf8()..];
+        }
+    }
+
+    Ok(strokes)
+}
+
+fn map_localized_character(character: char, language: keyboard::Language) -> Option<KeyStroke> {
+    // keys that differ from the English keyboard, all others type the same character codes
+    let (line, bit, shift) = match (language, character) {
+        (keyboard::Language::French, _) if character.is_ascii_alphabetic() => {
+            let (line, bit) = match character.to_ascii_uppercase() {
+                'A' => (8, 3),
+                'Q' => (8, 5),
+                'Z' => (7, 3),
+                'W' => (8, 7),
+                'M' => (3, 5),
+                _ => return None,
+            };
+            (line, bit, character.is_ascii_uppercase())
+        }
+        (keyboard::Language::French, _) if character.is_ascii_digit() => {
+            let stroke = map_character(character)?;
+            (stroke.line, stroke.bit, true)
+        }
+        (keyboard::Language::French, '&') => (8, 0, false),
+        (keyboard::Language::French, '{') => (8, 1, false), // é
+        (keyboard::Language::French, '"') => (7, 1, false),
+        (keyboard::Language::French, '\'') => (7, 0, false),
+        (keyboard::Language::French, '(') => (6, 1, false),
+        (keyboard::Language::French, '}') => (5, 1, false), // è
+        (keyboard::Language::French, '\\') => (4, 1, false), // ç
+        (keyboard::Language::French, '@') => (4, 0, false), // à
+        (keyboard::Language::French, ',') => (4, 6, false),
+        (keyboard::Language::Spanish, '|') => (3, 5, false), // ñ
+        (keyboard::Language::Spanish, '\\') => (3, 5, true), // Ñ
+        (keyboard::Language::Danish, '{') => (3, 5, false), // æ
+        (keyboard::Language::Danish, '[') => (3, 5, true), // Æ
+        (keyboard::Language::Danish, '|') => (3, 4, false), // ø
+        (keyboard::Language::Danish, '\\') => (3, 4, true), // Ø
+        (keyboard::Language::Danish, '}') => (3, 2, false), // å
+        (keyboard::Language::Danish, ']') => (3, 2, true), // Å
+        _ => return None,
+    };
+
+    Some(KeyStroke { line, bit, shift })
+}
+
+fn map_character(character: char) -> Option<KeyStroke> {
+    let (line, bit, shift) = match character {
+        '^' => (3, 0, false),
+        '£' => (3, 0, true),
+        '-' => (3, 1, false),
+        '=' => (3, 1, true),
+        '@' => (3, 2, false),
+        '|' => (3, 2, true),
+        ';' => (3, 4, false),
+        '+' => (3, 4, true),
+        ':' => (3, 5, false),
+        '*' => (3, 5, true),
+        '/' => (3, 6, false),
+        '?' => (3, 6, true),
+        '.' => (3, 7, false),
+        '>' => (3, 7, true),
+        '0' => (4, 0, false),
+        '_' => (4, 0, true),
+        '9' => (4, 1, false),
+        ')' => (4, 1, true),
+        ',' => (4, 7, false),
+        '<' => (4, 7, true),
+        '8' => (5, 0, false),
+        '(' => (5, 0, true),
+        '7' => (5, 1, false),
+        '\'' => (5, 1, true),
+        ' ' => (5, 7, false),
+        '6' => (6, 0, false),
+        '&' => (6, 0, true),
+        '5' => (6, 1, false),
+        '%' => (6, 1, true),
+        '4' => (7, 0, false),
+        '$' => (7, 0, true),
+        '3' => (7, 1, false),
+        '#' => (7, 1, true),
+        '1' => (8, 0, false),
+        '!' => (8, 0, true),
+        '2' => (8, 1, false),
+        '"' => (8, 1, true),
+        '[' => (2, 1, false),
+        '{' => (2, 1, true),
+        ']' => (2, 3, false),
+        '}' => (2, 3, true),
+        '\\' => (2, 6, false),
+        '`' => (2, 6, true),
+        '\n' => (2, 2, false),
+        '\t' => (8, 4, false),
+        _ if character.is_ascii_alphabetic() => {
+            let (line, bit) = match character.to_ascii_uppercase() {
+                'P' => (3, 3),
+                'O' => (4, 2),
+                'I' => (4, 3),
+                'L' => (4, 4),
+                'K' => (4, 5),
+                'M' => (4, 6),
+                'U' => (5, 2),
+                'Y' => (5, 3),
+                'H' => (5, 4),
+                'J' => (5, 5),
+                'N' => (5, 6),
+                'R' => (6, 2),
+                'T' => (6, 3),
+                'G' => (6, 4),
+                'F' => (6, 5),
+                'B' => (6, 6),
+                'V' => (6, 7),
+                'E' => (7, 2),
+                'W' => (7, 3),
+                'S' => (7, 4),
+                'D' => (7, 5),
+                'C' => (7, 6),
+                'X' => (7, 7),
+                'Q' => (8, 3),
+                'A' => (8, 5),
+                'Z' => (8, 7),
+                _ => unreachable!(),
+            };
+
+            (line, bit, character.is_ascii_uppercase())
+        }
+        _ => return None,
+    };
+
+    Some(KeyStroke { line, bit, shift })
+}
+
+fn invalid_data(message: String) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message)
+}
@@ -1,11 +1,123 @@
 use std::cell::RefCell;
+use std::collections::VecDeque;
 use std::rc::Rc;
 
 pub type KeyboardShared = Rc<RefCell<Keyboard>>;
 
+#[derive(Clone, Debug, PartialEq)]
+pub struct KeyEvent {
+    pub timestamp: u64, // in microseconds of emulated time
+    pub line: usize,
+    pub bit: u8,
+    pub pressed: bool,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Language {
+    #[default]
+    English,
+    French,
+    Spanish,
+    Danish,
+}
+
+impl Language {
+    pub fn from_name(name: &str) -> Option<Language> {
+        match name {
+            "english" => Some(Language::English),
+            "french" => Some(Language::French),
+            "spanish" => Some(Language::Spanish),
+            "danish" => Some(Language::Danish),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Language::English => "english",
+            Language::French => "french",
+            Language::Spanish => "spanish",
+            Language::Danish => "danish",
+        }
+    }
+
+    pub fn to_character_code(&self, character: char) -> Option<u8> {
+        // localized firmware shows national characters in place of some ASCII symbols, following ISO 646
+        let replaced: &[(char, u8)] = match self {
+            Language::English => &[('£', 0xa3)],
+            Language::French => &[
+                ('£', 0x23),
+                ('à', 0x40),
+                ('°', 0x5b),
+                ('ç', 0x5c),
+                ('§', 0x5d),
+                ('é', 0x7b),
+                ('ù', 0x7c),
+                ('è', 0x7d),
+                ('¨', 0x7e),
+            ],
+            Language::Spanish => &[('§', 0x40), ('¡', 0x5b), ('Ñ', 0x5c), ('¿', 0x5d), ('°', 0x7b), ('ñ', 0x7c), ('ç', 0x7d)],
+            Language::Danish => &[('Æ', 0x5b), ('Ø', 0x5c), ('Å', 0x5d), ('æ', 0x7b), ('ø', 0x7c), ('å', 0x7d)],
+        };
+
+        if let Some((_, code)) = replaced.iter().find(|(national, _)| *national == character) {
+            return Some(*code);
+        }
+
+        // the ASCII symbol itself cannot be typed when a national character took its place
+        let code = character as u32;
+        if code < 0x80 && !replaced.iter().any(|(_, replaced_code)| *replaced_code as u32 == code) {
+            Some(code as u8)
+        } else {
+            None
+        }
+    }
+}
+
+const RECENT_EVENTS_LENGTH: usize = 1000; // key changes kept for bug reports
+const MODIFIER_KEYS: [(usize, u8); 3] = [(2, 5), (2, 7), (1, 1)]; // shift, control and copy do not repeat
+
+// repeats the last key held down by releasing and pressing it again in the matrix, so programs that
+// scan the matrix themselves see it repeat like the firmware would. The defaults are the firmware's
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AutoRepeat {
+    pub delay_microseconds: u64,  // from pressing the key to its first repeat
+    pub period_microseconds: u64, // between repeats, the key is released for half of it
+}
+
+impl Default for AutoRepeat {
+    fn default() -> Self {
+        AutoRepeat {
+            delay_microseconds: 600_000,
+            period_microseconds: 40_000,
+        }
+    }
+}
+
+impl AutoRepeat {
+    pub fn parse(text: &str) -> Option<AutoRepeat> {
+        // the delay and the period in milliseconds, e.g. "600,40"
+        let (delay, period) = text.split_once(',')?;
+        Some(AutoRepeat {
+            delay_microseconds: delay.trim().parse::<u64>().ok()? * 1000,
+            period_microseconds: period.trim().parse::<u64>().ok()? * 1000,
+        })
+    }
+
+    pub fn name(&self) -> String {
+        format!("{},{}", self.delay_microseconds / 1000, self.period_microseconds / 1000)
+    }
+}
+
 pub struct Keyboard {
     lines: [u8; 10],
     active_line: usize,
+    pending_events: VecDeque<KeyEvent>,
+    elapsed_microseconds: u64,
+    recording: Option<Vec<KeyEvent>>,
+    recent_events: VecDeque<KeyEvent>,
+    auto_repeat: Option<AutoRepeat>,
+    held_key: Option<(usize, u8, u64)>, // the key that repeats and when it was pressed
 }
 
 impl Keyboard {
@@ -13,23 +125,185 @@ impl Keyboard {
         let keyboard = Keyboard {
             lines: [0xff; 10],
             active_line: 0,
+            pending_events: VecDeque::new(),
+            elapsed_microseconds: 0,
+            recording: None,
+            recent_events: VecDeque::with_capacity(RECENT_EVENTS_LENGTH),
+            auto_repeat: None,
+            held_key: None,
         };
 
         Rc::new(RefCell::new(keyboard))
     }
 
     pub fn reset_all(&mut self) {
-        self.lines = [0xff; 10];
+        for line in 0..self.lines.len() {
+            for bit in 0..8 {
+                self.unset_key(line, bit);
+            }
+        }
+        self.pending_events.clear();
     }
 
     pub fn set_key(&mut self, line: usize, bit: u8) {
-        self.lines[line] &= !(1 << bit);
+        if !MODIFIER_KEYS.contains(&(line, bit)) {
+            // a key that stops repeating is still held down
+            if let Some((held_line, held_bit, _)) = self.held_key {
+                self.change_key(held_line, held_bit, true);
+            }
+            self.held_key = Some((line, bit, self.elapsed_microseconds));
+        }
+        self.change_key(line, bit, true);
+    }
+
+    pub fn unset_key(&mut self, line: usize, bit: u8) {
+        if let Some((held_line, held_bit, _)) = self.held_key {
+            if (held_line, held_bit) == (line, bit) {
+                self.held_key = None;
+            }
+        }
+        self.change_key(line, bit, false);
+    }
+
+    fn change_key(&mut self, line: usize, bit: u8, pressed: bool) {
+        self.record(line, bit, pressed);
+        if pressed {
+            self.lines[line] &= !(1 << bit);
+        } else {
+            self.lines[line] |= 1 << bit;
+        }
+    }
+
+    pub fn set_auto_repeat(&mut self, auto_repeat: Option<AutoRepeat>) {
+        // None leaves repeating to the firmware, which programs reading the matrix do not see
+        self.auto_repeat = auto_repeat;
+    }
+
+    pub fn get_auto_repeat(&self) -> Option<AutoRepeat> {
+        self.auto_repeat
+    }
+
+    pub fn start_recording(&mut self) {
+        self.recording = Some(Vec::new());
+    }
+
+    pub fn take_recording(&mut self) -> Vec<KeyEvent> {
+        self.recording.take().unwrap_or_default()
+    }
+
+    pub fn schedule_key_events(&mut self, events: &[KeyEvent]) {
+        // unlike queued events these use absolute timestamps, e.g. to play back a recording
+        self.pending_events.extend(events.iter().cloned());
+    }
+
+    pub fn read_recent_events(&self) -> Vec<KeyEvent> {
+        // the last key changes, whether recording or not
+        self.recent_events.iter().cloned().collect()
+    }
+
+    fn record(&mut self, line: usize, bit: u8, pressed: bool) {
+        // only actual changes are recorded, so held keys and auto-repeat do not bloat the log
+        let currently_pressed = self.lines[line] & (1 << bit) == 0;
+        if currently_pressed == pressed {
+            return;
+        }
+
+        let event = KeyEvent {
+            timestamp: self.elapsed_microseconds,
+            line,
+            bit,
+            pressed,
+        };
+        if let Some(recording) = &mut self.recording {
+            recording.push(event.clone());
+        }
+        if self.recent_events.len() == RECENT_EVENTS_LENGTH {
+            self.recent_events.pop_front();
+        }
+        self.recent_events.push_back(event);
+    }
+
+    pub fn queue_key_event(&mut self, delay_microseconds: u64, line: usize, bit: u8, pressed: bool) {
+        // the delay is relative to the last pending event, so sequences of events keep their order
+        let timestamp = self.read_queue_end() + delay_microseconds;
+
+        self.pending_events.push_back(KeyEvent {
+            timestamp,
+            line,
+            bit,
+            pressed,
+        });
+    }
+
+    pub fn delay_from_now(&self, delay_microseconds: u64) -> u64 {
+        // the delay to queue an event with so it happens this long from now, or right after the
+        // pending events if they last longer
+        (self.elapsed_microseconds + delay_microseconds).saturating_sub(self.read_queue_end())
+    }
+
+    fn read_queue_end(&self) -> u64 {
+        match self.pending_events.back() {
+            Some(last_event) => last_event.timestamp.max(self.elapsed_microseconds),
+            None => self.elapsed_microseconds,
+        }
+    }
+
+    pub fn step(&mut self, microseconds: u64) {
+        self.elapsed_microseconds += microseconds;
+
+        while let Some(event) = self.pending_events.front() {
+            if event.timestamp > self.elapsed_microseconds {
+                break;
+            }
+
+            if event.pressed {
+                self.set_key(event.line, event.bit);
+            } else {
+                self.unset_key(event.line, event.bit);
+            }
+            self.pending_events.pop_front();
+        }
+
+        self.update_auto_repeat();
+    }
+
+    fn update_auto_repeat(&mut self) {
+        let (auto_repeat, (line, bit, pressed_at)) = match (self.auto_repeat, self.held_key) {
+            (Some(auto_repeat), Some(held_key)) => (auto_repeat, held_key),
+            _ => return,
+        };
+        let held = self.elapsed_microseconds - pressed_at;
+        if held < auto_repeat.delay_microseconds {
+            return;
+        }
+
+        // released in the first half of each period, so every repeat starts with a press
+        let period = auto_repeat.period_microseconds.max(2);
+        let released = (held - auto_repeat.delay_microseconds) % period < period / 2;
+        self.change_key(line, bit, !released);
     }
 
     pub fn set_active_line(&mut self, line: usize) {
         self.active_line = line;
     }
 
+    pub fn read_matrix(&self) -> [u8; 10] {
+        // a cleared bit is a pressed key, like the hardware reports it
+        self.lines
+    }
+
+    pub fn get_elapsed_microseconds(&self) -> u64 {
+        self.elapsed_microseconds
+    }
+
+    pub fn seek(&mut self, microseconds: u64, matrix: [u8; 10]) {
+        // e.g. after restoring a snapshot taken at that time, pending events are dropped
+        self.elapsed_microseconds = microseconds;
+        self.lines = matrix;
+        self.pending_events.clear();
+        self.held_key = None;
+    }
+
     pub fn scan_active_line(&self) -> u8 {
         if self.active_line < 10 {
             self.lines[self.active_line]
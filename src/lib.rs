@@ -0,0 +1,64 @@
+#![allow(dead_code, unused_variables)]
+
+pub mod actions;
+pub mod amsdos;
+pub mod analysis_file;
+pub mod audio_output;
+pub mod audio_ring;
+pub mod basic;
+pub mod bug_report;
+pub mod bus;
+pub mod bytes;
+pub mod cdt_file;
+pub mod cheats;
+pub mod checksums;
+pub mod config_file;
+pub mod coverage;
+pub mod cpu;
+pub mod crtc;
+pub mod debug_console;
+pub mod debugger;
+pub mod dsk_file;
+pub mod expansion;
+pub mod expression;
+pub mod fdc;
+pub mod file_watcher;
+pub mod frame_pacer;
+pub mod game_database;
+pub mod gate_array;
+pub mod gui;
+pub mod input;
+pub mod instruction;
+pub mod key_configurator;
+pub mod key_mapper;
+pub mod keyboard;
+pub mod lite_gui;
+pub mod logger;
+pub mod media;
+pub mod media_library;
+pub mod memory;
+pub mod midi_clock;
+pub mod mixer;
+pub mod opcode_table;
+pub mod palette;
+pub mod patches;
+pub mod ppi;
+pub mod project;
+pub mod psg;
+pub mod replay;
+pub mod rsx;
+pub mod rtc;
+pub mod screen;
+pub mod setup_wizard;
+pub mod sna_file;
+pub mod snapshot_diff;
+pub mod speech;
+pub mod status_bar;
+pub mod storage;
+pub mod system;
+pub mod tape;
+pub mod timeline;
+pub mod triggers;
+pub mod vgm_file;
+pub mod window_title;
+pub mod workbench;
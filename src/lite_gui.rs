@@ -0,0 +1,123 @@
+use crate::{audio_output, frame_pacer, gui, key_mapper, patches, replay, screen, system};
+
+// a frontend with just the screen, the keyboard and the sound: no menus, debug panels or status
+// bar, for slow hosts. It paces and maps keys like the full GUI
+pub struct LiteGUI {
+    system: Box<dyn system::System>,
+    window: minifb::Window,
+    host_input: gui::HostInput,
+    key_mapper: key_mapper::KeyMapper,
+    audio_output: Option<audio_output::AudioOutput>,
+    patches: patches::PatchSet,
+    frame_pacer: Option<frame_pacer::FramePacer>, // None presents every emulated frame at 50Hz
+    recording: Option<replay::Replay>,
+}
+
+impl LiteGUI {
+    pub fn new(
+        system: Box<dyn system::System>,
+        key_mapper: key_mapper::KeyMapper,
+        scale: gui::WindowScale,
+    ) -> std::io::Result<LiteGUI> {
+        let mut window = minifb::Window::new("Ronald", screen::BUFFER_WIDTH, screen::BUFFER_HEIGHT, scale.window_options())
+        .map_err(|error| std::io::Error::new(std::io::ErrorKind::InvalidInput, error.to_string()))?;
+        window.limit_update_rate(None); // paced by the run loop, which polls the input while it waits
+
+        Ok(LiteGUI {
+            system,
+            window,
+            host_input: gui::HostInput::new(),
+            key_mapper,
+            audio_output: None,
+            patches: patches::PatchSet::new(),
+            frame_pacer: None,
+            recording: None,
+        })
+    }
+
+    pub fn open_audio(&mut self, settings: &audio_output::AudioSettings) {
+        let mixer = self.system.get_mixer();
+        let result = audio_output::AudioOutput::open(&mut mixer.borrow_mut(), settings);
+        match result {
+            Ok(output) => self.audio_output = Some(output),
+            Err(error) => println!("Could not open the audio device: {}", error),
+        }
+    }
+
+    pub fn set_patches(&mut self, patches: patches::PatchSet) {
+        self.patches = patches;
+    }
+
+    pub fn set_recording(&mut self, recording: replay::Replay) {
+        self.recording = Some(recording);
+    }
+
+    pub fn take_recording(&mut self) -> Option<replay::Replay> {
+        self.recording.take()
+    }
+
+    pub fn set_frame_pacer(&mut self, frame_pacer: frame_pacer::FramePacer) {
+        log::info!("Frame pacing: {}", frame_pacer.get_pacing().name());
+        self.frame_pacer = Some(frame_pacer);
+    }
+
+    pub fn into_system(self) -> Box<dyn system::System> {
+        self.system
+    }
+
+    pub fn run(&mut self) {
+        let present_interval = match &self.frame_pacer {
+            Some(frame_pacer) => frame_pacer.get_present_interval(),
+            None => std::time::Duration::from_micros(20_000),
+        };
+        let mut last_present = std::time::Instant::now();
+
+        while self.window.is_open() {
+            let frames = match &mut self.frame_pacer {
+                Some(frame_pacer) => frame_pacer.frames_due(),
+                None => 1,
+            };
+            self.update_keys(frames);
+            let mut elapsed_microseconds: u32 = 0;
+            for frame in 1..=frames {
+                while elapsed_microseconds < frame * 20_000 {
+                    elapsed_microseconds += self.system.emulate() as u32;
+                }
+                self.patches.update(self.system.as_mut());
+                if let Some(recording) = &mut self.recording {
+                    recording.record_frame(self.system.as_ref());
+                }
+                if let Some(frame_pacer) = &mut self.frame_pacer {
+                    frame_pacer.add_frame(self.system.get_screen().borrow().get_frame_buffer());
+                }
+            }
+
+            self.host_input.wait_until(&mut self.window, last_present + present_interval);
+            last_present = std::time::Instant::now();
+            let screen = self.system.get_screen();
+            let result = match self.frame_pacer.as_mut().and_then(|frame_pacer| frame_pacer.present()) {
+                Some(frame) => self.window.update_with_buffer(frame, screen::BUFFER_WIDTH, screen::BUFFER_HEIGHT),
+                None => self.window.update_with_buffer(screen.borrow().get_frame_buffer(), screen::BUFFER_WIDTH, screen::BUFFER_HEIGHT),
+            };
+            if let Err(error) = result {
+                println!("Could not update the window: {}", error);
+                break;
+            }
+            self.host_input.poll(&mut self.window);
+            if self.frame_pacer.is_none() || frames > 0 {
+                self.system.notify_frame_presented();
+            }
+        }
+    }
+
+    fn update_keys(&mut self, frames: u32) {
+        let keyboard = self.system.get_keyboard();
+        for (delay, key, pressed) in self.host_input.take_key_events(frames) {
+            if pressed {
+                self.key_mapper.press_key(key, delay, &mut keyboard.borrow_mut());
+            } else {
+                self.key_mapper.release_key(key, delay, &mut keyboard.borrow_mut());
+            }
+        }
+    }
+}
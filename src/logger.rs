@@ -0,0 +1,180 @@
+use std::collections::VecDeque;
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+const HISTORY_LENGTH: usize = 1000;
+const MAX_ECHOED_PER_SECOND: u32 = 50; // devices logging on every port access would otherwise flood the terminal
+
+pub type LogHistoryShared = Arc<Mutex<LogHistory>>;
+
+#[derive(Clone, Debug)]
+pub struct LogRecord {
+    pub level: log::Level,
+    pub target: String,
+    pub message: String,
+    pub repeated: u32, // further identical messages collapsed into this one
+}
+
+// the most recent log messages, shown in the log panel of the workbench
+pub struct LogHistory {
+    records: VecDeque<LogRecord>,
+}
+
+impl LogHistory {
+    pub fn new_shared() -> LogHistoryShared {
+        let history = LogHistory {
+            records: VecDeque::with_capacity(HISTORY_LENGTH),
+        };
+
+        Arc::new(Mutex::new(history))
+    }
+
+    pub fn search(&self, query: &str) -> Vec<LogRecord> {
+        // oldest first, matching the query case insensitively against module and message
+        let query = query.to_lowercase();
+        self.records
+            .iter()
+            .filter(|record| {
+                record.message.to_lowercase().contains(&query) || record.target.to_lowercase().contains(&query)
+            })
+            .cloned()
+            .collect()
+    }
+
+    fn add(&mut self, level: log::Level, target: &str, message: String) -> bool {
+        // returns whether the message is new rather than a repetition of the previous one
+        if let Some(last) = self.records.back_mut() {
+            if last.level == level && last.target == target && last.message == message {
+                last.repeated += 1;
+                return false;
+            }
+        }
+
+        if self.records.len() == HISTORY_LENGTH {
+            self.records.pop_front();
+        }
+        self.records.push_back(LogRecord {
+            level,
+            target: target.to_string(),
+            message,
+            repeated: 0,
+        });
+
+        true
+    }
+}
+
+struct Throttle {
+    window_start: Instant,
+    echoed: u32,
+    suppressed: u32,
+}
+
+// keeps log messages for the workbench and echoes them to stderr, with levels per module:
+//
+// warn,ronald::fdc=debug
+pub struct Logger {
+    default_level: log::LevelFilter,
+    module_levels: Vec<(String, log::LevelFilter)>,
+    history: LogHistoryShared,
+    throttle: Mutex<Throttle>,
+}
+
+impl Logger {
+    pub fn new(spec: &str, history: LogHistoryShared) -> Result<Logger, String> {
+        let mut default_level = log::LevelFilter::Warn;
+        let mut module_levels = Vec::new();
+        for entry in spec.split(',').map(|entry| entry.trim()).filter(|entry| !entry.is_empty()) {
+            match entry.split_once('=') {
+                Some((module, level)) => module_levels.push((module.trim().to_string(), parse_level(level.trim())?)),
+                None => default_level = parse_level(entry)?,
+            }
+        }
+
+        // the most specific module wins
+        module_levels.sort_by_key(|(module, _)| std::cmp::Reverse(module.len()));
+
+        Ok(Logger {
+            default_level,
+            module_levels,
+            history,
+            throttle: Mutex::new(Throttle {
+                window_start: Instant::now(),
+                echoed: 0,
+                suppressed: 0,
+            }),
+        })
+    }
+
+    pub fn install(self) -> Result<(), log::SetLoggerError> {
+        let max_level = self
+            .module_levels
+            .iter()
+            .map(|(_, level)| *level)
+            .fold(self.default_level, |max, level| max.max(level));
+        log::set_boxed_logger(Box::new(self))?;
+        log::set_max_level(max_level);
+
+        Ok(())
+    }
+
+    fn level_for(&self, target: &str) -> log::LevelFilter {
+        let module = self.module_levels.iter().find(|(module, _)| {
+            target == module || (target.starts_with(module.as_str()) && target[module.len()..].starts_with("::"))
+        });
+
+        match module {
+            Some((_, level)) => *level,
+            None => self.default_level,
+        }
+    }
+
+    fn echo(&self, record: &log::Record) {
+        let mut throttle = self.throttle.lock().unwrap();
+        if throttle.window_start.elapsed().as_secs() >= 1 {
+            if throttle.suppressed > 0 {
+                eprintln!("[ronald] {} log messages suppressed", throttle.suppressed);
+            }
+            throttle.window_start = Instant::now();
+            throttle.echoed = 0;
+            throttle.suppressed = 0;
+        }
+
+        if throttle.echoed < MAX_ECHOED_PER_SECOND {
+            throttle.echoed += 1;
+            eprintln!("[{} {}] {}", record.level(), record.target(), record.args());
+        } else {
+            throttle.suppressed += 1;
+        }
+    }
+}
+
+impl log::Log for Logger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= self.level_for(metadata.target())
+    }
+
+    fn log(&self, record: &log::Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let is_new = self
+            .history
+            .lock()
+            .unwrap()
+            .add(record.level(), record.target(), record.args().to_string());
+        if is_new {
+            self.echo(record);
+        }
+    }
+
+    fn flush(&self) {
+        let _ = std::io::stderr().flush();
+    }
+}
+
+fn parse_level(name: &str) -> Result<log::LevelFilter, String> {
+    name.parse().map_err(|_| format!("Unknown log level \"{}\".", name))
+}
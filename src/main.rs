@@ -1,77 +1,2132 @@
 #![allow(dead_code, unused_variables)]
 
-mod bus;
-mod cpu;
-mod crtc;
-mod debugger;
-mod dsk_file;
-mod fdc;
-mod gate_array;
-mod gui;
-mod instruction;
-mod keyboard;
-mod memory;
-mod ppi;
-mod psg;
-mod screen;
-mod system;
-mod tape;
-
-use clap::{App, Arg};
-
-use crate::system::System;
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
 
-fn main() {
-    env_logger::init();
+use ronald::system::System;
+use ronald::game_database::GameDatabase;
+use ronald::{amsdos, analysis_file, audio_output, basic, bug_report, bus, cdt_file, cheats, checksums, config_file, crtc, dsk_file, frame_pacer, game_database, gui, key_mapper, keyboard, lite_gui, logger, media, media_library, memory, midi_clock, mixer, opcode_table, patches, project, replay, setup_wizard, sna_file, snapshot_diff, speech, storage, system, vgm_file, window_title};
+
+const DEFAULT_CONFIG_FILE: &str = "ronald.toml"; // written by the setup command, read by runs without --config
+const DEFAULT_LAYOUT_FILE: &str = "ronald.layout";
+const AUTORUN_DELAY_MICROSECONDS: u64 = 2_000_000; // until the firmware shows the READY prompt
 
-    let matches = App::new(env!("CARGO_PKG_NAME"))
+fn app() -> App<'static, 'static> {
+    App::new(env!("CARGO_PKG_NAME"))
         .version(env!("CARGO_PKG_VERSION"))
         .about("an Amstrad CPC emulator")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
         .arg(
-            Arg::with_name("debug")
-                .short("d")
-                .long("debug")
-                .value_name("DEBUG")
-                .help("Runs the emulator in debug mode (not available for zexdoc)")
-                .takes_value(false),
+            Arg::with_name("log")
+                .long("log")
+                .value_name("LEVELS")
+                .help("Log levels, optionally per module, e.g. warn,ronald::fdc=debug (defaults to RUST_LOG or warn)")
+                .global(true)
+                .takes_value(true),
+        )
+        .subcommand(
+            SubCommand::with_name("run")
+                .about("Runs a CPC 464 with the given media inserted")
+                .arg(
+                    Arg::with_name("config")
+                        .long("config")
+                        .value_name("CONFIG")
+                        .help("Reads the settings from a TOML config file, options on the command line take precedence")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("debug")
+                        .short("d")
+                        .long("debug")
+                        .help("Starts the emulator in the debugger")
+                        .takes_value(false),
+                )
+                .arg(
+                    Arg::with_name("project")
+                        .long("project")
+                        .value_name("PROJECT")
+                        .help("Keeps debugger labels, comments and data regions in this file (defaults to one next to the first media file)")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("keymap")
+                        .short("k")
+                        .long("keymap")
+                        .value_name("KEYMAP")
+                        .help("Loads hotkey macros from a key map file")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("auto-repeat")
+                        .long("auto-repeat")
+                        .value_name("DELAY,PERIOD")
+                        .help("Repeats held keys in the keyboard matrix after the delay and every period in milliseconds, also for programs that do not use the firmware to read the keyboard, or types them once with \"off\" [default: 600,40 or as in the key map]")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("watch")
+                        .long("watch")
+                        .help("Reloads the ROMs and the key map whenever their files change"),
+                )
+                .arg(
+                    Arg::with_name("keyboard-mode")
+                        .long("keyboard-mode")
+                        .value_name("MODE")
+                        .help("Maps host keys by position for games or by symbol for typing (Ctrl+K switches)")
+                        .possible_values(&["positional", "symbolic"])
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("deterministic")
+                        .long("deterministic")
+                        .help("Runs without any host time or entropy influencing the emulation")
+                        .takes_value(false),
+                )
+                .arg(
+                    Arg::with_name("seed")
+                        .long("seed")
+                        .value_name("SEED")
+                        .help("Seeds the PSG noise generator and random RAM contents in deterministic mode")
+                        .requires("deterministic")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("ram-pattern")
+                        .long("ram-pattern")
+                        .value_name("PATTERN")
+                        .help("Selects the contents of RAM after power-up")
+                        .possible_values(&["zeros", "stripes", "random"])
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("power-on-random")
+                        .long("power-on-random")
+                        .value_name("DEVICES")
+                        .help("Starts these devices with the state real hardware leaves undefined randomized instead of cleared, from the seed in deterministic mode [crtc, gate-array, psg, all]")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("ram")
+                        .long("ram")
+                        .value_name("SIZE")
+                        .help("RAM including expansions, banked like on the 6128 (default: 64k)")
+                        .possible_values(&["64k", "128k", "320k", "576k"])
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("crtc")
+                        .long("crtc")
+                        .value_name("TYPE")
+                        .help("CRTC type, which changes sync widths and register reads (default: 0)")
+                        .possible_values(&["0", "1", "2", "3"])
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("rtc")
+                        .long("rtc")
+                        .help("Attaches a real time clock expansion")
+                        .takes_value(false),
+                )
+                .arg(
+                    Arg::with_name("rtc-time")
+                        .long("rtc-time")
+                        .value_name("UNIX_TIME")
+                        .help("Stops the real time clock at the given time (in seconds since 1970)")
+                        .requires("rtc")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("speech")
+                        .long("speech")
+                        .value_name("INTERFACE")
+                        .help("Attaches a speech synthesizer expansion")
+                        .possible_values(&["ssa1", "dktronics"])
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("headless")
+                        .long("headless")
+                        .help("Runs without a window and exits with status 0 once the --until condition is met")
+                        .takes_value(false),
+                )
+                .arg(
+                    Arg::with_name("until")
+                        .long("until")
+                        .value_name("CONDITION")
+                        .help("Stops a headless run once the condition is met (e.g. pc=0x1234)")
+                        .requires("headless")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("timeout")
+                        .long("timeout")
+                        .value_name("TIMEOUT")
+                        .help("Stops a headless run with status 1 after this much emulated time (e.g. 60s or 500ms)")
+                        .requires("headless")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("dump-screen")
+                        .long("dump-screen")
+                        .value_name("PNG")
+                        .help("Saves the screen to a PNG file when a headless run stops")
+                        .requires("headless")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("dump-layers")
+                        .long("dump-layers")
+                        .value_name("PREFIX")
+                        .help("Saves the border, each pen and the display enable signal of the last frame to separate PNG files when a headless run stops")
+                        .requires("headless")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("print-screen")
+                        .long("print-screen")
+                        .help("Prints the text on screen when a headless run stops")
+                        .requires("headless")
+                        .takes_value(false),
+                )
+                .arg(
+                    Arg::with_name("io-trace")
+                        .long("io-trace")
+                        .value_name("CSV")
+                        .help("Records all I/O port accesses of a headless run to a CSV file")
+                        .requires("headless")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("vgm")
+                        .long("vgm")
+                        .value_name("VGM")
+                        .help("Records all PSG register writes to a VGM file")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("patches")
+                        .long("patches")
+                        .value_name("PATCHES")
+                        .help("Applies pokes from a TOML patch file, or all of them in a directory, e.g. trainers")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1),
+                )
+                .arg(
+                    Arg::with_name("midi-out")
+                        .long("midi-out")
+                        .value_name("DEVICE")
+                        .help("Sends MIDI clock, start and stop messages to a raw MIDI device, e.g. /dev/snd/midiC1D0")
+                        .requires("midi-tick")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("midi-tick")
+                        .long("midi-tick")
+                        .value_name("SOURCE")
+                        .help("What advances the MIDI clock: byte=<address> when a byte changes (e.g. a tracker's row counter) or frames=<count>")
+                        .requires("midi-out")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("midi-clocks-per-tick")
+                        .long("midi-clocks-per-tick")
+                        .value_name("COUNT")
+                        .help("MIDI clocks sent per tick, at 24 per quarter note [default: 6]")
+                        .requires("midi-out")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("export-analysis")
+                        .long("export-analysis")
+                        .value_name("SCRIPT")
+                        .help("Exports executed code, entry points and project annotations as a Ghidra (.py) or IDA (.idc) script on exit")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("bug-report")
+                        .long("bug-report")
+                        .value_name("ZIP")
+                        .help("Writes config, machine state, recent input and an instruction trace at the end of a headless run")
+                        .requires("headless")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("autorun")
+                        .long("autorun")
+                        .help("Types the detected run command after inserting a disk or tape")
+                        .takes_value(false),
+                )
+                .arg(
+                    Arg::with_name("media-parsing")
+                        .long("media-parsing")
+                        .value_name("MODE")
+                        .help("Repairs common mistakes in disk images with a warning, or rejects such images [permissive, strict] (default: permissive)")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("game-db")
+                        .long("game-db")
+                        .value_name("DATABASE")
+                        .help("Recognizes the inserted media and applies per-title settings from a game database")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("tape-sound")
+                        .long("tape-sound")
+                        .help("Plays the cassette signal through the sound output while loading")
+                        .takes_value(false),
+                )
+                .arg(
+                    Arg::with_name("fast-load")
+                        .long("fast-load")
+                        .help("Loads tape records instantly instead of in real time")
+                        .takes_value(false),
+                )
+                .arg(
+                    Arg::with_name("write-protect")
+                        .long("write-protect")
+                        .help("Write protects the inserted disk, so the machine cannot change it")
+                        .takes_value(false),
+                )
+                .arg(
+                    Arg::with_name("language")
+                        .long("language")
+                        .value_name("LANGUAGE")
+                        .help("Firmware language, which selects the ROMs and the keyboard layout [english, french, spanish, danish]")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("firmware")
+                        .long("firmware")
+                        .value_name("FIRMWARE")
+                        .help("Runs the Amstrad ROMs, the built-in minimal firmware, which needs no ROM files but only runs snapshots, or the built-in hardware diagnostics [amstrad, minimal, diagnostic]")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("os-rom")
+                        .long("os-rom")
+                        .value_name("ROM")
+                        .help("Loads the operating system from this file instead of the firmware's")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("basic-rom")
+                        .long("basic-rom")
+                        .value_name("ROM")
+                        .help("Loads BASIC from this file instead of the firmware's")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("amsdos-rom")
+                        .long("amsdos-rom")
+                        .value_name("ROM")
+                        .help("Loads AMSDOS from this file instead of the firmware's")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("audio-device")
+                        .long("audio-device")
+                        .value_name("DEVICE")
+                        .help("Plays the sound on this audio device instead of the default one, see the audiodevices command")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("audio-buffer")
+                        .long("audio-buffer")
+                        .value_name("FRAMES")
+                        .help("Frames per buffer of the audio device, smaller buffers lower the latency")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("audio-latency")
+                        .long("audio-latency")
+                        .value_name("MILLISECONDS")
+                        .help("Sound buffered ahead of the audio device, raise it if the sound crackles (default: 60)")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("frame-pacing")
+                        .long("frame-pacing")
+                        .value_name("PACING")
+                        .help("Presents the 50Hz frames at the refresh rate of the display by repeating them or blending the two latest ones, leave it off for variable refresh rate displays [repeat, blend]")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("refresh-rate")
+                        .long("refresh-rate")
+                        .value_name("HZ")
+                        .help("Refresh rate of the display for --frame-pacing (default: 60)")
+                        .requires("frame-pacing")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("paste")
+                        .long("paste")
+                        .value_name("TEXT_FILE")
+                        .help("Types the contents of a text file after power-on, e.g. a BASIC listing")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("lite")
+                        .long("lite")
+                        .help("Opens a plain window with just the screen, without menus, debug panels and status bar, for slow hosts")
+                        .conflicts_with_all(&["debug", "midi-out", "watch"])
+                        .takes_value(false),
+                )
+                .arg(
+                    Arg::with_name("scale")
+                        .long("scale")
+                        .value_name("SCALE")
+                        .help("Enlarges the window by 1, 2, 4 or to fit the desktop, the window system stretches the frame (default: 1)")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("storage")
+                        .long("storage")
+                        .value_name("DIRECTORY")
+                        .help("Saves states, screenshots and bug reports there, in a folder per game (default: storage)")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("layout")
+                        .long("layout")
+                        .value_name("LAYOUT")
+                        .help("Restores and saves the arrangement of debug panels (default: ronald.layout)")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("record")
+                        .long("record")
+                        .value_name("REPLAY")
+                        .help("Records a replay of the session (implies --deterministic)")
+                        .conflicts_with_all(&["headless", "rtc-time"])
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("media")
+                        .value_name("MEDIA")
+                        .help("Disk, snapshot, tape or cartridge image (detected automatically)")
+                        .multiple(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("replay")
+                .about("Plays back a recorded replay")
+                .arg(Arg::with_name("replay").value_name("REPLAY").required(true))
+                .arg(
+                    Arg::with_name("verify")
+                        .long("verify")
+                        .help("Plays back without video output and checks that the replay reproduces exactly")
+                        .takes_value(false),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("bench")
+                .about("Measures emulation speed without video output")
+                .arg(
+                    Arg::with_name("frames")
+                        .short("f")
+                        .long("frames")
+                        .value_name("FRAMES")
+                        .help("Number of frames to emulate (default: 500)")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("hash")
+                .about("Prints the hashes used to identify media in game databases")
+                .arg(Arg::with_name("media").value_name("MEDIA").required(true).multiple(true)),
+        )
+        .subcommand(
+            SubCommand::with_name("setup")
+                .about("Asks for the firmware, keyboard and audio settings and saves them to a config file")
+                .arg(
+                    Arg::with_name("config")
+                        .long("config")
+                        .value_name("CONFIG")
+                        .help("Config file to write (default: ronald.toml)")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("verify")
+                .about("Checks the firmware ROMs and media against known good dumps")
+                .arg(Arg::with_name("media").value_name("MEDIA").multiple(true))
+                .arg(
+                    Arg::with_name("language")
+                        .long("language")
+                        .value_name("LANGUAGE")
+                        .help("Firmware language, which selects the ROMs to check [english, french, spanish, danish]")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("rom")
+                        .long("rom")
+                        .value_name("ROM")
+                        .help("Checks another ROM file")
+                        .multiple(true)
+                        .number_of_values(1),
+                )
+                .arg(
+                    Arg::with_name("game-db")
+                        .long("game-db")
+                        .value_name("DATABASE")
+                        .help("Looks up the media in a game database"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("opcodes")
+                .about("Writes the table of all opcodes with their mnemonics, sizes and timings, and lists those without a timing")
+                .arg(
+                    Arg::with_name("format")
+                        .long("format")
+                        .value_name("FORMAT")
+                        .help("Format of the table")
+                        .possible_values(&["json", "markdown"])
+                        .default_value("json"),
+                )
+                .arg(
+                    Arg::with_name("output")
+                        .long("output")
+                        .value_name("FILE")
+                        .help("File to write the table to, instead of checking the timings only")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(zex_subcommand("zexdoc", "Runs the zexdoc Z80 instruction exerciser, which checks the documented flags"))
+        .subcommand(zex_subcommand("zexall", "Runs the zexall Z80 instruction exerciser, which checks all flags"))
+.subcommand(SubCommand::with_name("audiodevices").about("Lists the audio devices for --audio-device"))
+        .subcommand(
+            SubCommand::with_name("keyconfig")
+                .about("Checks a key map file and lists its bindings")
+                .arg(Arg::with_name("keymap").value_name("KEYMAP").required(true)),
+        )
+        .subcommand(
+            SubCommand::with_name("pok")
+                .about("Checks a POKE file and lists its trainers")
+                .arg(Arg::with_name("pok").value_name("POK").required(true)),
         )
+        .subcommand(
+            SubCommand::with_name("snapshot")
+                .about("Inspects and converts snapshot files")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    SubCommand::with_name("info")
+                        .about("Shows the machine state in a snapshot file or imports a raw memory dump")
+                        .arg(Arg::with_name("snapshot").value_name("SNAPSHOT").required(true))
+                        .arg(
+                            Arg::with_name("descriptor")
+                                .long("descriptor")
+                                .value_name("DESCRIPTOR")
+                                .help("Machine state for a raw 64K/128K memory dump (lines like \"pc = 0x4000\")")
+                                .takes_value(true),
+                        )
+                        .arg(
+                            Arg::with_name("output")
+                                .long("output")
+                                .value_name("SNA")
+                                .help("Writes the (imported) snapshot as an SNA file")
+                                .takes_value(true),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("convert")
+                        .about("Converts between SNA and JSON snapshots, the format is taken from the file extension")
+                        .arg(Arg::with_name("input").value_name("INPUT").required(true))
+                        .arg(Arg::with_name("output").value_name("OUTPUT").required(true))
+                        .arg(
+                            Arg::with_name("sna-version")
+                                .long("sna-version")
+                                .value_name("VERSION")
+                                .help("Version of a written SNA snapshot, 3 compresses the memory")
+                                .possible_values(&["1", "2", "3"])
+                                .default_value("2"),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("extract-screen")
+                        .about("Runs a snapshot until it has drawn a complete frame and saves the screen as PNG")
+                        .arg(Arg::with_name("snapshot").value_name("SNAPSHOT").required(true))
+                        .arg(Arg::with_name("png").value_name("PNG").required(true))
+                        .arg(
+                            Arg::with_name("frames")
+                                .long("frames")
+                                .value_name("FRAMES")
+                                .help("Number of VSYNCs to run to, the first frame is usually incomplete (default: 2)")
+                                .takes_value(true),
+                        ),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("snapshotdiff")
+                .about("Lists the registers, device registers and memory ranges that differ between two snapshots")
+                .arg(Arg::with_name("before").value_name("BEFORE").required(true))
+                .arg(Arg::with_name("after").value_name("AFTER").required(true)),
+        )
+        .subcommand(
+            SubCommand::with_name("convert")
+                .about("Converts disk images between the standard and extended formats and snapshots between versions")
+                .arg(Arg::with_name("input").value_name("INPUT").required(true))
+                .arg(Arg::with_name("output").value_name("OUTPUT").required(true))
+                .arg(
+                    Arg::with_name("disk-format")
+                        .long("disk-format")
+                        .value_name("FORMAT")
+                        .help("Format of the written disk image")
+                        .possible_values(&["standard", "extended"])
+                        .default_value("extended"),
+                )
+                .arg(
+                    Arg::with_name("normalize")
+                        .long("normalize")
+                        .help("Sorts the tracks and cuts or pads sectors to their declared sizes"),
+                )
+                .arg(
+                    Arg::with_name("sna-version")
+                        .long("sna-version")
+                        .value_name("VERSION")
+                        .help("Version of the written snapshot, 3 compresses the memory")
+                        .possible_values(&["1", "2", "3"])
+                        .default_value("2"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("basic")
+                .about("Lists and tokenizes Locomotive BASIC programs")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    SubCommand::with_name("list")
+                        .about("Lists a tokenized program from a file or from a disk image")
+                        .arg(Arg::with_name("file").value_name("FILE").required(true))
+                        .arg(
+                            Arg::with_name("name")
+                                .value_name("NAME")
+                                .help("Name of the program on the disk image"),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("tokenize")
+                        .about("Converts a program listing to its tokenized form")
+                        .arg(Arg::with_name("input").value_name("INPUT").required(true))
+                        .arg(Arg::with_name("output").value_name("OUTPUT").required(true)),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("cdt")
+                .about("Creates a tape image with binary or BASIC files, saved like the firmware saves them")
+                .arg(Arg::with_name("cdt").value_name("CDT").required(true))
+                .arg(Arg::with_name("files").value_name("FILE").required(true).multiple(true))
+                .arg(
+                    Arg::with_name("type")
+                        .long("type")
+                        .value_name("TYPE")
+                        .help("Type in the tape header")
+                        .possible_values(&["binary", "basic"])
+                        .default_value("binary"),
+                )
+                .arg(
+                    Arg::with_name("load")
+                        .long("load")
+                        .value_name("ADDRESS")
+                        .help("Load address of binary files, hex with 0x (default: 0x4000)")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("entry")
+                        .long("entry")
+                        .value_name("ADDRESS")
+                        .help("Entry address of binary files, hex with 0x (default: the load address)")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("name")
+                        .long("name")
+                        .value_name("NAME")
+                        .help("Tape name of a single file (default: the host file name without extension)")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("dsk")
+                .about("Creates AMSDOS disk images and copies files to and from them")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    SubCommand::with_name("new")
+                        .about("Creates a blank formatted disk image")
+                        .arg(Arg::with_name("dsk").value_name("DSK").required(true))
+                        .arg(
+                            Arg::with_name("format")
+                                .long("format")
+                                .value_name("FORMAT")
+                                .help("Disk format, system disks reserve two tracks for CP/M")
+                                .possible_values(&["data", "system"])
+                                .default_value("data"),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("add")
+                        .about("Copies host files to a disk image")
+                        .arg(Arg::with_name("dsk").value_name("DSK").required(true))
+                        .arg(Arg::with_name("files").value_name("FILE").required(true).multiple(true))
+                        .arg(
+                            Arg::with_name("type")
+                                .long("type")
+                                .value_name("TYPE")
+                                .help("AMSDOS header to add, raw files have none")
+                                .possible_values(&["raw", "binary", "basic"])
+                                .default_value("binary"),
+                        )
+                        .arg(
+                            Arg::with_name("load")
+                                .long("load")
+                                .value_name("ADDRESS")
+                                .help("Load address of binary files, hex with 0x (default: 0x4000)")
+                                .takes_value(true),
+                        )
+                        .arg(
+                            Arg::with_name("entry")
+                                .long("entry")
+                                .value_name("ADDRESS")
+                                .help("Entry address of binary files, hex with 0x (default: the load address)")
+                                .takes_value(true),
+                        )
+                        .arg(
+                            Arg::with_name("name")
+                                .long("name")
+                                .value_name("NAME")
+                                .help("AMSDOS name of a single file (default: the host file name)")
+                                .takes_value(true),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("extract")
+                        .about("Copies files from a disk image to host files, without their AMSDOS headers")
+                        .arg(Arg::with_name("dsk").value_name("DSK").required(true))
+                        .arg(
+                            Arg::with_name("names")
+                                .value_name("NAME")
+                                .help("Files to copy (default: all)")
+                                .multiple(true),
+                        )
+                        .arg(
+                            Arg::with_name("output")
+                                .long("output")
+                                .value_name("DIRECTORY")
+                                .help("Directory to write the files to")
+                                .default_value("."),
+                        ),
+                ),
+        )
+}
+
+fn zex_subcommand(name: &'static str, about: &'static str) -> App<'static, 'static> {
+    SubCommand::with_name(name)
+        .about(about)
         .arg(
-            Arg::with_name("system")
-                .short("s")
-                .long("system")
-                .value_name("SYSTEM")
-                .help("Selects the system to run")
+            Arg::with_name("tests")
+                .long("tests")
+                .value_name("TESTS")
+                .help("Comma separated numbers or parts of names of the instruction groups to run (default: all)")
                 .takes_value(true),
         )
-        .get_matches();
+        .arg(
+            Arg::with_name("threads")
+                .long("threads")
+                .value_name("THREADS")
+                .help("Number of groups to run at the same time")
+                .default_value("4"),
+        )
+        .arg(
+            Arg::with_name("timeout")
+                .long("timeout")
+                .value_name("DURATION")
+                .help("Time after which a group counts as failed, e.g. 60s")
+                .default_value("600s"),
+        )
+        .arg(Arg::with_name("list").long("list").help("Lists the instruction groups and exits"))
+}
+
+fn main() {
+    let mut matches = app().get_matches();
+
+    // a config file adds the options that are missing on the command line
+    let config_arguments = match matches.subcommand() {
+        ("run", Some(run_matches)) => match run_matches.value_of("config") {
+            Some(filename) => match config_arguments(filename, run_matches) {
+                Some(arguments) => arguments,
+                None => return,
+            },
+            None if std::path::Path::new(DEFAULT_CONFIG_FILE).exists() => match config_arguments(DEFAULT_CONFIG_FILE, run_matches) {
+                Some(arguments) => arguments,
+                None => return,
+            },
+            None => Vec::new(),
+        },
+        _ => Vec::new(),
+    };
+    if !config_arguments.is_empty() {
+        matches = app().get_matches_from(std::env::args().chain(config_arguments));
+    }
+
+    let log_levels = match matches.value_of("log") {
+        Some(levels) => levels.to_string(),
+        None => std::env::var("RUST_LOG").unwrap_or_else(|_| "warn".to_string()),
+    };
+    let log_history = logger::LogHistory::new_shared();
+    match logger::Logger::new(&log_levels, log_history.clone()) {
+        Ok(logger) => logger.install().expect("the logger is only installed once"),
+        Err(error) => {
+            println!("Invalid log levels \"{}\": {}", log_levels, error);
+            return;
+        }
+    }
+
+    match matches.subcommand() {
+        ("run", Some(matches)) => run(matches, log_history),
+        ("bench", Some(matches)) => bench(matches),
+        ("replay", Some(matches)) => play_replay(matches, log_history),
+        ("hash", Some(matches)) => {
+            for filename in matches.values_of("media").unwrap() {
+                match media::hash_media(filename) {
+                    Ok(hash) => println!("{:#010x}  {}", hash, filename),
+                    Err(error) => println!("Could not hash \"{}\": {}", filename, error),
+                }
+            }
+        }
+        ("setup", Some(matches)) => {
+            let filename = matches.value_of("config").unwrap_or(DEFAULT_CONFIG_FILE);
+            if let Err(error) = setup_wizard::SetupWizard::new(filename).run() {
+                println!("Setup failed: {}", error);
+            }
+        }
+        ("verify", Some(matches)) => verify(matches),
+        ("snapshotdiff", Some(matches)) => {
+            let mut snapshots = Vec::new();
+            for filename in [matches.value_of("before").unwrap(), matches.value_of("after").unwrap()].iter() {
+                match sna_file::Snapshot::load(filename) {
+                    Ok(snapshot) => snapshots.push(snapshot),
+                    Err(error) => {
+                        println!("Could not load snapshot \"{}\": {}", filename, error);
+                        return;
+                    }
+                }
+            }
+            for line in snapshot_diff::SnapshotDiff::compare(&snapshots[0], &snapshots[1]).lines() {
+                println!("{}", line);
+            }
+        }
+        ("opcodes", Some(matches)) => {
+            if !opcodes(matches) {
+                std::process::exit(1);
+            }
+        }
+        ("zexdoc", Some(matches)) => {
+            if !zex("rom/zexdoc.rom", matches) {
+                std::process::exit(1);
+            }
+        }
+        ("zexall", Some(matches)) => {
+            if !zex("rom/zexall.rom", matches) {
+                std::process::exit(1);
+            }
+        }
+        ("audiodevices", Some(_)) => {
+            for device in audio_output::list_devices() {
+                println!("{}", device);
+            }
+        }
+        ("keyconfig", Some(matches)) => {
+            let filename = matches.value_of("keymap").unwrap();
+            match key_mapper::KeyMapper::load(filename) {
+                Ok(key_mapper) => key_mapper.print_bindings(),
+                Err(error) => println!("Could not load key map \"{}\": {}", filename, error),
+            }
+        }
+        ("pok", Some(matches)) => {
+            let filename = matches.value_of("pok").unwrap();
+            let mut cheat_manager = cheats::CheatManager::new();
+            match cheat_manager.load_pok(filename) {
+                Ok(_) => cheat_manager.print_trainers(),
+                Err(error) => println!("Could not load POKE file \"{}\": {}", filename, error),
+            }
+        }
+        ("snapshot", Some(matches)) => snapshot(matches),
+        ("convert", Some(matches)) => convert(matches),
+        ("basic", Some(matches)) => basic(matches),
+        ("cdt", Some(matches)) => cdt(matches),
+        ("dsk", Some(matches)) => dsk(matches),
+        _ => unreachable!(),
+    }
+}
+
+fn run(matches: &ArgMatches, log_history: logger::LogHistoryShared) {
+    let seed = match matches.value_of("seed").unwrap_or("0").parse::<u64>() {
+        Ok(seed) => seed,
+        Err(error) => {
+            println!("Invalid seed: {}", error);
+            return;
+        }
+    };
+    let language = match keyboard::Language::from_name(matches.value_of("language").unwrap_or("english")) {
+        Some(language) => language,
+        None => {
+            println!("Unknown language \"{}\".", matches.value_of("language").unwrap());
+            return;
+        }
+    };
+    let firmware = match memory::Firmware::from_name(matches.value_of("firmware").unwrap_or("amstrad")) {
+        Some(firmware) => firmware,
+        None => {
+            println!("Unknown firmware \"{}\".", matches.value_of("firmware").unwrap());
+            return;
+        }
+    };
+    let power_on = match system::PowerOnState::from_names(matches.value_of("power-on-random").unwrap_or("none")) {
+        Some(power_on) => power_on,
+        None => {
+            println!("Unknown power-on devices \"{}\", expected crtc, gate-array, psg or all.", matches.value_of("power-on-random").unwrap());
+            return;
+        }
+    };
+    let roms = memory::RomPaths {
+        os: matches.value_of("os-rom").map(|path| path.to_string()),
+        basic: matches.value_of("basic-rom").map(|path| path.to_string()),
+        amsdos: matches.value_of("amsdos-rom").map(|path| path.to_string()),
+    };
+    if !is_firmware_installed(language, firmware, &roms) {
+        return;
+    }
+
+    let mut config = system::SystemConfig {
+        deterministic: matches.is_present("deterministic") || matches.is_present("record"),
+        seed,
+        ram_pattern: memory::RamPattern::from_name(matches.value_of("ram-pattern").unwrap_or("zeros")).unwrap(),
+        power_on,
+        language,
+        firmware,
+        roms,
+        crtc_type: crtc::CrtcType::from_name(matches.value_of("crtc").unwrap_or("0")).unwrap(),
+        ram_size: memory::RamSize::from_name(matches.value_of("ram").unwrap_or("64k")).unwrap(),
+        ..Default::default()
+    };
+    match matches.value_of("speech").and_then(speech::Interface::from_name) {
+        Some(speech::Interface::Ssa1) => config.register_expansion(speech::new_ssa1),
+        Some(speech::Interface::DkTronics) => config.register_expansion(speech::new_dktronics),
+        None => (),
+    }
+
+    let mut recording = None;
+    if matches.is_present("record") {
+        let mut replay = replay::Replay::new(&config, matches.is_present("rtc"));
+        for filename in matches.values_of("media").into_iter().flatten() {
+            // snapshots go into the replay, so it can be shared as a single file
+            let result = match media::MediaType::detect(filename) {
+                Some(media::MediaType::Snapshot) => replay.embed_snapshot(filename),
+                _ => replay.add_media(filename),
+            };
+            if let Err(error) = result {
+                println!("Could not read \"{}\": {}", filename, error);
+                return;
+            }
+        }
+        recording = Some(replay);
+    }
+
+    let mut cpc = Box::new(system::CPC464::new(config));
+
+    let parse_mode = match media::ParseMode::from_name(matches.value_of("media-parsing").unwrap_or("permissive")) {
+        Some(parse_mode) => parse_mode,
+        None => {
+            println!("Unknown media parsing mode \"{}\".", matches.value_of("media-parsing").unwrap());
+            return;
+        }
+    };
+    let mut library = media_library::MediaLibrary::new();
+    for filename in matches.values_of("media").into_iter().flatten() {
+        if !load_media(cpc.as_mut(), &mut library, filename, matches.is_present("autorun"), parse_mode) {
+            return;
+        }
+    }
+
+    let project_filename = match matches.value_of("project") {
+        Some(filename) => Some(filename.to_string()),
+        None => matches.values_of("media").into_iter().flatten().next().map(project::sidecar_filename),
+    };
+    if let Some(filename) = &project_filename {
+        match project::Project::load(filename) {
+            Ok(project) => {
+                cpc.get_project().replace(project);
+            }
+            Err(error) => {
+                println!("Could not load project \"{}\": {}", filename, error);
+                return;
+            }
+        }
+    }
+
+    if let Some(filename) = matches.value_of("paste") {
+        let text = match std::fs::read_to_string(filename) {
+            Ok(text) => text,
+            Err(error) => {
+                println!("Could not read \"{}\": {}", filename, error);
+                return;
+            }
+        };
+        let keyboard = cpc.get_keyboard();
+        let result = key_mapper::autotype_after(&mut keyboard.borrow_mut(), AUTORUN_DELAY_MICROSECONDS, &text, language);
+        if let Err(error) = result {
+            println!("Could not type \"{}\": {}", filename, error);
+            return;
+        }
+    }
+
+    if matches.is_present("tape-sound") {
+        cpc.get_mixer().borrow_mut().set_gain(mixer::AudioSource::Tape, 1.0);
+    }
+    if matches.is_present("fast-load") {
+        cpc.get_tape().borrow_mut().set_fast_load(true);
+    }
+    if matches.is_present("write-protect") {
+        cpc.set_write_protected(true);
+    }
+    if matches.is_present("vgm") {
+        cpc.enable_psg_capture(true);
+    }
+
+    let mut title_keymap = None;
+    let mut media_name = matches.values_of("media").into_iter().flatten().next().map(window_title::media_name);
+    if let Some(filename) = matches.value_of("game-db") {
+        let database = match game_database::FileGameDatabase::load(filename) {
+            Ok(database) => database,
+            Err(error) => {
+                println!("Could not load game database \"{}\": {}", filename, error);
+                return;
+            }
+        };
+
+        for (index, filename) in matches.values_of("media").into_iter().flatten().enumerate() {
+            let info = media::hash_media(filename).ok().and_then(|hash| database.lookup(hash));
+            if let Some(info) = info {
+                println!("Recognized \"{}\" as {}", filename, info.title);
+                if index == 0 {
+                    media_name = Some(info.title.clone()); // the window is named after the first medium
+                }
+                // TODO: apply the CRTC type once other CRTC types are emulated
+                title_keymap = title_keymap.or(info.keymap);
+            }
+        }
+    }
+
+    if matches.is_present("rtc") {
+        cpc.enable_real_time_clock();
+
+        if let Some(time) = matches.value_of("rtc-time") {
+            match time.parse::<i64>() {
+                Ok(time) => cpc.get_real_time_clock().unwrap().borrow_mut().pin_time(Some(time)),
+                Err(error) => {
+                    println!("Invalid clock time \"{}\": {}", time, error);
+                    return;
+                }
+            }
+        }
+    }
+
+    let mut midi_clock = None;
+    if let Some(device) = matches.value_of("midi-out") {
+        let source = matches.value_of("midi-tick").unwrap();
+        let source = match midi_clock::TickSource::parse(source) {
+            Some(source) => source,
+            None => {
+                println!("Invalid MIDI tick source \"{}\". Expected byte=<address> or frames=<count>.", source);
+                return;
+            }
+        };
+        let clocks_per_tick = match matches.value_of("midi-clocks-per-tick").unwrap_or("6").parse::<u64>() {
+            Ok(count) => count,
+            Err(error) => {
+                println!("Invalid number of MIDI clocks per tick: {}", error);
+                return;
+            }
+        };
+        let output = match midi_clock::FileMidiOutput::open(device) {
+            Ok(output) => output,
+            Err(error) => {
+                println!("Could not open MIDI device \"{}\": {}", device, error);
+                return;
+            }
+        };
+        midi_clock = Some(midi_clock::MidiClock::new_shared(Box::new(output), source, clocks_per_tick));
+    }
+
+    let mut patches = patches::PatchSet::new();
+    for path in matches.values_of("patches").into_iter().flatten() {
+        if let Err(error) = patches.load(path) {
+            println!("Could not load patches \"{}\": {}", path, error);
+            return;
+        }
+    }
+    for filename in matches.values_of("media").into_iter().flatten() {
+        if let Ok(hash) = media::hash_media(filename) {
+            patches.select_media(hash);
+        }
+    }
+
+    if matches.is_present("headless") {
+        std::process::exit(run_headless(cpc, matches, midi_clock, patches));
+    }
+
+    if matches.is_present("debug") {
+        cpc.activate_debugger();
+    }
+
+    let mut key_mapper = match matches.value_of("keymap").or(title_keymap.as_deref()) {
+        Some(filename) => match key_mapper::KeyMapper::load(filename) {
+            Ok(key_mapper) => key_mapper,
+            Err(error) => {
+                println!("Could not load key map \"{}\": {}", filename, error);
+                return;
+            }
+        },
+        None => key_mapper::KeyMapper::new(),
+    };
+    key_mapper.set_language(language);
+    if let Some(mode) = matches.value_of("keyboard-mode").and_then(key_mapper::KeyboardMode::from_name) {
+        key_mapper.set_mode(mode, &mut cpc.get_keyboard().borrow_mut());
+    }
+
+    let auto_repeat = match matches.value_of("auto-repeat") {
+        Some("off") => None,
+        Some(value) => match keyboard::AutoRepeat::parse(value) {
+            Some(auto_repeat) => Some(auto_repeat),
+            None => {
+                println!("Invalid auto repeat \"{}\", expected delay and period in milliseconds.", value);
+                return;
+            }
+        },
+        None => key_mapper.get_auto_repeat(),
+    };
+    key_mapper.set_auto_repeat(auto_repeat, &mut cpc.get_keyboard().borrow_mut());
+
+    if recording.is_some() {
+        cpc.get_keyboard().borrow_mut().start_recording();
+    }
+
+    let mut audio_settings = audio_output::AudioSettings {
+        device: matches.value_of("audio-device").map(|device| device.to_string()),
+        ..audio_output::AudioSettings::default()
+    };
+    if let Some(frames) = matches.value_of("audio-buffer") {
+        match frames.parse::<u32>() {
+            Ok(frames) => audio_settings.buffer_frames = Some(frames),
+            Err(error) => {
+                println!("Invalid audio buffer size \"{}\": {}", frames, error);
+                return;
+            }
+        }
+    }
+    if let Some(latency) = matches.value_of("audio-latency") {
+        match latency.parse::<u32>() {
+            Ok(latency) if latency > 0 => audio_settings.latency_milliseconds = latency,
+            _ => {
+                println!("Invalid audio latency \"{}\".", latency);
+                return;
+            }
+        }
+    }
+
+    let scale_name = matches.value_of("scale").unwrap_or("1");
+    let scale = match gui::WindowScale::from_name(scale_name) {
+        Some(scale) => scale,
+        None => {
+            println!("Unknown window scale \"{}\".", scale_name);
+            return;
+        }
+    };
+
+    let mut pacer = None;
+    if let Some(name) = matches.value_of("frame-pacing") {
+        let pacing = match frame_pacer::FramePacing::from_name(name) {
+            Some(pacing) => pacing,
+            None => {
+                println!("Unknown frame pacing \"{}\".", name);
+                return;
+            }
+        };
+        let refresh_rate = match matches.value_of("refresh-rate").unwrap_or("60").parse::<u32>() {
+            Ok(refresh_rate) if refresh_rate > 0 => refresh_rate,
+            _ => {
+                println!("Invalid refresh rate \"{}\".", matches.value_of("refresh-rate").unwrap());
+                return;
+            }
+        };
+        pacer = Some(frame_pacer::FramePacer::new(pacing, refresh_rate));
+    }
+
+    let (recording, mut cpc) = if matches.is_present("lite") {
+        let mut gui = match lite_gui::LiteGUI::new(cpc, key_mapper, scale) {
+            Ok(gui) => gui,
+            Err(error) => {
+                println!("Could not open the window: {}", error);
+                return;
+            }
+        };
+        gui.open_audio(&audio_settings);
+        if let Some(pacer) = pacer {
+            gui.set_frame_pacer(pacer);
+        }
+        gui.set_patches(patches);
+        if let Some(replay) = recording {
+            gui.set_recording(replay);
+        }
+        gui.run();
+
+        (gui.take_recording(), gui.into_system())
+    } else {
+        let mut gui = gui::GUI::new(cpc, key_mapper, log_history, scale);
+        gui.open_audio(&audio_settings);
+        if let Some(pacer) = pacer {
+            gui.set_frame_pacer(pacer);
+        }
+        if let Some(midi_clock) = midi_clock {
+            gui.set_midi_clock(midi_clock);
+        }
+        gui.set_patches(patches);
+        if matches.is_present("watch") {
+            gui.enable_hot_reload();
+        }
+        gui.set_media_library(library);
+        let mut storage = storage::StorageLayout::new(matches.value_of("storage").unwrap_or(storage::DEFAULT_ROOT));
+        let first_media = matches.values_of("media").into_iter().flatten().next();
+        if let (Some(hash), Some(name)) = (first_media.and_then(|filename| media::hash_media(filename).ok()), &media_name) {
+            storage.select_media(hash, name);
+        }
+        gui.set_storage(storage);
+        gui.set_media_name(media_name);
+        gui.load_layout(matches.value_of("layout").unwrap_or(DEFAULT_LAYOUT_FILE));
+        if let Some(replay) = recording {
+            gui.set_recording(replay);
+        }
+        gui.run();
+
+        (gui.take_recording(), gui.into_system())
+    };
+    if let Some(filename) = matches.value_of("vgm") {
+        write_psg_capture(cpc.as_mut(), filename);
+    }
+
+    if let Some(filename) = matches.value_of("export-analysis") {
+        export_analysis(cpc.as_ref(), filename);
+    }
+
+    let project = cpc.get_project();
+    if project.borrow().is_dirty() {
+        if let Err(error) = project.borrow_mut().save() {
+            println!("Could not save project \"{}\": {}", project_filename.unwrap_or_default(), error);
+        }
+    }
+
+    if let Some(mut replay) = recording {
+        let filename = matches.value_of("record").unwrap();
+        replay.finish(cpc.as_mut());
+        if let Err(error) = replay.save(filename) {
+            println!("Could not write replay \"{}\": {}", filename, error);
+        }
+    }
+}
+
+fn export_analysis(cpc: &dyn system::System, filename: &str) -> bool {
+    let project = cpc.get_project();
+    let result = analysis_file::write_analysis(filename, &cpc.read_coverage(), &project.borrow());
+    match result {
+        Ok(()) => true,
+        Err(error) => {
+            println!("Could not export analysis to \"{}\": {}", filename, error);
+            false
+        }
+    }
+}
+
+fn config_arguments(filename: &str, matches: &ArgMatches) -> Option<Vec<String>> {
+    // the settings of a config file as arguments to append, leaving out those already given
+    let config_file = match config_file::ConfigFile::load(filename) {
+        Ok(config_file) => config_file,
+        Err(error) => {
+            println!("Could not load config \"{}\": {}", filename, error);
+            return None;
+        }
+    };
+
+    let mut arguments = Vec::new();
+    for (name, value) in config_file.get_options() {
+        if matches.occurrences_of(name) > 0 {
+            continue;
+        }
+        match value {
+            Some(value) => arguments.push(format!("--{}={}", name, value)),
+            None => arguments.push(format!("--{}", name)),
+        }
+    }
+    if !matches.is_present("media") && !config_file.get_media().is_empty() {
+        arguments.push("--".to_string());
+        arguments.extend(config_file.get_media().iter().cloned());
+    }
+
+    Some(arguments)
+}
+
+fn is_firmware_installed(language: keyboard::Language, firmware: memory::Firmware, roms: &memory::RomPaths) -> bool {
+    if firmware != memory::Firmware::Amstrad {
+        return true; // built in
+    }
+
+    let (os_path, basic_path, _) = roms.resolve(language);
+    for path in [os_path, basic_path].iter() {
+        if !std::path::Path::new(path).exists() {
+            println!("The {} firmware is not installed, \"{}\" is missing. The setup command helps to find it.", language.name(), path);
+            return false;
+        }
+    }
+
+    true
+}
+
+fn load_media(
+    cpc: &mut dyn system::System,
+    library: &mut media_library::MediaLibrary,
+    filename: &str,
+    autorun: bool,
+    parse_mode: media::ParseMode,
+) -> bool {
+    let language = cpc.get_config().language;
+    match media::MediaType::detect(filename) {
+        Some(media::MediaType::Disk) => match dsk_file::Disk::load_with_mode(filename, parse_mode) {
+            Ok(disk) if !library.is_empty() => {
+                // further disks, e.g. the other sides of a game, wait in the library
+                library.add_disk(disk);
+                println!("Added \"{}\" to the disk library, press F8 to insert the next disk", filename);
+                true
+            }
+            Ok(disk) => {
+                if let Some(command) = amsdos::detect_run_command(&disk) {
+                    if autorun {
+                        let keyboard = cpc.get_keyboard();
+                        let text = format!("{}\n", command);
+                        let result = key_mapper::autotype_codes_after(&mut keyboard.borrow_mut(), AUTORUN_DELAY_MICROSECONDS, text.as_bytes(), language);
+                        if let Err(error) = result {
+                            println!("Could not type \"{}\": {}", command, error);
+                        }
+                    } else {
+                        println!("Type {} to start \"{}\" (or use --autorun)", command, filename);
+                    }
+                }
+
+                let index = library.add_disk(disk);
+                library.insert_disk(cpc, index);
+                true
+            }
+            Err(error) => {
+                println!("Could not load disk \"{}\": {}", filename, error);
+                false
+            }
+        },
+        Some(media::MediaType::Tape) => match cdt_file::Tape::load(filename) {
+            Ok(tape) => {
+                // AMSDOS redirects file access to the disk drive until |TAPE is typed, and the
+                // firmware asks for a key press before it starts reading
+                if autorun {
+                    let keyboard = cpc.get_keyboard();
+                    let result = key_mapper::autotype_codes_after(&mut keyboard.borrow_mut(), AUTORUN_DELAY_MICROSECONDS, b"|TAPE\nRUN\"\n ", language);
+                    if let Err(error) = result {
+                        println!("Could not type RUN\": {}", error);
+                    }
+                } else {
+                    println!("Type |TAPE and RUN\" and press a key to start \"{}\" (or use --autorun)", filename);
+                }
+
+                cpc.load_tape(tape);
+                true
+            }
+            Err(error) => {
+                println!("Could not load tape \"{}\": {}", filename, error);
+                false
+            }
+        },
+        Some(media::MediaType::Snapshot) => match sna_file::Snapshot::load(filename) {
+            Ok(snapshot) => {
+                cpc.load_snapshot(&snapshot);
+                true
+            }
+            Err(error) => {
+                println!("Could not load snapshot \"{}\": {}", filename, error);
+                false
+            }
+        },
+        Some(media_type) => {
+            println!("Media type {:?} of \"{}\" is not supported yet.", media_type, filename);
+            false
+        }
+        None => {
+            println!("Could not detect the media type of \"{}\".", filename);
+            false
+        }
+    }
+}
+
+fn play_replay(matches: &ArgMatches, log_history: logger::LogHistoryShared) {
+    let filename = matches.value_of("replay").unwrap();
+    let replay = match replay::Replay::load(filename) {
+        Ok(replay) => replay,
+        Err(error) => {
+            println!("Could not load replay \"{}\": {}", filename, error);
+            return;
+        }
+    };
 
-    let system = matches.value_of("system").unwrap_or("cpc464");
+    if let Err(error) = replay.verify_media() {
+        println!("Media of the replay does not match: {}", error);
+        return;
+    }
 
-    match system {
-        "cpc464" => {
-            match dsk_file::Disk::load("data/Fruity_Frank_1984_Kuma_Computers.dsk") {
-                Ok(_) => println!("LOAD OK"),
-                Err(error) => println!("LOAD ERROR: {}", error),
+    if !is_firmware_installed(replay.language, replay.firmware, &memory::RomPaths::default()) {
+        return;
+    }
+
+    let mut cpc = Box::new(system::CPC464::new(replay.config()));
+    let mut library = media_library::MediaLibrary::new(); // swapping disks would desynchronize the replay
+    for entry in &replay.media {
+        // replays were recorded with whatever the images contained, so they are repaired the same way
+        if !load_media(cpc.as_mut(), &mut library, &entry.path, false, media::ParseMode::Permissive) {
+            return;
+        }
+    }
+    if replay.real_time_clock {
+        cpc.enable_real_time_clock();
+    }
+
+    if matches.is_present("verify") {
+        if replay.verify(cpc.as_mut()) {
+            println!("Replay reproduced exactly ({} key events).", replay.key_events.len());
+        } else {
+            println!("Replay diverged from the recording.");
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    replay.start_playback(cpc.as_mut());
+    let mut gui = gui::GUI::new(cpc, key_mapper::KeyMapper::new(), log_history, gui::WindowScale::default());
+    gui.disable_input();
+    gui.run();
+}
+
+fn run_headless(
+    mut cpc: Box<dyn system::System>,
+    matches: &ArgMatches,
+    midi_clock: Option<midi_clock::MidiClockShared>,
+    mut patches: patches::PatchSet,
+) -> i32 {
+    let until_pc = match matches.value_of("until") {
+        Some(condition) => match parse_condition(condition) {
+            Some(address) => Some(address),
+            None => {
+                println!("Invalid condition \"{}\". Expected pc=<address>.", condition);
+                return 2;
             }
+        },
+        None => None,
+    };
 
-            let debug = matches.is_present("debug");
-            let mut cpc = Box::new(system::CPC464::new());
-            if debug {
-                cpc.activate_debugger();
+    let timeout = match matches.value_of("timeout") {
+        Some(timeout) => match parse_duration(timeout) {
+            Some(microseconds) => microseconds,
+            None => {
+                println!("Invalid timeout \"{}\". Expected e.g. 60s or 500ms.", timeout);
+                return 2;
             }
+        },
+        None => 60_000_000,
+    };
 
-            let mut gui = gui::GUI::new(cpc);
-            gui.run();
+    let mut io_trace = match matches.value_of("io-trace") {
+        Some(filename) => match bus::IoTraceWriter::create(filename) {
+            Ok(writer) => {
+                cpc.enable_io_trace(true);
+                Some((filename, writer))
+            }
+            Err(error) => {
+                println!("Could not write I/O trace to \"{}\": {}", filename, error);
+                return 2;
+            }
+        },
+        None => None,
+    };
+    if matches.is_present("dump-layers") {
+        cpc.enable_layer_capture(true);
+    }
+
+    let mut elapsed_microseconds: u64 = 0;
+    let status = loop {
+        if until_pc == Some(cpc.get_program_counter()) {
+            break 0;
+        }
+
+        if elapsed_microseconds >= timeout {
+            break if until_pc.is_some() { 1 } else { 0 };
+        }
+
+        let frame = elapsed_microseconds / 20_000;
+        elapsed_microseconds += cpc.emulate() as u64;
+        if elapsed_microseconds / 20_000 != frame {
+            if let Some(midi_clock) = &midi_clock {
+                midi_clock.borrow_mut().update(cpc.as_ref());
+            }
+            patches.update(cpc.as_mut());
+            // streamed every frame, so the trace of a long run does not pile up in memory
+            if let Some((filename, writer)) = &mut io_trace {
+                if let Err(error) = writer.write(&cpc.take_io_trace()) {
+                    println!("Could not write I/O trace to \"{}\": {}", filename, error);
+                    return 2;
+                }
+            }
+        }
+    };
+    if let Some(midi_clock) = &midi_clock {
+        midi_clock.borrow_mut().stop();
+    }
+
+    if matches.is_present("print-screen") {
+        print!("{}", cpc.read_screen_text());
+    }
+
+    if cpc.is_disk_dirty() {
+        println!("The disk was changed, but the changes are not saved in headless mode.");
+    }
+
+    if let Some(filename) = matches.value_of("dump-screen") {
+        if let Err(error) = cpc.get_screen().borrow().write_png(filename) {
+            println!("Could not write screen to \"{}\": {}", filename, error);
+            return 2;
         }
-        "zexdoc" => {
-            let mut zex_harness = system::ZexHarness::new("rom/zexdoc.rom");
-            zex_harness.emulate();
+    }
+
+    if let Some(prefix) = matches.value_of("dump-layers") {
+        match cpc.get_screen().borrow().write_layer_pngs(prefix) {
+            Ok(filenames) => println!("Wrote {}.", filenames.join(", ")),
+            Err(error) => {
+                println!("Could not write the layers to \"{}\": {}", prefix, error);
+                return 2;
+            }
         }
-        unknown_system => {
-            println!(
-                "Unknown system \"{}\". Valid systems are:\n\n\tcpc464\n\tzexdoc\n",
-                unknown_system
-            );
+    }
+
+    if let Some((filename, mut writer)) = io_trace {
+        if let Err(error) = writer.write(&cpc.take_io_trace()).and_then(|_| writer.finish()) {
+            println!("Could not write I/O trace to \"{}\": {}", filename, error);
+            return 2;
         }
     }
+
+    if let Some(filename) = matches.value_of("vgm") {
+        if !write_psg_capture(cpc.as_mut(), filename) {
+            return 2;
+        }
+    }
+
+    if let Some(filename) = matches.value_of("export-analysis") {
+        if !export_analysis(cpc.as_ref(), filename) {
+            return 2;
+        }
+    }
+
+    if let Some(filename) = matches.value_of("bug-report") {
+        if let Err(error) = bug_report::write_bug_report(cpc.as_ref(), filename) {
+            println!("Could not write bug report \"{}\": {}", filename, error);
+            return 2;
+        }
+    }
+
+    status
+}
+
+fn write_psg_capture(cpc: &mut dyn system::System, filename: &str) -> bool {
+    let (duration, writes) = cpc.take_psg_capture();
+    match vgm_file::write_vgm(filename, duration, &writes) {
+        Ok(()) => true,
+        Err(error) => {
+            println!("Could not write VGM file \"{}\": {}", filename, error);
+            false
+        }
+    }
+}
+
+fn parse_condition(condition: &str) -> Option<u16> {
+    parse_address(condition.strip_prefix("pc=")?)
+}
+
+fn parse_address(address: &str) -> Option<u16> {
+    match address.strip_prefix("0x") {
+        Some(hex) => u16::from_str_radix(hex, 16).ok(),
+        None => address.parse().ok(),
+    }
+}
+
+fn parse_duration(duration: &str) -> Option<u64> {
+    if let Some(milliseconds) = duration.strip_suffix("ms") {
+        return milliseconds.parse::<u64>().ok().map(|milliseconds| milliseconds * 1_000);
+    }
+
+    let seconds = duration.strip_suffix('s').unwrap_or(duration);
+    seconds.parse::<u64>().ok().map(|seconds| seconds * 1_000_000)
+}
+
+fn verify(matches: &ArgMatches) {
+    // exits with status 1 if anything is wrong, so scripts can check their setup
+    let language = match keyboard::Language::from_name(matches.value_of("language").unwrap_or("english")) {
+        Some(language) => language,
+        None => {
+            println!("Unknown language \"{}\".", matches.value_of("language").unwrap());
+            return;
+        }
+    };
+    let database = match matches.value_of("game-db") {
+        Some(filename) => match game_database::FileGameDatabase::load(filename) {
+            Ok(database) => Some(database),
+            Err(error) => {
+                println!("Could not load game database \"{}\": {}", filename, error);
+                return;
+            }
+        },
+        None => None,
+    };
+
+    let (os_path, basic_path) = memory::firmware_paths(language);
+    let mut roms = vec![os_path, basic_path, "rom/amsdos_0.5.rom".to_string()];
+    roms.extend(matches.values_of("rom").into_iter().flatten().map(|filename| filename.to_string()));
+
+    let mut verifications = Vec::new();
+    for filename in &roms {
+        verifications.push(checksums::verify_rom(filename).map_err(|error| (filename.as_str(), error)));
+    }
+    for filename in matches.values_of("media").into_iter().flatten() {
+        let verification = checksums::verify_media(filename).map(|mut verification| {
+            verification.known_as = database.as_ref().and_then(|database| database.lookup(verification.crc)).map(|info| info.title);
+            verification
+        });
+        verifications.push(verification.map_err(|error| (filename, error)));
+    }
+
+    let mut good = true;
+    for verification in verifications {
+        match verification {
+            Ok(verification) => {
+                verification.print();
+                good &= verification.is_good();
+            }
+            Err((filename, error)) => {
+                println!("Could not read \"{}\": {}", filename, error);
+                good = false;
+            }
+        }
+    }
+    if !good {
+        std::process::exit(1);
+    }
+}
+
+fn convert(matches: &ArgMatches) {
+    // the output gets the media type of the input, read with the same parsers the emulator uses
+    let input = matches.value_of("input").unwrap();
+    let output = matches.value_of("output").unwrap();
+    let contents = match media::MediaType::detect(input) {
+        Some(media::MediaType::Disk) => {
+            let mut disk = match dsk_file::Disk::load(input) {
+                Ok(disk) => disk,
+                Err(error) => {
+                    println!("Could not load disk image \"{}\": {}", input, error);
+                    return;
+                }
+            };
+            if matches.is_present("normalize") {
+                println!("Normalized {} sectors.", disk.normalize());
+            }
+
+            let format = dsk_file::DiskFormat::from_name(matches.value_of("disk-format").unwrap()).unwrap(); // checked by clap
+            let from = if disk.is_extended() { "extended" } else { "standard" };
+            println!("Converting from the {} to the {} disk format.", from, format.name());
+            disk.to_bytes_as(format)
+        }
+        Some(media::MediaType::Snapshot) => {
+            let snapshot = match sna_file::Snapshot::load(input) {
+                Ok(snapshot) => snapshot,
+                Err(error) => {
+                    println!("Could not load snapshot \"{}\": {}", input, error);
+                    return;
+                }
+            };
+
+            let version = matches.value_of("sna-version").unwrap().parse().unwrap(); // checked by clap
+            println!("Converting a version {} snapshot to version {}.", snapshot.version, version);
+            snapshot.to_bytes_with_version(version)
+        }
+        _ => {
+            println!("Only disk images and snapshots can be converted.");
+            return;
+        }
+    };
+
+    match contents.and_then(|contents| std::fs::write(output, contents)) {
+        Ok(_) => println!("Wrote \"{}\".", output),
+        Err(error) => println!("Could not write \"{}\": {}", output, error),
+    }
+}
+
+fn snapshot(matches: &ArgMatches) {
+    match matches.subcommand() {
+        ("info", Some(matches)) => {
+            let filename = matches.value_of("snapshot").unwrap();
+            let snapshot = match media::MediaType::detect(filename) {
+                Some(media::MediaType::Snapshot) => sna_file::Snapshot::load(filename),
+                _ => sna_file::Snapshot::from_raw_dump(filename, matches.value_of("descriptor")),
+            };
+
+            match snapshot {
+                Ok(snapshot) => {
+                    snapshot.print_info();
+
+                    if let Some(output) = matches.value_of("output") {
+                        if let Err(error) = snapshot.save(output) {
+                            println!("Could not write \"{}\": {}", output, error);
+                        }
+                    }
+                }
+                Err(error) => println!("Could not load snapshot \"{}\": {}", filename, error),
+            }
+        }
+        ("convert", Some(matches)) => {
+            let input = matches.value_of("input").unwrap();
+            let output = matches.value_of("output").unwrap();
+            let is_json = |filename: &str| filename.to_lowercase().ends_with(".json");
+
+            let snapshot = if is_json(input) {
+                std::fs::read_to_string(input).and_then(|text| sna_file::Snapshot::from_json(&text))
+            } else {
+                sna_file::Snapshot::load(input)
+            };
+            let snapshot = match snapshot {
+                Ok(snapshot) => snapshot,
+                Err(error) => {
+                    println!("Could not load snapshot \"{}\": {}", input, error);
+                    return;
+                }
+            };
+
+            let contents = if is_json(output) {
+                Ok(snapshot.to_json().into_bytes())
+            } else {
+                let version = matches.value_of("sna-version").unwrap().parse().unwrap(); // checked by clap
+                snapshot.to_bytes_with_version(version)
+            };
+            if let Err(error) = contents.and_then(|contents| std::fs::write(output, contents)) {
+                println!("Could not write \"{}\": {}", output, error);
+            }
+        }
+        ("extract-screen", Some(matches)) => {
+            let filename = matches.value_of("snapshot").unwrap();
+            let output = matches.value_of("png").unwrap();
+            let frames = match matches.value_of("frames").unwrap_or("2").parse::<u64>() {
+                Ok(frames) if frames > 0 => frames,
+                _ => {
+                    println!("Invalid number of frames \"{}\".", matches.value_of("frames").unwrap());
+                    return;
+                }
+            };
+            let snapshot = match sna_file::Snapshot::load(filename) {
+                Ok(snapshot) => snapshot,
+                Err(error) => {
+                    println!("Could not load snapshot \"{}\": {}", filename, error);
+                    return;
+                }
+            };
+
+            // the screen is drawn by emulating the snapshot like the screen tests do, so it shows split
+            // screens and palette changes the program makes during the frame
+            let config = system::SystemConfig {
+                deterministic: true,
+                ..Default::default()
+            };
+            let mut cpc = system::CPC464::new(config);
+            cpc.load_snapshot(&snapshot);
+            cpc.run_to_sync(bus::SyncSignal::Vertical, frames);
+            if let Err(error) = cpc.get_screen().borrow().write_png(output) {
+                println!("Could not write \"{}\": {}", output, error);
+            }
+        }
+        _ => unreachable!(),
+    }
+}
+
+fn basic(matches: &ArgMatches) {
+    match matches.subcommand() {
+        ("list", Some(matches)) => {
+            let filename = matches.value_of("file").unwrap();
+            let program = match matches.value_of("name") {
+                Some(name) => {
+                    let disk = match dsk_file::Disk::load(filename) {
+                        Ok(disk) => disk,
+                        Err(error) => {
+                            println!("Could not load disk \"{}\": {}", filename, error);
+                            return;
+                        }
+                    };
+
+                    match amsdos::read_file(&disk, name) {
+                        Some(file) => file.data,
+                        None => {
+                            println!("Could not find \"{}\" on disk. Files are: {}", name, amsdos::list_files(&disk).join(", "));
+                            return;
+                        }
+                    }
+                }
+                None => match std::fs::read(filename) {
+                    Ok(data) => data,
+                    Err(error) => {
+                        println!("Could not read \"{}\": {}", filename, error);
+                        return;
+                    }
+                },
+            };
+
+            print!("{}", basic::detokenize(&program));
+        }
+        ("tokenize", Some(matches)) => {
+            let input = matches.value_of("input").unwrap();
+            let output = matches.value_of("output").unwrap();
+
+            let text = match std::fs::read_to_string(input) {
+                Ok(text) => text,
+                Err(error) => {
+                    println!("Could not read \"{}\": {}", input, error);
+                    return;
+                }
+            };
+
+            match basic::tokenize(&text) {
+                Ok(program) => {
+                    if let Err(error) = std::fs::write(output, program) {
+                        println!("Could not write \"{}\": {}", output, error);
+                    }
+                }
+                Err(error) => println!("{}", error),
+            }
+        }
+        _ => unreachable!(),
+    }
+}
+
+fn dsk(matches: &ArgMatches) {
+    match matches.subcommand() {
+        ("new", Some(matches)) => {
+            let filename = matches.value_of("dsk").unwrap();
+            let kind = amsdos::DiskKind::from_name(matches.value_of("format").unwrap()).unwrap();
+
+            let mut disk = amsdos::create_disk(kind, filename);
+            match disk.save_as(filename) {
+                Ok(()) => println!("Created {} disk \"{}\"", kind.name(), filename),
+                Err(error) => println!("Could not write disk \"{}\": {}", filename, error),
+            }
+        }
+        ("add", Some(matches)) => {
+            let filename = matches.value_of("dsk").unwrap();
+            let files = match read_host_files(matches, |path| path.file_name().unwrap().to_string_lossy().to_string()) {
+                Some(files) => files,
+                None => return,
+            };
+
+            let mut disk = match dsk_file::Disk::load(filename) {
+                Ok(disk) => disk,
+                Err(error) => {
+                    println!("Could not load disk \"{}\": {}", filename, error);
+                    return;
+                }
+            };
+
+            for (host_filename, file) in files {
+                match amsdos::write_file(&mut disk, &file) {
+                    Ok(()) => println!("Added \"{}\" as {}", host_filename, file.name.to_uppercase()),
+                    Err(error) => {
+                        println!("Could not add \"{}\": {}", host_filename, error);
+                        return;
+                    }
+                }
+            }
+
+            if let Err(error) = disk.save() {
+                println!("Could not write disk \"{}\": {}", filename, error);
+            }
+        }
+        ("extract", Some(matches)) => {
+            let filename = matches.value_of("dsk").unwrap();
+            let directory = std::path::Path::new(matches.value_of("output").unwrap());
+            let disk = match dsk_file::Disk::load(filename) {
+                Ok(disk) => disk,
+                Err(error) => {
+                    println!("Could not load disk \"{}\": {}", filename, error);
+                    return;
+                }
+            };
+
+            let names = match matches.values_of("names") {
+                Some(names) => names.map(|name| name.to_string()).collect(),
+                None => amsdos::list_files(&disk),
+            };
+            for name in names {
+                let file = match amsdos::read_file(&disk, &name) {
+                    Some(file) => file,
+                    None => {
+                        println!("Could not find \"{}\" on disk. Files are: {}", name, amsdos::list_files(&disk).join(", "));
+                        return;
+                    }
+                };
+
+                // files without an extension keep their name without the dot on the host
+                let path = directory.join(file.name.trim_end_matches('.'));
+                match std::fs::write(&path, &file.data) {
+                    Ok(()) => println!("Extracted {} to \"{}\"", file.name, path.display()),
+                    Err(error) => {
+                        println!("Could not write \"{}\": {}", path.display(), error);
+                        return;
+                    }
+                }
+            }
+        }
+        _ => unreachable!(),
+    }
+}
+
+fn cdt(matches: &ArgMatches) {
+    let filename = matches.value_of("cdt").unwrap();
+    let files = match read_host_files(matches, |path| path.file_stem().unwrap().to_string_lossy().to_string()) {
+        Some(files) => files,
+        None => return,
+    };
+
+    let mut builder = cdt_file::TapeBuilder::new();
+    for (host_filename, file) in files {
+        match builder.add_file(&file) {
+            Ok(()) => println!("Added \"{}\" as {}", host_filename, file.name.to_uppercase()),
+            Err(error) => {
+                println!("Could not add \"{}\": {}", host_filename, error);
+                return;
+            }
+        }
+    }
+
+    if let Err(error) = builder.save(filename) {
+        println!("Could not write tape \"{}\": {}", filename, error);
+    }
+}
+
+fn read_host_files<'a>(matches: &'a ArgMatches, default_name: fn(&std::path::Path) -> String) -> Option<Vec<(&'a str, amsdos::File)>> {
+    // the files to put on a disk or tape with the headers the type, load and entry options ask for
+    let filenames: Vec<&str> = matches.values_of("files").unwrap().collect();
+    if matches.is_present("name") && filenames.len() > 1 {
+        println!("--name only works with a single file");
+        return None;
+    }
+    let addresses = ["load", "entry"].iter().map(|name| match matches.value_of(name) {
+        Some(address) => parse_address(address).ok_or_else(|| format!("Invalid {} address \"{}\"", name, address)).map(Some),
+        None => Ok(None),
+    });
+    let addresses: Result<Vec<Option<u16>>, String> = addresses.collect();
+    let (load_address, entry_address) = match addresses {
+        Ok(addresses) => (addresses[0], addresses[1]),
+        Err(error) => {
+            println!("{}", error);
+            return None;
+        }
+    };
+
+    let mut files = Vec::new();
+    for host_filename in filenames {
+        let data = match std::fs::read(host_filename) {
+            Ok(data) => data,
+            Err(error) => {
+                println!("Could not read \"{}\": {}", host_filename, error);
+                return None;
+            }
+        };
+        let name = match matches.value_of("name") {
+            Some(name) => name.to_string(),
+            None => default_name(std::path::Path::new(host_filename)),
+        };
+        // BASIC programs are loaded to where BASIC keeps its program, like SAVE writes them
+        let (file_type, load_address) = match matches.value_of("type").unwrap() {
+            "raw" => (None, 0),
+            "basic" => (Some(amsdos::FILE_TYPE_BASIC), basic::PROGRAM_START as u16),
+            _ => (Some(amsdos::FILE_TYPE_BINARY), load_address.unwrap_or(0x4000)),
+        };
+        files.push((
+            host_filename,
+            amsdos::File {
+                name,
+                file_type,
+                load_address,
+                entry_address: entry_address.unwrap_or(load_address),
+                data,
+            },
+        ));
+    }
+
+    Some(files)
+}
+
+fn opcodes(matches: &ArgMatches) -> bool {
+    let entries = opcode_table::generate();
+    if let Some(filename) = matches.value_of("output") {
+        let text = match matches.value_of("format").unwrap() {
+            "markdown" => opcode_table::to_markdown(&entries),
+            _ => opcode_table::to_json(&entries),
+        };
+        if let Err(error) = std::fs::write(filename, text) {
+            println!("Could not write \"{}\": {}", filename, error);
+            return false;
+        }
+        println!("Wrote {} opcodes to \"{}\"", entries.len(), filename);
+    }
+
+    // instructions without a timing stop the emulation when they are executed
+    let missing = opcode_table::missing_timings(&entries);
+    for entry in &missing {
+        println!("No timing for {}{:02x}: {}", entry.prefix, entry.opcode, entry.mnemonic);
+    }
+    println!("{} of {} opcodes have a timing", entries.len() - missing.len(), entries.len());
+
+    missing.is_empty()
+}
+
+fn zex(rom_path: &'static str, matches: &ArgMatches) -> bool {
+    // every instruction group runs in a harness of its own, so the groups run in parallel threads
+    let names = system::ZexHarness::new(rom_path).list_tests();
+    if matches.is_present("list") {
+        for (index, name) in names.iter().enumerate() {
+            println!("{:>3}  {}", index + 1, name);
+        }
+        return true;
+    }
+
+    let selected: Vec<usize> = match matches.value_of("tests") {
+        Some(tests) => {
+            let mut selected = Vec::new();
+            for test in tests.split(',').map(|test| test.trim()) {
+                let matching: Vec<usize> = match test.parse::<usize>() {
+                    Ok(number) if number >= 1 && number <= names.len() => vec![number - 1],
+                    _ => (0..names.len()).filter(|index| names[*index].contains(test)).collect(),
+                };
+                if matching.is_empty() {
+                    println!("No instruction group matches \"{}\", --list shows them", test);
+                    return false;
+                }
+                selected.extend(matching);
+            }
+            selected.sort_unstable();
+            selected.dedup();
+            selected
+        }
+        None => (0..names.len()).collect(),
+    };
+    let threads = match matches.value_of("threads").unwrap().parse::<usize>() {
+        Ok(threads) if threads > 0 => threads,
+        _ => {
+            println!("Invalid number of threads \"{}\"", matches.value_of("threads").unwrap());
+            return false;
+        }
+    };
+    let timeout = match parse_duration(matches.value_of("timeout").unwrap()) {
+        Some(microseconds) => std::time::Duration::from_micros(microseconds),
+        None => {
+            println!("Invalid timeout \"{}\". Expected e.g. 60s or 500ms.", matches.value_of("timeout").unwrap());
+            return false;
+        }
+    };
+
+    let queue = std::sync::Arc::new(std::sync::Mutex::new(selected.clone().into_iter()));
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let workers: Vec<_> = (0..threads.min(selected.len()))
+        .map(|_| {
+            let queue = queue.clone();
+            let sender = sender.clone();
+            std::thread::spawn(move || loop {
+                let index = match queue.lock().unwrap().next() {
+                    Some(index) => index,
+                    None => break,
+                };
+                let started = std::time::Instant::now();
+                let mut zex_harness = system::ZexHarness::new(rom_path);
+                zex_harness.select_test(index);
+                let result = zex_harness.run_test(timeout);
+                if sender.send((index, result, started.elapsed())).is_err() {
+                    break;
+                }
+            })
+        })
+        .collect();
+    drop(sender);
+
+    let mut passed = 0;
+    let mut timed_out = 0;
+    for (finished, (index, result, elapsed)) in receiver.iter().enumerate() {
+        let status = match &result {
+            system::ZexResult::Passed => {
+                passed += 1;
+                "OK".to_string()
+            }
+            system::ZexResult::Failed(message) => message.clone(),
+            system::ZexResult::TimedOut => {
+                timed_out += 1;
+                "TIMEOUT".to_string()
+            }
+        };
+        println!("[{:>2}/{}] {:>2} {:.<32} {} ({:.1}s)", finished + 1, selected.len(), index + 1, names[index], status, elapsed.as_secs_f32());
+    }
+    for worker in workers {
+        let _ = worker.join();
+    }
+
+    // groups whose thread panicked never report, they count as failed
+    let failed = selected.len() - passed - timed_out;
+    println!("{} passed, {} failed, {} timed out", passed, failed, timed_out);
+    passed == selected.len()
+}
+
+fn bench(matches: &ArgMatches) {
+    let frames = match matches.value_of("frames").unwrap_or("500").parse::<u64>() {
+        Ok(frames) => frames,
+        Err(error) => {
+            println!("Invalid number of frames: {}", error);
+            return;
+        }
+    };
+
+    // benchmark runs should be comparable
+    let config = system::SystemConfig {
+        deterministic: true,
+        seed: 0,
+        ram_pattern: memory::RamPattern::Zeros,
+        ..Default::default()
+    };
+    let mut cpc = system::CPC464::new(config);
+    let start = std::time::Instant::now();
+
+    let mut elapsed_microseconds: u64 = 0;
+    while elapsed_microseconds < frames * 20_000 {
+        elapsed_microseconds += cpc.emulate() as u64;
+    }
+
+    let duration = start.elapsed();
+    println!(
+        "Emulated {} frames in {:.2}s ({:.0}% of real time)",
+        frames,
+        duration.as_secs_f64(),
+        elapsed_microseconds as f64 / duration.as_micros() as f64 * 100.0
+    );
 }
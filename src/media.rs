@@ -0,0 +1,153 @@
+use std::io::Read;
+use std::path::Path;
+
+use crate::cdt_file;
+use crate::dsk_file;
+
+#[derive(Debug, PartialEq)]
+pub enum MediaType {
+    Disk,
+    Snapshot,
+    Tape,
+    Cartridge,
+}
+
+impl MediaType {
+    pub fn detect(filename: &str) -> Option<MediaType> {
+        let extension = Path::new(filename)
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase());
+
+        match extension.as_deref() {
+            Some("dsk") => return Some(MediaType::Disk),
+            Some("sna") => return Some(MediaType::Snapshot),
+            Some("cdt") | Some("tzx") => return Some(MediaType::Tape),
+            Some("cpr") => return Some(MediaType::Cartridge),
+            _ => (),
+        }
+
+        // fall back to the file signature if the extension is missing or unknown
+        let mut header = [0; 12];
+        let mut file = std::fs::File::open(filename).ok()?;
+        file.read_exact(&mut header).ok()?;
+
+        match &header {
+            _ if &header[0..8] == b"MV - CPC" || &header[0..8] == b"EXTENDED" => Some(MediaType::Disk),
+            _ if &header[0..8] == b"MV - SNA" => Some(MediaType::Snapshot),
+            _ if &header[0..7] == b"ZXTape!" => Some(MediaType::Tape),
+            _ if &header[0..4] == b"RIFF" && &header[8..12] == b"AMS!" => Some(MediaType::Cartridge),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ParseMode {
+    Strict,     // rejects images that do not follow their format, e.g. for archiving
+    Permissive, // repairs common mistakes of the tools that made the image and logs a warning
+}
+
+impl ParseMode {
+    pub fn from_name(name: &str) -> Option<ParseMode> {
+        match name {
+            "strict" => Some(ParseMode::Strict),
+            "permissive" => Some(ParseMode::Permissive),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ParseMode::Strict => "strict",
+            ParseMode::Permissive => "permissive",
+        }
+    }
+}
+
+// receives changed media, e.g. a disk that was written to, so it can be stored where it came from
+pub trait MediaWriter {
+    fn write(&mut self, contents: &[u8]) -> std::io::Result<()>;
+}
+
+struct FileWriter {
+    filename: String,
+}
+
+impl MediaWriter for FileWriter {
+    fn write(&mut self, contents: &[u8]) -> std::io::Result<()> {
+        std::fs::write(&self.filename, contents)
+    }
+}
+
+// where media came from, without the emulation having to know about files
+pub struct MediaSource {
+    pub name: String,           // shown to the user, e.g. in the disk menu
+    pub origin: Option<String>, // e.g. the path or URL, if there is one
+    writer: Option<Box<dyn MediaWriter>>,
+}
+
+impl MediaSource {
+    pub fn new(name: &str) -> MediaSource {
+        // media that only lives in memory, changes are lost unless it is saved elsewhere
+        MediaSource {
+            name: name.to_string(),
+            origin: None,
+            writer: None,
+        }
+    }
+
+    pub fn from_file(filename: &str) -> MediaSource {
+        let name = match Path::new(filename).file_name() {
+            Some(name) => name.to_string_lossy().to_string(),
+            None => filename.to_string(),
+        };
+
+        MediaSource {
+            name,
+            origin: Some(filename.to_string()),
+            writer: Some(Box::new(FileWriter {
+                filename: filename.to_string(),
+            })),
+        }
+    }
+
+    pub fn with_writer(mut self, writer: Box<dyn MediaWriter>) -> MediaSource {
+        self.writer = Some(writer);
+        self
+    }
+
+    pub fn can_write_back(&self) -> bool {
+        self.writer.is_some()
+    }
+
+    pub fn write_back(&mut self, contents: &[u8]) -> std::io::Result<()> {
+        match &mut self.writer {
+            Some(writer) => writer.write(contents),
+            None => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("\"{}\" cannot be written back to where it came from.", self.name),
+            )),
+        }
+    }
+}
+
+pub fn hash_media(filename: &str) -> std::io::Result<u32> {
+    match MediaType::detect(filename) {
+        Some(MediaType::Disk) => Ok(dsk_file::Disk::load(filename)?.content_hash()),
+        Some(MediaType::Tape) => Ok(cdt_file::Tape::load(filename)?.content_hash()),
+        _ => Ok(crc32(&std::fs::read(filename)?)),
+    }
+}
+
+pub fn crc32(data: &[u8]) -> u32 {
+    // CRC-32 as used by zip, which is what most media databases list
+    let mut crc = 0xffff_ffff;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 0x01 != 0 { (crc >> 1) ^ 0xedb8_8320 } else { crc >> 1 };
+        }
+    }
+
+    !crc
+}
@@ -0,0 +1,108 @@
+
+use crate::dsk_file;
+use crate::system;
+
+struct Entry {
+    name: String,
+    disk: Option<dsk_file::Disk>, // None while the disk is in the drive
+}
+
+// disks loaded for a session, e.g. all sides of a multi-disk game, ready to be swapped into drive A
+pub struct MediaLibrary {
+    entries: Vec<Entry>,
+    inserted: Option<usize>,
+}
+
+impl MediaLibrary {
+    pub fn new() -> MediaLibrary {
+        MediaLibrary {
+            entries: Vec::new(),
+            inserted: None,
+        }
+    }
+
+    pub fn add_disk(&mut self, disk: dsk_file::Disk) -> usize {
+        let name = disk.get_source().name.clone();
+        self.entries.push(Entry { name, disk: Some(disk) });
+
+        self.entries.len() - 1
+    }
+
+    pub fn insert_disk(&mut self, system: &mut dyn system::System, index: usize) {
+        if index >= self.entries.len() || self.inserted == Some(index) {
+            return;
+        }
+
+        self.eject_disk(system);
+        if let Some(disk) = self.entries[index].disk.take() {
+            system.load_disk(disk);
+            self.inserted = Some(index);
+        }
+    }
+
+    pub fn insert_next_disk(&mut self, system: &mut dyn system::System) {
+        // cycles through the library, starting over after the last disk
+        if self.entries.is_empty() {
+            return;
+        }
+
+        let next = match self.inserted {
+            Some(index) => (index + 1) % self.entries.len(),
+            None => 0,
+        };
+        self.insert_disk(system, next);
+    }
+
+    pub fn eject_disk(&mut self, system: &mut dyn system::System) {
+        // the disk goes back on the shelf, including any changes that were not saved yet
+        let disk = system.eject_disk(0);
+        match (self.inserted.take(), disk) {
+            (Some(index), Some(disk)) => self.entries[index].disk = Some(disk),
+            (None, Some(disk)) => {
+                self.add_disk(disk);
+            }
+            _ => (),
+        }
+    }
+
+    pub fn has_unsaved_changes(&self, system: &dyn system::System) -> bool {
+        system.is_disk_dirty() || self.entries.iter().any(|entry| matches!(&entry.disk, Some(disk) if disk.is_dirty()))
+    }
+
+    pub fn save_disks(&mut self, system: &mut dyn system::System) -> std::io::Result<()> {
+        // writes every changed disk back to the file it was loaded from
+        if system.is_disk_dirty() {
+            system.save_disk()?;
+        }
+        for disk in self.entries.iter_mut().filter_map(|entry| entry.disk.as_mut()) {
+            if disk.is_dirty() {
+                disk.save()?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn list_disks(&self) -> Vec<(&str, bool)> {
+        // names with whether the disk is in the drive
+        self.entries
+            .iter()
+            .enumerate()
+            .map(|(index, entry)| (entry.name.as_str(), self.inserted == Some(index)))
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl Default for MediaLibrary {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,10 +1,13 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::fs::*;
 use std::io;
 
 use std::cell::RefCell;
 use std::rc::Rc;
 
+use crate::expansion;
+use crate::keyboard;
+
 pub type MemoryShared = Rc<RefCell<Memory>>;
 
 pub trait Read {
@@ -27,8 +30,40 @@ pub trait Write {
     }
 }
 
+const MINIMAL_FIRMWARE: &[u8] = include_bytes!("../rom/minimal.rom"); // built from rom/minimal.asm
+const DIAGNOSTIC_FIRMWARE: &[u8] = include_bytes!("../rom/diagnostic.rom"); // built from rom/diagnostic.asm
+pub const DIAGNOSTIC_FAILURES_ADDRESS: usize = 0x8002; // where the diagnostic kernel counts failed tests
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Firmware {
+    #[default]
+    Amstrad, // the original ROMs, with BASIC and AMSDOS
+    Minimal, // the built-in replacement for the lower ROM, enough to run snapshots
+    Diagnostic, // a built-in kernel that tests the emulated hardware and prints the results
+}
+
+impl Firmware {
+    pub fn from_name(name: &str) -> Option<Firmware> {
+        match name {
+            "amstrad" => Some(Firmware::Amstrad),
+            "minimal" => Some(Firmware::Minimal),
+            "diagnostic" => Some(Firmware::Diagnostic),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Firmware::Amstrad => "amstrad",
+            Firmware::Minimal => "minimal",
+            Firmware::Diagnostic => "diagnostic",
+        }
+    }
+}
+
 pub struct ROM {
     data: Vec<u8>,
+    path: Option<String>, // None if built in
 }
 
 impl ROM {
@@ -37,7 +72,45 @@ impl ROM {
         // TODO: check ROM size (should be 16k)
         ROM {
             data: std::fs::read(path).unwrap_or_else(|_| panic!("ROM file \"{}\" could not be read.", path)),
+            path: Some(path.to_string()),
+        }
+    }
+
+    pub fn minimal_firmware() -> ROM {
+        ROM {
+            data: MINIMAL_FIRMWARE.to_vec(),
+            path: None,
+        }
+    }
+
+    pub fn diagnostic_firmware() -> ROM {
+        ROM {
+            data: DIAGNOSTIC_FIRMWARE.to_vec(),
+            path: None,
+        }
+    }
+
+    pub fn font() -> ROM {
+        // the character matrix for the frontend, from the minimal firmware if the OS ROM is missing
+        match std::fs::read("rom/os_464.rom") {
+            Ok(data) => ROM {
+                data,
+                path: Some("rom/os_464.rom".to_string()),
+            },
+            Err(_) => ROM::minimal_firmware(),
+        }
+    }
+
+    pub fn reload(&mut self) -> io::Result<()> {
+        // keeps the old contents if the file cannot be read, e.g. while it is being rewritten
+        if let Some(path) = &self.path {
+            self.data = std::fs::read(path)?;
         }
+        Ok(())
+    }
+
+    pub fn path(&self) -> Option<&str> {
+        self.path.as_deref()
     }
 }
 
@@ -47,6 +120,74 @@ impl Read for ROM {
     }
 }
 
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum RamPattern {
+    #[default]
+    Zeros,
+    Stripes, // alternating blocks of 0x00 and 0xff, like real CPC RAM after power-up
+    Random,
+}
+
+impl RamPattern {
+    pub fn from_name(name: &str) -> Option<RamPattern> {
+        match name {
+            "zeros" => Some(RamPattern::Zeros),
+            "stripes" => Some(RamPattern::Stripes),
+            "random" => Some(RamPattern::Random),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            RamPattern::Zeros => "zeros",
+            RamPattern::Stripes => "stripes",
+            RamPattern::Random => "random",
+        }
+    }
+}
+
+// the internal 64K and the expansion RAM banked in through the gate array's RAM configuration, like
+// the 6128 (128K) and the Dk'tronics expansions for the 464 (256K and 512K more)
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum RamSize {
+    #[default]
+    Kilobytes64,
+    Kilobytes128,
+    Kilobytes320,
+    Kilobytes576,
+}
+
+impl RamSize {
+    pub fn from_name(name: &str) -> Option<RamSize> {
+        match name.to_lowercase().as_str() {
+            "64k" => Some(RamSize::Kilobytes64),
+            "128k" => Some(RamSize::Kilobytes128),
+            "320k" => Some(RamSize::Kilobytes320),
+            "576k" => Some(RamSize::Kilobytes576),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            RamSize::Kilobytes64 => "64k",
+            RamSize::Kilobytes128 => "128k",
+            RamSize::Kilobytes320 => "320k",
+            RamSize::Kilobytes576 => "576k",
+        }
+    }
+
+    pub fn bytes(&self) -> usize {
+        match self {
+            RamSize::Kilobytes64 => 0x10000,
+            RamSize::Kilobytes128 => 0x20000,
+            RamSize::Kilobytes320 => 0x50000,
+            RamSize::Kilobytes576 => 0x90000,
+        }
+    }
+}
+
 pub struct RAM {
     data: Vec<u8>,
 }
@@ -58,6 +199,16 @@ impl RAM {
         }
     }
 
+    pub fn with_pattern(size: usize, pattern: RamPattern, seed: u64) -> RAM {
+        let data = match pattern {
+            RamPattern::Zeros => vec![0; size],
+            RamPattern::Stripes => (0..size).map(|address| if address & 0x04 == 0 { 0x00 } else { 0xff }).collect(),
+            RamPattern::Random => random_bytes(size, seed),
+        };
+
+        RAM { data }
+    }
+
     pub fn from_file(size: usize, path: &str, offset: usize) -> RAM {
         // TODO: better error handling
         // TODO: check if ROM fits
@@ -89,75 +240,553 @@ impl Write for RAM {
     }
 }
 
+const MAX_BANKING_EVENTS: usize = 256;
+
+// the 16K blocks seen at 0x0000, 0x4000, 0x8000 and 0xc000 in each RAM configuration, blocks 4 to 7
+// are in the selected 64K of expansion RAM
+const RAM_CONFIGURATIONS: [[usize; 4]; 8] = [
+    [0, 1, 2, 3],
+    [0, 1, 2, 7],
+    [4, 5, 6, 7],
+    [0, 3, 2, 7],
+    [0, 4, 2, 3],
+    [0, 5, 2, 3],
+    [0, 6, 2, 3],
+    [0, 7, 2, 3],
+];
+const MAX_WATCH_DIFFS: usize = 256;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Bank {
+    Ram(u8), // 16K block number
+    LowerRom,
+    UpperRom(u8),
+}
+
+impl std::fmt::Display for Bank {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Bank::Ram(block) => write!(f, "RAM {}", block),
+            Bank::LowerRom => write!(f, "lower ROM"),
+            Bank::UpperRom(rom_nr) => write!(f, "upper ROM {}", rom_nr),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct BankingEvent {
+    pub previous: [Bank; 4], // one entry per 16K slot (for reading)
+    pub current: [Bank; 4],
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WatchMode {
+    EveryWrite,
+    EveryFrame,
+}
+
+struct MemoryWatch {
+    id: usize,
+    start: usize,
+    mode: WatchMode,
+    contents: Vec<u8>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DiffOrigin {
+    Write(Option<u16>), // address of the instruction that wrote the byte, None until the CPU tags it
+    Frame,              // found by comparing the watched range at the end of a frame
+}
+
+#[derive(Clone, Debug)]
+pub struct MemoryDiff {
+    pub watch_id: usize,
+    pub origin: DiffOrigin,
+    pub start: usize,
+    pub before: Vec<u8>,
+    pub after: Vec<u8>,
+}
+
+impl MemoryDiff {
+    pub fn lines(&self) -> Vec<String> {
+        // a heading, then the changed rows of 8 bytes
+        let mut lines = vec![match self.origin {
+            DiffOrigin::Write(Some(program_counter)) => format!("Watch #{} changed by instruction at {:#06x}:", self.watch_id, program_counter),
+            _ => format!("Watch #{} changed:", self.watch_id),
+        }];
+
+        for (offset, (before, after)) in self.before.chunks(8).zip(self.after.chunks(8)).enumerate() {
+            if before != after {
+                let format = |bytes: &[u8]| bytes.iter().map(|byte| format!("{:02x}", byte)).collect::<Vec<_>>().join(" ");
+                lines.push(format!("{:#06x}: {} -> {}", self.start + offset * 8, format(before), format(after)));
+            }
+        }
+
+        lines
+    }
+}
+
 pub struct Memory {
     ram: RAM,
+    ram_configuration: u8,
+    ram_blocks: [usize; 4], // where the RAM at each 16K of the address space starts
     lower_rom: ROM,
     lower_rom_enabled: bool,
     upper_roms: HashMap<u8, ROM>,
     selected_upper_rom: u8,
     upper_rom_enabled: bool,
+    banking_events: VecDeque<BankingEvent>,
+    watches: Vec<MemoryWatch>,
+    next_watch_id: usize,
+    watch_diffs: VecDeque<MemoryDiff>,
+    overlays: Vec<expansion::ExpansionDeviceShared>,
+}
+
+pub fn random_bytes(count: usize, seed: u64) -> Vec<u8> {
+    // xorshift64, the state must never be zero
+    let mut state = seed.max(1);
+    (0..count)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state >> 24) as u8
+        })
+        .collect()
+}
+
+pub fn firmware_paths(language: keyboard::Language) -> (String, String) {
+    // the operating system and BASIC ROMs, localized versions have the language as a suffix
+    match language {
+        keyboard::Language::English => ("rom/os_464.rom".to_string(), "rom/basic_1.0.rom".to_string()),
+        _ => (
+            format!("rom/os_464_{}.rom", language.name()),
+            format!("rom/basic_1.0_{}.rom", language.name()),
+        ),
+    }
+}
+
+// ROM files that replace the ones of the firmware, e.g. patched or translated versions
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RomPaths {
+    pub os: Option<String>,
+    pub basic: Option<String>,
+    pub amsdos: Option<String>,
+}
+
+impl RomPaths {
+    pub fn resolve(&self, language: keyboard::Language) -> (String, String, String) {
+        // the operating system, BASIC and AMSDOS ROMs to load
+        let (os_path, basic_path) = firmware_paths(language);
+        (
+            self.os.clone().unwrap_or(os_path),
+            self.basic.clone().unwrap_or(basic_path),
+            self.amsdos.clone().unwrap_or_else(|| "rom/amsdos_0.5.rom".to_string()),
+        )
+    }
 }
 
 impl Memory {
-    pub fn new_shared() -> MemoryShared {
-        // TODO: receive rom paths as parameters
+    pub fn new_shared(
+        ram_pattern: RamPattern,
+        seed: u64,
+        language: keyboard::Language,
+        firmware: Firmware,
+        roms: &RomPaths,
+        ram_size: RamSize,
+    ) -> MemoryShared {
         let mut upper_roms = HashMap::new();
-        upper_roms.insert(0, ROM::from_file("rom/basic_1.0.rom"));
-        upper_roms.insert(7, ROM::from_file("rom/amsdos_0.5.rom"));
+        let lower_rom = match firmware {
+            Firmware::Amstrad => {
+                let (os_path, basic_path, amsdos_path) = roms.resolve(language);
+                upper_roms.insert(0, ROM::from_file(&basic_path));
+                upper_roms.insert(7, ROM::from_file(&amsdos_path));
+                ROM::from_file(&os_path)
+            }
+            Firmware::Minimal => ROM::minimal_firmware(), // without upper ROMs, RAM shows through
+            Firmware::Diagnostic => ROM::diagnostic_firmware(),
+        };
 
         let memory = Memory {
-            ram: RAM::new(0x10000),
-            lower_rom: ROM::from_file("rom/os_464.rom"),
+            ram: RAM::with_pattern(ram_size.bytes(), ram_pattern, seed),
+            ram_configuration: 0,
+            ram_blocks: [0x0000, 0x4000, 0x8000, 0xc000],
+            lower_rom,
             lower_rom_enabled: true,
             upper_roms,
             selected_upper_rom: 0,
             upper_rom_enabled: true,
+            banking_events: VecDeque::new(),
+            watches: Vec::new(),
+            next_watch_id: 0,
+            watch_diffs: VecDeque::new(),
+            overlays: Vec::new(),
         };
 
         Rc::new(RefCell::new(memory))
     }
 
+    pub fn reload_roms(&mut self) -> io::Result<()> {
+        // swaps in the current contents of the ROM files without resetting the machine
+        self.lower_rom.reload()?;
+        for rom in self.upper_roms.values_mut() {
+            rom.reload()?;
+        }
+        Ok(())
+    }
+
+    pub fn list_rom_files(&self) -> Vec<String> {
+        let mut rom_numbers: Vec<&u8> = self.upper_roms.keys().collect();
+        rom_numbers.sort();
+        std::iter::once(self.lower_rom.path())
+            .chain(rom_numbers.iter().map(|rom_number| self.upper_roms[*rom_number].path()))
+            .flatten()
+            .map(|path| path.to_string())
+            .collect()
+    }
+
     pub fn enable_lower_rom(&mut self, enable: bool) {
-        self.lower_rom_enabled = enable;
+        self.update_banking(|memory| memory.lower_rom_enabled = enable);
     }
 
     pub fn enable_upper_rom(&mut self, enable: bool) {
-        self.upper_rom_enabled = enable;
+        self.update_banking(|memory| memory.upper_rom_enabled = enable);
     }
 
     pub fn select_upper_rom(&mut self, upper_rom_nr: u8) {
-        self.selected_upper_rom = upper_rom_nr;
+        self.update_banking(|memory| memory.selected_upper_rom = upper_rom_nr);
+    }
+
+    pub fn read_rom_configuration(&self) -> (bool, bool, u8) {
+        // whether the lower and upper ROMs are enabled, and the selected upper ROM
+        (self.lower_rom_enabled, self.upper_rom_enabled, self.selected_upper_rom)
+    }
+
+    pub fn select_ram_configuration(&mut self, configuration: u8) {
+        // bits 0 to 2 select the configuration, bits 3 to 5 the 64K of expansion RAM
+        let expansion_banks = self.ram.data.len() / 0x10000 - 1;
+        if expansion_banks == 0 {
+            // the 464 ignores it, programs write it to detect extra RAM
+            log::debug!("Ignored RAM configuration {:#04x} without expansion RAM", configuration);
+            return;
+        }
+
+        self.update_banking(|memory| {
+            memory.ram_configuration = configuration & 0x3f;
+            let expansion_start = 0x10000 * (1 + (configuration as usize >> 3 & 0x07) % expansion_banks);
+            for (start, block) in memory.ram_blocks.iter_mut().zip(RAM_CONFIGURATIONS[configuration as usize & 0x07].iter()) {
+                *start = match block {
+                    0..=3 => block * 0x4000,
+                    _ => expansion_start + (block - 4) * 0x4000,
+                };
+            }
+        });
+    }
+
+    pub fn read_ram_configuration(&self) -> u8 {
+        self.ram_configuration
+    }
+
+    pub fn read_ram_size(&self) -> usize {
+        self.ram.data.len()
+    }
+
+    pub fn read_bank_configuration(&self) -> [Bank; 4] {
+        let ram_bank = |index: usize| Bank::Ram((self.ram_blocks[index] / 0x4000) as u8);
+        let upper_bank = match self.mapped_upper_rom() {
+            Some(rom_nr) if self.upper_rom_enabled => Bank::UpperRom(rom_nr),
+            _ => ram_bank(3),
+        };
+
+        [
+            if self.lower_rom_enabled { Bank::LowerRom } else { ram_bank(0) },
+            ram_bank(1),
+            ram_bank(2),
+            upper_bank,
+        ]
+    }
+
+    fn mapped_upper_rom(&self) -> Option<u8> {
+        // no expansion answers to an unused ROM number, so the internal ROM with BASIC stays selected
+        if self.upper_roms.contains_key(&self.selected_upper_rom) {
+            Some(self.selected_upper_rom)
+        } else if self.upper_roms.contains_key(&0) {
+            Some(0)
+        } else {
+            None
+        }
+    }
+
+    pub fn read_banking_events(&self) -> Vec<BankingEvent> {
+        self.banking_events.iter().cloned().collect()
+    }
+
+    pub fn add_watch(&mut self, start: usize, length: usize, mode: WatchMode) -> usize {
+        // by physical address like the other RAM accessors, so a watch follows its bytes when they are banked out
+        let end = (start + length).min(self.read_ram_size()).max(start);
+        let id = self.next_watch_id;
+        self.next_watch_id += 1;
+
+        self.watches.push(MemoryWatch {
+            id,
+            start,
+            mode,
+            contents: (start..end).map(|address| self.ram.read_byte(address)).collect(),
+        });
+
+        id
+    }
+
+    pub fn remove_watch(&mut self, id: usize) {
+        self.watches.retain(|watch| watch.id != id);
+    }
+
+    pub fn has_watches(&self) -> bool {
+        !self.watches.is_empty()
+    }
+
+    pub fn tag_watch_diffs(&mut self, program_counter: u16) {
+        // diffs are recorded while the instruction is still executing, so the CPU tags them afterwards
+        for diff in self.watch_diffs.iter_mut().rev() {
+            if diff.origin != DiffOrigin::Write(None) {
+                break;
+            }
+            diff.origin = DiffOrigin::Write(Some(program_counter));
+        }
+    }
+
+    pub fn capture_frame_watches(&mut self) {
+        for i in 0..self.watches.len() {
+            if self.watches[i].mode == WatchMode::EveryFrame {
+                let watch = &self.watches[i];
+                let contents = (watch.start..(watch.start + watch.contents.len()))
+                    .map(|address| self.ram.read_byte(address))
+                    .collect();
+                self.record_frame_diff(i, contents);
+            }
+        }
+    }
+
+    pub fn read_watch_diffs(&self) -> Vec<MemoryDiff> {
+        self.watch_diffs.iter().cloned().collect()
+    }
+
+    fn record_frame_diff(&mut self, watch_index: usize, contents: Vec<u8>) {
+        let watch = &mut self.watches[watch_index];
+        if watch.contents == contents {
+            return;
+        }
+
+        let before = std::mem::replace(&mut watch.contents, contents);
+        let diff = MemoryDiff {
+            watch_id: watch.id,
+            origin: DiffOrigin::Frame,
+            start: watch.start,
+            before,
+            after: watch.contents.clone(),
+        };
+        self.push_watch_diff(diff);
+    }
+
+    #[cfg(feature = "instrumentation")]
+    fn record_write_diff(&mut self, watch_index: usize, address: usize, value: u8) {
+        // only the written byte, so a loop filling a large watched range stays cheap
+        let watch = &mut self.watches[watch_index];
+        let before = std::mem::replace(&mut watch.contents[address - watch.start], value);
+        let diff = MemoryDiff {
+            watch_id: watch.id,
+            origin: DiffOrigin::Write(None),
+            start: address,
+            before: vec![before],
+            after: vec![value],
+        };
+        self.push_watch_diff(diff);
+    }
+
+    fn push_watch_diff(&mut self, diff: MemoryDiff) {
+        if self.watch_diffs.len() == MAX_WATCH_DIFFS {
+            self.watch_diffs.pop_front();
+        }
+        self.watch_diffs.push_back(diff);
+    }
+
+    fn update_banking(&mut self, change: impl FnOnce(&mut Memory)) {
+        let previous = self.read_bank_configuration();
+        change(self);
+        let current = self.read_bank_configuration();
+
+        if previous != current {
+            log::debug!(
+                "Memory banking changed: {} / {} / {} / {}",
+                current[0], current[1], current[2], current[3]
+            );
+
+            if self.banking_events.len() == MAX_BANKING_EVENTS {
+                self.banking_events.pop_front();
+            }
+            self.banking_events.push_back(BankingEvent { previous, current });
+        }
+    }
+
+    pub fn attach_overlay(&mut self, device: expansion::ExpansionDeviceShared) {
+        self.overlays.push(device);
+    }
+
+    pub fn detach_overlay(&mut self, name: &str) {
+        self.overlays.retain(|device| device.borrow().name() != name);
     }
 
     pub fn read_byte_from_ram(&self, address: usize) -> u8 {
+        // by physical address, as the gate array reads the screen, regardless of the RAM configuration
         self.ram.read_byte(address)
     }
+
+    pub fn write_byte_to_ram(&mut self, address: usize, value: u8) {
+        self.ram.write_byte(address, value);
+    }
+
+    pub fn read_byte_from_lower_rom(&self, address: usize) -> u8 {
+        self.lower_rom.read_byte(address)
+    }
+
+    pub fn read_byte_from_upper_rom(&self, address: usize) -> Option<u8> {
+        // from the selected upper ROM, whether it is enabled or not
+        self.mapped_upper_rom().map(|rom_nr| self.upper_roms[&rom_nr].read_byte(address))
+    }
 }
 
 impl Read for Memory {
     fn read_byte(&self, address: usize) -> u8 {
+        for device in &self.overlays {
+            if let Some(value) = device.borrow().read_memory(address) {
+                return value;
+            }
+        }
+
         // TODO: define proper constants
         if self.lower_rom_enabled && address < 0x4000 {
             return self.lower_rom.read_byte(address);
         }
 
         if self.upper_rom_enabled && address >= 0xc000 {
-            match self.upper_roms.get(&self.selected_upper_rom) {
-                Some(upper_rom) => {
-                    return upper_rom.read_byte(address - 0xc000);
-                }
-                None => {
-                    return self.ram.read_byte(address);
-                }
+            if let Some(rom_nr) = self.mapped_upper_rom() {
+                return self.upper_roms[&rom_nr].read_byte(address - 0xc000);
             }
         }
 
-        self.ram.read_byte(address)
+        self.ram.read_byte(self.ram_blocks[address >> 14] + (address & 0x3fff))
+    }
+
+    fn read_word(&self, address: usize) -> u16 {
+        // the Z80's address space wraps around after 0xffff
+        u16::from_le_bytes([self.read_byte(address & 0xffff), self.read_byte(address.wrapping_add(1) & 0xffff)])
     }
 }
 
 impl Write for Memory {
     fn write_byte(&mut self, address: usize, value: u8) {
-        self.ram.write_byte(address, value);
+        let physical_address = self.ram_blocks[address >> 14] + (address & 0x3fff);
+        self.ram.write_byte(physical_address, value);
+
+        #[cfg(feature = "instrumentation")]
+        for i in 0..self.watches.len() {
+            let watch = &self.watches[i];
+            let offset = physical_address.wrapping_sub(watch.start);
+            if watch.mode == WatchMode::EveryWrite && offset < watch.contents.len() && watch.contents[offset] != value {
+                self.record_write_diff(i, physical_address, value);
+            }
+        }
+    }
+
+    fn write_word(&mut self, address: usize, value: u16) {
+        let bytes = value.to_le_bytes();
+        self.write_byte(address & 0xffff, bytes[0]);
+        self.write_byte(address.wrapping_add(1) & 0xffff, bytes[1]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_memory(ram_size: RamSize) -> MemoryShared {
+        Memory::new_shared(RamPattern::Zeros, 0, keyboard::Language::English, Firmware::Minimal, &RomPaths::default(), ram_size)
+    }
+
+    #[test]
+    fn ram_accessors_use_physical_addresses() {
+        let memory = new_memory(RamSize::Kilobytes128);
+        let mut memory = memory.borrow_mut();
+        memory.select_ram_configuration(2); // blocks 4 to 7 at 0x0000 to 0xffff
+
+        memory.write_byte(0x0000, 0x12);
+        assert_eq!(memory.read_byte_from_ram(0x10000), 0x12);
+        assert_eq!(memory.read_byte_from_ram(0x0000), 0x00);
+
+        memory.write_byte_to_ram(0x4000, 0x34);
+        assert_eq!(memory.read_byte_from_ram(0x4000), 0x34);
+        assert_eq!(memory.read_byte(0x4000), 0x00);
+    }
+
+    #[cfg(feature = "instrumentation")]
+    #[test]
+    fn watches_follow_banked_out_ram() {
+        let memory = new_memory(RamSize::Kilobytes128);
+        let mut memory = memory.borrow_mut();
+        memory.add_watch(0x10000, 2, WatchMode::EveryWrite);
+
+        memory.write_byte(0x0000, 0x12); // block 0, not watched
+        memory.select_ram_configuration(2);
+        memory.write_byte(0x0001, 0x34); // block 4
+
+        let diffs = memory.read_watch_diffs();
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(diffs[0].start, 0x10001);
+        assert_eq!((diffs[0].before.as_slice(), diffs[0].after.as_slice()), (&[0x00][..], &[0x34][..]));
+    }
+
+    #[cfg(feature = "instrumentation")]
+    #[test]
+    fn describes_writes_to_watched_ram() {
+        let memory = new_memory(RamSize::Kilobytes64);
+        let mut memory = memory.borrow_mut();
+        let id = memory.add_watch(0x4000, 16, WatchMode::EveryWrite);
+
+        memory.write_byte(0x4009, 0xaa);
+        memory.tag_watch_diffs(0x1234);
+        memory.write_byte(0x3fff, 0xbb); // outside the watch
+        memory.write_byte(0x4000, 0x00); // unchanged
+
+        let diffs = memory.read_watch_diffs();
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(
+            diffs[0].lines(),
+            [format!("Watch #{} changed by instruction at 0x1234:", id), "0x4009: 00 -> aa".to_string()]
+        );
+    }
+
+    #[test]
+    fn describes_changes_at_the_end_of_a_frame() {
+        let memory = new_memory(RamSize::Kilobytes64);
+        let mut memory = memory.borrow_mut();
+        let id = memory.add_watch(0x8000, 24, WatchMode::EveryFrame);
+
+        memory.capture_frame_watches();
+        assert!(memory.read_watch_diffs().is_empty());
+
+        memory.write_byte(0x8001, 0x11);
+        memory.write_byte(0x8011, 0x22);
+        memory.capture_frame_watches();
+
+        let diffs = memory.read_watch_diffs();
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(
+            diffs[0].lines(),
+            [
+                format!("Watch #{} changed:", id),
+                "0x8000: 00 00 00 00 00 00 00 00 -> 00 11 00 00 00 00 00 00".to_string(),
+                "0x8010: 00 00 00 00 00 00 00 00 -> 00 22 00 00 00 00 00 00".to_string(),
+            ]
+        );
     }
 }
@@ -0,0 +1,153 @@
+use std::cell::RefCell;
+use std::io::Write;
+use std::rc::Rc;
+
+use crate::system;
+
+pub type MidiClockShared = Rc<RefCell<MidiClock>>;
+
+const TIMING_CLOCK: u8 = 0xf8;
+const START: u8 = 0xfa;
+const STOP: u8 = 0xfc;
+const TICKS_UNTIL_STOP: u64 = 4; // missed tick periods after which the music is considered stopped
+
+// implemented by frontends for whatever MIDI device they can reach
+pub trait MidiOutput {
+    fn send(&mut self, message: &[u8]);
+}
+
+// raw MIDI device files like /dev/snd/midiC1D0 accept the message bytes as they are
+pub struct FileMidiOutput {
+    file: std::fs::File,
+}
+
+impl FileMidiOutput {
+    pub fn open(path: &str) -> std::io::Result<FileMidiOutput> {
+        let file = std::fs::OpenOptions::new().append(true).create(true).open(path)?;
+
+        Ok(FileMidiOutput { file })
+    }
+}
+
+impl MidiOutput for FileMidiOutput {
+    fn send(&mut self, message: &[u8]) {
+        if let Err(error) = self.file.write_all(message).and_then(|_| self.file.flush()) {
+            log::warn!("Could not send MIDI message: {}", error);
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TickSource {
+    ByteChanged(u16), // e.g. the row counter of a tracker's player routine
+    Frames(u64),      // every n frames, following the 50Hz interrupt
+}
+
+impl TickSource {
+    pub fn parse(text: &str) -> Option<TickSource> {
+        // byte=<address> or frames=<count>, with hexadecimal addresses prefixed by 0x
+        let (kind, value) = text.split_once('=')?;
+        let value = value.trim();
+        let number = match value.strip_prefix("0x") {
+            Some(hex) => u64::from_str_radix(hex, 16).ok()?,
+            None => value.parse().ok()?,
+        };
+
+        match kind.trim() {
+            "byte" if number <= 0xffff => Some(TickSource::ByteChanged(number as u16)),
+            "frames" if number > 0 => Some(TickSource::Frames(number)),
+            _ => None,
+        }
+    }
+}
+
+// derives MIDI start, stop and timing clock messages from the music playing on the CPC, so a DAW
+// can follow the tempo of a tracker, e.g. with 6 clocks per row for 16th note rows at 24 PPQN
+pub struct MidiClock {
+    output: Box<dyn MidiOutput>,
+    source: TickSource,
+    clocks_per_tick: u64,
+    frame: u64,
+    previous_byte: Option<u8>,
+    last_tick_frame: Option<u64>,
+    tick_period: Option<u64>, // in frames, measured between the last two ticks
+    clocks_sent: u64, // since the last tick
+    running: bool,
+}
+
+impl MidiClock {
+    pub fn new_shared(output: Box<dyn MidiOutput>, source: TickSource, clocks_per_tick: u64) -> MidiClockShared {
+        let clock = MidiClock {
+            output,
+            source,
+            clocks_per_tick: clocks_per_tick.max(1),
+            frame: 0,
+            previous_byte: None,
+            last_tick_frame: None,
+            tick_period: None,
+            clocks_sent: 0,
+            running: false,
+        };
+
+        Rc::new(RefCell::new(clock))
+    }
+
+    pub fn update(&mut self, system: &dyn system::System) {
+        // called once per emulated frame, the clocks of a tick are spread over the expected tick period
+        self.frame += 1;
+        let ticked = match self.source {
+            TickSource::ByteChanged(address) => {
+                let value = system.read_ram(address as usize);
+                let changed = matches!(self.previous_byte, Some(previous) if previous != value);
+                self.previous_byte = Some(value);
+                changed
+            }
+            TickSource::Frames(count) => self.frame.checked_rem(count) == Some(0),
+        };
+
+        if ticked {
+            // clocks still owed for the previous tick are sent right away, so none get lost when the tempo rises
+            if self.running {
+                self.send_clocks(self.clocks_per_tick - self.clocks_sent);
+            } else {
+                self.output.send(&[START]);
+                self.running = true;
+            }
+            if let Some(last_tick_frame) = self.last_tick_frame {
+                self.tick_period = Some((self.frame - last_tick_frame).max(1));
+            }
+            self.last_tick_frame = Some(self.frame);
+            self.clocks_sent = 0;
+        }
+
+        if let (true, Some(last_tick_frame)) = (self.running, self.last_tick_frame) {
+            // until a second tick reveals the tempo, the clocks of a tick are sent at once
+            let elapsed = self.frame - last_tick_frame + 1;
+            let due = match self.tick_period {
+                Some(period) if elapsed > period * TICKS_UNTIL_STOP => {
+                    self.stop();
+                    return;
+                }
+                Some(period) => (self.clocks_per_tick * elapsed / period).min(self.clocks_per_tick),
+                None => self.clocks_per_tick,
+            };
+            self.send_clocks(due.saturating_sub(self.clocks_sent));
+        }
+    }
+
+    pub fn stop(&mut self) {
+        if self.running {
+            self.output.send(&[STOP]);
+            self.running = false;
+            self.last_tick_frame = None;
+            self.tick_period = None;
+        }
+    }
+
+    fn send_clocks(&mut self, count: u64) {
+        for _ in 0..count {
+            self.output.send(&[TIMING_CLOCK]);
+        }
+        self.clocks_sent += count;
+    }
+}
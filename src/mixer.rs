@@ -0,0 +1,194 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::rc::Rc;
+
+use crate::audio_ring;
+
+pub type MixerShared = Rc<RefCell<Mixer>>;
+
+pub const DEFAULT_SAMPLE_RATE: u32 = 44_100;
+const FRAME_MICROSECONDS: u32 = 20_000; // the rate is adjusted once per 50Hz frame
+const PPM: u64 = 1_000_000;
+const TARGET_FILL: f32 = 0.5; // of the output buffer
+const FILL_SMOOTHING: f32 = 0.1; // the device takes samples in chunks, so single readings jump
+const MAX_RATE_ADJUSTMENT_PPM: f32 = 5000.0; // 0.5%, too little to hear the pitch change
+const DRIFT_GAIN: f32 = 10.0; // ppm per frame and fill error, slowly learns the clock difference
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AudioSource {
+    Psg,
+    Tape,
+    Digiblaster, // 8 bit DAC on the printer port
+    Expansion,   // e.g. speech synthesizers
+}
+
+const SOURCES: [AudioSource; 4] = [
+    AudioSource::Psg,
+    AudioSource::Tape,
+    AudioSource::Digiblaster,
+    AudioSource::Expansion,
+];
+
+// combines all sound sources into mono samples, so frontends only have to play them back
+pub struct Mixer {
+    sample_rate: u32,
+    master_volume: f32,
+    muted: bool,
+    gains: [f32; 4],
+    elapsed: u64, // in microseconds times the sample rate times PPM
+    frame_microseconds: u32,
+    rate_adjustment_ppm: i32,
+    average_fill: f32,
+    drift_ppm: f32, // the learned difference between the clocks
+    samples: VecDeque<f32>,
+    output: Option<audio_ring::AudioProducer>, // replaces the samples queue once connected
+    dropped_samples: u64,
+}
+
+impl Mixer {
+    pub fn new_shared(sample_rate: u32) -> MixerShared {
+        let mixer = Mixer {
+            sample_rate,
+            master_volume: 1.0,
+            muted: false,
+            gains: [1.0, 0.0, 0.0, 1.0], // the cassette signal is not audible on real machines, and a Digiblaster is not connected by default
+            elapsed: 0,
+            frame_microseconds: 0,
+            rate_adjustment_ppm: 0,
+            average_fill: TARGET_FILL,
+            drift_ppm: 0.0,
+            samples: VecDeque::with_capacity(sample_rate as usize),
+            output: None,
+            dropped_samples: 0,
+        };
+
+        Rc::new(RefCell::new(mixer))
+    }
+
+    pub fn set_master_volume(&mut self, volume: f32) {
+        self.master_volume = volume.clamp(0.0, 1.0);
+    }
+
+    pub fn get_master_volume(&self) -> f32 {
+        self.master_volume
+    }
+
+    pub fn set_muted(&mut self, muted: bool) {
+        self.muted = muted;
+    }
+
+    pub fn is_muted(&self) -> bool {
+        self.muted
+    }
+
+    pub fn set_gain(&mut self, source: AudioSource, gain: f32) {
+        self.gains[source as usize] = gain.max(0.0);
+    }
+
+    pub fn get_gain(&self, source: AudioSource) -> f32 {
+        self.gains[source as usize]
+    }
+
+    pub fn get_sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn connect_output(&mut self, capacity: usize) -> audio_ring::AudioConsumer {
+        // for audio devices that pull samples on their own thread
+        let (producer, consumer) = audio_ring::audio_ring(capacity);
+        self.output = Some(producer);
+        self.samples.clear();
+        self.average_fill = TARGET_FILL;
+        self.drift_ppm = 0.0;
+        self.rate_adjustment_ppm = 0;
+
+        consumer
+    }
+
+    pub fn disconnect_output(&mut self) {
+        // e.g. when the audio device could not be started
+        self.output = None;
+        self.rate_adjustment_ppm = 0;
+    }
+
+    pub fn read_output_fill(&self) -> Option<f32> {
+        self.output.as_ref().map(|output| output.read_fill())
+    }
+
+    pub fn count_dropped_samples(&self) -> u64 {
+        // because the output did not keep up
+        self.dropped_samples
+    }
+
+    pub fn get_rate_adjustment_ppm(&self) -> i32 {
+        self.rate_adjustment_ppm
+    }
+
+    pub fn advance(&mut self, microseconds: u8) -> u32 {
+        // returns how many samples are due, the sources only have to be read if there are any.
+        // Samples are due at exact emulated times, however the instructions step the time
+        let rate = (PPM as i64 + self.rate_adjustment_ppm as i64) as u64;
+        self.elapsed += microseconds as u64 * self.sample_rate as u64 * rate;
+        let due = self.elapsed / (1_000_000 * PPM);
+        self.elapsed %= 1_000_000 * PPM;
+
+        self.frame_microseconds += microseconds as u32;
+        if self.frame_microseconds >= FRAME_MICROSECONDS {
+            self.frame_microseconds -= FRAME_MICROSECONDS;
+            self.adjust_rate();
+        }
+
+        due as u32
+    }
+
+    fn adjust_rate(&mut self) {
+        // the emulation is paced by the video frames and the audio device by its own clock, which
+        // never run at exactly the same speed. Resampling by the fill of the output buffer keeps
+        // them from drifting apart, which would otherwise end in crackling under- or overruns.
+        // Without an output, e.g. when capturing sound, the rate is exact
+        let fill = match &self.output {
+            Some(output) => output.read_fill(),
+            None => return,
+        };
+        self.average_fill += (fill - self.average_fill) * FILL_SMOOTHING;
+        let error = TARGET_FILL - self.average_fill;
+        self.drift_ppm = (self.drift_ppm + error * DRIFT_GAIN).clamp(-MAX_RATE_ADJUSTMENT_PPM, MAX_RATE_ADJUSTMENT_PPM);
+        let adjustment = self.drift_ppm + error * MAX_RATE_ADJUSTMENT_PPM;
+        self.rate_adjustment_ppm = adjustment.clamp(-MAX_RATE_ADJUSTMENT_PPM, MAX_RATE_ADJUSTMENT_PPM) as i32;
+    }
+
+    pub fn mix(&mut self, count: u32, levels: [f32; 4]) {
+        // levels are between 0 and 1 and in the order of SOURCES
+        let sample = if self.muted {
+            0.0
+        } else {
+            let mixed: f32 = SOURCES.iter().map(|source| levels[*source as usize] * self.gains[*source as usize]).sum();
+            (mixed * self.master_volume).min(1.0)
+        };
+
+        for _ in 0..count {
+            match &mut self.output {
+                Some(output) => {
+                    if !output.push(sample) {
+                        self.dropped_samples += 1;
+                    }
+                }
+                None => {
+                    // keep at most one second of samples if the frontend does not play them
+                    if self.samples.len() == self.sample_rate as usize {
+                        self.samples.pop_front();
+                    }
+                    self.samples.push_back(sample);
+                }
+            }
+        }
+    }
+
+    pub fn take_samples(&mut self) -> Vec<f32> {
+        self.samples.drain(..).collect()
+    }
+
+    pub fn buffered_samples(&self) -> usize {
+        self.samples.len()
+    }
+}
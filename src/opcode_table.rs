@@ -0,0 +1,89 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use serde::Serialize;
+
+use crate::instruction;
+use crate::memory;
+use crate::memory::Write;
+
+// the opcode pages of the Z80, by the bytes in front of the opcode. Indexed bit instructions have
+// their displacement between the prefix and the opcode
+const PAGES: [(&str, &[u8]); 7] = [
+    ("", &[]),
+    ("cb", &[0xcb]),
+    ("ed", &[0xed]),
+    ("dd", &[0xdd]),
+    ("fd", &[0xfd]),
+    ("ddcb", &[0xdd, 0xcb, 0x00]),
+    ("fdcb", &[0xfd, 0xcb, 0x00]),
+];
+
+#[derive(Clone, Debug, Serialize)]
+pub struct OpcodeEntry {
+    pub prefix: &'static str,
+    pub opcode: u8,
+    pub mnemonic: String, // as the disassembler shows it, with operand bytes of zero
+    pub size: usize,
+    pub timing: Option<u8>, // in NOPs, None where the timing table has no entry
+}
+
+pub fn generate() -> Vec<OpcodeEntry> {
+    // every opcode as the decoder sees it. Prefixes are pages of their own, and a DD or FD prefix
+    // in front of an instruction that does not use HL acts on its own, these are left out
+    let memory = Rc::new(RefCell::new(memory::RAM::new(0x10)));
+    let mut decoder = instruction::Decoder::new(memory.clone());
+    let mut entries = Vec::new();
+    for (prefix, prefix_bytes) in PAGES.iter() {
+        for opcode in 0..=0xff {
+            if ["", "dd", "fd"].contains(prefix) && [0xcb, 0xdd, 0xed, 0xfd].contains(&opcode) {
+                continue;
+            }
+
+            {
+                let mut memory = memory.borrow_mut();
+                for address in 0..0x10 {
+                    memory.write_byte(address, 0);
+                }
+                for (address, byte) in prefix_bytes.iter().chain(std::iter::once(&opcode)).enumerate() {
+                    memory.write_byte(address, *byte);
+                }
+            }
+            let (instruction, next_address) = decoder.decode_at(0);
+            if next_address <= prefix_bytes.len() {
+                continue;
+            }
+
+            entries.push(OpcodeEntry {
+                prefix,
+                opcode,
+                mnemonic: instruction.to_string(),
+                size: next_address,
+                timing: instruction.lookup_timing(),
+            });
+        }
+    }
+
+    entries
+}
+
+pub fn missing_timings(entries: &[OpcodeEntry]) -> Vec<&OpcodeEntry> {
+    entries.iter().filter(|entry| entry.timing.is_none()).collect()
+}
+
+pub fn to_json(entries: &[OpcodeEntry]) -> String {
+    serde_json::to_string_pretty(entries).expect("opcode entries always serialize")
+}
+
+pub fn to_markdown(entries: &[OpcodeEntry]) -> String {
+    let mut text = "| Opcode | Mnemonic | Size | NOPs |\n|---|---|---|---|\n".to_string();
+    for entry in entries {
+        let timing = match entry.timing {
+            Some(timing) => timing.to_string(),
+            None => "?".to_string(),
+        };
+        text += &format!("| {}{:02x} | `{}` | {} | {} |\n", entry.prefix, entry.opcode, entry.mnemonic, entry.size, timing);
+    }
+
+    text
+}
@@ -0,0 +1,139 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::actions;
+use crate::expression;
+use crate::memory;
+use crate::system;
+use crate::workbench;
+
+pub enum PaletteEvent {
+    None,
+    Chosen(actions::Action),
+    Closed,
+}
+
+pub struct TypedText {
+    pub text: Rc<RefCell<String>>,
+}
+
+impl minifb::InputCallback for TypedText {
+    fn add_char(&mut self, uni_char: u32) {
+        match std::char::from_u32(uni_char) {
+            Some(character) if !character.is_control() => self.text.borrow_mut().push(character),
+            _ => (),
+        }
+    }
+}
+
+// lists all actions matching the typed query, Enter runs the selected one and Escape closes the palette.
+// Queries starting with = are evaluated as expressions instead, e.g. =peekw(sp)
+pub struct CommandPalette {
+    window: minifb::Window,
+    query: Rc<RefCell<String>>,
+    selected: usize,
+    buffer: Vec<u32>,
+    font: memory::ROM,
+}
+
+impl CommandPalette {
+    pub fn open() -> Option<CommandPalette> {
+        let options = minifb::WindowOptions {
+            scale: minifb::Scale::X2,
+            ..minifb::WindowOptions::default()
+        };
+        let mut window = match minifb::Window::new("Command Palette", workbench::PANEL_WIDTH, workbench::PANEL_HEIGHT, options) {
+            Ok(window) => window,
+            Err(error) => {
+                log::error!("Could not open the command palette: {}", error);
+                return None;
+            }
+        };
+
+        let query = Rc::new(RefCell::new(String::new()));
+        window.set_input_callback(Box::new(TypedText { text: query.clone() }));
+
+        Some(CommandPalette {
+            window,
+            query,
+            selected: 0,
+            buffer: vec![0; workbench::PANEL_WIDTH * workbench::PANEL_HEIGHT],
+            font: memory::ROM::font(),
+        })
+    }
+
+    pub fn update(&mut self, system: &dyn system::System) -> PaletteEvent {
+        if !self.window.is_open() {
+            return PaletteEvent::Closed;
+        }
+
+        let query = self.query.borrow().clone();
+        if let Some(text) = query.strip_prefix('=') {
+            return self.update_expression(system, text);
+        }
+
+        let mut matches = actions::search(&self.query.borrow());
+        let keys = self.window.get_keys_pressed(minifb::KeyRepeat::Yes).unwrap_or_default();
+        for key in keys {
+            match key {
+                minifb::Key::Escape => return PaletteEvent::Closed,
+                minifb::Key::Enter => {
+                    return match matches.get(self.selected) {
+                        Some(info) => PaletteEvent::Chosen(info.action),
+                        None => PaletteEvent::None,
+                    };
+                }
+                minifb::Key::Up => self.selected = self.selected.saturating_sub(1),
+                minifb::Key::Down => self.selected += 1,
+                minifb::Key::Backspace => {
+                    self.query.borrow_mut().pop();
+                    self.selected = 0;
+                    matches = actions::search(&self.query.borrow());
+                }
+                _ => (),
+            }
+        }
+        self.selected = self.selected.min(matches.len().saturating_sub(1));
+
+        let mut lines = vec![format!("> {}_", self.query.borrow()), String::new()];
+        for (index, info) in matches.iter().take(workbench::PANEL_ROWS - 2).enumerate() {
+            let marker = if index == self.selected { '*' } else { ' ' };
+            lines.push(format!("{} {}", marker, info.name));
+        }
+        workbench::draw_text(&self.font, &mut self.buffer, workbench::PANEL_WIDTH, 1, &lines);
+        if let Err(error) = self.window.update_with_buffer(&self.buffer, workbench::PANEL_WIDTH, workbench::PANEL_HEIGHT) {
+            log::error!("Could not update the command palette: {}", error);
+        }
+
+        PaletteEvent::None
+    }
+
+    fn update_expression(&mut self, system: &dyn system::System, text: &str) -> PaletteEvent {
+        let keys = self.window.get_keys_pressed(minifb::KeyRepeat::Yes).unwrap_or_default();
+        for key in keys {
+            match key {
+                minifb::Key::Escape => return PaletteEvent::Closed,
+                minifb::Key::Backspace => {
+                    self.query.borrow_mut().pop();
+                }
+                _ => (),
+            }
+        }
+
+        let result = match expression::Expression::parse(text) {
+            Ok(expression) => match expression.evaluate(&expression::SystemContext(system)) {
+                Ok(value) => format!("{} ({:#06x})", value, value as u16),
+                Err(error) => error,
+            },
+            Err(_) if text.trim().is_empty() => String::new(),
+            Err(error) => error,
+        };
+        let lines = vec![format!("> {}_", self.query.borrow()), String::new(), result];
+        workbench::draw_text(&self.font, &mut self.buffer, workbench::PANEL_WIDTH, 1, &lines);
+        if let Err(error) = self.window.update_with_buffer(&self.buffer, workbench::PANEL_WIDTH, workbench::PANEL_HEIGHT) {
+            log::error!("Could not update the command palette: {}", error);
+        }
+
+        PaletteEvent::None
+    }
+}
@@ -0,0 +1,197 @@
+use serde::Deserialize;
+
+use crate::system;
+
+const BOOT_FRAMES: u64 = 75; // the firmware has cleared the memory and started BASIC by then
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum When {
+    Boot, // once, after the firmware is initialized
+    Frame(u64), // once, this many frames after the patch became active
+    Always, // on every frame, e.g. to keep a game from overwriting its trainer
+}
+
+impl When {
+    pub fn parse(text: &str) -> Option<When> {
+        let words: Vec<&str> = text.split_whitespace().collect();
+        match words.as_slice() {
+            ["boot"] => Some(When::Boot),
+            ["always"] => Some(When::Always),
+            ["frame", frame] => frame.parse().ok().map(When::Frame),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct PatchFileContents {
+    media: Option<u32>, // as printed by the hash command
+    #[serde(default, rename = "patch")]
+    patches: Vec<PatchContents>,
+}
+
+#[derive(Deserialize)]
+struct PatchContents {
+    name: Option<String>,
+    address: u16,
+    values: Vec<u8>,
+    when: Option<String>,
+}
+
+pub struct Patch {
+    pub name: Option<String>,
+    pub address: u16,
+    pub values: Vec<u8>,
+    pub when: When,
+}
+
+struct PatchFile {
+    filename: String,
+    media: Option<u32>,
+    patches: Vec<Patch>,
+    activated_frame: Option<u64>, // when the file started to apply, None while its media is not loaded
+    applied: Vec<bool>,
+}
+
+// pokes from TOML files that are applied to RAM automatically, e.g. trainers or compatibility
+// fixes. Files naming a media hash only apply while that media is loaded:
+//
+//     media = 0x1a2b3c4d
+//
+//     [[patch]]
+//     name = "Infinite lives"
+//     address = 0x8c3e
+//     values = [0x00]
+//     when = "always"
+pub struct PatchSet {
+    files: Vec<PatchFile>,
+    frame: u64,
+}
+
+impl Default for PatchSet {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PatchSet {
+    pub fn new() -> PatchSet {
+        PatchSet {
+            files: Vec::new(),
+            frame: 0,
+        }
+    }
+
+    pub fn load(&mut self, path: &str) -> std::io::Result<usize> {
+        // a directory loads all the .toml files in it, returns the number of patches loaded
+        if std::path::Path::new(path).is_dir() {
+            let mut filenames: Vec<String> = std::fs::read_dir(path)?
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| path.extension().map(|extension| extension == "toml").unwrap_or(false))
+                .map(|path| path.to_string_lossy().to_string())
+                .collect();
+            filenames.sort();
+
+            let mut count = 0;
+            for filename in filenames {
+                count += self.load_file(&filename)?;
+            }
+            return Ok(count);
+        }
+
+        self.load_file(path)
+    }
+
+    fn load_file(&mut self, filename: &str) -> std::io::Result<usize> {
+        let contents = std::fs::read_to_string(filename)?;
+        let contents: PatchFileContents =
+            toml::from_str(&contents).map_err(|error| invalid_data(filename, &error.to_string()))?;
+
+        let mut patches = Vec::new();
+        for patch in contents.patches {
+            let when = match &patch.when {
+                Some(when) => When::parse(when).ok_or_else(|| {
+                    invalid_data(filename, &format!("unknown time \"{}\", expected boot, always or frame <count>", when))
+                })?,
+                None => When::Boot,
+            };
+            if patch.values.is_empty() || patch.address as usize + patch.values.len() > 0x10000 {
+                return Err(invalid_data(filename, &format!("no values or too many values at {:#06x}", patch.address)));
+            }
+
+            patches.push(Patch {
+                name: patch.name,
+                address: patch.address,
+                values: patch.values,
+                when,
+            });
+        }
+
+        let count = patches.len();
+        self.files.push(PatchFile {
+            filename: filename.to_string(),
+            media: contents.media,
+            activated_frame: if contents.media.is_none() { Some(self.frame) } else { None },
+            applied: vec![false; count],
+            patches,
+        });
+
+        Ok(count)
+    }
+
+    pub fn select_media(&mut self, hash: u32) {
+        // activates the patches for the media, the ones for other media are deactivated
+        for file in &mut self.files {
+            if let Some(media) = file.media {
+                if media == hash {
+                    if file.activated_frame.is_none() {
+                        log::info!("Applying patches from \"{}\".", file.filename);
+                        file.activated_frame = Some(self.frame);
+                        file.applied.iter_mut().for_each(|applied| *applied = false);
+                    }
+                } else {
+                    file.activated_frame = None;
+                }
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.iter().all(|file| file.patches.is_empty())
+    }
+
+    pub fn update(&mut self, system: &mut dyn system::System) {
+        // called once per emulated frame
+        self.frame += 1;
+
+        for file in &mut self.files {
+            let activated_frame = match file.activated_frame {
+                Some(frame) => frame,
+                None => continue,
+            };
+
+            for (patch, applied) in file.patches.iter().zip(file.applied.iter_mut()) {
+                let due = match patch.when {
+                    When::Boot => !*applied && self.frame >= BOOT_FRAMES,
+                    When::Frame(frame) => !*applied && self.frame >= activated_frame + frame,
+                    When::Always => true,
+                };
+                if !due {
+                    continue;
+                }
+
+                for (offset, value) in patch.values.iter().enumerate() {
+                    system.write_ram(patch.address.wrapping_add(offset as u16) as usize, *value);
+                }
+                if !*applied {
+                    log::debug!("Applied patch {} at {:#06x}.", patch.name.as_deref().unwrap_or("without name"), patch.address);
+                }
+                *applied = true;
+            }
+        }
+    }
+}
+
+fn invalid_data(filename: &str, message: &str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, format!("{}: {}", filename, message))
+}
@@ -28,6 +28,10 @@ pub struct PeripheralInterface {
     direction_c_upper: Direction,
     mode_a_and_c_upper: Mode,
     mode_b_and_c_lower: Mode,
+    port_a: u8, // output latches, only driven onto the pins of ports configured as outputs
+    port_b: u8,
+    port_c: u8,
+    control: u8, // last mode definition
     crtc: crtc::CRTControllerShared,
     keyboard: keyboard::KeyboardShared,
     psg: psg::SoundGeneratorShared,
@@ -48,6 +52,10 @@ impl PeripheralInterface {
             direction_c_upper: Direction::Input,
             mode_a_and_c_upper: Mode::Basic,
             mode_b_and_c_lower: Mode::Basic,
+            port_a: 0,
+            port_b: 0,
+            port_c: 0,
+            control: 0x9b, // all ports are inputs after a reset
             crtc,
             keyboard,
             psg,
@@ -58,6 +66,7 @@ impl PeripheralInterface {
     }
 
     pub fn read_byte(&self, port: u16) -> u8 {
+        // ports configured as outputs read back their latches
         let function = (port >> 8) & 0x03;
 
         match function {
@@ -65,7 +74,7 @@ impl PeripheralInterface {
                 if self.direction_a == Direction::Input {
                     self.psg.borrow().read_byte()
                 } else {
-                    0
+                    self.port_a
                 }
             }
             1 => {
@@ -82,80 +91,116 @@ impl PeripheralInterface {
 
                     value
                 } else {
-                    0
+                    self.port_b
                 }
             }
-            2 => 0,
-            3 => 0,
+            2 => {
+                // nothing drives the pins of port C on the CPC, so halves configured as inputs float high
+                let mut value = 0xff;
+                if self.direction_c_lower == Direction::Output {
+                    value = (value & 0xf0) | (self.port_c & 0x0f);
+                }
+                if self.direction_c_upper == Direction::Output {
+                    value = (value & 0x0f) | (self.port_c & 0xf0);
+                }
+                value
+            }
+            3 => 0xff, // the control register cannot be read
             _ => unreachable!(),
         }
     }
 
+    pub fn read_ports(&self) -> [u8; 4] {
+        // ports A, B and C and the control register, as stored in snapshots
+        [self.read_byte(0xf400), self.read_byte(0xf500), self.port_c, self.control]
+    }
+
     pub fn write_byte(&mut self, port: u16, value: u8) {
+        // writes always go to the latches, even of ports configured as inputs
         let function = (port >> 8) & 0x03;
 
         match function {
             0 => {
-                if self.direction_a == Direction::Output {
-                    self.psg.borrow_mut().write_byte(value);
-                }
+                self.port_a = value;
+                self.update_port_a();
             }
-            1 => (),
+            1 => self.port_b = value, // the pins of port B are only connected to inputs
             2 => {
-                if self.direction_c_lower == Direction::Output {
-                    self.keyboard
-                        .borrow_mut()
-                        .set_active_line(value as usize & 0x0f);
-                }
-
-                if self.direction_c_upper == Direction::Output {
-                    self.psg.borrow_mut().perform_function((value >> 6) & 0x03);
-                    self.tape
-                        .borrow_mut()
-                        .write_sample((value >> 5) & 0x01 != 0);
-                    self.tape.borrow_mut().switch_motor(value & 0x10 != 0);
-                }
+                self.port_c = value;
+                self.update_port_c();
             }
             3 => {
                 if value & 0x80 != 0 {
+                    self.define_mode(value);
+                } else {
+                    // bit set/reset of a single output of port C
+                    let bit = 1 << ((value >> 1) & 0x07);
                     if value & 0x01 != 0 {
-                        self.direction_c_lower = Direction::Input;
+                        self.port_c |= bit;
                     } else {
-                        self.direction_c_lower = Direction::Output;
+                        self.port_c &= !bit;
                     }
+                    self.update_port_c();
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
 
-                    if value & 0x02 != 0 {
-                        self.direction_b = Direction::Input;
-                    } else {
-                        self.direction_b = Direction::Output;
-                    }
+    fn define_mode(&mut self, value: u8) {
+        // a mode definition clears all output latches, also if the directions stay the same
+        self.control = value;
+        self.direction_c_lower = direction(value & 0x01);
+        self.direction_b = direction(value & 0x02);
+        self.mode_b_and_c_lower = if value & 0x04 != 0 { Mode::Strobed } else { Mode::Basic };
+        self.direction_c_upper = direction(value & 0x08);
+        self.direction_a = direction(value & 0x10);
+        self.mode_a_and_c_upper = match (value & 0x60) >> 5 {
+            0 => Mode::Basic,
+            1 => Mode::Strobed,
+            _ => Mode::Bidirectional,
+        };
+        if value & 0x64 != 0 {
+            // the CPC has no handshake lines, port C works like in the basic mode
+            log::debug!("PPI mode definition {:#04x} selects a strobed mode.", value);
+        }
 
-                    if value & 0x04 != 0 {
-                        self.mode_b_and_c_lower = Mode::Strobed;
-                    } else {
-                        self.mode_b_and_c_lower = Mode::Basic;
-                    }
+        self.port_a = 0;
+        self.port_b = 0;
+        self.port_c = 0;
+        self.update_port_a();
+        self.update_port_c();
+    }
 
-                    if value & 0x08 != 0 {
-                        self.direction_c_upper = Direction::Input;
-                    } else {
-                        self.direction_c_upper = Direction::Output;
-                    }
+    fn update_port_a(&mut self) {
+        // port A is the data bus of the PSG
+        if self.direction_a == Direction::Output {
+            self.psg.borrow_mut().write_byte(self.port_a);
+        }
+    }
 
-                    if value & 0x10 != 0 {
-                        self.direction_a = Direction::Input;
-                    } else {
-                        self.direction_a = Direction::Output;
-                    }
+    fn update_port_c(&mut self) {
+        let value = self.port_c;
+        if self.direction_c_lower == Direction::Output {
+            self.keyboard
+                .borrow_mut()
+                .set_active_line(value as usize & 0x0f);
+        }
 
-                    self.mode_a_and_c_upper = match (value & 0x60) >> 5 {
-                        0 => Mode::Basic,
-                        1 => Mode::Strobed,
-                        _ => Mode::Bidirectional,
-                    }
-                }
-            }
-            _ => unreachable!(),
+        if self.direction_c_upper == Direction::Output {
+            self.psg.borrow_mut().perform_function((value >> 6) & 0x03);
+            self.tape
+                .borrow_mut()
+                .write_sample((value >> 5) & 0x01 != 0);
+            self.tape.borrow_mut().switch_motor(value & 0x10 != 0);
         }
     }
 }
+
+fn direction(bit: u8) -> Direction {
+    if bit != 0 {
+        Direction::Input
+    } else {
+        Direction::Output
+    }
+}
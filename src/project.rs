@@ -0,0 +1,154 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+use serde::{Deserialize, Serialize};
+
+pub type ProjectShared = Rc<RefCell<Project>>;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DataRegion {
+    pub start: u16,
+    pub end: u16, // inclusive
+}
+
+// labels, comments and data regions entered while debugging a program, kept in a JSON file next
+// to its media so they are there again in the next session
+#[derive(Default, Serialize, Deserialize)]
+pub struct Project {
+    labels: BTreeMap<u16, String>,
+    comments: BTreeMap<u16, String>,
+    data_regions: Vec<DataRegion>,
+    #[serde(skip)]
+    filename: Option<String>,
+    #[serde(skip)]
+    dirty: bool,
+}
+
+impl Project {
+    pub fn new_shared() -> ProjectShared {
+        Rc::new(RefCell::new(Project::default()))
+    }
+
+    pub fn load(filename: &str) -> std::io::Result<Project> {
+        // a project that was not saved yet starts out empty
+        let mut project = match std::fs::read_to_string(filename) {
+            Ok(contents) => serde_json::from_str(&contents).map_err(|error| invalid_data(error.to_string()))?,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Project::default(),
+            Err(error) => return Err(error),
+        };
+        project.filename = Some(filename.to_string());
+
+        Ok(project)
+    }
+
+    pub fn save(&mut self) -> std::io::Result<()> {
+        let filename = match &self.filename {
+            Some(filename) => filename,
+            None => return Ok(()), // nothing to save for sessions without a project file
+        };
+        let contents = serde_json::to_string_pretty(self).map_err(|error| invalid_data(error.to_string()))?;
+        std::fs::write(filename, contents)?;
+        self.dirty = false;
+
+        Ok(())
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    pub fn set_label(&mut self, address: u16, label: Option<String>) {
+        match label {
+            Some(label) => self.labels.insert(address, label),
+            None => self.labels.remove(&address),
+        };
+        self.dirty = true;
+    }
+
+    pub fn read_label(&self, address: u16) -> Option<&str> {
+        self.labels.get(&address).map(|label| label.as_str())
+    }
+
+    pub fn read_labels(&self) -> impl Iterator<Item = (u16, &str)> {
+        self.labels.iter().map(|(address, label)| (*address, label.as_str()))
+    }
+
+    pub fn find_label(&self, label: &str) -> Option<u16> {
+        self.labels.iter().find(|(_, name)| name.as_str() == label).map(|(address, _)| *address)
+    }
+
+    pub fn set_comment(&mut self, address: u16, comment: Option<String>) {
+        match comment {
+            Some(comment) => self.comments.insert(address, comment),
+            None => self.comments.remove(&address),
+        };
+        self.dirty = true;
+    }
+
+    pub fn read_comment(&self, address: u16) -> Option<&str> {
+        self.comments.get(&address).map(|comment| comment.as_str())
+    }
+
+    pub fn read_comments(&self) -> impl Iterator<Item = (u16, &str)> {
+        self.comments.iter().map(|(address, comment)| (*address, comment.as_str()))
+    }
+
+    pub fn mark_data(&mut self, start: u16, end: u16) {
+        self.unmark_data(start, end);
+        self.data_regions.push(DataRegion { start, end });
+        self.data_regions.sort_by_key(|region| region.start);
+        self.dirty = true;
+    }
+
+    pub fn unmark_data(&mut self, start: u16, end: u16) {
+        // regions overlapping the range are removed entirely
+        let before = self.data_regions.len();
+        self.data_regions.retain(|region| region.end < start || region.start > end);
+        self.dirty |= self.data_regions.len() != before;
+    }
+
+    pub fn read_data_regions(&self) -> &[DataRegion] {
+        &self.data_regions
+    }
+
+    pub fn find_data_region(&self, address: u16) -> Option<DataRegion> {
+        self.data_regions
+            .iter()
+            .find(|region| (region.start..=region.end).contains(&address))
+            .copied()
+    }
+
+    pub fn disassemble_data(&self, address: u16, read_memory: impl Fn(u16) -> u8) -> Option<(String, u16)> {
+        // data regions are shown as up to 8 bytes per line instead of instructions, returns the text and next address
+        let region = self.find_data_region(address)?;
+        let count = (region.end - address).min(7) + 1;
+        let bytes: Vec<String> = (0..count)
+            .map(|offset| format!("{:#04x}", read_memory(address.wrapping_add(offset))))
+            .collect();
+
+        Some((format!("db {}", bytes.join(",")), address.wrapping_add(count)))
+    }
+
+    pub fn annotate(&self, address: u16, text: &str) -> Vec<String> {
+        // a line of disassembly, preceded by its label and followed by its comment
+        let mut lines = Vec::new();
+        if let Some(label) = self.read_label(address) {
+            lines.push(format!("{}:", label));
+        }
+        match self.read_comment(address) {
+            Some(comment) => lines.push(format!("{:04x}  {}  ; {}", address, text, comment)),
+            None => lines.push(format!("{:04x}  {}", address, text)),
+        }
+
+        lines
+    }
+}
+
+pub fn sidecar_filename(media_filename: &str) -> String {
+    format!("{}.project.json", media_filename)
+}
+
+fn invalid_data(message: String) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message)
+}
@@ -1,22 +1,88 @@
 use std::cell::RefCell;
+use std::collections::VecDeque;
 use std::rc::Rc;
 
 use crate::keyboard;
 
 pub type SoundGeneratorShared = Rc<RefCell<SoundGenerator>>;
 
+const HISTORY_LENGTH: usize = 4096; // in 16µs ticks
+const VOLUME_LEVELS: [f32; 16] = [
+    0.0, 0.0137, 0.0205, 0.0291, 0.0423, 0.0618, 0.0847, 0.1369, 0.1691, 0.2647, 0.3527, 0.4499, 0.5704, 0.6873, 0.8482,
+    1.0,
+]; // the DAC of the AY-3-8912 is logarithmic
+const REGISTER_MASKS: [u8; 16] = [
+    0xff, 0x0f, 0xff, 0x0f, 0xff, 0x0f, 0x1f, 0xff, 0x1f, 0x1f, 0x1f, 0xff, 0xff, 0x0f, 0xff, 0xff,
+];
+
+#[derive(Clone, Copy, Debug)]
+pub struct RegisterWrite {
+    pub timestamp: u64, // in microseconds since the capture started
+    pub register: u8,
+    pub value: u8,
+    pub external: bool, // written through the System by a tool, not by the CPU
+}
+
+// the generators' counters, which the registers alone do not determine
+#[derive(Clone)]
+pub struct GeneratorState {
+    buffer: u8,
+    noise_shift_register: u32,
+    elapsed_microseconds: u8,
+    odd_tone_tick: bool,
+    tone_counters: [u16; 3],
+    tone_outputs: [bool; 3],
+    noise_counter: u8,
+    noise_output: bool,
+    envelope_counter: u32,
+    envelope_step: u8,
+    envelope_attack: bool,
+    envelope_holding: bool,
+    envelope_volume: u8,
+}
+
 pub struct SoundGenerator {
     keyboard: keyboard::KeyboardShared,
     buffer: u8,
     selected_register: u8,
+    registers: [u8; 16],
+    noise_shift_register: u32,
+    elapsed_microseconds: u8,
+    odd_tone_tick: bool, // the noise and envelope generators only advance on every other tone tick
+    tone_counters: [u16; 3],
+    tone_outputs: [bool; 3],
+    noise_counter: u8,
+    noise_output: bool,
+    envelope_counter: u32,
+    envelope_step: u8,
+    envelope_attack: bool,
+    envelope_holding: bool,
+    envelope_volume: u8,
+    channel_history: VecDeque<[u8; 3]>,
+    capture: Option<(u64, Vec<RegisterWrite>)>, // with the time elapsed since the capture started
 }
 
 impl SoundGenerator {
-    pub fn new_shared(keyboard: keyboard::KeyboardShared) -> SoundGeneratorShared {
+    pub fn new_shared(keyboard: keyboard::KeyboardShared, noise_seed: u64) -> SoundGeneratorShared {
         let psg = SoundGenerator {
             keyboard,
             buffer: 0,
             selected_register: 0,
+            registers: [0; 16],
+            noise_shift_register: (noise_seed as u32 & 0x1ffff).max(1), // the shift register must never be all zeros
+            elapsed_microseconds: 0,
+            odd_tone_tick: false,
+            tone_counters: [0; 3],
+            tone_outputs: [false; 3],
+            noise_counter: 0,
+            noise_output: false,
+            envelope_counter: 0,
+            envelope_step: 0,
+            envelope_attack: false,
+            envelope_holding: false,
+            envelope_volume: 0,
+            channel_history: VecDeque::with_capacity(HISTORY_LENGTH),
+            capture: None,
         };
 
         Rc::new(RefCell::new(psg))
@@ -30,12 +96,13 @@ impl SoundGenerator {
                     0x0e => {
                         self.buffer = self.keyboard.borrow().scan_active_line();
                     }
+                    0x00..=0x0f => {
+                        self.buffer = self.registers[self.selected_register as usize];
+                    }
                     _ => unimplemented!(),
                 }
             },
-            2 => {
-                // println!("PSG {:#04x}: {:#04x}", self.selected_register, self.buffer); // TODO: process data
-            },
+            2 => self.store_register(self.selected_register, self.buffer, false),
             3 => {
                 self.selected_register = self.buffer;
             }
@@ -50,4 +117,293 @@ impl SoundGenerator {
     pub fn write_byte(&mut self, value: u8) {
         self.buffer = value;
     }
+
+    pub fn read_registers(&self) -> [u8; 16] {
+        self.registers
+    }
+
+    pub fn save_generators(&self) -> GeneratorState {
+        GeneratorState {
+            buffer: self.buffer,
+            noise_shift_register: self.noise_shift_register,
+            elapsed_microseconds: self.elapsed_microseconds,
+            odd_tone_tick: self.odd_tone_tick,
+            tone_counters: self.tone_counters,
+            tone_outputs: self.tone_outputs,
+            noise_counter: self.noise_counter,
+            noise_output: self.noise_output,
+            envelope_counter: self.envelope_counter,
+            envelope_step: self.envelope_step,
+            envelope_attack: self.envelope_attack,
+            envelope_holding: self.envelope_holding,
+            envelope_volume: self.envelope_volume,
+        }
+    }
+
+    pub fn load_generators(&mut self, state: &GeneratorState) {
+        self.buffer = state.buffer;
+        self.noise_shift_register = state.noise_shift_register;
+        self.elapsed_microseconds = state.elapsed_microseconds;
+        self.odd_tone_tick = state.odd_tone_tick;
+        self.tone_counters = state.tone_counters;
+        self.tone_outputs = state.tone_outputs;
+        self.noise_counter = state.noise_counter;
+        self.noise_output = state.noise_output;
+        self.envelope_counter = state.envelope_counter;
+        self.envelope_step = state.envelope_step;
+        self.envelope_attack = state.envelope_attack;
+        self.envelope_holding = state.envelope_holding;
+        self.envelope_volume = state.envelope_volume;
+    }
+
+    pub fn write_register(&mut self, register: u8, value: u8) {
+        // for tools, e.g. to try out sounds, the selected register and the bus stay as the CPU left them
+        self.store_register(register, value, true);
+    }
+
+    fn store_register(&mut self, register: u8, value: u8, external: bool) {
+        let mask = match REGISTER_MASKS.get(register as usize) {
+            Some(mask) => mask,
+            None => return,
+        };
+
+        self.registers[register as usize] = value & mask;
+        match &mut self.capture {
+            // registers 14 and 15 drive the I/O ports rather than the sound
+            Some((elapsed, writes)) if register < 0x0e => writes.push(RegisterWrite {
+                timestamp: *elapsed,
+                register,
+                value: value & mask,
+                external,
+            }),
+            _ => (),
+        }
+        if register == 0x0d {
+            self.restart_envelope();
+        }
+    }
+
+    pub fn read_selected_register(&self) -> u8 {
+        self.selected_register
+    }
+
+    pub fn read_channel_history(&self) -> Vec<[u8; 3]> {
+        // output levels of channels A, B and C, oldest first
+        self.channel_history.iter().copied().collect()
+    }
+
+    pub fn enable_capture(&mut self, enable: bool) {
+        // the capture starts with the current register contents, so it can be played back on its own
+        self.capture = if enable {
+            let writes = (0..14)
+                .map(|register| RegisterWrite {
+                    timestamp: 0,
+                    register,
+                    value: self.registers[register as usize],
+                    external: false,
+                })
+                .collect();
+            Some((0, writes))
+        } else {
+            None
+        };
+    }
+
+    pub fn take_capture(&mut self) -> (u64, Vec<RegisterWrite>) {
+        // the captured duration in microseconds and the register writes, the capture continues afterwards
+        match &mut self.capture {
+            Some((elapsed, writes)) => {
+                let capture = (*elapsed, std::mem::take(writes));
+                *elapsed = 0;
+                capture
+            }
+            None => (0, Vec::new()),
+        }
+    }
+
+    pub fn step(&mut self, microseconds: u8) {
+        // the PSG is clocked at 1MHz, its tone generators advance every 8 clock cycles, so a full
+        // wave takes 16 times the period, and the noise and envelope generators every 16
+        if let Some((elapsed, _)) = &mut self.capture {
+            *elapsed += microseconds as u64;
+        }
+        self.elapsed_microseconds += microseconds;
+        while self.elapsed_microseconds >= 8 {
+            self.elapsed_microseconds -= 8;
+            self.tick_tones();
+            self.odd_tone_tick = !self.odd_tone_tick;
+            if !self.odd_tone_tick {
+                self.tick();
+            }
+        }
+    }
+
+    pub fn step_noise(&mut self) -> bool {
+        // 17 bit LFSR with taps at bits 0 and 3
+        let feedback = (self.noise_shift_register ^ (self.noise_shift_register >> 3)) & 0x01;
+        self.noise_shift_register = (self.noise_shift_register >> 1) | (feedback << 16);
+
+        self.noise_shift_register & 0x01 != 0
+    }
+
+    fn tick_tones(&mut self) {
+        for channel in 0..3 {
+            let period = (self.registers[channel * 2] as u16 | (self.registers[channel * 2 + 1] as u16) << 8).max(1);
+            self.tone_counters[channel] += 1;
+            if self.tone_counters[channel] >= period {
+                self.tone_counters[channel] = 0;
+                self.tone_outputs[channel] = !self.tone_outputs[channel];
+            }
+        }
+    }
+
+    fn tick(&mut self) {
+        self.noise_counter += 1;
+        if self.noise_counter >= self.registers[0x06].max(1) {
+            self.noise_counter = 0;
+            self.noise_output = self.step_noise();
+        }
+
+        // each of the 16 envelope steps lasts 16 clock cycles times the period
+        self.envelope_counter += 1;
+        let envelope_period = (self.registers[0x0b] as u32 | (self.registers[0x0c] as u32) << 8).max(1);
+        if self.envelope_counter >= envelope_period {
+            self.envelope_counter = 0;
+            self.step_envelope();
+        }
+
+        #[cfg(feature = "instrumentation")]
+        self.record_channel_levels();
+    }
+
+    pub fn read_output_level(&self) -> f32 {
+        // all channels are mixed to mono, like on the internal speaker
+        let levels = self.channel_levels();
+        levels.iter().map(|level| VOLUME_LEVELS[*level as usize]).sum::<f32>() / 3.0
+    }
+
+    fn channel_levels(&self) -> [u8; 3] {
+        let mut levels = [0; 3];
+        for (channel, level) in levels.iter_mut().enumerate() {
+            let tone_disabled = self.registers[0x07] & (0x01 << channel) != 0;
+            let noise_disabled = self.registers[0x07] & (0x08 << channel) != 0;
+            if (self.tone_outputs[channel] || tone_disabled) && (self.noise_output || noise_disabled) {
+                let amplitude = self.registers[0x08 + channel];
+                *level = if amplitude & 0x10 != 0 { self.envelope_volume } else { amplitude & 0x0f };
+            }
+        }
+
+        levels
+    }
+
+    #[cfg(feature = "instrumentation")]
+    fn record_channel_levels(&mut self) {
+        if self.channel_history.len() == HISTORY_LENGTH {
+            self.channel_history.pop_front();
+        }
+        self.channel_history.push_back(self.channel_levels());
+    }
+
+    fn restart_envelope(&mut self) {
+        self.envelope_counter = 0;
+        self.envelope_step = 0;
+        self.envelope_attack = self.registers[0x0d] & 0x04 != 0;
+        self.envelope_holding = false;
+        self.update_envelope_volume();
+    }
+
+    fn step_envelope(&mut self) {
+        if self.envelope_holding {
+            return;
+        }
+
+        let shape = self.registers[0x0d];
+        self.envelope_step += 1;
+        if self.envelope_step > 15 {
+            if shape & 0x08 == 0 {
+                // without "continue" all shapes end silent
+                self.envelope_holding = true;
+                self.envelope_volume = 0;
+                return;
+            }
+
+            if shape & 0x01 != 0 {
+                // "hold" keeps the last volume, or the opposite one with "alternate"
+                self.envelope_holding = true;
+                self.envelope_step = 15;
+                if shape & 0x02 != 0 {
+                    self.envelope_attack = !self.envelope_attack;
+                }
+            } else {
+                self.envelope_step = 0;
+                if shape & 0x02 != 0 {
+                    self.envelope_attack = !self.envelope_attack;
+                }
+            }
+        }
+
+        self.update_envelope_volume();
+    }
+
+    fn update_envelope_volume(&mut self) {
+        self.envelope_volume = if self.envelope_attack {
+            self.envelope_step
+        } else {
+            15 - self.envelope_step
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flip_intervals(psg: &mut SoundGenerator, sample: impl Fn(&SoundGenerator) -> u8, count: usize) -> Vec<u32> {
+        // the microseconds between consecutive changes of the sampled value
+        let mut intervals = Vec::new();
+        let mut last = sample(psg);
+        let mut elapsed = 0;
+        while intervals.len() < count {
+            psg.step(1);
+            elapsed += 1;
+            let current = sample(psg);
+            if current != last {
+                intervals.push(elapsed);
+                last = current;
+                elapsed = 0;
+            }
+        }
+
+        intervals
+    }
+
+    #[test]
+    fn tone_half_period_is_8_clock_cycles_times_the_period() {
+        for period in [1u32, 100, 0xfff] {
+            let psg = SoundGenerator::new_shared(keyboard::Keyboard::new_shared(), 1);
+            let mut psg = psg.borrow_mut();
+            psg.write_register(0x00, period as u8);
+            psg.write_register(0x01, (period >> 8) as u8);
+            psg.write_register(0x07, 0x3e);
+            psg.write_register(0x08, 0x0f);
+
+            let intervals = flip_intervals(&mut psg, |psg| psg.tone_outputs[0] as u8, 4);
+            // the first interval starts part way into the first half-period
+            assert!(intervals[1..].iter().all(|interval| *interval == 8 * period), "{:?}", intervals);
+        }
+    }
+
+    #[test]
+    fn envelope_step_is_16_clock_cycles_times_the_period() {
+        for period in [1u32, 10, 0x100] {
+            let psg = SoundGenerator::new_shared(keyboard::Keyboard::new_shared(), 1);
+            let mut psg = psg.borrow_mut();
+            psg.write_register(0x0b, period as u8);
+            psg.write_register(0x0c, (period >> 8) as u8);
+            psg.write_register(0x0d, 0x0c);
+
+            let intervals = flip_intervals(&mut psg, |psg| psg.envelope_volume, 20);
+            assert!(intervals.iter().all(|interval| *interval == 16 * period), "{:?}", intervals);
+        }
+    }
 }
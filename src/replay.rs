@@ -0,0 +1,500 @@
+use std::path::Path;
+
+use crate::cpu;
+use crate::crtc;
+use crate::keyboard;
+use crate::media;
+use crate::memory;
+use crate::sna_file;
+use crate::system::{MachineState, PowerOnState, System, SystemConfig};
+
+const FORMAT_VERSION: u32 = 4; // version 1 has no frame entries, version 2 no power-on state, version 3 no snapshot
+const FRAME_MICROSECONDS: u64 = 20_000;
+const CHECKPOINT_INTERVAL: usize = 50; // frames between the checkpoints of the editor
+
+pub struct MediaEntry {
+    pub path: String,
+    pub crc32: u32,
+}
+
+// what a frame ended with, to find where an edited replay starts to differ from the recording
+#[derive(Clone, Debug, PartialEq)]
+pub struct FrameInfo {
+    pub microseconds: u64, // emulated time at the end of the frame
+    pub matrix: [u8; 10],  // keyboard matrix, a cleared bit is a pressed key
+    pub r_register: u8,    // games often take their random numbers from it
+    pub ram_checksum: u32,
+}
+
+// replays start from a deterministic power-on, so the inserted media and the key presses
+// are all that is needed to reproduce a session. A snapshot can be embedded to start from a save
+// state instead, it is loaded right after power-on in both recording and playback
+pub struct Replay {
+    pub seed: u64,
+    pub ram_pattern: memory::RamPattern,
+    pub power_on: PowerOnState,
+    pub language: keyboard::Language,
+    pub firmware: memory::Firmware,
+    pub crtc_type: crtc::CrtcType,
+    pub ram_size: memory::RamSize,
+    pub real_time_clock: bool,
+    pub media: Vec<MediaEntry>,
+    pub snapshot: Option<sna_file::Snapshot>,
+    pub key_events: Vec<keyboard::KeyEvent>,
+    pub frames: Vec<FrameInfo>,
+    pub end_microseconds: u64,
+    pub ram_checksum: u32, // of the final machine state, used to verify playback
+}
+
+impl Replay {
+    pub fn new(config: &SystemConfig, real_time_clock: bool) -> Replay {
+        Replay {
+            seed: config.seed,
+            ram_pattern: config.ram_pattern,
+            power_on: config.power_on,
+            language: config.language,
+            firmware: config.firmware,
+            crtc_type: config.crtc_type,
+            ram_size: config.ram_size,
+            real_time_clock,
+            media: Vec::new(),
+            snapshot: None,
+            key_events: Vec::new(),
+            frames: Vec::new(),
+            end_microseconds: 0,
+            ram_checksum: 0,
+        }
+    }
+
+    pub fn load(filename: &str) -> std::io::Result<Replay> {
+        let contents = std::fs::read_to_string(filename)?;
+        let directory = Path::new(filename).parent().unwrap_or_else(|| Path::new(""));
+        let mut replay = Replay::new(&SystemConfig::default(), false);
+
+        for line in contents.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (key, value) = match line.split_once('=') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => return Err(invalid_data(format!("Missing \"=\" in replay line \"{}\".", line))),
+            };
+
+            match key {
+                "version" => {
+                    if !(1..=FORMAT_VERSION).contains(&value.parse::<u32>().unwrap_or(0)) {
+                        return Err(invalid_data(format!("Unsupported replay version \"{}\".", value)));
+                    }
+                }
+                "seed" => replay.seed = parse_number(value)?,
+                "ram_pattern" => {
+                    replay.ram_pattern = memory::RamPattern::from_name(value)
+                        .ok_or_else(|| invalid_data(format!("Unknown RAM pattern \"{}\".", value)))?
+                }
+                "power_on_random" => {
+                    replay.power_on = PowerOnState::from_names(value)
+                        .ok_or_else(|| invalid_data(format!("Unknown power-on devices \"{}\".", value)))?
+                }
+                "language" => {
+                    replay.language = keyboard::Language::from_name(value)
+                        .ok_or_else(|| invalid_data(format!("Unknown language \"{}\".", value)))?
+                }
+                "firmware" => {
+                    replay.firmware = memory::Firmware::from_name(value)
+                        .ok_or_else(|| invalid_data(format!("Unknown firmware \"{}\".", value)))?
+                }
+                "crtc" => {
+                    replay.crtc_type = crtc::CrtcType::from_name(value)
+                        .ok_or_else(|| invalid_data(format!("Unknown CRTC type \"{}\".", value)))?
+                }
+                "ram" => {
+                    replay.ram_size = memory::RamSize::from_name(value)
+                        .ok_or_else(|| invalid_data(format!("Unknown RAM size \"{}\".", value)))?
+                }
+                "rtc" => replay.real_time_clock = value == "on",
+                "media" => {
+                    let (crc32, name) = value
+                        .split_once(' ')
+                        .ok_or_else(|| invalid_data(format!("Invalid media entry \"{}\".", value)))?;
+                    replay.media.push(MediaEntry {
+                        path: directory.join(name.trim()).to_string_lossy().to_string(),
+                        crc32: parse_number(crc32)? as u32,
+                    });
+                }
+                "snapshot" => replay.snapshot = Some(parse_snapshot(value)?),
+                "end" => replay.end_microseconds = parse_number(value)?,
+                "ram_checksum" => replay.ram_checksum = parse_number(value)? as u32,
+                "key" => replay.key_events.push(parse_key_event(value)?),
+                "frame" => replay.frames.push(parse_frame(value)?),
+                _ => return Err(invalid_data(format!("Unknown replay entry \"{}\".", key))),
+            }
+        }
+
+        Ok(replay)
+    }
+
+    pub fn save(&self, filename: &str) -> std::io::Result<()> {
+        let mut contents = String::from("# ronald replay\n");
+        contents.push_str(&format!("version = {}\n", FORMAT_VERSION));
+        contents.push_str(&format!("seed = {}\n", self.seed));
+        contents.push_str(&format!("ram_pattern = {}\n", self.ram_pattern.name()));
+        contents.push_str(&format!("power_on_random = {}\n", self.power_on.names()));
+        contents.push_str(&format!("language = {}\n", self.language.name()));
+        contents.push_str(&format!("firmware = {}\n", self.firmware.name()));
+        contents.push_str(&format!("crtc = {}\n", self.crtc_type.number()));
+        contents.push_str(&format!("ram = {}\n", self.ram_size.name()));
+        contents.push_str(&format!("rtc = {}\n", if self.real_time_clock { "on" } else { "off" }));
+        for entry in &self.media {
+            // media is looked up next to the replay file when playing back
+            let name = Path::new(&entry.path).file_name().unwrap_or_default().to_string_lossy();
+            contents.push_str(&format!("media = {:#010x} {}\n", entry.crc32, name));
+        }
+        if let Some(snapshot) = &self.snapshot {
+            // compressed, the file stays text so replays can be diffed and edited
+            let bytes = snapshot.to_bytes_with_version(3)?;
+            let hex: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+            contents.push_str(&format!("snapshot = {}\n", hex));
+        }
+        contents.push_str(&format!("end = {}\n", self.end_microseconds));
+        contents.push_str(&format!("ram_checksum = {:#010x}\n", self.ram_checksum));
+        for event in &self.key_events {
+            contents.push_str(&format!(
+                "key = {} {} {} {}\n",
+                event.timestamp,
+                event.line,
+                event.bit,
+                if event.pressed { "down" } else { "up" }
+            ));
+        }
+        for frame in &self.frames {
+            let matrix: String = frame.matrix.iter().map(|line| format!("{:02x}", line)).collect();
+            contents.push_str(&format!(
+                "frame = {} {:#04x} {:#010x} {}\n",
+                frame.microseconds, frame.r_register, frame.ram_checksum, matrix
+            ));
+        }
+
+        std::fs::write(filename, contents)
+    }
+
+    pub fn config(&self) -> SystemConfig {
+        SystemConfig {
+            deterministic: true,
+            seed: self.seed,
+            ram_pattern: self.ram_pattern,
+            power_on: self.power_on,
+            language: self.language,
+            firmware: self.firmware,
+            crtc_type: self.crtc_type,
+            ram_size: self.ram_size,
+            ..Default::default()
+        }
+    }
+
+    pub fn add_media(&mut self, path: &str) -> std::io::Result<()> {
+        self.media.push(MediaEntry {
+            path: path.to_string(),
+            crc32: media::hash_media(path)?,
+        });
+
+        Ok(())
+    }
+
+    pub fn embed_snapshot(&mut self, path: &str) -> std::io::Result<()> {
+        // the system has to load the same snapshot before the first frame
+        self.snapshot = Some(sna_file::Snapshot::load(path)?);
+
+        Ok(())
+    }
+
+    pub fn verify_media(&self) -> std::io::Result<()> {
+        for entry in &self.media {
+            let crc32 = media::hash_media(&entry.path)?;
+            if crc32 != entry.crc32 {
+                return Err(invalid_data(format!(
+                    "\"{}\" has checksum {:#010x} instead of {:#010x}.",
+                    entry.path, crc32, entry.crc32
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn finish(&mut self, system: &mut dyn System) {
+        self.key_events = system.get_keyboard().borrow_mut().take_recording();
+        self.end_microseconds = system.get_sync_statistics().emulated_microseconds;
+        self.ram_checksum = ram_checksum(system);
+    }
+
+    pub fn record_frame(&mut self, system: &dyn System) {
+        // called at the end of each emulated frame while recording
+        self.frames.push(frame_info(system));
+    }
+
+    pub fn set_key(&mut self, frame: usize, line: usize, bit: u8, pressed: bool) -> bool {
+        // holds the key down or up for the whole frame, the frames after it keep their input.
+        // Returns false for frames that were not recorded
+        let (start, end) = match (self.frame_start(frame), self.frames.get(frame)) {
+            (Some(start), Some(info)) => (start, info.microseconds),
+            _ => return false,
+        };
+
+        let is_key = |event: &keyboard::KeyEvent| event.line == line && event.bit == bit;
+        let pressed_at = |events: &[keyboard::KeyEvent], time: u64| {
+            events.iter().rev().find(|event| is_key(event) && event.timestamp <= time).map(|event| event.pressed).unwrap_or(false)
+        };
+        let pressed_before = pressed_at(&self.key_events, start);
+        let pressed_after = pressed_at(&self.key_events, end);
+
+        // events take effect at the end of the instruction they fall into, so the edit starts just
+        // after the previous frame ended
+        self.key_events.retain(|event| !(is_key(event) && event.timestamp > start && event.timestamp <= end));
+        let mut edits = Vec::new();
+        if pressed != pressed_before {
+            edits.push(keyboard::KeyEvent { timestamp: start + 1, line, bit, pressed });
+        }
+        if pressed != pressed_after {
+            edits.push(keyboard::KeyEvent { timestamp: end + 1, line, bit, pressed: pressed_after });
+        }
+        for edit in edits {
+            let index = self.key_events.iter().position(|event| event.timestamp > edit.timestamp).unwrap_or(self.key_events.len());
+            self.key_events.insert(index, edit);
+        }
+
+        true
+    }
+
+    fn frame_start(&self, frame: usize) -> Option<u64> {
+        match frame {
+            0 => Some(0),
+            _ => self.frames.get(frame - 1).map(|info| info.microseconds),
+        }
+    }
+
+    pub fn start_playback(&self, system: &mut dyn System) {
+        // on a freshly powered on system with the media inserted
+        if let Some(snapshot) = &self.snapshot {
+            system.load_snapshot(snapshot);
+        }
+        system.get_keyboard().borrow_mut().schedule_key_events(&self.key_events);
+    }
+
+    pub fn verify(&self, system: &mut dyn System) -> bool {
+        // plays back the whole replay and compares the resulting machine state
+        self.start_playback(system);
+
+        let mut elapsed_microseconds = system.get_sync_statistics().emulated_microseconds;
+        while elapsed_microseconds < self.end_microseconds {
+            elapsed_microseconds += system.emulate() as u64;
+        }
+
+        elapsed_microseconds == self.end_microseconds && ram_checksum(system) == self.ram_checksum
+    }
+}
+
+struct Checkpoint {
+    frame: usize, // the state is saved before this frame
+    microseconds: u64,
+    matrix: [u8; 10],
+    state: MachineState,
+}
+
+// plays a replay frame by frame for editing its input, e.g. in a TAS editor. Machine states saved
+// along the way make going back cheap: after an edit only the frames since the last checkpoint
+// before it are emulated again, and they turn out the same as in a run from power-on
+pub struct ReplayEditor {
+    replay: Replay,
+    recorded: Vec<FrameInfo>, // what the frames looked like before the edits, for comparison
+    frame: usize,             // the next frame to emulate
+    checkpoints: Vec<Checkpoint>,
+}
+
+impl ReplayEditor {
+    pub fn new(replay: Replay, system: &mut dyn System) -> ReplayEditor {
+        // the system has to be freshly powered on with the replay's config and media
+        replay.start_playback(system);
+        let mut editor = ReplayEditor {
+            recorded: replay.frames.clone(),
+            replay,
+            frame: 0,
+            checkpoints: Vec::new(),
+        };
+        editor.add_checkpoint(system);
+
+        editor
+    }
+
+    pub fn get_replay(&self) -> &Replay {
+        &self.replay
+    }
+
+    pub fn into_replay(self) -> Replay {
+        self.replay
+    }
+
+    pub fn get_frame(&self) -> usize {
+        self.frame
+    }
+
+    pub fn get_recorded_frame(&self, frame: usize) -> Option<&FrameInfo> {
+        self.recorded.get(frame)
+    }
+
+    pub fn step_frame(&mut self, system: &mut dyn System) -> bool {
+        // returns false if the frame ends differently than it was recorded
+        let keyboard = system.get_keyboard();
+        let end = keyboard.borrow().get_elapsed_microseconds() + FRAME_MICROSECONDS;
+        while keyboard.borrow().get_elapsed_microseconds() < end {
+            system.emulate();
+        }
+
+        let info = frame_info(system);
+        let matches = self.recorded.get(self.frame).map(|recorded| *recorded == info).unwrap_or(true);
+        self.replay.frames.truncate(self.frame);
+        self.replay.frames.push(info);
+        self.frame += 1;
+        let frame = self.frame;
+        let last_checkpoint = self.checkpoints.iter().map(|checkpoint| checkpoint.frame).filter(|checkpoint| *checkpoint <= frame).max();
+        if frame - last_checkpoint.unwrap_or(0) >= CHECKPOINT_INTERVAL {
+            self.add_checkpoint(system);
+        }
+
+        matches
+    }
+
+    pub fn set_key(&mut self, system: &mut dyn System, frame: usize, line: usize, bit: u8, pressed: bool) -> bool {
+        // edits the input and emulates up to the frame again, so it can be stepped through
+        if !self.replay.set_key(frame, line, bit, pressed) {
+            return false;
+        }
+
+        self.checkpoints.retain(|checkpoint| checkpoint.frame <= frame);
+        self.seek(system, frame);
+        true
+    }
+
+    pub fn seek(&mut self, system: &mut dyn System, frame: usize) {
+        // goes back through the last checkpoint before the frame, forward by emulating
+        if frame < self.frame {
+            let checkpoint = self
+                .checkpoints
+                .iter()
+                .filter(|checkpoint| checkpoint.frame <= frame)
+                .max_by_key(|checkpoint| checkpoint.frame)
+                .expect("there is a checkpoint at power-on");
+            system.load_machine_state(&checkpoint.state);
+            let keyboard = system.get_keyboard();
+            let mut keyboard = keyboard.borrow_mut();
+            keyboard.seek(checkpoint.microseconds, checkpoint.matrix);
+            let events: Vec<keyboard::KeyEvent> =
+                self.replay.key_events.iter().filter(|event| event.timestamp > checkpoint.microseconds).cloned().collect();
+            keyboard.schedule_key_events(&events);
+            self.frame = checkpoint.frame;
+        }
+
+        while self.frame < frame {
+            self.step_frame(system);
+        }
+    }
+
+    fn add_checkpoint(&mut self, system: &dyn System) {
+        let mut state = system.save_machine_state();
+        if let Some(previous) = self.checkpoints.last() {
+            state.share_unchanged(&previous.state);
+        }
+        let keyboard = system.get_keyboard();
+        let keyboard = keyboard.borrow();
+        self.checkpoints.push(Checkpoint {
+            frame: self.frame,
+            microseconds: keyboard.get_elapsed_microseconds(),
+            matrix: keyboard.read_matrix(),
+            state,
+        });
+    }
+}
+
+fn frame_info(system: &dyn System) -> FrameInfo {
+    let keyboard = system.get_keyboard();
+    let keyboard = keyboard.borrow();
+
+    FrameInfo {
+        microseconds: keyboard.get_elapsed_microseconds(),
+        matrix: keyboard.read_matrix(),
+        r_register: system.read_register8(cpu::Register8::R),
+        ram_checksum: ram_checksum(system),
+    }
+}
+
+fn ram_checksum(system: &dyn System) -> u32 {
+    let ram: Vec<u8> = (0..0x10000).map(|address| system.read_ram(address)).collect();
+
+    media::crc32(&ram)
+}
+
+fn parse_key_event(value: &str) -> std::io::Result<keyboard::KeyEvent> {
+    let fields: Vec<&str> = value.split_whitespace().collect();
+    if fields.len() != 4 || (fields[3] != "down" && fields[3] != "up") {
+        return Err(invalid_data(format!("Invalid key event \"{}\".", value)));
+    }
+
+    let line = parse_number(fields[1])? as usize;
+    let bit = parse_number(fields[2])? as u8;
+    if line >= 10 || bit >= 8 {
+        return Err(invalid_data(format!("Key event \"{}\" is outside of the keyboard matrix.", value)));
+    }
+
+    Ok(keyboard::KeyEvent {
+        timestamp: parse_number(fields[0])?,
+        line,
+        bit,
+        pressed: fields[3] == "down",
+    })
+}
+
+fn parse_snapshot(value: &str) -> std::io::Result<sna_file::Snapshot> {
+    if value.len() & 1 != 0 || !value.is_ascii() {
+        return Err(invalid_data("Invalid snapshot entry.".to_string()));
+    }
+
+    let bytes = (0..value.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(&value[index..(index + 2)], 16))
+        .collect::<Result<Vec<u8>, _>>()
+        .map_err(|error| invalid_data(format!("Invalid snapshot entry: {}", error)))?;
+    sna_file::Snapshot::from_bytes(&bytes)
+}
+
+fn parse_frame(value: &str) -> std::io::Result<FrameInfo> {
+    let fields: Vec<&str> = value.split_whitespace().collect();
+    if fields.len() != 4 || fields[3].len() != 20 || !fields[3].is_ascii() {
+        return Err(invalid_data(format!("Invalid frame \"{}\".", value)));
+    }
+
+    let mut matrix = [0xff; 10];
+    for (line, value) in matrix.iter_mut().enumerate() {
+        *value = parse_number(&format!("0x{}", &fields[3][(2 * line)..(2 * line + 2)]))? as u8;
+    }
+
+    Ok(FrameInfo {
+        microseconds: parse_number(fields[0])?,
+        matrix,
+        r_register: parse_number(fields[1])? as u8,
+        ram_checksum: parse_number(fields[2])? as u32,
+    })
+}
+
+fn parse_number(value: &str) -> std::io::Result<u64> {
+    let result = match value.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => value.parse::<u64>(),
+    };
+
+    result.map_err(|error| invalid_data(format!("Invalid number \"{}\": {}", value, error)))
+}
+
+fn invalid_data(message: String) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message)
+}
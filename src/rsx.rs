@@ -0,0 +1,89 @@
+use crate::cpu;
+
+const KL_FIND_COMMAND: u16 = 0xbcd4; // firmware jumpblock entry, always in RAM
+const MAX_NAME_LENGTH: usize = 16;
+
+enum State {
+    Idle,
+    Searching { name: String, return_address: u16, stack_pointer: u16 },
+    Found { name: String, address: u16, rom: u8 },
+}
+
+// follows |commands through KL FIND COMMAND and logs them with their parameters at debug level,
+// e.g. with --log warn,ronald::rsx=debug
+pub struct RsxTracer {
+    state: State,
+}
+
+impl RsxTracer {
+    pub fn new() -> RsxTracer {
+        RsxTracer { state: State::Idle }
+    }
+
+    pub fn update(&mut self, registers: &cpu::RegisterFile, read_memory: impl Fn(u16) -> u8) {
+        // called before each instruction, as seen by the CPU
+        let program_counter = registers.read_word(&cpu::Register16::PC);
+        let stack_pointer = registers.read_word(&cpu::Register16::SP);
+
+        if program_counter == KL_FIND_COMMAND {
+            // HL points to the name, its last character has bit 7 set
+            let address = registers.read_word(&cpu::Register16::HL);
+            let mut name = String::new();
+            for offset in 0..MAX_NAME_LENGTH as u16 {
+                let value = read_memory(address.wrapping_add(offset));
+                name.push((value & 0x7f) as char);
+                if value & 0x80 != 0 {
+                    break;
+                }
+            }
+            let return_address = read_memory(stack_pointer) as u16 | (read_memory(stack_pointer.wrapping_add(1)) as u16) << 8;
+            self.state = State::Searching {
+                name,
+                return_address,
+                stack_pointer,
+            };
+            return;
+        }
+
+        self.state = match std::mem::replace(&mut self.state, State::Idle) {
+            State::Searching {
+                name,
+                return_address,
+                stack_pointer: search_stack_pointer,
+            } if program_counter == return_address && stack_pointer == search_stack_pointer.wrapping_add(2) => {
+                // carry is set if a ROM or RAM extension knows the command
+                if registers.read_byte(&cpu::Register8::F) & 0x01 != 0 {
+                    let address = registers.read_word(&cpu::Register16::HL);
+                    let rom = registers.read_byte(&cpu::Register8::C);
+                    log::debug!("|{} found at {:#06x} in ROM {}", name, address, rom);
+                    State::Found { name, address, rom }
+                } else {
+                    log::debug!("|{} is an unknown command", name);
+                    State::Idle
+                }
+            }
+            State::Found { name, address, rom } if program_counter == address => {
+                // A holds the number of parameters, IX points to the last one
+                let count = registers.read_byte(&cpu::Register8::A);
+                let parameters_address = registers.read_word(&cpu::Register16::IX);
+                let parameters = (0..count as u16)
+                    .rev()
+                    .map(|index| {
+                        let address = parameters_address.wrapping_add(index * 2);
+                        let value = read_memory(address) as u16 | (read_memory(address.wrapping_add(1)) as u16) << 8;
+                        format!("{:#06x}", value)
+                    })
+                    .collect::<Vec<_>>();
+                log::debug!("|{}({}) called in ROM {}", name, parameters.join(", "), rom);
+                State::Idle
+            }
+            state => state,
+        };
+    }
+}
+
+impl Default for RsxTracer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,142 @@
+use std::cell::RefCell;
+use std::ops::RangeInclusive;
+use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::expansion;
+
+pub type RealTimeClockShared = Rc<RefCell<RealTimeClock>>;
+
+// DS12887 compatible clock as found on the Symbiface II
+pub struct RealTimeClock {
+    selected_register: usize,
+    registers: [u8; 128], // only control registers and battery backed RAM are stored here
+    offset_seconds: i64,
+    fixed_time: Option<i64>,
+}
+
+impl RealTimeClock {
+    pub fn new_shared() -> RealTimeClockShared {
+        let mut registers = [0; 128];
+        registers[0x0b] = 0x02; // 24 hour mode, BCD
+        registers[0x0d] = 0x80; // valid RAM and time
+
+        let rtc = RealTimeClock {
+            selected_register: 0,
+            registers,
+            offset_seconds: 0,
+            fixed_time: None,
+        };
+
+        Rc::new(RefCell::new(rtc))
+    }
+
+    pub fn set_offset(&mut self, seconds: i64) {
+        self.offset_seconds = seconds;
+    }
+
+    pub fn pin_time(&mut self, unix_seconds: Option<i64>) {
+        // a pinned clock does not advance, which keeps test runs reproducible
+        self.fixed_time = unix_seconds;
+    }
+
+    pub fn read_byte(&self, port: u16) -> u8 {
+        match port & 0x01 {
+            0 => self.read_register(self.selected_register),
+            _ => 0xff,
+        }
+    }
+
+    pub fn write_byte(&mut self, port: u16, value: u8) {
+        match port & 0x01 {
+            0 => {
+                // setting the time from the CPC side is not supported, only the RAM and mode bits are writable
+                if self.selected_register >= 0x0a && self.selected_register != 0x0c && self.selected_register != 0x0d {
+                    self.registers[self.selected_register] = value;
+                }
+            }
+            _ => self.selected_register = value as usize & 0x7f,
+        }
+    }
+
+    fn read_register(&self, register: usize) -> u8 {
+        let (year, month, day, weekday, hours, minutes, seconds) = civil_time(self.current_time());
+        let value = match register {
+            0x00 => seconds,
+            0x02 => minutes,
+            0x04 => hours,
+            0x06 => weekday,
+            0x07 => day,
+            0x08 => month,
+            0x09 => (year % 100) as u8,
+            0x01 | 0x03 | 0x05 => 0, // alarms are not supported
+            _ => return self.registers[register],
+        };
+
+        if self.registers[0x0b] & 0x04 != 0 {
+            value // binary mode
+        } else {
+            (value / 10) << 4 | (value % 10)
+        }
+    }
+
+    fn current_time(&self) -> i64 {
+        match self.fixed_time {
+            Some(fixed_time) => fixed_time,
+            None => {
+                let now = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|duration| duration.as_secs() as i64)
+                    .unwrap_or(0);
+                now + self.offset_seconds
+            }
+        }
+    }
+}
+
+impl expansion::ExpansionDevice for RealTimeClock {
+    fn name(&self) -> &'static str {
+        "RTC"
+    }
+
+    fn port_ranges(&self) -> Vec<RangeInclusive<u16>> {
+        vec![0xfd14..=0xfd15]
+    }
+
+    fn read_byte(&mut self, port: u16) -> u8 {
+        RealTimeClock::read_byte(self, port)
+    }
+
+    fn write_byte(&mut self, port: u16, value: u8) {
+        RealTimeClock::write_byte(self, port, value)
+    }
+}
+
+pub fn civil_time(unix_seconds: i64) -> (i64, u8, u8, u8, u8, u8, u8) {
+    // converts a unix timestamp into year, month, day, weekday (1 = Sunday), hours, minutes and seconds
+    let days = unix_seconds.div_euclid(86_400);
+    let seconds_of_day = unix_seconds.rem_euclid(86_400);
+
+    // see http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    let weekday = (days + 4).rem_euclid(7) + 1; // 1970-01-01 was a Thursday
+
+    (
+        year,
+        month as u8,
+        day as u8,
+        weekday as u8,
+        (seconds_of_day / 3600) as u8,
+        (seconds_of_day / 60 % 60) as u8,
+        (seconds_of_day % 60) as u8,
+    )
+}
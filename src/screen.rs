@@ -4,6 +4,10 @@ use std::rc::Rc;
 pub const BUFFER_WIDTH: usize = 64 * 16;
 pub const BUFFER_HEIGHT: usize = 39 * 16;
 
+// what drew a pixel when capturing layers, pens are 0 to 15
+pub const SOURCE_BORDER: u8 = 0x10;
+pub const SOURCE_BLANK: u8 = 0xff; // sync or blanking
+
 #[allow(clippy::identity_op, clippy::eq_op)]
 const FIRMWARE_COLORS: [u32; 27] = [
     0x00 << 16 | 0x00 << 8 | 0x00, // 0
@@ -76,6 +80,7 @@ pub struct Screen {
     buffer: Vec<u32>,
     gun_position: usize,
     waiting_for_vsync: bool,
+    sources: Option<Vec<u8>>, // what drew each pixel, while capturing layers
 }
 
 impl Screen {
@@ -84,6 +89,7 @@ impl Screen {
             buffer: vec![FIRMWARE_COLORS[0]; BUFFER_WIDTH * BUFFER_HEIGHT],
             gun_position: 0,
             waiting_for_vsync: true,
+            sources: None,
         };
 
         Rc::new(RefCell::new(screen))
@@ -93,14 +99,46 @@ impl Screen {
         &self.buffer
     }
 
-    pub fn write(&mut self, color: usize) {
+    pub fn read_gun_position(&self) -> Option<usize> {
+        if self.waiting_for_vsync {
+            None
+        } else {
+            Some(self.gun_position)
+        }
+    }
+
+    pub fn write_gun_position(&mut self, position: Option<usize>) {
+        // e.g. when restoring a machine state, None waits for the next VSYNC
+        self.waiting_for_vsync = position.is_none();
+        self.gun_position = position.unwrap_or(0);
+    }
+
+    pub fn write(&mut self, color: usize, source: u8) {
         if self.waiting_for_vsync {
             return;
         }
 
         self.buffer[self.gun_position] = FIRMWARE_COLORS[HARDWARE_TO_FIRMWARE_COLORS[color]];
         self.buffer[self.gun_position + BUFFER_WIDTH] = FIRMWARE_COLORS[HARDWARE_TO_FIRMWARE_COLORS[color]];
+        if let Some(sources) = &mut self.sources {
+            sources[self.gun_position] = source;
+            sources[self.gun_position + BUFFER_WIDTH] = source;
+        }
+
+        self.advance_gun();
+    }
 
+    pub fn skip(&mut self, count: usize) {
+        // leaves pixels unchanged that would be drawn the same as in the previous frame
+        for _ in 0..count {
+            if self.waiting_for_vsync {
+                return;
+            }
+            self.advance_gun();
+        }
+    }
+
+    fn advance_gun(&mut self) {
         self.gun_position += 1;
 
         if self.gun_position % BUFFER_WIDTH == 0 {
@@ -113,7 +151,108 @@ impl Screen {
         }
     }
 
+    pub fn enable_layers(&mut self, enable: bool) {
+        // the sources are complete once a whole frame was drawn after enabling them
+        self.sources = if enable { Some(vec![SOURCE_BLANK; self.buffer.len()]) } else { None };
+    }
+
+    pub fn has_layers(&self) -> bool {
+        self.sources.is_some()
+    }
+
+    pub fn write_png(&self, filename: &str) -> std::io::Result<()> {
+        let mut data = Vec::with_capacity(self.buffer.len() * 3);
+        for pixel in &self.buffer {
+            data.push((pixel >> 16) as u8);
+            data.push((pixel >> 8) as u8);
+            data.push(*pixel as u8);
+        }
+
+        write_image(filename, png::ColorType::RGB, &data)
+    }
+
+    pub fn write_layer_pngs(&self, prefix: &str) -> std::io::Result<Vec<String>> {
+        // one image for the border, one for each pen that was drawn and one of the display enable
+        // signal, i.e. white where the CRTC displays memory, gray for the border and black while
+        // blanking. Pixels outside of a layer are transparent. Returns the files written
+        let sources = match &self.sources {
+            Some(sources) => sources,
+            None => return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "layers are not captured")),
+        };
+
+        let mut filenames = Vec::new();
+        for source in (0..=SOURCE_BORDER).filter(|source| sources.contains(source)) {
+            let mut data = Vec::with_capacity(self.buffer.len() * 4);
+            for (pixel, pixel_source) in self.buffer.iter().zip(sources) {
+                let alpha = if *pixel_source == source { 0xff } else { 0 };
+                data.extend_from_slice(&[(pixel >> 16) as u8, (pixel >> 8) as u8, *pixel as u8, alpha]);
+            }
+
+            let filename = match source {
+                SOURCE_BORDER => format!("{}_border.png", prefix),
+                pen => format!("{}_pen{}.png", prefix, pen),
+            };
+            write_image(&filename, png::ColorType::RGBA, &data)?;
+            filenames.push(filename);
+        }
+
+        let mut data = Vec::with_capacity(self.buffer.len() * 3);
+        for source in sources {
+            let level = match *source {
+                SOURCE_BLANK => 0x00,
+                SOURCE_BORDER => 0x80,
+                _ => 0xff,
+            };
+            data.extend_from_slice(&[level, level, level]);
+        }
+        let filename = format!("{}_display.png", prefix);
+        write_image(&filename, png::ColorType::RGB, &data)?;
+        filenames.push(filename);
+
+        Ok(filenames)
+    }
+
+    pub fn compare_png(&self, filename: &str) -> std::io::Result<usize> {
+        // returns the number of pixels that differ from the image
+        let decoder = png::Decoder::new(std::fs::File::open(filename)?);
+        let (info, mut reader) = decoder.read_info()?;
+        if info.width as usize != BUFFER_WIDTH
+            || info.height as usize != BUFFER_HEIGHT
+            || info.color_type != png::ColorType::RGB
+            || info.bit_depth != png::BitDepth::Eight
+        {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "image is not an 8-bit RGB screenshot of the same size",
+            ));
+        }
+
+        let mut data = vec![0; info.buffer_size()];
+        reader.next_frame(&mut data)?;
+
+        let differences = self
+            .buffer
+            .iter()
+            .zip(data.chunks(3))
+            .filter(|(pixel, rgb)| **pixel != (rgb[0] as u32) << 16 | (rgb[1] as u32) << 8 | rgb[2] as u32)
+            .count();
+
+        Ok(differences)
+    }
+
     pub fn trigger_vsync(&mut self) {
         self.waiting_for_vsync = false;
     }
 }
+
+fn write_image(filename: &str, color_type: png::ColorType, data: &[u8]) -> std::io::Result<()> {
+    let file = std::fs::File::create(filename)?;
+    let mut encoder = png::Encoder::new(std::io::BufWriter::new(file), BUFFER_WIDTH as u32, BUFFER_HEIGHT as u32);
+    encoder.set_color(color_type);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(data)?;
+
+    Ok(())
+}
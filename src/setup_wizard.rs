@@ -0,0 +1,253 @@
+use std::io::{BufRead, Write};
+
+use crate::audio_output;
+use crate::checksums;
+use crate::config_file;
+use crate::key_mapper;
+use crate::keyboard;
+use crate::memory;
+use crate::mixer;
+
+const LANGUAGES: [&str; 4] = ["english", "french", "spanish", "danish"];
+const TEST_TONE_HZ: u32 = 440;
+const TEST_TONE_LEVEL: f32 = 0.25;
+const TEST_TONE_CHUNKS: u32 = 100; // of 10ms each
+
+// asks on the terminal for the settings a first run needs, i.e. the firmware, the keyboard and the
+// audio device, and writes them to a config file, so later runs start without any options
+pub struct SetupWizard {
+    filename: String,
+    sections: Vec<(&'static str, Vec<(&'static str, toml::Value)>)>,
+}
+
+impl SetupWizard {
+    pub fn new(filename: &str) -> SetupWizard {
+        SetupWizard {
+            filename: filename.to_string(),
+            sections: Vec::new(),
+        }
+    }
+
+    pub fn run(&mut self) -> std::io::Result<()> {
+        if std::path::Path::new(&self.filename).exists() && !ask_yes_no(&format!("\"{}\" exists, overwrite it?", self.filename), false)? {
+            return Ok(());
+        }
+
+        let language = self.ask_machine()?;
+        self.ask_roms(language)?;
+        self.ask_keyboard()?;
+        self.ask_audio()?;
+
+        let text = self.to_toml();
+        config_file::ConfigFile::parse(&self.filename, &text)?; // the wizard only writes what runs accept
+        std::fs::write(&self.filename, text)?;
+        println!();
+        println!("Saved the settings to \"{}\", the run command reads them from there.", self.filename);
+
+        Ok(())
+    }
+
+    fn set(&mut self, section: &'static str, key: &'static str, value: toml::Value) {
+        match self.sections.iter_mut().find(|(name, _)| *name == section) {
+            Some((_, values)) => values.push((key, value)),
+            None => self.sections.push((section, vec![(key, value)])),
+        }
+    }
+
+    fn to_toml(&self) -> String {
+        let mut text = "# written by the setup wizard\n".to_string();
+        for (section, values) in &self.sections {
+            text += &format!("\n[{}]\n", section);
+            for (key, value) in values {
+                text += &format!("{} = {}\n", key, value);
+            }
+        }
+
+        text
+    }
+
+    fn ask_machine(&mut self) -> std::io::Result<keyboard::Language> {
+        println!("Ronald emulates the CPC 464, its firmware comes in several languages.");
+        let language = ask_choice("Language", &LANGUAGES, "english")?;
+        self.set("machine", "language", toml::Value::from(language.as_str()));
+
+        Ok(keyboard::Language::from_name(&language).unwrap())
+    }
+
+    fn ask_roms(&mut self, language: keyboard::Language) -> std::io::Result<()> {
+        println!();
+        let mut roms = memory::RomPaths::default();
+        loop {
+            let (os_path, basic_path, amsdos_path) = roms.resolve(language);
+            let mut missing = false;
+            for path in [os_path, basic_path, amsdos_path].iter() {
+                match checksums::verify_rom(path) {
+                    Ok(verification) => verification.print(),
+                    Err(error) => {
+                        println!("{}: {}", path, error);
+                        missing = true;
+                    }
+                }
+            }
+            if !missing {
+                break;
+            }
+
+            // there is no download, only the English ROMs may be redistributed with the emulator
+            println!("The ROMs are incomplete. The English ones come with the emulator in the rom directory,");
+            println!("the others have to be dumped from a machine you own.");
+            let directory = ask("Directory with the ROM files, or nothing to use the built-in minimal firmware", "")?;
+            if directory.is_empty() {
+                self.set("machine", "firmware", toml::Value::from(memory::Firmware::Minimal.name()));
+                return Ok(());
+            }
+
+            // the files are expected under the names the emulator loads them by
+            let (os_path, basic_path, amsdos_path) = memory::RomPaths::default().resolve(language);
+            let in_directory = |path: String| {
+                let name = std::path::Path::new(&path).file_name().unwrap().to_owned();
+                Some(std::path::Path::new(&directory).join(name).to_string_lossy().to_string())
+            };
+            roms = memory::RomPaths {
+                os: in_directory(os_path),
+                basic: in_directory(basic_path),
+                amsdos: in_directory(amsdos_path),
+            };
+        }
+
+        self.set("machine", "firmware", toml::Value::from(memory::Firmware::Amstrad.name()));
+        let overrides = [("os", roms.os), ("basic", roms.basic), ("amsdos", roms.amsdos)];
+        for (key, path) in overrides.iter() {
+            if let Some(path) = path {
+                self.set("roms", key, toml::Value::from(path.as_str()));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn ask_keyboard(&mut self) -> std::io::Result<()> {
+        println!();
+        println!("Host keys map to CPC keys by their position, which suits games, or by their symbol, which");
+        println!("suits typing. Ctrl+K switches while running.");
+        let mode = ask_choice("Keyboard mapping", &["positional", "symbolic"], "positional")?;
+        self.set("machine", "keyboard_mode", toml::Value::from(mode.as_str()));
+
+        loop {
+            let keymap = ask("Key map file with hotkeys and remapped keys, or nothing for none", "")?;
+            if keymap.is_empty() {
+                return Ok(());
+            }
+            match key_mapper::KeyMapper::load(&keymap) {
+                Ok(_) => {
+                    self.set("machine", "keymap", toml::Value::from(keymap.as_str()));
+                    return Ok(());
+                }
+                Err(error) => println!("Could not load key map \"{}\": {}", keymap, error),
+            }
+        }
+    }
+
+    fn ask_audio(&mut self) -> std::io::Result<()> {
+        println!();
+        let devices = audio_output::list_devices();
+        for (index, device) in devices.iter().enumerate() {
+            println!("{:>3}  {}", index + 1, device);
+        }
+
+        loop {
+            let mut settings = audio_output::AudioSettings::default();
+            let choice = ask("Number of the audio device, or nothing for the default one", "")?;
+            if !choice.is_empty() {
+                match choice.parse::<usize>().ok().and_then(|number| devices.get(number.wrapping_sub(1))) {
+                    Some(device) => settings.device = Some(device.clone()),
+                    None => {
+                        println!("There is no device {}.", choice);
+                        continue;
+                    }
+                }
+            }
+            let latency = ask("Audio latency in milliseconds, raise it if the sound crackles", &settings.latency_milliseconds.to_string())?;
+            match latency.parse::<u32>() {
+                Ok(milliseconds) if milliseconds > 0 => settings.latency_milliseconds = milliseconds,
+                _ => {
+                    println!("Invalid latency \"{}\".", latency);
+                    continue;
+                }
+            }
+
+            if let Err(error) = play_test_tone(&settings) {
+                println!("Could not play the test tone: {}", error);
+            } else if ask_yes_no("Did you hear a one second tone?", true)? {
+                if let Some(device) = settings.device {
+                    self.set("audio", "device", toml::Value::from(device));
+                }
+                self.set("audio", "latency", toml::Value::from(settings.latency_milliseconds as i64));
+                return Ok(());
+            }
+            if !ask_yes_no("Try other audio settings?", true)? {
+                return Ok(());
+            }
+        }
+    }
+}
+
+fn play_test_tone(settings: &audio_output::AudioSettings) -> std::io::Result<()> {
+    // a square wave, like the PSG would play it, pushed in 10ms chunks
+    let mixer = mixer::Mixer::new_shared(mixer::DEFAULT_SAMPLE_RATE);
+    let output = audio_output::AudioOutput::open(&mut mixer.borrow_mut(), settings)?;
+    let sample_rate = mixer.borrow().get_sample_rate();
+    let half_period = sample_rate / (2 * TEST_TONE_HZ);
+
+    let mut position = 0;
+    for _ in 0..TEST_TONE_CHUNKS {
+        for _ in 0..sample_rate / 100 {
+            let level = if (position / half_period) & 1 == 0 { TEST_TONE_LEVEL } else { 0.0 };
+            mixer.borrow_mut().mix(1, [level, 0.0, 0.0, 0.0]);
+            position += 1;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    }
+    std::thread::sleep(std::time::Duration::from_millis(settings.latency_milliseconds as u64)); // let the device play what is buffered
+    drop(output);
+
+    Ok(())
+}
+
+fn ask(question: &str, default: &str) -> std::io::Result<String> {
+    // an empty answer takes the default, the end of the input cancels the wizard
+    if default.is_empty() {
+        print!("{}: ", question);
+    } else {
+        print!("{} [{}]: ", question, default);
+    }
+    std::io::stdout().flush()?;
+
+    let mut answer = String::new();
+    if std::io::stdin().lock().read_line(&mut answer)? == 0 {
+        return Err(std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "setup cancelled"));
+    }
+    let answer = answer.trim();
+    Ok(if answer.is_empty() { default.to_string() } else { answer.to_string() })
+}
+
+fn ask_choice(question: &str, choices: &[&str], default: &str) -> std::io::Result<String> {
+    loop {
+        let answer = ask(&format!("{} ({})", question, choices.join(", ")), default)?.to_lowercase();
+        if choices.contains(&answer.as_str()) {
+            return Ok(answer);
+        }
+        println!("Unknown choice \"{}\".", answer);
+    }
+}
+
+fn ask_yes_no(question: &str, default: bool) -> std::io::Result<bool> {
+    loop {
+        let answer = ask(question, if default { "y" } else { "n" })?.to_lowercase();
+        match answer.as_str() {
+            "y" | "yes" => return Ok(true),
+            "n" | "no" => return Ok(false),
+            _ => println!("Please answer y or n."),
+        }
+    }
+}
@@ -0,0 +1,402 @@
+use std::convert::TryInto;
+use std::io::{Read, Write};
+
+use serde::{Deserialize, Serialize};
+
+use crate::bytes;
+
+const DEFAULT_CRTC_REGISTERS: [u8; 18] = [63, 40, 46, 0x8e, 38, 0, 25, 30, 0, 7, 0, 0, 0x30, 0, 0, 0, 0, 0];
+const JSON_BYTES_PER_ROW: usize = 32;
+const DEFAULT_PEN_COLORS: [u8; 17] = [
+    0x04, 0x0a, 0x13, 0x0c, 0x0b, 0x14, 0x15, 0x0d, 0x06, 0x1e, 0x1f, 0x07, 0x12, 0x19, 0x04, 0x17, 0x04,
+];
+
+#[derive(Serialize, Deserialize)]
+pub struct Snapshot {
+    pub version: u8,
+    pub registers: [u16; 13], // AF, BC, DE, HL, AF', BC', DE', HL', IR, IX, IY, SP, PC
+    pub iff1: bool,
+    pub iff2: bool,
+    pub interrupt_mode: u8,
+    pub selected_pen: u8,
+    pub pen_colors: [u8; 17],
+    pub multi_configuration: u8,
+    pub ram_configuration: u8,
+    pub crtc_selected_register: u8,
+    pub crtc_registers: [u8; 18],
+    pub upper_rom: u8,
+    pub ppi_ports: [u8; 4],
+    pub psg_selected_register: u8,
+    pub psg_registers: [u8; 16],
+    #[serde(with = "memory_rows")]
+    pub memory: Vec<u8>,
+}
+
+impl Snapshot {
+    pub fn load(filename: &str) -> std::io::Result<Snapshot> {
+        let mut file = std::fs::File::open(filename)?;
+        let mut contents = Vec::new();
+        file.read_to_end(&mut contents)?;
+
+        Snapshot::from_bytes(&contents)
+    }
+
+    pub fn from_bytes(contents: &[u8]) -> std::io::Result<Snapshot> {
+        // e.g. for frontends that get their files from somewhere else than the file system
+        if contents.len() < 0x100 || &contents[0..8] != b"MV - SNA" {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "Could not find the expected file header."));
+        }
+
+        let word_at = |offset: usize| bytes::read_word(contents, offset).unwrap(); // the header is complete
+
+        let registers = [
+            word_at(0x11), // AF
+            word_at(0x13), // BC
+            word_at(0x15), // DE
+            word_at(0x17), // HL
+            word_at(0x26), // AF'
+            word_at(0x28), // BC'
+            word_at(0x2a), // DE'
+            word_at(0x2c), // HL'
+            word_at(0x19), // IR
+            word_at(0x1d), // IX
+            word_at(0x1f), // IY
+            word_at(0x21), // SP
+            word_at(0x23), // PC
+        ];
+
+        let memory_size = word_at(0x6b) as usize * 1024;
+        // version 3 snapshots may omit the memory dump and store it in chunks instead
+        let memory_end = (0x100 + memory_size).min(contents.len());
+        let mut memory = contents[0x100..memory_end].to_vec();
+        read_memory_chunks(&contents[memory_end..], &mut memory)?;
+
+        let snapshot = Snapshot {
+            version: contents[0x10],
+            registers,
+            iff1: contents[0x1b] & 0x01 != 0,
+            iff2: contents[0x1c] & 0x01 != 0,
+            interrupt_mode: contents[0x25],
+            selected_pen: contents[0x2e],
+            pen_colors: contents[0x2f..0x40].try_into().unwrap(),
+            multi_configuration: contents[0x40],
+            ram_configuration: contents[0x41],
+            crtc_selected_register: contents[0x42],
+            crtc_registers: contents[0x43..0x55].try_into().unwrap(),
+            upper_rom: contents[0x55],
+            ppi_ports: contents[0x56..0x5a].try_into().unwrap(),
+            psg_selected_register: contents[0x5a],
+            psg_registers: contents[0x5b..0x6b].try_into().unwrap(),
+            memory,
+        };
+
+        Ok(snapshot)
+    }
+
+    pub fn from_raw_dump(filename: &str, descriptor_filename: Option<&str>) -> std::io::Result<Snapshot> {
+        // raw 64K or 128K memory dumps (e.g. from WinAPE) do not contain any machine state,
+        // so it is taken from an optional descriptor with lines like "pc = 0x4000"
+        let memory = std::fs::read(filename)?;
+        if memory.len() != 0x10000 && memory.len() != 0x20000 {
+            return Err(invalid_data(format!("Unexpected memory dump size of {} bytes.", memory.len())));
+        }
+
+        let mut snapshot = Snapshot {
+            version: 2,
+            registers: [0; 13],
+            iff1: true,
+            iff2: true,
+            interrupt_mode: 1,
+            selected_pen: 0,
+            pen_colors: DEFAULT_PEN_COLORS,
+            multi_configuration: 0x8d, // mode 1, both ROMs disabled
+            ram_configuration: 0,
+            crtc_selected_register: 0,
+            crtc_registers: DEFAULT_CRTC_REGISTERS,
+            upper_rom: 0,
+            ppi_ports: [0xff, 0x00, 0x00, 0x82],
+            psg_selected_register: 0,
+            psg_registers: [0; 16],
+            memory,
+        };
+        snapshot.registers[11] = 0xc000; // SP
+
+        if let Some(descriptor_filename) = descriptor_filename {
+            let descriptor = std::fs::read_to_string(descriptor_filename)?;
+            for line in descriptor.lines() {
+                let line = line.split('#').next().unwrap().trim();
+                if line.is_empty() {
+                    continue;
+                }
+
+                let (key, value) = match line.find('=') {
+                    Some(position) => (line[..position].trim().to_lowercase(), parse_number(line[(position + 1)..].trim())?),
+                    None => return Err(invalid_data(format!("Missing \"=\" in line \"{}\".", line))),
+                };
+                snapshot.apply_descriptor_entry(&key, value)?;
+            }
+        }
+
+        Ok(snapshot)
+    }
+
+    pub fn from_json(text: &str) -> std::io::Result<Snapshot> {
+        // the same fields as the binary format, for editing by hand or generating with scripts
+        let snapshot: Snapshot = serde_json::from_str(text).map_err(|error| invalid_data(error.to_string()))?;
+        if snapshot.memory.is_empty() || snapshot.memory.len() & 0xffff != 0 {
+            return Err(invalid_data(format!("Memory has to be a multiple of 64K, not {} bytes.", snapshot.memory.len())));
+        }
+
+        Ok(snapshot)
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap() // every field has a JSON representation
+    }
+
+    pub fn save(&self, filename: &str) -> std::io::Result<()> {
+        let mut file = std::fs::File::create(filename)?;
+        file.write_all(&self.to_bytes())
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        // version 2 is read by every emulator that reads snapshots of more than 64K
+        self.to_bytes_with_version(2).unwrap() // version 2 takes any memory size
+    }
+
+    pub fn to_bytes_with_version(&self, version: u8) -> std::io::Result<Vec<u8>> {
+        // version 3 stores the memory in compressed MEM chunks, versions 1 and 2 as a plain dump.
+        // The fields version 2 and 3 added are left at 0, which means unknown
+        match version {
+            1 | 2 => {
+                if self.memory.len() > 0xffff * 1024 {
+                    return Err(invalid_data(format!("{} bytes of memory do not fit a version {} snapshot.", self.memory.len(), version)));
+                }
+            }
+            3 => {
+                if self.memory.len() > 9 * 0x10000 {
+                    return Err(invalid_data("More than 576K of memory do not fit a version 3 snapshot.".to_string()));
+                }
+            }
+            _ => return Err(invalid_data(format!("Snapshot version {} is not supported.", version))),
+        }
+
+        let mut header = vec![0; 0x100];
+        header[0..8].copy_from_slice(b"MV - SNA");
+        header[0x10] = version;
+
+        let offsets = [0x11, 0x13, 0x15, 0x17, 0x26, 0x28, 0x2a, 0x2c, 0x19, 0x1d, 0x1f, 0x21, 0x23];
+        for (offset, value) in offsets.iter().zip(self.registers.iter()) {
+            bytes::write_word(&mut header, *offset, *value);
+        }
+
+        header[0x1b] = self.iff1 as u8;
+        header[0x1c] = self.iff2 as u8;
+        header[0x25] = self.interrupt_mode;
+        header[0x2e] = self.selected_pen;
+        header[0x2f..0x40].copy_from_slice(&self.pen_colors);
+        header[0x40] = self.multi_configuration;
+        header[0x41] = self.ram_configuration;
+        header[0x42] = self.crtc_selected_register;
+        header[0x43..0x55].copy_from_slice(&self.crtc_registers);
+        header[0x55] = self.upper_rom;
+        header[0x56..0x5a].copy_from_slice(&self.ppi_ports);
+        header[0x5a] = self.psg_selected_register;
+        header[0x5b..0x6b].copy_from_slice(&self.psg_registers);
+
+        let mut contents = header;
+        if version < 3 {
+            // the size is stored in K, a partial K from a cut off snapshot is filled up
+            let kilobytes = (self.memory.len() + 0x3ff) >> 10;
+            bytes::write_word(&mut contents, 0x6b, kilobytes as u16);
+            contents.extend_from_slice(&self.memory);
+            contents.resize(0x100 + kilobytes * 1024, 0);
+            return Ok(contents);
+        }
+
+        // the memory size stays 0, so readers take the memory from the chunks
+        for (bank, data) in self.memory.chunks(0x10000).enumerate() {
+            let chunk = compress_chunk(data);
+            contents.extend_from_slice(&[b'M', b'E', b'M', b'0' + bank as u8, 0, 0, 0, 0]);
+            let length_offset = contents.len() - 4;
+            bytes::write_dword(&mut contents, length_offset, chunk.len() as u32);
+            contents.extend_from_slice(&chunk);
+        }
+
+        Ok(contents)
+    }
+
+    fn apply_descriptor_entry(&mut self, key: &str, value: u16) -> std::io::Result<()> {
+        let register = ["af", "bc", "de", "hl", "af'", "bc'", "de'", "hl'", "ir", "ix", "iy", "sp", "pc"]
+            .iter()
+            .position(|name| *name == key);
+
+        match (key, register) {
+            (_, Some(register)) => self.registers[register] = value,
+            ("im", _) => self.interrupt_mode = value as u8,
+            ("iff", _) => {
+                self.iff1 = value != 0;
+                self.iff2 = value != 0;
+            }
+            ("mode", _) => self.multi_configuration = (self.multi_configuration & !0x03) | (value as u8 & 0x03),
+            ("ram_configuration", _) => self.ram_configuration = value as u8,
+            ("upper_rom", _) => self.upper_rom = value as u8,
+            _ => return Err(invalid_data(format!("Unknown descriptor entry \"{}\".", key))),
+        }
+
+        Ok(())
+    }
+
+    pub fn print_info(&self) {
+        println!("Version: {}", self.version);
+        println!(
+            "AF = {:#06x}, BC = {:#06x}, DE = {:#06x}, HL = {:#06x}",
+            self.registers[0], self.registers[1], self.registers[2], self.registers[3]
+        );
+        println!(
+            "IX = {:#06x}, IY = {:#06x}, SP = {:#06x}, PC = {:#06x}",
+            self.registers[9], self.registers[10], self.registers[11], self.registers[12]
+        );
+        println!(
+            "IM = {}, IFF1 = {}, IFF2 = {}",
+            self.interrupt_mode, self.iff1, self.iff2
+        );
+        println!(
+            "Screen mode: {}, lower ROM: {}, upper ROM: {} (#{})",
+            self.multi_configuration & 0x03,
+            if self.multi_configuration & 0x04 == 0 { "on" } else { "off" },
+            if self.multi_configuration & 0x08 == 0 { "on" } else { "off" },
+            self.upper_rom
+        );
+        println!("Memory: {}K", self.memory.len() / 1024);
+    }
+}
+
+fn read_memory_chunks(mut chunks: &[u8], memory: &mut Vec<u8>) -> std::io::Result<()> {
+    // MEM0 to MEM8 chunks of version 3 snapshots (as written by CPCEC) hold run-length encoded 64K banks
+    while chunks.len() >= 8 {
+        let id = &chunks[0..4];
+        let length = bytes::read_dword(chunks, 4).unwrap() as usize; // the chunk header is complete
+        if chunks.len() - 8 < length {
+            return Err(invalid_data("Snapshot chunk exceeds the file size.".to_string()));
+        }
+        let data = &chunks[8..(8 + length)];
+
+        if &id[0..3] == b"MEM" && (b'0'..=b'8').contains(&id[3]) {
+            let bank_start = (id[3] - b'0') as usize * 0x10000;
+            let bank = decompress_chunk(data);
+            if memory.len() < bank_start + bank.len() {
+                memory.resize(bank_start + bank.len(), 0);
+            }
+            memory[bank_start..(bank_start + bank.len())].copy_from_slice(&bank);
+        }
+
+        chunks = &chunks[(8 + length)..];
+    }
+
+    Ok(())
+}
+
+fn decompress_chunk(data: &[u8]) -> Vec<u8> {
+    if data.len() == 0x10000 {
+        return data.to_vec(); // stored uncompressed
+    }
+
+    let mut bank = Vec::with_capacity(0x10000);
+    let mut i = 0;
+    while i < data.len() {
+        if data[i] == 0xe5 && i + 1 < data.len() {
+            let count = data[i + 1] as usize;
+            if count == 0 {
+                bank.push(0xe5);
+                i += 2;
+            } else if i + 2 < data.len() {
+                bank.resize(bank.len() + count, data[i + 2]);
+                i += 3;
+            } else {
+                break;
+            }
+        } else {
+            bank.push(data[i]);
+            i += 1;
+        }
+    }
+    bank.resize(0x10000, 0);
+
+    bank
+}
+
+fn compress_chunk(data: &[u8]) -> Vec<u8> {
+    // the inverse of decompress_chunk: runs of 3 or more bytes become E5 <count> <byte>, a single
+    // E5 becomes E5 00. Banks that do not get smaller are stored uncompressed
+    let mut chunk = Vec::new();
+    let mut i = 0;
+    while i < data.len() {
+        let value = data[i];
+        let run = data[i..].iter().take(0xff).take_while(|byte| **byte == value).count();
+        if run >= 3 || (value == 0xe5 && run > 1) {
+            chunk.extend_from_slice(&[0xe5, run as u8, value]);
+            i += run;
+        } else if value == 0xe5 {
+            chunk.extend_from_slice(&[0xe5, 0]);
+            i += 1;
+        } else {
+            chunk.push(value);
+            i += 1;
+        }
+    }
+
+    if chunk.len() >= 0x10000 {
+        let mut bank = data.to_vec();
+        bank.resize(0x10000, 0);
+        return bank;
+    }
+
+    chunk
+}
+
+fn parse_number(value: &str) -> std::io::Result<u16> {
+    let result = if let Some(hex) = value.strip_prefix("0x").or_else(|| value.strip_prefix('&')).or_else(|| value.strip_prefix('#')) {
+        u16::from_str_radix(hex, 16)
+    } else {
+        value.parse::<u16>()
+    };
+
+    result.map_err(|error| invalid_data(format!("Invalid number \"{}\": {}", value, error)))
+}
+
+mod memory_rows {
+    // the memory as rows of hex digits instead of one number per byte, which keeps JSON snapshots
+    // small enough to read and diff
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(memory: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        let rows = memory.chunks(super::JSON_BYTES_PER_ROW).map(|row| {
+            row.iter().map(|byte| format!("{:02x}", byte)).collect::<String>()
+        });
+        serializer.collect_seq(rows)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let rows = Vec::<String>::deserialize(deserializer)?;
+        let mut memory = Vec::with_capacity(rows.len() * super::JSON_BYTES_PER_ROW);
+        for row in rows {
+            if row.len() % 2 != 0 {
+                return Err(serde::de::Error::custom(format!("odd number of hex digits in \"{}\"", row)));
+            }
+            for index in (0..row.len()).step_by(2) {
+                let byte = row
+                    .get(index..index + 2)
+                    .and_then(|digits| u8::from_str_radix(digits, 16).ok())
+                    .ok_or_else(|| serde::de::Error::custom(format!("invalid hex digits in \"{}\"", row)))?;
+                memory.push(byte);
+            }
+        }
+
+        Ok(memory)
+    }
+}
+
+fn invalid_data(message: String) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message)
+}
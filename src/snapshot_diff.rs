@@ -0,0 +1,136 @@
+use crate::sna_file;
+
+const MERGE_DISTANCE: usize = 16; // changed bytes closer than this form one range
+
+const REGISTER_NAMES: [&str; 13] = ["AF", "BC", "DE", "HL", "AF'", "BC'", "DE'", "HL'", "IR", "IX", "IY", "SP", "PC"];
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Change {
+    pub name: String,
+    pub before: u16,
+    pub after: u16,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MemoryRange {
+    pub start: usize,
+    pub end: usize,     // inclusive
+    pub changed: usize, // bytes in the range that differ, the range may include unchanged ones
+}
+
+// what changed between two machine states, e.g. before and after running a routine: the CPU, the
+// registers of the devices and the ranges of memory that were written
+pub struct SnapshotDiff {
+    pub cpu: Vec<Change>,
+    pub devices: Vec<Change>,
+    pub memory: Vec<MemoryRange>,
+    pub memory_sizes: (usize, usize), // of both snapshots, in bytes
+}
+
+impl SnapshotDiff {
+    pub fn compare(before: &sna_file::Snapshot, after: &sna_file::Snapshot) -> SnapshotDiff {
+        let mut cpu = Vec::new();
+        for (index, name) in REGISTER_NAMES.iter().enumerate() {
+            push_change(&mut cpu, name, before.registers[index], after.registers[index]);
+        }
+        push_change(&mut cpu, "IFF1", before.iff1 as u16, after.iff1 as u16);
+        push_change(&mut cpu, "IFF2", before.iff2 as u16, after.iff2 as u16);
+        push_change(&mut cpu, "IM", before.interrupt_mode as u16, after.interrupt_mode as u16);
+
+        let mut devices = Vec::new();
+        push_change(&mut devices, "Gate array pen", before.selected_pen as u16, after.selected_pen as u16);
+        for (pen, (before_color, after_color)) in before.pen_colors.iter().zip(after.pen_colors.iter()).enumerate() {
+            let name = if pen == 16 { "Border color".to_string() } else { format!("Pen {} color", pen) };
+            push_change(&mut devices, &name, *before_color as u16, *after_color as u16);
+        }
+        push_change(&mut devices, "Multi configuration", before.multi_configuration as u16, after.multi_configuration as u16);
+        push_change(&mut devices, "RAM configuration", before.ram_configuration as u16, after.ram_configuration as u16);
+        push_change(&mut devices, "Upper ROM", before.upper_rom as u16, after.upper_rom as u16);
+        push_change(&mut devices, "CRTC register select", before.crtc_selected_register as u16, after.crtc_selected_register as u16);
+        for (register, (before_value, after_value)) in before.crtc_registers.iter().zip(after.crtc_registers.iter()).enumerate() {
+            push_change(&mut devices, &format!("CRTC R{}", register), *before_value as u16, *after_value as u16);
+        }
+        for (index, name) in ["PPI port A", "PPI port B", "PPI port C", "PPI control"].iter().enumerate() {
+            push_change(&mut devices, name, before.ppi_ports[index] as u16, after.ppi_ports[index] as u16);
+        }
+        push_change(&mut devices, "PSG register select", before.psg_selected_register as u16, after.psg_selected_register as u16);
+        for (register, (before_value, after_value)) in before.psg_registers.iter().zip(after.psg_registers.iter()).enumerate() {
+            push_change(&mut devices, &format!("PSG R{}", register), *before_value as u16, *after_value as u16);
+        }
+
+        SnapshotDiff {
+            cpu,
+            devices,
+            memory: changed_ranges(&before.memory, &after.memory),
+            memory_sizes: (before.memory.len(), after.memory.len()),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cpu.is_empty() && self.devices.is_empty() && self.memory.is_empty() && self.memory_sizes.0 == self.memory_sizes.1
+    }
+
+    pub fn lines(&self) -> Vec<String> {
+        if self.is_empty() {
+            return vec!["No differences".to_string()];
+        }
+
+        let mut lines = Vec::new();
+        for (title, changes) in [("CPU", &self.cpu), ("Devices", &self.devices)].iter() {
+            if changes.is_empty() {
+                continue;
+            }
+            lines.push(format!("{}:", title));
+            for change in changes.iter() {
+                lines.push(format!("  {:<22}{:04x} -> {:04x}", change.name, change.before, change.after));
+            }
+        }
+
+        if self.memory_sizes.0 != self.memory_sizes.1 {
+            lines.push(format!("Memory size: {}K -> {}K", self.memory_sizes.0 >> 10, self.memory_sizes.1 >> 10));
+        }
+        if !self.memory.is_empty() {
+            let total: usize = self.memory.iter().map(|range| range.changed).sum();
+            lines.push(format!("Memory: {} bytes changed in {} ranges", total, self.memory.len()));
+            for range in &self.memory {
+                lines.push(format!("  {:05x}-{:05x}  {} of {} bytes", range.start, range.end, range.changed, range.end - range.start + 1));
+            }
+        }
+
+        lines
+    }
+}
+
+fn push_change(changes: &mut Vec<Change>, name: &str, before: u16, after: u16) {
+    if before != after {
+        changes.push(Change {
+            name: name.to_string(),
+            before,
+            after,
+        });
+    }
+}
+
+fn changed_ranges(before: &[u8], after: &[u8]) -> Vec<MemoryRange> {
+    // only memory both snapshots have is compared, e.g. the first 64K of a 64K and a 128K snapshot
+    let mut ranges: Vec<MemoryRange> = Vec::new();
+    for (address, (before_value, after_value)) in before.iter().zip(after.iter()).enumerate() {
+        if before_value == after_value {
+            continue;
+        }
+
+        match ranges.last_mut() {
+            Some(range) if address - range.end < MERGE_DISTANCE => {
+                range.end = address;
+                range.changed += 1;
+            }
+            _ => ranges.push(MemoryRange {
+                start: address,
+                end: address,
+                changed: 1,
+            }),
+        }
+    }
+
+    ranges
+}
@@ -0,0 +1,272 @@
+use std::cell::RefCell;
+use std::f32::consts::PI;
+use std::ops::RangeInclusive;
+use std::rc::Rc;
+
+use crate::expansion;
+use crate::system::SystemConfig;
+
+const STATUS_LOAD_REQUEST: u8 = 0x80; // set while the input buffer holds an allophone, i.e. no new one can be written
+const STATUS_STANDBY: u8 = 0x40; // set while nothing is spoken
+const PITCH: f32 = 100.0; // in Hz, the SP0256-AL2 speaks with a fixed pitch
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Interface {
+    Ssa1,      // Amstrad SSA-1
+    DkTronics, // dk'tronics speech synthesizer
+}
+
+impl Interface {
+    pub fn from_name(name: &str) -> Option<Interface> {
+        match name {
+            "ssa1" => Some(Interface::Ssa1),
+            "dktronics" => Some(Interface::DkTronics),
+            _ => None,
+        }
+    }
+
+    fn port(&self) -> u16 {
+        match self {
+            Interface::Ssa1 => 0xfbee,
+            Interface::DkTronics => 0xfbfe,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Sound {
+    Pause,
+    Voiced,   // vowels, nasals and liquids
+    Unvoiced, // fricatives like SS or FF
+    Mixed,    // voiced fricatives like ZZ or VV
+    Plosive,  // closure followed by a short burst
+}
+
+struct Allophone {
+    duration: u16, // in milliseconds
+    sound: Sound,
+    formants: (f32, f32), // the first two formants in Hz
+}
+
+const fn allophone(duration: u16, sound: Sound, f1: f32, f2: f32) -> Allophone {
+    Allophone {
+        duration,
+        sound,
+        formants: (f1, f2),
+    }
+}
+
+// the 64 allophones of the SP0256-AL2 with their durations from the datasheet, PA1 to BB2
+const ALLOPHONES: [Allophone; 64] = [
+    allophone(10, Sound::Pause, 0.0, 0.0),         // PA1
+    allophone(30, Sound::Pause, 0.0, 0.0),         // PA2
+    allophone(50, Sound::Pause, 0.0, 0.0),         // PA3
+    allophone(100, Sound::Pause, 0.0, 0.0),        // PA4
+    allophone(200, Sound::Pause, 0.0, 0.0),        // PA5
+    allophone(420, Sound::Voiced, 570.0, 840.0),   // OY
+    allophone(260, Sound::Voiced, 730.0, 1090.0),  // AY
+    allophone(70, Sound::Voiced, 530.0, 1840.0),   // EH
+    allophone(120, Sound::Plosive, 0.0, 0.0),      // KK3
+    allophone(210, Sound::Plosive, 0.0, 0.0),      // PP
+    allophone(140, Sound::Mixed, 280.0, 2200.0),   // JH
+    allophone(140, Sound::Voiced, 280.0, 1700.0),  // NN1
+    allophone(70, Sound::Voiced, 390.0, 1990.0),   // IH
+    allophone(140, Sound::Plosive, 0.0, 0.0),      // TT2
+    allophone(170, Sound::Voiced, 490.0, 1350.0),  // RR1
+    allophone(70, Sound::Voiced, 500.0, 1500.0),   // AX
+    allophone(180, Sound::Voiced, 280.0, 900.0),   // MM
+    allophone(100, Sound::Plosive, 0.0, 0.0),      // TT1
+    allophone(290, Sound::Mixed, 300.0, 1600.0),   // DH1
+    allophone(250, Sound::Voiced, 270.0, 2290.0),  // IY
+    allophone(280, Sound::Voiced, 530.0, 1840.0),  // EY
+    allophone(70, Sound::Plosive, 0.0, 0.0),       // DD1
+    allophone(100, Sound::Voiced, 300.0, 870.0),   // UW1
+    allophone(100, Sound::Voiced, 570.0, 840.0),   // AO
+    allophone(100, Sound::Voiced, 730.0, 1090.0),  // AA
+    allophone(180, Sound::Voiced, 270.0, 2290.0),  // YY2
+    allophone(120, Sound::Voiced, 660.0, 1720.0),  // AE
+    allophone(130, Sound::Unvoiced, 0.0, 0.0),     // HH1
+    allophone(80, Sound::Plosive, 0.0, 0.0),       // BB1
+    allophone(180, Sound::Unvoiced, 0.0, 0.0),     // TH
+    allophone(100, Sound::Voiced, 440.0, 1020.0),  // UH
+    allophone(260, Sound::Voiced, 300.0, 870.0),   // UW2
+    allophone(370, Sound::Voiced, 730.0, 1090.0),  // AW
+    allophone(160, Sound::Plosive, 0.0, 0.0),      // DD2
+    allophone(140, Sound::Plosive, 0.0, 0.0),      // GG3
+    allophone(190, Sound::Mixed, 300.0, 1100.0),   // VV
+    allophone(80, Sound::Plosive, 0.0, 0.0),       // GG1
+    allophone(160, Sound::Unvoiced, 0.0, 0.0),     // SH
+    allophone(190, Sound::Mixed, 300.0, 1800.0),   // ZH
+    allophone(120, Sound::Voiced, 490.0, 1350.0),  // RR2
+    allophone(150, Sound::Unvoiced, 0.0, 0.0),     // FF
+    allophone(190, Sound::Plosive, 0.0, 0.0),      // KK2
+    allophone(160, Sound::Plosive, 0.0, 0.0),      // KK1
+    allophone(210, Sound::Mixed, 300.0, 1600.0),   // ZZ
+    allophone(220, Sound::Voiced, 280.0, 2300.0),  // NG
+    allophone(110, Sound::Voiced, 360.0, 1300.0),  // LL
+    allophone(180, Sound::Voiced, 300.0, 610.0),   // WW
+    allophone(360, Sound::Voiced, 490.0, 1350.0),  // XR
+    allophone(200, Sound::Mixed, 300.0, 610.0),    // WH
+    allophone(130, Sound::Voiced, 270.0, 2290.0),  // YY1
+    allophone(190, Sound::Unvoiced, 0.0, 0.0),     // CH
+    allophone(160, Sound::Voiced, 490.0, 1350.0),  // ER1
+    allophone(300, Sound::Voiced, 490.0, 1350.0),  // ER2
+    allophone(240, Sound::Voiced, 450.0, 900.0),   // OW
+    allophone(240, Sound::Mixed, 300.0, 1600.0),   // DH2
+    allophone(90, Sound::Unvoiced, 0.0, 0.0),      // SS
+    allophone(190, Sound::Voiced, 280.0, 1700.0),  // NN2
+    allophone(180, Sound::Unvoiced, 0.0, 0.0),     // HH2
+    allophone(330, Sound::Voiced, 570.0, 840.0),   // OR
+    allophone(290, Sound::Voiced, 730.0, 1090.0),  // AR
+    allophone(350, Sound::Voiced, 390.0, 1990.0),  // YR
+    allophone(40, Sound::Plosive, 0.0, 0.0),       // GG2
+    allophone(190, Sound::Voiced, 360.0, 1300.0),  // EL
+    allophone(50, Sound::Plosive, 0.0, 0.0),       // BB2
+];
+
+pub type SpeechSynthesizerShared = Rc<RefCell<SpeechSynthesizer>>;
+
+// SP0256-AL2 based speech synthesizers. Without the chip's ROM the allophones are approximated by
+// formants and noise, which keeps the timing exact and speech recognizable enough for games.
+pub struct SpeechSynthesizer {
+    interface: Interface,
+    speaking: Option<(u8, u32)>, // allophone and elapsed microseconds
+    buffered: Option<u8>,
+    elapsed: u32, // in microseconds, for the oscillators
+    noise: u32,   // 17 bit LFSR
+    level: f32,
+}
+
+impl SpeechSynthesizer {
+    pub fn new_shared(interface: Interface) -> SpeechSynthesizerShared {
+        let speech = SpeechSynthesizer {
+            interface,
+            speaking: None,
+            buffered: None,
+            elapsed: 0,
+            noise: 1,
+            level: 0.0,
+        };
+
+        Rc::new(RefCell::new(speech))
+    }
+
+    pub fn read_status(&self) -> u8 {
+        let mut status = 0;
+        if self.buffered.is_some() {
+            status |= STATUS_LOAD_REQUEST;
+        }
+        if self.speaking.is_none() && self.buffered.is_none() {
+            status |= STATUS_STANDBY;
+        }
+
+        status
+    }
+
+    pub fn speak(&mut self, allophone: u8) {
+        // allophones written while the buffer is full are lost, like on the real chip
+        if self.buffered.is_none() {
+            self.buffered = Some(allophone & 0x3f);
+        }
+        if self.speaking.is_none() {
+            self.next_allophone();
+        }
+    }
+
+    pub fn step(&mut self, microseconds: u8) {
+        self.elapsed = (self.elapsed + microseconds as u32) % 1_000_000; // keeps the oscillators precise
+        self.noise_step(microseconds);
+
+        let (number, elapsed) = match &mut self.speaking {
+            Some((number, elapsed)) => {
+                *elapsed += microseconds as u32;
+                (*number, *elapsed)
+            }
+            None => {
+                self.level = 0.0;
+                return;
+            }
+        };
+
+        let allophone = &ALLOPHONES[number as usize];
+        let duration = allophone.duration as u32 * 1000;
+        if elapsed >= duration {
+            self.next_allophone();
+        }
+        self.level = self.synthesize(allophone, elapsed.min(duration), duration);
+    }
+
+    pub fn read_output_level(&self) -> f32 {
+        self.level
+    }
+
+    fn next_allophone(&mut self) {
+        self.speaking = self.buffered.take().map(|number| (number, 0));
+    }
+
+    fn synthesize(&self, allophone: &Allophone, elapsed: u32, duration: u32) -> f32 {
+        // a glottal pulse exciting the first two formants, and noise for fricatives and bursts
+        let time = self.elapsed as f32 / 1_000_000.0;
+        let glottal_phase = (time * PITCH).fract();
+        let (f1, f2) = allophone.formants;
+        let voice = (-glottal_phase * 8.0).exp() * ((2.0 * PI * f1 * time).sin() + 0.5 * (2.0 * PI * f2 * time).sin()) / 1.5;
+        let noise = if self.noise & 0x01 != 0 { 1.0 } else { -1.0 };
+
+        let amplitude = match allophone.sound {
+            Sound::Pause => return 0.0,
+            Sound::Voiced => voice,
+            Sound::Unvoiced => 0.3 * noise,
+            Sound::Mixed => 0.6 * voice + 0.2 * noise,
+            Sound::Plosive if elapsed < duration * 3 / 4 => 0.0,
+            Sound::Plosive => 0.5 * noise,
+        };
+
+        // fades in and out over 5ms to avoid clicks between allophones
+        let fade = (elapsed.min(duration - elapsed) as f32 / 5000.0).min(1.0);
+
+        (0.5 * (1.0 + amplitude) * fade).clamp(0.0, 1.0)
+    }
+
+    fn noise_step(&mut self, microseconds: u8) {
+        // clocked at roughly 1MHz like the PSG's noise generator
+        for _ in 0..microseconds {
+            let feedback = (self.noise ^ (self.noise >> 3)) & 0x01;
+            self.noise = (self.noise >> 1) | (feedback << 16);
+        }
+    }
+}
+
+impl expansion::ExpansionDevice for SpeechSynthesizer {
+    fn name(&self) -> &'static str {
+        "Speech"
+    }
+
+    fn port_ranges(&self) -> Vec<RangeInclusive<u16>> {
+        let port = self.interface.port();
+        vec![port..=port]
+    }
+
+    fn read_byte(&mut self, _port: u16) -> u8 {
+        self.read_status()
+    }
+
+    fn write_byte(&mut self, _port: u16, value: u8) {
+        self.speak(value);
+    }
+
+    fn step(&mut self, microseconds: u8) {
+        SpeechSynthesizer::step(self, microseconds)
+    }
+
+    fn read_audio_level(&self) -> Option<f32> {
+        Some(self.level)
+    }
+}
+
+pub fn new_ssa1(_config: &SystemConfig) -> expansion::ExpansionDeviceShared {
+    SpeechSynthesizer::new_shared(Interface::Ssa1)
+}
+
+pub fn new_dktronics(_config: &SystemConfig) -> expansion::ExpansionDeviceShared {
+    SpeechSynthesizer::new_shared(Interface::DkTronics)
+}
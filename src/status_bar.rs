@@ -0,0 +1,90 @@
+use std::time::Instant;
+
+use crate::memory;
+use crate::screen;
+use crate::system;
+use crate::workbench;
+
+pub const STATUS_BAR_HEIGHT: usize = 16;
+const TEXT_SCALE: usize = 2;
+
+// emulation speed, frame rate and media activity below the screen
+pub struct StatusBar {
+    font: memory::ROM,
+    buffer: Vec<u32>,
+    measure_start: Option<(Instant, u64)>, // with the emulated time in microseconds at that instant
+    frames_presented: u32,
+    speed: f64, // in percent of real time
+    frames_per_second: f64,
+}
+
+impl StatusBar {
+    pub fn new() -> StatusBar {
+        StatusBar {
+            font: memory::ROM::font(),
+            buffer: vec![0; screen::BUFFER_WIDTH * STATUS_BAR_HEIGHT],
+            measure_start: None,
+            frames_presented: 0,
+            speed: 0.0,
+            frames_per_second: 0.0,
+        }
+    }
+
+    pub fn update(&mut self, system: &dyn system::System, paused: bool, turbo: bool) {
+        // called once per presented frame, rates are averaged over a second to keep them readable
+        let emulated_microseconds = system.get_sync_statistics().emulated_microseconds;
+        self.frames_presented += 1;
+        match self.measure_start {
+            Some((start, start_microseconds)) => {
+                let elapsed = start.elapsed().as_secs_f64();
+                if elapsed >= 1.0 {
+                    self.speed = emulated_microseconds.saturating_sub(start_microseconds) as f64 / (elapsed * 10_000.0);
+                    self.frames_per_second = self.frames_presented as f64 / elapsed;
+                    self.measure_start = Some((Instant::now(), emulated_microseconds));
+                    self.frames_presented = 0;
+                }
+            }
+            None => self.measure_start = Some((Instant::now(), emulated_microseconds)),
+        }
+
+        let activity = system.get_drive_activity();
+        let disk = match (activity.disk_inserted, activity.disk_motor) {
+            (_, true) => "RUN",
+            (true, false) => "IN",
+            (false, false) => "--",
+        };
+        let disk = format!("{}{}", disk, if activity.disk_dirty { "*" } else { "" }); // unsaved changes
+        let tape = match (activity.tape_inserted, activity.tape_playing) {
+            (_, true) => "PLAY",
+            (true, false) => "IN",
+            (false, false) => "--",
+        };
+        let mut text = format!(
+            "{:4.0}% {:3.0} FPS  DISK {:<4} TAPE {:<4}",
+            self.speed, self.frames_per_second, disk, tape
+        );
+        if paused {
+            text.push_str("  PAUSED");
+        }
+        if turbo {
+            text.push_str("  TURBO");
+        }
+
+        workbench::draw_text(&self.font, &mut self.buffer, screen::BUFFER_WIDTH, TEXT_SCALE, &[text]);
+    }
+
+    pub fn get_speed(&self) -> f64 {
+        // in percent of real time, 0 until the first second has been measured
+        self.speed
+    }
+
+    pub fn get_buffer(&self) -> &[u32] {
+        &self.buffer
+    }
+}
+
+impl Default for StatusBar {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,99 @@
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::rtc;
+
+pub const DEFAULT_ROOT: &str = "storage";
+const NO_MEDIA_FOLDER: &str = "no-media";
+const MAX_TITLE_LENGTH: usize = 40; // of the folder name, before the hash
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StorageKind {
+    States,
+    Screenshots,
+    BugReports,
+}
+
+impl StorageKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            StorageKind::States => "states",
+            StorageKind::Screenshots => "screenshots",
+            StorageKind::BugReports => "bug-reports",
+        }
+    }
+
+    fn extension(&self) -> &'static str {
+        match self {
+            StorageKind::States => "sna",
+            StorageKind::Screenshots => "png",
+            StorageKind::BugReports => "zip",
+        }
+    }
+}
+
+// where the frontend puts the files it writes, one folder per game named after its title and media
+// hash, so two versions of a game stay apart, and one folder per kind of file in there:
+//
+//     storage/Chase HQ-1a2b3c4d/screenshots/2021-03-14_15-09-26.png
+//
+// The file names are the UTC time they were written, later files sort last
+pub struct StorageLayout {
+    root: PathBuf,
+    folder: String,
+}
+
+impl StorageLayout {
+    pub fn new(root: &str) -> StorageLayout {
+        StorageLayout {
+            root: PathBuf::from(root),
+            folder: NO_MEDIA_FOLDER.to_string(),
+        }
+    }
+
+    pub fn select_media(&mut self, hash: u32, title: &str) {
+        self.folder = format!("{}-{:08x}", folder_title(title), hash);
+    }
+
+    pub fn new_path(&self, kind: StorageKind) -> std::io::Result<String> {
+        // creates the folder, a second file within the same second gets a number instead of overwriting
+        let directory = self.root.join(&self.folder).join(kind.name());
+        std::fs::create_dir_all(&directory)?;
+
+        let timestamp = timestamp();
+        let mut path = directory.join(format!("{}.{}", timestamp, kind.extension()));
+        let mut number = 2;
+        while path.exists() {
+            path = directory.join(format!("{}-{}.{}", timestamp, number, kind.extension()));
+            number += 1;
+        }
+
+        Ok(path.to_string_lossy().to_string())
+    }
+}
+
+fn folder_title(title: &str) -> String {
+    // titles come from file names and the game database, only characters every file system accepts are kept
+    let title: String = title
+        .chars()
+        .map(|character| if character.is_ascii_alphanumeric() || " -_.".contains(character) { character } else { '_' })
+        .take(MAX_TITLE_LENGTH)
+        .collect();
+    let title = title.trim_matches(|character| character == ' ' || character == '.');
+
+    if title.is_empty() {
+        "untitled".to_string()
+    } else {
+        title.to_string()
+    }
+}
+
+fn timestamp() -> String {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0);
+    let (year, month, day, _, hours, minutes, seconds) = rtc::civil_time(seconds as i64);
+
+    format!("{:04}-{:02}-{:02}_{:02}-{:02}-{:02}", year, month, day, hours, minutes, seconds)
+}
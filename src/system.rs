@@ -1,19 +1,127 @@
+use crate::basic;
 use crate::bus;
+use crate::cdt_file;
+use crate::coverage;
 use crate::cpu;
 use crate::crtc;
 use crate::debugger;
+use crate::dsk_file;
+use crate::expansion;
+use crate::expression;
 use crate::fdc;
 use crate::gate_array;
 use crate::keyboard;
 use crate::memory;
+use crate::mixer;
 use crate::ppi;
+use crate::project;
 use crate::psg;
+use crate::rsx;
+use crate::rtc;
 use crate::screen;
+use crate::sna_file;
 use crate::tape;
+use crate::timeline;
+use crate::triggers;
+use bus::Bus;
 use memory::{Read, Write};
 
 use std::cell::RefCell;
+use std::collections::VecDeque;
 use std::rc::Rc;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+const DETERMINISTIC_CLOCK_TIME: i64 = 946_684_800; // 2000-01-01 00:00:00
+const CAS_READ_ADDRESS: u16 = 0x2836; // in the 464 lower ROM, also used by the other cassette routines
+const RECENT_TRACE_LENGTH: usize = 10_000; // executed instructions kept for bug reports
+
+const POWER_ON_SEED_SALT: u64 = 0x9e37_79b9_7f4a_7c15; // so the devices do not repeat the random RAM contents
+
+// which devices start with the parts of their state that real hardware leaves undefined randomized,
+// instead of cleared. The PPI is not among them, its reset defines all of its state. The values come
+// from the seed, so deterministic runs stay reproducible
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PowerOnState {
+    pub crtc: bool,       // all registers and the selected one, the firmware programs them while booting
+    pub gate_array: bool, // the pen colors and the selected pen
+    pub psg: bool,        // the selected register and the data bus, the reset line clears the registers
+}
+
+impl PowerOnState {
+    pub fn from_names(names: &str) -> Option<PowerOnState> {
+        // a comma separated list of devices, or all or none
+        let mut state = PowerOnState::default();
+        for name in names.split(',').map(|name| name.trim()) {
+            match name {
+                "crtc" => state.crtc = true,
+                "gate-array" => state.gate_array = true,
+                "psg" => state.psg = true,
+                "all" => {
+                    state = PowerOnState {
+                        crtc: true,
+                        gate_array: true,
+                        psg: true,
+                    }
+                }
+                "none" => (),
+                _ => return None,
+            }
+        }
+
+        Some(state)
+    }
+
+    pub fn names(&self) -> String {
+        let names: Vec<&str> = [(self.crtc, "crtc"), (self.gate_array, "gate-array"), (self.psg, "psg")]
+            .iter()
+            .filter(|(randomized, _)| *randomized)
+            .map(|(_, name)| *name)
+            .collect();
+        if names.is_empty() {
+            "none".to_string()
+        } else {
+            names.join(",")
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct SystemConfig {
+    pub deterministic: bool, // no host time or other entropy may influence the emulation
+    pub seed: u64,
+    pub ram_pattern: memory::RamPattern,
+    pub power_on: PowerOnState,
+    pub language: keyboard::Language, // selects the firmware ROMs
+    pub firmware: memory::Firmware,
+    pub roms: memory::RomPaths,
+    pub crtc_type: crtc::CrtcType,
+    pub ram_size: memory::RamSize,
+    pub expansions: Vec<expansion::ExpansionFactory>, // devices created and attached on power-on
+}
+
+impl SystemConfig {
+    pub fn register_expansion(&mut self, factory: expansion::ExpansionFactory) {
+        self.expansions.push(factory);
+    }
+
+    pub fn seed(&self) -> u64 {
+        if self.deterministic {
+            self.seed
+        } else {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|duration| duration.as_nanos() as u64)
+                .unwrap_or(self.seed)
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ZexResult {
+    Passed,
+    Failed(String), // the exerciser's message with the expected and the found CRC
+    TimedOut,
+}
 
 pub struct ZexHarness {
     cpu: cpu::CPUShared<memory::RAM, bus::DummyBus>,
@@ -21,8 +129,14 @@ pub struct ZexHarness {
 }
 
 impl ZexHarness {
+    const PROGRAM_ADDRESS: usize = 0x100;
+    const TEST_LIST_LOAD_OFFSET: usize = 12; // of the ld hl,tests after start, which the program jumps to first
+    const TEST_NAME_OFFSET: usize = 65; // after the flag mask, three 20 byte test vectors and the CRC
+    const TEST_NAME_LENGTH: usize = 30;
+    const TIMEOUT_CHECK_MASK: u32 = 0xffff; // the clock is read every 65536 instructions
+
     pub fn new(rom_path: &str) -> ZexHarness {
-        let mut memory = memory::RAM::from_file(0x10000, rom_path, 0x100);
+        let mut memory = memory::RAM::from_file(0x10000, rom_path, Self::PROGRAM_ADDRESS);
         memory.write_byte(0x0005, 0xc9); // patch with RET instruction
         memory.write_word(0x0006, 0xe400); // patch with initial SP
 
@@ -30,21 +144,82 @@ impl ZexHarness {
         let bus_shared = Rc::new(RefCell::new(bus::DummyBus::new()));
 
         ZexHarness {
-            cpu: cpu::CPU::new_shared(memory_shared.clone(), bus_shared, 0x100),
+            cpu: cpu::CPU::new_shared(memory_shared.clone(), bus_shared, Self::PROGRAM_ADDRESS as u16),
             memory: memory_shared,
         }
     }
 
+    pub fn list_tests(&self) -> Vec<String> {
+        // the names of the instruction groups, in the order the exerciser runs them
+        let memory = self.memory.borrow();
+        let mut names = Vec::new();
+        let mut entry = self.test_list_address();
+        loop {
+            let test = memory.read_word(entry) as usize;
+            if test == 0 {
+                break;
+            }
+            let name: String = (0..Self::TEST_NAME_LENGTH)
+                .map(|offset| memory.read_byte(test + Self::TEST_NAME_OFFSET + offset) as char)
+                .collect();
+            names.push(name.trim_end_matches('.').to_string());
+            entry += 2;
+        }
+
+        names
+    }
+
+    pub fn select_test(&mut self, index: usize) {
+        // the list is shortened to the one test, so the exerciser runs it and exits
+        let list_address = self.test_list_address();
+        let mut memory = self.memory.borrow_mut();
+        let test = memory.read_word(list_address + 2 * index);
+        memory.write_word(list_address, test);
+        memory.write_word(list_address + 2, 0);
+    }
+
     pub fn emulate(&mut self) {
+        self.run(&mut |character| print!("{}", character), None);
+        println!();
+    }
+
+    pub fn run_test(&mut self, timeout: std::time::Duration) -> ZexResult {
+        let mut output = String::new();
+        let deadline = std::time::Instant::now() + timeout;
+        if !self.run(&mut |character| output.push(character), Some(deadline)) {
+            return ZexResult::TimedOut;
+        }
+
+        match output.find("ERROR") {
+            Some(index) => ZexResult::Failed(output[index..].trim().to_string()),
+            None => ZexResult::Passed,
+        }
+    }
+
+    fn test_list_address(&self) -> usize {
+        let memory = self.memory.borrow();
+        let load_address = memory.read_word(Self::PROGRAM_ADDRESS + 1) as usize + Self::TEST_LIST_LOAD_OFFSET;
+        assert_eq!(memory.read_byte(load_address), 0x21, "not a zexdoc or zexall ROM");
+        memory.read_word(load_address + 1) as usize
+    }
+
+    fn run(&mut self, output: &mut dyn FnMut(char), deadline: Option<std::time::Instant>) -> bool {
+        // runs until the exerciser's warm boot, false if it took past the deadline
+        let mut instructions: u32 = 0;
         loop {
-            match self.cpu.borrow().registers.read_word(&cpu::Register16::PC) {
+            instructions = instructions.wrapping_add(1);
+            if instructions & Self::TIMEOUT_CHECK_MASK == 0 && matches!(deadline, Some(deadline) if std::time::Instant::now() > deadline) {
+                return false;
+            }
+
+            // the registers are read before the match, a borrow in it would last until its end
+            let program_counter = self.cpu.borrow().registers.read_word(&cpu::Register16::PC);
+            match program_counter {
                 0x0000 => break,
                 0x0005 => {
-                    match self.cpu.borrow().registers.read_byte(&cpu::Register8::C) {
-                        2 => print!(
-                            "{}",
-                            self.cpu.borrow().registers.read_byte(&cpu::Register8::E) as char
-                        ),
+                    let function = self.cpu.borrow().registers.read_byte(&cpu::Register8::C);
+                    match function {
+                        2 => output(self.cpu.borrow().registers.read_byte(&cpu::Register8::E) as char),
                         9 => {
                             let mut address =
                                 self.cpu.borrow().registers.read_word(&cpu::Register16::DE) as usize;
@@ -53,7 +228,7 @@ impl ZexHarness {
                                 if character == '$' {
                                     break;
                                 } else {
-                                    print!("{}", character);
+                                    output(character);
                                 }
                                 address += 1;
                             }
@@ -67,66 +242,378 @@ impl ZexHarness {
                 }
             }
         }
-        println!();
+
+        true
     }
 }
 
+// everything the emulation depends on at an instruction boundary, so it continues from a restored
+// state exactly like it did the first time. Snapshots are for exchange with other emulators and
+// leave out the counters. The keyboard keeps its own time, see keyboard::Keyboard::seek
+pub struct MachineState {
+    snapshot: sna_file::Snapshot,
+    cpu: cpu::ExecutionState,
+    crtc: crtc::CRTController,
+    gate_array: gate_array::CounterState,
+    fdc: fdc::ControllerState,
+    psg: psg::GeneratorState,
+    tape_position: (usize, u64),
+    bus: bus::SyncState,
+    gun_position: Option<usize>,
+    frame_microseconds: u32,
+    emulated_microseconds: u64,
+}
+
+impl MachineState {
+    pub fn share_unchanged(&mut self, previous: &MachineState) {
+        // keeps large unchanged parts such as the disk contents only once
+        self.fdc.share_disk_contents(&previous.fdc);
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SyncStatistics {
+    pub emulated_microseconds: u64,
+    pub wall_clock_microseconds: u64,
+    pub drift_microseconds: i64, // positive if the emulation runs ahead of real time
+    pub audio_buffer_fill: Option<f32>, // between 0 and 1, not available without audio output
+    pub audio_rate_adjustment_ppm: i32, // positive while more samples are made to fill the audio buffer
+    pub frames_emulated: u64,
+    pub frames_dropped: u64, // emulated frames that were never presented
+}
+
+#[derive(Clone, Debug)]
+pub struct DriveActivity {
+    pub disk_inserted: bool,
+    pub disk_motor: bool,
+    pub disk_dirty: bool, // written since it was loaded or saved
+    pub tape_inserted: bool,
+    pub tape_playing: bool,
+}
+
+pub struct SystemDebugView {
+    pub memory_banks: [memory::Bank; 4],
+    pub banking_events: Vec<memory::BankingEvent>,
+    pub memory_diffs: Vec<memory::MemoryDiff>,
+    pub interrupt_timeline: Vec<timeline::TimelineEvent>, // events of the last complete frame
+    pub psg_registers: [u8; 16],
+    pub psg_channels: Vec<[u8; 3]>, // recent output levels per channel, sampled every 16µs
+}
+
 pub trait System {
     fn emulate(&mut self) -> u8;
     fn get_screen(&self) -> screen::ScreenShared;
+    fn enable_layer_capture(&mut self, enable: bool);
     fn get_keyboard(&self) -> keyboard::KeyboardShared;
+    fn set_key_matrix(&mut self, line: usize, bit: u8, pressed: bool) -> bool;
+    fn read_key_matrix(&self) -> [u8; 10];
     fn activate_debugger(&mut self);
+    fn is_debugger_stopped(&self) -> bool;
+    fn resume_debugger(&mut self, resume: debugger::Resume);
+    fn add_breakpoint(&mut self, address: u16, condition: Option<expression::Expression>);
+    fn remove_breakpoint(&mut self, address: u16) -> bool;
+    fn list_breakpoints(&self) -> Vec<(u16, Option<String>)>;
+    fn load_disk(&mut self, disk: dsk_file::Disk);
+    fn eject_disk(&mut self, drive: usize) -> Option<dsk_file::Disk>;
+    fn save_disk(&mut self) -> std::io::Result<()>;
+    fn is_disk_dirty(&self) -> bool;
+    fn set_write_protected(&mut self, protect: bool);
+    fn is_write_protected(&self) -> bool;
+    fn load_tape(&mut self, tape: cdt_file::Tape);
+    fn get_tape(&self) -> tape::TapeControllerShared;
+    fn get_program_counter(&self) -> u16;
+    fn read_register(&self, register: cpu::Register16) -> u16;
+    fn read_register8(&self, register: cpu::Register8) -> u8;
+    fn read_raster_position(&self) -> (u16, u8);
+    fn write_register(&mut self, register: cpu::Register16, value: u16);
+    fn read_memory(&self, address: u16) -> u8;
+    fn disassemble(&self, address: u16, count: usize) -> Vec<(u16, String)>;
+    fn read_screen_text(&self) -> String;
+    fn read_basic_program(&self) -> String;
+    fn enable_real_time_clock(&mut self);
+    fn get_real_time_clock(&self) -> Option<rtc::RealTimeClockShared>;
+    fn get_debug_view(&self) -> SystemDebugView;
+    fn watch_memory(&mut self, start: usize, length: usize, mode: memory::WatchMode) -> usize; // by physical address
+    fn unwatch_memory(&mut self, id: usize);
+    fn notify_frame_presented(&mut self);
+    fn get_sync_statistics(&self) -> SyncStatistics;
+    fn reset_sync_statistics(&mut self);
+    fn get_drive_activity(&self) -> DriveActivity;
+    fn run_to_sync(&mut self, signal: bus::SyncSignal, count: u64) -> u64;
+    fn step_cycles(&mut self, microseconds: u64) -> u64;
+    fn step_scanline(&mut self) -> u64;
+    fn enable_io_trace(&mut self, enable: bool);
+    fn take_io_trace(&mut self) -> Vec<bus::IoAccess>;
+    fn set_io_snooper(&mut self, snooper: Option<bus::IoSnooper>) -> Option<bus::IoSnooper>;
+    fn enable_psg_capture(&mut self, enable: bool);
+    fn take_psg_capture(&mut self) -> (u64, Vec<psg::RegisterWrite>);
+    fn read_psg_register(&self, register: u8) -> u8;
+    fn write_psg_register(&mut self, register: u8, value: u8);
+    // by physical address, regardless of the RAM configuration, expansion RAM starts at 0x10000
+    fn read_ram(&self, address: usize) -> u8;
+    fn write_ram(&mut self, address: usize, value: u8);
+    fn attach_expansion(&mut self, device: expansion::ExpansionDeviceShared);
+    fn detach_expansion(&mut self, name: &str) -> bool;
+    fn list_expansions(&self) -> Vec<&'static str>;
+    fn get_config(&self) -> SystemConfig;
+    fn reconfigure(&mut self, config: SystemConfig);
+    fn reload_roms(&mut self) -> std::io::Result<()>;
+    fn list_rom_files(&self) -> Vec<String>;
+    fn add_trigger(&mut self, conditions: Vec<triggers::Condition>, callback: triggers::TriggerCallback) -> usize;
+    fn remove_trigger(&mut self, id: usize);
+    fn add_memory_observer(&mut self, observer: cpu::MemoryObserverShared) -> usize;
+    fn add_io_observer(&mut self, observer: cpu::IoObserverShared) -> usize;
+    fn remove_observer(&mut self, id: usize);
+    fn get_mixer(&self) -> mixer::MixerShared;
+    fn save_snapshot(&self) -> sna_file::Snapshot;
+    fn load_snapshot(&mut self, snapshot: &sna_file::Snapshot);
+    fn save_machine_state(&self) -> MachineState;
+    fn load_machine_state(&mut self, state: &MachineState);
+    fn read_recent_trace(&self) -> Vec<(u64, u16)>;
+    fn get_project(&self) -> project::ProjectShared;
+    fn read_coverage(&self) -> coverage::Coverage;
 }
 
 pub struct CPC464 {
     cpu: cpu::CPUShared<memory::Memory, bus::StandardBus>,
     bus: bus::StandardBusShared,
+    memory: memory::MemoryShared,
+    crtc: crtc::CRTControllerShared,
+    psg: psg::SoundGeneratorShared,
+    tape: tape::TapeControllerShared,
+    mixer: mixer::MixerShared,
+    fdc: fdc::FloppyDiskControllerShared,
+    gate_array: gate_array::GateArrayShared,
+    ppi: ppi::PeripheralInterfaceShared,
     screen: screen::ScreenShared,
     keyboard: keyboard::KeyboardShared,
+    rtc: Option<rtc::RealTimeClockShared>,
+    hot_plugged: Vec<expansion::ExpansionDeviceShared>, // attached at runtime rather than through the config
+    config: SystemConfig,
+    frame_microseconds: u32,
+    emulated_microseconds: u64,
+    frames_presented: u64,
+    sync_start: Option<Instant>,
+    interrupt_timeline: timeline::InterruptTimeline,
+    triggers: triggers::TriggerEngine,
     debugger: debugger::Debugger<memory::Memory, bus::StandardBus>,
+    recent_trace: VecDeque<(u64, u16)>, // emulated time in microseconds and address of each instruction
+    project: project::ProjectShared,
+    coverage: coverage::Coverage,
+    rsx_tracer: rsx::RsxTracer,
 }
 
 impl CPC464 {
-    pub fn new() -> CPC464 {
+    pub fn new(config: SystemConfig) -> CPC464 {
         // TODO: receive shared screen here
-        let memory = memory::Memory::new_shared();
-        let crtc = crtc::CRTController::new_shared();
+        let memory = memory::Memory::new_shared(
+            config.ram_pattern,
+            config.seed(),
+            config.language,
+            config.firmware,
+            &config.roms,
+            config.ram_size,
+        );
+        let crtc = crtc::CRTController::new_shared(config.crtc_type);
         let keyboard = keyboard::Keyboard::new_shared();
-        let psg = psg::SoundGenerator::new_shared(keyboard.clone());
+        let psg = psg::SoundGenerator::new_shared(keyboard.clone(), config.seed());
         let screen = screen::Screen::new_shared();
         let tape = tape::TapeController::new_shared();
-        let bus = bus::StandardBus::new_shared(
-            crtc.clone(),
-            fdc::FloppyDiskController::new_shared(),
-            gate_array::GateArray::new_shared(memory.clone(), crtc.clone(), screen.clone()),
-            memory.clone(),
-            ppi::PeripheralInterface::new_shared(crtc, keyboard.clone(), psg, tape),
-        );
-        let cpu = cpu::CPU::new_shared(memory, bus.clone(), 0);
-        let debugger = debugger::Debugger::new_shared(cpu.clone());
+        let fdc = fdc::FloppyDiskController::new_shared();
+        let gate_array = gate_array::GateArray::new_shared(memory.clone(), crtc.clone(), screen.clone());
+        let ppi = ppi::PeripheralInterface::new_shared(crtc.clone(), keyboard.clone(), psg.clone(), tape.clone());
+        let bus = bus::StandardBus::new_shared(crtc.clone(), fdc.clone(), gate_array.clone(), memory.clone(), ppi.clone());
+        let cpu = cpu::CPU::new_shared(memory.clone(), bus.clone(), 0);
+        let project = project::Project::new_shared();
+        let debugger = debugger::Debugger::new_shared(cpu.clone(), project.clone());
+        let expansions = config.expansions.iter().map(|factory| factory(&config)).collect::<Vec<_>>();
 
-        CPC464 {
+        let mut cpc = CPC464 {
             cpu,
             bus,
+            memory,
+            crtc,
+            psg,
+            tape,
+            mixer: mixer::Mixer::new_shared(mixer::DEFAULT_SAMPLE_RATE),
+            fdc,
+            gate_array,
+            ppi,
             screen,
             keyboard,
+            rtc: None,
+            hot_plugged: Vec::new(),
+            config,
+            frame_microseconds: 0,
+            emulated_microseconds: 0,
+            frames_presented: 0,
+            sync_start: None,
+            interrupt_timeline: timeline::InterruptTimeline::new(),
+            triggers: triggers::TriggerEngine::new(),
             debugger,
+            recent_trace: VecDeque::with_capacity(RECENT_TRACE_LENGTH),
+            project,
+            coverage: coverage::Coverage::new(),
+            rsx_tracer: rsx::RsxTracer::new(),
+        };
+
+        for device in expansions {
+            cpc.connect_expansion(device);
+        }
+        cpc.randomize_power_on_state();
+
+        cpc
+    }
+
+    fn randomize_power_on_state(&mut self) {
+        let power_on = self.config.power_on;
+        let mut random = memory::random_bytes(40, self.config.seed() ^ POWER_ON_SEED_SALT).into_iter();
+        let mut next = || random.next().unwrap(); // enough for all devices
+
+        if power_on.crtc {
+            let mut crtc = self.crtc.borrow_mut();
+            for register in 0..16 {
+                crtc.write_byte(0xbc00, register);
+                crtc.write_byte(0xbd00, next());
+            }
+            crtc.write_byte(0xbc00, next() & 0x1f);
+        }
+
+        if power_on.gate_array {
+            let mut gate_array = self.gate_array.borrow_mut();
+            for pen in 0..=0x10 {
+                gate_array.write_byte(0x7f00, pen);
+                gate_array.write_byte(0x7f00, 0x40 | (next() & 0x1f));
+            }
+            gate_array.write_byte(0x7f00, next() & 0x1f);
+        }
+
+        if power_on.psg {
+            let mut psg = self.psg.borrow_mut();
+            psg.write_byte(next() & 0x0f);
+            psg.perform_function(3); // latch the register address
+            psg.write_byte(next());
         }
     }
+
+    fn connect_expansion(&mut self, device: expansion::ExpansionDeviceShared) {
+        if device.borrow().overlays_memory() {
+            self.memory.borrow_mut().attach_overlay(device.clone());
+        }
+        self.bus.borrow_mut().attach_expansion(device);
+    }
+
+    fn fast_load(&mut self) -> bool {
+        // instead of running the firmware loader, the record is copied from the tape directly
+        let mut cpu = self.cpu.borrow_mut();
+        let sync_byte = cpu.registers.read_byte(&cpu::Register8::A);
+        let payload = match self.tape.borrow_mut().fast_load_block(sync_byte) {
+            Some(payload) => payload,
+            None => return false, // let the firmware report a read error
+        };
+
+        let mut memory = self.memory.borrow_mut();
+        let address = cpu.registers.read_word(&cpu::Register16::HL);
+        let length = cpu.registers.read_word(&cpu::Register16::DE) as usize;
+        for (offset, value) in payload.iter().take(length).enumerate() {
+            memory.write_byte(address.wrapping_add(offset as u16) as usize, *value);
+        }
+
+        // return from CAS READ with carry set to signal success
+        let stack_pointer = cpu.registers.read_word(&cpu::Register16::SP);
+        let return_address = memory.read_byte(stack_pointer as usize) as u16
+            | (memory.read_byte(stack_pointer.wrapping_add(1) as usize) as u16) << 8;
+        cpu.registers.write_word(&cpu::Register16::SP, stack_pointer.wrapping_add(2));
+        cpu.registers.write_word(&cpu::Register16::PC, return_address);
+        cpu.registers.write_word(&cpu::Register16::AF, 0x0001);
+
+        true
+    }
+
+    #[cfg(feature = "instrumentation")]
+    fn record_entry_point(&mut self, program_counter: u16, stack_pointer: u16, interrupt_acknowledged: bool) {
+        // a call or restart pushes the address of the next instruction, which is 3 or 1 bytes further
+        let cpu = self.cpu.borrow();
+        let target = cpu.registers.read_word(&cpu::Register16::PC);
+        if interrupt_acknowledged {
+            self.coverage.record_entry_point(target);
+        } else if cpu.registers.read_word(&cpu::Register16::SP) == stack_pointer.wrapping_sub(2) {
+            let memory = self.memory.borrow();
+            let pushed = memory.read_word(stack_pointer.wrapping_sub(2) as usize);
+            let next_instruction = pushed.wrapping_sub(program_counter);
+            if (next_instruction == 1 || next_instruction == 3) && target != pushed {
+                self.coverage.record_entry_point(target);
+            }
+        }
+    }
+
+    fn record_timeline_event(&mut self, kind: timeline::TimelineEventKind) {
+        let position = self.crtc.borrow().read_raster_position();
+        self.interrupt_timeline.record(kind, position);
+    }
 }
 
 impl System for CPC464 {
     fn emulate(&mut self) -> u8 {
-        if self.debugger.is_active() {
-            self.debugger.run_command_shell();
+        #[cfg(feature = "instrumentation")]
+        if self.debugger.check() {
+            return 0; // stopped until the debug console resumes
+        }
+
+        #[cfg(feature = "instrumentation")]
+        let program_counter = self.cpu.borrow().registers.read_word(&cpu::Register16::PC);
+        #[cfg(feature = "instrumentation")]
+        let was_halted = self.cpu.borrow().is_halted();
+        #[cfg(feature = "instrumentation")]
+        let stack_pointer = self.cpu.borrow().registers.read_word(&cpu::Register16::SP);
+
+        #[cfg(feature = "instrumentation")]
+        {
+            let memory = self.memory.borrow();
+            self.rsx_tracer
+                .update(&self.cpu.borrow().registers, |address| memory.read_byte(address as usize));
+        }
+
+        if self.tape.borrow().is_fast_load_enabled()
+            && self.cpu.borrow().registers.read_word(&cpu::Register16::PC) == CAS_READ_ADDRESS
+            && self.memory.borrow().read_bank_configuration()[0] == memory::Bank::LowerRom
+        {
+            self.fast_load();
         }
 
         let (cycles, interrupt_acknowledged) = self.cpu.borrow_mut().fetch_and_execute();
 
+        #[cfg(feature = "instrumentation")]
+        {
+            self.bus.borrow().tag_io_trace(program_counter);
+
+            if self.recent_trace.len() == RECENT_TRACE_LENGTH {
+                self.recent_trace.pop_front();
+            }
+            self.recent_trace.push_back((self.emulated_microseconds, program_counter));
+
+            self.coverage.record_instruction(program_counter);
+            self.record_entry_point(program_counter, stack_pointer, interrupt_acknowledged);
+        }
+
+        #[cfg(feature = "instrumentation")]
+        if self.memory.borrow().has_watches() {
+            self.memory.borrow_mut().tag_watch_diffs(program_counter);
+
+            self.frame_microseconds += cycles as u32;
+            if self.frame_microseconds >= 20_000 {
+                self.frame_microseconds -= 20_000;
+                self.memory.borrow_mut().capture_frame_watches();
+            }
+        }
+
         for _ in 0..cycles {
             let interrupt = self.bus.borrow_mut().step();
             if interrupt {
                 self.cpu.borrow_mut().request_interrupt();
+
+                #[cfg(feature = "instrumentation")]
+                self.record_timeline_event(timeline::TimelineEventKind::InterruptRequested);
             }
         }
 
@@ -134,6 +621,62 @@ impl System for CPC464 {
             // TODO: communicate with gate array directly?
             // What about external hardware triggering (non-maskable) interrupts?
             self.bus.borrow_mut().acknowledge_interrupt();
+
+            #[cfg(feature = "instrumentation")]
+            self.record_timeline_event(timeline::TimelineEventKind::InterruptAcknowledged);
+        }
+        #[cfg(feature = "instrumentation")]
+        if self.cpu.borrow().is_interrupt_deferred() {
+            self.record_timeline_event(timeline::TimelineEventKind::InterruptDeferred);
+        }
+
+        #[cfg(feature = "instrumentation")]
+        {
+            let halted = self.cpu.borrow().is_halted();
+            if halted != was_halted {
+                let kind = if halted {
+                    timeline::TimelineEventKind::HaltStarted
+                } else {
+                    timeline::TimelineEventKind::HaltEnded
+                };
+                self.record_timeline_event(kind);
+            } else {
+                let (raster_line, _) = self.crtc.borrow().read_raster_position();
+                self.interrupt_timeline.update_raster_line(raster_line);
+            }
+        }
+
+        self.keyboard.borrow_mut().step(cycles as u64);
+        self.psg.borrow_mut().step(cycles);
+        self.tape.borrow_mut().step(cycles);
+
+        let due_samples = self.mixer.borrow_mut().advance(cycles);
+        if due_samples > 0 {
+            let levels = [
+                self.psg.borrow().read_output_level(),
+                if self.tape.borrow().read_sample() { 1.0 } else { 0.0 },
+                (self.bus.borrow().read_printer_data() ^ 0x80) as f32 / 255.0, // bit 7 is inverted by the strobe line
+                self.bus.borrow().read_expansion_audio_level(),
+            ];
+            self.mixer.borrow_mut().mix(due_samples, levels);
+        }
+        self.bus.borrow_mut().step_expansions(cycles);
+
+        // wall clock time is only used for statistics and never influences the emulation
+        if self.sync_start.is_none() {
+            self.sync_start = Some(Instant::now());
+        }
+        self.emulated_microseconds += cycles as u64;
+
+        let frame = self.emulated_microseconds / 20_000;
+        if !self.triggers.is_empty() && frame != (self.emulated_microseconds - cycles as u64) / 20_000 {
+            let memory = self.memory.borrow();
+            let cpu = self.cpu.borrow();
+            self.triggers.evaluate(
+                frame,
+                |address| memory.read_byte_from_ram(address as usize),
+                |register| cpu.registers.read_word(&register),
+            );
         }
 
         cycles
@@ -144,11 +687,544 @@ impl System for CPC464 {
         self.screen.clone()
     }
 
+    fn enable_layer_capture(&mut self, enable: bool) {
+        // cached lines would keep the sources of pixels from before
+        self.screen.borrow_mut().enable_layers(enable);
+        self.gate_array.borrow_mut().clear_line_cache();
+    }
+
     fn get_keyboard(&self) -> keyboard::KeyboardShared {
         self.keyboard.clone()
     }
 
+    fn set_key_matrix(&mut self, line: usize, bit: u8, pressed: bool) -> bool {
+        // for frontends without named keys, e.g. custom hardware. Takes effect immediately, unlike
+        // input::inject which queues the change. Returns false outside of the matrix
+        if line >= 10 || bit >= 8 {
+            return false;
+        }
+
+        let mut keyboard = self.keyboard.borrow_mut();
+        if pressed {
+            keyboard.set_key(line, bit);
+        } else {
+            keyboard.unset_key(line, bit);
+        }
+        true
+    }
+
+    fn read_key_matrix(&self) -> [u8; 10] {
+        // one byte per line, a cleared bit is a pressed key
+        self.keyboard.borrow().read_matrix()
+    }
+
     fn activate_debugger(&mut self) {
         self.debugger.activate();
     }
+
+    fn is_debugger_stopped(&self) -> bool {
+        self.debugger.is_stopped()
+    }
+
+    fn resume_debugger(&mut self, resume: debugger::Resume) {
+        self.debugger.resume(resume);
+    }
+
+    fn add_breakpoint(&mut self, address: u16, condition: Option<expression::Expression>) {
+        self.debugger.add_breakpoint(address, condition);
+    }
+
+    fn remove_breakpoint(&mut self, address: u16) -> bool {
+        self.debugger.remove_breakpoint(address)
+    }
+
+    fn list_breakpoints(&self) -> Vec<(u16, Option<String>)> {
+        self.debugger.list_breakpoints()
+    }
+
+    fn load_disk(&mut self, disk: dsk_file::Disk) {
+        self.fdc.borrow_mut().insert_disk(disk);
+    }
+
+    fn eject_disk(&mut self, drive: usize) -> Option<dsk_file::Disk> {
+        // only drive A is connected
+        match drive {
+            0 => self.fdc.borrow_mut().eject_disk(),
+            _ => None,
+        }
+    }
+
+    fn save_disk(&mut self) -> std::io::Result<()> {
+        // writes the disk back to the file it was loaded from
+        self.fdc.borrow_mut().save_disk()
+    }
+
+    fn is_disk_dirty(&self) -> bool {
+        self.fdc.borrow().is_disk_dirty()
+    }
+
+    fn set_write_protected(&mut self, protect: bool) {
+        self.fdc.borrow_mut().set_write_protected(protect);
+    }
+
+    fn is_write_protected(&self) -> bool {
+        self.fdc.borrow().is_write_protected()
+    }
+
+    fn load_tape(&mut self, tape: cdt_file::Tape) {
+        self.tape.borrow_mut().insert_tape(tape);
+    }
+
+    fn get_tape(&self) -> tape::TapeControllerShared {
+        self.tape.clone()
+    }
+
+    fn get_program_counter(&self) -> u16 {
+        self.cpu.borrow().registers.read_word(&cpu::Register16::PC)
+    }
+
+    fn read_register(&self, register: cpu::Register16) -> u16 {
+        self.cpu.borrow().registers.read_word(&register)
+    }
+
+    fn read_register8(&self, register: cpu::Register8) -> u8 {
+        self.cpu.borrow().registers.read_byte(&register)
+    }
+
+    fn read_raster_position(&self) -> (u16, u8) {
+        // the scan line within the frame and the character within the scan line, as the CRTC counts them
+        self.crtc.borrow().read_raster_position()
+    }
+
+    fn write_register(&mut self, register: cpu::Register16, value: u16) {
+        self.cpu.borrow_mut().registers.write_word(&register, value);
+    }
+
+    fn read_memory(&self, address: u16) -> u8 {
+        // as seen by the CPU, i.e. including enabled ROMs
+        self.memory.borrow().read_byte(address as usize)
+    }
+
+    fn disassemble(&self, address: u16, count: usize) -> Vec<(u16, String)> {
+        let mut cpu = self.cpu.borrow_mut();
+        let project = self.project.borrow();
+        let memory = self.memory.borrow();
+        let mut address = address as usize;
+        let mut lines = Vec::with_capacity(count);
+        for _ in 0..count {
+            if let Some((data, next_address)) = project.disassemble_data(address as u16, |address| memory.read_byte(address as usize)) {
+                lines.push((address as u16, data));
+                address = next_address as usize;
+                continue;
+            }
+            let (instruction, next_address) = cpu.decoder.decode_at(address);
+            lines.push((address as u16, instruction.to_string()));
+            address = next_address & 0xffff;
+        }
+
+        lines
+    }
+
+    fn read_screen_text(&self) -> String {
+        self.gate_array.borrow().read_screen_text()
+    }
+
+    fn read_basic_program(&self) -> String {
+        let memory = self.memory.borrow();
+        let program = basic::read_program_from_memory(|address| memory.read_byte_from_ram(address));
+
+        basic::detokenize(&program)
+    }
+
+    fn enable_real_time_clock(&mut self) {
+        let rtc = rtc::RealTimeClock::new_shared();
+        if self.config.deterministic {
+            rtc.borrow_mut().pin_time(Some(DETERMINISTIC_CLOCK_TIME));
+        }
+        self.attach_expansion(rtc.clone());
+        self.rtc = Some(rtc);
+    }
+
+    fn get_real_time_clock(&self) -> Option<rtc::RealTimeClockShared> {
+        self.rtc.clone()
+    }
+
+    fn get_debug_view(&self) -> SystemDebugView {
+        let memory = self.memory.borrow();
+
+        SystemDebugView {
+            memory_banks: memory.read_bank_configuration(),
+            banking_events: memory.read_banking_events(),
+            memory_diffs: memory.read_watch_diffs(),
+            interrupt_timeline: self.interrupt_timeline.read_last_frame(),
+            psg_registers: self.psg.borrow().read_registers(),
+            psg_channels: self.psg.borrow().read_channel_history(),
+        }
+    }
+
+    fn watch_memory(&mut self, start: usize, length: usize, mode: memory::WatchMode) -> usize {
+        self.memory.borrow_mut().add_watch(start, length, mode)
+    }
+
+    fn unwatch_memory(&mut self, id: usize) {
+        self.memory.borrow_mut().remove_watch(id);
+    }
+
+    fn notify_frame_presented(&mut self) {
+        self.frames_presented += 1;
+    }
+
+    fn get_sync_statistics(&self) -> SyncStatistics {
+        let wall_clock_microseconds = match self.sync_start {
+            Some(sync_start) => sync_start.elapsed().as_micros() as u64,
+            None => 0,
+        };
+        let frames_emulated = self.emulated_microseconds / 20_000;
+
+        SyncStatistics {
+            emulated_microseconds: self.emulated_microseconds,
+            wall_clock_microseconds,
+            drift_microseconds: self.emulated_microseconds as i64 - wall_clock_microseconds as i64,
+            audio_buffer_fill: self.mixer.borrow().read_output_fill(),
+            audio_rate_adjustment_ppm: self.mixer.borrow().get_rate_adjustment_ppm(),
+            frames_emulated,
+            frames_dropped: frames_emulated.saturating_sub(self.frames_presented),
+        }
+    }
+
+    fn reset_sync_statistics(&mut self) {
+        self.emulated_microseconds = 0;
+        self.frames_presented = 0;
+        self.sync_start = None;
+    }
+
+    fn get_drive_activity(&self) -> DriveActivity {
+        let fdc = self.fdc.borrow();
+        let tape = self.tape.borrow();
+
+        DriveActivity {
+            disk_inserted: fdc.has_disk(),
+            disk_motor: fdc.is_motor_on(),
+            disk_dirty: fdc.is_disk_dirty(),
+            tape_inserted: tape.has_tape(),
+            tape_playing: tape.is_playing(),
+        }
+    }
+
+    fn run_to_sync(&mut self, signal: bus::SyncSignal, count: u64) -> u64 {
+        // returns the emulated time in microseconds
+        let target = self.bus.borrow().count_sync_signals(signal) + count;
+        let mut elapsed_microseconds = 0;
+        while self.bus.borrow().count_sync_signals(signal) < target && !self.is_debugger_stopped() {
+            elapsed_microseconds += self.emulate() as u64;
+        }
+
+        elapsed_microseconds
+    }
+
+    fn step_cycles(&mut self, microseconds: u64) -> u64 {
+        // whole instructions are executed, so this may run up to one instruction longer than asked
+        let mut elapsed_microseconds = 0;
+        while elapsed_microseconds < microseconds && !self.is_debugger_stopped() {
+            elapsed_microseconds += self.emulate() as u64;
+        }
+
+        elapsed_microseconds
+    }
+
+    fn step_scanline(&mut self) -> u64 {
+        // runs until the CRTC starts the next scan line
+        let (raster_line, _) = self.crtc.borrow().read_raster_position();
+        let mut elapsed_microseconds = 0;
+        while self.crtc.borrow().read_raster_position().0 == raster_line && !self.is_debugger_stopped() {
+            elapsed_microseconds += self.emulate() as u64;
+        }
+
+        elapsed_microseconds
+    }
+
+    fn enable_io_trace(&mut self, enable: bool) {
+        self.bus.borrow_mut().enable_io_trace(enable);
+    }
+
+    fn take_io_trace(&mut self) -> Vec<bus::IoAccess> {
+        self.bus.borrow_mut().take_io_trace()
+    }
+
+    fn set_io_snooper(&mut self, snooper: Option<bus::IoSnooper>) -> Option<bus::IoSnooper> {
+        self.bus.borrow_mut().set_io_snooper(snooper)
+    }
+
+    fn enable_psg_capture(&mut self, enable: bool) {
+        self.psg.borrow_mut().enable_capture(enable);
+    }
+
+    fn take_psg_capture(&mut self) -> (u64, Vec<psg::RegisterWrite>) {
+        self.psg.borrow_mut().take_capture()
+    }
+
+    fn read_psg_register(&self, register: u8) -> u8 {
+        // as last written, register 14 does not scan the keyboard
+        self.psg.borrow().read_registers().get(register as usize).copied().unwrap_or(0xff)
+    }
+
+    fn write_psg_register(&mut self, register: u8, value: u8) {
+        // bypasses the PPI, captured writes are tagged as external
+        self.psg.borrow_mut().write_register(register, value);
+    }
+
+    fn read_ram(&self, address: usize) -> u8 {
+        self.memory.borrow().read_byte_from_ram(address)
+    }
+
+    fn write_ram(&mut self, address: usize, value: u8) {
+        self.memory.borrow_mut().write_byte_to_ram(address, value);
+    }
+
+    fn attach_expansion(&mut self, device: expansion::ExpansionDeviceShared) {
+        self.hot_plugged.push(device.clone());
+        self.connect_expansion(device);
+    }
+
+    fn detach_expansion(&mut self, name: &str) -> bool {
+        self.memory.borrow_mut().detach_overlay(name);
+        self.hot_plugged.retain(|device| device.borrow().name() != name);
+        let rtc_detached = match &self.rtc {
+            Some(rtc) => expansion::ExpansionDevice::name(&*rtc.borrow()) == name,
+            None => false,
+        };
+        if rtc_detached {
+            self.rtc = None;
+        }
+
+        self.bus.borrow_mut().detach_expansion(name).is_some()
+    }
+
+    fn list_expansions(&self) -> Vec<&'static str> {
+        self.bus.borrow().read_expansion_names()
+    }
+
+    fn get_config(&self) -> SystemConfig {
+        self.config.clone()
+    }
+
+    fn reload_roms(&mut self) -> std::io::Result<()> {
+        self.memory.borrow_mut().reload_roms()
+    }
+
+    fn list_rom_files(&self) -> Vec<String> {
+        self.memory.borrow().list_rom_files()
+    }
+
+    fn add_trigger(&mut self, conditions: Vec<triggers::Condition>, callback: triggers::TriggerCallback) -> usize {
+        self.triggers.add(conditions, callback)
+    }
+
+    fn remove_trigger(&mut self, id: usize) {
+        self.triggers.remove(id);
+    }
+
+    fn add_memory_observer(&mut self, observer: cpu::MemoryObserverShared) -> usize {
+        self.cpu.borrow_mut().add_memory_observer(observer)
+    }
+
+    fn add_io_observer(&mut self, observer: cpu::IoObserverShared) -> usize {
+        self.cpu.borrow_mut().add_io_observer(observer)
+    }
+
+    fn remove_observer(&mut self, id: usize) {
+        self.cpu.borrow_mut().remove_observer(id);
+    }
+
+    fn get_mixer(&self) -> mixer::MixerShared {
+        self.mixer.clone()
+    }
+
+    fn save_snapshot(&self) -> sna_file::Snapshot {
+        let cpu = self.cpu.borrow();
+        let registers = &cpu.registers;
+        let (iff1, iff2, interrupt_mode) = cpu.read_interrupt_state();
+        let (selected_pen, pen_colors) = self.gate_array.borrow().read_pens();
+        let (lower_rom_enabled, upper_rom_enabled, upper_rom) = self.memory.borrow().read_rom_configuration();
+        let (crtc_selected_register, crtc_registers) = self.crtc.borrow().read_registers();
+        let psg = self.psg.borrow();
+        let memory = self.memory.borrow();
+
+        let mut snapshot_pen_colors = [0; 17];
+        snapshot_pen_colors.copy_from_slice(&pen_colors[..17]);
+        let ir = (registers.read_byte(&cpu::Register8::I) as u16) << 8 | registers.read_byte(&cpu::Register8::R) as u16;
+
+        sna_file::Snapshot {
+            version: 2,
+            registers: [
+                registers.read_word(&cpu::Register16::AF),
+                registers.read_word(&cpu::Register16::BC),
+                registers.read_word(&cpu::Register16::DE),
+                registers.read_word(&cpu::Register16::HL),
+                registers.read_shadow_word(&cpu::Register16::AF),
+                registers.read_shadow_word(&cpu::Register16::BC),
+                registers.read_shadow_word(&cpu::Register16::DE),
+                registers.read_shadow_word(&cpu::Register16::HL),
+                ir,
+                registers.read_word(&cpu::Register16::IX),
+                registers.read_word(&cpu::Register16::IY),
+                registers.read_word(&cpu::Register16::SP),
+                registers.read_word(&cpu::Register16::PC),
+            ],
+            iff1,
+            iff2,
+            interrupt_mode,
+            selected_pen,
+            pen_colors: snapshot_pen_colors,
+            multi_configuration: 0x80
+                | (!upper_rom_enabled as u8) << 3
+                | (!lower_rom_enabled as u8) << 2
+                | self.gate_array.borrow().read_screen_mode(),
+            ram_configuration: memory.read_ram_configuration(),
+            crtc_selected_register,
+            crtc_registers,
+            upper_rom,
+            ppi_ports: self.ppi.borrow().read_ports(),
+            psg_selected_register: psg.read_selected_register(),
+            psg_registers: psg.read_registers(),
+            memory: (0..memory.read_ram_size()).map(|address| memory.read_byte_from_ram(address)).collect(),
+        }
+    }
+
+    fn load_snapshot(&mut self, snapshot: &sna_file::Snapshot) {
+        // the hardware is restored through the same port writes a program would use
+        let mut bus = self.bus.borrow_mut();
+        for (pen, color) in snapshot.pen_colors.iter().enumerate() {
+            bus.write_byte(0x7f00, pen as u8);
+            bus.write_byte(0x7f00, 0x40 | (color & 0x1f));
+        }
+        bus.write_byte(0x7f00, snapshot.selected_pen & 0x1f);
+        bus.write_byte(0x7f00, 0x80 | (snapshot.multi_configuration & 0x0f));
+        bus.write_byte(0xdf00, snapshot.upper_rom);
+
+        for (register, value) in snapshot.crtc_registers.iter().enumerate() {
+            bus.write_byte(0xbc00, register as u8);
+            bus.write_byte(0xbd00, *value);
+        }
+        bus.write_byte(0xbc00, snapshot.crtc_selected_register);
+
+        // PSG registers are written through PPI port A, then the PPI gets its own state back
+        bus.write_byte(0xf700, 0x82);
+        for (register, value) in snapshot.psg_registers.iter().enumerate() {
+            write_psg(&mut bus, 0xc0, register as u8);
+            write_psg(&mut bus, 0x80, *value);
+        }
+        write_psg(&mut bus, 0xc0, snapshot.psg_selected_register);
+        let [port_a, _, port_c, control] = snapshot.ppi_ports;
+        bus.write_byte(0xf700, control);
+        bus.write_byte(0xf400, port_a);
+        bus.write_byte(0xf600, port_c);
+        drop(bus);
+
+        // expansion RAM the snapshot has but the machine has not is left out
+        let mut memory = self.memory.borrow_mut();
+        for (address, value) in snapshot.memory.iter().take(memory.read_ram_size()).enumerate() {
+            memory.write_byte_to_ram(address, *value);
+        }
+        memory.select_ram_configuration(snapshot.ram_configuration);
+        drop(memory);
+
+        let mut cpu = self.cpu.borrow_mut();
+        let [af, bc, de, hl, shadow_af, shadow_bc, shadow_de, shadow_hl, ir, ix, iy, sp, pc] = snapshot.registers;
+        for (register, value, shadow_value) in [
+            (cpu::Register16::AF, af, shadow_af),
+            (cpu::Register16::BC, bc, shadow_bc),
+            (cpu::Register16::DE, de, shadow_de),
+            (cpu::Register16::HL, hl, shadow_hl),
+        ] {
+            cpu.registers.write_word(&register, value);
+            cpu.registers.write_shadow_word(&register, shadow_value);
+        }
+        cpu.registers.write_byte(&cpu::Register8::I, (ir >> 8) as u8);
+        cpu.registers.write_byte(&cpu::Register8::R, ir as u8);
+        cpu.registers.write_word(&cpu::Register16::IX, ix);
+        cpu.registers.write_word(&cpu::Register16::IY, iy);
+        cpu.registers.write_word(&cpu::Register16::SP, sp);
+        cpu.registers.write_word(&cpu::Register16::PC, pc);
+        cpu.write_interrupt_state(snapshot.iff1, snapshot.iff2, snapshot.interrupt_mode);
+    }
+
+    fn save_machine_state(&self) -> MachineState {
+        MachineState {
+            snapshot: self.save_snapshot(),
+            cpu: self.cpu.borrow().save_execution_state(),
+            crtc: self.crtc.borrow().clone(),
+            gate_array: self.gate_array.borrow().save_counters(),
+            fdc: self.fdc.borrow().save_state(),
+            psg: self.psg.borrow().save_generators(),
+            tape_position: self.tape.borrow().read_position(),
+            bus: self.bus.borrow().save_sync_state(),
+            gun_position: self.screen.borrow().read_gun_position(),
+            frame_microseconds: self.frame_microseconds,
+            emulated_microseconds: self.emulated_microseconds,
+        }
+    }
+
+    fn load_machine_state(&mut self, state: &MachineState) {
+        // the snapshot goes first, the port writes that restore it disturb the counters
+        self.load_snapshot(&state.snapshot);
+        self.cpu.borrow_mut().load_execution_state(&state.cpu);
+        *self.crtc.borrow_mut() = state.crtc.clone();
+        self.gate_array.borrow_mut().load_counters(&state.gate_array);
+        self.fdc.borrow_mut().load_state(&state.fdc);
+        self.psg.borrow_mut().load_generators(&state.psg);
+        self.tape.borrow_mut().seek(state.tape_position);
+        self.bus.borrow_mut().load_sync_state(&state.bus);
+        self.screen.borrow_mut().write_gun_position(state.gun_position);
+        self.frame_microseconds = state.frame_microseconds;
+        self.emulated_microseconds = state.emulated_microseconds;
+    }
+
+    fn read_recent_trace(&self) -> Vec<(u64, u16)> {
+        // oldest first, empty without the instrumentation feature
+        self.recent_trace.iter().copied().collect()
+    }
+
+    fn get_project(&self) -> project::ProjectShared {
+        self.project.clone()
+    }
+
+    fn read_coverage(&self) -> coverage::Coverage {
+        // empty without the instrumentation feature
+        self.coverage.clone()
+    }
+
+    fn reconfigure(&mut self, config: SystemConfig) {
+        // a controlled reset: the machine is rebuilt from the new config, while the inserted media
+        // and devices plugged in at runtime stay connected
+        let disk = self.fdc.borrow_mut().eject_disk();
+        let tape = self.tape.borrow_mut().eject_tape();
+        let fast_load = self.tape.borrow().is_fast_load_enabled();
+        let hot_plugged = std::mem::take(&mut self.hot_plugged);
+        let rtc = self.rtc.take();
+        let project = self.project.take();
+
+        *self = CPC464::new(config);
+
+        self.project.replace(project);
+
+        if let Some(disk) = disk {
+            self.load_disk(disk);
+        }
+        if let Some(tape) = tape {
+            self.load_tape(tape);
+        }
+        self.tape.borrow_mut().set_fast_load(fast_load);
+        for device in hot_plugged {
+            self.attach_expansion(device);
+        }
+        self.rtc = rtc;
+    }
+}
+
+fn write_psg(bus: &mut bus::StandardBus, function: u8, value: u8) {
+    // puts the value on PPI port A and pulses the PSG function (0xc0 selects a register, 0x80 writes it)
+    bus.write_byte(0xf400, value);
+    bus.write_byte(0xf600, function);
+    bus.write_byte(0xf600, 0x00);
 }
@@ -1,26 +1,142 @@
 use std::cell::RefCell;
 use std::rc::Rc;
 
+use crate::cdt_file;
+
 pub type TapeControllerShared = Rc<RefCell<TapeController>>;
 
-pub struct TapeController {}
+pub struct TapeController {
+    tape: Option<cdt_file::Tape>,
+    motor_on: bool,
+    pulse_index: usize,
+    pulse_elapsed: u64, // in T-states times 2, as TZX timings run at 3.5MHz
+    fast_load: bool,
+}
 
 impl TapeController {
     pub fn new_shared() -> TapeControllerShared {
-        let tape = TapeController {};
+        let tape = TapeController {
+            tape: None,
+            motor_on: false,
+            pulse_index: 0,
+            pulse_elapsed: 0,
+            fast_load: false,
+        };
 
         Rc::new(RefCell::new(tape))
     }
 
+    pub fn insert_tape(&mut self, tape: cdt_file::Tape) {
+        self.tape = Some(tape);
+        self.rewind();
+    }
+
+    pub fn eject_tape(&mut self) -> Option<cdt_file::Tape> {
+        self.rewind();
+        self.tape.take()
+    }
+
+    pub fn rewind(&mut self) {
+        self.pulse_index = 0;
+        self.pulse_elapsed = 0;
+    }
+
+    pub fn read_position(&self) -> (usize, u64) {
+        // the current pulse and how far into it the tape has played
+        (self.pulse_index, self.pulse_elapsed)
+    }
+
+    pub fn seek(&mut self, position: (usize, u64)) {
+        let (pulse_index, pulse_elapsed) = position;
+        self.pulse_index = pulse_index;
+        self.pulse_elapsed = pulse_elapsed;
+    }
+
+    pub fn has_tape(&self) -> bool {
+        self.tape.is_some()
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.motor_on && self.tape.is_some() && !self.is_at_end()
+    }
+
+    pub fn read_progress(&self) -> Option<f32> {
+        // how much of the tape has been played, between 0 and 1
+        let pulses = self.tape.as_ref()?.read_pulses().len();
+        if pulses == 0 {
+            return None;
+        }
+        Some(self.pulse_index.min(pulses) as f32 / pulses as f32)
+    }
+
+    pub fn set_fast_load(&mut self, enable: bool) {
+        self.fast_load = enable;
+    }
+
+    pub fn is_fast_load_enabled(&self) -> bool {
+        self.fast_load
+    }
+
     pub fn switch_motor(&mut self, on: bool) {
-        // TODO: implement this
+        self.motor_on = on;
+    }
+
+    pub fn step(&mut self, microseconds: u8) {
+        if !self.motor_on {
+            return;
+        }
+
+        let pulses = match &self.tape {
+            Some(tape) => tape.read_pulses(),
+            None => return,
+        };
+
+        self.pulse_elapsed += microseconds as u64 * 7; // 3.5 T-states per microsecond
+        while let Some(pulse) = pulses.get(self.pulse_index) {
+            if self.pulse_elapsed < pulse.duration as u64 * 2 {
+                break;
+            }
+            self.pulse_elapsed -= pulse.duration as u64 * 2;
+            self.pulse_index += 1;
+        }
     }
 
     pub fn read_sample(&self) -> bool {
-        false // TODO: implement this
+        match &self.tape {
+            Some(tape) if self.motor_on => matches!(tape.read_pulses().get(self.pulse_index), Some(pulse) if pulse.level),
+            _ => false,
+        }
     }
 
     pub fn write_sample(&mut self, high_amplitude: bool) {
         // TODO: implement this
     }
+
+    pub fn fast_load_block(&mut self, sync_byte: u8) -> Option<Vec<u8>> {
+        // returns the payload of the next data block starting with the sync byte and winds the tape past it
+        let tape = self.tape.as_ref()?;
+        let block = tape
+            .read_data_blocks()
+            .iter()
+            .find(|block| block.end_pulse > self.pulse_index && block.data.first() == Some(&sync_byte))?;
+
+        self.pulse_index = block.end_pulse;
+        self.pulse_elapsed = 0;
+
+        // CPC records are split into 256 byte segments, each followed by a 2 byte CRC
+        let payload = block.data[1..]
+            .chunks(258)
+            .flat_map(|segment| segment.iter().take(256))
+            .copied()
+            .collect();
+
+        Some(payload)
+    }
+
+    fn is_at_end(&self) -> bool {
+        match &self.tape {
+            Some(tape) => self.pulse_index >= tape.read_pulses().len(),
+            None => true,
+        }
+    }
 }
@@ -0,0 +1,58 @@
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TimelineEventKind {
+    InterruptRequested,
+    InterruptAcknowledged,
+    InterruptDeferred, // by an EI or a prefix right before
+    HaltStarted,
+    HaltEnded,
+}
+
+#[derive(Clone, Debug)]
+pub struct TimelineEvent {
+    pub kind: TimelineEventKind,
+    pub raster_line: u16,
+    pub character: u8, // horizontal position within the raster line
+}
+
+pub struct InterruptTimeline {
+    current_frame: Vec<TimelineEvent>,
+    last_frame: Vec<TimelineEvent>,
+    last_raster_line: u16,
+}
+
+impl Default for InterruptTimeline {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InterruptTimeline {
+    pub fn new() -> InterruptTimeline {
+        InterruptTimeline {
+            current_frame: Vec::new(),
+            last_frame: Vec::new(),
+            last_raster_line: 0,
+        }
+    }
+
+    pub fn update_raster_line(&mut self, raster_line: u16) {
+        // the raster position wraps around at the start of each frame
+        if raster_line < self.last_raster_line {
+            self.last_frame = std::mem::take(&mut self.current_frame);
+        }
+        self.last_raster_line = raster_line;
+    }
+
+    pub fn record(&mut self, kind: TimelineEventKind, (raster_line, character): (u16, u8)) {
+        self.update_raster_line(raster_line);
+        self.current_frame.push(TimelineEvent {
+            kind,
+            raster_line,
+            character,
+        });
+    }
+
+    pub fn read_last_frame(&self) -> Vec<TimelineEvent> {
+        self.last_frame.clone()
+    }
+}
@@ -0,0 +1,144 @@
+use std::collections::HashMap;
+
+use crate::cpu;
+
+#[derive(Clone, Copy, Debug)]
+pub enum Operand {
+    Byte(u16),
+    Word(u16), // little endian, as stored by the Z80
+    PreviousByte(u16), // value at the end of the previous frame
+    Register(cpu::Register16),
+    Constant(u16),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Condition {
+    pub left: Operand,
+    pub comparison: Comparison,
+    pub right: Operand,
+}
+
+impl Condition {
+    pub fn new(left: Operand, comparison: Comparison, right: Operand) -> Condition {
+        Condition {
+            left,
+            comparison,
+            right,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct TriggerEvent {
+    pub trigger_id: usize,
+    pub frame: u64,
+}
+
+pub type TriggerCallback = Box<dyn FnMut(TriggerEvent)>;
+
+struct Trigger {
+    id: usize,
+    conditions: Vec<Condition>, // all of them must hold
+    callback: TriggerCallback,
+    satisfied: bool,
+}
+
+// conditions over memory and registers, checked once per frame, e.g. for achievements or to detect game states in tests
+#[derive(Default)]
+pub struct TriggerEngine {
+    triggers: Vec<Trigger>,
+    next_id: usize,
+    previous_bytes: HashMap<u16, u8>,
+}
+
+impl TriggerEngine {
+    pub fn new() -> TriggerEngine {
+        TriggerEngine::default()
+    }
+
+    pub fn add(&mut self, conditions: Vec<Condition>, callback: TriggerCallback) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.triggers.push(Trigger {
+            id,
+            conditions,
+            callback,
+            satisfied: false,
+        });
+
+        id
+    }
+
+    pub fn remove(&mut self, id: usize) {
+        self.triggers.retain(|trigger| trigger.id != id);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.triggers.is_empty()
+    }
+
+    pub fn evaluate(
+        &mut self,
+        frame: u64,
+        read_memory: impl Fn(u16) -> u8,
+        read_register: impl Fn(cpu::Register16) -> u16,
+    ) {
+        let previous_bytes = &self.previous_bytes;
+        let value_of = |operand: Operand| match operand {
+            Operand::Byte(address) => read_memory(address) as u16,
+            Operand::Word(address) => read_memory(address) as u16 | (read_memory(address.wrapping_add(1)) as u16) << 8,
+            Operand::PreviousByte(address) => match previous_bytes.get(&address) {
+                Some(value) => *value as u16,
+                None => read_memory(address) as u16, // nothing changed before the first frame
+            },
+            Operand::Register(register) => read_register(register),
+            Operand::Constant(value) => value,
+        };
+
+        for trigger in &mut self.triggers {
+            let satisfied = trigger.conditions.iter().all(|condition| {
+                let left = value_of(condition.left);
+                let right = value_of(condition.right);
+                match condition.comparison {
+                    Comparison::Equal => left == right,
+                    Comparison::NotEqual => left != right,
+                    Comparison::Less => left < right,
+                    Comparison::LessOrEqual => left <= right,
+                    Comparison::Greater => left > right,
+                    Comparison::GreaterOrEqual => left >= right,
+                }
+            });
+
+            // callbacks only fire when a trigger becomes satisfied, not on every frame it stays that way
+            if satisfied && !trigger.satisfied {
+                (trigger.callback)(TriggerEvent {
+                    trigger_id: trigger.id,
+                    frame,
+                });
+            }
+            trigger.satisfied = satisfied;
+        }
+
+        let mut previous_bytes = HashMap::new();
+        for trigger in &self.triggers {
+            for condition in &trigger.conditions {
+                for operand in [condition.left, condition.right].iter() {
+                    if let Operand::PreviousByte(address) = operand {
+                        previous_bytes.insert(*address, read_memory(*address));
+                    }
+                }
+            }
+        }
+        self.previous_bytes = previous_bytes;
+    }
+}
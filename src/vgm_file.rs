@@ -0,0 +1,52 @@
+use crate::psg;
+
+const SAMPLE_RATE: u64 = 44_100; // VGM timings are always given in samples at this rate
+const PSG_CLOCK: u32 = 1_000_000;
+const VERSION: u32 = 0x0000_0171;
+const HEADER_SIZE: usize = 0x100;
+
+// PSG register writes in the format of the VGM tools, to be played back or converted
+// without emulating the rest of the machine
+pub fn write_vgm(filename: &str, duration_microseconds: u64, writes: &[psg::RegisterWrite]) -> std::io::Result<()> {
+    let mut commands = Vec::new();
+    let mut samples_written = 0;
+    for write in writes {
+        wait(&mut commands, &mut samples_written, to_samples(write.timestamp));
+        commands.extend_from_slice(&[0xa0, write.register, write.value]);
+    }
+    wait(&mut commands, &mut samples_written, to_samples(duration_microseconds));
+    commands.push(0x66); // end of sound data
+
+    let mut header = vec![0; HEADER_SIZE];
+    header[0x00..0x04].copy_from_slice(b"Vgm ");
+    header[0x04..0x08].copy_from_slice(&((HEADER_SIZE + commands.len() - 0x04) as u32).to_le_bytes());
+    header[0x08..0x0c].copy_from_slice(&VERSION.to_le_bytes());
+    header[0x18..0x1c].copy_from_slice(&(samples_written as u32).to_le_bytes());
+    header[0x24..0x28].copy_from_slice(&50u32.to_le_bytes()); // frame rate
+    header[0x34..0x38].copy_from_slice(&((HEADER_SIZE - 0x34) as u32).to_le_bytes());
+    header[0x74..0x78].copy_from_slice(&PSG_CLOCK.to_le_bytes());
+    header[0x78] = 0x01; // AY-3-8912
+    header[0x79] = 0x01; // legacy output
+
+    header.extend_from_slice(&commands);
+    std::fs::write(filename, header)
+}
+
+fn to_samples(microseconds: u64) -> u64 {
+    microseconds * SAMPLE_RATE / 1_000_000
+}
+
+fn wait(commands: &mut Vec<u8>, samples_written: &mut u64, until: u64) {
+    while *samples_written < until {
+        let samples = (until - *samples_written).min(0xffff);
+        match samples {
+            882 => commands.push(0x63), // one frame at 50Hz
+            1..=16 => commands.push(0x70 + samples as u8 - 1),
+            _ => {
+                commands.push(0x61);
+                commands.extend_from_slice(&(samples as u16).to_le_bytes());
+            }
+        }
+        *samples_written += samples;
+    }
+}
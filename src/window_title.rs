@@ -0,0 +1,70 @@
+use crate::system;
+
+const APPLICATION_TITLE: &str = "Ronald - Amstrad CPC Emulator";
+
+// the window title shows what runs and how: the name of the media, the speed or whether the
+// emulation is paused, and how much of a tape has been read while it loads. minifb has no taskbar
+// progress, so the title is the only place outside the window that shows it
+pub struct WindowTitle {
+    media_name: Option<String>,
+    title: String,
+}
+
+impl WindowTitle {
+    pub fn new() -> WindowTitle {
+        WindowTitle {
+            media_name: None,
+            title: APPLICATION_TITLE.to_string(),
+        }
+    }
+
+    pub fn set_media_name(&mut self, name: Option<String>) {
+        self.media_name = name;
+    }
+
+    pub fn update(&mut self, system: &dyn system::System, speed: f64, paused: bool, turbo: bool) -> Option<&str> {
+        // returns the title if it changed, setting it every frame makes some window managers flicker
+        let mut states = Vec::new();
+        if paused {
+            states.push("paused".to_string());
+        } else if speed > 0.0 {
+            states.push(format!("{:.0}%", speed));
+        }
+        if turbo {
+            states.push("turbo".to_string());
+        }
+        let tape = system.get_tape();
+        let tape = tape.borrow();
+        if let (true, Some(progress)) = (tape.is_playing(), tape.read_progress()) {
+            states.push(format!("tape {:.0}%", progress * 100.0));
+        }
+
+        let mut title = match &self.media_name {
+            Some(name) => format!("{} - Ronald", name),
+            None => APPLICATION_TITLE.to_string(),
+        };
+        if !states.is_empty() {
+            title += &format!(" ({})", states.join(", "));
+        }
+
+        if title == self.title {
+            return None;
+        }
+        self.title = title;
+        Some(&self.title)
+    }
+}
+
+impl Default for WindowTitle {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub fn media_name(filename: &str) -> String {
+    // the file name without directory and extension, for media the game database does not know
+    std::path::Path::new(filename)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_else(|| filename.to_string())
+}
@@ -0,0 +1,399 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::debug_console;
+use crate::logger;
+use crate::memory::{self, Read};
+use crate::palette;
+use crate::system;
+
+pub const PANEL_COLUMNS: usize = 40;
+pub const PANEL_ROWS: usize = 24;
+pub const PANEL_WIDTH: usize = PANEL_COLUMNS * 8;
+pub const PANEL_HEIGHT: usize = PANEL_ROWS * 8;
+const PANEL_SCALE: usize = 2;
+const FONT_ADDRESS: usize = 0x3800; // character matrix table in the lower ROM
+const MAX_MONITOR_OUTPUT: usize = 1000; // lines kept for scrolling back
+const ORIGIN: (usize, usize) = (32, 32); // keeps the windows clear of desktop menu bars
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PanelKind {
+    Registers,
+    Memory,
+    Disassembly,
+    Log,
+    Monitor,
+}
+
+impl PanelKind {
+    fn from_name(name: &str) -> Option<PanelKind> {
+        match name {
+            "registers" => Some(PanelKind::Registers),
+            "memory" => Some(PanelKind::Memory),
+            "disassembly" => Some(PanelKind::Disassembly),
+            "log" => Some(PanelKind::Log),
+            "monitor" => Some(PanelKind::Monitor),
+            _ => None,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            PanelKind::Registers => "registers",
+            PanelKind::Memory => "memory",
+            PanelKind::Disassembly => "disassembly",
+            PanelKind::Log => "log",
+            PanelKind::Monitor => "monitor",
+        }
+    }
+
+    fn title(&self) -> &'static str {
+        match self {
+            PanelKind::Registers => "Registers",
+            PanelKind::Memory => "Memory",
+            PanelKind::Disassembly => "Disassembly",
+            PanelKind::Log => "Log",
+            PanelKind::Monitor => "Monitor",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Dock {
+    Left,
+    Right,
+    Bottom,
+}
+
+impl Dock {
+    fn from_name(name: &str) -> Option<Dock> {
+        match name {
+            "left" => Some(Dock::Left),
+            "right" => Some(Dock::Right),
+            "bottom" => Some(Dock::Bottom),
+            _ => None,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Dock::Left => "left",
+            Dock::Right => "right",
+            Dock::Bottom => "bottom",
+        }
+    }
+}
+
+// open panels and where they are docked, in stacking order:
+//
+// registers = right
+// disassembly = right
+// memory = bottom
+#[derive(Clone, Debug, Default)]
+pub struct Layout {
+    pub panels: Vec<(PanelKind, Dock)>,
+}
+
+impl Layout {
+    pub fn load(filename: &str) -> std::io::Result<Layout> {
+        let contents = std::fs::read_to_string(filename)?;
+        let mut layout = Layout::default();
+
+        for line in contents.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (key, value) = match line.split_once('=') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => return Err(invalid_data(format!("Missing \"=\" in layout line \"{}\".", line))),
+            };
+            let kind = PanelKind::from_name(key).ok_or_else(|| invalid_data(format!("Unknown panel \"{}\".", key)))?;
+            let dock = Dock::from_name(value).ok_or_else(|| invalid_data(format!("Unknown dock position \"{}\".", value)))?;
+            layout.panels.retain(|(open_kind, _)| *open_kind != kind);
+            layout.panels.push((kind, dock));
+        }
+
+        Ok(layout)
+    }
+
+    pub fn save(&self, filename: &str) -> std::io::Result<()> {
+        let mut contents = String::from("# ronald workbench layout\n");
+        for (kind, dock) in &self.panels {
+            contents.push_str(&format!("{} = {}\n", kind.name(), dock.name()));
+        }
+
+        std::fs::write(filename, contents)
+    }
+}
+
+struct Panel {
+    kind: PanelKind,
+    dock: Dock,
+    window: minifb::Window,
+    buffer: Vec<u32>,
+    address: u16, // first address shown in the memory panel
+    scroll: usize, // log messages hidden below the log panel
+    query: Rc<RefCell<String>>, // typed into the log panel to search it, or the monitor command
+    output: Vec<String>, // of the monitor commands, the newest at the end
+}
+
+// debug panels in windows of their own, docked around the screen window
+pub struct Workbench {
+    panels: Vec<Panel>,
+    font: memory::ROM,
+    log_history: logger::LogHistoryShared,
+    debug_console: debug_console::DebugConsoleShared,
+    stopped: bool, // whether the debugger was stopped in the last update
+}
+
+impl Workbench {
+    pub fn new(
+        layout: &Layout,
+        log_history: logger::LogHistoryShared,
+        debug_console: debug_console::DebugConsoleShared,
+    ) -> Workbench {
+        let mut workbench = Workbench {
+            panels: Vec::new(),
+            font: memory::ROM::font(),
+            log_history,
+            debug_console,
+            stopped: false,
+        };
+        for (kind, dock) in &layout.panels {
+            workbench.open_panel(*kind, *dock);
+        }
+
+        workbench
+    }
+
+    pub fn layout(&self) -> Layout {
+        Layout {
+            panels: self.panels.iter().map(|panel| (panel.kind, panel.dock)).collect(),
+        }
+    }
+
+    pub fn is_open(&self, kind: PanelKind) -> bool {
+        self.panels.iter().any(|panel| panel.kind == kind)
+    }
+
+    pub fn toggle_panel(&mut self, kind: PanelKind) {
+        if self.panels.iter().any(|panel| panel.kind == kind) {
+            self.panels.retain(|panel| panel.kind != kind);
+        } else {
+            self.open_panel(kind, Dock::Right);
+        }
+    }
+
+    pub fn arrange(&mut self, screen_window: &mut minifb::Window) {
+        // the screen window sits right of any left docked panels, the others are stacked along its edges
+        let (screen_width, screen_height) = screen_window.get_size();
+        let panel_width = PANEL_WIDTH * PANEL_SCALE;
+        let panel_height = PANEL_HEIGHT * PANEL_SCALE;
+        let docked_left = self.panels.iter().any(|panel| panel.dock == Dock::Left);
+        let screen_x = ORIGIN.0 + if docked_left { panel_width } else { 0 };
+        let screen_y = ORIGIN.1;
+        screen_window.set_position(screen_x as isize, screen_y as isize);
+
+        let mut offsets = [0; 3];
+        for panel in &mut self.panels {
+            let offset = &mut offsets[panel.dock as usize];
+            let (x, y) = match panel.dock {
+                Dock::Left => (ORIGIN.0, screen_y + *offset * panel_height),
+                Dock::Right => (screen_x + screen_width, screen_y + *offset * panel_height),
+                Dock::Bottom => (screen_x + *offset * panel_width, screen_y + screen_height),
+            };
+            panel.window.set_position(x as isize, y as isize);
+            *offset += 1;
+        }
+    }
+
+    pub fn update(&mut self, system: &mut dyn system::System) -> bool {
+        // returns whether panels were closed or docked elsewhere, so the caller can rearrange them
+        let mut changed = false;
+        self.panels.retain(|panel| panel.window.is_open());
+
+        let stopped = system.is_debugger_stopped();
+        if stopped && !self.stopped {
+            let lines = self.debug_console.borrow_mut().stop_lines(&*system);
+            for panel in self.panels.iter_mut().filter(|panel| panel.kind == PanelKind::Monitor) {
+                panel.output.extend(lines.iter().cloned());
+            }
+        }
+        self.stopped = stopped;
+
+        for panel in &mut self.panels {
+            let keys = panel.window.get_keys_pressed(minifb::KeyRepeat::Yes).unwrap_or_default();
+            for key in keys {
+                if panel.kind == PanelKind::Monitor && update_monitor(panel, key, &self.debug_console, system) {
+                    continue;
+                }
+                let dock = match key {
+                    minifb::Key::Left => Some(Dock::Left),
+                    minifb::Key::Right => Some(Dock::Right),
+                    minifb::Key::Down => Some(Dock::Bottom),
+                    minifb::Key::PageUp => {
+                        panel.address = panel.address.wrapping_sub(8 * PANEL_ROWS as u16);
+                        panel.scroll += PANEL_ROWS - 2;
+                        None
+                    }
+                    minifb::Key::PageDown => {
+                        panel.address = panel.address.wrapping_add(8 * PANEL_ROWS as u16);
+                        panel.scroll = panel.scroll.saturating_sub(PANEL_ROWS - 2);
+                        None
+                    }
+                    minifb::Key::Backspace => {
+                        panel.query.borrow_mut().pop();
+                        None
+                    }
+                    minifb::Key::Home => {
+                        panel.address = system.get_program_counter() & 0xfff8;
+                        None
+                    }
+                    _ => None,
+                };
+                if let Some(dock) = dock {
+                    changed |= panel.dock != dock;
+                    panel.dock = dock;
+                }
+            }
+
+            let lines = match panel.kind {
+                PanelKind::Registers => debug_console::register_lines(system),
+                PanelKind::Memory => debug_console::memory_lines(system, panel.address, PANEL_ROWS),
+                PanelKind::Disassembly => debug_console::disassembly_lines(system, system.get_program_counter(), PANEL_ROWS),
+                PanelKind::Log => {
+                    let records = self.log_history.lock().unwrap().search(&panel.query.borrow());
+                    panel.scroll = panel.scroll.min(records.len().saturating_sub(PANEL_ROWS - 2));
+                    log_lines(&records, &panel.query.borrow(), panel.scroll)
+                }
+                PanelKind::Monitor => {
+                    panel.scroll = panel.scroll.min(panel.output.len().saturating_sub(PANEL_ROWS - 1));
+                    monitor_lines(&panel.output, &panel.query.borrow(), panel.scroll, self.stopped)
+                }
+            };
+            draw_text(&self.font, &mut panel.buffer, PANEL_WIDTH, 1, &lines);
+            if let Err(error) = panel.window.update_with_buffer(&panel.buffer, PANEL_WIDTH, PANEL_HEIGHT) {
+                log::error!("Could not update the {} panel: {}", panel.kind.name(), error);
+            }
+        }
+
+        changed
+    }
+
+    fn open_panel(&mut self, kind: PanelKind, dock: Dock) {
+        let options = minifb::WindowOptions {
+            scale: minifb::Scale::X2,
+            ..minifb::WindowOptions::default()
+        };
+        let mut window = match minifb::Window::new(kind.title(), PANEL_WIDTH, PANEL_HEIGHT, options) {
+            Ok(window) => window,
+            Err(error) => {
+                log::error!("Could not open the {} panel: {}", kind.name(), error);
+                return;
+            }
+        };
+
+        let query = Rc::new(RefCell::new(String::new()));
+        if kind == PanelKind::Log || kind == PanelKind::Monitor {
+            window.set_input_callback(Box::new(palette::TypedText { text: query.clone() }));
+        }
+
+        self.panels.push(Panel {
+            kind,
+            dock,
+            window,
+            buffer: vec![0; PANEL_WIDTH * PANEL_HEIGHT],
+            address: 0,
+            scroll: 0,
+            query,
+            output: Vec::new(),
+        });
+    }
+}
+
+fn log_lines(records: &[logger::LogRecord], query: &str, scroll: usize) -> Vec<String> {
+    // the newest matching messages at the bottom, above them the search query
+    let mut lines = vec![format!("/{}_", query), String::new()];
+    let end = records.len() - scroll.min(records.len());
+    let start = end.saturating_sub(PANEL_ROWS - 2);
+    for record in &records[start..end] {
+        let level = record.level.as_str().chars().next().unwrap_or(' ');
+        let module = record.target.strip_prefix("ronald::").unwrap_or(&record.target);
+        let repeated = if record.repeated > 0 { format!(" (x{})", record.repeated + 1) } else { String::new() };
+        lines.push(format!("{} {}: {}{}", level, module, record.message, repeated));
+    }
+
+    lines
+}
+
+fn update_monitor(
+    panel: &mut Panel,
+    key: minifb::Key,
+    debug_console: &debug_console::DebugConsoleShared,
+    system: &mut dyn system::System,
+) -> bool {
+    // Enter runs the typed command, Up and Down browse the history. Returns whether the key was used
+    let mut debug_console = debug_console.borrow_mut();
+    match key {
+        minifb::Key::Enter => {
+            let command = panel.query.replace(String::new());
+            panel.output.push(format!("> {}", command));
+            panel.output.extend(debug_console.execute(system, &command));
+            if panel.output.len() > MAX_MONITOR_OUTPUT {
+                panel.output.drain(..panel.output.len() - MAX_MONITOR_OUTPUT);
+            }
+            panel.scroll = 0;
+        }
+        minifb::Key::Up => {
+            let command = debug_console.previous_command().unwrap_or_default().to_string();
+            panel.query.replace(command);
+        }
+        minifb::Key::Down => {
+            let command = debug_console.next_command().unwrap_or_default().to_string();
+            panel.query.replace(command);
+        }
+        _ => return false,
+    }
+
+    true
+}
+
+fn monitor_lines(output: &[String], command: &str, scroll: usize, stopped: bool) -> Vec<String> {
+    // the newest output at the bottom, followed by the command being typed
+    let end = output.len() - scroll.min(output.len());
+    let start = end.saturating_sub(PANEL_ROWS - 1);
+    let mut lines = output[start..end].to_vec();
+    let prompt = if stopped { ">" } else { "(running)" };
+    lines.push(format!("{} {}_", prompt, command));
+
+    lines
+}
+
+pub fn draw_text(font: &memory::ROM, buffer: &mut [u32], width: usize, scale: usize, lines: &[String]) {
+    // uses the firmware font, scaled up by whole pixels
+    for pixel in buffer.iter_mut() {
+        *pixel = 0x000080; // firmware blue
+    }
+
+    let glyph_size = 8 * scale;
+    let rows = buffer.len() / width / glyph_size;
+    for (row, line) in lines.iter().take(rows).enumerate() {
+        for (column, character) in line.chars().take(width / glyph_size).enumerate() {
+            let code = if character.is_ascii() { character as usize } else { '?' as usize };
+            for y in 0..glyph_size {
+                let bits = font.read_byte(FONT_ADDRESS + code * 8 + y / scale);
+                for x in 0..glyph_size {
+                    if bits & (0x80 >> (x / scale)) != 0 {
+                        buffer[(row * glyph_size + y) * width + column * glyph_size + x] = 0xffff00; // bright yellow
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn invalid_data(message: String) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message)
+}
@@ -0,0 +1,302 @@
+// checks that LDIR, CPIR, INIR, OTIR and their relatives repeat by executing again, one iteration at
+// a time: with the flags of each iteration, 6 NOPs for all but the last, port accesses 24 T-states
+// apart, and interrupts that return to the instruction to finish it
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use ronald::bus::Bus;
+use ronald::cpu::{self, Register16::*};
+use ronald::memory::{self, Read, Write};
+
+const PROGRAM_ADDRESS: u16 = 0x100;
+const STACK_ADDRESS: u16 = 0x8000;
+const INTERRUPT_HANDLER: [u8; 2] = [0xfb, 0xc9]; // ei; ret
+const MAX_STEPS: usize = 64;
+
+// answers every port read with the high byte of the port, i.e. with B for the block instructions
+struct EchoBus {}
+
+impl Bus for EchoBus {
+    fn read_byte(&self, port: u16) -> u8 {
+        (port >> 8) as u8
+    }
+
+    fn write_byte(&mut self, _port: u16, _value: u8) {}
+}
+
+#[derive(Default)]
+struct PortRecorder {
+    accesses: Vec<(u64, u16, u8)>,
+}
+
+impl cpu::IoObserver for PortRecorder {
+    fn notify_port_read(&mut self, t_state: u64, port: u16, value: u8) {
+        self.accesses.push((t_state, port, value));
+    }
+
+    fn notify_port_write(&mut self, t_state: u64, port: u16, value: u8) {
+        self.accesses.push((t_state, port, value));
+    }
+}
+
+// a repeating block instruction, what it starts with and what it leaves, with the flags after its
+// first and last iteration
+struct Case {
+    name: &'static str,
+    opcode: u8, // after the ED prefix
+    registers: &'static [(cpu::Register16, u16)],
+    memory: &'static [(u16, &'static [u8])],
+    iterations: usize,
+    flags_mask: u8,
+    flags_first: u8,
+    flags_last: u8,
+    registers_after: &'static [(cpu::Register16, u16)],
+    memory_after: &'static [(u16, &'static [u8])],
+    ports: &'static [(u16, u8)],
+    interrupted_after: Option<usize>, // the iteration after which an interrupt is accepted
+}
+
+const CASES: [Case; 9] = [
+    Case {
+        name: "ldir",
+        opcode: 0xb0,
+        registers: &[(BC, 3), (DE, 0x5000), (HL, 0x4000)],
+        memory: &[(0x4000, &[1, 2, 3])],
+        iterations: 3,
+        flags_mask: 0x16,
+        flags_first: 0x04,
+        flags_last: 0x00,
+        registers_after: &[(BC, 0), (DE, 0x5003), (HL, 0x4003)],
+        memory_after: &[(0x5000, &[1, 2, 3])],
+        ports: &[],
+        interrupted_after: None,
+    },
+    Case {
+        name: "lddr",
+        opcode: 0xb8,
+        registers: &[(BC, 3), (DE, 0x5002), (HL, 0x4002)],
+        memory: &[(0x4000, &[1, 2, 3])],
+        iterations: 3,
+        flags_mask: 0x16,
+        flags_first: 0x04,
+        flags_last: 0x00,
+        registers_after: &[(BC, 0), (DE, 0x4fff), (HL, 0x3fff)],
+        memory_after: &[(0x5000, &[1, 2, 3])],
+        ports: &[],
+        interrupted_after: None,
+    },
+    Case {
+        // stops at the match, with BC still counting
+        name: "cpir",
+        opcode: 0xb1,
+        registers: &[(AF, 0x0300), (BC, 5), (HL, 0x4000)],
+        memory: &[(0x4000, &[1, 2, 3, 4, 5])],
+        iterations: 3,
+        flags_mask: 0x46,
+        flags_first: 0x06,
+        flags_last: 0x46,
+        registers_after: &[(BC, 2), (HL, 0x4003)],
+        memory_after: &[],
+        ports: &[],
+        interrupted_after: None,
+    },
+    Case {
+        // the match and the end of the count come together
+        name: "cpdr",
+        opcode: 0xb9,
+        registers: &[(AF, 0x0100), (BC, 5), (HL, 0x4004)],
+        memory: &[(0x4000, &[1, 2, 3, 4, 5])],
+        iterations: 5,
+        flags_mask: 0x46,
+        flags_first: 0x06,
+        flags_last: 0x42,
+        registers_after: &[(BC, 0), (HL, 0x3fff)],
+        memory_after: &[],
+        ports: &[],
+        interrupted_after: None,
+    },
+    Case {
+        // B is on the bus before it counts down, C + 1 overflows into the carries
+        name: "inir",
+        opcode: 0xb2,
+        registers: &[(BC, 0x03fe), (HL, 0x4000)],
+        memory: &[],
+        iterations: 3,
+        flags_mask: 0x53,
+        flags_first: 0x11,
+        flags_last: 0x51,
+        registers_after: &[(BC, 0x00fe), (HL, 0x4003)],
+        memory_after: &[(0x4000, &[3, 2, 1])],
+        ports: &[(0x03fe, 3), (0x02fe, 2), (0x01fe, 1)],
+        interrupted_after: None,
+    },
+    Case {
+        name: "indr",
+        opcode: 0xba,
+        registers: &[(BC, 0x0210), (HL, 0x4001)],
+        memory: &[],
+        iterations: 2,
+        flags_mask: 0x53,
+        flags_first: 0x00,
+        flags_last: 0x40,
+        registers_after: &[(BC, 0x0010), (HL, 0x3fff)],
+        memory_after: &[(0x4000, &[1, 2])],
+        ports: &[(0x0210, 2), (0x0110, 1)],
+        interrupted_after: None,
+    },
+    Case {
+        // B counts down before it is on the bus, the sign of the byte sets N
+        name: "otir",
+        opcode: 0xb3,
+        registers: &[(BC, 0x03a0), (HL, 0x4000)],
+        memory: &[(0x4000, &[0x81, 2, 3])],
+        iterations: 3,
+        flags_mask: 0x53,
+        flags_first: 0x02,
+        flags_last: 0x40,
+        registers_after: &[(BC, 0x00a0), (HL, 0x4003)],
+        memory_after: &[],
+        ports: &[(0x02a0, 0x81), (0x01a0, 2), (0x00a0, 3)],
+        interrupted_after: None,
+    },
+    Case {
+        // the byte and L after the count overflow into the carries
+        name: "otdr",
+        opcode: 0xbb,
+        registers: &[(BC, 0x02a0), (HL, 0x40ff)],
+        memory: &[(0x40fe, &[0x10, 0xf0])],
+        iterations: 2,
+        flags_mask: 0x53,
+        flags_first: 0x13,
+        flags_last: 0x51,
+        registers_after: &[(BC, 0x00a0), (HL, 0x40fd)],
+        memory_after: &[],
+        ports: &[(0x01a0, 0xf0), (0x00a0, 0x10)],
+        interrupted_after: None,
+    },
+    Case {
+        // the interrupt returns to the LDIR, which carries on where it stopped
+        name: "ldir, interrupted",
+        opcode: 0xb0,
+        registers: &[(BC, 4), (DE, 0x5000), (HL, 0x4000)],
+        memory: &[(0x4000, &[1, 2, 3, 4])],
+        iterations: 4,
+        flags_mask: 0x16,
+        flags_first: 0x04,
+        flags_last: 0x00,
+        registers_after: &[(BC, 0), (DE, 0x5004), (HL, 0x4004), (SP, 0x8000)],
+        memory_after: &[(0x5000, &[1, 2, 3, 4])],
+        ports: &[],
+        interrupted_after: Some(3),
+    },
+];
+
+fn run_case(case: &Case) -> Vec<String> {
+    let mut memory = memory::RAM::new(0x10000);
+    for (offset, value) in [0xed, case.opcode, 0x00].iter().enumerate() {
+        memory.write_byte(PROGRAM_ADDRESS as usize + offset, *value);
+    }
+    for (offset, value) in INTERRUPT_HANDLER.iter().enumerate() {
+        memory.write_byte(0x38 + offset, *value);
+    }
+    for (address, bytes) in case.memory.iter() {
+        for (offset, value) in bytes.iter().enumerate() {
+            memory.write_byte(*address as usize + offset, *value);
+        }
+    }
+    let memory = Rc::new(RefCell::new(memory));
+    let bus = Rc::new(RefCell::new(EchoBus {}));
+    let cpu = cpu::CPU::new_shared(memory.clone(), bus, PROGRAM_ADDRESS);
+    let recorder = Rc::new(RefCell::new(PortRecorder::default()));
+
+    let mut cpu = cpu.borrow_mut();
+    cpu.add_io_observer(recorder.clone());
+    cpu.registers.write_word(&SP, STACK_ADDRESS);
+    for (register, value) in case.registers.iter() {
+        cpu.registers.write_word(register, *value);
+    }
+    cpu.write_interrupt_state(true, true, 1);
+
+    // the instruction stays at its address until the last iteration, the interrupt handler runs in between
+    let mut errors = Vec::new();
+    let mut timings = Vec::new();
+    let mut flags = Vec::new();
+    for _ in 0..MAX_STEPS {
+        let address = cpu.registers.read_word(&PC);
+        if address == PROGRAM_ADDRESS + 2 {
+            break;
+        }
+        if address == PROGRAM_ADDRESS && case.interrupted_after == Some(timings.len() + 1) {
+            cpu.request_interrupt();
+        }
+
+        let (timing, interrupt_acknowledged) = cpu.fetch_and_execute();
+        if address != PROGRAM_ADDRESS {
+            continue;
+        }
+        flags.push(cpu.registers.read_byte(&cpu::Register8::F) & case.flags_mask);
+        if interrupt_acknowledged {
+            let stack_pointer = cpu.registers.read_word(&SP);
+            let return_address = memory.borrow().read_word(stack_pointer as usize);
+            if return_address != PROGRAM_ADDRESS {
+                errors.push(format!("interrupt returns to {:#06x}", return_address));
+            }
+            timings.push(timing - 4); // without the 4 NOPs of the RST 38h
+        } else {
+            timings.push(timing);
+        }
+    }
+
+    let mut expected_timings = vec![6; case.iterations - 1];
+    expected_timings.push(5);
+    if timings != expected_timings {
+        errors.push(format!("took {:?} NOPs instead of {:?}", timings, expected_timings));
+    }
+    if flags.first() != Some(&case.flags_first) || flags.last() != Some(&case.flags_last) {
+        errors.push(format!("flags {:x?} instead of {:#04x} at first and {:#04x} at last", flags, case.flags_first, case.flags_last));
+    }
+    for (register, value) in case.registers_after.iter() {
+        let actual = cpu.registers.read_word(register);
+        if actual != *value {
+            errors.push(format!("{:?} = {:#06x} instead of {:#06x}", register, actual, value));
+        }
+    }
+    for (address, bytes) in case.memory_after.iter() {
+        for (offset, value) in bytes.iter().enumerate() {
+            let actual = memory.borrow().read_byte(*address as usize + offset);
+            if actual != *value {
+                errors.push(format!("({:#06x}) = {:#04x} instead of {:#04x}", *address as usize + offset, actual, value));
+            }
+        }
+    }
+
+    let accesses = &recorder.borrow().accesses;
+    let ports: Vec<(u16, u8)> = accesses.iter().map(|(_, port, value)| (*port, *value)).collect();
+    if ports != case.ports {
+        errors.push(format!("accessed ports {:x?} instead of {:x?}", ports, case.ports));
+    }
+    if accesses.windows(2).any(|pair| pair[1].0 - pair[0].0 != 24) {
+        let t_states: Vec<u64> = accesses.iter().map(|(t_state, _, _)| *t_state).collect();
+        errors.push(format!("accessed ports at T-states {:?} instead of 24 apart", t_states));
+    }
+
+    errors
+}
+
+#[test]
+fn block_instructions_repeat_one_iteration_at_a_time() {
+    let failures: Vec<String> = CASES
+        .iter()
+        .filter_map(|case| {
+            let errors = run_case(case);
+            if errors.is_empty() {
+                None
+            } else {
+                Some(format!("{}: {}", case.name, errors.join(", ")))
+            }
+        })
+        .collect();
+
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
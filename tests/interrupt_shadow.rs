@@ -0,0 +1,140 @@
+// checks that EI holds back a pending interrupt for one more instruction, around critical sections,
+// HALT, prefixes and block instructions, which interrupts may stop between iterations
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use ronald::bus;
+use ronald::cpu::{self, Register16::*};
+use ronald::memory::{self, Read, Write};
+
+const PROGRAM_ADDRESS: u16 = 0x100;
+const STACK_ADDRESS: u16 = 0x8000;
+const MAX_INSTRUCTIONS: usize = 16;
+
+// an interrupt that arrives while a program enables interrupts, and the instruction after which
+// the CPU has to accept it, counted from the first one, None if it must not be accepted at all
+struct Case {
+    name: &'static str,
+    program: &'static [u8],
+    registers: &'static [(cpu::Register16, u16)],
+    accepted_after: Option<usize>,
+    registers_after: &'static [(cpu::Register16, u16)], // when the interrupt is accepted
+    return_address: u16,
+}
+
+const CASES: [Case; 7] = [
+    Case {
+        name: "ei; nop",
+        program: &[0xfb, 0x00, 0x00],
+        registers: &[],
+        accepted_after: Some(2),
+        registers_after: &[],
+        return_address: 0x102,
+    },
+    Case {
+        name: "ei; ei; ei; nop",
+        program: &[0xfb, 0xfb, 0xfb, 0x00, 0x00],
+        registers: &[],
+        accepted_after: Some(4),
+        registers_after: &[],
+        return_address: 0x104,
+    },
+    Case {
+        name: "ei; di",
+        program: &[0xfb, 0xf3, 0x00, 0x00, 0x00, 0x00],
+        registers: &[],
+        accepted_after: None,
+        registers_after: &[],
+        return_address: 0,
+    },
+    Case {
+        name: "ei; halt",
+        program: &[0xfb, 0x76, 0x00],
+        registers: &[],
+        accepted_after: Some(2),
+        registers_after: &[],
+        return_address: 0x102,
+    },
+    Case {
+        name: "ei; dd; ld ix,nn",
+        program: &[0xfb, 0xdd, 0xdd, 0x21, 0x34, 0x12, 0x00],
+        registers: &[],
+        accepted_after: Some(3),
+        registers_after: &[],
+        return_address: 0x106,
+    },
+    Case {
+        // the interrupt stops LDIR after the first byte, returning to repeat it
+        name: "ei; ldir",
+        program: &[0xfb, 0xed, 0xb0, 0x00],
+        registers: &[(BC, 0x0010), (DE, 0x5000), (HL, 0x4000)],
+        accepted_after: Some(2),
+        registers_after: &[(BC, 0x000f), (HL, 0x4001)],
+        return_address: 0x101,
+    },
+    Case {
+        // a critical section in a subroutine, which EI; RET leaves before the interrupt comes
+        name: "call; di; nop; ei; ret",
+        program: &[0xcd, 0x10, 0x01, 0x00, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xf3, 0x00, 0xfb, 0xc9],
+        registers: &[],
+        accepted_after: Some(5),
+        registers_after: &[],
+        return_address: 0x103,
+    },
+];
+
+fn run_case(case: &Case) -> (Option<usize>, u16, bool) {
+    // the interrupt is pending from the start, like one the gate array raised during a DI
+    let mut memory = memory::RAM::new(0x10000);
+    for (offset, value) in case.program.iter().enumerate() {
+        memory.write_byte(PROGRAM_ADDRESS as usize + offset, *value);
+    }
+    let memory = Rc::new(RefCell::new(memory));
+    let bus = Rc::new(RefCell::new(bus::DummyBus::new()));
+    let cpu = cpu::CPU::new_shared(memory.clone(), bus, PROGRAM_ADDRESS);
+
+    let mut cpu = cpu.borrow_mut();
+    cpu.registers.write_word(&SP, STACK_ADDRESS);
+    for (register, value) in case.registers.iter() {
+        cpu.registers.write_word(register, *value);
+    }
+    cpu.write_interrupt_state(false, false, 1);
+    cpu.request_interrupt();
+
+    for count in 1..=MAX_INSTRUCTIONS {
+        let (_, interrupt_acknowledged) = cpu.fetch_and_execute();
+        if interrupt_acknowledged {
+            let stack_pointer = cpu.registers.read_word(&SP);
+            let return_address = memory.borrow().read_word(stack_pointer as usize);
+            let registers_match = case.registers_after.iter().all(|(register, value)| cpu.registers.read_word(register) == *value);
+            return (Some(count), return_address, registers_match);
+        }
+    }
+
+    (None, 0, true)
+}
+
+#[test]
+fn ei_delays_interrupts_by_one_instruction() {
+    let describe = |accepted_after: Option<usize>, return_address: u16| match accepted_after {
+        Some(count) => format!("after instruction {}, returning to {:#06x}", count, return_address),
+        None => "never".to_string(),
+    };
+
+    let mut failures = Vec::new();
+    for case in CASES.iter() {
+        let (accepted_after, return_address, registers_match) = run_case(case);
+        if accepted_after != case.accepted_after || (accepted_after.is_some() && return_address != case.return_address) || !registers_match {
+            failures.push(format!(
+                "{}: accepted {} instead of {}{}",
+                case.name,
+                describe(accepted_after, return_address),
+                describe(case.accepted_after, case.return_address),
+                if registers_match { "" } else { ", with other register values" }
+            ));
+        }
+    }
+
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
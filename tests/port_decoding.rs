@@ -0,0 +1,181 @@
+// checks that the devices decode the port by their address lines rather than by the exact port,
+// with the mirrors programs use, e.g. OUT (C),C with the value in the low byte of the port
+
+use ronald::bus::Device::{self, Crtc, Fdc, GateArray, PeripheralReset, Ppi, Printer, RomSelect, Unknown};
+use ronald::cpu::{Register16, Register8};
+use ronald::system::{self, System};
+
+const OUT_ADDRESS: u16 = 0x4000; // out (c),a
+const IN_ADDRESS: u16 = 0x4002; // in a,(c)
+
+// a port access as real software makes it, and the devices whose address lines it pulls low
+struct Case {
+    name: &'static str,
+    port: u16,
+    write: Option<u8>, // None for reads
+    devices: &'static [Device],
+    read_value: Option<u8>, // for reads with a known result
+}
+
+const CASES: [Case; 16] = [
+    Case {
+        name: "out (c),0 with bc=&0000",
+        port: 0x0000,
+        write: Some(0x00),
+        devices: &[Crtc, RomSelect, Printer, Ppi, Fdc],
+        read_value: None,
+    },
+    Case {
+        name: "ld bc,&7f10: out (c),c",
+        port: 0x7f10,
+        write: Some(0x10),
+        devices: &[GateArray],
+        read_value: None,
+    },
+    Case {
+        name: "ld b,&7f: out (c),a",
+        port: 0x7f00,
+        write: Some(0x54),
+        devices: &[GateArray],
+        read_value: None,
+    },
+    Case {
+        name: "ld bc,&7f8d: out (c),c",
+        port: 0x7f8d,
+        write: Some(0x8d),
+        devices: &[GateArray],
+        read_value: None,
+    },
+    Case {
+        name: "ld bc,&bc0c: out (c),c",
+        port: 0xbc0c,
+        write: Some(0x0c),
+        devices: &[Crtc],
+        read_value: None,
+    },
+    Case {
+        name: "ld bc,&beff: outi",
+        port: 0xbdff,
+        write: Some(0x30),
+        devices: &[Crtc],
+        read_value: None,
+    },
+    Case {
+        name: "ld b,&bf: in a,(c)",
+        port: 0xbf0c,
+        write: None,
+        devices: &[Crtc],
+        read_value: None,
+    },
+    Case {
+        name: "ld bc,&df07: out (c),c",
+        port: 0xdf07,
+        write: Some(0x07),
+        devices: &[RomSelect],
+        read_value: None,
+    },
+    Case {
+        name: "ld b,&ef: out (c),a",
+        port: 0xef41,
+        write: Some(0x41),
+        devices: &[Printer],
+        read_value: None,
+    },
+    Case {
+        name: "ld b,&f5: in a,(c)",
+        port: 0xf5ff,
+        write: None,
+        devices: &[Ppi],
+        read_value: None,
+    },
+    Case {
+        name: "ld bc,&f782: out (c),c",
+        port: 0xf782,
+        write: Some(0x82),
+        devices: &[Ppi],
+        read_value: None,
+    },
+    Case {
+        name: "ld bc,&fa7e: out (c),c",
+        port: 0xfa7e,
+        write: Some(0x7e),
+        devices: &[Fdc],
+        read_value: None,
+    },
+    Case {
+        name: "ld bc,&fb7e: in a,(c)",
+        port: 0xfb7e,
+        write: None,
+        devices: &[Fdc],
+        read_value: Some(0x80), // ready for a command
+    },
+    Case {
+        name: "ld bc,&f8ff: out (c),c",
+        port: 0xf8ff,
+        write: Some(0xff),
+        devices: &[PeripheralReset],
+        read_value: None,
+    },
+    Case {
+        name: "ld bc,&ffff: in a,(c)",
+        port: 0xffff,
+        write: None,
+        devices: &[Unknown],
+        read_value: Some(0xff),
+    },
+    Case {
+        // reads do not reach the write-only gate array
+        name: "ld b,&7f: in a,(c)",
+        port: 0x7fff,
+        write: None,
+        devices: &[Unknown],
+        read_value: Some(0xff),
+    },
+];
+
+#[test]
+fn devices_decode_ports_by_their_address_lines() {
+    // the CPU executes the accesses, interrupts stay disabled after the reset
+    let mut cpc = system::CPC464::new(system::SystemConfig::default());
+    for (offset, byte) in [0xed, 0x79, 0xed, 0x78].iter().enumerate() {
+        cpc.write_ram(OUT_ADDRESS as usize + offset, *byte);
+    }
+    cpc.enable_io_trace(true);
+
+    let names = |devices: &[Device]| devices.iter().map(|device| device.to_string()).collect::<Vec<String>>().join(", ");
+    let mut failures = Vec::new();
+    for case in CASES.iter() {
+        cpc.write_register(Register16::BC, case.port);
+        cpc.write_register(Register16::AF, (case.write.unwrap_or(0) as u16) << 8);
+        cpc.write_register(Register16::PC, if case.write.is_some() { OUT_ADDRESS } else { IN_ADDRESS });
+        cpc.emulate();
+        let devices: Vec<Device> = cpc.take_io_trace().iter().map(|access| access.device).collect();
+
+        if devices != case.devices {
+            failures.push(format!("{}: reached {} instead of {}", case.name, names(&devices), names(case.devices)));
+        }
+        let value = cpc.read_register8(Register8::A);
+        match case.read_value {
+            Some(expected) if value != expected => failures.push(format!("{}: read {:#04x} instead of {:#04x}", case.name, value, expected)),
+            _ => (),
+        }
+    }
+    cpc.enable_io_trace(false);
+
+    // what the writes through the mirrors left in the devices
+    let snapshot = cpc.save_snapshot();
+    let checks = [
+        ("border color", snapshot.pen_colors[16], 0x14),
+        ("multi configuration", snapshot.multi_configuration, 0x8d),
+        ("CRTC R12", snapshot.crtc_registers[12], 0x30),
+        ("upper ROM", snapshot.upper_rom, 0x07),
+        ("PPI control", snapshot.ppi_ports[3], 0x82),
+    ];
+    for (name, value, expected) in checks.iter() {
+        if value != expected {
+            failures.push(format!("{}: {:#04x} instead of {:#04x}", name, value, expected));
+        }
+    }
+
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
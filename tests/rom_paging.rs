@@ -0,0 +1,102 @@
+// pages the ROMs in and out through the firmware's jumpblock while interrupts keep arriving, and
+// checks after every call that both 16K slots show what the firmware selected. The interrupt handler
+// has to enter with any combination of ROMs and leave it as it was
+
+use ronald::cpu;
+use ronald::memory;
+use ronald::system::{self, System};
+
+const PROGRAM_ADDRESS: u16 = 0x4000;
+const LOOP_ADDRESS: u16 = 0x4001;
+const ERROR_ADDRESS: u16 = 0x4080;
+const LOWER_TEST_ADDRESS: u16 = 0x3fff; // bytes that differ between ROM and RAM
+const UPPER_TEST_ADDRESS: u16 = 0xc7ff; // not displayed, so the screen hides the marker
+const INTERRUPT_ADDRESS: u16 = 0x0038;
+const KL_U_ROM_ENABLE: u16 = 0xb900;
+const KL_U_ROM_DISABLE: u16 = 0xb903;
+const KL_L_ROM_ENABLE: u16 = 0xb906;
+const KL_L_ROM_DISABLE: u16 = 0xb909;
+const FRAMES: u64 = 50;
+
+fn program(lower_rom: u8, upper_rom: u8) -> Vec<u8> {
+    // after each step: ld a,(address); cp expected; call nz,error for both test addresses
+    let steps: [(&[u16], bool, bool); 4] = [
+        (&[KL_U_ROM_ENABLE, KL_L_ROM_ENABLE], true, true),
+        (&[KL_L_ROM_DISABLE], false, true),
+        (&[KL_U_ROM_DISABLE], false, false),
+        (&[KL_L_ROM_ENABLE], true, false),
+    ];
+
+    let mut program = vec![0xfb]; // ei
+    for (calls, lower_enabled, upper_enabled) in steps.iter() {
+        for address in calls.iter() {
+            program.push(0xcd); // call
+            program.extend_from_slice(&address.to_le_bytes());
+        }
+        let checks = [
+            (LOWER_TEST_ADDRESS, if *lower_enabled { lower_rom } else { !lower_rom }),
+            (UPPER_TEST_ADDRESS, if *upper_enabled { upper_rom } else { !upper_rom }),
+        ];
+        for (address, expected) in checks.iter() {
+            program.push(0x3a); // ld a,(nn)
+            program.extend_from_slice(&address.to_le_bytes());
+            program.extend_from_slice(&[0xfe, *expected]); // cp n
+            program.push(0xc4); // call nz,nn
+            program.extend_from_slice(&ERROR_ADDRESS.to_le_bytes());
+        }
+    }
+    program.push(0xc3); // jp nn
+    program.extend_from_slice(&LOOP_ADDRESS.to_le_bytes());
+
+    program
+}
+
+#[test]
+fn firmware_pages_roms_while_interrupts_occur() {
+    let mut cpc = system::CPC464::new(system::SystemConfig::default());
+    let mut microseconds = 0;
+    while microseconds < 1_000_000 {
+        microseconds += cpc.emulate() as u64; // let the firmware set up its jumpblock
+    }
+
+    // the firmware's ROMs, BASIC is the selected upper ROM once it runs
+    let (os_path, basic_path, _) = memory::RomPaths::default().resolve(cpc.get_config().language);
+    let lower_rom = std::fs::read(os_path).unwrap()[LOWER_TEST_ADDRESS as usize];
+    let upper_rom = std::fs::read(basic_path).unwrap()[(UPPER_TEST_ADDRESS - 0xc000) as usize];
+    cpc.write_ram(LOWER_TEST_ADDRESS as usize, !lower_rom);
+    cpc.write_ram(UPPER_TEST_ADDRESS as usize, !upper_rom);
+
+    for (offset, byte) in program(lower_rom, upper_rom).iter().enumerate() {
+        cpc.write_ram(PROGRAM_ADDRESS as usize + offset, *byte);
+    }
+    cpc.write_ram(ERROR_ADDRESS as usize, 0xc9); // ret
+    cpc.write_register(cpu::Register16::PC, PROGRAM_ADDRESS);
+
+    let mut iterations = 0;
+    let mut failed_checks = Vec::new();
+    let mut interrupts = [0; 4]; // by lower and upper ROM enabled
+    let end = microseconds + FRAMES * 20_000;
+    while microseconds < end {
+        let pc = cpc.get_program_counter();
+        if pc == LOOP_ADDRESS {
+            iterations += 1;
+        } else if pc == ERROR_ADDRESS {
+            let sp = cpc.read_register(cpu::Register16::SP);
+            let return_address = u16::from_le_bytes([cpc.read_memory(sp), cpc.read_memory(sp.wrapping_add(1))]);
+            failed_checks.push(return_address.wrapping_sub(3)); // of the call nz
+        } else if pc == INTERRUPT_ADDRESS {
+            let banks = cpc.get_debug_view().memory_banks;
+            let lower_enabled = banks[0] == memory::Bank::LowerRom;
+            let upper_enabled = matches!(banks[3], memory::Bank::UpperRom(_));
+            interrupts[(lower_enabled as usize) << 1 | upper_enabled as usize] += 1;
+        }
+        microseconds += cpc.emulate() as u64;
+    }
+
+    failed_checks.sort_unstable();
+    failed_checks.dedup();
+    assert!(iterations > 0);
+    assert!(failed_checks.is_empty(), "checks at {:04x?} failed", failed_checks);
+    // by lower and upper ROM enabled: off/off, off/on, on/off, on/on
+    assert!(interrupts.iter().all(|count| *count > 0), "interrupts with each ROM combination: {:?}", interrupts);
+}
@@ -0,0 +1,63 @@
+// compares the screens of the test programs in screentests with reference images per CRTC type, e.g.
+// bars.crtc0.png for bars.sna. Types without a reference image are skipped. Running with
+// UPDATE_SCREENTESTS=1 writes the references for all types instead
+
+use ronald::bus;
+use ronald::crtc;
+use ronald::memory;
+use ronald::sna_file;
+use ronald::system::{self, System};
+
+const DIRECTORY: &str = "screentests";
+const FRAMES: u64 = 50;
+const CRTC_TYPES: [crtc::CrtcType; 4] = [crtc::CrtcType::Type0, crtc::CrtcType::Type1, crtc::CrtcType::Type2, crtc::CrtcType::Type3];
+
+#[test]
+fn screens_match_the_reference_images() {
+    let update = std::env::var_os("UPDATE_SCREENTESTS").is_some();
+    let mut snapshots: Vec<_> = std::fs::read_dir(DIRECTORY)
+        .unwrap()
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().map(|extension| extension == "sna").unwrap_or(false))
+        .collect();
+    snapshots.sort();
+    assert!(!snapshots.is_empty());
+
+    let mut failures = Vec::new();
+    for path in snapshots {
+        let name = path.file_stem().unwrap().to_string_lossy().to_string();
+        let snapshot = sna_file::Snapshot::load(&path.to_string_lossy()).unwrap();
+        for crtc_type in CRTC_TYPES.iter() {
+            let reference = path.with_file_name(format!("{}.crtc{}.png", name, crtc_type.number()));
+            if !update && !reference.exists() {
+                continue;
+            }
+            let reference = reference.to_string_lossy();
+
+            // the screens have to be exactly reproducible
+            let config = system::SystemConfig {
+                deterministic: true,
+                seed: 0,
+                ram_pattern: memory::RamPattern::Zeros,
+                crtc_type: *crtc_type,
+                ..Default::default()
+            };
+            let mut cpc = system::CPC464::new(config);
+            cpc.load_snapshot(&snapshot);
+            cpc.run_to_sync(bus::SyncSignal::Vertical, FRAMES);
+
+            let screen = cpc.get_screen();
+            let screen = screen.borrow();
+            if update {
+                screen.write_png(&reference).unwrap();
+                continue;
+            }
+            match screen.compare_png(&reference).unwrap() {
+                0 => (),
+                differences => failures.push(format!("{}: {} pixels differ", reference, differences)),
+            }
+        }
+    }
+
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
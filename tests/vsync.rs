@@ -0,0 +1,88 @@
+// runs a VSYNC polling loop on PPI port B, like games and demos use for timing, and compares what it
+// measures with the timing programmed into the CRTC
+
+use ronald::cpu;
+use ronald::crtc;
+use ronald::system::{self, System};
+
+const PROGRAM_ADDRESS: u16 = 0x4000;
+const VSYNC_STARTED_ADDRESS: u16 = 0x400e;
+const VSYNC_ENDED_ADDRESS: u16 = 0x4009;
+const PROGRAM: [u8; 16] = [
+    0xf3, // di
+    0x01, 0x00, 0xf5, // ld bc,0xf500
+    0xed, 0x78, // wait until VSYNC ends: in a,(c)
+    0x1f, // rra
+    0x38, 0xfb, // jr c,0x4004
+    0xed, 0x78, // wait until VSYNC starts: in a,(c)
+    0x1f, // rra
+    0x30, 0xfb, // jr nc,0x4009
+    0x18, 0xf4, // jr 0x4004
+];
+const POLLING_MICROSECONDS: u64 = 8; // how late the loop may notice a change
+const FRAMES: usize = 50;
+
+fn measure(crtc_type: crtc::CrtcType) {
+    let mut cpc = system::CPC464::new(system::SystemConfig { crtc_type, ..Default::default() });
+    let mut microseconds = 0;
+    while microseconds < 1_000_000 {
+        microseconds += cpc.emulate() as u64; // let the firmware set up the CRTC
+    }
+
+    for (offset, byte) in PROGRAM.iter().enumerate() {
+        cpc.write_ram(PROGRAM_ADDRESS as usize + offset, *byte);
+    }
+    cpc.write_register(cpu::Register16::PC, PROGRAM_ADDRESS);
+
+    // types 1 and 2 ignore the programmed VSYNC width and always use 16 scan lines
+    let registers = cpc.save_snapshot().crtc_registers;
+    let line = registers[0] as u64 + 1;
+    let expected_period = line * ((registers[4] as u64 + 1) * (registers[9] as u64 + 1) + registers[5] as u64);
+    let width = match crtc_type {
+        crtc::CrtcType::Type1 | crtc::CrtcType::Type2 => 0,
+        _ => registers[3] >> 4,
+    };
+    let expected_width = line * if width == 0 { 16 } else { width as u64 };
+
+    let mut starts = Vec::new();
+    let mut widths = Vec::new();
+    let mut in_vsync = false;
+    while starts.len() <= FRAMES {
+        let pc = cpc.get_program_counter();
+        if pc == VSYNC_STARTED_ADDRESS && !in_vsync {
+            starts.push(microseconds);
+            in_vsync = true;
+        } else if pc == VSYNC_ENDED_ADDRESS && in_vsync {
+            widths.push(microseconds - starts[starts.len() - 1]);
+            in_vsync = false;
+        }
+        microseconds += cpc.emulate() as u64;
+    }
+
+    let periods: Vec<u64> = starts.windows(2).map(|pair| pair[1] - pair[0]).collect();
+    let within = |measurements: &[u64], expected: u64| {
+        measurements.iter().all(|measurement| measurement.abs_diff(expected) <= POLLING_MICROSECONDS)
+    };
+    assert!(within(&periods, expected_period), "type {}: VSYNC periods {:?} instead of {}µs", crtc_type.number(), periods, expected_period);
+    assert!(within(&widths, expected_width), "type {}: VSYNC widths {:?} instead of {}µs", crtc_type.number(), widths, expected_width);
+}
+
+#[test]
+fn vsync_timing_matches_the_crtc_type_0() {
+    measure(crtc::CrtcType::Type0);
+}
+
+#[test]
+fn vsync_timing_matches_the_crtc_type_1() {
+    measure(crtc::CrtcType::Type1);
+}
+
+#[test]
+fn vsync_timing_matches_the_crtc_type_2() {
+    measure(crtc::CrtcType::Type2);
+}
+
+#[test]
+fn vsync_timing_matches_the_crtc_type_3() {
+    measure(crtc::CrtcType::Type3);
+}
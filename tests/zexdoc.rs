@@ -0,0 +1,54 @@
+// runs the instruction groups of the zexdoc exerciser that finish in about a second, the loads and
+// block transfers. The zexdoc and zexall subcommands run all of them
+
+use ronald::system::{ZexHarness, ZexResult};
+
+const ROM_PATH: &str = "rom/zexdoc.rom";
+const TIMEOUT: std::time::Duration = std::time::Duration::from_secs(60);
+
+const FAST_GROUPS: [&str; 17] = [
+    "ld <bc,de>,(nnnn)",
+    "ld hl,(nnnn)",
+    "ld sp,(nnnn)",
+    "ld <ix,iy>,(nnnn)",
+    "ld (nnnn),<bc,de>",
+    "ld (nnnn),hl",
+    "ld (nnnn),sp",
+    "ld (nnnn),<ix,iy>",
+    "ld <bc,de,hl,sp>,nnnn",
+    "ld <ix,iy>,nnnn",
+    "ld a,<(bc),(de)>",
+    "ld a,(nnnn) / ld (nnnn),a",
+    "ldd<r> (1)",
+    "ldd<r> (2)",
+    "ldi<r> (1)",
+    "ldi<r> (2)",
+    "ld (<bc,de>),a",
+];
+
+#[test]
+fn lists_the_instruction_groups() {
+    let names = ZexHarness::new(ROM_PATH).list_tests();
+
+    assert_eq!(names.len(), 67);
+    assert_eq!(names[0], "<adc,sbc> hl,<bc,de,hl,sp>");
+    assert_eq!(names[66], "ld (<bc,de>),a");
+}
+
+#[test]
+fn passes_the_fast_instruction_groups() {
+    let names = ZexHarness::new(ROM_PATH).list_tests();
+    let mut failures = Vec::new();
+    for group in FAST_GROUPS.iter() {
+        let index = names.iter().position(|name| name == group).unwrap_or_else(|| panic!("no group \"{}\"", group));
+        let mut zex_harness = ZexHarness::new(ROM_PATH);
+        zex_harness.select_test(index);
+        match zex_harness.run_test(TIMEOUT) {
+            ZexResult::Passed => (),
+            ZexResult::Failed(message) => failures.push(format!("{}: {}", group, message)),
+            ZexResult::TimedOut => failures.push(format!("{}: timed out", group)),
+        }
+    }
+
+    assert!(failures.is_empty(), "failed groups:\n{}", failures.join("\n"));
+}